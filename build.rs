@@ -0,0 +1,10 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Proto3 optional fields need a newer protoc than distributions usually ship.
+    std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+
+    tonic_build::configure()
+        .build_client(false)
+        .compile(&["proto/conference/v1/internal.proto"], &["proto"])?;
+
+    Ok(())
+}
@@ -0,0 +1,16 @@
+create table if not exists backend_error (
+    id uuid not null default gen_random_uuid(),
+    backend_id agent_id,
+    session_id bigint not null,
+    kind text not null,
+    payload jsonb not null,
+    room_id uuid,
+    rtc_stream_id uuid,
+    trace_id text,
+    correlation_id text,
+    created_at timestamp with time zone not null default now(),
+
+    primary key (id)
+);
+
+create index if not exists backend_error_created_at_idx on backend_error using btree (created_at);
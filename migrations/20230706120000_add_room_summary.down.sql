@@ -0,0 +1,2 @@
+drop table if exists room_summary;
+drop table if exists room_counter;
@@ -0,0 +1,22 @@
+create table if not exists room_counter (
+    room_id uuid not null,
+    peak_concurrency integer not null default 0,
+    slow_link_count integer not null default 0,
+    broadcast_count integer not null default 0,
+
+    foreign key (room_id) references room (id) on delete cascade,
+    primary key (room_id)
+);
+
+create table if not exists room_summary (
+    room_id uuid not null,
+    duration_ms bigint not null,
+    peak_concurrency integer not null,
+    streams_count bigint not null,
+    slow_link_count integer not null,
+    broadcast_count integer not null,
+    created_at timestamp with time zone not null default now(),
+
+    foreign key (room_id) references room (id) on delete cascade,
+    primary key (room_id)
+);
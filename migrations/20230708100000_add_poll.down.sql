@@ -0,0 +1,3 @@
+alter table room_summary drop column if exists polls;
+drop table if exists poll_vote;
+drop table if exists poll;
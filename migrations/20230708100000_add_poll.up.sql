@@ -0,0 +1,26 @@
+create table if not exists poll (
+    id uuid not null default gen_random_uuid(),
+    room_id uuid not null,
+    created_by agent_id not null,
+    question text not null,
+    options text[] not null,
+    closed_at timestamp with time zone,
+    created_at timestamp with time zone not null default now(),
+
+    foreign key (room_id) references room (id) on delete cascade,
+    primary key (id)
+);
+
+create index if not exists poll_room_id_idx on poll using btree (room_id);
+
+create table if not exists poll_vote (
+    poll_id uuid not null,
+    agent_id agent_id not null,
+    option_idx integer not null,
+    created_at timestamp with time zone not null default now(),
+
+    foreign key (poll_id) references poll (id) on delete cascade,
+    primary key (poll_id, agent_id)
+);
+
+alter table room_summary add column if not exists polls jsonb not null default '[]'::jsonb;
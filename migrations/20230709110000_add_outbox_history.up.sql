@@ -0,0 +1,17 @@
+create table if not exists outbox_history (
+    id bigint not null,
+    entity_type text not null,
+    operation text not null,
+    classroom_id uuid,
+    stage jsonb not null,
+    payload_hash text not null,
+    status text not null,
+    error_kind text,
+    replay_count integer not null default 0,
+    created_at timestamp with time zone not null default now(),
+    updated_at timestamp with time zone not null default now(),
+
+    primary key (entity_type, operation, id)
+);
+
+create index if not exists outbox_history_classroom_id_idx on outbox_history using btree (classroom_id, id);
@@ -0,0 +1,2 @@
+drop table if exists room_health;
+alter table room_counter drop column if exists hangup_count;
@@ -0,0 +1,18 @@
+alter table room_counter add column if not exists hangup_count integer not null default 0;
+
+create table if not exists room_health (
+    room_id uuid not null,
+    score real not null default 0,
+    reasons text[] not null default '{}',
+    error_rate real not null default 0,
+    slow_link_rate real not null default 0,
+    hangup_rate real not null default 0,
+    slow_link_count integer not null default 0,
+    hangup_count integer not null default 0,
+    updated_at timestamp with time zone not null default now(),
+
+    foreign key (room_id) references room (id) on delete cascade,
+    primary key (room_id)
+);
+
+create index if not exists room_health_score_idx on room_health (score desc);
@@ -0,0 +1,2 @@
+alter table janus_backend drop column if exists capabilities;
+alter table room drop column if exists audio_processing;
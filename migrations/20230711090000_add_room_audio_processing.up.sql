@@ -0,0 +1,2 @@
+alter table room add column if not exists audio_processing jsonb;
+alter table janus_backend add column if not exists capabilities text[] not null default '{}';
@@ -0,0 +1,3 @@
+drop table if exists classroom_ban_audit;
+drop table if exists classroom_ban;
+drop type if exists classroom_ban_action;
@@ -0,0 +1,33 @@
+do $$
+begin
+    if not exists (select 1 from pg_type where typname = 'classroom_ban_action') then
+        create type classroom_ban_action as enum ('ban', 'unban');
+    end if;
+end$$;
+
+create table if not exists classroom_ban (
+    classroom_id uuid not null,
+    account_id account_id not null,
+    banned_by agent_id not null,
+    reason text,
+    expires_at timestamp with time zone,
+    created_at timestamp with time zone not null default now(),
+
+    primary key (classroom_id, account_id)
+);
+
+create table if not exists classroom_ban_audit (
+    id uuid not null default gen_random_uuid(),
+    classroom_id uuid not null,
+    account_id account_id not null,
+    action classroom_ban_action not null,
+    performed_by agent_id not null,
+    reason text,
+    expires_at timestamp with time zone,
+    created_at timestamp with time zone not null default now(),
+
+    primary key (id)
+);
+
+create index if not exists classroom_ban_audit_classroom_id_idx
+    on classroom_ban_audit using btree (classroom_id, created_at);
@@ -0,0 +1,14 @@
+create table if not exists room_reserve_audit (
+    id uuid not null default gen_random_uuid(),
+    room_id uuid not null,
+    old_reserve integer,
+    new_reserve integer,
+    changed_by agent_id not null,
+    created_at timestamp with time zone not null default now(),
+
+    foreign key (room_id) references room (id) on delete cascade,
+    primary key (id)
+);
+
+create index if not exists room_reserve_audit_room_id_idx
+    on room_reserve_audit using btree (room_id, created_at);
@@ -0,0 +1 @@
+drop table if exists tenant_usage;
@@ -0,0 +1,13 @@
+create table if not exists tenant_usage (
+    audience text not null,
+    period_start timestamp with time zone not null,
+    requests bigint not null default 0,
+    db_time_ms bigint not null default 0,
+    janus_calls bigint not null default 0,
+    bytes_published bigint not null default 0,
+    updated_at timestamp with time zone not null default now(),
+
+    primary key (audience, period_start)
+);
+
+create index if not exists tenant_usage_period_start_idx on tenant_usage (period_start);
@@ -0,0 +1,2 @@
+drop table if exists message_archive;
+alter table room drop column if exists archive_messages;
@@ -0,0 +1,20 @@
+alter table room add column if not exists archive_messages boolean not null default false;
+
+create table if not exists message_archive (
+    id uuid not null default gen_random_uuid(),
+    room_id uuid not null,
+    agent_id agent_id not null,
+    label text,
+    data jsonb not null,
+    search tsvector generated always as (jsonb_to_tsvector('simple', data, '["string"]')) stored,
+    created_at timestamp with time zone not null default now(),
+
+    foreign key (room_id) references room (id) on delete cascade,
+    primary key (id)
+);
+
+create index if not exists message_archive_room_id_idx
+    on message_archive using btree (room_id, created_at);
+
+create index if not exists message_archive_search_idx
+    on message_archive using gin (search);
@@ -0,0 +1,2 @@
+drop table if exists composite_recording;
+alter table room drop column if exists composite_recording;
@@ -0,0 +1,11 @@
+alter table room add column if not exists composite_recording boolean not null default false;
+
+create table if not exists composite_recording (
+    room_id uuid not null,
+    backend_id agent_id not null,
+    status recording_status not null default 'in_progress',
+    started_at timestamp with time zone not null default now(),
+
+    foreign key (room_id) references room (id) on delete cascade,
+    primary key (room_id)
+);
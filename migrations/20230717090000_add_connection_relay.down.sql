@@ -0,0 +1,4 @@
+alter table room_counter drop column if exists direct_connection_count;
+alter table room_counter drop column if exists relayed_connection_count;
+
+alter table agent_connection drop column if exists relayed;
@@ -0,0 +1,4 @@
+alter table agent_connection add column if not exists relayed boolean;
+
+alter table room_counter add column if not exists relayed_connection_count integer not null default 0;
+alter table room_counter add column if not exists direct_connection_count integer not null default 0;
@@ -0,0 +1,6 @@
+drop index if exists rtc_reader_config_pending_since_idx;
+
+alter table rtc_reader_config drop column if exists applied_receive_audio;
+alter table rtc_reader_config drop column if exists applied_receive_video;
+alter table rtc_reader_config drop column if exists push_attempts;
+alter table rtc_reader_config drop column if exists pending_since;
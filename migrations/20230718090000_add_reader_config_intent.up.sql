@@ -0,0 +1,11 @@
+alter table rtc_reader_config add column if not exists pending_since timestamptz;
+alter table rtc_reader_config add column if not exists push_attempts integer not null default 0;
+alter table rtc_reader_config add column if not exists applied_receive_video boolean not null default true;
+alter table rtc_reader_config add column if not exists applied_receive_audio boolean not null default true;
+
+update rtc_reader_config
+set applied_receive_video = receive_video, applied_receive_audio = receive_audio;
+
+create index if not exists rtc_reader_config_pending_since_idx
+on rtc_reader_config (pending_since)
+where pending_since is not null;
@@ -0,0 +1,2 @@
+alter table rtc drop column if exists record;
+alter table room drop column if exists record_rtcs;
@@ -0,0 +1,2 @@
+alter table room add column if not exists record_rtcs boolean not null default true;
+alter table rtc add column if not exists record boolean not null default true;
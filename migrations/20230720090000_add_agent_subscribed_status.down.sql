@@ -0,0 +1,2 @@
+-- Postgres can't drop an enum label, so `subscribed` stays unused.
+update agent set status = 'in_progress' where status = 'subscribed';
@@ -0,0 +1,3 @@
+-- `in_progress` stays the label of the requested state so instances of the previous
+-- version keep working during a rolling deploy.
+alter type agent_status add value if not exists 'subscribed' before 'ready';
@@ -0,0 +1,2 @@
+alter table recording drop column if exists object_size;
+alter table recording drop column if exists verified_at;
@@ -0,0 +1,2 @@
+alter table recording add column if not exists verified_at timestamptz;
+alter table recording add column if not exists object_size bigint;
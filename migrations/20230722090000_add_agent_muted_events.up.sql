@@ -0,0 +1 @@
+alter table agent add column if not exists muted_events text[] not null default '{}';
@@ -0,0 +1,14 @@
+create table if not exists room_bulk_close (
+    id uuid not null default gen_random_uuid(),
+    audience text,
+    classroom_ids uuid[],
+    closed_before timestamp with time zone,
+    total bigint not null default 0,
+    closed bigint not null default 0,
+    failed bigint not null default 0,
+    created_by agent_id not null,
+    created_at timestamp with time zone not null default now(),
+    finished_at timestamp with time zone,
+
+    primary key (id)
+);
@@ -0,0 +1 @@
+alter table rtc drop column if exists tags;
@@ -0,0 +1 @@
+alter table rtc add column if not exists tags jsonb;
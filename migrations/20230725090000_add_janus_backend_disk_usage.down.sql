@@ -0,0 +1,2 @@
+alter table janus_backend drop column if exists disk_full;
+alter table janus_backend drop column if exists disk_usage;
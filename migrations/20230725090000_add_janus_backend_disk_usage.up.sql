@@ -0,0 +1,2 @@
+alter table janus_backend add column if not exists disk_usage real;
+alter table janus_backend add column if not exists disk_full boolean not null default false;
@@ -0,0 +1 @@
+drop table if exists rtc_subscribe_set;
@@ -0,0 +1,9 @@
+create table if not exists rtc_subscribe_set (
+    room_id uuid not null,
+    reader_id agent_id not null,
+    rtc_ids uuid[] not null,
+    updated_at timestamp with time zone not null default now(),
+
+    foreign key (room_id) references room (id) on delete cascade,
+    primary key (room_id, reader_id)
+);
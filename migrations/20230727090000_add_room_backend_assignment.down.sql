@@ -0,0 +1,3 @@
+drop trigger if exists room_backend_assignment_trigger on room;
+drop function if exists on_room_backend_assignment();
+drop table if exists room_backend_assignment;
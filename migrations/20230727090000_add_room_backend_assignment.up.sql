@@ -0,0 +1,38 @@
+create table if not exists room_backend_assignment (
+    id uuid not null default gen_random_uuid(),
+    room_id uuid not null,
+    backend_id agent_id not null,
+    previous_backend_id agent_id,
+    backend_group text,
+    assigned_at timestamp with time zone not null default now(),
+
+    primary key (id)
+);
+
+create index if not exists room_backend_assignment_assigned_at_idx
+on room_backend_assignment (assigned_at);
+
+-- The history outlives the rooms and backends so there are no foreign keys.
+create or replace function on_room_backend_assignment() returns trigger
+    language plpgsql
+    as $$
+begin
+    if new.backend_id is not null and (tg_op = 'INSERT' or new.backend_id is distinct from old.backend_id) then
+        insert into room_backend_assignment (room_id, backend_id, previous_backend_id, backend_group)
+        values (
+            new.id,
+            new.backend_id,
+            case when tg_op = 'UPDATE' then old.backend_id end,
+            (select jb.group from janus_backend as jb where jb.id = new.backend_id)
+        );
+    end if;
+
+    return null;
+end;
+$$;
+
+drop trigger if exists room_backend_assignment_trigger on room;
+
+create trigger room_backend_assignment_trigger
+after insert or update of backend_id on room
+for each row execute function on_room_backend_assignment();
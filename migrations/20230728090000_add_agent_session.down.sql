@@ -0,0 +1,4 @@
+alter table room_summary drop column if exists reconnects_count;
+alter table room_summary drop column if exists sessions_count;
+drop table if exists agent_session;
+alter table agent drop column if exists session_key;
@@ -0,0 +1,17 @@
+alter table agent add column if not exists session_key uuid not null default gen_random_uuid();
+
+create table if not exists agent_session (
+    room_id uuid not null,
+    session_key uuid not null,
+    -- The latest agent of the session.
+    agent_id agent_id not null,
+    reconnects integer not null default 0,
+    created_at timestamp with time zone not null default now(),
+    updated_at timestamp with time zone not null default now(),
+
+    foreign key (room_id) references room (id) on delete cascade,
+    primary key (room_id, session_key)
+);
+
+alter table room_summary add column if not exists sessions_count bigint not null default 0;
+alter table room_summary add column if not exists reconnects_count bigint not null default 0;
@@ -0,0 +1,2 @@
+drop table if exists janus_event;
+drop table if exists janus_event_capture;
@@ -0,0 +1,22 @@
+create table if not exists janus_event_capture (
+    room_id uuid not null,
+    started_at timestamp with time zone not null default now(),
+    expires_at timestamp with time zone not null,
+
+    primary key (room_id),
+    foreign key (room_id) references room (id) on delete cascade
+);
+
+create table if not exists janus_event (
+    id uuid not null default gen_random_uuid(),
+    room_id uuid not null,
+    backend_id agent_id not null,
+    payload jsonb not null,
+    created_at timestamp with time zone not null default now(),
+
+    primary key (id),
+    foreign key (room_id) references room (id) on delete cascade
+);
+
+create index if not exists janus_event_room_id_created_at_idx on janus_event using btree (room_id, created_at);
+create index if not exists janus_event_created_at_idx on janus_event using btree (created_at);
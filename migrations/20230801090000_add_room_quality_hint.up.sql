@@ -0,0 +1,7 @@
+create table if not exists room_quality_hint (
+    room_id uuid not null,
+    sent_at timestamp with time zone not null default now(),
+
+    primary key (room_id),
+    foreign key (room_id) references room (id) on delete cascade
+);
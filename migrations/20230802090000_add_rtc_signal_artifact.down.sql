@@ -0,0 +1,3 @@
+drop table if exists rtc_signal_artifact;
+drop table if exists rtc_signal_debug;
+drop type if exists rtc_signal_artifact_kind;
@@ -0,0 +1,30 @@
+do $$
+begin
+    if not exists (select 1 from pg_type where typname = 'rtc_signal_artifact_kind') then
+        create type rtc_signal_artifact_kind as enum ('offer', 'answer', 'candidates');
+    end if;
+end$$;
+
+create table if not exists rtc_signal_debug (
+    room_id uuid not null,
+    created_at timestamp with time zone not null default now(),
+
+    primary key (room_id),
+    foreign key (room_id) references room (id) on delete cascade
+);
+
+create table if not exists rtc_signal_artifact (
+    id uuid not null default gen_random_uuid(),
+    room_id uuid not null,
+    rtc_id uuid not null,
+    agent_id agent_id not null,
+    kind rtc_signal_artifact_kind not null,
+    body text not null,
+    created_at timestamp with time zone not null default now(),
+
+    primary key (id),
+    foreign key (room_id) references room (id) on delete cascade
+);
+
+create index if not exists rtc_signal_artifact_rtc_id_agent_id_idx
+    on rtc_signal_artifact using btree (rtc_id, agent_id, created_at);
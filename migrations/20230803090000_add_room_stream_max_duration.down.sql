@@ -0,0 +1 @@
+alter table room drop column if exists stream_max_duration;
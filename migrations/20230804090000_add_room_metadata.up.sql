@@ -0,0 +1 @@
+alter table room add column if not exists metadata jsonb;
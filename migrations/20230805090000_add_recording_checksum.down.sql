@@ -0,0 +1,2 @@
+alter table recording drop column if exists object_checksum;
+alter table recording drop column if exists checksum;
@@ -0,0 +1,2 @@
+alter table recording add column if not exists checksum text;
+alter table recording add column if not exists object_checksum text;
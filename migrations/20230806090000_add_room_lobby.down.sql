@@ -0,0 +1,3 @@
+-- Postgres can't drop an enum label, so `pending` stays unused.
+delete from agent where status = 'pending';
+alter table room drop column if exists lobby;
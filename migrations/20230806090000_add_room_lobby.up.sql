@@ -0,0 +1,2 @@
+alter table room add column if not exists lobby boolean not null default false;
+alter type agent_status add value if not exists 'pending' before 'in_progress';
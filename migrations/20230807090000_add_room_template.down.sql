@@ -0,0 +1,2 @@
+drop table if exists room_config_default;
+drop table if exists room_template;
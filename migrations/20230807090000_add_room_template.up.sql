@@ -0,0 +1,22 @@
+create table if not exists room_template (
+    audience text not null,
+    name text not null,
+    rtc_sharing_policy rtc_sharing_policy not null default 'none',
+    reserve integer,
+    metadata jsonb,
+    writer_config jsonb,
+    reader_config jsonb,
+    updated_by agent_id not null,
+    updated_at timestamp with time zone not null default now(),
+
+    primary key (audience, name)
+);
+
+create table if not exists room_config_default (
+    room_id uuid not null,
+    writer_config jsonb,
+    reader_config jsonb,
+
+    foreign key (room_id) references room (id) on delete cascade,
+    primary key (room_id)
+);
@@ -0,0 +1,2 @@
+drop table if exists room_schedule_occurrence;
+drop table if exists room_schedule;
@@ -0,0 +1,38 @@
+create table if not exists room_schedule (
+    id uuid not null default gen_random_uuid(),
+    audience text not null,
+    classroom_id uuid not null,
+    recurrence text not null,
+    starts_at timestamp with time zone not null,
+    duration integer not null,
+    template text,
+    rtc_sharing_policy rtc_sharing_policy not null default 'none',
+    reserve integer,
+    tags jsonb,
+    last_occurrence_at timestamp with time zone,
+    next_occurrence_at timestamp with time zone,
+    created_by agent_id not null,
+    created_at timestamp with time zone not null default now(),
+
+    check (duration > 0),
+    primary key (id)
+);
+
+create index if not exists room_schedule_next_occurrence_at_idx
+    on room_schedule using btree (next_occurrence_at)
+    where next_occurrence_at is not null;
+
+create table if not exists room_schedule_occurrence (
+    room_id uuid not null,
+    schedule_id uuid,
+    opens_at timestamp with time zone not null,
+    opened_at timestamp with time zone,
+
+    foreign key (room_id) references room (id) on delete cascade,
+    foreign key (schedule_id) references room_schedule (id) on delete set null,
+    primary key (room_id)
+);
+
+create index if not exists room_schedule_occurrence_opens_at_idx
+    on room_schedule_occurrence using btree (opens_at)
+    where opened_at is null;
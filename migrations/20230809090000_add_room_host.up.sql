@@ -0,0 +1,14 @@
+create table if not exists room_host (
+    room_id uuid not null,
+    agent_id agent_id not null,
+    created_at timestamp with time zone not null default now(),
+
+    foreign key (room_id) references room (id) on delete cascade,
+    primary key (room_id, agent_id)
+);
+
+insert into room_host (room_id, agent_id)
+select id, host
+from room
+where host is not null
+on conflict do nothing;
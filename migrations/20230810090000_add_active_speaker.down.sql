@@ -0,0 +1,3 @@
+alter table room_summary drop column if exists speaking_time;
+drop table if exists room_speaking_time;
+alter table room drop column if exists active_speaker_events;
@@ -0,0 +1,13 @@
+alter table room add column if not exists active_speaker_events boolean not null default false;
+
+create table if not exists room_speaking_time (
+    room_id uuid not null,
+    agent_id agent_id not null,
+    speaking_ms bigint not null default 0,
+    updated_at timestamp with time zone not null default now(),
+
+    foreign key (room_id) references room (id) on delete cascade,
+    primary key (room_id, agent_id)
+);
+
+alter table room_summary add column if not exists speaking_time jsonb not null default '[]'::jsonb;
@@ -0,0 +1 @@
+alter table room drop column if exists max_agents;
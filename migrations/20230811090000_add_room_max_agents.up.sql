@@ -0,0 +1 @@
+alter table room add column if not exists max_agents integer check (max_agents > 0);
@@ -0,0 +1,2 @@
+alter table rtc drop column if exists kind;
+drop type if exists rtc_kind;
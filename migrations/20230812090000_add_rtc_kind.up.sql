@@ -0,0 +1,8 @@
+do $$
+begin
+    if not exists (select 1 from pg_type where typname = 'rtc_kind') then
+        create type rtc_kind as enum ('camera', 'screen');
+    end if;
+end$$;
+
+alter table rtc add column if not exists kind rtc_kind not null default 'camera';
@@ -0,0 +1 @@
+alter table janus_backend drop column if exists rtt_ms;
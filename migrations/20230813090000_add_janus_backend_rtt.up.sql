@@ -0,0 +1 @@
+alter table janus_backend add column if not exists rtt_ms integer;
@@ -0,0 +1,2 @@
+alter table rtc_writer_config drop column if exists max_audio_bitrate;
+alter table rtc_writer_config drop column if exists max_video_bitrate;
@@ -0,0 +1,4 @@
+alter table rtc_writer_config add column if not exists max_video_bitrate bigint;
+alter table rtc_writer_config add column if not exists max_audio_bitrate bigint;
+alter table rtc_writer_config add constraint rtc_writer_config_max_video_bitrate_check check (max_video_bitrate is null or max_video_bitrate > 0);
+alter table rtc_writer_config add constraint rtc_writer_config_max_audio_bitrate_check check (max_audio_bitrate is null or max_audio_bitrate > 0);
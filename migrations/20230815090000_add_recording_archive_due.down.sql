@@ -0,0 +1 @@
+alter table recording drop column if exists archive_due_at;
@@ -0,0 +1 @@
+alter table recording add column if not exists archive_due_at timestamptz;
@@ -0,0 +1 @@
+alter table agent_connection drop column if exists ice_ufrag;
@@ -0,0 +1 @@
+alter table agent_connection add column if not exists ice_ufrag text;
@@ -0,0 +1,12 @@
+create table if not exists room_upload_job (
+    room_id uuid not null,
+    due_at timestamp with time zone not null,
+    attempts integer not null default 0,
+    created_at timestamp with time zone not null default now(),
+
+    foreign key (room_id) references room (id) on delete cascade,
+    primary key (room_id)
+);
+
+create index if not exists room_upload_job_due_at_idx
+    on room_upload_job using btree (due_at);
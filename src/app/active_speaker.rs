@@ -0,0 +1,399 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use parking_lot::Mutex;
+use serde::Serialize;
+use svc_agent::AgentId;
+use tracing::error;
+
+use crate::{app::context::GlobalContext, config::ActiveSpeakerConfig, db};
+
+const ACTIVE_SPEAKER_CHANGED_LABEL: &str = "room.active_speaker_changed";
+
+#[derive(Debug, Serialize, PartialEq)]
+struct ActiveSpeakerChange {
+    room_id: db::room::Id,
+    /// Active speakers in the order they've become ones.
+    speakers: Vec<AgentId>,
+}
+
+/// Speakers of the rooms debounced by `activation_delay` and `release_delay`.
+#[derive(Clone, Default)]
+pub struct ActiveSpeakers {
+    config: ActiveSpeakerConfig,
+    rooms: Arc<Mutex<HashMap<db::room::Id, RoomSpeakers>>>,
+}
+
+#[derive(Default)]
+struct RoomSpeakers {
+    agents: HashMap<AgentId, AgentState>,
+    active: Vec<AgentId>,
+}
+
+#[derive(Default)]
+struct AgentState {
+    speaking_since: Option<Instant>,
+    silent_since: Option<Instant>,
+    /// Speaking time is counted up to this moment while the agent speaks.
+    counted_at: Option<Instant>,
+    unflushed: Duration,
+}
+
+impl AgentState {
+    fn start(&mut self, now: Instant) {
+        if self.speaking_since.is_none() {
+            self.speaking_since = Some(now);
+            self.counted_at = Some(now);
+        }
+
+        self.silent_since = None;
+    }
+
+    fn stop(&mut self, now: Instant) {
+        if self.speaking_since.take().is_some() {
+            self.count(now);
+            self.counted_at = None;
+            self.silent_since = Some(now);
+        }
+    }
+
+    fn count(&mut self, now: Instant) {
+        if let Some(counted_at) = &mut self.counted_at {
+            self.unflushed += now.saturating_duration_since(*counted_at);
+            *counted_at = now;
+        }
+    }
+
+    fn is_activated(&self, now: Instant, activation_delay: Duration) -> bool {
+        self.speaking_since.map_or(false, |since| {
+            now.saturating_duration_since(since) >= activation_delay
+        })
+    }
+
+    fn is_held(&self, now: Instant, release_delay: Duration) -> bool {
+        self.speaking_since.is_some()
+            || self.silent_since.map_or(false, |since| {
+                now.saturating_duration_since(since) < release_delay
+            })
+    }
+}
+
+#[derive(Default)]
+struct Tick {
+    changes: Vec<ActiveSpeakerChange>,
+    speaking_time: Vec<(db::room::Id, AgentId, Duration)>,
+}
+
+impl ActiveSpeakers {
+    pub fn new(config: ActiveSpeakerConfig) -> Self {
+        Self {
+            config,
+            rooms: Arc::default(),
+        }
+    }
+
+    pub fn observe(&self, room_id: db::room::Id, agent_id: &AgentId, speaking: bool, now: Instant) {
+        let mut rooms = self.rooms.lock();
+        let state = rooms
+            .entry(room_id)
+            .or_default()
+            .agents
+            .entry(agent_id.to_owned())
+            .or_default();
+
+        if speaking {
+            state.start(now);
+        } else {
+            state.stop(now);
+        }
+    }
+
+    /// Ends the agent's speech when its stream stops without a notification.
+    pub fn silence(&self, room_id: db::room::Id, agent_id: &AgentId, now: Instant) {
+        let mut rooms = self.rooms.lock();
+
+        if let Some(state) = rooms
+            .get_mut(&room_id)
+            .and_then(|room| room.agents.get_mut(agent_id))
+        {
+            state.stop(now);
+        }
+    }
+
+    fn tick(&self, now: Instant) -> Tick {
+        let mut tick = Tick::default();
+        let mut rooms = self.rooms.lock();
+
+        for (room_id, RoomSpeakers { agents, active }) in rooms.iter_mut() {
+            for (agent_id, state) in agents.iter_mut() {
+                state.count(now);
+
+                if !state.unflushed.is_zero() {
+                    let speaking_time = std::mem::take(&mut state.unflushed);
+                    tick.speaking_time
+                        .push((*room_id, agent_id.to_owned(), speaking_time));
+                }
+            }
+
+            let previous = active.clone();
+
+            active.retain(|agent_id| {
+                agents
+                    .get(agent_id)
+                    .map_or(false, |state| state.is_held(now, self.config.release_delay))
+            });
+
+            let mut activated = agents
+                .iter()
+                .filter(|(agent_id, state)| {
+                    !active.contains(agent_id)
+                        && state.is_activated(now, self.config.activation_delay)
+                })
+                .collect::<Vec<_>>();
+
+            activated.sort_by_key(|(_, state)| state.speaking_since);
+            active.extend(
+                activated
+                    .into_iter()
+                    .map(|(agent_id, _)| agent_id.to_owned()),
+            );
+
+            if *active != previous {
+                tick.changes.push(ActiveSpeakerChange {
+                    room_id: *room_id,
+                    speakers: active.clone(),
+                });
+            }
+
+            agents.retain(|agent_id, state| {
+                state.speaking_since.is_some() || active.contains(agent_id)
+            });
+        }
+
+        rooms.retain(|_, room| !room.agents.is_empty());
+        tick
+    }
+}
+
+/// Periodically reevaluates the active speakers, publishes the changes and flushes
+/// the speaking time.
+pub async fn start_publisher(ctx: Arc<dyn GlobalContext + Send + Sync>) {
+    let interval = ctx.config().active_speaker.interval;
+
+    loop {
+        if let Err(err) = publish(ctx.as_ref(), Instant::now()).await {
+            error!(?err, "Failed to publish active speakers");
+        }
+
+        tokio::time::sleep(interval).await;
+    }
+}
+
+async fn publish<C: GlobalContext + ?Sized>(
+    ctx: &C,
+    now: Instant,
+) -> anyhow::Result<Vec<ActiveSpeakerChange>> {
+    let tick = ctx.active_speakers().tick(now);
+
+    if tick.changes.is_empty() && tick.speaking_time.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut conn = ctx.get_conn().await?;
+
+    for (room_id, agent_id, speaking_time) in &tick.speaking_time {
+        let speaking_ms = speaking_time.as_millis() as i64;
+
+        if let Err(err) =
+            db::room_speaking_time::add(*room_id, agent_id, speaking_ms, &mut conn).await
+        {
+            error!(?err, %room_id, %agent_id, "Failed to store speaking time");
+        }
+    }
+
+    if tick.changes.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let room_ids = tick
+        .changes
+        .iter()
+        .map(|change| change.room_id)
+        .collect::<Vec<_>>();
+
+    let enabled_room_ids = db::room::with_active_speaker_events(&room_ids, &mut conn)
+        .await
+        .context("Failed to find rooms with active speaker events")?;
+
+    let pseudonyms = ctx.pseudonyms();
+    let mut published = vec![];
+
+    for mut change in tick.changes {
+        if !enabled_room_ids.contains(&change.room_id) {
+            continue;
+        }
+
+        for speaker in change.speakers.iter_mut() {
+            if let Some(pseudonym) = pseudonyms.agent_id(speaker) {
+                *speaker = pseudonym;
+            }
+        }
+
+        let topic = format!("rooms/{}/events", change.room_id);
+        let payload = serde_json::to_value(&change)?;
+
+        if let Err(err) =
+            ctx.mqtt_client()
+                .lock()
+                .publish_payload(ACTIVE_SPEAKER_CHANGED_LABEL, &topic, payload)
+        {
+            error!(?err, room_id = %change.room_id, "Failed to publish active speakers");
+        }
+
+        published.push(change);
+    }
+
+    Ok(published)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Bound;
+
+    use chrono::Utc;
+
+    use super::*;
+    use crate::test_helpers::{db::TestDb, prelude::*};
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn total_speaking_time(tick: &Tick) -> Duration {
+        tick.speaking_time.iter().map(|(_, _, time)| *time).sum()
+    }
+
+    #[test]
+    fn speakers_with_hysteresis() {
+        let speakers = ActiveSpeakers::new(ActiveSpeakerConfig {
+            activation_delay: ms(500),
+            release_delay: ms(1500),
+            ..Default::default()
+        });
+
+        let room_id = db::room::Id::random();
+        let alpha = TestAgent::new("web", "alpha", USR_AUDIENCE);
+        let bravo = TestAgent::new("web", "bravo", USR_AUDIENCE);
+        let start = Instant::now();
+
+        // A short blip doesn't make a speaker.
+        speakers.observe(room_id, alpha.agent_id(), true, start);
+        let tick = speakers.tick(start + ms(100));
+        assert!(tick.changes.is_empty());
+        speakers.observe(room_id, alpha.agent_id(), false, start + ms(200));
+        let tick = speakers.tick(start + ms(600));
+        assert!(tick.changes.is_empty());
+        assert_eq!(total_speaking_time(&tick), ms(100));
+
+        speakers.observe(room_id, alpha.agent_id(), true, start + ms(1000));
+        speakers.observe(room_id, bravo.agent_id(), true, start + ms(1200));
+        let tick = speakers.tick(start + ms(1600));
+
+        assert_eq!(
+            tick.changes,
+            vec![ActiveSpeakerChange {
+                room_id,
+                speakers: vec![alpha.agent_id().to_owned()],
+            }]
+        );
+
+        let tick = speakers.tick(start + ms(1800));
+        assert_eq!(tick.changes[0].speakers.len(), 2);
+
+        // Pauses shorter than the release delay keep the speaker.
+        speakers.observe(room_id, alpha.agent_id(), false, start + ms(2000));
+        let tick = speakers.tick(start + ms(3000));
+        assert!(tick.changes.is_empty());
+        speakers.observe(room_id, alpha.agent_id(), true, start + ms(3200));
+        speakers.observe(room_id, alpha.agent_id(), false, start + ms(3400));
+        let tick = speakers.tick(start + ms(4000));
+        assert!(tick.changes.is_empty());
+
+        let tick = speakers.tick(start + ms(5000));
+        assert_eq!(tick.changes[0].speakers, vec![bravo.agent_id().to_owned()]);
+
+        speakers.silence(room_id, bravo.agent_id(), start + ms(5000));
+        let tick = speakers.tick(start + ms(7000));
+        assert!(tick.changes[0].speakers.is_empty());
+        assert!(speakers.rooms.lock().is_empty());
+    }
+
+    #[sqlx::test]
+    async fn publish_to_rooms_with_flag(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let mut conn = db.get_conn().await;
+        let now = Utc::now();
+
+        let room = factory::Room::new()
+            .audience(USR_AUDIENCE)
+            .time((Bound::Included(now), Bound::Unbounded))
+            .rtc_sharing_policy(db::rtc::SharingPolicy::Shared)
+            .active_speaker_events(true)
+            .insert(&mut conn)
+            .await;
+
+        let quiet_room = factory::Room::new()
+            .audience(USR_AUDIENCE)
+            .time((Bound::Included(now), Bound::Unbounded))
+            .rtc_sharing_policy(db::rtc::SharingPolicy::Shared)
+            .insert(&mut conn)
+            .await;
+
+        let context = TestContext::new(db.clone(), TestAuthz::new()).await;
+        let agent = TestAgent::new("web", "alpha", USR_AUDIENCE);
+        let start = Instant::now();
+
+        for room_id in [room.id(), quiet_room.id()] {
+            context
+                .active_speakers()
+                .observe(room_id, agent.agent_id(), true, start);
+        }
+
+        let changes = publish(&context, start + ms(1000))
+            .await
+            .expect("Failed to publish");
+
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].room_id, room.id());
+        assert_eq!(changes[0].speakers, vec![agent.agent_id().to_owned()]);
+
+        for room_id in [room.id(), quiet_room.id()] {
+            context
+                .active_speakers()
+                .observe(room_id, agent.agent_id(), false, start + ms(3000));
+        }
+
+        publish(&context, start + ms(3500))
+            .await
+            .expect("Failed to publish");
+
+        for room_id in [room.id(), quiet_room.id()] {
+            let summary = db::room_summary::InsertQuery::new(room_id)
+                .execute(&mut conn)
+                .await
+                .expect("Failed to insert room summary");
+
+            assert_eq!(
+                summary.speaking_time,
+                serde_json::json!([{
+                    "agent_id": agent.agent_id().to_string(),
+                    "speaking_ms": 3000,
+                }])
+            );
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use svc_agent::AgentId;
+
+use crate::{config::AudioLevelConfig, db};
+
+/// Level of silence in dBov as Janus reports it.
+const SILENCE: u8 = 127;
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct AudioLevelSample {
+    pub agent_id: AgentId,
+    /// Last reported level in dB, from -127 for silence to 0 for the loudest sound.
+    pub level: f32,
+    /// Average level over the window in dB.
+    pub average: f32,
+    /// The loudest level over the window in dB.
+    pub peak: f32,
+    /// The publisher is loud but doesn't speak, e.g. there's noise in the background.
+    pub suggest_mute: bool,
+}
+
+/// Rolling levels of the publishers in memory of the instance handling the backend's events.
+#[derive(Clone, Default)]
+pub struct AudioLevels {
+    config: AudioLevelConfig,
+    publishers: Arc<Mutex<HashMap<(db::room::Id, AgentId), PublisherLevels>>>,
+}
+
+#[derive(Default)]
+struct PublisherLevels {
+    levels: VecDeque<(Instant, f32)>,
+    speaking: bool,
+    sampled_at: Option<Instant>,
+}
+
+impl PublisherLevels {
+    fn average(&self) -> f32 {
+        let sum = self.levels.iter().map(|(_, level)| level).sum::<f32>();
+        sum / self.levels.len().max(1) as f32
+    }
+
+    fn peak(&self) -> f32 {
+        self.levels
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(-(SILENCE as f32), f32::max)
+    }
+
+    /// Whether the levels cover most of the window so the average isn't made of a single burst.
+    fn covers(&self, now: Instant, window: Duration) -> bool {
+        self.levels.front().map_or(false, |(at, _)| {
+            now.saturating_duration_since(*at) >= window * 9 / 10
+        })
+    }
+}
+
+impl AudioLevels {
+    pub fn new(config: AudioLevelConfig) -> Self {
+        Self {
+            config,
+            publishers: Arc::default(),
+        }
+    }
+
+    /// Records the level of the publisher in dBov, 0 for the loudest sound and 127 for silence.
+    /// Returns a sample to publish if `sample_interval` has passed since the previous one.
+    pub fn observe(
+        &self,
+        room_id: db::room::Id,
+        agent_id: &AgentId,
+        dbov: u8,
+        now: Instant,
+    ) -> Option<AudioLevelSample> {
+        let mut publishers = self.publishers.lock();
+        let state = publishers
+            .entry((room_id, agent_id.to_owned()))
+            .or_default();
+
+        let level = -(dbov.min(SILENCE) as f32);
+        state.levels.push_back((now, level));
+
+        while let Some((at, _)) = state.levels.front() {
+            if now.saturating_duration_since(*at) > self.config.window {
+                state.levels.pop_front();
+            } else {
+                break;
+            }
+        }
+
+        if self.config.sample_interval.is_zero() {
+            return None;
+        }
+
+        let due = state.sampled_at.map_or(true, |sampled_at| {
+            now.saturating_duration_since(sampled_at) >= self.config.sample_interval
+        });
+
+        if !due {
+            return None;
+        }
+
+        state.sampled_at = Some(now);
+        let average = state.average();
+
+        Some(AudioLevelSample {
+            agent_id: agent_id.to_owned(),
+            level,
+            average,
+            peak: state.peak(),
+            suggest_mute: !state.speaking
+                && average >= self.config.noisy_level
+                && state.covers(now, self.config.window),
+        })
+    }
+
+    /// Keeps track of the speech so that a loud speaker isn't taken for noise.
+    pub fn set_speaking(&self, room_id: db::room::Id, agent_id: &AgentId, speaking: bool) {
+        self.publishers
+            .lock()
+            .entry((room_id, agent_id.to_owned()))
+            .or_default()
+            .speaking = speaking;
+    }
+
+    /// Drops the levels of the publisher whose stream has stopped.
+    pub fn forget(&self, room_id: db::room::Id, agent_id: &AgentId) {
+        self.publishers
+            .lock()
+            .remove(&(room_id, agent_id.to_owned()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::prelude::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn levels() -> AudioLevels {
+        AudioLevels::new(AudioLevelConfig {
+            sample_interval: ms(500),
+            window: ms(2000),
+            noisy_level: -40.0,
+        })
+    }
+
+    #[test]
+    fn sample_levels() {
+        let levels = levels();
+        let room_id = db::room::Id::random();
+        let agent = TestAgent::new("web", "alpha", USR_AUDIENCE);
+        let start = Instant::now();
+
+        let sample = levels
+            .observe(room_id, agent.agent_id(), 60, start)
+            .expect("First level not sampled");
+
+        assert_eq!(sample.level, -60.0);
+        assert_eq!(sample.average, -60.0);
+        assert!(!sample.suggest_mute);
+
+        assert!(levels
+            .observe(room_id, agent.agent_id(), 20, start + ms(100))
+            .is_none());
+
+        let sample = levels
+            .observe(room_id, agent.agent_id(), 40, start + ms(500))
+            .expect("Level not sampled after the interval");
+
+        assert_eq!(sample.level, -40.0);
+        assert_eq!(sample.average, -40.0);
+        assert_eq!(sample.peak, -20.0);
+
+        // Levels older than the window are out of the stats.
+        let sample = levels
+            .observe(room_id, agent.agent_id(), 127, start + ms(2200))
+            .expect("Level not sampled after the interval");
+
+        assert_eq!(sample.peak, -40.0);
+        assert_eq!(sample.average, -83.5);
+    }
+
+    #[test]
+    fn suggest_muting_noise() {
+        let levels = levels();
+        let room_id = db::room::Id::random();
+        let agent = TestAgent::new("web", "alpha", USR_AUDIENCE);
+        let start = Instant::now();
+
+        let mut last = None;
+
+        for n in 0..=20 {
+            if let Some(sample) = levels.observe(room_id, agent.agent_id(), 30, start + ms(n * 100))
+            {
+                last = Some(sample);
+            }
+        }
+
+        assert!(last.expect("No samples").suggest_mute);
+
+        // A loud speaker isn't a noise.
+        levels.set_speaking(room_id, agent.agent_id(), true);
+
+        let sample = levels
+            .observe(room_id, agent.agent_id(), 30, start + ms(2500))
+            .expect("Level not sampled");
+
+        assert!(!sample.suggest_mute);
+
+        levels.forget(room_id, agent.agent_id());
+        assert!(levels.publishers.lock().is_empty());
+    }
+
+    #[test]
+    fn skip_samples_when_disabled() {
+        let levels = AudioLevels::new(AudioLevelConfig {
+            sample_interval: Duration::ZERO,
+            ..Default::default()
+        });
+
+        let agent = TestAgent::new("web", "alpha", USR_AUDIENCE);
+
+        assert!(levels
+            .observe(db::room::Id::random(), agent.agent_id(), 30, Instant::now())
+            .is_none());
+    }
+}
@@ -0,0 +1,391 @@
+use std::sync::Arc;
+
+use anyhow::{Context as AnyhowContext, Result};
+use parking_lot::RwLock;
+use prometheus::{IntCounter, IntGauge};
+use svc_agent::{
+    mqtt::{
+        Address, Agent, AgentBuilder, AgentConfig, AgentNotification, ConnectionMode,
+        IntoPublishableMessage, OutgoingMessage, PublishableMessage, QoS, SubscriptionTopic,
+    },
+    Addressable, AgentId, Error, SharedGroup,
+};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tracing::{error, info, warn};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// MQTT agent connected to one broker of an ordered list at a time.
+///
+/// Clones share the active connection so a failover redirects all of them at once. Every
+/// subscription made through the agent is remembered to be repeated on the next broker.
+#[derive(Clone)]
+pub struct BrokerAgent {
+    address: Address,
+    state: Arc<RwLock<State>>,
+}
+
+struct State {
+    agent: Agent,
+    subscriptions: Vec<(Topic, QoS, Option<SharedGroup>)>,
+}
+
+impl BrokerAgent {
+    fn new(agent: Agent) -> Self {
+        Self {
+            address: agent.address().to_owned(),
+            state: Arc::new(RwLock::new(State {
+                agent,
+                subscriptions: vec![],
+            })),
+        }
+    }
+
+    pub fn address(&self) -> &Address {
+        &self.address
+    }
+
+    pub fn id(&self) -> &AgentId {
+        self.address.id()
+    }
+
+    pub fn publish<T: serde::Serialize>(
+        &mut self,
+        message: OutgoingMessage<T>,
+    ) -> Result<(), Error> {
+        self.current().publish(message)
+    }
+
+    pub fn publish_publishable(
+        &mut self,
+        message: Box<dyn IntoPublishableMessage>,
+    ) -> Result<(), Error> {
+        self.current().publish_publishable(message)
+    }
+
+    pub fn publish_dump(&mut self, dump: PublishableMessage) -> Result<(), Error> {
+        self.current().publish_dump(dump)
+    }
+
+    pub fn subscribe<S: SubscriptionTopic>(
+        &mut self,
+        subscription: &S,
+        qos: QoS,
+        maybe_group: Option<&SharedGroup>,
+    ) -> Result<(), Error> {
+        let topic = Topic(subscription.subscription_topic(self.id(), self.address.version())?);
+        let mut state = self.state.write();
+        state.agent.subscribe(&topic, qos, maybe_group)?;
+
+        let group = maybe_group.cloned();
+
+        if !state
+            .subscriptions
+            .iter()
+            .any(|(t, _, g)| *t == topic && *g == group)
+        {
+            state.subscriptions.push((topic, qos, group));
+        }
+
+        Ok(())
+    }
+
+    pub fn unsubscribe<S: SubscriptionTopic>(
+        &mut self,
+        subscription: &S,
+        maybe_group: Option<&SharedGroup>,
+    ) -> Result<(), Error> {
+        let topic = Topic(subscription.subscription_topic(self.id(), self.address.version())?);
+        let mut state = self.state.write();
+        state.agent.unsubscribe(&topic, maybe_group)?;
+        state
+            .subscriptions
+            .retain(|(t, _, g)| !(*t == topic && g.as_ref() == maybe_group));
+        Ok(())
+    }
+
+    fn current(&self) -> Agent {
+        self.state.read().agent.clone()
+    }
+
+    /// Makes `agent` the active one and moves all subscriptions over to it.
+    ///
+    /// The previous agent is asked to unsubscribe. If its broker is down the requests stay queued
+    /// and get sent as soon as it reconnects so it stops receiving our share of the messages.
+    fn switch(&self, mut agent: Agent) {
+        let mut state = self.state.write();
+
+        for (topic, qos, group) in &state.subscriptions {
+            if let Err(err) = agent.subscribe(topic, *qos, group.as_ref()) {
+                error!(%err, topic = %topic.0, "Failed to resubscribe on failover");
+            }
+        }
+
+        let mut previous = std::mem::replace(&mut state.agent, agent);
+
+        for (topic, _qos, group) in &state.subscriptions {
+            if let Err(err) = previous.unsubscribe(topic, group.as_ref()) {
+                warn!(%err, topic = %topic.0, "Failed to unsubscribe from the abandoned broker");
+            }
+        }
+    }
+}
+
+/// Already resolved subscription topic.
+#[derive(Clone, Debug, PartialEq)]
+struct Topic(String);
+
+impl SubscriptionTopic for Topic {
+    fn subscription_topic<A>(&self, _agent_id: &A, _me_version: &str) -> Result<String, Error>
+    where
+        A: Addressable,
+    {
+        Ok(self.0.clone())
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+pub struct Broker {
+    pub name: String,
+    pub config: AgentConfig,
+}
+
+pub struct FailoverMetrics {
+    pub failover: IntCounter,
+    pub active_broker: IntGauge,
+}
+
+/// Connects to the first of `brokers` and moves on to the next one in order after
+/// `max_connection_errors` consecutive connection errors on the active broker. Without the limit
+/// it sticks to the first broker and relies on reconnection only.
+///
+/// Only notifications of the active broker reach the returned receiver.
+pub fn start(
+    agent_id: AgentId,
+    api_version: &str,
+    brokers: Vec<Broker>,
+    max_connection_errors: Option<u32>,
+    metrics: FailoverMetrics,
+) -> Result<(BrokerAgent, UnboundedReceiver<AgentNotification>)> {
+    let first = brokers.first().context("No MQTT brokers configured")?;
+
+    let (agent, rx) = AgentBuilder::new(agent_id.clone(), api_version)
+        .connection_mode(ConnectionMode::Service)
+        .start(&first.config)
+        .context("Failed to create an agent")?;
+
+    info!(broker = %first.name, "Connecting to MQTT broker");
+
+    let broker_agent = BrokerAgent::new(agent.clone());
+    let (notifications_tx, mut notifications_rx) = mpsc::unbounded_channel();
+    let (tx, out_rx) = mpsc::unbounded_channel();
+
+    tokio::spawn(forward(0, rx, notifications_tx.clone()));
+    metrics.active_broker.set(0);
+
+    let mut supervisor = Supervisor {
+        agent_id,
+        api_version: api_version.to_owned(),
+        agents: brokers.iter().map(|_| None).collect(),
+        brokers,
+        active: 0,
+        connection_errors: 0,
+        max_connection_errors,
+        agent: broker_agent.clone(),
+        notifications_tx,
+        metrics,
+    };
+
+    supervisor.agents[0] = Some(agent);
+
+    tokio::spawn(async move {
+        while let Some((idx, notification)) = notifications_rx.recv().await {
+            if idx != supervisor.active {
+                continue;
+            }
+
+            let is_connection_error = matches!(notification, AgentNotification::ConnectionError);
+
+            if tx.send(notification).is_err() {
+                break;
+            }
+
+            if is_connection_error {
+                supervisor.on_connection_error();
+            } else {
+                supervisor.connection_errors = 0;
+            }
+        }
+    });
+
+    Ok((broker_agent, out_rx))
+}
+
+async fn forward(
+    idx: usize,
+    mut rx: UnboundedReceiver<AgentNotification>,
+    tx: UnboundedSender<(usize, AgentNotification)>,
+) {
+    while let Some(notification) = rx.recv().await {
+        if tx.send((idx, notification)).is_err() {
+            break;
+        }
+    }
+}
+
+struct Supervisor {
+    agent_id: AgentId,
+    api_version: String,
+    brokers: Vec<Broker>,
+    agents: Vec<Option<Agent>>,
+    active: usize,
+    connection_errors: u32,
+    max_connection_errors: Option<u32>,
+    agent: BrokerAgent,
+    notifications_tx: UnboundedSender<(usize, AgentNotification)>,
+    metrics: FailoverMetrics,
+}
+
+impl Supervisor {
+    fn on_connection_error(&mut self) {
+        self.connection_errors += 1;
+
+        match self.max_connection_errors {
+            Some(max) if self.connection_errors >= max && self.brokers.len() > 1 => self.failover(),
+            _ => (),
+        }
+    }
+
+    fn failover(&mut self) {
+        let from = self.active;
+
+        for step in 1..self.brokers.len() {
+            let idx = (from + step) % self.brokers.len();
+
+            let agent = match self.agent_for(idx) {
+                Ok(agent) => agent,
+                Err(err) => {
+                    error!(?err, broker = %self.brokers[idx].name, "Failed to start MQTT agent");
+                    continue;
+                }
+            };
+
+            self.agent.switch(agent);
+            self.active = idx;
+            self.connection_errors = 0;
+            self.metrics.failover.inc();
+            self.metrics.active_broker.set(idx as i64);
+
+            error!(
+                from = %self.brokers[from].name,
+                to = %self.brokers[idx].name,
+                connection_errors = self.max_connection_errors,
+                "MQTT broker failover"
+            );
+
+            return;
+        }
+    }
+
+    /// Agents get started on first use and then kept around so that falling back to a broker
+    /// doesn't leave its previous connection loop behind.
+    fn agent_for(&mut self, idx: usize) -> Result<Agent> {
+        if let Some(agent) = &self.agents[idx] {
+            return Ok(agent.clone());
+        }
+
+        let (agent, rx) = AgentBuilder::new(self.agent_id.clone(), &self.api_version)
+            .connection_mode(ConnectionMode::Service)
+            .start(&self.brokers[idx].config)?;
+
+        tokio::spawn(forward(idx, rx, self.notifications_tx.clone()));
+        self.agents[idx] = Some(agent.clone());
+        Ok(agent)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::json;
+    use svc_agent::{AccountId, Subscription};
+
+    use super::*;
+
+    fn broker(name: &str, port: u16) -> Broker {
+        let config = serde_json::from_value(json!({
+            "uri": format!("mqtt://127.0.0.1:{}", port),
+            "reconnect_interval": 0,
+        }))
+        .expect("Failed to build agent config");
+
+        Broker {
+            name: name.to_owned(),
+            config,
+        }
+    }
+
+    fn metrics() -> FailoverMetrics {
+        FailoverMetrics {
+            failover: IntCounter::new("failover", "failover").unwrap(),
+            active_broker: IntGauge::new("active_broker", "active_broker").unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    async fn fails_over_to_next_broker() {
+        let agent_id = AgentId::new("test", AccountId::new("conference", "svc.example.org"));
+        let metrics = metrics();
+        let failover = metrics.failover.clone();
+        let active_broker = metrics.active_broker.clone();
+
+        let (mut agent, mut rx) = start(
+            agent_id,
+            "v1",
+            vec![broker("primary", 1), broker("secondary", 2)],
+            Some(2),
+            metrics,
+        )
+        .expect("Failed to start agent");
+
+        agent
+            .subscribe(
+                &Subscription::multicast_requests(Some("v1")),
+                QoS::AtMostOnce,
+                None,
+            )
+            .expect("Failed to subscribe");
+
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while failover.get() == 0 {
+                rx.recv().await.expect("Notifications channel closed");
+            }
+        })
+        .await
+        .expect("No failover happened");
+
+        assert_eq!(active_broker.get(), 1);
+        assert_eq!(agent.state.read().subscriptions.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn sticks_to_single_broker() {
+        let agent_id = AgentId::new("test", AccountId::new("conference", "svc.example.org"));
+        let metrics = metrics();
+        let failover = metrics.failover.clone();
+
+        let (_agent, mut rx) = start(agent_id, "v1", vec![broker("primary", 1)], Some(1), metrics)
+            .expect("Failed to start agent");
+
+        for _ in 0..3 {
+            let notification = rx.recv().await.expect("Notifications channel closed");
+            assert!(matches!(notification, AgentNotification::ConnectionError));
+        }
+
+        assert_eq!(failover.get(), 0);
+    }
+}
@@ -0,0 +1,137 @@
+use serde_json::Value as JsonValue;
+use svc_agent::{mqtt::IncomingRequestProperties, Authenticable};
+
+use crate::config::CompatConfig;
+
+/// Payload shapes the clients speak. Those still on a legacy shape get their requests mapped
+/// to the current types before the handler and the responses mapped back after it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shape {
+    V1,
+    Current,
+}
+
+impl Shape {
+    pub fn detect(config: &CompatConfig, reqp: &IncomingRequestProperties) -> Self {
+        if config
+            .v1_audiences
+            .contains(reqp.as_account_id().audience())
+        {
+            Self::V1
+        } else {
+            Self::Current
+        }
+    }
+
+    pub fn adapt_response(self, method: &str, payload: &mut JsonValue) {
+        if self == Self::V1 {
+            v1::downgrade_response(method, payload);
+        }
+    }
+}
+
+pub mod v1 {
+    use serde::de::DeserializeOwned;
+    use serde_json::Value as JsonValue;
+
+    /// Methods responding with a room object.
+    const ROOM_METHODS: &[&str] = &["room.create", "room.read", "room.update", "room.close"];
+
+    pub fn parse_request<T: DeserializeOwned>(
+        method: &str,
+        payload: &str,
+    ) -> serde_json::Result<T> {
+        let mut payload = serde_json::from_str(payload)?;
+        upgrade_request(method, &mut payload);
+        serde_json::from_value(payload)
+    }
+
+    fn upgrade_request(method: &str, payload: &mut JsonValue) {
+        if method != "room.create" {
+            return;
+        }
+
+        let object = match payload.as_object_mut() {
+            Some(object) => object,
+            None => return,
+        };
+
+        // v1 rooms had `backend` instead of `rtc_sharing_policy`.
+        if let Some(backend) = object.remove("backend") {
+            let rtc_sharing_policy = match backend.as_str() {
+                Some("janus") => "shared",
+                _ => "none",
+            };
+
+            object
+                .entry("rtc_sharing_policy")
+                .or_insert_with(|| rtc_sharing_policy.into());
+        }
+    }
+
+    pub(super) fn downgrade_response(method: &str, payload: &mut JsonValue) {
+        if !ROOM_METHODS.contains(&method) {
+            return;
+        }
+
+        let object = match payload.as_object_mut() {
+            Some(object) => object,
+            None => return,
+        };
+
+        if let Some(rtc_sharing_policy) = object.remove("rtc_sharing_policy") {
+            let backend = match rtc_sharing_policy.as_str() {
+                Some("shared") => "janus",
+                _ => "none",
+            };
+
+            object.insert("backend".to_owned(), backend.into());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn upgrade_v1_room_create() {
+        let payload = r#"{"audience": "example.org", "backend": "janus"}"#;
+
+        let payload: JsonValue =
+            v1::parse_request("room.create", payload).expect("Failed to parse request");
+
+        assert_eq!(
+            payload,
+            json!({"audience": "example.org", "rtc_sharing_policy": "shared"})
+        );
+
+        // Current shape wins over the legacy one.
+        let payload = r#"{"backend": "none", "rtc_sharing_policy": "owned"}"#;
+
+        let payload: JsonValue =
+            v1::parse_request("room.create", payload).expect("Failed to parse request");
+
+        assert_eq!(payload, json!({"rtc_sharing_policy": "owned"}));
+    }
+
+    #[test]
+    fn downgrade_v1_room() {
+        let room = json!({"id": "room", "backend": "none", "rtc_sharing_policy": "shared"});
+
+        let mut payload = room.clone();
+        Shape::V1.adapt_response("room.read", &mut payload);
+        assert_eq!(payload, json!({"id": "room", "backend": "janus"}));
+
+        // Other methods and shapes are left intact.
+        let mut payload = room.clone();
+        Shape::V1.adapt_response("rtc.read", &mut payload);
+        assert_eq!(payload, room);
+
+        let mut payload = room.clone();
+        Shape::Current.adapt_response("room.read", &mut payload);
+        assert_eq!(payload, room);
+    }
+}
@@ -0,0 +1,255 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context as AnyhowContext};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use svc_agent::{mqtt::ResponseStatus, Addressable};
+use svc_events::EventId;
+use svc_nats_client::{consumer::HandleMessageFailure, Message};
+use tracing::{error, info};
+
+use crate::{
+    app::{
+        context::{AppContext, Context, GlobalContext},
+        endpoint::{agent_writer_config, RequestHandler, RequestResult},
+        error::{Error as AppError, ErrorExt, ErrorKind as AppErrorKind},
+        message_handler::nats,
+        service_utils::{RequestParams, Response},
+    },
+    db::{self, room::FindQueryable},
+};
+
+const SUBJECT_PREFIX: &str = "conference";
+const ENTITY_TYPE: &str = "config_sync";
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Internal event sent to other conference instances when a room's configs change so that
+/// those owning the room's backend could push the new state to it.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SyncEvent {
+    WriterConfig { room_id: db::room::Id },
+}
+
+impl SyncEvent {
+    fn operation(&self) -> &'static str {
+        match self {
+            Self::WriterConfig { .. } => "writer_config",
+        }
+    }
+}
+
+/// Publishes the event. Failures are only logged since the change itself is already persisted.
+pub async fn publish<C: GlobalContext + ?Sized>(
+    ctx: &C,
+    room: &db::room::Object,
+    event: SyncEvent,
+) {
+    if let Err(err) = try_publish(ctx, room, &event).await {
+        error!(?err, ?event, "Failed to publish config sync event");
+        err.notify_sentry();
+    }
+}
+
+async fn try_publish<C: GlobalContext + ?Sized>(
+    ctx: &C,
+    room: &db::room::Object,
+    event: &SyncEvent,
+) -> Result<(), AppError> {
+    let nats_client = match ctx.nats_client() {
+        Some(nats_client) => nats_client,
+        None => return Ok(()),
+    };
+
+    let payload = serde_json::to_vec(event)
+        .context("invalid payload")
+        .error(AppErrorKind::InvalidPayload)?;
+
+    let subject = svc_nats_client::Subject::new(
+        SUBJECT_PREFIX.to_string(),
+        room.classroom_id(),
+        ENTITY_TYPE.to_string(),
+    );
+
+    let event_id: EventId = (
+        ENTITY_TYPE.to_string(),
+        event.operation().to_string(),
+        chrono::Utc::now().timestamp_nanos(),
+    )
+        .into();
+
+    let event =
+        svc_nats_client::event::Builder::new(subject, payload, event_id, ctx.agent_id().to_owned())
+            .internal(true)
+            .build();
+
+    nats_client
+        .publish(&event)
+        .await
+        .error(AppErrorKind::NatsPublishFailed)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+pub async fn handle_message(
+    ctx: AppContext,
+    message: Arc<Message>,
+) -> Result<(), HandleMessageFailure<anyhow::Error>> {
+    nats::handle::<SyncHandler, _>(&mut ctx.start_message(), &message)
+        .await
+        .map(|_| ())
+}
+
+struct SyncHandler;
+
+#[async_trait]
+impl RequestHandler for SyncHandler {
+    type Payload = SyncEvent;
+    const ERROR_TITLE: &'static str = "Failed to sync config";
+    const SERVES_WHILE_DRAINING: bool = true;
+
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        // The sending instance has already applied the change.
+        if reqp.as_agent_id() != context.agent_id() {
+            match payload {
+                SyncEvent::WriterConfig { room_id } => sync_writer_config(context, room_id).await?,
+            }
+        }
+
+        Ok(Response::new(
+            ResponseStatus::NO_CONTENT,
+            json!({}),
+            context.start_timestamp(),
+            None,
+        ))
+    }
+}
+
+async fn sync_writer_config<C: GlobalContext + ?Sized>(
+    ctx: &C,
+    room_id: db::room::Id,
+) -> Result<(), AppError> {
+    let mut conn = ctx.get_conn().await?;
+
+    let room = db::room::FindQuery::new(room_id)
+        .execute(&mut conn)
+        .await
+        .context("failed to find room")
+        .error(AppErrorKind::DbQueryFailed)?
+        .ok_or_else(|| anyhow!("room {} not found", room_id))
+        .error(AppErrorKind::RoomNotFound)?;
+
+    let backend = match room.backend_id() {
+        Some(backend_id) => db::janus_backend::FindQuery::new(backend_id)
+            .execute(&mut conn)
+            .await
+            .context("failed to find backend")
+            .error(AppErrorKind::DbQueryFailed)?,
+        None => None,
+    };
+
+    let backend = match backend {
+        Some(backend) if ctx.janus_clients().owns(&backend) => backend,
+        _ => return Ok(()),
+    };
+
+    let configs = db::rtc_writer_config::ListWithRtcQuery::new(room_id)
+        .execute(&mut conn)
+        .await
+        .context("failed to list writer configs")
+        .error(AppErrorKind::DbQueryFailed)?;
+
+    agent_writer_config::update_backend(ctx, &backend, &configs).await?;
+
+    info!(%room_id, backend_id = %backend.id(), "Synced writer config");
+    Ok(())
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        app::message_handler::handle_request,
+        backend::janus::client::{HandleId, SessionId},
+        test_helpers::{db::TestDb, prelude::*},
+    };
+
+    async fn handle_sync_event(
+        context: &mut TestContext,
+        sender: &TestAgent,
+        event: SyncEvent,
+    ) -> Result<Response, HandleMessageFailure<anyhow::Error>> {
+        handle_request::<nats::Nats, SyncHandler, _>(context, sender.agent_id(), event).await
+    }
+
+    #[test]
+    fn serialize_event() {
+        let room_id = db::room::Id::random();
+        let event = SyncEvent::WriterConfig { room_id };
+
+        let json = serde_json::to_value(&event).expect("Failed to serialize event");
+        assert_eq!(json["type"], "writer_config");
+        assert_eq!(json["room_id"], room_id.to_string());
+
+        let parsed: SyncEvent = serde_json::from_value(json).expect("Failed to parse event");
+        assert_eq!(parsed, event);
+    }
+
+    #[sqlx::test]
+    async fn skip_backend_of_another_group(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let mut conn = db.get_conn().await;
+
+        let backend = shared_helpers::insert_janus_backend(
+            &mut conn,
+            "test",
+            SessionId::random(),
+            HandleId::random(),
+        )
+        .await;
+        let room = shared_helpers::insert_room_with_backend_id(&mut conn, backend.id()).await;
+
+        let mut context = TestContext::new(db, TestAuthz::new()).await;
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+        context.with_grouped_janus("other", tx);
+
+        let sender = TestAgent::new("beta", "conference", SVC_AUDIENCE);
+        handle_sync_event(
+            &mut context,
+            &sender,
+            SyncEvent::WriterConfig { room_id: room.id() },
+        )
+        .await
+        .expect("Failed to handle sync event");
+
+        assert_eq!(context.janus_clients().clients_count(), 0);
+    }
+
+    #[sqlx::test]
+    async fn missing_room(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let mut context = TestContext::new(db, TestAuthz::new()).await;
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+        context.with_janus(tx);
+
+        let sender = TestAgent::new("beta", "conference", SVC_AUDIENCE);
+        let result = handle_sync_event(
+            &mut context,
+            &sender,
+            SyncEvent::WriterConfig {
+                room_id: db::room::Id::random(),
+            },
+        )
+        .await;
+
+        assert!(matches!(result, Err(HandleMessageFailure::Permanent(_))));
+    }
+}
@@ -18,7 +18,10 @@ use crate::{
     config::Config,
 };
 
-use super::metrics::Metrics;
+use super::{
+    active_speaker::ActiveSpeakers, audio_level::AudioLevels, metrics::Metrics,
+    pseudonym::Pseudonyms, room_authz::RoomAuthzCache, tenant_usage::MeteredConnection,
+};
 
 ///////////////////////////////////////////////////////////////////////////////
 
@@ -36,11 +39,18 @@ pub trait GlobalContext {
     fn conference_client(&self) -> &ConferenceHttpClient;
     fn mqtt_client(&self) -> &Mutex<dyn MqttClient>;
     fn nats_client(&self) -> Option<&dyn NatsClient>;
-    fn get_conn(&self) -> BoxFuture<Result<sqlx::pool::PoolConnection<sqlx::Postgres>, AppError>> {
+    fn active_speakers(&self) -> &ActiveSpeakers;
+    fn audio_levels(&self) -> &AudioLevels;
+    fn room_authz(&self) -> &RoomAuthzCache;
+    fn pseudonyms(&self) -> Pseudonyms {
+        Pseudonyms::new(&self.config().pseudonymize)
+    }
+    fn get_conn(&self) -> BoxFuture<Result<MeteredConnection, AppError>> {
         let db = self.db().clone();
         async move {
             db.acquire()
                 .await
+                .map(MeteredConnection::new)
                 .context("failed to acquire DB connection")
                 .error(AppErrorKind::DbConnAcquisitionFailed)
         }
@@ -92,6 +102,22 @@ impl GlobalContext for Arc<dyn GlobalContext> {
     fn nats_client(&self) -> Option<&dyn NatsClient> {
         self.as_ref().nats_client()
     }
+
+    fn active_speakers(&self) -> &ActiveSpeakers {
+        self.as_ref().active_speakers()
+    }
+
+    fn audio_levels(&self) -> &AudioLevels {
+        self.as_ref().audio_levels()
+    }
+
+    fn room_authz(&self) -> &RoomAuthzCache {
+        self.as_ref().room_authz()
+    }
+
+    fn pseudonyms(&self) -> Pseudonyms {
+        self.as_ref().pseudonyms()
+    }
 }
 
 pub trait MessageContext {
@@ -113,6 +139,10 @@ pub struct AppContext {
     conference_client: ConferenceHttpClient,
     mqtt_client: Arc<Mutex<dyn MqttClient>>,
     nats_client: Option<Arc<dyn NatsClient>>,
+    pseudonyms: Pseudonyms,
+    active_speakers: ActiveSpeakers,
+    audio_levels: AudioLevels,
+    room_authz: RoomAuthzCache,
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -131,6 +161,10 @@ impl AppContext {
         M: MqttClient + 'static,
     {
         let agent_id = AgentId::new(&config.agent_label, config.id.to_owned());
+        let pseudonyms = Pseudonyms::new(&config.pseudonymize);
+        let active_speakers = ActiveSpeakers::new(config.active_speaker.clone());
+        let audio_levels = AudioLevels::new(config.audio_level.clone());
+        let room_authz = RoomAuthzCache::new(config.room_authz_cache.clone());
 
         Self {
             config: Arc::new(config),
@@ -143,6 +177,10 @@ impl AppContext {
             conference_client,
             mqtt_client: Arc::new(Mutex::new(mqtt_client)),
             nats_client: None,
+            pseudonyms,
+            active_speakers,
+            audio_levels,
+            room_authz,
             db,
         }
     }
@@ -210,6 +248,22 @@ impl GlobalContext for AppContext {
     fn nats_client(&self) -> Option<&dyn NatsClient> {
         self.nats_client.as_deref()
     }
+
+    fn active_speakers(&self) -> &ActiveSpeakers {
+        &self.active_speakers
+    }
+
+    fn audio_levels(&self) -> &AudioLevels {
+        &self.audio_levels
+    }
+
+    fn room_authz(&self) -> &RoomAuthzCache {
+        &self.room_authz
+    }
+
+    fn pseudonyms(&self) -> Pseudonyms {
+        self.pseudonyms.clone()
+    }
 }
 
 ///////////////////////////////////////////////////////////////////////////////
@@ -272,6 +326,22 @@ impl<'a, C: GlobalContext> GlobalContext for AppMessageContext<'a, C> {
     fn nats_client(&self) -> Option<&dyn NatsClient> {
         self.global_context.nats_client()
     }
+
+    fn active_speakers(&self) -> &ActiveSpeakers {
+        self.global_context.active_speakers()
+    }
+
+    fn audio_levels(&self) -> &AudioLevels {
+        self.global_context.audio_levels()
+    }
+
+    fn room_authz(&self) -> &RoomAuthzCache {
+        self.global_context.room_authz()
+    }
+
+    fn pseudonyms(&self) -> Pseudonyms {
+        self.global_context.pseudonyms()
+    }
 }
 
 impl<'a, C: GlobalContext> MessageContext for AppMessageContext<'a, C> {
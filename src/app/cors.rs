@@ -0,0 +1,128 @@
+use svc_agent::AccountId;
+
+use crate::{
+    app::{context::GlobalContext, error::Error as AppError},
+    config::CorsConfigMap,
+    db::{self, room::FindQueryable},
+};
+
+/// Outcome of checking the origin of a request.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Verdict {
+    /// The audience has no policy so the defaults apply.
+    Unrestricted,
+    /// The origin is explicitly allowed and should be echoed back.
+    Allowed,
+    /// The audience has a policy which doesn't list the origin.
+    Rejected { audience: String },
+}
+
+/// Checks the origin of a browser request against the policy of the audience.
+pub fn check(config: &CorsConfigMap, audience: &str, origin: &str) -> Verdict {
+    match config.get(audience) {
+        None => Verdict::Unrestricted,
+        Some(policy) if policy.allows(origin) => Verdict::Allowed,
+        Some(_) => Verdict::Rejected {
+            audience: audience.to_owned(),
+        },
+    }
+}
+
+/// Resolves the audience of an HTTP request: the caller's one if it's authenticated, otherwise
+/// the one of the room addressed by `/rooms/:id/...` or `/rtcs/:id/...`.
+pub async fn resolve_audience<C: GlobalContext + ?Sized>(
+    context: &C,
+    caller: Option<&AccountId>,
+    path: &str,
+) -> Result<Option<String>, AppError> {
+    if let Some(account_id) = caller {
+        return Ok(Some(account_id.audience().to_owned()));
+    }
+
+    let target = match path_target(path) {
+        Some(target) => target,
+        None => return Ok(None),
+    };
+
+    let mut conn = context.get_conn().await?;
+
+    let room = match target {
+        PathTarget::Room(id) => db::room::FindQuery::new(id).execute(&mut conn).await,
+        PathTarget::Rtc(id) => db::room::FindByRtcIdQuery::new(id).execute(&mut conn).await,
+    }?;
+
+    Ok(room.map(|room| room.audience().to_owned()))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum PathTarget {
+    Room(db::room::Id),
+    Rtc(db::rtc::Id),
+}
+
+fn path_target(path: &str) -> Option<PathTarget> {
+    let mut segments = path.trim_start_matches("/api/v1").split('/').skip(1);
+    let kind = segments.next()?;
+    let id = segments.next()?.parse().ok()?;
+
+    match kind {
+        "rooms" => Some(PathTarget::Room(id)),
+        "rtcs" => Some(PathTarget::Rtc(id)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::CorsConfig;
+
+    use super::*;
+
+    #[test]
+    fn check_origins() {
+        let mut config = CorsConfigMap::new();
+
+        config.insert(
+            "private.example.org".to_owned(),
+            CorsConfig {
+                allowed_origins: ["https://app.example.org".to_owned()].into(),
+            },
+        );
+
+        assert_eq!(
+            check(&config, "private.example.org", "https://app.example.org"),
+            Verdict::Allowed
+        );
+
+        assert_eq!(
+            check(&config, "private.example.org", "https://evil.example.com"),
+            Verdict::Rejected {
+                audience: "private.example.org".to_owned()
+            }
+        );
+
+        assert_eq!(
+            check(&config, "public.example.org", "https://evil.example.com"),
+            Verdict::Unrestricted
+        );
+    }
+
+    #[test]
+    fn path_targets() {
+        let id = db::room::Id::random();
+
+        assert_eq!(
+            path_target(&format!("/rooms/{id}/rtcs")),
+            Some(PathTarget::Room(id))
+        );
+
+        assert_eq!(
+            path_target(&format!("/api/v1/rtcs/{id}")),
+            Some(PathTarget::Rtc(id))
+        );
+
+        assert_eq!(path_target(&format!("/polls/{id}")), None);
+        assert_eq!(path_target("/rooms"), None);
+        assert_eq!(path_target("/streams/signal"), None);
+    }
+}
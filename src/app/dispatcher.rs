@@ -0,0 +1,365 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use enum_iterator::IntoEnumIterator;
+use prometheus::IntGauge;
+use svc_agent::mqtt::{Address, IntoPublishableMessage, PublishableMessage};
+use tokio::{sync::Notify, task::JoinHandle};
+use tracing::{error, warn};
+
+use crate::app::{
+    broker::BrokerAgent,
+    error::{Error as AppError, ErrorKind as AppErrorKind},
+    long_poll::EventJournal,
+    ws::Sockets,
+};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Maximum number of messages queued for a single destination within a priority class.
+/// The oldest message is dropped when a new one exceeds it.
+const MAX_DESTINATION_QUEUE_LEN: usize = 1_000;
+
+/// Priority class of an outgoing message. Variants are ordered from the most to the least
+/// latency-sensitive one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, IntoEnumIterator)]
+pub enum Priority {
+    /// Responses to `rtc.connect` and `rtc_signal.*` requests.
+    Signaling,
+    /// Responses to regular requests and to Janus transactions.
+    Response,
+    /// Broadcast events and requests.
+    Notification,
+    /// Responses to list and read requests which may be large and are not time critical.
+    Bulk,
+}
+
+impl Priority {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Signaling => "signaling",
+            Self::Response => "response",
+            Self::Notification => "notification",
+            Self::Bulk => "bulk",
+        }
+    }
+
+    /// Chooses the class for a message produced while handling a request with `method`.
+    /// Responses without a method don't come from a request handler so they keep the
+    /// regular response class.
+    pub fn classify(method: Option<&str>, message: &PublishableMessage) -> Self {
+        match (message, method) {
+            (PublishableMessage::Response(_), None) => Self::Response,
+            (PublishableMessage::Response(_), Some(method)) => Self::for_method(method),
+            (PublishableMessage::Event(_), _) | (PublishableMessage::Request(_), _) => {
+                Self::Notification
+            }
+        }
+    }
+
+    fn for_method(method: &str) -> Self {
+        if method == "rtc.connect" || method.starts_with("rtc_signal.") {
+            return Self::Signaling;
+        }
+
+        if method.ends_with(".list") || method.ends_with(".read") {
+            return Self::Bulk;
+        }
+
+        Self::Response
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Messages of a single priority class keyed by their destination topic which is the agent's
+/// inbox for responses and the room for events. Destinations are served round robin so that
+/// a burst to one agent doesn't hold back the others.
+#[derive(Default)]
+struct ClassQueue {
+    destinations: HashMap<String, VecDeque<PublishableMessage>>,
+    order: VecDeque<String>,
+}
+
+impl ClassQueue {
+    /// Returns `false` if the destination's queue was full and its oldest message was dropped.
+    fn push(&mut self, message: PublishableMessage) -> bool {
+        let destination = message.topic().to_owned();
+
+        let queue = match self.destinations.get_mut(&destination) {
+            Some(queue) => queue,
+            None => {
+                self.order.push_back(destination.clone());
+                self.destinations.entry(destination).or_default()
+            }
+        };
+
+        let overflown = queue.len() >= MAX_DESTINATION_QUEUE_LEN;
+
+        if overflown {
+            queue.pop_front();
+        }
+
+        queue.push_back(message);
+        !overflown
+    }
+
+    fn pop(&mut self) -> Option<PublishableMessage> {
+        let destination = self.order.pop_front()?;
+        let queue = self.destinations.get_mut(&destination)?;
+        let message = queue.pop_front();
+
+        if queue.is_empty() {
+            self.destinations.remove(&destination);
+        } else {
+            self.order.push_back(destination);
+        }
+
+        message
+    }
+}
+
+struct Queues {
+    queues: HashMap<Priority, ClassQueue>,
+    depth: HashMap<Priority, IntGauge>,
+}
+
+impl Queues {
+    fn new(depth: HashMap<Priority, IntGauge>) -> Self {
+        Self {
+            queues: Priority::into_enum_iter()
+                .map(|p| (p, ClassQueue::default()))
+                .collect(),
+            depth,
+        }
+    }
+
+    fn push(&mut self, priority: Priority, message: PublishableMessage) {
+        let topic = message.topic().to_owned();
+
+        let pushed = self
+            .queues
+            .get_mut(&priority)
+            .map(|q| q.push(message))
+            .unwrap_or(false);
+
+        if !pushed {
+            warn!(
+                %topic,
+                priority = priority.as_str(),
+                "Outgoing queue is full, dropped the oldest message"
+            );
+            return;
+        }
+
+        if let Some(gauge) = self.depth.get(&priority) {
+            gauge.inc();
+        }
+    }
+
+    fn pop(&mut self) -> Option<PublishableMessage> {
+        for priority in Priority::into_enum_iter() {
+            let message = self.queues.get_mut(&priority).and_then(|q| q.pop());
+
+            if let Some(message) = message {
+                if let Some(gauge) = self.depth.get(&priority) {
+                    gauge.dec();
+                }
+
+                return Some(message);
+            }
+        }
+
+        None
+    }
+}
+
+struct Inner {
+    address: Address,
+    queues: Mutex<Queues>,
+    notify: Notify,
+    sockets: Sockets,
+    journal: EventJournal,
+}
+
+/// Publishes outgoing MQTT messages in the order of their priority class so that signaling
+/// responses don't wait behind a burst of room notifications or large list responses.
+/// Within a class each destination has its own bounded queue and they are served in turns.
+/// Events are pushed to the WebSocket subscribers and recorded for long polling right away.
+#[derive(Clone)]
+pub struct Dispatcher {
+    inner: Arc<Inner>,
+}
+
+impl Dispatcher {
+    pub fn start(
+        agent: BrokerAgent,
+        depth: HashMap<Priority, IntGauge>,
+        sockets: Sockets,
+        journal: EventJournal,
+    ) -> (Self, JoinHandle<()>) {
+        let inner = Arc::new(Inner {
+            address: agent.address().to_owned(),
+            queues: Mutex::new(Queues::new(depth)),
+            notify: Notify::new(),
+            sockets,
+            journal,
+        });
+
+        let handle = tokio::spawn(run(agent, inner.clone()));
+        (Self { inner }, handle)
+    }
+
+    pub fn dispatch(
+        &self,
+        method: Option<&str>,
+        message: Box<dyn IntoPublishableMessage + Send + Sync + 'static>,
+    ) {
+        let dump = match message.into_dump(&self.inner.address) {
+            Ok(dump) => dump,
+            Err(err) => {
+                error!(?err, "Failed to dump message");
+                AppError::new(AppErrorKind::MqttPublishFailed, err).notify_sentry();
+                return;
+            }
+        };
+
+        self.inner.sockets.deliver(&dump);
+        self.inner.journal.record(&dump);
+        let priority = Priority::classify(method, &dump);
+
+        self.inner
+            .queues
+            .lock()
+            .expect("Dispatcher queues mutex poisoned")
+            .push(priority, dump);
+
+        self.inner.notify.notify_one();
+    }
+}
+
+async fn run(mut agent: BrokerAgent, inner: Arc<Inner>) {
+    loop {
+        loop {
+            let message = inner
+                .queues
+                .lock()
+                .expect("Dispatcher queues mutex poisoned")
+                .pop();
+
+            match message {
+                Some(message) => {
+                    if let Err(err) = agent.publish_dump(message) {
+                        error!(?err, "Failed to publish message");
+                        AppError::new(AppErrorKind::MqttPublishFailed, err).notify_sentry();
+                    }
+                }
+                None => break,
+            }
+        }
+
+        inner.notify.notified().await;
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use svc_agent::{
+        mqtt::{OutgoingEvent, OutgoingEventProperties, ShortTermTimingProperties},
+        AccountId, AgentId,
+    };
+
+    fn event(path: &str) -> PublishableMessage {
+        let address = Address::new(
+            AgentId::new(
+                "instance01",
+                AccountId::new("conference", "svc.example.org"),
+            ),
+            "v1",
+        );
+
+        let timing = ShortTermTimingProperties::new(chrono::Utc::now());
+        let props = OutgoingEventProperties::new("room.update", timing);
+        let event = OutgoingEvent::broadcast(serde_json::json!({}), props, path);
+
+        Box::new(event)
+            .into_dump(&address)
+            .expect("Failed to dump event")
+    }
+
+    #[test]
+    fn classify_by_method() {
+        assert_eq!(
+            Priority::for_method("rtc_signal.create"),
+            Priority::Signaling
+        );
+        assert_eq!(Priority::for_method("rtc.connect"), Priority::Signaling);
+        assert_eq!(Priority::for_method("room.enter"), Priority::Response);
+        assert_eq!(Priority::for_method("agent.list"), Priority::Bulk);
+        assert_eq!(
+            Priority::for_method("agent_reader_config.read"),
+            Priority::Bulk
+        );
+
+        let evt = event("rooms/1/events");
+        assert_eq!(
+            Priority::classify(Some("agent.list"), &evt),
+            Priority::Notification
+        );
+    }
+
+    #[test]
+    fn pop_in_priority_order() {
+        let depth = Priority::into_enum_iter()
+            .map(|p| (p, IntGauge::new(p.as_str(), p.as_str()).unwrap()))
+            .collect::<HashMap<_, _>>();
+
+        let mut queues = Queues::new(depth.clone());
+        queues.push(Priority::Bulk, event("bulk"));
+        queues.push(Priority::Notification, event("notification"));
+        queues.push(Priority::Response, event("response"));
+        queues.push(Priority::Signaling, event("signaling"));
+
+        assert_eq!(depth[&Priority::Bulk].get(), 1);
+
+        let order = std::iter::from_fn(|| queues.pop())
+            .map(|m| m.topic().to_owned())
+            .collect::<Vec<_>>();
+
+        assert_eq!(order, vec!["signaling", "response", "notification", "bulk"]);
+        assert!(depth.values().all(|g| g.get() == 0));
+    }
+
+    #[test]
+    fn serve_destinations_in_turns() {
+        let mut queue = ClassQueue::default();
+        queue.push(event("a"));
+        queue.push(event("a"));
+        queue.push(event("a"));
+        queue.push(event("b"));
+
+        let order = std::iter::from_fn(|| queue.pop())
+            .map(|m| m.topic().to_owned())
+            .collect::<Vec<_>>();
+
+        assert_eq!(order, vec!["a", "b", "a", "a"]);
+    }
+
+    #[test]
+    fn drop_oldest_on_overflow() {
+        let mut queue = ClassQueue::default();
+
+        for _ in 0..MAX_DESTINATION_QUEUE_LEN {
+            assert!(queue.push(event("a")));
+        }
+
+        assert!(!queue.push(event("a")));
+        assert!(queue.push(event("b")));
+        assert_eq!(queue.destinations["a"].len(), MAX_DESTINATION_QUEUE_LEN);
+    }
+}
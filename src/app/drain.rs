@@ -0,0 +1,167 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use anyhow::anyhow;
+use prometheus::{IntGauge, IntGaugeVec, Opts, Registry};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app::{
+        context::GlobalContext,
+        error::{Error as AppError, ErrorKind as AppErrorKind},
+    },
+    outbox,
+};
+
+/// Transports requests are counted by, see `message_handler::Transport::NAME`.
+const TRANSPORTS: &[&str] = &["mqtt", "http", "ws", "grpc", "nats"];
+
+/// Takes the instance out of rotation before a deploy by refusing new requests.
+#[derive(Clone)]
+pub struct Drain {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    draining: AtomicBool,
+    draining_gauge: IntGauge,
+    in_flight: IntGaugeVec,
+}
+
+impl Drain {
+    pub fn new(registry: &Registry) -> anyhow::Result<Self> {
+        let draining_gauge =
+            IntGauge::new("draining", "Whether the instance refuses new requests")?;
+        let in_flight = IntGaugeVec::new(
+            Opts::new("in_flight_requests", "Requests being handled by transport"),
+            &["transport"],
+        )?;
+
+        registry.register(Box::new(draining_gauge.clone()))?;
+        registry.register(Box::new(in_flight.clone()))?;
+
+        Ok(Self {
+            inner: Arc::new(Inner {
+                draining: AtomicBool::new(false),
+                draining_gauge,
+                in_flight,
+            }),
+        })
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.inner.draining.load(Ordering::Relaxed)
+    }
+
+    /// Starts refusing new requests or, when `false`, resumes taking them.
+    pub fn set_draining(&self, draining: bool) {
+        self.inner.draining.store(draining, Ordering::Relaxed);
+        self.inner.draining_gauge.set(draining as i64);
+    }
+
+    /// Counts the request in flight until the returned guard is dropped. While draining the
+    /// request is refused unless `serves_while_draining` is set.
+    pub fn admit(
+        &self,
+        transport: &'static str,
+        serves_while_draining: bool,
+    ) -> Result<InFlight, AppError> {
+        if self.is_draining() && !serves_while_draining {
+            let err = anyhow!("The instance is draining, retry the request");
+            return Err(AppError::new(AppErrorKind::ServiceDraining, err));
+        }
+
+        let gauge = self.inner.in_flight.with_label_values(&[transport]);
+        gauge.inc();
+        Ok(InFlight(gauge))
+    }
+
+    fn in_flight(&self) -> BTreeMap<String, i64> {
+        TRANSPORTS
+            .iter()
+            .map(|transport| {
+                let count = self.inner.in_flight.with_label_values(&[transport]).get();
+                (transport.to_string(), count)
+            })
+            .collect()
+    }
+}
+
+/// Keeps a request counted in flight.
+pub struct InFlight(IntGauge);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.dec();
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Status {
+    pub draining: bool,
+    /// Requests being handled by transport.
+    pub in_flight: BTreeMap<String, i64>,
+    /// Janus transactions still waiting for a response.
+    pub janus_transactions: u64,
+    /// Outbox events due for delivery. The outbox is shared by the instances so any of them
+    /// may take these over.
+    pub outbox_backlog: i64,
+}
+
+impl Status {
+    /// Whether the instance may be killed without dropping any work.
+    pub fn is_idle(&self) -> bool {
+        self.draining
+            && self.in_flight.values().all(|count| *count == 0)
+            && self.janus_transactions == 0
+            && self.outbox_backlog == 0
+    }
+}
+
+pub async fn status<C: GlobalContext>(context: &C) -> Result<Status, AppError> {
+    let drain = &context.metrics().drain;
+
+    let outbox_backlog = {
+        let mut conn = context.get_conn().await?;
+        outbox::db::sqlx::BacklogQuery.execute(&mut conn).await?
+    };
+
+    Ok(Status {
+        draining: drain.is_draining(),
+        in_flight: drain.in_flight(),
+        janus_transactions: context.janus_clients().correlations().stats().in_flight,
+        outbox_backlog,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refuse_while_draining() {
+        let drain = Drain::new(&Registry::new()).expect("Failed to create drain");
+
+        let guard = drain.admit("mqtt", false).expect("Request refused");
+        assert_eq!(drain.in_flight()["mqtt"], 1);
+
+        drain.set_draining(true);
+
+        let err = drain.admit("http", false).err().expect("Request admitted");
+        assert_eq!(err.kind(), "service_draining");
+
+        // Draining can be stopped while draining.
+        let _toggle = drain.admit("http", true).expect("Request refused");
+
+        drop(guard);
+        assert_eq!(drain.in_flight()["mqtt"], 0);
+        assert_eq!(drain.in_flight()["http"], 1);
+    }
+}
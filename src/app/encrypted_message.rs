@@ -0,0 +1,171 @@
+use std::fmt;
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app::error::{Error as AppError, ErrorExt, ErrorKind as AppErrorKind},
+    config::EncryptedMessageConfig,
+};
+
+const MAX_ALG_LEN: usize = 64;
+const MAX_KEY_ID_LEN: usize = 256;
+/// Decoded nonce length bounds, wide enough for the common AEAD and box constructions.
+const NONCE_LEN: std::ops::RangeInclusive<usize> = 8..=64;
+
+/// End-to-end encrypted payload relayed as is. `Debug` leaves the ciphertext out.
+#[derive(Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Envelope {
+    /// Encryption scheme agreed by the clients, e.g. `x25519-aes-256-gcm`.
+    alg: String,
+    /// Identifies the key the recipient decrypts with.
+    key_id: String,
+    /// Base64 encoded.
+    nonce: String,
+    /// Base64 encoded.
+    ciphertext: String,
+}
+
+impl Envelope {
+    /// Checks the envelope structurally, nothing gets decrypted.
+    pub fn validate(&self, config: &EncryptedMessageConfig) -> Result<(), AppError> {
+        let is_alg_char = |c: char| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.');
+
+        if self.alg.is_empty() || self.alg.len() > MAX_ALG_LEN || !self.alg.chars().all(is_alg_char)
+        {
+            return invalid("Malformed encryption scheme");
+        }
+
+        if !config.algorithms.is_empty() && !config.algorithms.contains(&self.alg) {
+            return invalid(&format!("Encryption scheme {} is not allowed", self.alg));
+        }
+
+        if self.key_id.is_empty()
+            || self.key_id.len() > MAX_KEY_ID_LEN
+            || !self.key_id.chars().all(|c| c.is_ascii_graphic())
+        {
+            return invalid("Malformed key id");
+        }
+
+        match base64::decode(&self.nonce) {
+            Ok(nonce) if NONCE_LEN.contains(&nonce.len()) => (),
+            _ => return invalid("Malformed nonce"),
+        }
+
+        // Base64 takes 4 characters per 3 bytes so there is no need to decode an oversized one.
+        if self.ciphertext.len() > (config.max_size + 2) / 3 * 4 {
+            return too_large(config.max_size);
+        }
+
+        match base64::decode(&self.ciphertext) {
+            Ok(ciphertext) if ciphertext.is_empty() => invalid("Empty ciphertext"),
+            Ok(ciphertext) if ciphertext.len() > config.max_size => too_large(config.max_size),
+            Ok(_) => Ok(()),
+            Err(_) => invalid("Malformed ciphertext"),
+        }
+    }
+}
+
+impl fmt::Debug for Envelope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Envelope")
+            .field("alg", &self.alg)
+            .field("key_id", &self.key_id)
+            .field("ciphertext_len", &self.ciphertext.len())
+            .finish()
+    }
+}
+
+fn invalid(reason: &str) -> Result<(), AppError> {
+    Err(anyhow!("{} in the encrypted envelope", reason)).error(AppErrorKind::InvalidPayload)
+}
+
+fn too_large(max_size: usize) -> Result<(), AppError> {
+    Err(anyhow!(
+        "Ciphertext exceeds the limit of {} bytes",
+        max_size
+    ))
+    .error(AppErrorKind::MessageTooLarge)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn envelope() -> Envelope {
+        Envelope {
+            alg: "x25519-aes-256-gcm".to_owned(),
+            key_id: "receiver-2023-07".to_owned(),
+            nonce: base64::encode([0u8; 12]),
+            ciphertext: base64::encode([1u8; 32]),
+        }
+    }
+
+    #[test]
+    fn validate_envelope() {
+        let config = EncryptedMessageConfig::default();
+        envelope()
+            .validate(&config)
+            .expect("Valid envelope rejected");
+
+        let cases = [
+            Envelope {
+                alg: "".to_owned(),
+                ..envelope()
+            },
+            Envelope {
+                key_id: "with space".to_owned(),
+                ..envelope()
+            },
+            Envelope {
+                nonce: base64::encode([0u8; 4]),
+                ..envelope()
+            },
+            Envelope {
+                ciphertext: "not base64!".to_owned(),
+                ..envelope()
+            },
+        ];
+
+        for case in cases {
+            let err = case
+                .validate(&config)
+                .expect_err("Invalid envelope accepted");
+            assert_eq!(err.kind(), "invalid_payload");
+        }
+    }
+
+    #[test]
+    fn validate_size_and_scheme() {
+        let config = EncryptedMessageConfig {
+            max_size: 16,
+            algorithms: vec!["x25519-aes-256-gcm".to_owned()].into_iter().collect(),
+        };
+
+        let err = envelope()
+            .validate(&config)
+            .expect_err("Large ciphertext accepted");
+        assert_eq!(err.kind(), "message_too_large");
+
+        let small = Envelope {
+            ciphertext: base64::encode([1u8; 16]),
+            ..envelope()
+        };
+        small.validate(&config).expect("Valid envelope rejected");
+
+        let other = Envelope {
+            alg: "rot13".to_owned(),
+            ..small
+        };
+        let err = other
+            .validate(&config)
+            .expect_err("Unknown scheme accepted");
+        assert_eq!(err.kind(), "invalid_payload");
+    }
+
+    #[test]
+    fn debug_leaves_ciphertext_out() {
+        let envelope = envelope();
+        assert!(!format!("{:?}", envelope).contains(&envelope.ciphertext));
+    }
+}
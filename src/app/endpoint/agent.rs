@@ -1,16 +1,22 @@
 use std::sync::Arc;
 
+use anyhow::anyhow;
 use async_trait::async_trait;
-use axum::extract::{Extension, Path, Query};
+use axum::{
+    extract::{Extension, Path, Query},
+    Json,
+};
 
-use serde::Deserialize;
-use svc_agent::mqtt::ResponseStatus;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use svc_agent::{mqtt::ResponseStatus, Addressable};
 use svc_utils::extractors::AgentIdExtractor;
 
 use crate::{
     app::{
         context::{AppContext, Context},
         endpoint::prelude::*,
+        message_handler::http,
         metrics::HistogramExt,
         service_utils::{RequestParams, Response},
     },
@@ -20,7 +26,7 @@ use crate::{
 
 ///////////////////////////////////////////////////////////////////////////////
 
-const MAX_LIMIT: i64 = 25;
+const MAX_LIMIT: i64 = helpers::LIST_MAX_LIMIT;
 
 #[derive(Debug, Deserialize)]
 pub struct ListRequest {
@@ -48,14 +54,7 @@ pub async fn list(
         offset: query.map(|x| x.offset),
         limit: query.map(|x| x.limit),
     };
-    ListHandler::handle(
-        &mut ctx.start_message(),
-        request,
-        RequestParams::Http {
-            agent_id: &agent_id,
-        },
-    )
-    .await
+    http::handle::<ListHandler>(&ctx, &agent_id, request).await
 }
 
 pub struct ListHandler;
@@ -64,6 +63,7 @@ pub struct ListHandler;
 impl RequestHandler for ListHandler {
     type Payload = ListRequest;
     const ERROR_TITLE: &'static str = "Failed to list agents";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::CLASSROOM_READ);
 
     async fn handle<C: Context + Send + Sync>(
         context: &mut C,
@@ -75,6 +75,7 @@ impl RequestHandler for ListHandler {
             helpers::find_room_by_id(
                 payload.room_id,
                 helpers::RoomTimeRequirement::Open,
+                helpers::AudienceScope::caller(context, &reqp),
                 &mut conn,
             )
             .await?
@@ -94,7 +95,8 @@ impl RequestHandler for ListHandler {
             .await?;
         context.metrics().observe_auth(authz_time);
 
-        // Get agents list in the room.
+        // Get agents list in the room. Agents still entering are listed as well so
+        // clients can wait for their own `ready` status.
         let mut conn = context.get_conn().await?;
         let agents = db::agent::ListQuery::new()
             .room_id(payload.room_id)
@@ -121,11 +123,102 @@ impl RequestHandler for ListHandler {
 
 ///////////////////////////////////////////////////////////////////////////////
 
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PreferencesUpdateRequest {
+    room_id: db::room::Id,
+    muted_events: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Preferences {
+    muted_events: Vec<String>,
+}
+
+pub async fn update_preferences(
+    Extension(ctx): Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<db::room::Id>,
+    Json(preferences): Json<Preferences>,
+) -> RequestResult {
+    tracing::Span::current().record("room_id", tracing::field::display(room_id));
+
+    let request = PreferencesUpdateRequest {
+        room_id,
+        muted_events: preferences.muted_events,
+    };
+    http::handle::<PreferencesUpdateHandler>(&ctx, &agent_id, request).await
+}
+
+pub struct PreferencesUpdateHandler;
+
+#[async_trait]
+impl RequestHandler for PreferencesUpdateHandler {
+    type Payload = PreferencesUpdateRequest;
+    const ERROR_TITLE: &'static str = "Failed to update agent preferences";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::Presence);
+
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let PreferencesUpdateRequest {
+            room_id,
+            mut muted_events,
+        } = payload;
+
+        if let Some(label) = muted_events
+            .iter()
+            .find(|label| !db::agent::MUTABLE_EVENTS.contains(&label.as_str()))
+        {
+            return Err(anyhow!("Event '{}' can't be muted", label))
+                .error(AppErrorKind::InvalidPayload);
+        }
+
+        muted_events.sort();
+        muted_events.dedup();
+
+        // Preferences are the agent's own so entering the room is the only requirement.
+        let mut conn = context.get_conn().await?;
+        let room = helpers::find_room_by_id(
+            room_id,
+            helpers::RoomTimeRequirement::Open,
+            helpers::AudienceScope::caller(context, &reqp),
+            &mut conn,
+        )
+        .await?;
+
+        let agent =
+            db::agent::set_muted_events(reqp.as_agent_id(), room.id(), &muted_events, &mut conn)
+                .await?
+                .ok_or_else(|| anyhow!("Agent has not entered the room"))
+                .error(AppErrorKind::AgentNotEnteredTheRoom)?;
+
+        context
+            .metrics()
+            .request_duration
+            .agent_preferences_update
+            .observe_timestamp(context.start_timestamp());
+
+        Ok(Response::new(
+            ResponseStatus::OK,
+            json!({
+                "room_id": room.id(),
+                "muted_events": agent.muted_events(),
+            }),
+            context.start_timestamp(),
+            None,
+        ))
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
 #[cfg(test)]
 mod tests {
     mod list {
         use serde::Deserialize;
-        use svc_agent::AgentId;
+        use svc_agent::{AgentId, Authenticable};
 
         use crate::test_helpers::{db::TestDb, prelude::*};
 
@@ -137,6 +230,7 @@ mod tests {
         struct Agent {
             agent_id: AgentId,
             room_id: db::room::Id,
+            status: db::agent::Status,
         }
 
         #[sqlx::test]
@@ -177,6 +271,108 @@ mod tests {
             assert_eq!(agents.len(), 1);
             assert_eq!(&agents[0].agent_id, agent.agent_id());
             assert_eq!(agents[0].room_id, room.id());
+            assert_eq!(agents[0].status, db::agent::Status::Ready);
+        }
+
+        #[sqlx::test]
+        async fn list_agents_still_entering(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+
+            factory::Agent::new()
+                .agent_id(agent.agent_id())
+                .room_id(room.id())
+                .status(db::agent::Status::Requested)
+                .insert(&mut conn)
+                .await;
+
+            let mut authz = TestAuthz::new();
+            authz.allow(
+                agent.account_id(),
+                vec!["classrooms", &room.classroom_id().to_string()],
+                "read",
+            );
+
+            let mut context = TestContext::new(db, authz).await;
+
+            let payload = ListRequest {
+                room_id: room.id(),
+                offset: None,
+                limit: None,
+            };
+
+            let messages = handle_request::<ListHandler>(&mut context, &agent, payload)
+                .await
+                .expect("Agents listing failed");
+
+            let (agents, _, _) = find_response::<Vec<Agent>>(messages.as_slice());
+            assert_eq!(agents.len(), 1);
+            assert_eq!(agents[0].status, db::agent::Status::Requested);
+        }
+
+        #[sqlx::test]
+        async fn list_agents_pseudonymized(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let reader = TestAgent::new("web", "user456", USR_AUDIENCE);
+            let service = TestAgent::new("alpha", "event", SVC_AUDIENCE);
+
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+            shared_helpers::insert_agent(&mut conn, agent.agent_id(), room.id()).await;
+
+            let mut authz = TestAuthz::new();
+            let classroom_id = room.classroom_id().to_string();
+            authz.allow(
+                reader.account_id(),
+                vec!["classrooms", &classroom_id],
+                "read",
+            );
+            authz.allow(
+                service.account_id(),
+                vec!["classrooms", &classroom_id],
+                "read",
+            );
+
+            let mut context = TestContext::new(db, authz).await;
+
+            context.config_mut().pseudonymize.insert(
+                USR_AUDIENCE.to_owned(),
+                crate::config::PseudonymizeConfig {
+                    secret: "secret".to_owned(),
+                },
+            );
+
+            let payload = || ListRequest {
+                room_id: room.id(),
+                offset: None,
+                limit: None,
+            };
+
+            // Other participants get a pseudonym.
+            let messages = handle_request::<ListHandler>(&mut context, &reader, payload())
+                .await
+                .expect("Agents listing failed");
+
+            let (agents, _, _) = find_response::<Vec<Agent>>(messages.as_slice());
+            assert_eq!(agents.len(), 1);
+            assert_ne!(&agents[0].agent_id, agent.agent_id());
+            assert_eq!(agents[0].agent_id.label(), "web");
+
+            let account_id = agents[0].agent_id.as_account_id();
+            assert!(account_id.label().starts_with("anon-"));
+            assert_eq!(account_id.audience(), USR_AUDIENCE);
+
+            // Services see the real id.
+            let messages = handle_request::<ListHandler>(&mut context, &service, payload())
+                .await
+                .expect("Agents listing failed");
+
+            let (agents, _, _) = find_response::<Vec<Agent>>(messages.as_slice());
+            assert_eq!(&agents[0].agent_id, agent.agent_id());
         }
 
         #[sqlx::test]
@@ -261,4 +457,103 @@ mod tests {
             assert_eq!(err.kind(), "room_not_found");
         }
     }
+
+    mod preferences_update {
+        use svc_agent::mqtt::ResponseStatus;
+
+        use crate::test_helpers::{db::TestDb, prelude::*};
+
+        use super::super::*;
+
+        #[sqlx::test]
+        async fn update_preferences(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+            shared_helpers::insert_agent(&mut conn, agent.agent_id(), room.id()).await;
+
+            let mut context = TestContext::new(db, TestAuthz::new()).await;
+
+            let payload = PreferencesUpdateRequest {
+                room_id: room.id(),
+                muted_events: vec![
+                    "rtc_stream.update".to_owned(),
+                    "rtc_stream.agent_speaking".to_owned(),
+                    "rtc_stream.update".to_owned(),
+                ],
+            };
+
+            let messages =
+                handle_request::<PreferencesUpdateHandler>(&mut context, &agent, payload)
+                    .await
+                    .expect("Preferences update failed");
+
+            let (resp, respp, _) = find_response::<PreferencesUpdateRequest>(messages.as_slice());
+            assert_eq!(respp.status(), ResponseStatus::OK);
+            assert_eq!(
+                resp.muted_events,
+                vec!["rtc_stream.agent_speaking", "rtc_stream.update"]
+            );
+
+            let agents = db::agent::ListQuery::new()
+                .status(db::agent::Status::Ready)
+                .agent_id(agent.agent_id())
+                .room_id(room.id())
+                .execute(&mut conn)
+                .await
+                .expect("Failed to list agents");
+
+            assert!(!agents[0].accepts("rtc_stream.update"));
+            assert!(agents[0].accepts("message.unicast"));
+        }
+
+        #[sqlx::test]
+        async fn update_preferences_with_unknown_event(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+            shared_helpers::insert_agent(&mut conn, agent.agent_id(), room.id()).await;
+
+            let mut context = TestContext::new(db, TestAuthz::new()).await;
+
+            let payload = PreferencesUpdateRequest {
+                room_id: room.id(),
+                muted_events: vec!["room.close".to_owned()],
+            };
+
+            let err = handle_request::<PreferencesUpdateHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on muting room.close");
+
+            assert_eq!(err.status(), ResponseStatus::BAD_REQUEST);
+            assert_eq!(err.kind(), "invalid_payload");
+        }
+
+        #[sqlx::test]
+        async fn update_preferences_not_entered(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+
+            let mut context = TestContext::new(db, TestAuthz::new()).await;
+
+            let payload = PreferencesUpdateRequest {
+                room_id: room.id(),
+                muted_events: vec![],
+            };
+
+            let err = handle_request::<PreferencesUpdateHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success without entering the room");
+
+            assert_eq!(err.status(), ResponseStatus::NOT_FOUND);
+            assert_eq!(err.kind(), "agent_not_entered_the_room");
+        }
+    }
 }
@@ -1,9 +1,13 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
 use crate::{
     app::{
         context::{AppContext, Context},
         endpoint::prelude::*,
+        message_handler::http,
         metrics::HistogramExt,
         service_utils::{RequestParams, Response},
     },
@@ -14,19 +18,23 @@ use crate::{
     },
     db::{self, rtc::Object as Rtc, rtc_reader_config::Object as RtcReaderConfig},
 };
-use anyhow::{anyhow, Context as AnyhowContext};
+use anyhow::anyhow;
 use async_trait::async_trait;
 use axum::{
     extract::{Extension, Path},
     Json,
 };
+use chrono::SubsecRound;
 use serde::{Deserialize, Serialize};
 use sqlx::Connection;
 use svc_agent::{mqtt::ResponseStatus, Addressable, AgentId};
 
 use svc_utils::extractors::AgentIdExtractor;
+use tracing::warn;
 
 const MAX_STATE_CONFIGS_LEN: usize = 20;
+/// Simulcast publishers send up to 3 substreams with up to 3 temporal layers each.
+const MAX_SIMULCAST_LAYER: u8 = 2;
 
 ////////////////////////////////////////////////////////////////////////////////
 
@@ -34,10 +42,34 @@ const MAX_STATE_CONFIGS_LEN: usize = 20;
 pub struct State {
     room_id: db::room::Id,
     configs: Vec<StateConfigItem>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    status: Option<ApplyStatus>,
+}
+
+/// Whether the stored configs reached Janus.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ApplyStatus {
+    Applied,
+    /// Stored but not pushed to Janus yet. `system.reader_config_reconcile` retries the push.
+    Pending,
+}
+
+impl ApplyStatus {
+    pub(crate) fn response_status(self) -> ResponseStatus {
+        match self {
+            ApplyStatus::Applied => ResponseStatus::OK,
+            ApplyStatus::Pending => ResponseStatus::ACCEPTED,
+        }
+    }
 }
 
 impl State {
-    fn new(room_id: db::room::Id, rtc_reader_configs: &[(RtcReaderConfig, Rtc)]) -> State {
+    fn new(
+        room_id: db::room::Id,
+        rtc_reader_configs: &[(RtcReaderConfig, Rtc)],
+        status: ApplyStatus,
+    ) -> State {
         let configs = rtc_reader_configs
             .iter()
             .map(|(rtc_reader_config, rtc)| {
@@ -47,7 +79,11 @@ impl State {
             })
             .collect::<Vec<_>>();
 
-        Self { room_id, configs }
+        Self {
+            room_id,
+            configs,
+            status: Some(status),
+        }
     }
 }
 
@@ -56,6 +92,10 @@ pub struct StateConfigItem {
     agent_id: AgentId,
     receive_video: Option<bool>,
     receive_audio: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    spatial_layer: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    temporal_layer: Option<u8>,
 }
 
 impl StateConfigItem {
@@ -64,6 +104,8 @@ impl StateConfigItem {
             agent_id,
             receive_video: None,
             receive_audio: None,
+            spatial_layer: None,
+            temporal_layer: None,
         }
     }
 
@@ -99,15 +141,9 @@ pub async fn update(
     let request = State {
         room_id,
         configs: configs.configs,
+        status: None,
     };
-    UpdateHandler::handle(
-        &mut ctx.start_message(),
-        request,
-        RequestParams::Http {
-            agent_id: &agent_id,
-        },
-    )
-    .await
+    http::handle::<UpdateHandler>(&ctx, &agent_id, request).await
 }
 
 pub struct UpdateHandler;
@@ -116,6 +152,7 @@ pub struct UpdateHandler;
 impl RequestHandler for UpdateHandler {
     type Payload = State;
     const ERROR_TITLE: &'static str = "Failed to update agent reader config";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::CLASSROOM_UPDATE);
 
     async fn handle<C: Context + Send + Sync>(
         context: &mut C,
@@ -127,13 +164,34 @@ impl RequestHandler for UpdateHandler {
                 .error(AppErrorKind::InvalidPayload)?;
         }
 
-        let State { room_id, configs } = payload;
+        let layers = payload
+            .configs
+            .iter()
+            .flat_map(|c| c.spatial_layer.into_iter().chain(c.temporal_layer));
+
+        for layer in layers {
+            if layer > MAX_SIMULCAST_LAYER {
+                return Err(anyhow!(
+                    "Simulcast layer must not exceed {}",
+                    MAX_SIMULCAST_LAYER
+                ))
+                .error(AppErrorKind::InvalidPayload)?;
+            }
+        }
+
+        let State {
+            room_id, configs, ..
+        } = payload;
 
         let room = {
             let mut conn = context.get_conn().await?;
-            let room =
-                helpers::find_room_by_id(room_id, helpers::RoomTimeRequirement::Open, &mut conn)
-                    .await?;
+            let room = helpers::find_room_by_id(
+                room_id,
+                helpers::RoomTimeRequirement::Open,
+                helpers::AudienceScope::caller(context, &reqp),
+                &mut conn,
+            )
+            .await?;
 
             helpers::check_room_presence(&room, reqp.as_agent_id(), &mut conn).await?;
 
@@ -173,9 +231,13 @@ impl RequestHandler for UpdateHandler {
             }
         };
 
+        // Configs are written as an intent first. It gets confirmed once Janus accepts the push
+        // so that a failed push doesn't leave the database and Janus diverged unnoticed.
+        // Postgres keeps microseconds so the timestamp is truncated to match it on confirmation.
+        let pending_since = context.start_timestamp().trunc_subsecs(6);
         let agent_id = reqp.as_agent_id().clone();
         let room_id = room.id();
-        let rtc_reader_configs_with_rtcs = conn
+        let (rtc_reader_configs_with_rtcs, updated_rtc_ids, rtc_layers) = conn
             .transaction::<_, _, AppError>(|conn| {
                 Box::pin(async move {
                     // An agent can create/update reader configs only for agents in the same group
@@ -200,6 +262,9 @@ impl RequestHandler for UpdateHandler {
                         .map(|rtc| (rtc.created_by(), rtc.id()))
                         .collect::<HashMap<_, _>>();
 
+                    let mut updated_rtc_ids = HashSet::new();
+                    let mut rtc_layers = HashMap::new();
+
                     // Create or update the config.
                     for state_config_item in configs {
                         let rtc_id = agents_to_rtcs
@@ -217,7 +282,8 @@ impl RequestHandler for UpdateHandler {
                             .error(AppErrorKind::InvalidPayload)?;
                         }
 
-                        let mut q = db::rtc_reader_config::UpsertQuery::new(*rtc_id, &agent_id);
+                        let mut q = db::rtc_reader_config::UpsertQuery::new(*rtc_id, &agent_id)
+                            .pending_since(pending_since);
 
                         if let Some(receive_video) = state_config_item.receive_video {
                             q = q.receive_video(receive_video);
@@ -228,6 +294,15 @@ impl RequestHandler for UpdateHandler {
                         }
 
                         q.execute(conn).await?;
+                        updated_rtc_ids.insert(*rtc_id);
+
+                        rtc_layers.insert(
+                            *rtc_id,
+                            (
+                                state_config_item.spatial_layer,
+                                state_config_item.temporal_layer,
+                            ),
+                        );
                     }
 
                     // Retrieve state data.
@@ -236,37 +311,49 @@ impl RequestHandler for UpdateHandler {
                             .execute(conn)
                             .await?;
 
-                    Ok(rtc_reader_configs_with_rtcs)
+                    Ok((rtc_reader_configs_with_rtcs, updated_rtc_ids, rtc_layers))
                 })
             })
             .await?;
 
-        if let Some(backend) = maybe_backend {
-            let items = rtc_reader_configs_with_rtcs
-                .iter()
-                .map(
-                    |(rtc_reader_config, rtc)| UpdateReaderConfigRequestBodyConfigItem {
-                        reader_id: rtc_reader_config.reader_id().to_owned(),
-                        stream_id: rtc.id(),
-                        receive_video: rtc_reader_config.receive_video(),
-                        receive_audio: rtc_reader_config.receive_audio(),
-                    },
-                )
-                .collect();
+        let status = match maybe_backend {
+            Some(backend) => {
+                // The response carries the agent's whole state but Janus only needs the updated part.
+                let items = rtc_reader_configs_with_rtcs
+                    .iter()
+                    .filter(|(_, rtc)| updated_rtc_ids.contains(&rtc.id()))
+                    .map(|(rtc_reader_config, rtc)| {
+                        let (spatial_layer, temporal_layer) =
+                            rtc_layers.get(&rtc.id()).copied().unwrap_or_default();
+
+                        UpdateReaderConfigRequestBodyConfigItem {
+                            reader_id: rtc_reader_config.reader_id().to_owned(),
+                            stream_id: rtc.id(),
+                            receive_video: rtc_reader_config.receive_video(),
+                            receive_audio: rtc_reader_config.receive_audio(),
+                            spatial_layer,
+                            temporal_layer,
+                        }
+                    })
+                    .collect::<Vec<_>>();
 
-            let request = UpdateReaderConfigRequest {
-                session_id: backend.session_id(),
-                handle_id: backend.handle_id(),
-                body: UpdateReaderConfigRequestBody::new(items),
-            };
-            context
-                .janus_clients()
-                .get_or_insert(&backend)
-                .error(AppErrorKind::BackendClientCreationFailed)?
-                .reader_update(request)
-                .await
-                .context("Reader update")
-                .error(AppErrorKind::BackendRequestFailed)?
+                push(context, &backend, items, rtc_reader_configs_with_rtcs.len()).await?
+            }
+            // Janus gets the configs from the database when the stream is read.
+            None => ApplyStatus::Applied,
+        };
+
+        if status == ApplyStatus::Applied {
+            let updated_rtc_ids = updated_rtc_ids.into_iter().collect::<Vec<_>>();
+            let mut conn = context.get_conn().await?;
+
+            db::rtc_reader_config::confirm(
+                reqp.as_agent_id(),
+                &updated_rtc_ids,
+                pending_since,
+                &mut conn,
+            )
+            .await?;
         }
 
         context
@@ -275,14 +362,52 @@ impl RequestHandler for UpdateHandler {
             .agent_reader_config_update
             .observe_timestamp(context.start_timestamp());
         Ok(Response::new(
-            ResponseStatus::OK,
-            State::new(room.id(), &rtc_reader_configs_with_rtcs),
+            status.response_status(),
+            State::new(room.id(), &rtc_reader_configs_with_rtcs, status),
             context.start_timestamp(),
             None,
         ))
     }
 }
 
+/// Pushes the configs to the backend. A failed push leaves them pending for
+/// `system.reader_config_reconcile` to retry.
+pub(crate) async fn push<C: Context>(
+    context: &C,
+    backend: &db::janus_backend::Object,
+    items: Vec<UpdateReaderConfigRequestBodyConfigItem>,
+    full_size: usize,
+) -> Result<ApplyStatus, AppError> {
+    let delta = items.len();
+
+    let request = UpdateReaderConfigRequest {
+        session_id: backend.session_id(),
+        handle_id: backend.handle_id(),
+        body: UpdateReaderConfigRequestBody::new(items),
+    };
+
+    let result = context
+        .janus_clients()
+        .get_or_insert(backend)
+        .error(AppErrorKind::BackendClientCreationFailed)?
+        .reader_update(request)
+        .await;
+
+    match result {
+        Ok(()) => {
+            context
+                .metrics()
+                .observe_reader_config_push(delta, full_size);
+
+            Ok(ApplyStatus::Applied)
+        }
+        Err(err) => {
+            warn!(?err, backend = %backend.id(), "Reader update failed, left pending");
+            Ok(ApplyStatus::Pending)
+        }
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 
 #[derive(Debug, Deserialize)]
@@ -298,14 +423,7 @@ pub async fn read(
     tracing::Span::current().record("room_id", &tracing::field::display(room_id));
 
     let request = ReadRequest { room_id };
-    ReadHandler::handle(
-        &mut ctx.start_message(),
-        request,
-        RequestParams::Http {
-            agent_id: &agent_id,
-        },
-    )
-    .await
+    http::handle::<ReadHandler>(&ctx, &agent_id, request).await
 }
 
 pub struct ReadHandler;
@@ -314,6 +432,7 @@ pub struct ReadHandler;
 impl RequestHandler for ReadHandler {
     type Payload = ReadRequest;
     const ERROR_TITLE: &'static str = "Failed to read agent reader config";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::CLASSROOM_READ);
 
     async fn handle<C: Context + Send + Sync>(
         context: &mut C,
@@ -325,6 +444,7 @@ impl RequestHandler for ReadHandler {
             let room = helpers::find_room_by_id(
                 payload.room_id,
                 helpers::RoomTimeRequirement::Open,
+                helpers::AudienceScope::caller(context, &reqp),
                 &mut conn,
             )
             .await?;
@@ -356,11 +476,24 @@ impl RequestHandler for ReadHandler {
             .await?;
         context.metrics().observe_auth(authz_time);
 
-        let rtc_reader_configs_with_rtcs = {
+        let (rtc_reader_configs_with_rtcs, has_pending) = {
             let mut conn = context.get_conn().await?;
-            db::rtc_reader_config::ListWithRtcQuery::new(room.id(), &[reqp.as_agent_id()])
-                .execute(&mut conn)
-                .await?
+            let configs =
+                db::rtc_reader_config::ListWithRtcQuery::new(room.id(), &[reqp.as_agent_id()])
+                    .execute(&mut conn)
+                    .await?;
+
+            let has_pending =
+                db::rtc_reader_config::has_pending(room.id(), reqp.as_agent_id(), &mut conn)
+                    .await?;
+
+            (configs, has_pending)
+        };
+
+        let status = if has_pending {
+            ApplyStatus::Pending
+        } else {
+            ApplyStatus::Applied
         };
 
         context
@@ -371,7 +504,7 @@ impl RequestHandler for ReadHandler {
 
         Ok(Response::new(
             ResponseStatus::OK,
-            State::new(room.id(), &rtc_reader_configs_with_rtcs),
+            State::new(room.id(), &rtc_reader_configs_with_rtcs, status),
             context.start_timestamp(),
             None,
         ))
@@ -385,6 +518,7 @@ mod tests {
     mod update {
         use std::ops::Bound;
 
+        use crate::backend::janus::client::{HandleId, SessionId};
         use crate::db::group_agent::{GroupItem, Groups};
         use crate::{
             db::rtc::SharingPolicy as RtcSharingPolicy,
@@ -463,16 +597,21 @@ mod tests {
 
             let payload = State {
                 room_id: room.id(),
+                status: None,
                 configs: vec![
                     StateConfigItem {
                         agent_id: agent2.agent_id().to_owned(),
                         receive_video: Some(true),
                         receive_audio: Some(false),
+                        spatial_layer: None,
+                        temporal_layer: None,
                     },
                     StateConfigItem {
                         agent_id: agent3.agent_id().to_owned(),
                         receive_video: Some(false),
                         receive_audio: Some(false),
+                        spatial_layer: None,
+                        temporal_layer: None,
                     },
                 ],
             };
@@ -508,16 +647,21 @@ mod tests {
             // Make one more agent_reader_config.update request.
             let payload = State {
                 room_id: room.id(),
+                status: None,
                 configs: vec![
                     StateConfigItem {
                         agent_id: agent4.agent_id().to_owned(),
                         receive_video: Some(true),
                         receive_audio: Some(true),
+                        spatial_layer: None,
+                        temporal_layer: None,
                     },
                     StateConfigItem {
                         agent_id: agent3.agent_id().to_owned(),
                         receive_video: None,
                         receive_audio: Some(true),
+                        spatial_layer: None,
+                        temporal_layer: None,
                     },
                 ],
             };
@@ -563,6 +707,103 @@ mod tests {
             Ok(())
         }
 
+        #[sqlx::test]
+        async fn unreachable_backend(pool: sqlx::PgPool) -> std::io::Result<()> {
+            let db = TestDb::new(pool);
+            let agent1 = TestAgent::new("web", "user1", USR_AUDIENCE);
+            let agent2 = TestAgent::new("web", "user2", USR_AUDIENCE);
+
+            let mut conn = db.get_conn().await;
+
+            let backend = shared_helpers::insert_janus_backend(
+                &mut conn,
+                "http://127.0.0.1:1/janus",
+                SessionId::random(),
+                HandleId::random(),
+            )
+            .await;
+
+            let room = factory::Room::new()
+                .audience(USR_AUDIENCE)
+                .time((Bound::Included(Utc::now()), Bound::Unbounded))
+                .rtc_sharing_policy(RtcSharingPolicy::Owned)
+                .backend_id(backend.id())
+                .insert(&mut conn)
+                .await;
+
+            for agent in &[&agent1, &agent2] {
+                shared_helpers::insert_agent(&mut conn, agent.agent_id(), room.id()).await;
+            }
+
+            factory::Rtc::new(room.id())
+                .created_by(agent2.agent_id().to_owned())
+                .insert(&mut conn)
+                .await;
+
+            let groups = Groups::new(vec![GroupItem::new(
+                0,
+                vec![agent1.agent_id().clone(), agent2.agent_id().clone()],
+            )]);
+
+            factory::GroupAgent::new(room.id(), groups)
+                .upsert(&mut conn)
+                .await;
+
+            let mut authz = TestAuthz::new();
+            let classroom_id = room.classroom_id().to_string();
+            authz.allow(
+                agent1.account_id(),
+                vec!["classrooms", &classroom_id],
+                "update",
+            );
+            authz.allow(
+                agent1.account_id(),
+                vec!["classrooms", &classroom_id],
+                "read",
+            );
+
+            let mut context = TestContext::new(db, authz).await;
+            let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+            context.with_janus(tx);
+
+            let payload = State {
+                room_id: room.id(),
+                status: None,
+                configs: vec![StateConfigItem {
+                    agent_id: agent2.agent_id().to_owned(),
+                    receive_video: Some(false),
+                    receive_audio: Some(true),
+                    spatial_layer: None,
+                    temporal_layer: None,
+                }],
+            };
+
+            let messages = handle_request::<UpdateHandler>(&mut context, &agent1, payload)
+                .await
+                .expect("Agent reader config update failed");
+
+            // The config is stored but not applied.
+            let (state, respp, _) = find_response::<State>(messages.as_slice());
+            assert_eq!(respp.status(), ResponseStatus::ACCEPTED);
+            assert_eq!(state.status, Some(ApplyStatus::Pending));
+            assert_eq!(state.configs.len(), 1);
+            assert_eq!(state.configs[0].receive_video, Some(false));
+
+            let messages = handle_request::<ReadHandler>(
+                &mut context,
+                &agent1,
+                ReadRequest { room_id: room.id() },
+            )
+            .await
+            .expect("Agent reader config read failed");
+
+            let (state, _, _) = find_response::<State>(messages.as_slice());
+            assert_eq!(state.status, Some(ApplyStatus::Pending));
+
+            context.janus_clients().remove_client(&backend);
+            Ok(())
+        }
+
         #[sqlx::test]
         async fn too_many_config_items(pool: sqlx::PgPool) -> std::io::Result<()> {
             // Make agent_reader_config.update request.
@@ -578,12 +819,15 @@ mod tests {
                         agent_id: agent.agent_id().to_owned(),
                         receive_video: Some(false),
                         receive_audio: Some(true),
+                        spatial_layer: None,
+                        temporal_layer: None,
                     }
                 })
                 .collect::<Vec<_>>();
 
             let payload = State {
                 room_id: db::room::Id::random(),
+                status: None,
                 configs,
             };
 
@@ -597,6 +841,33 @@ mod tests {
             Ok(())
         }
 
+        #[sqlx::test]
+        async fn simulcast_layer_out_of_range(pool: sqlx::PgPool) -> std::io::Result<()> {
+            let db = TestDb::new(pool);
+            let agent = TestAgent::new("web", "user", USR_AUDIENCE);
+            let mut context = TestContext::new(db, TestAuthz::new()).await;
+
+            let payload = State {
+                room_id: db::room::Id::random(),
+                status: None,
+                configs: vec![StateConfigItem {
+                    agent_id: agent.agent_id().to_owned(),
+                    receive_video: Some(true),
+                    receive_audio: Some(true),
+                    spatial_layer: Some(0),
+                    temporal_layer: Some(MAX_SIMULCAST_LAYER + 1),
+                }],
+            };
+
+            let err = handle_request::<UpdateHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected agent reader config update success");
+
+            assert_eq!(err.status(), ResponseStatus::BAD_REQUEST);
+            assert_eq!(err.kind(), "invalid_payload");
+            Ok(())
+        }
+
         #[sqlx::test]
         async fn agent_without_rtc(pool: sqlx::PgPool) -> std::io::Result<()> {
             let db = TestDb::new(pool);
@@ -634,10 +905,13 @@ mod tests {
 
             let payload = State {
                 room_id: room.id(),
+                status: None,
                 configs: vec![StateConfigItem {
                     agent_id: agent2.agent_id().to_owned(),
                     receive_video: Some(false),
                     receive_audio: Some(true),
+                    spatial_layer: None,
+                    temporal_layer: None,
                 }],
             };
 
@@ -674,6 +948,7 @@ mod tests {
 
             let payload = State {
                 room_id: room.id(),
+                status: None,
                 configs: vec![],
             };
 
@@ -712,6 +987,7 @@ mod tests {
 
             let payload = State {
                 room_id: room.id(),
+                status: None,
                 configs: vec![],
             };
 
@@ -756,6 +1032,7 @@ mod tests {
 
             let payload = State {
                 room_id: room.id(),
+                status: None,
                 configs: vec![],
             };
 
@@ -778,6 +1055,7 @@ mod tests {
 
             let payload = State {
                 room_id: db::room::Id::random(),
+                status: None,
                 configs: vec![],
             };
 
@@ -849,10 +1127,13 @@ mod tests {
 
             let payload = State {
                 room_id: room.id(),
+                status: None,
                 configs: vec![StateConfigItem {
                     agent_id: agent2.agent_id().to_owned(),
                     receive_video: Some(true),
                     receive_audio: Some(false),
+                    spatial_layer: None,
+                    temporal_layer: None,
                 }],
             };
 
@@ -2,8 +2,10 @@ use std::{collections::HashMap, sync::Arc};
 
 use crate::{
     app::{
-        context::{AppContext, Context},
+        config_sync::{self, SyncEvent},
+        context::{AppContext, Context, GlobalContext},
         endpoint::prelude::*,
+        message_handler::http,
         metrics::HistogramExt,
         service_utils::{RequestParams, Response},
     },
@@ -13,7 +15,10 @@ use crate::{
         UpdateWriterConfigRequestBodyConfigItem,
     },
     db,
-    db::{rtc::Object as Rtc, rtc_writer_config::Object as RtcWriterConfig},
+    db::{
+        rtc::{Kind as RtcKind, Object as Rtc},
+        rtc_writer_config::Object as RtcWriterConfig,
+    },
 };
 use anyhow::anyhow;
 use async_trait::async_trait;
@@ -54,6 +59,7 @@ impl State {
             .iter()
             .map(|(rtc_writer_config, rtc)| {
                 let mut config_item = StateConfigItem::new(rtc.created_by().to_owned())
+                    .kind(rtc.kind())
                     .send_video(rtc_writer_config.send_video())
                     .send_audio(rtc_writer_config.send_audio());
 
@@ -61,6 +67,14 @@ impl State {
                     config_item = config_item.video_remb(video_remb as u32);
                 }
 
+                if let Some(max_video_bitrate) = rtc_writer_config.max_video_bitrate() {
+                    config_item = config_item.max_video_bitrate(max_video_bitrate as u32);
+                }
+
+                if let Some(max_audio_bitrate) = rtc_writer_config.max_audio_bitrate() {
+                    config_item = config_item.max_audio_bitrate(max_audio_bitrate as u32);
+                }
+
                 if let Some(send_audio_updated_by) = rtc_writer_config.send_audio_updated_by() {
                     config_item =
                         config_item.send_audio_updated_by(send_audio_updated_by.to_owned());
@@ -81,9 +95,17 @@ impl State {
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct StateConfigItem {
     agent_id: AgentId,
+    /// Picks the agent's rtc when they publish both a camera and a screen.
+    #[serde(default)]
+    kind: RtcKind,
     send_video: Option<bool>,
     send_audio: Option<bool>,
     video_remb: Option<u32>,
+    /// Caps of the publisher's bitrates in bits per second, kept across backend changes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_video_bitrate: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_audio_bitrate: Option<u32>,
     #[cfg_attr(not(test), serde(skip_deserializing))]
     send_audio_updated_by: Option<AgentId>,
 }
@@ -92,13 +114,20 @@ impl StateConfigItem {
     fn new(agent_id: AgentId) -> Self {
         Self {
             agent_id,
+            kind: RtcKind::Camera,
             send_video: None,
             send_audio: None,
             video_remb: None,
+            max_video_bitrate: None,
+            max_audio_bitrate: None,
             send_audio_updated_by: None,
         }
     }
 
+    fn kind(self, kind: RtcKind) -> Self {
+        Self { kind, ..self }
+    }
+
     fn send_video(self, send_video: bool) -> Self {
         Self {
             send_video: Some(send_video),
@@ -120,6 +149,20 @@ impl StateConfigItem {
         }
     }
 
+    fn max_video_bitrate(self, max_video_bitrate: u32) -> Self {
+        Self {
+            max_video_bitrate: Some(max_video_bitrate),
+            ..self
+        }
+    }
+
+    fn max_audio_bitrate(self, max_audio_bitrate: u32) -> Self {
+        Self {
+            max_audio_bitrate: Some(max_audio_bitrate),
+            ..self
+        }
+    }
+
     fn send_audio_updated_by(self, send_audio_updated_by: AgentId) -> Self {
         Self {
             send_audio_updated_by: Some(send_audio_updated_by),
@@ -151,14 +194,7 @@ pub async fn update(
         configs: configs.configs,
         updated_at_ns: configs.updated_at_ns,
     };
-    UpdateHandler::handle(
-        &mut ctx.start_message(),
-        request,
-        RequestParams::Http {
-            agent_id: &agent_id,
-        },
-    )
-    .await
+    http::handle::<UpdateHandler>(&ctx, &agent_id, request).await
 }
 
 pub struct UpdateHandler;
@@ -167,6 +203,7 @@ pub struct UpdateHandler;
 impl RequestHandler for UpdateHandler {
     type Payload = State;
     const ERROR_TITLE: &'static str = "Failed to update agent writer config";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::CLASSROOM_UPDATE);
 
     async fn handle<C: Context + Send + Sync>(
         context: &mut C,
@@ -183,6 +220,7 @@ impl RequestHandler for UpdateHandler {
             let room = helpers::find_room_by_id(
                 payload.room_id,
                 helpers::RoomTimeRequirement::Open,
+                helpers::AudienceScope::caller(context, &reqp),
                 &mut conn,
             )
             .await?;
@@ -252,14 +290,20 @@ impl RequestHandler for UpdateHandler {
 
                 let agents_to_rtcs = rtcs
                     .iter()
-                    .map(|rtc| (rtc.created_by(), rtc.id()))
+                    .map(|rtc| ((rtc.created_by(), rtc.kind()), rtc.id()))
                     .collect::<HashMap<_, _>>();
 
                 // Create or update the config.
                 for state_config_item in payload.configs {
                     let rtc_id = agents_to_rtcs
-                        .get(&state_config_item.agent_id)
-                        .ok_or_else(|| anyhow!("{} has no owned RTC", state_config_item.agent_id))
+                        .get(&(&state_config_item.agent_id, state_config_item.kind))
+                        .ok_or_else(|| {
+                            anyhow!(
+                                "{} has no owned RTC of kind {:?}",
+                                state_config_item.agent_id,
+                                state_config_item.kind
+                            )
+                        })
                         .error(AppErrorKind::InvalidPayload)?;
 
                     let mut q = db::rtc_writer_config::UpsertQuery::new(*rtc_id);
@@ -276,6 +320,14 @@ impl RequestHandler for UpdateHandler {
                         q = q.video_remb(video_remb.into());
                     }
 
+                    if let Some(max_video_bitrate) = state_config_item.max_video_bitrate {
+                        q = q.max_video_bitrate(max_video_bitrate.into());
+                    }
+
+                    if let Some(max_audio_bitrate) = state_config_item.max_audio_bitrate {
+                        q = q.max_audio_bitrate(max_audio_bitrate.into());
+                    }
+
                     q.execute(conn).await?;
 
                     if state_config_item.send_video.is_some()
@@ -299,33 +351,15 @@ impl RequestHandler for UpdateHandler {
             .execute(&mut conn)
             .await?;
 
-        if let Some(backend) = maybe_backend {
-            let items = rtc_writer_configs_with_rtcs
-                .iter()
-                .map(
-                    |(rtc_writer_config, rtc)| UpdateWriterConfigRequestBodyConfigItem {
-                        stream_id: rtc.id(),
-                        send_video: rtc_writer_config.send_video(),
-                        send_audio: rtc_writer_config.send_audio(),
-                        video_remb: rtc_writer_config.video_remb().map(|x| x as u32),
-                    },
-                )
-                .collect::<Vec<UpdateWriterConfigRequestBodyConfigItem>>();
-
-            let request = UpdateWriterConfigRequest {
-                session_id: backend.session_id(),
-                handle_id: backend.handle_id(),
-                body: UpdateWriterConfigRequestBody::new(items),
-            };
-            context
-                .janus_clients()
-                .get_or_insert(&backend)
-                .error(AppErrorKind::BackendClientCreationFailed)?
-                .writer_update(request)
-                .await
-                .error(AppErrorKind::BackendRequestFailed)?;
+        // Backends of other groups get updated by the instances owning them on the sync event.
+        if let Some(backend) = maybe_backend.filter(|backend| {
+            context.nats_client().is_none() || context.janus_clients().owns(backend)
+        }) {
+            update_backend(context, &backend, &rtc_writer_configs_with_rtcs).await?;
         }
 
+        config_sync::publish(context, &room, SyncEvent::WriterConfig { room_id }).await;
+
         // Respond to the agent and broadcast notification.
         let state = State::new(room.id(), &rtc_writer_configs_with_rtcs);
 
@@ -351,6 +385,43 @@ impl RequestHandler for UpdateHandler {
     }
 }
 
+/// Pushes the room's writer configs to the backend.
+pub(crate) async fn update_backend<C: GlobalContext + ?Sized>(
+    context: &C,
+    backend: &db::janus_backend::Object,
+    rtc_writer_configs_with_rtcs: &[(RtcWriterConfig, Rtc)],
+) -> Result<(), AppError> {
+    let items = rtc_writer_configs_with_rtcs
+        .iter()
+        .map(
+            |(rtc_writer_config, rtc)| UpdateWriterConfigRequestBodyConfigItem {
+                stream_id: rtc.id(),
+                send_video: rtc_writer_config.send_video(),
+                send_audio: rtc_writer_config.send_audio(),
+                video_remb: rtc_writer_config.video_remb().map(|x| x as u32),
+                max_video_bitrate: rtc_writer_config.max_video_bitrate().map(|x| x as u32),
+                max_audio_bitrate: rtc_writer_config.max_audio_bitrate().map(|x| x as u32),
+            },
+        )
+        .collect::<Vec<UpdateWriterConfigRequestBodyConfigItem>>();
+
+    let request = UpdateWriterConfigRequest {
+        session_id: backend.session_id(),
+        handle_id: backend.handle_id(),
+        body: UpdateWriterConfigRequestBody::new(items),
+    };
+
+    context
+        .janus_clients()
+        .get_or_insert(backend)
+        .error(AppErrorKind::BackendClientCreationFailed)?
+        .writer_update(request)
+        .await
+        .error(AppErrorKind::BackendRequestFailed)?;
+
+    Ok(())
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 
 #[derive(Debug, Deserialize)]
@@ -366,14 +437,7 @@ pub async fn read(
     tracing::Span::current().record("room_id", &tracing::field::display(room_id));
 
     let request = ReadRequest { room_id };
-    ReadHandler::handle(
-        &mut ctx.start_message(),
-        request,
-        RequestParams::Http {
-            agent_id: &agent_id,
-        },
-    )
-    .await
+    http::handle::<ReadHandler>(&ctx, &agent_id, request).await
 }
 
 pub struct ReadHandler;
@@ -382,6 +446,7 @@ pub struct ReadHandler;
 impl RequestHandler for ReadHandler {
     type Payload = ReadRequest;
     const ERROR_TITLE: &'static str = "Failed to read agent writer config";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::Presence);
 
     async fn handle<C: Context + Send + Sync>(
         context: &mut C,
@@ -393,6 +458,7 @@ impl RequestHandler for ReadHandler {
             let room = helpers::find_room_by_id(
                 payload.room_id,
                 helpers::RoomTimeRequirement::Open,
+                helpers::AudienceScope::caller(context, &reqp),
                 &mut conn,
             )
             .await?;
@@ -503,16 +569,22 @@ mod tests {
                 configs: vec![
                     StateConfigItem {
                         agent_id: agent2.agent_id().to_owned(),
+                        kind: RtcKind::Camera,
                         send_video: Some(true),
                         send_audio: Some(false),
                         video_remb: Some(300_000),
+                        max_video_bitrate: Some(500_000),
+                        max_audio_bitrate: Some(64_000),
                         send_audio_updated_by: None,
                     },
                     StateConfigItem {
                         agent_id: agent3.agent_id().to_owned(),
+                        kind: RtcKind::Camera,
                         send_video: Some(false),
                         send_audio: Some(false),
                         video_remb: None,
+                        max_video_bitrate: None,
+                        max_audio_bitrate: None,
                         send_audio_updated_by: None,
                     },
                 ],
@@ -536,6 +608,8 @@ mod tests {
             assert_eq!(agent2_config.send_video, Some(true));
             assert_eq!(agent2_config.send_audio, Some(false));
             assert_eq!(agent2_config.video_remb, Some(300_000));
+            assert_eq!(agent2_config.max_video_bitrate, Some(500_000));
+            assert_eq!(agent2_config.max_audio_bitrate, Some(64_000));
 
             assert_eq!(
                 agent2_config.send_audio_updated_by,
@@ -595,16 +669,22 @@ mod tests {
                 configs: vec![
                     StateConfigItem {
                         agent_id: agent4.agent_id().to_owned(),
+                        kind: RtcKind::Camera,
                         send_video: Some(true),
                         send_audio: Some(true),
                         video_remb: Some(1_000_000),
+                        max_video_bitrate: None,
+                        max_audio_bitrate: None,
                         send_audio_updated_by: None,
                     },
                     StateConfigItem {
                         agent_id: agent3.agent_id().to_owned(),
+                        kind: RtcKind::Camera,
                         send_video: None,
                         send_audio: Some(true),
                         video_remb: None,
+                        max_video_bitrate: None,
+                        max_audio_bitrate: None,
                         send_audio_updated_by: None,
                     },
                 ],
@@ -737,9 +817,12 @@ mod tests {
 
                     StateConfigItem {
                         agent_id: agent.agent_id().to_owned(),
+                        kind: RtcKind::Camera,
                         send_video: Some(false),
                         send_audio: Some(true),
                         video_remb: Some(300_000),
+                        max_video_bitrate: None,
+                        max_audio_bitrate: None,
                         send_audio_updated_by: None,
                     }
                 })
@@ -0,0 +1,404 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use axum::{
+    extract::{Extension, Path},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use svc_agent::{mqtt::ResponseStatus, AccountId, Addressable};
+use svc_utils::extractors::AgentIdExtractor;
+use tracing::info;
+use tracing_attributes::instrument;
+
+use crate::{
+    app::{
+        context::{AppContext, Context},
+        endpoint::prelude::*,
+        message_handler::http,
+        service_utils::{RequestParams, Response},
+    },
+    db,
+};
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct CreateRequest {
+    room_id: db::room::Id,
+    account_id: AccountId,
+    #[serde(default, with = "chrono::serde::ts_seconds_option")]
+    expires_at: Option<DateTime<Utc>>,
+    reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateFields {
+    account_id: AccountId,
+    #[serde(default, with = "chrono::serde::ts_seconds_option")]
+    expires_at: Option<DateTime<Utc>>,
+    reason: Option<String>,
+}
+
+pub async fn create(
+    Extension(ctx): Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<db::room::Id>,
+    Json(request): Json<CreateFields>,
+) -> RequestResult {
+    tracing::Span::current().record("room_id", tracing::field::display(room_id));
+
+    let request = CreateRequest {
+        room_id,
+        account_id: request.account_id,
+        expires_at: request.expires_at,
+        reason: request.reason,
+    };
+    http::handle::<CreateHandler>(&ctx, &agent_id, request).await
+}
+
+pub struct CreateHandler;
+
+#[async_trait]
+impl RequestHandler for CreateHandler {
+    type Payload = CreateRequest;
+    const ERROR_TITLE: &'static str = "Failed to ban account";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::Host);
+
+    #[instrument(skip(context, payload, reqp), fields(room_id = %payload.room_id))]
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        if payload.expires_at.is_some_and(|t| t <= Utc::now()) {
+            return Err(anyhow!("Ban expiration time is in the past"))
+                .error(AppErrorKind::InvalidPayload);
+        }
+
+        let room = find_room(context, payload.room_id, reqp).await?;
+        let authz_time = helpers::authorize_host(context, &room, reqp).await?;
+
+        let ban = {
+            let mut conn = context.get_conn().await?;
+
+            let mut q = db::classroom_ban::UpsertQuery::new(
+                room.classroom_id(),
+                &payload.account_id,
+                reqp.as_agent_id(),
+            );
+
+            if let Some(ref reason) = payload.reason {
+                q = q.reason(reason);
+            }
+
+            if let Some(expires_at) = payload.expires_at {
+                q = q.expires_at(expires_at);
+            }
+
+            q.execute(&mut conn).await?
+        };
+
+        info!(
+            classroom_id = %ban.classroom_id,
+            account_id = %ban.account_id,
+            banned_by = %ban.banned_by,
+            "Account banned"
+        );
+
+        context.room_authz().forget_account(&payload.account_id);
+
+        Ok(Response::new(
+            ResponseStatus::CREATED,
+            ban,
+            context.start_timestamp(),
+            authz_time,
+        ))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct ListRequest {
+    room_id: db::room::Id,
+}
+
+pub async fn list(
+    Extension(ctx): Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<db::room::Id>,
+) -> RequestResult {
+    tracing::Span::current().record("room_id", tracing::field::display(room_id));
+
+    let request = ListRequest { room_id };
+    http::handle::<ListHandler>(&ctx, &agent_id, request).await
+}
+
+pub struct ListHandler;
+
+#[async_trait]
+impl RequestHandler for ListHandler {
+    type Payload = ListRequest;
+    const ERROR_TITLE: &'static str = "Failed to list bans";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::Host);
+
+    #[instrument(skip(context, payload, reqp), fields(room_id = %payload.room_id))]
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let room = find_room(context, payload.room_id, reqp).await?;
+        let authz_time = helpers::authorize_host(context, &room, reqp).await?;
+
+        let bans = {
+            let mut conn = context.get_conn().await?;
+
+            db::classroom_ban::ListQuery::new(room.classroom_id())
+                .execute(&mut conn)
+                .await?
+        };
+
+        Ok(Response::new(
+            ResponseStatus::OK,
+            bans,
+            context.start_timestamp(),
+            authz_time,
+        ))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteRequest {
+    room_id: db::room::Id,
+    account_id: AccountId,
+}
+
+pub async fn delete(
+    Extension(ctx): Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path((room_id, account_id)): Path<(db::room::Id, AccountId)>,
+) -> RequestResult {
+    tracing::Span::current().record("room_id", tracing::field::display(room_id));
+
+    let request = DeleteRequest {
+        room_id,
+        account_id,
+    };
+    http::handle::<DeleteHandler>(&ctx, &agent_id, request).await
+}
+
+pub struct DeleteHandler;
+
+#[async_trait]
+impl RequestHandler for DeleteHandler {
+    type Payload = DeleteRequest;
+    const ERROR_TITLE: &'static str = "Failed to lift ban";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::Host);
+
+    #[instrument(skip(context, payload, reqp), fields(room_id = %payload.room_id))]
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let room = find_room(context, payload.room_id, reqp).await?;
+        let authz_time = helpers::authorize_host(context, &room, reqp).await?;
+
+        let ban = {
+            let mut conn = context.get_conn().await?;
+
+            db::classroom_ban::delete(
+                room.classroom_id(),
+                &payload.account_id,
+                reqp.as_agent_id(),
+                &mut conn,
+            )
+            .await?
+            .ok_or_else(|| anyhow!("Account is not banned in the classroom"))
+            .error(AppErrorKind::BanNotFound)?
+        };
+
+        info!(
+            classroom_id = %ban.classroom_id,
+            account_id = %ban.account_id,
+            "Account unbanned"
+        );
+
+        Ok(Response::new(
+            ResponseStatus::OK,
+            ban,
+            context.start_timestamp(),
+            authz_time,
+        ))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+async fn find_room<C: Context>(
+    context: &mut C,
+    room_id: db::room::Id,
+    reqp: RequestParams<'_>,
+) -> Result<db::room::Object, AppError> {
+    let mut conn = context.get_conn().await?;
+
+    helpers::find_room_by_id(
+        room_id,
+        helpers::RoomTimeRequirement::NotClosed,
+        helpers::AudienceScope::caller(context, &reqp),
+        &mut conn,
+    )
+    .await
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Bound;
+
+    use chrono::Duration;
+
+    use crate::test_helpers::{db::TestDb, prelude::*};
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn ban_lifecycle(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let host = TestAgent::new("web", "host", USR_AUDIENCE);
+        let user = TestAgent::new("web", "user", USR_AUDIENCE);
+
+        let mut conn = db.get_conn().await;
+        let room = shared_helpers::insert_room(&mut conn).await;
+
+        // Another room of the same classroom series.
+        let next_room = db::room::InsertQuery::new(
+            db::room::RoomTime::new(Bound::Included(Utc::now()), Bound::Unbounded)
+                .expect("Invalid room time"),
+            USR_AUDIENCE,
+            db::rtc::SharingPolicy::Shared,
+            room.classroom_id(),
+        )
+        .execute(&mut conn)
+        .await
+        .expect("Failed to insert room");
+
+        let mut authz = TestAuthz::new();
+        let classroom_id = room.classroom_id().to_string();
+        authz.allow(
+            host.account_id(),
+            vec!["classrooms", &classroom_id],
+            "update",
+        );
+        let mut context = TestContext::new(db, authz).await;
+
+        let payload = CreateRequest {
+            room_id: room.id(),
+            account_id: user.account_id().to_owned(),
+            expires_at: Some(Utc::now() + Duration::hours(1)),
+            reason: Some("spam".to_owned()),
+        };
+
+        let messages = handle_request::<CreateHandler>(&mut context, &host, payload)
+            .await
+            .expect("Ban creation failed");
+
+        let (ban, respp, _) = find_response::<db::classroom_ban::Object>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::CREATED);
+        assert_eq!(&ban.account_id, user.account_id());
+
+        // The ban applies to any room of the classroom.
+        let ban =
+            db::classroom_ban::find_active(next_room.classroom_id(), user.account_id(), &mut conn)
+                .await
+                .expect("Failed to find ban")
+                .expect("Ban not found");
+
+        let err = helpers::check_ban(&next_room, user.agent_id(), &mut conn)
+            .await
+            .expect_err("Unexpected success checking a banned agent");
+
+        assert_eq!(err.kind(), "agent_banned");
+        assert_eq!(ban.reason.as_deref(), Some("spam"));
+
+        let payload = ListRequest {
+            room_id: next_room.id(),
+        };
+
+        let messages = handle_request::<ListHandler>(&mut context, &host, payload)
+            .await
+            .expect("Bans listing failed");
+
+        let (bans, _, _) = find_response::<Vec<db::classroom_ban::Object>>(messages.as_slice());
+        assert_eq!(bans.len(), 1);
+
+        let payload = DeleteRequest {
+            room_id: room.id(),
+            account_id: user.account_id().to_owned(),
+        };
+
+        handle_request::<DeleteHandler>(&mut context, &host, payload)
+            .await
+            .expect("Ban removal failed");
+
+        helpers::check_ban(&next_room, user.agent_id(), &mut conn)
+            .await
+            .expect("Agent is still banned");
+
+        // Both actions are audited.
+        let audit: Vec<(String,)> = sqlx::query_as(
+            "SELECT action::text FROM classroom_ban_audit WHERE classroom_id = $1 ORDER BY created_at",
+        )
+        .bind(room.classroom_id())
+        .fetch_all(&mut conn)
+        .await
+        .expect("Failed to read ban audit");
+
+        assert_eq!(audit, vec![("ban".to_owned(),), ("unban".to_owned(),)]);
+
+        let payload = DeleteRequest {
+            room_id: room.id(),
+            account_id: user.account_id().to_owned(),
+        };
+
+        let err = handle_request::<DeleteHandler>(&mut context, &host, payload)
+            .await
+            .expect_err("Unexpected success removing missing ban");
+
+        assert_eq!(err.kind(), "ban_not_found");
+    }
+
+    #[sqlx::test]
+    async fn ban_not_authorized(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let user = TestAgent::new("web", "user", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            shared_helpers::insert_room(&mut conn).await
+        };
+
+        let mut context = TestContext::new(db, TestAuthz::new()).await;
+
+        let payload = CreateRequest {
+            room_id: room.id(),
+            account_id: user.account_id().to_owned(),
+            expires_at: None,
+            reason: None,
+        };
+
+        let err = handle_request::<CreateHandler>(&mut context, &user, payload)
+            .await
+            .expect_err("Unexpected success banning without permission");
+
+        assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+        assert_eq!(err.kind(), "access_denied");
+    }
+}
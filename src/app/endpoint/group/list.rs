@@ -1,8 +1,9 @@
 use crate::{
     app::{
         context::{AppContext, Context},
-        endpoint::{helpers, prelude::AppErrorKind, RequestHandler, RequestResult},
+        endpoint::{helpers, prelude::AppErrorKind, AuthzIntent, RequestHandler, RequestResult},
         error::ErrorExt,
+        message_handler::http,
         metrics::HistogramExt,
         service_utils::{RequestParams, Response},
     },
@@ -44,14 +45,7 @@ pub async fn list(
         within_group: query.unwrap_or_default().within_group,
     };
 
-    Handler::handle(
-        &mut ctx.start_message(),
-        payload,
-        RequestParams::Http {
-            agent_id: &agent_id,
-        },
-    )
-    .await
+    http::handle::<Handler>(&ctx, &agent_id, payload).await
 }
 
 pub struct Handler;
@@ -60,6 +54,7 @@ pub struct Handler;
 impl RequestHandler for Handler {
     type Payload = Payload;
     const ERROR_TITLE: &'static str = "Failed to get groups";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::CLASSROOM_READ);
 
     async fn handle<C: Context + Send + Sync>(
         context: &mut C,
@@ -71,8 +66,13 @@ impl RequestHandler for Handler {
 
         let room = {
             let mut conn = context.get_conn().await?;
-            helpers::find_room_by_id(room_id, helpers::RoomTimeRequirement::NotClosed, &mut conn)
-                .await?
+            helpers::find_room_by_id(
+                room_id,
+                helpers::RoomTimeRequirement::NotClosed,
+                helpers::AudienceScope::caller(context, &reqp),
+                &mut conn,
+            )
+            .await?
         };
 
         tracing::Span::current().record(
@@ -8,6 +8,7 @@ use crate::{
         },
         error::ErrorExt,
         group_reader_config,
+        message_handler::http,
         metrics::HistogramExt,
         service_utils::{RequestParams, Response},
         stage::{self, video_group::VideoGroupUpdateJanusConfig, AppStage},
@@ -47,6 +48,8 @@ pub async fn update(
 ) -> RequestResult {
     tracing::Span::current().record("room_id", &tracing::field::display(room_id));
 
+    let _in_flight = http::admit(&ctx, false)?;
+
     Handler::handle(
         ctx,
         Payload { room_id, groups },
@@ -73,8 +76,13 @@ impl Handler {
 
         let room = {
             let mut conn = context.get_conn().await?;
-            helpers::find_room_by_id(room_id, helpers::RoomTimeRequirement::NotClosed, &mut conn)
-                .await?
+            helpers::find_room_by_id(
+                room_id,
+                helpers::RoomTimeRequirement::NotClosed,
+                helpers::AudienceScope::caller(&*context, &reqp),
+                &mut conn,
+            )
+            .await?
         };
 
         tracing::Span::current().record(
@@ -149,13 +157,16 @@ impl Handler {
 
                     // Generate config items for janus
                     let items = configs
+                        .delta
                         .into_iter()
-                        .map(|((rtc_id, agent_id), value)| {
+                        .map(|((rtc_id, agent_id), (video, audio))| {
                             UpdateReaderConfigRequestBodyConfigItem {
                                 reader_id: agent_id,
                                 stream_id: rtc_id,
-                                receive_video: value,
-                                receive_audio: value,
+                                receive_video: video,
+                                receive_audio: audio,
+                                spatial_layer: None,
+                                temporal_layer: None,
                             }
                         })
                         .collect();
@@ -166,6 +177,7 @@ impl Handler {
                         room.id(),
                         backend_id,
                         items,
+                        configs.full_size,
                     );
 
                     let serialized_stage = serde_json::to_value(init_stage)
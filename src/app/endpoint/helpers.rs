@@ -1,10 +1,12 @@
-use std::ops::Bound;
-
 use crate::{
     app::{
+        context::{Context as AppContext, GlobalContext},
         error::{Error as AppError, ErrorExt, ErrorKind as AppErrorKind},
+        room_authz::ActionClass,
+        service_utils::RequestParams,
         API_VERSION,
     },
+    authz::AuthzObject,
     db,
     db::room::Object as Room,
 };
@@ -16,11 +18,16 @@ use svc_agent::{
         IncomingRequestProperties, IntoPublishableMessage, OutgoingEvent, OutgoingEventProperties,
         OutgoingResponse, ResponseStatus, ShortTermTimingProperties, TrackingProperties,
     },
-    AgentId,
+    AccountId, Addressable, AgentId, Authenticable,
 };
+use tracing::{error, warn};
+use uuid::Uuid;
 
 ///////////////////////////////////////////////////////////////////////////////
 
+/// Upper bound of `limit` shared by the list requests of the clients.
+pub const LIST_MAX_LIMIT: i64 = 25;
+
 pub fn build_response(
     status: ResponseStatus,
     payload: impl Serialize + Send + Sync + 'static,
@@ -51,6 +58,24 @@ pub fn build_notification(
     Box::new(OutgoingEvent::broadcast(payload, props, path))
 }
 
+/// Takes the room's metrics snapshot on close. Failures are only logged to not prevent the room
+/// from closing.
+pub async fn summarize_room(
+    room_id: db::room::Id,
+    conn: &mut sqlx::PgConnection,
+) -> Option<db::room_summary::Object> {
+    match db::room_summary::InsertQuery::new(room_id)
+        .execute(conn)
+        .await
+    {
+        Ok(summary) => Some(summary),
+        Err(err) => {
+            error!(?err, %room_id, "Failed to summarize room");
+            None
+        }
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 
 pub enum RoomTimeRequirement {
@@ -60,27 +85,142 @@ pub enum RoomTimeRequirement {
     Open,
 }
 
+/// Audience whose rooms a caller may reach.
+///
+/// Tenants are separated by room audience. Authorization is the primary check but rooms are also
+/// scoped right where they get fetched so that a gap in the former doesn't expose another tenant's
+/// data. Service accounts of our own audience serve all tenants.
+#[derive(Clone, Copy, Debug)]
+pub enum AudienceScope<'a> {
+    /// Internal processing not driven by any caller, e.g. Janus events.
+    Unrestricted,
+    Caller {
+        account_id: &'a AccountId,
+        service_audience: &'a str,
+    },
+}
+
+impl<'a> AudienceScope<'a> {
+    pub fn caller<C, A>(context: &'a C, caller: &'a A) -> Self
+    where
+        C: GlobalContext + ?Sized,
+        A: Authenticable,
+    {
+        Self::Caller {
+            account_id: caller.as_account_id(),
+            service_audience: context.agent_id().as_account_id().audience(),
+        }
+    }
+
+    pub fn allows(&self, audience: &str) -> bool {
+        match self {
+            Self::Unrestricted => true,
+            Self::Caller {
+                account_id,
+                service_audience,
+            } => account_id.audience() == audience || account_id.audience() == *service_audience,
+        }
+    }
+}
+
 pub async fn find_room_by_id(
     id: db::room::Id,
     opening_requirement: RoomTimeRequirement,
+    scope: AudienceScope<'_>,
     conn: &mut sqlx::PgConnection,
 ) -> Result<db::room::Object, AppError> {
     let query = db::room::FindQuery::new(id);
-    find_room(query, opening_requirement, conn).await
+    find_room(query, opening_requirement, scope, conn).await
 }
 
 pub async fn find_room_by_rtc_id(
     rtc_id: db::rtc::Id,
     opening_requirement: RoomTimeRequirement,
+    scope: AudienceScope<'_>,
     conn: &mut sqlx::PgConnection,
 ) -> Result<db::room::Object, AppError> {
     let query = db::room::FindByRtcIdQuery::new(rtc_id);
-    find_room(query, opening_requirement, conn).await
+    find_room(query, opening_requirement, scope, conn).await
+}
+
+/// Rooms of a classroom looked through to pick the one the classroom stands for.
+const CLASSROOM_ROOMS_LIMIT: i64 = 10;
+
+/// A room addressed either by its id or by the classroom it belongs to. Callers knowing only
+/// the classroom get its room not closed yet or the latest one if all of them are closed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RoomRef {
+    Id(db::room::Id),
+    Classroom(Uuid),
+}
+
+impl RoomRef {
+    /// The room id takes precedence when a payload has both fields.
+    pub fn new(id: Option<db::room::Id>, classroom_id: Option<Uuid>) -> Result<Self, AppError> {
+        match (id, classroom_id) {
+            (Some(id), _) => Ok(Self::Id(id)),
+            (None, Some(classroom_id)) => Ok(Self::Classroom(classroom_id)),
+            (None, None) => Err(anyhow!("Either room id or classroom_id is required"))
+                .error(AppErrorKind::InvalidPayload),
+        }
+    }
+}
+
+pub async fn find_room_by_ref(
+    room_ref: RoomRef,
+    opening_requirement: RoomTimeRequirement,
+    scope: AudienceScope<'_>,
+    conn: &mut sqlx::PgConnection,
+) -> Result<db::room::Object, AppError> {
+    match room_ref {
+        RoomRef::Id(id) => find_room_by_id(id, opening_requirement, scope, conn).await,
+        RoomRef::Classroom(classroom_id) => {
+            find_room_by_classroom_id(classroom_id, opening_requirement, scope, conn).await
+        }
+    }
+}
+
+pub async fn find_room_by_classroom_id(
+    classroom_id: Uuid,
+    opening_requirement: RoomTimeRequirement,
+    scope: AudienceScope<'_>,
+    conn: &mut sqlx::PgConnection,
+) -> Result<db::room::Object, AppError> {
+    let rooms = db::room::FindByClassroomIdQuery::new(classroom_id, CLASSROOM_ROOMS_LIMIT)
+        .execute(conn)
+        .await?
+        .into_iter()
+        .filter(|room| scope.allows(room.audience()))
+        .collect::<Vec<_>>();
+
+    let not_closed = rooms
+        .iter()
+        .filter(|room| !room.is_closed())
+        .map(|room| room.id().to_string())
+        .collect::<Vec<_>>();
+
+    if not_closed.len() > 1 {
+        return Err(AppError::new(
+            AppErrorKind::AmbiguousClassroom,
+            anyhow!("Classroom has {} rooms not closed yet", not_closed.len()),
+        )
+        .with_extra("room_ids", not_closed.join(",")));
+    }
+
+    // Rooms not closed yet come first so it's either the only one of them or the latest one.
+    let room = rooms
+        .into_iter()
+        .next()
+        .context("Room not found")
+        .error(AppErrorKind::RoomNotFound)?;
+
+    check_room_time(room, opening_requirement)
 }
 
 async fn find_room<Q>(
     query: Q,
     opening_requirement: RoomTimeRequirement,
+    scope: AudienceScope<'_>,
     conn: &mut sqlx::PgConnection,
 ) -> Result<Room, AppError>
 where
@@ -92,6 +232,21 @@ where
         .context("Room not found")
         .error(AppErrorKind::RoomNotFound)?;
 
+    if !scope.allows(room.audience()) {
+        warn!(
+            room_id = %room.id(),
+            room_audience = room.audience(),
+            ?scope,
+            "Rejected access to a room of another audience"
+        );
+
+        return Err(anyhow!("Room not found")).error(AppErrorKind::RoomNotFound);
+    }
+
+    check_room_time(room, opening_requirement)
+}
+
+fn check_room_time(room: Room, opening_requirement: RoomTimeRequirement) -> Result<Room, AppError> {
     match opening_requirement {
         // Room time doesn't matter.
         RoomTimeRequirement::Any => Ok(room),
@@ -99,54 +254,41 @@ where
         // Rooms without closing time are fine.
         // Rooms without opening time are forbidden.
         RoomTimeRequirement::NotClosed => {
-            let now = Utc::now();
+            let time = room.time();
 
-            match room.time() {
-                (Bound::Unbounded, _) => {
-                    Err(anyhow!("Room has no opening time")).error(AppErrorKind::RoomClosed)
-                }
-                (_, Bound::Included(dt)) | (_, Bound::Excluded(dt)) if dt < now => {
-                    Err(anyhow!("Room closed")).error(AppErrorKind::RoomClosed)
-                }
-                _ => Ok(room),
+            if time.opened_at().is_none() {
+                Err(anyhow!("Room has no opening time")).error(AppErrorKind::RoomClosed)
+            } else if time.is_closed_at(Utc::now()) {
+                Err(anyhow!("Room closed")).error(AppErrorKind::RoomClosed)
+            } else {
+                Ok(room)
             }
         }
         // Current time must be before room closing, including not yet opened rooms.
         // Rooms without closing time are fine.
         // Rooms without opening time are fine.
         RoomTimeRequirement::NotClosedOrUnboundedOpen => {
-            let now = Utc::now();
-
-            match room.time() {
-                (_, Bound::Included(dt)) | (_, Bound::Excluded(dt)) if dt < now => {
-                    Err(anyhow!("Room closed")).error(AppErrorKind::RoomClosed)
-                }
-                _ => Ok(room),
+            if room.time().is_closed_at(Utc::now()) {
+                Err(anyhow!("Room closed")).error(AppErrorKind::RoomClosed)
+            } else {
+                Ok(room)
             }
         }
         // Current time must be exactly in the room's time range.
         RoomTimeRequirement::Open => {
             let now = Utc::now();
-            let (opened_at, closed_at) = room.time();
+            let time = room.time();
 
-            match opened_at {
-                Bound::Unbounded => {
-                    Err(anyhow!("Room has no opening time")).error(AppErrorKind::RoomClosed)
+            match time.opened_at() {
+                None => Err(anyhow!("Room has no opening time")).error(AppErrorKind::RoomClosed),
+                Some(_) if time.is_closed_at(now) => {
+                    Err(anyhow!("Room closed")).error(AppErrorKind::RoomClosed)
                 }
-                Bound::Included(dt) | Bound::Excluded(dt) if dt >= now => {
+                Some(_) if !time.is_open_at(now) => {
                     Err(anyhow!("Room not opened")).error(AppErrorKind::RoomClosed)
                 }
-                _ => Ok(()),
-            }?;
-
-            match closed_at {
-                Bound::Included(dt) | Bound::Excluded(dt) if dt < now => {
-                    Err(anyhow!("Room closed")).error(AppErrorKind::RoomClosed)
-                }
-                _ => Ok(()),
-            }?;
-
-            Ok(room)
+                Some(_) => Ok(room),
+            }
         }
     }
 }
@@ -156,15 +298,625 @@ pub async fn check_room_presence(
     agent_id: &AgentId,
     conn: &mut sqlx::PgConnection,
 ) -> Result<(), AppError> {
-    let results = db::agent::ListQuery::new()
+    find_present_agent(room, agent_id, conn).await.map(|_| ())
+}
+
+pub async fn find_present_agent(
+    room: &db::room::Object,
+    agent_id: &AgentId,
+    conn: &mut sqlx::PgConnection,
+) -> Result<db::agent::Object, AppError> {
+    db::agent::ListQuery::new()
+        .status(db::agent::Status::Ready)
         .room_id(room.id())
         .agent_id(agent_id)
         .execute(conn)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("Agent is not online in the room"))
+        .error(AppErrorKind::AgentNotEnteredTheRoom)
+}
+
+/// Fails if the agent's account is banned in any room of the room's classroom.
+pub async fn check_ban(
+    room: &Room,
+    agent_id: &AgentId,
+    conn: &mut sqlx::PgConnection,
+) -> Result<(), AppError> {
+    let ban =
+        db::classroom_ban::find_active(room.classroom_id(), agent_id.as_account_id(), conn).await?;
+
+    match ban {
+        None => Ok(()),
+        Some(ban) => {
+            let err = AppError::new(
+                AppErrorKind::AgentBanned,
+                anyhow!(
+                    "Account {} is banned in classroom {}",
+                    ban.account_id,
+                    ban.classroom_id
+                ),
+            );
+
+            match ban.expires_at {
+                Some(expires_at) => {
+                    Err(err.with_extra("expires_at", expires_at.timestamp().to_string()))
+                }
+                None => Err(err),
+            }
+        }
+    }
+}
+
+/// Fails if the agent waits in the lobby of the room and hasn't been admitted by a host yet.
+pub async fn check_admission(
+    room: &Room,
+    agent_id: &AgentId,
+    conn: &mut sqlx::PgConnection,
+) -> Result<(), AppError> {
+    let pending = db::agent::ListQuery::new()
+        .room_id(room.id())
+        .agent_id(agent_id)
+        .status(db::agent::Status::Pending)
+        .limit(1)
+        .execute(conn)
         .await?;
 
-    if results.is_empty() {
-        Err(anyhow!("Agent is not online in the room")).error(AppErrorKind::AgentNotEnteredTheRoom)
+    if pending.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "Agent {} waits in the lobby of room {}",
+            agent_id,
+            room.id()
+        ))
+        .error(AppErrorKind::AgentNotAdmitted)
+    }
+}
+
+/// Checks the room has a seat for the entering session. Must be called in the transaction
+/// registering the agent since the room stays locked until it ends so that concurrent entries
+/// can't take the last seat twice.
+pub async fn reserve_room_seat(
+    room: &Room,
+    session_key: db::agent_session::SessionKey,
+    conn: &mut sqlx::PgConnection,
+) -> Result<(), AppError> {
+    let max_agents = match room.max_agents() {
+        Some(max_agents) => max_agents,
+        None => return Ok(()),
+    };
+
+    db::room::lock(room.id(), conn).await?;
+    let occupants = db::agent::count_occupants(room.id(), Some(session_key), conn).await?;
+
+    if occupants < max_agents as i64 {
+        Ok(())
     } else {
+        Err(room_full(room, max_agents))
+    }
+}
+
+/// Checks the agent's session is among the first `max_agents` ones in the room. Others may get
+/// there when the limit gets lowered or agents are admitted from the lobby over it.
+pub async fn check_room_capacity(
+    room: &Room,
+    agent_id: &AgentId,
+    conn: &mut sqlx::PgConnection,
+) -> Result<(), AppError> {
+    let max_agents = match room.max_agents() {
+        Some(max_agents) => max_agents,
+        None => return Ok(()),
+    };
+
+    let ahead = db::agent::count_occupants_ahead(room.id(), agent_id, conn).await?;
+
+    if ahead < max_agents as i64 {
         Ok(())
+    } else {
+        Err(room_full(room, max_agents))
+    }
+}
+
+fn room_full(room: &Room, max_agents: i32) -> AppError {
+    AppError::new(
+        AppErrorKind::RoomFull,
+        anyhow!("Room {} is full", room.id()),
+    )
+    .with_extra("max_agents", max_agents.to_string())
+}
+
+#[derive(Debug, Serialize)]
+struct ConnectionLimitExceeded<'a> {
+    account_id: &'a AccountId,
+    classroom_id: uuid::Uuid,
+    room_id: db::room::Id,
+    agents: i64,
+    max_agents: usize,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    expires_at: DateTime<Utc>,
+}
+
+/// Round trip bucket the balancer weights backends with for the room, see `JanusRttConfig`.
+pub fn rtt_bucket<C: GlobalContext + ?Sized>(context: &C, room: &Room) -> Option<i32> {
+    context
+        .config()
+        .janus_rtt
+        .as_ref()?
+        .balancer_bucket(room.audience())
+}
+
+/// Fails if the agent's account is present in open rooms from more agents than its audience
+/// allows. Such an account is banned in the room's classroom for a while.
+pub async fn check_connection_limit<C: GlobalContext + ?Sized>(
+    context: &C,
+    room: &Room,
+    agent_id: &AgentId,
+    conn: &mut sqlx::PgConnection,
+) -> Result<(), AppError> {
+    let config = match context.config().connection_limit.get(room.audience()) {
+        Some(config) => config,
+        None => return Ok(()),
+    };
+
+    let agents = db::agent::count_account_agents(agent_id, conn).await? + 1;
+
+    if agents <= config.max_agents as i64 {
+        return Ok(());
+    }
+
+    let account_id = agent_id.as_account_id();
+    let expires_at = Utc::now()
+        + Duration::from_std(config.ban_duration).unwrap_or_else(|_| Duration::max_value());
+
+    // The ban goes to the classroom ban audit along with the service as the one who banned.
+    db::classroom_ban::UpsertQuery::new(room.classroom_id(), account_id, context.agent_id())
+        .reason("connection_limit_exceeded")
+        .expires_at(expires_at)
+        .execute(conn)
+        .await?;
+
+    warn!(
+        %account_id,
+        classroom_id = %room.classroom_id(),
+        agents,
+        max_agents = config.max_agents,
+        "Account exceeded connection limit"
+    );
+
+    if config.notify {
+        let payload = ConnectionLimitExceeded {
+            account_id,
+            classroom_id: room.classroom_id(),
+            room_id: room.id(),
+            agents,
+            max_agents: config.max_agents,
+            expires_at,
+        };
+
+        let topic = format!("audiences/{}/events", room.audience());
+
+        let result = context.mqtt_client().lock().publish_payload(
+            "agent.connection_limit_exceeded",
+            &topic,
+            serde_json::json!(payload),
+        );
+
+        if let Err(err) = result {
+            error!(?err, %account_id, "Failed to publish connection limit event");
+        }
+    }
+
+    Err(AppError::new(
+        AppErrorKind::ConnectionLimitExceeded,
+        anyhow!(
+            "Account {} is used from {} agents while {} are allowed",
+            account_id,
+            agents,
+            config.max_agents
+        ),
+    )
+    .with_extra("max_agents", config.max_agents.to_string())
+    .with_extra("expires_at", expires_at.timestamp().to_string()))
+}
+
+/// Whether the agent is one of the room's hosts.
+async fn is_room_host<C: GlobalContext + ?Sized>(
+    context: &C,
+    room: &Room,
+    agent_id: &AgentId,
+) -> Result<bool, AppError> {
+    let mut conn = context.get_conn().await?;
+    let is_host = db::room_host::is_host(room.id(), agent_id, &mut conn).await?;
+    Ok(is_host)
+}
+
+/// Authorizes the agent on the object within the room unless it has been allowed the same
+/// class of actions in the room recently, see `RoomAuthzCache`. A reused decision takes no time.
+pub async fn authorize_in_room<C: GlobalContext + ?Sized>(
+    context: &C,
+    room: &Room,
+    reqp: RequestParams<'_>,
+    object: AuthzObject,
+    class: ActionClass,
+) -> Result<Duration, AppError> {
+    let now = std::time::Instant::now();
+
+    if context
+        .room_authz()
+        .is_allowed(reqp.as_agent_id(), room.id(), class, now)
+    {
+        return Ok(Duration::zero());
+    }
+
+    let authz_time = context
+        .authz()
+        .authorize(
+            room.audience().into(),
+            reqp,
+            object.into(),
+            class.action().into(),
+        )
+        .await?;
+    context.metrics().observe_auth(authz_time);
+
+    context
+        .room_authz()
+        .allow(reqp.as_agent_id(), room.id(), class, now);
+
+    Ok(authz_time)
+}
+
+/// What an agent is about to do with the media of an RTC.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MediaAction {
+    Listen,
+    Publish,
+}
+
+impl MediaAction {
+    pub fn fine_grained(self) -> &'static str {
+        match self {
+            MediaAction::Listen => "listen",
+            MediaAction::Publish => "publish",
+        }
+    }
+
+    pub fn coarse(self) -> &'static str {
+        match self {
+            MediaAction::Listen => "read",
+            MediaAction::Publish => "update",
+        }
+    }
+}
+
+/// Whether the room's audience authorizes listening and publishing with actions of their own.
+pub fn is_fine_grained_rtc_authz<C: GlobalContext + ?Sized>(context: &C, room: &Room) -> bool {
+    context
+        .config()
+        .rtc_authz
+        .get(room.audience())
+        .map_or(false, |config| config.fine_grained)
+}
+
+/// Authorizes listening to or publishing into an RTC of the room. Audiences of the fine-grained
+/// model check `listen` or `publish` and, with `fallback`, the coarse action once it's denied.
+/// Other audiences check `read` or `update` as before.
+pub async fn authorize_media<C, A>(
+    context: &C,
+    room: &Room,
+    subject: A,
+    object: AuthzObject,
+    action: MediaAction,
+) -> Result<Duration, AppError>
+where
+    C: GlobalContext + ?Sized,
+    A: Authenticable + Clone,
+{
+    let config = context
+        .config()
+        .rtc_authz
+        .get(room.audience())
+        .filter(|config| config.fine_grained);
+
+    let config = match config {
+        Some(config) => config,
+        None => {
+            let authz_time = context
+                .authz()
+                .authorize(
+                    room.audience().into(),
+                    subject,
+                    object.into(),
+                    action.coarse().into(),
+                )
+                .await?;
+            context.metrics().observe_auth(authz_time);
+            return Ok(authz_time);
+        }
+    };
+
+    let result = context
+        .authz()
+        .authorize(
+            room.audience().into(),
+            subject.clone(),
+            object.clone().into(),
+            action.fine_grained().into(),
+        )
+        .await
+        .map_err(AppError::from);
+
+    let authz_time = match result {
+        Err(err) if config.fallback && matches!(err.error_kind(), AppErrorKind::AccessDenied) => {
+            context
+                .authz()
+                .authorize(
+                    room.audience().into(),
+                    subject,
+                    object.into(),
+                    action.coarse().into(),
+                )
+                .await?
+        }
+        result => result?,
+    };
+
+    context.metrics().observe_auth(authz_time);
+    Ok(authz_time)
+}
+
+/// The room's hosts are allowed to manage it without authorization, others need the right to
+/// update the room.
+pub async fn authorize_host<C: AppContext>(
+    context: &mut C,
+    room: &Room,
+    reqp: RequestParams<'_>,
+) -> Result<Option<Duration>, AppError> {
+    if is_room_host(context, room, reqp.as_agent_id()).await? {
+        return Ok(None);
+    }
+
+    let classroom_id = room.classroom_id().to_string();
+    let object = AuthzObject::new(&["classrooms", &classroom_id]).into();
+
+    let authz_time = context
+        .authz()
+        .authorize(room.audience().into(), reqp, object, "update".into())
+        .await?;
+    context.metrics().observe_auth(authz_time);
+
+    Ok(Some(authz_time))
+}
+
+/// Whether the agent is one of the room's hosts or may update its classroom, unlike
+/// `authorize_host` reporting the denial instead of failing.
+pub async fn is_host<C: GlobalContext + ?Sized>(
+    context: &C,
+    room: &Room,
+    reqp: RequestParams<'_>,
+) -> Result<bool, AppError> {
+    if is_room_host(context, room, reqp.as_agent_id()).await? {
+        return Ok(true);
+    }
+
+    let classroom_id = room.classroom_id().to_string();
+    let object = AuthzObject::new(&["classrooms", &classroom_id]).into();
+
+    match context
+        .authz()
+        .authorize(room.audience().into(), reqp, object, "update".into())
+        .await
+    {
+        Ok(authz_time) => {
+            context.metrics().observe_auth(authz_time);
+            Ok(true)
+        }
+        Err(err) => match AppError::from(err) {
+            err if err.error_kind() == AppErrorKind::AccessDenied => Ok(false),
+            err => Err(err),
+        },
+    }
+}
+
+/// Backend capability required to apply `audio_processing` of the room.
+const AUDIO_PROCESSING_CAPABILITY: &str = "audio_processing";
+
+/// Returns the room's audio processing options to pass to the backend when publishing.
+/// Fails if they're enabled but the backend hasn't reported support for them.
+pub async fn audio_processing(
+    room: &Room,
+    backend_id: &AgentId,
+    conn: &mut sqlx::PgConnection,
+) -> Result<Option<db::room::AudioProcessing>, AppError> {
+    let audio_processing = match room.audio_processing() {
+        Some(audio_processing) if audio_processing.is_enabled() => audio_processing,
+        _ => return Ok(None),
+    };
+
+    let capabilities = db::janus_backend::capabilities(backend_id, conn).await?;
+
+    if capabilities
+        .iter()
+        .any(|c| c == AUDIO_PROCESSING_CAPABILITY)
+    {
+        Ok(Some(audio_processing))
+    } else {
+        Err(anyhow!(
+            "Backend {} doesn't support audio processing",
+            backend_id
+        ))
+        .error(AppErrorKind::AudioProcessingUnsupported)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Bound;
+
+    use super::*;
+    use crate::{
+        backend::janus::client::{HandleId, SessionId},
+        config::RtcAuthzConfig,
+        db::room::AudioProcessing,
+        test_helpers::{db::TestDb, prelude::*},
+    };
+
+    #[sqlx::test]
+    async fn find_room_scoped_by_audience(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let mut conn = db.get_conn().await;
+        let room = shared_helpers::insert_room(&mut conn).await;
+        let context = TestContext::new(db, TestAuthz::new()).await;
+
+        let user = TestAgent::new("web", "user", USR_AUDIENCE);
+        let service = TestAgent::new("alpha", "dispatcher", SVC_AUDIENCE);
+        let stranger = TestAgent::new("web", "user", "dev.other.example.org");
+
+        for scope in [
+            AudienceScope::caller(&context, user.agent_id()),
+            AudienceScope::caller(&context, service.agent_id()),
+            AudienceScope::Unrestricted,
+        ] {
+            find_room_by_id(room.id(), RoomTimeRequirement::Any, scope, &mut conn)
+                .await
+                .expect("Failed to find room");
+        }
+
+        let scope = AudienceScope::caller(&context, stranger.agent_id());
+
+        let err = find_room_by_id(room.id(), RoomTimeRequirement::Any, scope, &mut conn)
+            .await
+            .expect_err("Unexpected access to a room of another audience");
+
+        assert_eq!(err.kind(), "room_not_found");
+    }
+
+    #[sqlx::test]
+    async fn authorize_media_by_audience_model(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let mut conn = db.get_conn().await;
+        let room = shared_helpers::insert_room(&mut conn).await;
+        let rtc = shared_helpers::insert_rtc_with_room(&mut conn, &room).await;
+
+        let listener = TestAgent::new("web", "listener", USR_AUDIENCE);
+        let legacy = TestAgent::new("web", "legacy", USR_AUDIENCE);
+        let classroom_id = room.classroom_id().to_string();
+        let rtc_id = rtc.id().to_string();
+        let object = vec!["classrooms", &classroom_id, "rtcs", &rtc_id];
+
+        let mut authz = TestAuthz::new();
+        authz.allow(listener.account_id(), object.clone(), "listen");
+        authz.allow(legacy.account_id(), object.clone(), "read");
+
+        let mut context = TestContext::new(db, authz).await;
+
+        let authorize = |context: &TestContext, agent: &TestAgent, action: MediaAction| {
+            let context = context.clone();
+            let agent_id = agent.agent_id().to_owned();
+            let object = AuthzObject::new(&object);
+            let room = room.clone();
+
+            async move { authorize_media(&context, &room, agent_id, object, action).await }
+        };
+
+        // The coarse model checks `read` only.
+        assert!(authorize(&context, &legacy, MediaAction::Listen)
+            .await
+            .is_ok());
+        assert!(authorize(&context, &listener, MediaAction::Listen)
+            .await
+            .is_err());
+
+        context.config_mut().rtc_authz.insert(
+            USR_AUDIENCE.to_owned(),
+            RtcAuthzConfig {
+                fine_grained: true,
+                fallback: false,
+            },
+        );
+
+        assert!(authorize(&context, &listener, MediaAction::Listen)
+            .await
+            .is_ok());
+        assert!(authorize(&context, &listener, MediaAction::Publish)
+            .await
+            .is_err());
+
+        let err = authorize(&context, &legacy, MediaAction::Listen)
+            .await
+            .expect_err("Coarse action passed the fine-grained model");
+
+        assert_eq!(err.kind(), "access_denied");
+
+        context
+            .config_mut()
+            .rtc_authz
+            .get_mut(USR_AUDIENCE)
+            .unwrap()
+            .fallback = true;
+
+        assert!(authorize(&context, &legacy, MediaAction::Listen)
+            .await
+            .is_ok());
+        assert!(authorize(&context, &legacy, MediaAction::Publish)
+            .await
+            .is_err());
+    }
+
+    #[sqlx::test]
+    async fn audio_processing_capability(pool: sqlx::PgPool) {
+        let mut conn = pool.acquire().await.expect("Failed to get connection");
+        let old_backend = TestAgent::new("alpha", "janus-old", SVC_AUDIENCE);
+        let new_backend = TestAgent::new("beta", "janus-new", SVC_AUDIENCE);
+
+        for (backend, capabilities) in [
+            (&old_backend, vec![]),
+            (&new_backend, vec![AUDIO_PROCESSING_CAPABILITY]),
+        ] {
+            factory::JanusBackend::new(
+                backend.agent_id().to_owned(),
+                HandleId::random(),
+                SessionId::random(),
+                "test".to_owned(),
+            )
+            .capabilities(&capabilities)
+            .insert(&mut conn)
+            .await;
+        }
+
+        let audio_processing = AudioProcessing {
+            agc: true,
+            denoise: false,
+        };
+
+        let room = factory::Room::new()
+            .audience(USR_AUDIENCE)
+            .time((Bound::Unbounded, Bound::Unbounded))
+            .audio_processing(audio_processing)
+            .insert(&mut conn)
+            .await;
+
+        let result = super::audio_processing(&room, new_backend.agent_id(), &mut conn)
+            .await
+            .expect("Failed to get audio processing");
+
+        assert_eq!(result, Some(audio_processing));
+
+        let err = super::audio_processing(&room, old_backend.agent_id(), &mut conn)
+            .await
+            .expect_err("Unexpected success on a backend without audio processing");
+
+        assert_eq!(err.kind(), "audio_processing_unsupported");
+
+        // Rooms without audio processing go to any backend.
+        let plain_room = shared_helpers::insert_room(&mut conn).await;
+
+        let result = super::audio_processing(&plain_room, old_backend.agent_id(), &mut conn)
+            .await
+            .expect("Failed to get audio processing");
+
+        assert_eq!(result, None);
     }
 }
@@ -1,14 +1,17 @@
 use crate::{
     app::{
         context::Context,
+        encrypted_message,
         endpoint::prelude::*,
+        message_archive,
         metrics::HistogramExt,
         service_utils::{RequestParams, Response},
         API_VERSION,
     },
+    authz::AuthzObject,
     db,
 };
-use anyhow::Context as AnyhowContext;
+use anyhow::{anyhow, Context as AnyhowContext};
 use async_trait::async_trait;
 use futures::stream;
 use serde::{Deserialize, Serialize};
@@ -43,7 +46,24 @@ impl CorrelationDataPayload {
 pub struct UnicastRequest {
     agent_id: AgentId,
     room_id: db::room::Id,
-    data: JsonValue,
+    /// Exactly one of `data` and `encrypted` is expected.
+    data: Option<JsonValue>,
+    encrypted: Option<encrypted_message::Envelope>,
+}
+
+impl UnicastRequest {
+    /// Returns the payload relayed to the recipient.
+    fn relayed_data(self, context: &impl Context) -> Result<JsonValue, AppError> {
+        match (self.data, self.encrypted) {
+            (Some(data), None) => Ok(data),
+            (None, Some(envelope)) => {
+                envelope.validate(&context.config().encrypted_message)?;
+                Ok(json!({ "encrypted": envelope }))
+            }
+            _ => Err(anyhow!("Either data or encrypted is expected"))
+                .error(AppErrorKind::InvalidPayload),
+        }
+    }
 }
 
 pub struct UnicastHandler;
@@ -52,6 +72,7 @@ pub struct UnicastHandler;
 impl RequestHandler for UnicastHandler {
     type Payload = UnicastRequest;
     const ERROR_TITLE: &'static str = "Failed to send unicast message";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::Presence);
 
     #[instrument(skip(context, payload, reqp), fields(room_id = %payload.room_id))]
     async fn handle<C: Context + Send + Sync>(
@@ -60,20 +81,25 @@ impl RequestHandler for UnicastHandler {
         reqp: RequestParams<'_>,
     ) -> RequestResult {
         let mqtt_params = reqp.as_mqtt_params()?;
+        let room_id = payload.room_id;
+        let recipient_id = payload.agent_id.clone();
+        // Encrypted envelopes are only relayed, unlike broadcasts they never reach the archive.
+        let data = payload.relayed_data(context)?;
 
         let mut conn = context.get_conn().await?;
         let room = helpers::find_room_by_id(
-            payload.room_id,
+            room_id,
             helpers::RoomTimeRequirement::Open,
+            helpers::AudienceScope::caller(context, &reqp),
             &mut conn,
         )
         .await?;
 
         helpers::check_room_presence(&room, reqp.as_agent_id(), &mut conn).await?;
-        helpers::check_room_presence(&room, &payload.agent_id, &mut conn).await?;
+        let recipient = helpers::find_present_agent(&room, &recipient_id, &mut conn).await?;
 
         let response_topic =
-            Subscription::multicast_requests_from(&payload.agent_id, Some(API_VERSION))
+            Subscription::multicast_requests_from(&recipient_id, Some(API_VERSION))
                 .subscription_topic(context.agent_id(), API_VERSION)
                 .context("Error building responses subscription topic")
                 .error(AppErrorKind::MessageBuildingFailed)?;
@@ -96,12 +122,16 @@ impl RequestHandler for UnicastHandler {
             context.start_timestamp(),
             None,
         );
-        response.add_message(Box::new(OutgoingRequest::unicast(
-            payload.data.to_owned(),
-            props,
-            &payload.agent_id,
-            API_VERSION,
-        )));
+
+        // A recipient that muted unicasts is not told about them, the sender isn't either.
+        if recipient.accepts("message.unicast") {
+            response.add_message(Box::new(OutgoingRequest::unicast(
+                data,
+                props,
+                &recipient_id,
+                API_VERSION,
+            )));
+        }
 
         context
             .metrics()
@@ -119,7 +149,6 @@ impl RequestHandler for UnicastHandler {
 pub struct BroadcastRequest {
     room_id: db::room::Id,
     data: JsonValue,
-    #[allow(dead_code)]
     label: Option<String>,
 }
 
@@ -129,6 +158,7 @@ pub struct BroadcastHandler;
 impl RequestHandler for BroadcastHandler {
     type Payload = BroadcastRequest;
     const ERROR_TITLE: &'static str = "Failed to send broadcast message";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::Presence);
 
     #[instrument(skip(context, payload, reqp), fields(room_id = %payload.room_id))]
     async fn handle<C: Context + Send + Sync>(
@@ -140,12 +170,38 @@ impl RequestHandler for BroadcastHandler {
         let room = helpers::find_room_by_id(
             payload.room_id,
             helpers::RoomTimeRequirement::Open,
+            helpers::AudienceScope::caller(context, &reqp),
             &mut conn,
         )
         .await?;
 
         helpers::check_room_presence(&room, reqp.as_agent_id(), &mut conn).await?;
 
+        if room.archive_messages() {
+            let config = &context.config().message_archive;
+            let size = serde_json::to_vec(&payload.data).map_or(0, |data| data.len());
+
+            if size > config.max_payload_size {
+                return Err(anyhow!(
+                    "Message size {} exceeds the limit of {} bytes",
+                    size,
+                    config.max_payload_size
+                ))
+                .error(AppErrorKind::MessageTooLarge);
+            }
+
+            let data = message_archive::scrub(config, &payload.data);
+            let mut q = db::message_archive::InsertQuery::new(room.id(), reqp.as_agent_id(), &data);
+
+            if let Some(ref label) = payload.label {
+                q = q.label(label);
+            }
+
+            q.execute(&mut conn).await?;
+        }
+
+        db::room_summary::increment_broadcast_count(room.id(), &mut conn).await?;
+
         // Respond and broadcast to the room topic.
         let mut response = Response::new(
             ResponseStatus::OK,
@@ -170,6 +226,81 @@ impl RequestHandler for BroadcastHandler {
     }
 }
 
+///////////////////////////////////////////////////////////////////////////////
+
+pub(crate) const HISTORY_MAX_LIMIT: i64 = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct HistoryRequest {
+    room_id: db::room::Id,
+    #[serde(default)]
+    labels: Option<Vec<String>>,
+    query: Option<String>,
+    offset: Option<i64>,
+    limit: Option<i64>,
+}
+
+pub struct HistoryHandler;
+
+#[async_trait]
+impl RequestHandler for HistoryHandler {
+    type Payload = HistoryRequest;
+    const ERROR_TITLE: &'static str = "Failed to read message history";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::CLASSROOM_READ);
+
+    #[instrument(skip(context, payload, reqp), fields(room_id = %payload.room_id))]
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let room = {
+            let mut conn = context.get_conn().await?;
+            helpers::find_room_by_id(
+                payload.room_id,
+                helpers::RoomTimeRequirement::Any,
+                helpers::AudienceScope::caller(context, &reqp),
+                &mut conn,
+            )
+            .await?
+        };
+
+        // Reading the history is allowed to whoever may read the classroom even after it's over.
+        let object = AuthzObject::new(&["classrooms", &room.classroom_id().to_string()]);
+
+        let authz_time = context
+            .authz()
+            .authorize(room.audience().into(), reqp, object.into(), "read".into())
+            .await?;
+        context.metrics().observe_auth(authz_time);
+
+        let mut query = db::message_archive::ListQuery::new(room.id())
+            .offset(payload.offset.unwrap_or(0))
+            .limit(std::cmp::min(
+                payload.limit.unwrap_or(HISTORY_MAX_LIMIT),
+                HISTORY_MAX_LIMIT,
+            ));
+
+        if let Some(ref labels) = payload.labels {
+            query = query.labels(labels);
+        }
+
+        if let Some(ref search) = payload.query {
+            query = query.search(search);
+        }
+
+        let mut conn = context.get_conn().await?;
+        let messages = query.execute(&mut conn).await?;
+
+        Ok(Response::new(
+            ResponseStatus::OK,
+            messages,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 
 pub struct UnicastResponseHandler;
@@ -218,6 +349,7 @@ impl ResponseHandler for UnicastResponseHandler {
 #[cfg(test)]
 mod test {
     mod unicast {
+        use crate::test_helpers::outgoing_envelope::OutgoingEnvelopeProperties;
 
         use crate::{
             app::API_VERSION,
@@ -246,7 +378,8 @@ mod test {
             let payload = UnicastRequest {
                 agent_id: receiver.agent_id().to_owned(),
                 room_id: room.id(),
-                data: json!({ "key": "value" }),
+                data: Some(json!({ "key": "value" })),
+                encrypted: None,
             };
 
             let messages = handle_request::<UnicastHandler>(&mut context, &sender, payload)
@@ -267,6 +400,102 @@ mod test {
             assert_eq!(payload, json!({"key": "value"}));
         }
 
+        #[sqlx::test]
+        async fn unicast_encrypted_message(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+            let sender = TestAgent::new("web", "sender", USR_AUDIENCE);
+            let receiver = TestAgent::new("web", "receiver", USR_AUDIENCE);
+
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+
+            shared_helpers::insert_agent(&mut conn, sender.agent_id(), room.id()).await;
+            shared_helpers::insert_agent(&mut conn, receiver.agent_id(), room.id()).await;
+
+            let mut context = TestContext::new(db, TestAuthz::new()).await;
+
+            let envelope = json!({
+                "alg": "x25519-aes-256-gcm",
+                "key_id": "receiver-2023-07",
+                "nonce": "AAAAAAAAAAAAAAAA",
+                "ciphertext": "c2VjcmV0",
+            });
+
+            let payload = UnicastRequest {
+                agent_id: receiver.agent_id().to_owned(),
+                room_id: room.id(),
+                data: None,
+                encrypted: Some(serde_json::from_value(envelope.clone()).unwrap()),
+            };
+
+            let messages = handle_request::<UnicastHandler>(&mut context, &sender, payload)
+                .await
+                .expect("Unicast message sending failed");
+
+            // The envelope is relayed untouched.
+            let (payload, _reqp, _topic) = find_request::<JsonValue>(messages.as_slice());
+            assert_eq!(payload, json!({ "encrypted": envelope }));
+
+            // Plain data along with the envelope is ambiguous.
+            let payload = UnicastRequest {
+                agent_id: receiver.agent_id().to_owned(),
+                room_id: room.id(),
+                data: Some(json!({ "key": "value" })),
+                encrypted: Some(serde_json::from_value(envelope).unwrap()),
+            };
+
+            let err = handle_request::<UnicastHandler>(&mut context, &sender, payload)
+                .await
+                .expect_err("Unexpected success on unicast message sending");
+
+            assert_eq!(err.status(), ResponseStatus::BAD_REQUEST);
+            assert_eq!(err.kind(), "invalid_payload");
+        }
+
+        #[sqlx::test]
+        async fn unicast_message_to_muted_receiver(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+            let sender = TestAgent::new("web", "sender", USR_AUDIENCE);
+            let receiver = TestAgent::new("web", "receiver", USR_AUDIENCE);
+
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+
+            shared_helpers::insert_agent(&mut conn, sender.agent_id(), room.id()).await;
+            shared_helpers::insert_agent(&mut conn, receiver.agent_id(), room.id()).await;
+
+            db::agent::set_muted_events(
+                receiver.agent_id(),
+                room.id(),
+                &["message.unicast".to_owned()],
+                &mut conn,
+            )
+            .await
+            .expect("Failed to mute unicasts");
+
+            let mut context = TestContext::new(db, TestAuthz::new()).await;
+
+            let payload = UnicastRequest {
+                agent_id: receiver.agent_id().to_owned(),
+                room_id: room.id(),
+                data: Some(json!({ "key": "value" })),
+                encrypted: None,
+            };
+
+            let messages = handle_request::<UnicastHandler>(&mut context, &sender, payload)
+                .await
+                .expect("Unicast message sending failed");
+
+            // The sender gets a regular response but nothing is forwarded.
+            let (_, respp, _) = find_response::<JsonValue>(messages.as_slice());
+            assert_eq!(respp.status(), ResponseStatus::OK);
+
+            assert!(messages.iter().all(|message| !matches!(
+                message.properties(),
+                OutgoingEnvelopeProperties::Request(_)
+            )));
+        }
+
         #[sqlx::test]
         async fn unicast_message_to_missing_room(pool: sqlx::PgPool) {
             let db = TestDb::new(pool);
@@ -278,7 +507,8 @@ mod test {
             let payload = UnicastRequest {
                 agent_id: receiver.agent_id().to_owned(),
                 room_id: db::room::Id::random(),
-                data: json!({ "key": "value" }),
+                data: Some(json!({ "key": "value" })),
+                encrypted: None,
             };
 
             let err = handle_request::<UnicastHandler>(&mut context, &sender, payload)
@@ -307,7 +537,8 @@ mod test {
             let payload = UnicastRequest {
                 agent_id: receiver.agent_id().to_owned(),
                 room_id: room.id(),
-                data: json!({ "key": "value" }),
+                data: Some(json!({ "key": "value" })),
+                encrypted: None,
             };
 
             let err = handle_request::<UnicastHandler>(&mut context, &sender, payload)
@@ -336,7 +567,8 @@ mod test {
             let payload = UnicastRequest {
                 agent_id: receiver.agent_id().to_owned(),
                 room_id: room.id(),
-                data: json!({ "key": "value" }),
+                data: Some(json!({ "key": "value" })),
+                encrypted: None,
             };
 
             let err = handle_request::<UnicastHandler>(&mut context, &sender, payload)
@@ -448,4 +680,156 @@ mod test {
             assert_eq!(err.kind(), "agent_not_entered_the_room");
         }
     }
+
+    mod history {
+        use std::ops::Bound;
+
+        use chrono::{Duration, SubsecRound, Utc};
+
+        use crate::test_helpers::{db::TestDb, prelude::*};
+
+        use super::super::*;
+
+        async fn insert_archived_room(conn: &mut sqlx::PgConnection) -> db::room::Object {
+            let now = Utc::now().trunc_subsecs(0);
+
+            factory::Room::new()
+                .audience(USR_AUDIENCE)
+                .time((
+                    Bound::Included(now),
+                    Bound::Excluded(now + Duration::hours(1)),
+                ))
+                .rtc_sharing_policy(db::rtc::SharingPolicy::Shared)
+                .archive_messages(true)
+                .insert(conn)
+                .await
+        }
+
+        #[sqlx::test]
+        async fn archive_and_read_history(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+            let sender = TestAgent::new("web", "sender", USR_AUDIENCE);
+            let reader = TestAgent::new("web", "reader", USR_AUDIENCE);
+
+            let mut conn = db.get_conn().await;
+            let room = insert_archived_room(&mut conn).await;
+
+            factory::Agent::new()
+                .room_id(room.id())
+                .agent_id(sender.agent_id())
+                .insert(&mut conn)
+                .await;
+
+            let mut authz = TestAuthz::new();
+            let classroom_id = room.classroom_id().to_string();
+            authz.allow(
+                reader.account_id(),
+                vec!["classrooms", &classroom_id],
+                "read",
+            );
+
+            let mut context = TestContext::new(db, authz).await;
+            context
+                .config_mut()
+                .message_archive
+                .scrub_keys
+                .insert("email".to_owned());
+
+            let messages = [
+                (
+                    json!({ "text": "Hello there", "email": "john@example.org" }),
+                    "chat",
+                ),
+                (json!({ "text": "General Kenobi" }), "chat"),
+                (json!({ "emoji": "wave" }), "reaction"),
+            ];
+
+            for (data, label) in messages {
+                let payload = BroadcastRequest {
+                    room_id: room.id(),
+                    data,
+                    label: Some(label.to_owned()),
+                };
+
+                handle_request::<BroadcastHandler>(&mut context, &sender, payload)
+                    .await
+                    .expect("Broadcast message sending failed");
+            }
+
+            // Filter by label.
+            let payload = HistoryRequest {
+                room_id: room.id(),
+                labels: Some(vec!["chat".to_owned()]),
+                query: None,
+                offset: None,
+                limit: None,
+            };
+
+            let messages = handle_request::<HistoryHandler>(&mut context, &reader, payload)
+                .await
+                .expect("Message history reading failed");
+
+            let (history, respp, _) =
+                find_response::<Vec<db::message_archive::Object>>(messages.as_slice());
+
+            assert_eq!(respp.status(), ResponseStatus::OK);
+            assert_eq!(history.len(), 2);
+            assert!(history.iter().all(|m| m.agent_id == *sender.agent_id()));
+
+            // Search by text, scrubbed fields are neither stored nor searchable.
+            for (query, expected) in [("hello", 1), ("example.org", 0)] {
+                let payload = HistoryRequest {
+                    room_id: room.id(),
+                    labels: None,
+                    query: Some(query.to_owned()),
+                    offset: None,
+                    limit: None,
+                };
+
+                let messages = handle_request::<HistoryHandler>(&mut context, &reader, payload)
+                    .await
+                    .expect("Message history reading failed");
+
+                let (history, _, _) =
+                    find_response::<Vec<db::message_archive::Object>>(messages.as_slice());
+
+                assert_eq!(history.len(), expected);
+
+                if let Some(message) = history.first() {
+                    assert_eq!(message.data, json!({ "text": "Hello there" }));
+                }
+            }
+        }
+
+        #[sqlx::test]
+        async fn broadcast_too_large_message(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+            let sender = TestAgent::new("web", "sender", USR_AUDIENCE);
+
+            let mut conn = db.get_conn().await;
+            let room = insert_archived_room(&mut conn).await;
+
+            factory::Agent::new()
+                .room_id(room.id())
+                .agent_id(sender.agent_id())
+                .insert(&mut conn)
+                .await;
+
+            let mut context = TestContext::new(db, TestAuthz::new()).await;
+            context.config_mut().message_archive.max_payload_size = 16;
+
+            let payload = BroadcastRequest {
+                room_id: room.id(),
+                data: json!({ "text": "Way too long to be archived" }),
+                label: None,
+            };
+
+            let err = handle_request::<BroadcastHandler>(&mut context, &sender, payload)
+                .await
+                .expect_err("Unexpected success broadcasting too large message");
+
+            assert_eq!(err.status(), ResponseStatus::PAYLOAD_TOO_LARGE);
+            assert_eq!(err.kind(), "message_too_large");
+        }
+    }
 }
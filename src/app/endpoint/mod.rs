@@ -1,10 +1,13 @@
 use std::result::Result as StdResult;
 
-pub(self) use crate::app::message_handler::MessageStream;
+use crate::app::message_handler::MessageStream;
 use crate::app::{
     context::Context,
     error::Error as AppError,
-    message_handler::{EventEnvelopeHandler, RequestEnvelopeHandler, ResponseEnvelopeHandler},
+    message_handler::{
+        mqtt::RequestEnvelopeHandler, ws, EventEnvelopeHandler, ResponseEnvelopeHandler,
+    },
+    service_utils::Notification,
 };
 use async_trait::async_trait;
 use serde::de::DeserializeOwned;
@@ -23,6 +26,10 @@ pub type MqttResult = StdResult<MessageStream, AppError>;
 pub trait RequestHandler {
     type Payload: Send + DeserializeOwned;
     const ERROR_TITLE: &'static str;
+    /// Whether requests are still served while the instance is draining.
+    const SERVES_WHILE_DRAINING: bool = false;
+    /// What the caller gets authorized for, listed by `system.permission_matrix`.
+    const AUTHZ: Option<AuthzIntent> = None;
 
     async fn handle<C: Context + Send + Sync>(
         context: &mut C,
@@ -31,8 +38,56 @@ pub trait RequestHandler {
     ) -> RequestResult;
 }
 
+/// Which actions a handler authorizes on which object. Segments in braces are taken from the
+/// request, e.g. `{classroom_id}`.
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AuthzIntent {
+    /// One of the actions on the object depending on the request.
+    Object {
+        object: &'static [&'static str],
+        actions: &'static [&'static str],
+    },
+    /// Hosts of the room pass as is, anyone else needs `update` on `classrooms/{classroom_id}`.
+    Host,
+    /// The creator of the object passes without an authz call for the changes the handler
+    /// leaves to it, anything else goes as `Host`.
+    CreatorOrHost,
+    /// No authz call, the caller must have entered the room.
+    Presence,
+    /// No authz call, only the dispatcher of the service's audience passes.
+    Dispatcher,
+}
+
+impl AuthzIntent {
+    pub const SYSTEM_READ: Self = Self::Object {
+        object: &["system"],
+        actions: &["read"],
+    };
+
+    pub const SYSTEM_UPDATE: Self = Self::Object {
+        object: &["system"],
+        actions: &["update"],
+    };
+
+    pub const CLASSROOM_READ: Self = Self::Object {
+        object: &["classrooms", "{classroom_id}"],
+        actions: &["read"],
+    };
+
+    pub const CLASSROOM_UPDATE: Self = Self::Object {
+        object: &["classrooms", "{classroom_id}"],
+        actions: &["update"],
+    };
+}
+
 macro_rules! request_routes {
-    ($($m: pat => $h: ty),*) => {
+    ($($m: literal => $h: ty),*) => {
+        /// Authz intents of the request handlers by method.
+        pub fn permission_matrix() -> Vec<(&'static str, Option<AuthzIntent>)> {
+            vec![$(($m, <$h as RequestHandler>::AUTHZ),)*]
+        }
+
         pub async fn route_request<C: Context + Send + Sync>(
             context: &mut C,
             request: &IncomingRequest<String>,
@@ -45,37 +100,92 @@ macro_rules! request_routes {
                 _ => None,
             }
         }
+
+        pub async fn route_ws_request<C: Context + Send + Sync>(
+            context: &mut C,
+            request: &ws::Request,
+        ) -> Option<(ws::OutgoingFrame, Vec<Notification>)> {
+            match request.frame.method.as_str() {
+                $(
+                    $m => Some(ws::handle_frame::<$h, C>(context, request).await),
+                )*
+                _ => None,
+            }
+        }
     }
 }
 
 // Request routes configuration: method => RequestHandler
 request_routes!(
     "agent.list" => agent::ListHandler,
+    "agent.preferences_update" => agent::PreferencesUpdateHandler,
     "agent_reader_config.read" => agent_reader_config::ReadHandler,
     "agent_reader_config.update" => agent_reader_config::UpdateHandler,
     "agent_writer_config.read" => agent_writer_config::ReadHandler,
     "agent_writer_config.update" => agent_writer_config::UpdateHandler,
+    "ban.create" => ban::CreateHandler,
+    "ban.delete" => ban::DeleteHandler,
+    "ban.list" => ban::ListHandler,
     "message.broadcast" => message::BroadcastHandler,
+    "message.history" => message::HistoryHandler,
     "message.unicast" => message::UnicastHandler,
+    "poll.close" => poll::CloseHandler,
+    "poll.create" => poll::CreateHandler,
+    "poll.results" => poll::ResultsHandler,
+    "poll.vote" => poll::VoteHandler,
+    "room.admit" => room::AdmitHandler,
+    "room.bulk_close" => system::RoomBulkCloseHandler,
+    "room.bulk_close_read" => system::RoomBulkCloseReadHandler,
     "room.close" => room::CloseHandler,
     "room.create" => room::CreateHandler,
+    "room.create_from_template" => room::CreateFromTemplateHandler,
+    "room.delete" => room::DeleteHandler,
     // todo delete later unused routes
     // We comment this line, because we want to use the outbox crate in the
     // `room::EnterHandler` function and in order to do that, we need to pass
     // the context as `Arc<dyn GlobalContext>`
     // "room.enter" => room::EnterHandler,
     "room.leave" => room::LeaveHandler,
+    "room.limits_read" => room::LimitsReadHandler,
+    "room.list" => room::ListHandler,
     "room.read" => room::ReadHandler,
+    "room.reject" => room::RejectHandler,
     "room.update" => room::UpdateHandler,
+    "room_schedule.create" => room_schedule::CreateHandler,
+    "room_schedule.delete" => room_schedule::DeleteHandler,
+    "room_template.update" => room_template::UpdateHandler,
     "rtc.connect" => rtc::ConnectHandler,
+    "rtc.connect_explain" => rtc::ConnectExplainHandler,
     "rtc.create" => rtc::CreateHandler,
     "rtc.list" => rtc::ListHandler,
+    "rtc.preflight" => rtc::PreflightHandler,
     "rtc.read" => rtc::ReadHandler,
+    "rtc.subscribe_set" => rtc::SubscribeSetHandler,
+    "rtc.update" => rtc::UpdateHandler,
     "rtc_signal.create" => rtc_signal::CreateHandler,
+    "rtc_signal.history" => rtc_signal::HistoryHandler,
     "rtc_stream.list" => rtc_stream::ListHandler,
     "system.vacuum" => system::VacuumHandler,
     "system.agent_cleanup" => system::AgentCleanupHandler,
+    "system.agent_locate" => system::AgentLocateHandler,
     "system.agent_connection_cleanup" => system::AgentConnectionCleanupHandler,
+    "system.backend_assignment_export" => system::BackendAssignmentExportHandler,
+    "system.backend_errors_list" => system::BackendErrorsListHandler,
+    "system.backend_group_decommission" => system::BackendGroupDecommissionHandler,
+    "system.backend_list" => system::BackendListHandler,
+    "system.event_capture_start" => system::EventCaptureStartHandler,
+    "system.event_capture_stop" => system::EventCaptureStopHandler,
+    "system.permission_matrix" => system::PermissionMatrixHandler,
+    "system.reader_config_reconcile" => system::ReaderConfigReconcileHandler,
+    "system.recording_read" => system::RecordingReadHandler,
+    "system.relay_usage_read" => system::RelayUsageReadHandler,
+    "system.room_health_list" => system::RoomHealthListHandler,
+    "system.rtc.migrate" => system::RtcMigrateHandler,
+    "system.signal_debug_update" => system::SignalDebugUpdateHandler,
+    "system.stage_replay" => system::StageReplayHandler,
+    "system.stage_status" => system::StageStatusHandler,
+    "system.tenant_usage_read" => system::TenantUsageReadHandler,
+    "system.validate_backend" => system::ValidateBackendHandler,
     "writer_config_snapshot.read" => writer_config_snapshot::ReadHandler
 );
 
@@ -171,6 +281,7 @@ macro_rules! event_routes {
 
 // Event routes configuration: label => EventHandler
 event_routes!(
+    "subscription.create" => subscription::CreateEventHandler,
     "subscription.delete" => subscription::DeleteEventHandler,
     "system.close_orphaned_rooms" => system::OrphanedRoomCloseHandler
 );
@@ -180,19 +291,27 @@ event_routes!(
 pub mod agent;
 pub mod agent_reader_config;
 pub mod agent_writer_config;
+pub mod ban;
 pub mod group;
 pub mod helpers;
 pub mod message;
+pub mod poll;
 pub mod room;
+pub mod room_schedule;
+pub mod room_template;
 pub mod rtc;
 pub mod rtc_signal;
 pub mod rtc_stream;
 pub mod subscription;
 pub mod system;
+pub mod whep;
+pub mod whip;
 pub mod writer_config_snapshot;
 
-pub(self) mod prelude {
-    pub(super) use super::{helpers, EventHandler, RequestHandler, RequestResult, ResponseHandler};
+mod prelude {
+    pub(super) use super::{
+        helpers, AuthzIntent, EventHandler, RequestHandler, RequestResult, ResponseHandler,
+    };
     pub(super) use crate::app::{
         endpoint::CorrelationData,
         error::{Error as AppError, ErrorExt, ErrorKind as AppErrorKind},
@@ -0,0 +1,516 @@
+use std::sync::Arc;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use axum::{
+    extract::{Extension, Path},
+    Json,
+};
+use serde::Deserialize;
+use svc_agent::{mqtt::ResponseStatus, Addressable};
+use svc_utils::extractors::AgentIdExtractor;
+use tracing_attributes::instrument;
+
+use crate::{
+    app::{
+        context::{AppContext, Context},
+        endpoint::prelude::*,
+        message_handler::http,
+        service_utils::{RequestParams, Response},
+    },
+    authz::AuthzObject,
+    db,
+};
+
+const MIN_OPTIONS: usize = 2;
+const MAX_OPTIONS: usize = 10;
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct CreateRequest {
+    room_id: db::room::Id,
+    question: String,
+    options: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateFields {
+    question: String,
+    options: Vec<String>,
+}
+
+pub async fn create(
+    Extension(ctx): Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<db::room::Id>,
+    Json(request): Json<CreateFields>,
+) -> RequestResult {
+    tracing::Span::current().record("room_id", tracing::field::display(room_id));
+
+    let request = CreateRequest {
+        room_id,
+        question: request.question,
+        options: request.options,
+    };
+    http::handle::<CreateHandler>(&ctx, &agent_id, request).await
+}
+
+pub struct CreateHandler;
+
+#[async_trait]
+impl RequestHandler for CreateHandler {
+    type Payload = CreateRequest;
+    const ERROR_TITLE: &'static str = "Failed to create poll";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::Host);
+
+    #[instrument(skip(context, payload, reqp), fields(room_id = %payload.room_id))]
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        if payload.question.trim().is_empty() {
+            return Err(anyhow!("Poll question is empty")).error(AppErrorKind::InvalidPayload);
+        }
+
+        if payload.options.len() < MIN_OPTIONS || payload.options.len() > MAX_OPTIONS {
+            return Err(anyhow!(
+                "Poll must have from {} to {} options",
+                MIN_OPTIONS,
+                MAX_OPTIONS
+            ))
+            .error(AppErrorKind::InvalidPayload);
+        }
+
+        let room = {
+            let mut conn = context.get_conn().await?;
+
+            helpers::find_room_by_id(
+                payload.room_id,
+                helpers::RoomTimeRequirement::Open,
+                helpers::AudienceScope::caller(context, &reqp),
+                &mut conn,
+            )
+            .await?
+        };
+
+        let authz_time = helpers::authorize_host(context, &room, reqp).await?;
+
+        let poll = {
+            let mut conn = context.get_conn().await?;
+
+            db::poll::InsertQuery::new(
+                room.id(),
+                reqp.as_agent_id(),
+                &payload.question,
+                &payload.options,
+            )
+            .execute(&mut conn)
+            .await?
+        };
+
+        let mut response = Response::new(
+            ResponseStatus::CREATED,
+            poll.clone(),
+            context.start_timestamp(),
+            authz_time,
+        );
+
+        response.add_notification(
+            "poll.create",
+            &format!("rooms/{}/events", room.id()),
+            poll,
+            context.start_timestamp(),
+        );
+
+        Ok(response)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct VoteRequest {
+    id: db::poll::Id,
+    option: i32,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VoteFields {
+    option: i32,
+}
+
+pub async fn vote(
+    Extension(ctx): Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(poll_id): Path<db::poll::Id>,
+    Json(request): Json<VoteFields>,
+) -> RequestResult {
+    let request = VoteRequest {
+        id: poll_id,
+        option: request.option,
+    };
+    http::handle::<VoteHandler>(&ctx, &agent_id, request).await
+}
+
+pub struct VoteHandler;
+
+#[async_trait]
+impl RequestHandler for VoteHandler {
+    type Payload = VoteRequest;
+    const ERROR_TITLE: &'static str = "Failed to vote in poll";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::Presence);
+
+    #[instrument(skip(context, payload, reqp), fields(poll_id = %payload.id))]
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let mut conn = context.get_conn().await?;
+        let poll = find_poll(payload.id, &mut conn).await?;
+
+        let room = helpers::find_room_by_id(
+            poll.room_id(),
+            helpers::RoomTimeRequirement::Open,
+            helpers::AudienceScope::caller(context, &reqp),
+            &mut conn,
+        )
+        .await?;
+
+        helpers::check_room_presence(&room, reqp.as_agent_id(), &mut conn).await?;
+
+        if poll.is_closed() {
+            return Err(anyhow!("Poll has already been closed")).error(AppErrorKind::PollClosed);
+        }
+
+        if payload.option < 0 || payload.option as usize >= poll.options().len() {
+            return Err(anyhow!("Poll option {} is out of range", payload.option))
+                .error(AppErrorKind::InvalidPayload);
+        }
+
+        let voted = db::poll::VoteInsertQuery::new(poll.id(), reqp.as_agent_id(), payload.option)
+            .execute(&mut conn)
+            .await?;
+
+        if !voted {
+            return Err(anyhow!("Agent has already voted in the poll"))
+                .error(AppErrorKind::Conflict);
+        }
+
+        let results = db::poll::results(poll, &mut conn).await?;
+
+        let mut response = Response::new(
+            ResponseStatus::OK,
+            results.clone(),
+            context.start_timestamp(),
+            None,
+        );
+
+        response.add_notification(
+            "poll.update",
+            &format!("rooms/{}/events", room.id()),
+            results,
+            context.start_timestamp(),
+        );
+
+        Ok(response)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct CloseRequest {
+    id: db::poll::Id,
+}
+
+pub async fn close(
+    Extension(ctx): Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(poll_id): Path<db::poll::Id>,
+) -> RequestResult {
+    let request = CloseRequest { id: poll_id };
+    http::handle::<CloseHandler>(&ctx, &agent_id, request).await
+}
+
+pub struct CloseHandler;
+
+#[async_trait]
+impl RequestHandler for CloseHandler {
+    type Payload = CloseRequest;
+    const ERROR_TITLE: &'static str = "Failed to close poll";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::Host);
+
+    #[instrument(skip(context, payload, reqp), fields(poll_id = %payload.id))]
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let room = {
+            let mut conn = context.get_conn().await?;
+            let poll = find_poll(payload.id, &mut conn).await?;
+
+            helpers::find_room_by_id(
+                poll.room_id(),
+                helpers::RoomTimeRequirement::Any,
+                helpers::AudienceScope::caller(context, &reqp),
+                &mut conn,
+            )
+            .await?
+        };
+
+        let authz_time = helpers::authorize_host(context, &room, reqp).await?;
+
+        let results = {
+            let mut conn = context.get_conn().await?;
+
+            let poll = db::poll::close(payload.id, &mut conn)
+                .await?
+                .ok_or_else(|| anyhow!("Poll has already been closed"))
+                .error(AppErrorKind::PollClosed)?;
+
+            db::poll::results(poll, &mut conn).await?
+        };
+
+        let mut response = Response::new(
+            ResponseStatus::OK,
+            results.clone(),
+            context.start_timestamp(),
+            authz_time,
+        );
+
+        response.add_notification(
+            "poll.close",
+            &format!("rooms/{}/events", room.id()),
+            results,
+            context.start_timestamp(),
+        );
+
+        Ok(response)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct ResultsRequest {
+    id: db::poll::Id,
+}
+
+pub async fn results(
+    Extension(ctx): Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(poll_id): Path<db::poll::Id>,
+) -> RequestResult {
+    let request = ResultsRequest { id: poll_id };
+    http::handle::<ResultsHandler>(&ctx, &agent_id, request).await
+}
+
+pub struct ResultsHandler;
+
+#[async_trait]
+impl RequestHandler for ResultsHandler {
+    type Payload = ResultsRequest;
+    const ERROR_TITLE: &'static str = "Failed to read poll results";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::CLASSROOM_READ);
+
+    #[instrument(skip(context, payload, reqp), fields(poll_id = %payload.id))]
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let (poll, room) = {
+            let mut conn = context.get_conn().await?;
+            let poll = find_poll(payload.id, &mut conn).await?;
+
+            let room = helpers::find_room_by_id(
+                poll.room_id(),
+                helpers::RoomTimeRequirement::Any,
+                helpers::AudienceScope::caller(context, &reqp),
+                &mut conn,
+            )
+            .await?;
+
+            (poll, room)
+        };
+
+        // Authorize room reading on the tenant.
+        let classroom_id = room.classroom_id().to_string();
+        let object = AuthzObject::new(&["classrooms", &classroom_id]).into();
+
+        let authz_time = context
+            .authz()
+            .authorize(room.audience().into(), reqp, object, "read".into())
+            .await?;
+        context.metrics().observe_auth(authz_time);
+
+        let results = {
+            let mut conn = context.get_conn().await?;
+            db::poll::results(poll, &mut conn).await?
+        };
+
+        Ok(Response::new(
+            ResponseStatus::OK,
+            results,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+async fn find_poll(
+    id: db::poll::Id,
+    conn: &mut sqlx::PgConnection,
+) -> Result<db::poll::Object, AppError> {
+    db::poll::FindQuery::new(id)
+        .execute(conn)
+        .await?
+        .ok_or_else(|| anyhow!("Poll not found"))
+        .error(AppErrorKind::PollNotFound)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Bound;
+
+    use chrono::{Duration, Utc};
+    use serde_json::Value as JsonValue;
+
+    use crate::test_helpers::{db::TestDb, prelude::*};
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn poll_lifecycle(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let host = TestAgent::new("web", "host", USR_AUDIENCE);
+        let student = TestAgent::new("web", "student", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+
+            let room = factory::Room::new()
+                .audience(USR_AUDIENCE)
+                .time((
+                    Bound::Included(Utc::now() - Duration::hours(1)),
+                    Bound::Unbounded,
+                ))
+                .insert(&mut conn)
+                .await;
+
+            db::room_host::insert(room.id(), &[host.agent_id()], &mut conn)
+                .await
+                .expect("Failed to set room host");
+
+            shared_helpers::insert_agent(&mut conn, student.agent_id(), room.id()).await;
+            room
+        };
+
+        let mut context = TestContext::new(db, TestAuthz::new()).await;
+
+        // The host creates a poll without authorization.
+        let payload = CreateRequest {
+            room_id: room.id(),
+            question: "Ready?".to_owned(),
+            options: vec!["yes".to_owned(), "no".to_owned()],
+        };
+
+        let messages = handle_request::<CreateHandler>(&mut context, &host, payload)
+            .await
+            .expect("Poll creation failed");
+
+        let (poll, respp, _) = find_response::<db::poll::Object>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::CREATED);
+        let (_, _, topic) = find_event::<JsonValue>(messages.as_slice());
+        assert!(topic.ends_with(&format!("rooms/{}/events", room.id())));
+
+        // A room member votes once.
+        let payload = VoteRequest {
+            id: poll.id(),
+            option: 1,
+        };
+
+        let messages = handle_request::<VoteHandler>(&mut context, &student, payload)
+            .await
+            .expect("Voting failed");
+
+        let (results, _, _) = find_response::<db::poll::Results>(messages.as_slice());
+        assert_eq!(results.votes, vec![0, 1]);
+
+        let payload = VoteRequest {
+            id: poll.id(),
+            option: 0,
+        };
+
+        let err = handle_request::<VoteHandler>(&mut context, &student, payload)
+            .await
+            .expect_err("Unexpected success on repeated vote");
+
+        assert_eq!(err.status(), ResponseStatus::CONFLICT);
+
+        // Only the host closes the poll.
+        let payload = CloseRequest { id: poll.id() };
+
+        let err = handle_request::<CloseHandler>(&mut context, &student, payload)
+            .await
+            .expect_err("Unexpected success on poll closing");
+
+        assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+
+        let payload = CloseRequest { id: poll.id() };
+
+        let messages = handle_request::<CloseHandler>(&mut context, &host, payload)
+            .await
+            .expect("Poll closing failed");
+
+        let (results, _, _) = find_event::<db::poll::Results>(messages.as_slice());
+        assert!(results.poll.is_closed());
+        assert_eq!(results.votes, vec![0, 1]);
+
+        // No votes after closing.
+        let other = TestAgent::new("web", "other", USR_AUDIENCE);
+
+        {
+            let mut conn = context.get_conn().await.expect("Failed to get conn");
+            shared_helpers::insert_agent(&mut conn, other.agent_id(), room.id()).await;
+        }
+
+        let payload = VoteRequest {
+            id: poll.id(),
+            option: 0,
+        };
+
+        let err = handle_request::<VoteHandler>(&mut context, &other, payload)
+            .await
+            .expect_err("Unexpected success on voting in closed poll");
+
+        assert_eq!(err.kind(), "poll_closed");
+    }
+
+    #[sqlx::test]
+    async fn create_poll_with_single_option(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let agent = TestAgent::new("web", "host", USR_AUDIENCE);
+        let mut context = TestContext::new(db, TestAuthz::new()).await;
+
+        let payload = CreateRequest {
+            room_id: db::room::Id::random(),
+            question: "Ready?".to_owned(),
+            options: vec!["yes".to_owned()],
+        };
+
+        let err = handle_request::<CreateHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success on poll creation");
+
+        assert_eq!(err.kind(), "invalid_payload");
+    }
+}
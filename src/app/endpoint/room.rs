@@ -1,7 +1,7 @@
 use anyhow::{anyhow, Context as AnyhowContext};
 use async_trait::async_trait;
 use axum::{
-    extract::{Extension, Path},
+    extract::{Extension, Path, Query},
     Json,
 };
 use chrono::{DateTime, Utc};
@@ -10,12 +10,15 @@ use serde_json::{json, Value as JsonValue};
 use sqlx::Connection;
 use std::{ops::Bound, sync::Arc};
 use svc_agent::{
-    mqtt::{OutgoingRequest, ResponseStatus, ShortTermTimingProperties, SubscriptionTopic},
+    mqtt::{
+        IntoPublishableMessage, OutgoingEvent, OutgoingEventProperties, OutgoingRequest,
+        ResponseStatus, ShortTermTimingProperties, SubscriptionTopic,
+    },
     Addressable, AgentId, Authenticable, Subscription,
 };
 use svc_events::{EventV1 as Event, VideoGroupEventV1 as VideoGroupEvent};
 use svc_utils::extractors::AgentIdExtractor;
-use tracing::error;
+use tracing::{error, info};
 use tracing_attributes::instrument;
 use uuid::Uuid;
 
@@ -28,7 +31,9 @@ use crate::{
             subscription::CorrelationDataPayload,
         },
         group_reader_config,
+        message_handler::http,
         metrics::HistogramExt,
+        room_authz, room_policy, room_upload,
         service_utils::{RequestParams, Response},
         stage::{
             self,
@@ -40,10 +45,11 @@ use crate::{
     authz::AuthzObject,
     backend::janus::client::update_agent_reader_config::UpdateReaderConfigRequestBodyConfigItem,
     client::mqtt_gateway::MqttGatewayClient,
+    config::RoomUploadConfig,
     db::{
         self,
         group_agent::{GroupItem, Groups},
-        room::{RoomBackend, Time},
+        room::{AudioProcessing, RoomBackend, RoomTime},
         rtc::SharingPolicy as RtcSharingPolicy,
     },
     outbox::{
@@ -73,8 +79,7 @@ impl SubscriptionRequest {
 
 #[derive(Debug, Deserialize)]
 pub struct CreateRequest {
-    #[serde(with = "crate::serde::ts_seconds_bound_tuple")]
-    time: Time,
+    time: RoomTime,
     audience: String,
     // Deprecated in favor of `rtc_sharing_policy`.
     #[serde(default)]
@@ -84,6 +89,29 @@ pub struct CreateRequest {
     reserve: Option<i32>,
     tags: Option<JsonValue>,
     classroom_id: Uuid,
+    #[serde(default)]
+    audio_processing: Option<AudioProcessing>,
+    #[serde(default)]
+    archive_messages: Option<bool>,
+    #[serde(default)]
+    composite_recording: Option<bool>,
+    #[serde(default)]
+    record_rtcs: Option<bool>,
+    /// Seconds a stream of an owned rtc may last before it gets stopped.
+    #[serde(default)]
+    stream_max_duration: Option<i32>,
+    /// Arbitrary object of the frontend, e.g. title or layout hints.
+    #[serde(default)]
+    metadata: Option<JsonValue>,
+    /// Whether entering agents wait in the lobby until a host admits them.
+    #[serde(default)]
+    lobby: Option<bool>,
+    /// Whether the room gets `room.active_speaker_changed` events.
+    #[serde(default)]
+    active_speaker_events: Option<bool>,
+    /// Agents the room fits, unlimited if missing.
+    #[serde(default)]
+    max_agents: Option<i32>,
 }
 
 pub async fn create(
@@ -91,14 +119,7 @@ pub async fn create(
     AgentIdExtractor(agent_id): AgentIdExtractor,
     Json(request): Json<CreateRequest>,
 ) -> RequestResult {
-    CreateHandler::handle(
-        &mut ctx.start_message(),
-        request,
-        RequestParams::Http {
-            agent_id: &agent_id,
-        },
-    )
-    .await
+    http::handle::<CreateHandler>(&ctx, &agent_id, request).await
 }
 
 pub struct CreateHandler;
@@ -107,79 +128,236 @@ pub struct CreateHandler;
 impl RequestHandler for CreateHandler {
     type Payload = CreateRequest;
     const ERROR_TITLE: &'static str = "Failed to create room";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::Object {
+        object: &["classrooms"],
+        actions: &["create"],
+    });
 
     async fn handle<C: Context + Send + Sync>(
         context: &mut C,
         payload: Self::Payload,
         reqp: RequestParams<'_>,
     ) -> RequestResult {
-        // Prefer `rtc_sharing_policy` with fallback to `backend` and `None` as default.
-        let rtc_sharing_policy = payload
-            .rtc_sharing_policy
-            .or_else(|| payload.backend.map(|b| b.into()))
-            .unwrap_or(RtcSharingPolicy::None);
+        create_room(context, payload, reqp, None).await
+    }
+}
 
-        // Authorize room creation on the tenant.
-        let authz_time = context
-            .authz()
-            .authorize(
-                payload.audience.clone(),
-                reqp,
-                AuthzObject::new(&["classrooms"]).into(),
-                "create".into(),
-            )
-            .await?;
-        context.metrics().observe_auth(authz_time);
+/// Creates the room and copies the config defaults of the `template` it's created from.
+async fn create_room<C: Context + Send + Sync>(
+    context: &mut C,
+    payload: CreateRequest,
+    reqp: RequestParams<'_>,
+    template: Option<&db::room_template::Object>,
+) -> RequestResult {
+    // Prefer `rtc_sharing_policy` with fallback to `backend` and `None` as default.
+    let rtc_sharing_policy = payload
+        .rtc_sharing_policy
+        .or_else(|| payload.backend.map(|b| b.into()))
+        .unwrap_or(RtcSharingPolicy::None);
+
+    payload
+        .time
+        .validate()
+        .error(AppErrorKind::InvalidRoomTime)?;
+
+    // Authorize room creation on the tenant.
+    let authz_time = context
+        .authz()
+        .authorize(
+            payload.audience.clone(),
+            reqp,
+            AuthzObject::new(&["classrooms"]).into(),
+            "create".into(),
+        )
+        .await?;
+    context.metrics().observe_auth(authz_time);
+
+    let proposal = room_policy::Proposal {
+        method: "room.create",
+        audience: &payload.audience,
+        room_id: None,
+        time: Some(payload.time),
+        previous_opened_at: None,
+        reserve: payload.reserve,
+        classroom_id: Some(payload.classroom_id),
+        tags: payload.tags.as_ref(),
+        metadata: payload.metadata.as_ref(),
+    };
+
+    room_policy::check(context, &proposal).await?;
+
+    // Create a room.
+    let audience = payload.audience.clone();
+    let mut conn = context.get_conn().await?;
+    let mut q = db::room::InsertQuery::new(
+        payload.time,
+        &payload.audience,
+        rtc_sharing_policy,
+        payload.classroom_id,
+    );
+
+    if let Some(reserve) = payload.reserve {
+        q = q.reserve(reserve);
+    }
 
-        // Create a room.
-        let audience = payload.audience.clone();
+    if let Some(ref tags) = payload.tags {
+        q = q.tags(tags);
+    }
+
+    if let Some(audio_processing) = payload.audio_processing {
+        q = q.audio_processing(audio_processing);
+    }
+
+    if let Some(archive_messages) = payload.archive_messages {
+        q = q.archive_messages(archive_messages);
+    }
+
+    if let Some(composite_recording) = payload.composite_recording {
+        q = q.composite_recording(composite_recording);
+    }
+
+    if let Some(record_rtcs) = payload.record_rtcs {
+        q = q.record_rtcs(record_rtcs);
+    }
+
+    if let Some(stream_max_duration) = payload.stream_max_duration {
+        validate_stream_max_duration(stream_max_duration)?;
+        q = q.stream_max_duration(stream_max_duration);
+    }
+
+    if let Some(ref metadata) = payload.metadata {
+        validate_metadata(metadata)?;
+        q = q.metadata(metadata);
+    }
+
+    if let Some(lobby) = payload.lobby {
+        q = q.lobby(lobby);
+    }
+
+    if let Some(active_speaker_events) = payload.active_speaker_events {
+        q = q.active_speaker_events(active_speaker_events);
+    }
+
+    if let Some(max_agents) = payload.max_agents {
+        validate_max_agents(max_agents)?;
+        q = q.max_agents(max_agents);
+    }
+
+    let room = q.execute(&mut conn).await?;
+
+    if let Some(template) = template {
+        db::room_template::copy_defaults(room.id(), template, &mut conn).await?;
+    }
+
+    // Create a default group for minigroups
+    if room.rtc_sharing_policy() == db::rtc::SharingPolicy::Owned {
         let mut conn = context.get_conn().await?;
-        let mut q = db::room::InsertQuery::new(
-            payload.time,
-            &payload.audience,
-            rtc_sharing_policy,
-            payload.classroom_id,
-        );
+        let groups = Groups::new(vec![GroupItem::new(0, vec![])]);
+        db::group_agent::UpsertQuery::new(room.id(), &groups)
+            .execute(&mut conn)
+            .await?;
+    }
 
-        if let Some(reserve) = payload.reserve {
-            q = q.reserve(reserve);
-        }
+    tracing::Span::current().record(
+        "classroom_id",
+        &tracing::field::display(room.classroom_id()),
+    );
+
+    // Respond and broadcast to the audience topic.
+    let mut response = Response::new(
+        // TODO: Change to `ResponseStatus::CREATED` (breaking).
+        ResponseStatus::OK,
+        room.clone(),
+        context.start_timestamp(),
+        Some(authz_time),
+    );
+
+    response.add_notification(
+        "room.create",
+        &format!("audiences/{audience}/events"),
+        room,
+        context.start_timestamp(),
+    );
+
+    Ok(response)
+}
 
-        if let Some(ref tags) = payload.tags {
-            q = q.tags(tags);
-        }
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct CreateFromTemplateRequest {
+    audience: String,
+    /// Name of the audience's room template.
+    template: String,
+    time: RoomTime,
+    classroom_id: Uuid,
+    tags: Option<JsonValue>,
+    /// Overrides the template's reserve.
+    #[serde(default)]
+    reserve: Option<i32>,
+    /// Overrides the template's metadata.
+    #[serde(default)]
+    metadata: Option<JsonValue>,
+}
 
-        let room = q.execute(&mut conn).await?;
+pub async fn create_from_template(
+    Extension(ctx): Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Json(request): Json<CreateFromTemplateRequest>,
+) -> RequestResult {
+    http::handle::<CreateFromTemplateHandler>(&ctx, &agent_id, request).await
+}
 
-        // Create a default group for minigroups
-        if room.rtc_sharing_policy() == db::rtc::SharingPolicy::Owned {
+pub struct CreateFromTemplateHandler;
+
+#[async_trait]
+impl RequestHandler for CreateFromTemplateHandler {
+    type Payload = CreateFromTemplateRequest;
+    const ERROR_TITLE: &'static str = "Failed to create room from template";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::Object {
+        object: &["classrooms"],
+        actions: &["create"],
+    });
+
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let template = {
             let mut conn = context.get_conn().await?;
-            let groups = Groups::new(vec![GroupItem::new(0, vec![])]);
-            db::group_agent::UpsertQuery::new(room.id(), &groups)
-                .execute(&mut conn)
-                .await?;
-        }
+            db::room_template::find(&payload.audience, &payload.template, &mut conn)
+                .await?
+                .ok_or_else(|| anyhow!("Room template not found"))
+                .error(AppErrorKind::RoomTemplateNotFound)?
+        };
 
-        tracing::Span::current().record(
-            "classroom_id",
-            &tracing::field::display(room.classroom_id()),
-        );
+        let request = CreateRequest {
+            time: payload.time,
+            audience: payload.audience,
+            backend: None,
+            rtc_sharing_policy: Some(template.rtc_sharing_policy),
+            reserve: payload.reserve.or(template.reserve),
+            tags: payload.tags,
+            classroom_id: payload.classroom_id,
+            audio_processing: None,
+            archive_messages: None,
+            composite_recording: None,
+            record_rtcs: None,
+            stream_max_duration: None,
+            metadata: payload.metadata.or_else(|| template.metadata.clone()),
+            lobby: None,
+            active_speaker_events: None,
+            max_agents: None,
+        };
 
-        // Respond and broadcast to the audience topic.
-        let mut response = Response::new(
-            // TODO: Change to `ResponseStatus::CREATED` (breaking).
-            ResponseStatus::OK,
-            room.clone(),
-            context.start_timestamp(),
-            Some(authz_time),
-        );
+        let response = create_room(context, request, reqp, Some(&template)).await?;
 
-        response.add_notification(
-            "room.create",
-            &format!("audiences/{audience}/events"),
-            room,
-            context.start_timestamp(),
-        );
+        context
+            .metrics()
+            .request_duration
+            .room_create_from_template
+            .observe_timestamp(context.start_timestamp());
 
         Ok(response)
     }
@@ -189,7 +367,11 @@ impl RequestHandler for CreateHandler {
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct ReadRequest {
-    id: db::room::Id,
+    #[serde(default)]
+    id: Option<db::room::Id>,
+    /// Addresses the classroom's room when `id` is missing.
+    #[serde(default)]
+    classroom_id: Option<Uuid>,
 }
 
 pub async fn read(
@@ -199,15 +381,11 @@ pub async fn read(
 ) -> RequestResult {
     tracing::Span::current().record("room_id", &tracing::field::display(room_id));
 
-    let request = ReadRequest { id: room_id };
-    ReadHandler::handle(
-        &mut ctx.start_message(),
-        request,
-        RequestParams::Http {
-            agent_id: &agent_id,
-        },
-    )
-    .await
+    let request = ReadRequest {
+        id: Some(room_id),
+        classroom_id: None,
+    };
+    http::handle::<ReadHandler>(&ctx, &agent_id, request).await
 }
 
 pub struct ReadHandler;
@@ -216,16 +394,27 @@ pub struct ReadHandler;
 impl RequestHandler for ReadHandler {
     type Payload = ReadRequest;
     const ERROR_TITLE: &'static str = "Failed to read room";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::CLASSROOM_READ);
 
     async fn handle<C: Context + Send + Sync>(
         context: &mut C,
         payload: Self::Payload,
         reqp: RequestParams<'_>,
     ) -> RequestResult {
-        let room = {
+        let room_ref = helpers::RoomRef::new(payload.id, payload.classroom_id)?;
+
+        let (room, occupancy) = {
             let mut conn = context.get_conn().await?;
-            helpers::find_room_by_id(payload.id, helpers::RoomTimeRequirement::Any, &mut conn)
-                .await?
+            let room = helpers::find_room_by_ref(
+                room_ref,
+                helpers::RoomTimeRequirement::Any,
+                helpers::AudienceScope::caller(context, &reqp),
+                &mut conn,
+            )
+            .await?;
+
+            let occupancy = db::agent::count_occupants(room.id(), None, &mut conn).await?;
+            (room, occupancy)
         };
 
         tracing::Span::current().record(
@@ -250,251 +439,374 @@ impl RequestHandler for ReadHandler {
 
         Ok(Response::new(
             ResponseStatus::OK,
-            room,
+            ReadResponse { room, occupancy },
             context.start_timestamp(),
             Some(authz_time),
         ))
     }
 }
 
+/// The room along with the number of sessions in it so that clients can tell it's full.
+#[derive(Debug, Serialize)]
+struct ReadResponse {
+    #[serde(flatten)]
+    room: db::room::Object,
+    occupancy: i64,
+}
+
 ///////////////////////////////////////////////////////////////////////////////
 
 #[derive(Debug, Deserialize)]
-pub struct UpdateRequest {
+pub struct LimitsReadRequest {
     id: db::room::Id,
-    #[serde(default)]
-    #[serde(with = "crate::serde::ts_seconds_option_bound_tuple")]
-    time: Option<db::room::Time>,
-    reserve: Option<Option<i32>>,
-    tags: Option<JsonValue>,
-    classroom_id: Option<Uuid>,
-    host: Option<AgentId>,
-}
-
-#[derive(Debug, Deserialize)]
-pub struct UpdateFields {
-    #[serde(default)]
-    #[serde(with = "crate::serde::ts_seconds_option_bound_tuple")]
-    time: Option<db::room::Time>,
-    reserve: Option<Option<i32>>,
-    tags: Option<JsonValue>,
-    classroom_id: Option<Uuid>,
-    host: Option<AgentId>,
 }
 
-pub async fn update(
+pub async fn limits_read(
     Extension(ctx): Extension<Arc<AppContext>>,
     AgentIdExtractor(agent_id): AgentIdExtractor,
     Path(room_id): Path<db::room::Id>,
-    Json(request): Json<UpdateFields>,
 ) -> RequestResult {
     tracing::Span::current().record("room_id", &tracing::field::display(room_id));
 
-    let request = UpdateRequest {
-        id: room_id,
-        time: request.time,
-        reserve: request.reserve,
-        tags: request.tags,
-        classroom_id: request.classroom_id,
-        host: request.host,
-    };
-    UpdateHandler::handle(
-        &mut ctx.start_message(),
-        request,
-        RequestParams::Http {
-            agent_id: &agent_id,
-        },
-    )
-    .await
+    let request = LimitsReadRequest { id: room_id };
+    http::handle::<LimitsReadHandler>(&ctx, &agent_id, request).await
 }
 
-pub struct UpdateHandler;
+/// Limits the service applies to the requests in the room so that clients don't hardcode them.
+#[derive(Debug, Serialize)]
+pub struct Limits {
+    /// Upper bound of `limit` of `agent.list`, `rtc.list`, `rtc_stream.list` and `room.list`.
+    list_max_limit: i64,
+    /// Upper bound of `limit` of `message.history`.
+    history_max_limit: i64,
+    /// Broadcasts are checked only when they get archived.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_broadcast_payload_size: Option<usize>,
+    max_encrypted_message_size: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_sdp_size: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_agents: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_agents_per_account: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream_max_duration: Option<i32>,
+}
+
+impl Limits {
+    fn new(config: &crate::config::Config, room: &db::room::Object) -> Self {
+        Self {
+            list_max_limit: helpers::LIST_MAX_LIMIT,
+            history_max_limit: super::message::HISTORY_MAX_LIMIT,
+            max_broadcast_payload_size: room
+                .archive_messages()
+                .then(|| config.message_archive.max_payload_size),
+            max_encrypted_message_size: config.encrypted_message.max_size,
+            max_sdp_size: config.max_sdp_size,
+            max_agents: room.max_agents(),
+            max_agents_per_account: config
+                .connection_limit
+                .get(room.audience())
+                .map(|limit| limit.max_agents),
+            stream_max_duration: room.stream_max_duration(),
+        }
+    }
+}
+
+pub struct LimitsReadHandler;
 
 #[async_trait]
-impl RequestHandler for UpdateHandler {
-    type Payload = UpdateRequest;
-    const ERROR_TITLE: &'static str = "Failed to update room";
+impl RequestHandler for LimitsReadHandler {
+    type Payload = LimitsReadRequest;
+    const ERROR_TITLE: &'static str = "Failed to read room limits";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::CLASSROOM_READ);
 
     async fn handle<C: Context + Send + Sync>(
         context: &mut C,
         payload: Self::Payload,
         reqp: RequestParams<'_>,
     ) -> RequestResult {
-        let time_requirement = if payload.time.is_some() {
-            // Forbid changing time of a closed room.
-            helpers::RoomTimeRequirement::NotClosedOrUnboundedOpen
-        } else {
-            helpers::RoomTimeRequirement::Any
-        };
-
         let room = {
             let mut conn = context.get_conn().await?;
-            helpers::find_room_by_id(payload.id, time_requirement, &mut conn).await?
+            helpers::find_room_by_id(
+                payload.id,
+                helpers::RoomTimeRequirement::Any,
+                helpers::AudienceScope::caller(context, &reqp),
+                &mut conn,
+            )
+            .await?
         };
 
-        tracing::Span::current().record(
-            "classroom_id",
-            &tracing::field::display(room.classroom_id()),
-        );
-
-        // Authorize room updating on the tenant.
+        // Whoever may read the room may read its limits.
         let classroom_id = room.classroom_id().to_string();
         let object = AuthzObject::new(&["classrooms", &classroom_id]).into();
 
         let authz_time = context
             .authz()
-            .authorize(room.audience().into(), reqp, object, "update".into())
+            .authorize(room.audience().into(), reqp, object, "read".into())
             .await?;
         context.metrics().observe_auth(authz_time);
 
-        let room_was_open = !room.is_closed();
+        Ok(Response::new(
+            ResponseStatus::OK,
+            Limits::new(context.config(), &room),
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
 
-        // Update room.
-        let room = {
-            let mut conn = context.get_conn().await?;
+///////////////////////////////////////////////////////////////////////////////
 
-            let time = match payload.time {
-                None => None,
-                Some(new_time) => {
-                    match new_time {
-                        (Bound::Included(o), Bound::Excluded(c)) if o < c => (),
-                        (Bound::Included(_), Bound::Unbounded) => (),
-                        _ => {
-                            return Err(anyhow!("Invalid room time"))
-                                .error(AppErrorKind::InvalidRoomTime)
-                        }
-                    };
+const MAX_LIMIT: i64 = helpers::LIST_MAX_LIMIT;
 
-                    match room.time() {
-                        (_, Bound::Unbounded) => match new_time {
-                            (_, Bound::Excluded(_)) if room.infinite() => {
-                                return Err(anyhow!("Setting closing time is not allowed in this room since it's infinite"))
-                                .error(AppErrorKind::RoomTimeChangingForbidden);
-                            }
-                            // Allow any change when no closing date specified.
-                            _ => Some(new_time),
-                        },
-                        (Bound::Included(o), Bound::Excluded(c)) if c > Utc::now() => {
-                            match new_time {
-                                // Allow reschedule future closing.
-                                (_, Bound::Excluded(nc)) => {
-                                    let nc = std::cmp::max(nc, Utc::now());
-                                    Some((Bound::Included(o), Bound::Excluded(nc)))
-                                }
-                                _ => {
-                                    return Err(anyhow!("Setting unbounded closing time is not allowed in this room anymore"))
-                                        .error(AppErrorKind::RoomTimeChangingForbidden);
-                                }
-                            }
-                        }
-                        _ => {
-                            return Err(anyhow!("Room has been already closed"))
-                                .error(AppErrorKind::RoomTimeChangingForbidden);
-                        }
-                    }
-                }
-            };
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ListState {
+    /// Rooms not closed yet including the ones to be opened later.
+    Open,
+    Closed,
+}
 
-            db::room::UpdateQuery::new(room.id())
-                .time(time)
-                .reserve(payload.reserve)
-                .tags(payload.tags)
-                .classroom_id(payload.classroom_id)
-                .host(payload.host.as_ref())
-                .execute(&mut conn)
-                .await?
-        };
+#[derive(Debug, Deserialize)]
+pub struct ListRequest {
+    audience: String,
+    classroom_id: Option<Uuid>,
+    state: Option<ListState>,
+    backend_id: Option<AgentId>,
+    offset: Option<i64>,
+    limit: Option<i64>,
+}
 
-        // Respond and broadcast to the audience topic.
-        let mut response = Response::new(
-            ResponseStatus::OK,
-            room.clone(),
-            context.start_timestamp(),
-            Some(authz_time),
-        );
+pub async fn list(
+    Extension(ctx): Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Query(request): Query<ListRequest>,
+) -> RequestResult {
+    http::handle::<ListHandler>(&ctx, &agent_id, request).await
+}
 
-        response.add_notification(
-            "room.update",
-            &format!("audiences/{}/events", room.audience()),
-            room.clone(),
-            context.start_timestamp(),
-        );
+pub struct ListHandler;
 
-        // Publish room closed notification.
-        if let (_, Bound::Excluded(closed_at)) = room.time() {
-            if room_was_open && closed_at <= Utc::now() {
-                let room = {
-                    let mut conn = context.get_conn().await?;
-                    db::room::set_closed_by(room.id(), reqp.as_agent_id(), &mut conn).await?
-                };
+#[async_trait]
+impl RequestHandler for ListHandler {
+    type Payload = ListRequest;
+    const ERROR_TITLE: &'static str = "Failed to list rooms";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::Object {
+        object: &["classrooms"],
+        actions: &["list"],
+    });
+
+    #[instrument(skip(context, payload, reqp), fields(audience = %payload.audience))]
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        if !helpers::AudienceScope::caller(context, &reqp).allows(&payload.audience) {
+            return Err(anyhow!("Listing rooms of another audience"))
+                .error(AppErrorKind::AccessDenied);
+        }
 
-                response.add_notification(
-                    "room.close",
-                    &format!("rooms/{}/events", room.id()),
-                    room.clone(),
-                    context.start_timestamp(),
-                );
+        // Authorize room listing on the tenant.
+        let authz_time = context
+            .authz()
+            .authorize(
+                payload.audience.clone(),
+                reqp,
+                AuthzObject::new(&["classrooms"]).into(),
+                "list".into(),
+            )
+            .await?;
+        context.metrics().observe_auth(authz_time);
 
-                response.add_notification(
-                    "room.close",
-                    &format!("audiences/{}/events", room.audience()),
-                    room,
-                    context.start_timestamp(),
-                );
-            }
+        let mut query = db::room::ListQuery::new(&payload.audience);
+
+        if let Some(classroom_id) = payload.classroom_id {
+            query = query.classroom_id(classroom_id);
+        }
+
+        if let Some(state) = payload.state {
+            query = query.closed(state == ListState::Closed);
+        }
+
+        if let Some(ref backend_id) = payload.backend_id {
+            query = query.backend_id(backend_id);
+        }
+
+        if let Some(offset) = payload.offset {
+            query = query.offset(offset);
         }
+
+        let limit = std::cmp::min(payload.limit.unwrap_or(MAX_LIMIT), MAX_LIMIT);
+
+        let rooms = {
+            let mut conn = context.get_conn().await?;
+            query.limit(limit).execute(&mut conn).await?
+        };
+
         context
             .metrics()
             .request_duration
-            .room_update
+            .room_list
             .observe_timestamp(context.start_timestamp());
 
-        Ok(response)
+        Ok(Response::new(
+            ResponseStatus::OK,
+            rooms,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
     }
 }
 
 ///////////////////////////////////////////////////////////////////////////////
 
 #[derive(Debug, Deserialize)]
-pub struct CloseRequest {
-    id: db::room::Id,
+pub struct UpdateRequest {
+    /// The room gets addressed by `classroom_id` when missing. The classroom can't be changed
+    /// then.
+    #[serde(default)]
+    id: Option<db::room::Id>,
+    #[serde(default)]
+    time: Option<RoomTime>,
+    reserve: Option<Option<i32>>,
+    tags: Option<JsonValue>,
+    classroom_id: Option<Uuid>,
+    /// Makes the agent the only host of the room.
+    host: Option<AgentId>,
+    #[serde(default)]
+    add_hosts: Option<Vec<AgentId>>,
+    #[serde(default)]
+    remove_hosts: Option<Vec<AgentId>>,
+    audio_processing: Option<AudioProcessing>,
+    archive_messages: Option<bool>,
+    composite_recording: Option<bool>,
+    record_rtcs: Option<bool>,
+    stream_max_duration: Option<i32>,
+    metadata: Option<JsonValue>,
+    lobby: Option<bool>,
+    active_speaker_events: Option<bool>,
+    max_agents: Option<i32>,
+    #[serde(default)]
+    if_version: Option<i32>,
 }
 
-pub async fn close(
+#[derive(Debug, Deserialize)]
+pub struct UpdateFields {
+    #[serde(default)]
+    time: Option<RoomTime>,
+    reserve: Option<Option<i32>>,
+    tags: Option<JsonValue>,
+    classroom_id: Option<Uuid>,
+    /// Makes the agent the only host of the room.
+    host: Option<AgentId>,
+    #[serde(default)]
+    add_hosts: Option<Vec<AgentId>>,
+    #[serde(default)]
+    remove_hosts: Option<Vec<AgentId>>,
+    audio_processing: Option<AudioProcessing>,
+    archive_messages: Option<bool>,
+    composite_recording: Option<bool>,
+    record_rtcs: Option<bool>,
+    stream_max_duration: Option<i32>,
+    metadata: Option<JsonValue>,
+    lobby: Option<bool>,
+    active_speaker_events: Option<bool>,
+    max_agents: Option<i32>,
+    #[serde(default)]
+    if_version: Option<i32>,
+}
+
+pub async fn update(
     Extension(ctx): Extension<Arc<AppContext>>,
     AgentIdExtractor(agent_id): AgentIdExtractor,
     Path(room_id): Path<db::room::Id>,
+    Json(request): Json<UpdateFields>,
 ) -> RequestResult {
     tracing::Span::current().record("room_id", &tracing::field::display(room_id));
 
-    let request = CloseRequest { id: room_id };
-    CloseHandler::handle(
-        &mut ctx.start_message(),
-        request,
-        RequestParams::Http {
-            agent_id: &agent_id,
-        },
-    )
-    .await
+    let request = UpdateRequest {
+        id: Some(room_id),
+        time: request.time,
+        reserve: request.reserve,
+        tags: request.tags,
+        classroom_id: request.classroom_id,
+        host: request.host,
+        add_hosts: request.add_hosts,
+        remove_hosts: request.remove_hosts,
+        audio_processing: request.audio_processing,
+        archive_messages: request.archive_messages,
+        composite_recording: request.composite_recording,
+        record_rtcs: request.record_rtcs,
+        stream_max_duration: request.stream_max_duration,
+        metadata: request.metadata,
+        lobby: request.lobby,
+        active_speaker_events: request.active_speaker_events,
+        max_agents: request.max_agents,
+        if_version: request.if_version,
+    };
+    http::handle::<UpdateHandler>(&ctx, &agent_id, request).await
 }
 
-pub struct CloseHandler;
+pub struct UpdateHandler;
+
+/// Applies host changes of the update. Returns whether any of them has been requested.
+async fn update_hosts(
+    room_id: db::room::Id,
+    payload: &UpdateRequest,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<bool> {
+    if let Some(ref host) = payload.host {
+        db::room_host::replace(room_id, host, conn).await?;
+    }
+
+    if let Some(ref add_hosts) = payload.add_hosts {
+        let add_hosts = add_hosts.iter().collect::<Vec<_>>();
+        db::room_host::insert(room_id, &add_hosts, conn).await?;
+    }
+
+    if let Some(ref remove_hosts) = payload.remove_hosts {
+        let remove_hosts = remove_hosts.iter().collect::<Vec<_>>();
+        db::room_host::delete(room_id, &remove_hosts, conn).await?;
+    }
+
+    let changed =
+        payload.host.is_some() || payload.add_hosts.is_some() || payload.remove_hosts.is_some();
+
+    if changed {
+        db::room_host::sync_room(room_id, conn).await?;
+    }
+
+    Ok(changed)
+}
 
 #[async_trait]
-impl RequestHandler for CloseHandler {
-    type Payload = CloseRequest;
-    const ERROR_TITLE: &'static str = "Failed to close room";
+impl RequestHandler for UpdateHandler {
+    type Payload = UpdateRequest;
+    const ERROR_TITLE: &'static str = "Failed to update room";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::CLASSROOM_UPDATE);
 
     async fn handle<C: Context + Send + Sync>(
         context: &mut C,
         payload: Self::Payload,
         reqp: RequestParams<'_>,
     ) -> RequestResult {
+        let time_requirement = if payload.time.is_some() {
+            // Forbid changing time of a closed room.
+            helpers::RoomTimeRequirement::NotClosedOrUnboundedOpen
+        } else {
+            helpers::RoomTimeRequirement::Any
+        };
+
+        let room_ref = helpers::RoomRef::new(payload.id, payload.classroom_id)?;
+
         let room = {
             let mut conn = context.get_conn().await?;
-            helpers::find_room_by_id(
-                payload.id,
-                helpers::RoomTimeRequirement::NotClosedOrUnboundedOpen,
+            helpers::find_room_by_ref(
+                room_ref,
+                time_requirement,
+                helpers::AudienceScope::caller(context, &reqp),
                 &mut conn,
             )
             .await?
@@ -505,11 +817,6 @@ impl RequestHandler for CloseHandler {
             &tracing::field::display(room.classroom_id()),
         );
 
-        if room.infinite() {
-            return Err(anyhow!("Not closing this room because its infinite"))
-                .error(AppErrorKind::RoomTimeChangingForbidden);
-        }
-
         // Authorize room updating on the tenant.
         let classroom_id = room.classroom_id().to_string();
         let object = AuthzObject::new(&["classrooms", &classroom_id]).into();
@@ -520,10 +827,154 @@ impl RequestHandler for CloseHandler {
             .await?;
         context.metrics().observe_auth(authz_time);
 
+        if let Some(if_version) = payload.if_version {
+            if if_version != room.version() {
+                return Err(version_conflict(room.version()));
+            }
+        }
+
+        let room_was_open = !room.is_closed();
+        let old_reserve = room.reserve();
+
         // Update room.
         let room = {
             let mut conn = context.get_conn().await?;
-            db::room::set_closed_by(room.id(), reqp.as_agent_id(), &mut conn).await?
+
+            let time = match payload.time {
+                None => None,
+                Some(new_time) => {
+                    match (new_time.start(), new_time.end()) {
+                        (Bound::Included(o), Bound::Excluded(c)) if o < c => (),
+                        (Bound::Included(_), Bound::Unbounded) => (),
+                        _ => {
+                            return Err(anyhow!("Invalid room time"))
+                                .error(AppErrorKind::InvalidRoomTime)
+                        }
+                    };
+
+                    let time = room.time();
+
+                    match time.closed_at() {
+                        None if new_time.closed_at().is_some() && room.infinite() => {
+                            return Err(anyhow!("Setting closing time is not allowed in this room since it's infinite"))
+                                .error(AppErrorKind::RoomTimeChangingForbidden);
+                        }
+                        // Allow any change when no closing date specified.
+                        None => Some(new_time),
+                        Some(_) if !time.is_closed_at(Utc::now()) => match new_time.closed_at() {
+                            // Allow reschedule future closing.
+                            Some(nc) => Some(time.close_at(std::cmp::max(nc, Utc::now()))),
+                            None => {
+                                return Err(anyhow!("Setting unbounded closing time is not allowed in this room anymore"))
+                                    .error(AppErrorKind::RoomTimeChangingForbidden);
+                            }
+                        },
+                        Some(_) => {
+                            return Err(anyhow!("Room has been already closed"))
+                                .error(AppErrorKind::RoomTimeChangingForbidden);
+                        }
+                    }
+                }
+            };
+
+            if let Some(Some(reserve)) = payload.reserve {
+                check_reserve(&room, reserve, &mut conn).await?;
+            }
+
+            if let Some(stream_max_duration) = payload.stream_max_duration {
+                validate_stream_max_duration(stream_max_duration)?;
+            }
+
+            if let Some(max_agents) = payload.max_agents {
+                validate_max_agents(max_agents)?;
+            }
+
+            if let Some(ref metadata) = payload.metadata {
+                validate_metadata(metadata)?;
+            }
+
+            let proposal = room_policy::Proposal {
+                method: "room.update",
+                audience: room.audience(),
+                room_id: Some(room.id()),
+                time,
+                previous_opened_at: room.time().opened_at(),
+                reserve: payload.reserve.flatten(),
+                classroom_id: payload.classroom_id,
+                tags: payload.tags.as_ref(),
+                metadata: payload.metadata.as_ref(),
+            };
+
+            room_policy::check(context, &proposal).await?;
+
+            // Hosts change first so that `host` of the updated room mirrors them.
+            let mut txn = conn.begin().await?;
+            let hosts_changed = update_hosts(room.id(), &payload, &mut txn).await?;
+
+            if hosts_changed {
+                info!(room_id = %room.id(), changed_by = %reqp.as_agent_id(), "Room hosts changed");
+            }
+
+            let result = db::room::UpdateQuery::new(room.id())
+                .time(time)
+                .reserve(payload.reserve)
+                .tags(payload.tags)
+                .classroom_id(payload.classroom_id)
+                .audio_processing(payload.audio_processing)
+                .archive_messages(payload.archive_messages)
+                .composite_recording(payload.composite_recording)
+                .record_rtcs(payload.record_rtcs)
+                .stream_max_duration(payload.stream_max_duration)
+                .metadata(payload.metadata)
+                .lobby(payload.lobby)
+                .active_speaker_events(payload.active_speaker_events)
+                .max_agents(payload.max_agents)
+                .if_version(payload.if_version)
+                .execute(&mut txn)
+                .await;
+
+            let room = match result {
+                Ok(room) => room,
+                // The room has been updated concurrently after the version check above.
+                Err(sqlx::Error::RowNotFound) if payload.if_version.is_some() => {
+                    let room = helpers::find_room_by_id(
+                        room.id(),
+                        helpers::RoomTimeRequirement::Any,
+                        helpers::AudienceScope::caller(context, &reqp),
+                        &mut txn,
+                    )
+                    .await?;
+
+                    return Err(version_conflict(room.version()));
+                }
+                Err(err) => return Err(err.into()),
+            };
+
+            txn.commit().await?;
+
+            // Access to the room might have changed so its agents get authorized again.
+            context.room_authz().forget_room(room.id());
+
+            if room.reserve() != old_reserve {
+                db::room_reserve_audit::InsertQuery::new(
+                    room.id(),
+                    old_reserve,
+                    room.reserve(),
+                    reqp.as_agent_id(),
+                )
+                .execute(&mut conn)
+                .await?;
+
+                info!(
+                    room_id = %room.id(),
+                    ?old_reserve,
+                    new_reserve = ?room.reserve(),
+                    changed_by = %reqp.as_agent_id(),
+                    "Room reserve changed"
+                );
+            }
+
+            room
         };
 
         // Respond and broadcast to the audience topic.
@@ -541,55 +992,449 @@ impl RequestHandler for CloseHandler {
             context.start_timestamp(),
         );
 
-        response.add_notification(
-            "room.close",
-            &format!("rooms/{}/events", room.id()),
-            room.clone(),
-            context.start_timestamp(),
-        );
+        // Publish room closed notification.
+        if let Some(closed_at) = room.time().closed_at() {
+            if room_was_open && closed_at <= Utc::now() {
+                let (room, summary) = {
+                    let mut conn = context.get_conn().await?;
+                    let room =
+                        db::room::set_closed_by(room.id(), reqp.as_agent_id(), &mut conn).await?;
+                    let summary = helpers::summarize_room(room.id(), &mut conn).await;
+                    room_upload::schedule(&context.config().room_upload, &room, &mut conn).await?;
+                    (room, summary)
+                };
 
-        response.add_notification(
-            "room.close",
-            &format!("audiences/{}/events", room.audience()),
-            room,
-            context.start_timestamp(),
-        );
+                response.add_notification(
+                    "room.close",
+                    &format!("rooms/{}/events", room.id()),
+                    room.clone(),
+                    context.start_timestamp(),
+                );
+
+                if let Some(summary) = summary {
+                    response.add_notification(
+                        "room.summary",
+                        &format!("audiences/{}/events", room.audience()),
+                        summary,
+                        context.start_timestamp(),
+                    );
+                }
 
+                response.add_notification(
+                    "room.close",
+                    &format!("audiences/{}/events", room.audience()),
+                    room,
+                    context.start_timestamp(),
+                );
+            }
+        }
         context
             .metrics()
             .request_duration
-            .room_close
+            .room_update
             .observe_timestamp(context.start_timestamp());
 
         Ok(response)
     }
 }
 
-///////////////////////////////////////////////////////////////////////////////
+/// Makes sure the backend of an open room can take the grown reserve. Agents already connected
+/// stay where they are, the new reserve is taken into account when balancing new connects only.
+async fn check_reserve(
+    room: &db::room::Object,
+    reserve: i32,
+    conn: &mut sqlx::PgConnection,
+) -> Result<(), AppError> {
+    if reserve < 0 {
+        return Err(anyhow!("Reserve must not be negative")).error(AppErrorKind::InvalidPayload);
+    }
 
-#[derive(Deserialize)]
-pub struct EnterPayload {
-    #[serde(default)]
-    agent_label: Option<String>,
+    let backend_id = match room.backend_id() {
+        Some(backend_id) if !room.is_closed() => backend_id,
+        _ => return Ok(()),
+    };
+
+    if reserve <= room.reserve().unwrap_or(0) {
+        return Ok(());
+    }
+
+    let backend = match db::janus_backend::FindQuery::new(backend_id)
+        .execute(conn)
+        .await?
+    {
+        Some(backend) => backend,
+        None => return Ok(()),
+    };
+
+    let capacity = match backend.balancer_capacity.or(backend.capacity) {
+        Some(capacity) => i64::from(capacity),
+        None => return Ok(()),
+    };
+
+    let load = db::janus_backend::load_with_reserve(backend_id, room.id(), reserve, conn).await?;
+
+    if load > capacity {
+        let err = AppError::new(
+            AppErrorKind::ReserveExceedsCapacity,
+            anyhow!(
+                "Backend load would be {} with the reserve of {} while its capacity is {}",
+                load,
+                reserve,
+                capacity
+            ),
+        )
+        .with_extra("backend_id", backend_id.to_string())
+        .with_extra("load", load.to_string())
+        .with_extra("capacity", capacity.to_string());
+
+        return Err(err);
+    }
+
+    Ok(())
 }
 
-pub async fn enter(
-    Extension(ctx): Extension<Arc<AppContext>>,
-    AgentIdExtractor(agent_id): AgentIdExtractor,
-    Path(room_id): Path<db::room::Id>,
-    payload: Option<Json<EnterPayload>>,
-) -> RequestResult {
-    tracing::Span::current().record("room_id", &tracing::field::display(room_id));
+fn validate_stream_max_duration(stream_max_duration: i32) -> Result<(), AppError> {
+    if stream_max_duration <= 0 {
+        return Err(anyhow!("Stream max duration must be positive"))
+            .error(AppErrorKind::InvalidPayload);
+    }
 
-    let request = EnterRequest { id: room_id };
+    Ok(())
+}
 
-    let agent_id = payload
-        .and_then(|p| {
-            p.agent_label
-                .as_ref()
-                .map(|label| AgentId::new(label, agent_id.as_account_id().to_owned()))
-        })
-        .unwrap_or(agent_id);
+fn validate_max_agents(max_agents: i32) -> Result<(), AppError> {
+    if max_agents <= 0 {
+        return Err(anyhow!("Max agents must be positive")).error(AppErrorKind::InvalidPayload);
+    }
+
+    Ok(())
+}
+
+/// Limit of serialized `metadata` of a room.
+const MAX_METADATA_SIZE: usize = 16 * 1024;
+
+pub(crate) fn validate_metadata(metadata: &JsonValue) -> Result<(), AppError> {
+    if !metadata.is_object() {
+        return Err(anyhow!("Metadata must be an object")).error(AppErrorKind::InvalidPayload);
+    }
+
+    if metadata.to_string().len() > MAX_METADATA_SIZE {
+        return Err(anyhow!("Metadata exceeds {} bytes", MAX_METADATA_SIZE))
+            .error(AppErrorKind::InvalidPayload);
+    }
+
+    Ok(())
+}
+
+fn version_conflict(current_version: i32) -> AppError {
+    AppError::new(
+        AppErrorKind::Conflict,
+        anyhow!(
+            "Room has been modified, current version = {}",
+            current_version
+        ),
+    )
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct CloseRequest {
+    id: db::room::Id,
+}
+
+pub async fn close(
+    Extension(ctx): Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<db::room::Id>,
+) -> RequestResult {
+    tracing::Span::current().record("room_id", &tracing::field::display(room_id));
+
+    let request = CloseRequest { id: room_id };
+    http::handle::<CloseHandler>(&ctx, &agent_id, request).await
+}
+
+pub struct CloseHandler;
+
+#[async_trait]
+impl RequestHandler for CloseHandler {
+    type Payload = CloseRequest;
+    const ERROR_TITLE: &'static str = "Failed to close room";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::CLASSROOM_UPDATE);
+
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let room = {
+            let mut conn = context.get_conn().await?;
+            helpers::find_room_by_id(
+                payload.id,
+                helpers::RoomTimeRequirement::NotClosedOrUnboundedOpen,
+                helpers::AudienceScope::caller(context, &reqp),
+                &mut conn,
+            )
+            .await?
+        };
+
+        tracing::Span::current().record(
+            "classroom_id",
+            &tracing::field::display(room.classroom_id()),
+        );
+
+        if room.infinite() {
+            return Err(anyhow!("Not closing this room because its infinite"))
+                .error(AppErrorKind::RoomTimeChangingForbidden);
+        }
+
+        // Authorize room updating on the tenant.
+        let classroom_id = room.classroom_id().to_string();
+        let object = AuthzObject::new(&["classrooms", &classroom_id]).into();
+
+        let authz_time = context
+            .authz()
+            .authorize(room.audience().into(), reqp, object, "update".into())
+            .await?;
+        context.metrics().observe_auth(authz_time);
+
+        // Update room.
+        let (room, summary) = {
+            let mut conn = context.get_conn().await?;
+            close_room(
+                room.id(),
+                reqp.as_agent_id(),
+                &context.config().room_upload,
+                &mut conn,
+            )
+            .await?
+        };
+
+        // Respond and broadcast to the audience topic.
+        let mut response = Response::new(
+            ResponseStatus::OK,
+            room.clone(),
+            context.start_timestamp(),
+            Some(authz_time),
+        );
+
+        for (label, path, payload) in close_notifications(&room, summary) {
+            response.add_notification(label, &path, payload, context.start_timestamp());
+        }
+
+        context
+            .metrics()
+            .request_duration
+            .room_close
+            .observe_timestamp(context.start_timestamp());
+
+        Ok(response)
+    }
+}
+
+/// Closes the room right away on behalf of the agent, stores its summary and schedules the
+/// upload of its recordings.
+pub async fn close_room(
+    room_id: db::room::Id,
+    agent_id: &AgentId,
+    upload_config: &RoomUploadConfig,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<(db::room::Object, Option<db::room_summary::Object>)> {
+    let room = db::room::set_closed_by(room_id, agent_id, conn).await?;
+    let summary = helpers::summarize_room(room.id(), conn).await;
+    room_upload::schedule(upload_config, &room, conn).await?;
+    Ok((room, summary))
+}
+
+/// Label, topic and payload of each event about the room having been closed in publishing order.
+pub fn close_notifications(
+    room: &db::room::Object,
+    summary: Option<db::room_summary::Object>,
+) -> Vec<(&'static str, String, JsonValue)> {
+    let audience_topic = format!("audiences/{}/events", room.audience());
+    let room_json = json!(room);
+
+    let mut notifications = vec![
+        ("room.update", audience_topic.clone(), room_json.clone()),
+        (
+            "room.close",
+            format!("rooms/{}/events", room.id()),
+            room_json.clone(),
+        ),
+    ];
+
+    if let Some(summary) = summary {
+        notifications.push(("room.summary", audience_topic.clone(), json!(summary)));
+    }
+
+    notifications.push(("room.close", audience_topic, room_json));
+    notifications
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct DeleteRequest {
+    id: db::room::Id,
+}
+
+pub async fn delete(
+    Extension(ctx): Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<db::room::Id>,
+) -> RequestResult {
+    tracing::Span::current().record("room_id", &tracing::field::display(room_id));
+
+    let request = DeleteRequest { id: room_id };
+    http::handle::<DeleteHandler>(&ctx, &agent_id, request).await
+}
+
+pub struct DeleteHandler;
+
+#[async_trait]
+impl RequestHandler for DeleteHandler {
+    type Payload = DeleteRequest;
+    const ERROR_TITLE: &'static str = "Failed to delete room";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::Object {
+        object: &["classrooms", "{classroom_id}"],
+        actions: &["delete"],
+    });
+
+    #[instrument(skip(context, payload, reqp), fields(room_id = %payload.id))]
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let room = {
+            let mut conn = context.get_conn().await?;
+            helpers::find_room_by_id(
+                payload.id,
+                helpers::RoomTimeRequirement::Any,
+                helpers::AudienceScope::caller(context, &reqp),
+                &mut conn,
+            )
+            .await?
+        };
+
+        tracing::Span::current().record(
+            "classroom_id",
+            &tracing::field::display(room.classroom_id()),
+        );
+
+        // Authorize room deletion on the tenant.
+        let classroom_id = room.classroom_id().to_string();
+        let object = AuthzObject::new(&["classrooms", &classroom_id]).into();
+
+        let authz_time = context
+            .authz()
+            .authorize(room.audience().into(), reqp, object, "delete".into())
+            .await?;
+        context.metrics().observe_auth(authz_time);
+
+        // Streams of an open room would be left on the backend without their rows.
+        if !room.is_closed() {
+            return Err(anyhow!("Room must be closed before deletion"))
+                .error(AppErrorKind::Conflict);
+        }
+
+        {
+            let mut conn = context.get_conn().await?;
+            let room_id = room.id();
+
+            conn.transaction(|conn| {
+                Box::pin(async move {
+                    db::orphaned_room::remove_room(room_id, conn).await?;
+                    db::room::delete(room_id, conn).await
+                })
+            })
+            .await?;
+        }
+
+        info!(room_id = %room.id(), "Room deleted");
+
+        // Respond and broadcast to the audience topic.
+        let mut response = Response::new(
+            ResponseStatus::OK,
+            room.clone(),
+            context.start_timestamp(),
+            Some(authz_time),
+        );
+
+        response.add_notification(
+            "room.delete",
+            &format!("audiences/{}/events", room.audience()),
+            room,
+            context.start_timestamp(),
+        );
+
+        context
+            .metrics()
+            .request_duration
+            .room_delete
+            .observe_timestamp(context.start_timestamp());
+
+        Ok(response)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Deserialize)]
+pub struct EnterPayload {
+    #[serde(default)]
+    agent_label: Option<String>,
+    /// Key of the session the client has got on its previous entrance.
+    #[serde(default)]
+    session_key: Option<db::agent_session::SessionKey>,
+}
+
+pub async fn enter(
+    Extension(ctx): Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<db::room::Id>,
+    payload: Option<Json<EnterPayload>>,
+) -> RequestResult {
+    tracing::Span::current().record("room_id", &tracing::field::display(room_id));
+    enter_room(ctx, agent_id, helpers::RoomRef::Id(room_id), payload).await
+}
+
+pub async fn enter_classroom(
+    Extension(ctx): Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(classroom_id): Path<Uuid>,
+    payload: Option<Json<EnterPayload>>,
+) -> RequestResult {
+    tracing::Span::current().record("classroom_id", &tracing::field::display(classroom_id));
+    enter_room(
+        ctx,
+        agent_id,
+        helpers::RoomRef::Classroom(classroom_id),
+        payload,
+    )
+    .await
+}
+
+async fn enter_room(
+    ctx: Arc<AppContext>,
+    agent_id: AgentId,
+    room: helpers::RoomRef,
+    payload: Option<Json<EnterPayload>>,
+) -> RequestResult {
+    let _in_flight = http::admit(&ctx, false)?;
+
+    let request = EnterRequest {
+        room,
+        session_key: payload.as_ref().and_then(|p| p.session_key),
+    };
+
+    let agent_id = payload
+        .and_then(|p| {
+            p.agent_label
+                .as_ref()
+                .map(|label| AgentId::new(label, agent_id.as_account_id().to_owned()))
+        })
+        .unwrap_or(agent_id);
 
     EnterHandler::handle(
         ctx,
@@ -602,7 +1447,12 @@ pub async fn enter(
     .await
 }
 
-pub type EnterRequest = ReadRequest;
+#[derive(Debug, Clone)]
+pub struct EnterRequest {
+    room: helpers::RoomRef,
+    session_key: Option<db::agent_session::SessionKey>,
+}
+
 pub struct EnterHandler;
 
 impl EnterHandler {
@@ -614,9 +1464,10 @@ impl EnterHandler {
     ) -> RequestResult {
         let room = {
             let mut conn = context.get_conn().await?;
-            helpers::find_room_by_id(
-                payload.id,
+            helpers::find_room_by_ref(
+                payload.room,
                 helpers::RoomTimeRequirement::NotClosed,
+                helpers::AudienceScope::caller(&*context, &reqp),
                 &mut conn,
             )
             .await?
@@ -630,20 +1481,83 @@ impl EnterHandler {
         // Authorize subscribing to the room's events.
         let room_id = room.id().to_string();
         let classroom_id = room.classroom_id().to_string();
-        let object = AuthzObject::new(&["classrooms", &classroom_id]).into();
+        let object = AuthzObject::new(&["classrooms", &classroom_id]);
+
+        // Connecting to the room's RTCs right after reuses the decision.
+        helpers::authorize_in_room(
+            &*context,
+            &room,
+            reqp,
+            object,
+            room_authz::ActionClass::Read,
+        )
+        .await?;
+
+        // In lobby rooms everyone but the hosts waits to be admitted.
+        let status = if room.lobby() && !helpers::is_host(&*context, &room, reqp).await? {
+            db::agent::Status::Pending
+        } else {
+            db::agent::Status::Requested
+        };
 
-        let authz_time = context
-            .authz()
-            .authorize(room.audience().into(), reqp, object, "read".into())
+        // Register agent in `requested` state or `pending` in the lobby. A retried enter
+        // keeps whatever state the agent has already reached.
+        let (session_key, agent) = {
+            let mut conn = context.get_conn().await?;
+            helpers::check_ban(&room, reqp.as_agent_id(), &mut conn).await?;
+            helpers::check_connection_limit(&*context, &room, reqp.as_agent_id(), &mut conn)
+                .await?;
+
+            // A full room rejects the entrance so the session isn't recorded either.
+            let mut txn = conn.begin().await?;
+
+            let session_key = db::agent_session::resolve(
+                room.id(),
+                reqp.as_agent_id(),
+                payload.session_key,
+                &mut txn,
+            )
             .await?;
-        context.metrics().observe_auth(authz_time);
 
-        // Register agent in `in_progress` state.
-        {
-            let mut conn = context.get_conn().await?;
-            db::agent::InsertQuery::new(reqp.as_agent_id(), room.id())
-                .execute(&mut conn)
+            // Agents waiting in the lobby don't take a seat until admitted.
+            if status != db::agent::Status::Pending {
+                helpers::reserve_room_seat(&room, session_key, &mut txn).await?;
+            }
+
+            let agent = db::agent::InsertQuery::new(reqp.as_agent_id(), room.id())
+                .session_key(session_key)
+                .status(status)
+                .execute(&mut txn)
                 .await?;
+
+            txn.commit().await?;
+            (session_key, agent)
+        };
+
+        // Hosts are asked to admit the agent which gets neither the room's events nor
+        // an RTC until then. An agent left pending after the lobby got disabled goes on.
+        if status == db::agent::Status::Pending && agent.status() == db::agent::Status::Pending {
+            let mut response = Response::new(
+                ResponseStatus::OK,
+                json!({ "session_key": session_key, "pending": true }),
+                start_timestamp,
+                None,
+            );
+
+            response.add_notification(
+                "room.enter_request",
+                &format!("rooms/{room_id}/events"),
+                RoomEnterLeaveEvent::new(room.id(), reqp.as_agent_id().to_owned()),
+                start_timestamp,
+            );
+
+            context
+                .metrics()
+                .request_duration
+                .room_enter
+                .observe_timestamp(start_timestamp);
+
+            return Ok(response);
         }
 
         // Send dynamic subscription creation request to the broker.
@@ -665,18 +1579,24 @@ impl EnterHandler {
         {
             let mut conn = context.get_conn().await?;
 
-            if room.host() == Some(&subject) {
+            if db::room_host::is_host(room.id(), &subject, &mut conn).await? {
                 db::orphaned_room::remove_room(room.id(), &mut conn).await?;
             }
 
-            // Update agent state to `ready`.
+            // The broker has confirmed the subscription. Its `subscription.create`
+            // event may have already got the agent here.
             db::agent::UpdateQuery::new(&subject, room.id())
-                .status(db::agent::Status::Ready)
+                .status(db::agent::Status::Subscribed)
                 .execute(&mut conn)
                 .await?;
         }
 
-        let mut response = Response::new(ResponseStatus::OK, json!({}), start_timestamp, None);
+        let mut response = Response::new(
+            ResponseStatus::OK,
+            json!({ "session_key": session_key, "pending": false }),
+            start_timestamp,
+            None,
+        );
 
         let ctx = context.clone();
         let room_id = room.id();
@@ -701,6 +1621,8 @@ impl EnterHandler {
                     ctx: context.as_ref(),
                     room: either::Either::Left(room.clone()),
                     reqp,
+                    tags: None,
+                    kind: None,
                 }
                 .run()
                 .await?;
@@ -750,13 +1672,16 @@ impl EnterHandler {
 
                                 // Generate configs for janus
                                 let items = configs
+                                    .delta
                                     .into_iter()
-                                    .map(|((rtc_id, agent_id), value)| {
+                                    .map(|((rtc_id, agent_id), (video, audio))| {
                                         UpdateReaderConfigRequestBodyConfigItem {
                                             reader_id: agent_id,
                                             stream_id: rtc_id,
-                                            receive_video: value,
-                                            receive_audio: value,
+                                            receive_video: video,
+                                            receive_audio: audio,
+                                            spatial_layer: None,
+                                            temporal_layer: None,
                                         }
                                     })
                                     .collect();
@@ -771,6 +1696,7 @@ impl EnterHandler {
                                     room.id(),
                                     backend_id,
                                     items,
+                                    configs.full_size,
                                 );
 
                                 let serialized_stage = serde_json::to_value(init_stage)
@@ -829,6 +1755,18 @@ impl EnterHandler {
             }
         };
 
+        // Update agent state to `ready` once everything the room needs on entry is in place.
+        {
+            let mut conn = context.get_conn().await?;
+
+            db::agent::UpdateQuery::new(&subject, room_id)
+                .status(db::agent::Status::Ready)
+                .execute(&mut conn)
+                .await?;
+
+            db::room_summary::observe_concurrency(room_id, &mut conn).await?;
+        }
+
         response.add_notification(
             "room.enter",
             &format!("rooms/{room_id}/events"),
@@ -848,13 +1786,18 @@ impl EnterHandler {
 
 ///////////////////////////////////////////////////////////////////////////////
 
-pub type LeaveRequest = ReadRequest;
+#[derive(Debug, Deserialize, Clone)]
+pub struct LeaveRequest {
+    id: db::room::Id,
+}
+
 pub struct LeaveHandler;
 
 #[async_trait]
 impl RequestHandler for LeaveHandler {
     type Payload = LeaveRequest;
     const ERROR_TITLE: &'static str = "Failed to leave room";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::Presence);
 
     #[instrument(skip(context, payload, reqp), fields(room_id = %payload.id))]
     async fn handle<C: Context + Send + Sync>(
@@ -866,13 +1809,18 @@ impl RequestHandler for LeaveHandler {
 
         let mut conn = context.get_conn().await?;
 
-        let room =
-            helpers::find_room_by_id(payload.id, helpers::RoomTimeRequirement::Any, &mut conn)
-                .await?;
+        let room = helpers::find_room_by_id(
+            payload.id,
+            helpers::RoomTimeRequirement::Any,
+            helpers::AudienceScope::caller(context, &reqp),
+            &mut conn,
+        )
+        .await?;
 
         let agent_id = reqp.as_agent_id().clone();
         // Check room presence.
         let presence = db::agent::ListQuery::new()
+            .status(db::agent::Status::Ready)
             .room_id(room.id())
             .agent_id(&agent_id)
             .execute(&mut conn)
@@ -929,6 +1877,230 @@ impl RequestHandler for LeaveHandler {
 
 ///////////////////////////////////////////////////////////////////////////////
 
+#[derive(Debug, Deserialize)]
+pub struct LobbyRequest {
+    id: db::room::Id,
+    agent_id: AgentId,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LobbyFields {
+    agent_id: AgentId,
+}
+
+pub async fn admit(
+    Extension(ctx): Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<db::room::Id>,
+    Json(fields): Json<LobbyFields>,
+) -> RequestResult {
+    tracing::Span::current().record("room_id", &tracing::field::display(room_id));
+
+    let request = LobbyRequest {
+        id: room_id,
+        agent_id: fields.agent_id,
+    };
+    http::handle::<AdmitHandler>(&ctx, &agent_id, request).await
+}
+
+pub struct AdmitHandler;
+
+#[async_trait]
+impl RequestHandler for AdmitHandler {
+    type Payload = LobbyRequest;
+    const ERROR_TITLE: &'static str = "Failed to admit agent";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::Host);
+
+    #[instrument(skip(context, payload, reqp), fields(room_id = %payload.id))]
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let (room, authz_time) = find_lobby_room(context, &payload, reqp).await?;
+
+        // Subscribe the agent to the room's events as `room.enter` would have done.
+        let room_id = room.id().to_string();
+        let object = ["rooms", &room_id, "events"];
+
+        context
+            .mqtt_gateway_client()
+            .create_subscription(payload.agent_id.clone(), &object)
+            .await
+            .error(AppErrorKind::BrokerRequestFailed)?;
+
+        {
+            let mut conn = context.get_conn().await?;
+
+            db::agent::UpdateQuery::new(&payload.agent_id, room.id())
+                .status(db::agent::Status::Ready)
+                .execute(&mut conn)
+                .await?;
+
+            db::room_summary::observe_concurrency(room.id(), &mut conn).await?;
+        }
+
+        info!(agent_id = %payload.agent_id, admitted_by = %reqp.as_agent_id(), "Agent admitted");
+
+        let mut response = Response::new(
+            ResponseStatus::OK,
+            json!({}),
+            context.start_timestamp(),
+            authz_time,
+        );
+
+        response.add_message(lobby_event(context, "room.admit", &room, &payload.agent_id));
+
+        response.add_notification(
+            "room.enter",
+            &format!("rooms/{room_id}/events"),
+            RoomEnterLeaveEvent::new(room.id(), payload.agent_id),
+            context.start_timestamp(),
+        );
+
+        context
+            .metrics()
+            .request_duration
+            .room_admit
+            .observe_timestamp(context.start_timestamp());
+
+        Ok(response)
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+pub type RejectRequest = LobbyRequest;
+
+pub async fn reject(
+    Extension(ctx): Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<db::room::Id>,
+    Json(fields): Json<LobbyFields>,
+) -> RequestResult {
+    tracing::Span::current().record("room_id", &tracing::field::display(room_id));
+
+    let request = RejectRequest {
+        id: room_id,
+        agent_id: fields.agent_id,
+    };
+    http::handle::<RejectHandler>(&ctx, &agent_id, request).await
+}
+
+pub struct RejectHandler;
+
+#[async_trait]
+impl RequestHandler for RejectHandler {
+    type Payload = RejectRequest;
+    const ERROR_TITLE: &'static str = "Failed to reject agent";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::Host);
+
+    #[instrument(skip(context, payload, reqp), fields(room_id = %payload.id))]
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let (room, authz_time) = find_lobby_room(context, &payload, reqp).await?;
+
+        {
+            let mut conn = context.get_conn().await?;
+
+            db::agent::DeleteQuery::new()
+                .agent_id(&payload.agent_id)
+                .room_id(room.id())
+                .execute(&mut conn)
+                .await?;
+        }
+
+        info!(agent_id = %payload.agent_id, rejected_by = %reqp.as_agent_id(), "Agent rejected");
+
+        let mut response = Response::new(
+            ResponseStatus::OK,
+            json!({}),
+            context.start_timestamp(),
+            authz_time,
+        );
+
+        response.add_message(lobby_event(
+            context,
+            "room.reject",
+            &room,
+            &payload.agent_id,
+        ));
+
+        context
+            .metrics()
+            .request_duration
+            .room_reject
+            .observe_timestamp(context.start_timestamp());
+
+        Ok(response)
+    }
+}
+
+/// Finds the room for a host deciding on an agent waiting in its lobby.
+async fn find_lobby_room<C: Context>(
+    context: &mut C,
+    payload: &LobbyRequest,
+    reqp: RequestParams<'_>,
+) -> Result<(db::room::Object, Option<chrono::Duration>), AppError> {
+    let room = {
+        let mut conn = context.get_conn().await?;
+
+        helpers::find_room_by_id(
+            payload.id,
+            helpers::RoomTimeRequirement::NotClosed,
+            helpers::AudienceScope::caller(context, &reqp),
+            &mut conn,
+        )
+        .await?
+    };
+
+    let authz_time = helpers::authorize_host(context, &room, reqp).await?;
+
+    let mut conn = context.get_conn().await?;
+
+    let pending = db::agent::ListQuery::new()
+        .room_id(room.id())
+        .agent_id(&payload.agent_id)
+        .status(db::agent::Status::Pending)
+        .limit(1)
+        .execute(&mut conn)
+        .await?;
+
+    if pending.is_empty() {
+        return Err(anyhow!(
+            "Agent {} is not waiting in the lobby",
+            payload.agent_id
+        ))
+        .error(AppErrorKind::AgentNotEnteredTheRoom);
+    }
+
+    Ok((room, authz_time))
+}
+
+/// The agent waiting in the lobby isn't subscribed to the room's events, so it's told directly.
+fn lobby_event<C: Context>(
+    context: &C,
+    label: &'static str,
+    room: &db::room::Object,
+    agent_id: &AgentId,
+) -> Box<dyn IntoPublishableMessage + Send + Sync + 'static> {
+    let timing = ShortTermTimingProperties::until_now(context.start_timestamp());
+    let props = OutgoingEventProperties::new(label, timing);
+    let payload = RoomEnterLeaveEvent::new(room.id(), agent_id.to_owned());
+
+    Box::new(OutgoingEvent::multicast(
+        payload,
+        props,
+        agent_id,
+        API_VERSION,
+    ))
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
 #[cfg(test)]
 mod test {
     use serde::Deserialize;
@@ -944,7 +2116,7 @@ mod test {
     mod create {
         use std::ops::Bound;
 
-        use chrono::Utc;
+        use chrono::{Duration, Utc};
         use serde_json::json;
 
         use crate::db::group_agent::{GroupItem, Groups};
@@ -966,7 +2138,8 @@ mod test {
 
             // Make room.create request.
             let mut context = TestContext::new(db, authz).await;
-            let time = (Bound::Unbounded, Bound::Unbounded);
+            let time =
+                RoomTime::new(Bound::Unbounded, Bound::Unbounded).expect("Invalid room time");
             let classroom_id = Uuid::new_v4();
 
             let payload = CreateRequest {
@@ -977,6 +2150,15 @@ mod test {
                 reserve: Some(123),
                 tags: Some(json!({ "foo": "bar" })),
                 classroom_id,
+                audio_processing: None,
+                archive_messages: None,
+                composite_recording: None,
+                record_rtcs: None,
+                stream_max_duration: None,
+                metadata: Some(json!({ "title": "Lesson" })),
+                lobby: None,
+                active_speaker_events: None,
+                max_agents: None,
             };
 
             let messages = handle_request::<CreateHandler>(&mut context, &agent, payload)
@@ -992,6 +2174,7 @@ mod test {
             assert_eq!(room.reserve(), Some(123));
             assert_eq!(room.tags(), &json!({ "foo": "bar" }));
             assert_eq!(room.classroom_id(), classroom_id);
+            assert_eq!(room.metadata(), Some(&json!({ "title": "Lesson" })));
 
             // Assert notification.
             let (room, evp, topic) = find_event::<Room>(messages.as_slice());
@@ -1006,738 +2189,2319 @@ mod test {
         }
 
         #[sqlx::test]
-        async fn create_room_unauthorized(pool: sqlx::PgPool) {
+        async fn create_room_from_template(pool: sqlx::PgPool) {
             let db = TestDb::new(pool);
 
-            let mut context = TestContext::new(db, TestAuthz::new()).await;
+            let mut authz = TestAuthz::new();
             let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let reader = TestAgent::new("web", "reader", USR_AUDIENCE);
+            authz.allow(agent.account_id(), vec!["classrooms"], "create");
+
+            let mut conn = db.get_conn().await;
+            let metadata = json!({ "layout": "grid" });
+
+            db::room_template::UpsertQuery::new(
+                USR_AUDIENCE,
+                "math",
+                db::rtc::SharingPolicy::Owned,
+                agent.agent_id(),
+            )
+            .reserve(20)
+            .metadata(&metadata)
+            .writer_config(db::room_template::WriterConfigDefaults {
+                send_video: Some(false),
+                ..Default::default()
+            })
+            .reader_config(db::room_template::ReaderConfigDefaults {
+                receive_audio: Some(false),
+                ..Default::default()
+            })
+            .execute(&mut conn)
+            .await
+            .expect("Failed to insert room template");
+
+            let mut context = TestContext::new(db, authz).await;
+
+            let payload = CreateFromTemplateRequest {
+                audience: USR_AUDIENCE.to_owned(),
+                template: "math".to_owned(),
+                time: RoomTime::new(Bound::Included(Utc::now()), Bound::Unbounded)
+                    .expect("Invalid room time"),
+                classroom_id: Uuid::new_v4(),
+                tags: None,
+                reserve: Some(50),
+                metadata: None,
+            };
+
+            let messages =
+                handle_request::<CreateFromTemplateHandler>(&mut context, &agent, payload)
+                    .await
+                    .expect("Room creation from template failed");
+
+            let (room, _, _) = find_response::<Room>(messages.as_slice());
+            assert_eq!(room.rtc_sharing_policy(), db::rtc::SharingPolicy::Owned);
+            assert_eq!(room.reserve(), Some(50));
+            assert_eq!(room.metadata(), Some(&metadata));
+
+            // The rtcs of the room start with the template's config defaults.
+            let defaults = db::room_template::find_writer_defaults(room.id(), &mut conn)
+                .await
+                .expect("Failed to find writer defaults")
+                .expect("Writer defaults not found");
+
+            assert_eq!(defaults.send_video, Some(false));
+
+            shared_helpers::insert_agent(&mut conn, reader.agent_id(), room.id()).await;
+            let rtc = shared_helpers::insert_rtc_with_room(&mut conn, &room).await;
+
+            let configs = db::rtc_reader_config::apply_room_defaults(rtc.id(), &mut conn)
+                .await
+                .expect("Failed to apply reader defaults");
+
+            assert_eq!(configs.len(), 1);
+            assert_eq!(configs[0].reader_id(), reader.agent_id());
+            assert!(configs[0].receive_video());
+            assert!(!configs[0].receive_audio());
+        }
+
+        #[sqlx::test]
+        async fn create_room_from_missing_template(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+
+            let mut authz = TestAuthz::new();
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            authz.allow(agent.account_id(), vec!["classrooms"], "create");
+
+            let mut context = TestContext::new(db, authz).await;
+
+            let payload = CreateFromTemplateRequest {
+                audience: USR_AUDIENCE.to_owned(),
+                template: "missing".to_owned(),
+                time: RoomTime::new(Bound::Included(Utc::now()), Bound::Unbounded)
+                    .expect("Invalid room time"),
+                classroom_id: Uuid::new_v4(),
+                tags: None,
+                reserve: None,
+                metadata: None,
+            };
+
+            let err = handle_request::<CreateFromTemplateHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success creating room from missing template");
+
+            assert_eq!(err.status(), ResponseStatus::NOT_FOUND);
+            assert_eq!(err.kind(), "room_template_not_found");
+        }
+
+        #[sqlx::test]
+        async fn create_room_with_invalid_metadata(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+
+            let mut authz = TestAuthz::new();
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            authz.allow(agent.account_id(), vec!["classrooms"], "create");
+
+            let mut context = TestContext::new(db, authz).await;
 
-            // Make room.create request.
             let payload = CreateRequest {
-                time: (Bound::Included(Utc::now()), Bound::Unbounded),
+                time: RoomTime::new(Bound::Included(Utc::now()), Bound::Unbounded)
+                    .expect("Invalid room time"),
                 audience: USR_AUDIENCE.to_owned(),
                 backend: None,
                 rtc_sharing_policy: Some(db::rtc::SharingPolicy::Shared),
                 reserve: None,
                 tags: None,
                 classroom_id: Uuid::new_v4(),
+                audio_processing: None,
+                archive_messages: None,
+                composite_recording: None,
+                record_rtcs: None,
+                stream_max_duration: None,
+                metadata: Some(json!("Lesson")),
+                lobby: None,
+                active_speaker_events: None,
+                max_agents: None,
             };
 
             let err = handle_request::<CreateHandler>(&mut context, &agent, payload)
                 .await
                 .expect_err("Unexpected success on room creation");
 
-            assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
-            assert_eq!(err.kind(), "access_denied");
+            assert_eq!(err.status(), ResponseStatus::BAD_REQUEST);
+            assert_eq!(err.kind(), "invalid_payload");
+        }
+
+        fn policy(
+            max_reserve: Option<i32>,
+            endpoint: Option<&str>,
+        ) -> crate::config::RoomPolicyConfig {
+            crate::config::RoomPolicyConfig {
+                min_lead_time: None,
+                max_reserve,
+                classroom_id_prefix: None,
+                endpoint: endpoint.map(|url| url.parse().expect("Invalid policy endpoint")),
+                timeout: std::time::Duration::from_secs(5),
+            }
+        }
+
+        fn policy_payload(reserve: i32) -> CreateRequest {
+            CreateRequest {
+                time: RoomTime::new(Bound::Included(Utc::now()), Bound::Unbounded)
+                    .expect("Invalid room time"),
+                audience: USR_AUDIENCE.to_owned(),
+                backend: None,
+                rtc_sharing_policy: Some(db::rtc::SharingPolicy::Shared),
+                reserve: Some(reserve),
+                tags: None,
+                classroom_id: Uuid::new_v4(),
+                audio_processing: None,
+                archive_messages: None,
+                composite_recording: None,
+                record_rtcs: None,
+                stream_max_duration: None,
+                metadata: None,
+                lobby: None,
+                active_speaker_events: None,
+                max_agents: None,
+            }
+        }
+
+        #[sqlx::test]
+        async fn create_room_breaking_policy(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+
+            let mut authz = TestAuthz::new();
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            authz.allow(agent.account_id(), vec!["classrooms"], "create");
+
+            let mut context = TestContext::new(db, authz).await;
+
+            context
+                .config_mut()
+                .room_policy
+                .insert(USR_AUDIENCE.to_owned(), policy(Some(100), None));
+
+            let err = handle_request::<CreateHandler>(&mut context, &agent, policy_payload(200))
+                .await
+                .expect_err("Unexpected success on room creation");
+
+            assert_eq!(err.status(), ResponseStatus::UNPROCESSABLE_ENTITY);
+            assert_eq!(err.kind(), "room_policy_violation");
+
+            // Rooms following the policy are created as usual.
+            handle_request::<CreateHandler>(&mut context, &agent, policy_payload(50))
+                .await
+                .expect("Room creation failed");
         }
 
-        #[sqlx::test]
-        async fn create_default_group(pool: sqlx::PgPool) {
-            let db = TestDb::new(pool);
+        #[sqlx::test]
+        async fn create_room_rejected_by_policy_endpoint(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+
+            let mut authz = TestAuthz::new();
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            authz.allow(agent.account_id(), vec!["classrooms"], "create");
+
+            let mut context = TestContext::new(db, authz).await;
+
+            let policy_server = httpmock::MockServer::start_async().await;
+
+            let policy_mock = policy_server
+                .mock_async(|when, then| {
+                    when.method(httpmock::Method::POST)
+                        .path("/check")
+                        .json_body_partial(r#"{"method": "room.create", "reserve": 50}"#);
+
+                    then.status(200).json_body(json!({
+                        "violations": [{
+                            "rule": "business_hours",
+                            "message": "Rooms must open during business hours",
+                        }],
+                    }));
+                })
+                .await;
+
+            let endpoint = policy_server.url("/check");
+
+            context
+                .config_mut()
+                .room_policy
+                .insert(USR_AUDIENCE.to_owned(), policy(None, Some(&endpoint)));
+
+            let err = handle_request::<CreateHandler>(&mut context, &agent, policy_payload(50))
+                .await
+                .expect_err("Unexpected success on room creation");
+
+            policy_mock.assert_async().await;
+            assert_eq!(err.status(), ResponseStatus::UNPROCESSABLE_ENTITY);
+            assert_eq!(err.kind(), "room_policy_violation");
+        }
+
+        #[sqlx::test]
+        async fn create_room_unauthorized(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+
+            let mut context = TestContext::new(db, TestAuthz::new()).await;
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+            // Make room.create request.
+            let payload = CreateRequest {
+                time: RoomTime::new(Bound::Included(Utc::now()), Bound::Unbounded)
+                    .expect("Invalid room time"),
+                audience: USR_AUDIENCE.to_owned(),
+                backend: None,
+                rtc_sharing_policy: Some(db::rtc::SharingPolicy::Shared),
+                reserve: None,
+                tags: None,
+                classroom_id: Uuid::new_v4(),
+                audio_processing: None,
+                archive_messages: None,
+                composite_recording: None,
+                record_rtcs: None,
+                stream_max_duration: None,
+                metadata: None,
+                lobby: None,
+                active_speaker_events: None,
+                max_agents: None,
+            };
+
+            let err = handle_request::<CreateHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on room creation");
+
+            assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+            assert_eq!(err.kind(), "access_denied");
+        }
+
+        #[sqlx::test]
+        async fn create_room_with_reversed_time(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+
+            let mut authz = TestAuthz::new();
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            authz.allow(agent.account_id(), vec!["classrooms"], "create");
+
+            let mut context = TestContext::new(db, authz).await;
+            let now = Utc::now();
+
+            // Make room.create request.
+            let payload = CreateRequest {
+                time: (
+                    Bound::Included(now),
+                    Bound::Excluded(now - Duration::hours(1)),
+                )
+                    .into(),
+                audience: USR_AUDIENCE.to_owned(),
+                backend: None,
+                rtc_sharing_policy: Some(db::rtc::SharingPolicy::Shared),
+                reserve: None,
+                tags: None,
+                classroom_id: Uuid::new_v4(),
+                audio_processing: None,
+                archive_messages: None,
+                composite_recording: None,
+                record_rtcs: None,
+                stream_max_duration: None,
+                metadata: None,
+                lobby: None,
+                active_speaker_events: None,
+                max_agents: None,
+            };
+
+            let err = handle_request::<CreateHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on room creation");
+
+            assert_eq!(err.status(), ResponseStatus::BAD_REQUEST);
+            assert_eq!(err.kind(), "invalid_room_time");
+        }
+
+        #[sqlx::test]
+        async fn create_default_group(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+
+            // Allow user to create rooms.
+            let mut authz = TestAuthz::new();
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            authz.allow(agent.account_id(), vec!["classrooms"], "create");
+
+            // Make room.create request.
+            let mut context = TestContext::new(db, authz).await;
+            let time =
+                RoomTime::new(Bound::Unbounded, Bound::Unbounded).expect("Invalid room time");
+            let classroom_id = Uuid::new_v4();
+
+            let payload = CreateRequest {
+                time,
+                audience: USR_AUDIENCE.to_owned(),
+                backend: None,
+                rtc_sharing_policy: Some(db::rtc::SharingPolicy::Owned),
+                reserve: Some(123),
+                tags: Some(json!({ "foo": "bar" })),
+                classroom_id,
+                audio_processing: None,
+                archive_messages: None,
+                composite_recording: None,
+                record_rtcs: None,
+                stream_max_duration: None,
+                metadata: None,
+                lobby: None,
+                active_speaker_events: None,
+                max_agents: None,
+            };
+
+            let messages = handle_request::<CreateHandler>(&mut context, &agent, payload)
+                .await
+                .expect("Room creation failed");
+
+            let (room, _, _) = find_response::<Room>(messages.as_slice());
+
+            let mut conn = context
+                .get_conn()
+                .await
+                .expect("failed to get db connection");
+
+            let group_agent = db::group_agent::FindQuery::new(room.id())
+                .execute(&mut conn)
+                .await
+                .expect("failed to get group");
+
+            let groups = Groups::new(vec![GroupItem::new(0, vec![])]);
+            assert_eq!(group_agent.groups(), groups);
+        }
+    }
+
+    mod read {
+        use std::ops::Bound;
+
+        use chrono::Duration;
+
+        use crate::{
+            db::room::Object as Room,
+            test_helpers::{db::TestDb, prelude::*},
+        };
+
+        use super::super::*;
+
+        #[sqlx::test]
+        async fn read_room(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+
+            let room = {
+                let mut conn = db.get_conn().await;
+                // Create room.
+                shared_helpers::insert_room(&mut conn).await
+            };
+
+            // Allow agent to read the room.
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut authz = TestAuthz::new();
+            let classroom_id = room.classroom_id().to_string();
+            authz.allow(
+                agent.account_id(),
+                vec!["classrooms", &classroom_id],
+                "read",
+            );
+
+            // Make room.read request.
+            let mut context = TestContext::new(db, authz).await;
+            let payload = ReadRequest {
+                id: Some(room.id()),
+                classroom_id: None,
+            };
+
+            let messages = handle_request::<ReadHandler>(&mut context, &agent, payload)
+                .await
+                .expect("Room reading failed");
+
+            // Assert response.
+            let (resp_room, respp, _) = find_response::<Room>(messages.as_slice());
+            assert_eq!(respp.status(), ResponseStatus::OK);
+            assert_eq!(resp_room.audience(), room.audience());
+            assert_eq!(resp_room.time(), room.time());
+            assert_eq!(resp_room.rtc_sharing_policy(), room.rtc_sharing_policy());
+
+            let (resp, _, _) = find_response::<JsonValue>(messages.as_slice());
+            assert_eq!(resp["occupancy"], 0);
+        }
+
+        #[sqlx::test]
+        async fn read_room_not_authorized(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+            let room = {
+                let mut conn = db.get_conn().await;
+                shared_helpers::insert_room(&mut conn).await
+            };
+
+            let mut context = TestContext::new(db, TestAuthz::new()).await;
+            let payload = ReadRequest {
+                id: Some(room.id()),
+                classroom_id: None,
+            };
+
+            let err = handle_request::<ReadHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on room reading");
+
+            assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+            assert_eq!(err.kind(), "access_denied");
+        }
+
+        #[sqlx::test]
+        async fn read_room_missing(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut context = TestContext::new(db, TestAuthz::new()).await;
+            let payload = ReadRequest {
+                id: Some(db::room::Id::random()),
+                classroom_id: None,
+            };
+
+            let err = handle_request::<ReadHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on room reading");
+
+            assert_eq!(err.status(), ResponseStatus::NOT_FOUND);
+            assert_eq!(err.kind(), "room_not_found");
+        }
+
+        #[sqlx::test]
+        async fn read_limits(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+
+            let room = {
+                let mut conn = db.get_conn().await;
+                let now = Utc::now();
+
+                factory::Room::new()
+                    .audience(USR_AUDIENCE)
+                    .time((
+                        Bound::Included(now),
+                        Bound::Excluded(now + Duration::hours(1)),
+                    ))
+                    .archive_messages(true)
+                    .max_agents(10)
+                    .insert(&mut conn)
+                    .await
+            };
+
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut authz = TestAuthz::new();
+            let classroom_id = room.classroom_id().to_string();
+            authz.allow(
+                agent.account_id(),
+                vec!["classrooms", &classroom_id],
+                "read",
+            );
+
+            let mut context = TestContext::new(db, authz).await;
+            let payload = LimitsReadRequest { id: room.id() };
+
+            let messages = handle_request::<LimitsReadHandler>(&mut context, &agent, payload)
+                .await
+                .expect("Room limits reading failed");
+
+            let (limits, respp, _) = find_response::<JsonValue>(messages.as_slice());
+            assert_eq!(respp.status(), ResponseStatus::OK);
+            assert_eq!(limits["list_max_limit"], 25);
+            assert_eq!(limits["history_max_limit"], 100);
+            assert_eq!(limits["max_agents"], 10);
+            assert_eq!(
+                limits["max_broadcast_payload_size"],
+                context.config().message_archive.max_payload_size
+            );
+            assert!(limits.get("max_agents_per_account").is_none());
+        }
+
+        async fn insert_classroom_room(
+            conn: &mut sqlx::PgConnection,
+            classroom_id: Uuid,
+            time: crate::serde::Time,
+        ) -> Room {
+            factory::Room::new()
+                .audience(USR_AUDIENCE)
+                .time(time)
+                .classroom_id(classroom_id)
+                .insert(conn)
+                .await
+        }
+
+        #[sqlx::test]
+        async fn read_room_by_classroom_id(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+            let classroom_id = Uuid::new_v4();
+            let now = Utc::now();
+
+            let room = {
+                let mut conn = db.get_conn().await;
+
+                insert_classroom_room(
+                    &mut conn,
+                    classroom_id,
+                    (
+                        Bound::Included(now - Duration::hours(2)),
+                        Bound::Excluded(now - Duration::hours(1)),
+                    ),
+                )
+                .await;
+
+                insert_classroom_room(
+                    &mut conn,
+                    classroom_id,
+                    (
+                        Bound::Included(now),
+                        Bound::Excluded(now + Duration::hours(1)),
+                    ),
+                )
+                .await
+            };
+
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut authz = TestAuthz::new();
+            let classroom_id_str = classroom_id.to_string();
+            authz.allow(
+                agent.account_id(),
+                vec!["classrooms", &classroom_id_str],
+                "read",
+            );
+
+            let mut context = TestContext::new(db, authz).await;
+            let payload = ReadRequest {
+                id: None,
+                classroom_id: Some(classroom_id),
+            };
+
+            let messages = handle_request::<ReadHandler>(&mut context, &agent, payload)
+                .await
+                .expect("Room reading failed");
+
+            // The room not closed yet stands for the classroom.
+            let (resp_room, _, _) = find_response::<Room>(messages.as_slice());
+            assert_eq!(resp_room.id(), room.id());
+        }
+
+        #[sqlx::test]
+        async fn read_room_by_ambiguous_classroom_id(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+            let classroom_id = Uuid::new_v4();
+            let now = Utc::now();
+
+            {
+                let mut conn = db.get_conn().await;
+
+                for _ in 0..2 {
+                    insert_classroom_room(
+                        &mut conn,
+                        classroom_id,
+                        (
+                            Bound::Included(now),
+                            Bound::Excluded(now + Duration::hours(1)),
+                        ),
+                    )
+                    .await;
+                }
+            }
+
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut context = TestContext::new(db, TestAuthz::new()).await;
+            let payload = ReadRequest {
+                id: None,
+                classroom_id: Some(classroom_id),
+            };
+
+            let err = handle_request::<ReadHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on room reading");
+
+            assert_eq!(err.status(), ResponseStatus::CONFLICT);
+            assert_eq!(err.kind(), "ambiguous_classroom");
+        }
+
+        #[sqlx::test]
+        async fn read_room_without_address(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut context = TestContext::new(db, TestAuthz::new()).await;
+
+            let payload = ReadRequest {
+                id: None,
+                classroom_id: None,
+            };
+
+            let err = handle_request::<ReadHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on room reading");
+
+            assert_eq!(err.kind(), "invalid_payload");
+        }
+    }
+
+    mod list {
+        use crate::{
+            db::room::Object as Room,
+            test_helpers::{db::TestDb, prelude::*},
+        };
+
+        use super::super::*;
+
+        fn list_request(state: Option<ListState>) -> ListRequest {
+            ListRequest {
+                audience: USR_AUDIENCE.to_owned(),
+                classroom_id: None,
+                state,
+                backend_id: None,
+                offset: None,
+                limit: None,
+            }
+        }
+
+        #[sqlx::test]
+        async fn list_rooms_by_state(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+
+            let (open_room, closed_room) = {
+                let mut conn = db.get_conn().await;
+
+                (
+                    shared_helpers::insert_room(&mut conn).await,
+                    shared_helpers::insert_closed_room(&mut conn).await,
+                )
+            };
+
+            // Allow agent to list rooms of the audience.
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut authz = TestAuthz::new();
+            authz.allow(agent.account_id(), vec!["classrooms"], "list");
+
+            let mut context = TestContext::new(db, authz).await;
+
+            // Make room.list request for each state.
+            for (state, expected_room) in [
+                (ListState::Open, &open_room),
+                (ListState::Closed, &closed_room),
+            ] {
+                let messages =
+                    handle_request::<ListHandler>(&mut context, &agent, list_request(Some(state)))
+                        .await
+                        .expect("Rooms listing failed");
+
+                let (rooms, respp, _) = find_response::<Vec<Room>>(messages.as_slice());
+                assert_eq!(respp.status(), ResponseStatus::OK);
+                assert_eq!(rooms.len(), 1);
+                assert_eq!(rooms[0].id(), expected_room.id());
+            }
+
+            // Both of them without the filter.
+            let messages = handle_request::<ListHandler>(&mut context, &agent, list_request(None))
+                .await
+                .expect("Rooms listing failed");
+
+            let (rooms, _, _) = find_response::<Vec<Room>>(messages.as_slice());
+            assert_eq!(rooms.len(), 2);
+        }
+
+        #[sqlx::test]
+        async fn list_rooms_by_classroom_id(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+
+            let room = {
+                let mut conn = db.get_conn().await;
+                shared_helpers::insert_room(&mut conn).await;
+                shared_helpers::insert_room(&mut conn).await
+            };
+
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut authz = TestAuthz::new();
+            authz.allow(agent.account_id(), vec!["classrooms"], "list");
+
+            let mut context = TestContext::new(db, authz).await;
+
+            let payload = ListRequest {
+                classroom_id: Some(room.classroom_id()),
+                ..list_request(None)
+            };
+
+            let messages = handle_request::<ListHandler>(&mut context, &agent, payload)
+                .await
+                .expect("Rooms listing failed");
+
+            let (rooms, _, _) = find_response::<Vec<Room>>(messages.as_slice());
+            assert_eq!(rooms.len(), 1);
+            assert_eq!(rooms[0].id(), room.id());
+        }
+
+        #[sqlx::test]
+        async fn list_rooms_not_authorized(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut context = TestContext::new(db, TestAuthz::new()).await;
+
+            let err = handle_request::<ListHandler>(&mut context, &agent, list_request(None))
+                .await
+                .expect_err("Unexpected success on rooms listing");
+
+            assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+            assert_eq!(err.kind(), "access_denied");
+        }
+
+        #[sqlx::test]
+        async fn list_rooms_of_another_audience(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+
+            // Authz of the other tenant lets anyone in but the caller is not from there.
+            let agent = TestAgent::new("web", "user123", "another.example.org");
+            let mut authz = TestAuthz::new();
+            authz.allow(agent.account_id(), vec!["classrooms"], "list");
+
+            let mut context = TestContext::new(db, authz).await;
+
+            let err = handle_request::<ListHandler>(&mut context, &agent, list_request(None))
+                .await
+                .expect_err("Unexpected success on rooms listing");
+
+            assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+            assert_eq!(err.kind(), "access_denied");
+        }
+    }
+
+    mod update {
+        use std::ops::Bound;
+
+        use chrono::{Duration, SubsecRound, Utc};
+        use serde_json::json;
+        use uuid::Uuid;
+
+        use crate::{
+            backend::janus::client::{HandleId, SessionId},
+            db::room::Object as Room,
+            test_helpers::{db::TestDb, find_event_by_predicate, prelude::*},
+        };
+
+        use super::super::*;
+
+        #[sqlx::test]
+        async fn update_room(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+            let now = Utc::now().trunc_subsecs(0);
+
+            let room = {
+                let mut conn = db.get_conn().await;
+
+                // Create room.
+                factory::Room::new()
+                    .audience(USR_AUDIENCE)
+                    .time((Bound::Unbounded, Bound::Unbounded))
+                    .rtc_sharing_policy(db::rtc::SharingPolicy::Shared)
+                    .insert(&mut conn)
+                    .await
+            };
+
+            // Allow agent to update the room.
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut authz = TestAuthz::new();
+            let classroom_id = room.classroom_id().to_string();
+            authz.allow(
+                agent.account_id(),
+                vec!["classrooms", &classroom_id],
+                "update",
+            );
+
+            // Make room.update request.
+            let mut context = TestContext::new(db, authz).await;
+            let classroom_id = Uuid::new_v4();
+
+            let time = RoomTime::new(
+                Bound::Included(now + Duration::minutes(50)),
+                Bound::Unbounded,
+            )
+            .expect("Invalid room time");
+
+            let payload = UpdateRequest {
+                id: Some(room.id()),
+                time: Some(time),
+                reserve: Some(Some(123)),
+                tags: Some(json!({"foo": "bar"})),
+                classroom_id: Some(classroom_id),
+                host: Some(agent.agent_id().clone()),
+                add_hosts: None,
+                remove_hosts: None,
+                audio_processing: None,
+                archive_messages: None,
+                composite_recording: None,
+                record_rtcs: None,
+                stream_max_duration: None,
+                metadata: None,
+                lobby: None,
+                active_speaker_events: None,
+                max_agents: None,
+                if_version: None,
+            };
+
+            let messages = handle_request::<UpdateHandler>(&mut context, &agent, payload)
+                .await
+                .expect("Room update failed");
+
+            // Assert response.
+            let (resp_room, respp, _) = find_response::<Room>(messages.as_slice());
+            assert_eq!(respp.status(), ResponseStatus::OK);
+            assert_eq!(resp_room.id(), room.id());
+            assert_eq!(resp_room.audience(), room.audience());
+            assert_eq!(resp_room.time(), time);
+            assert_eq!(
+                resp_room.rtc_sharing_policy(),
+                db::rtc::SharingPolicy::Shared
+            );
+            assert_eq!(resp_room.reserve(), Some(123));
+            assert_eq!(resp_room.tags(), &json!({"foo": "bar"}));
+            assert_eq!(resp_room.classroom_id(), classroom_id);
+            assert_eq!(resp_room.host(), Some(agent.agent_id()));
+        }
+
+        fn hosts_request(
+            room_id: db::room::Id,
+            add_hosts: Option<Vec<AgentId>>,
+            remove_hosts: Option<Vec<AgentId>>,
+        ) -> UpdateRequest {
+            UpdateRequest {
+                id: Some(room_id),
+                time: None,
+                reserve: None,
+                tags: None,
+                classroom_id: None,
+                host: None,
+                add_hosts,
+                remove_hosts,
+                audio_processing: None,
+                archive_messages: None,
+                composite_recording: None,
+                record_rtcs: None,
+                stream_max_duration: None,
+                metadata: None,
+                lobby: None,
+                active_speaker_events: None,
+                max_agents: None,
+                if_version: None,
+            }
+        }
+
+        #[sqlx::test]
+        async fn update_room_hosts(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+
+            let room = {
+                let mut conn = db.get_conn().await;
+                shared_helpers::insert_room(&mut conn).await
+            };
+
+            let agent = TestAgent::new("web", "admin", USR_AUDIENCE);
+            let teacher1 = TestAgent::new("web", "teacher1", USR_AUDIENCE);
+            let teacher2 = TestAgent::new("web", "teacher2", USR_AUDIENCE);
+
+            let mut authz = TestAuthz::new();
+            let classroom_id = room.classroom_id().to_string();
+            authz.allow(
+                agent.account_id(),
+                vec!["classrooms", &classroom_id],
+                "update",
+            );
+
+            let mut context = TestContext::new(db, authz).await;
+
+            let payload = hosts_request(
+                room.id(),
+                Some(vec![
+                    teacher1.agent_id().to_owned(),
+                    teacher2.agent_id().to_owned(),
+                ]),
+                None,
+            );
+
+            let messages = handle_request::<UpdateHandler>(&mut context, &agent, payload)
+                .await
+                .expect("Room update failed");
+
+            let (resp_room, _, _) = find_response::<Room>(messages.as_slice());
+            assert!(resp_room.host().is_some());
+
+            // The one left stays the host.
+            let payload =
+                hosts_request(room.id(), None, Some(vec![teacher1.agent_id().to_owned()]));
+
+            let messages = handle_request::<UpdateHandler>(&mut context, &agent, payload)
+                .await
+                .expect("Room update failed");
+
+            let (resp_room, _, _) = find_response::<Room>(messages.as_slice());
+            assert_eq!(resp_room.host(), Some(teacher2.agent_id()));
+
+            let mut conn = context.get_conn().await.expect("Failed to get conn");
+            let hosts = db::room_host::list(room.id(), &mut conn)
+                .await
+                .expect("Failed to list room hosts");
+
+            assert_eq!(hosts, vec![teacher2.agent_id().to_owned()]);
+        }
+
+        #[sqlx::test]
+        async fn update_room_with_wrong_time(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+            let now = Utc::now().trunc_subsecs(0);
+
+            let room = {
+                let mut conn = db.get_conn().await;
+
+                // Create room.
+                factory::Room::new()
+                    .audience(USR_AUDIENCE)
+                    .time((
+                        Bound::Included(now - Duration::hours(1)),
+                        Bound::Excluded(now + Duration::hours(2)),
+                    ))
+                    .rtc_sharing_policy(db::rtc::SharingPolicy::Shared)
+                    .insert(&mut conn)
+                    .await
+            };
+
+            // Allow agent to update the room.
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut authz = TestAuthz::new();
+            let classroom_id = room.classroom_id().to_string();
+            authz.allow(
+                agent.account_id(),
+                vec!["classrooms", &classroom_id],
+                "update",
+            );
+
+            // Make room.update request.
+            let mut context = TestContext::new(db, authz).await;
+
+            let time = (
+                Bound::Included(now + Duration::hours(3)),
+                Bound::Excluded(now - Duration::hours(2)),
+            );
+
+            let payload = UpdateRequest {
+                id: Some(room.id()),
+                time: Some(time.into()),
+                reserve: Some(Some(123)),
+                tags: Some(json!({"foo": "bar"})),
+                classroom_id: None,
+                host: None,
+                add_hosts: None,
+                remove_hosts: None,
+                audio_processing: None,
+                archive_messages: None,
+                composite_recording: None,
+                record_rtcs: None,
+                stream_max_duration: None,
+                metadata: None,
+                lobby: None,
+                active_speaker_events: None,
+                max_agents: None,
+                if_version: None,
+            };
+
+            let err = handle_request::<UpdateHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Room update succeeded when it should've failed");
+
+            assert_eq!(err.kind(), "invalid_room_time");
+        }
+
+        #[sqlx::test]
+        async fn update_and_close_room(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+            let now = Utc::now().trunc_subsecs(0);
+
+            let room = {
+                let mut conn = db.get_conn().await;
+
+                // Create room.
+                factory::Room::new()
+                    .audience(USR_AUDIENCE)
+                    .time((
+                        Bound::Included(now - Duration::hours(1)),
+                        Bound::Excluded(now + Duration::hours(5)),
+                    ))
+                    .rtc_sharing_policy(db::rtc::SharingPolicy::Shared)
+                    .insert(&mut conn)
+                    .await
+            };
+
+            // Allow agent to update the room.
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut authz = TestAuthz::new();
+            let classroom_id = room.classroom_id().to_string();
+            authz.allow(
+                agent.account_id(),
+                vec!["classrooms", &classroom_id],
+                "update",
+            );
+
+            // Make room.update request.
+            let mut context = TestContext::new(db, authz).await;
+
+            let time = RoomTime::new(
+                Bound::Included(now - Duration::hours(1)),
+                Bound::Excluded(now - Duration::seconds(5)),
+            )
+            .expect("Invalid room time");
+
+            let payload = UpdateRequest {
+                id: Some(room.id()),
+                time: Some(time),
+                reserve: Some(Some(123)),
+                tags: Default::default(),
+                classroom_id: Default::default(),
+                host: None,
+                add_hosts: None,
+                remove_hosts: None,
+                audio_processing: None,
+                archive_messages: None,
+                composite_recording: None,
+                record_rtcs: None,
+                stream_max_duration: None,
+                metadata: None,
+                lobby: None,
+                active_speaker_events: None,
+                max_agents: None,
+                if_version: None,
+            };
+
+            let messages = handle_request::<UpdateHandler>(&mut context, &agent, payload)
+                .await
+                .expect("Room update failed");
+
+            assert_eq!(messages.len(), 5);
+
+            find_event_by_predicate::<JsonValue, _>(messages.as_slice(), |evp, _, topic| {
+                evp.label() == "room.summary" && topic.contains("audiences")
+            })
+            .expect("Failed to find room.summary event");
+
+            let (closed_tenant_notification, _, _) =
+                find_event_by_predicate::<JsonValue, _>(messages.as_slice(), |evp, _, topic| {
+                    evp.label() == "room.close" && topic.contains("audiences")
+                })
+                .expect("Failed to find room.close event");
+
+            assert_eq!(
+                closed_tenant_notification
+                    .get("id")
+                    .and_then(|v| v.as_str()),
+                Some(room.id().to_string()).as_deref()
+            );
+
+            let (closed_room_notification, _, _) =
+                find_event_by_predicate::<JsonValue, _>(messages.as_slice(), |evp, _, topic| {
+                    evp.label() == "room.close" && topic.contains("rooms")
+                })
+                .expect("Failed to find room.close event");
+
+            assert_eq!(
+                closed_room_notification.get("id").and_then(|v| v.as_str()),
+                Some(room.id().to_string()).as_deref()
+            );
+        }
+
+        #[sqlx::test]
+        async fn update_and_close_unbounded_room(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+            let now = Utc::now().trunc_subsecs(0);
+
+            let room = {
+                let mut conn = db.get_conn().await;
+
+                // Create room.
+                factory::Room::new()
+                    .audience(USR_AUDIENCE)
+                    .time((Bound::Included(now - Duration::hours(1)), Bound::Unbounded))
+                    .insert(&mut conn)
+                    .await
+            };
+
+            // Allow agent to update the room.
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut authz = TestAuthz::new();
+            let classroom_id = room.classroom_id().to_string();
+            authz.allow(
+                agent.account_id(),
+                vec!["classrooms", &classroom_id],
+                "update",
+            );
+
+            // Make room.update request.
+            let mut context = TestContext::new(db, authz).await;
+
+            let time = RoomTime::new(
+                Bound::Included(now - Duration::hours(1)),
+                Bound::Excluded(now - Duration::seconds(5)),
+            )
+            .expect("Invalid room time");
+
+            let payload = UpdateRequest {
+                id: Some(room.id()),
+                time: Some(time),
+                reserve: Default::default(),
+                tags: Default::default(),
+                classroom_id: Default::default(),
+                host: None,
+                add_hosts: None,
+                remove_hosts: None,
+                audio_processing: None,
+                archive_messages: None,
+                composite_recording: None,
+                record_rtcs: None,
+                stream_max_duration: None,
+                metadata: None,
+                lobby: None,
+                active_speaker_events: None,
+                max_agents: None,
+                if_version: None,
+            };
+
+            handle_request::<UpdateHandler>(&mut context, &agent, payload)
+                .await
+                .expect("Room update failed");
+        }
+
+        #[sqlx::test]
+        async fn update_room_missing(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut context = TestContext::new(db, TestAuthz::new()).await;
+
+            let payload = UpdateRequest {
+                id: Some(db::room::Id::random()),
+                time: Default::default(),
+                reserve: Default::default(),
+                tags: Default::default(),
+                classroom_id: Default::default(),
+                host: None,
+                add_hosts: None,
+                remove_hosts: None,
+                audio_processing: None,
+                archive_messages: None,
+                composite_recording: None,
+                record_rtcs: None,
+                stream_max_duration: None,
+                metadata: None,
+                lobby: None,
+                active_speaker_events: None,
+                max_agents: None,
+                if_version: None,
+            };
+
+            let err = handle_request::<UpdateHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on room update");
+
+            assert_eq!(err.status(), ResponseStatus::NOT_FOUND);
+            assert_eq!(err.kind(), "room_not_found");
+        }
+
+        #[sqlx::test]
+        async fn update_room_closed(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+            let room = {
+                let mut conn = db.get_conn().await;
+
+                // Create closed room.
+                shared_helpers::insert_closed_room(&mut conn).await
+            };
+
+            let mut context = TestContext::new(db, TestAuthz::new()).await;
+
+            let payload = UpdateRequest {
+                id: Some(room.id()),
+                time: Some((Bound::Included(Utc::now()), Bound::Excluded(Utc::now())).into()),
+                reserve: Default::default(),
+                tags: Default::default(),
+                classroom_id: Default::default(),
+                host: None,
+                add_hosts: None,
+                remove_hosts: None,
+                audio_processing: None,
+                archive_messages: None,
+                composite_recording: None,
+                record_rtcs: None,
+                stream_max_duration: None,
+                metadata: None,
+                lobby: None,
+                active_speaker_events: None,
+                max_agents: None,
+                if_version: None,
+            };
+
+            let err = handle_request::<UpdateHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on room update");
+
+            assert_eq!(err.status(), ResponseStatus::NOT_FOUND);
+            assert_eq!(err.kind(), "room_closed");
+        }
+
+        #[sqlx::test]
+        async fn update_room_with_stale_version(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+
+            let room = {
+                let mut conn = db.get_conn().await;
+
+                factory::Room::new()
+                    .audience(USR_AUDIENCE)
+                    .time((Bound::Unbounded, Bound::Unbounded))
+                    .insert(&mut conn)
+                    .await
+            };
+
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut authz = TestAuthz::new();
+            let classroom_id = room.classroom_id().to_string();
+            authz.allow(
+                agent.account_id(),
+                vec!["classrooms", &classroom_id],
+                "update",
+            );
+
+            let mut context = TestContext::new(db, authz).await;
+
+            // The first update bumps the version.
+            let payload = UpdateRequest {
+                id: Some(room.id()),
+                time: None,
+                reserve: None,
+                tags: Some(json!({"foo": "bar"})),
+                classroom_id: None,
+                host: None,
+                add_hosts: None,
+                remove_hosts: None,
+                audio_processing: None,
+                archive_messages: None,
+                composite_recording: None,
+                record_rtcs: None,
+                stream_max_duration: None,
+                metadata: None,
+                lobby: None,
+                active_speaker_events: None,
+                max_agents: None,
+                if_version: Some(room.version()),
+            };
+
+            let messages = handle_request::<UpdateHandler>(&mut context, &agent, payload)
+                .await
+                .expect("Room update failed");
+
+            let (resp_room, _, _) = find_response::<Room>(messages.as_slice());
+            assert_eq!(resp_room.version(), room.version() + 1);
+
+            // The second one is based on the outdated version.
+            let payload = UpdateRequest {
+                id: Some(room.id()),
+                time: None,
+                reserve: None,
+                tags: Some(json!({"foo": "baz"})),
+                classroom_id: None,
+                host: None,
+                add_hosts: None,
+                remove_hosts: None,
+                audio_processing: None,
+                archive_messages: None,
+                composite_recording: None,
+                record_rtcs: None,
+                stream_max_duration: None,
+                metadata: None,
+                lobby: None,
+                active_speaker_events: None,
+                max_agents: None,
+                if_version: Some(room.version()),
+            };
+
+            let err = handle_request::<UpdateHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on room update");
+
+            assert_eq!(err.status(), ResponseStatus::CONFLICT);
+            assert_eq!(err.kind(), "conflict");
+            assert!(err
+                .detail()
+                .contains(&format!("current version = {}", room.version() + 1)));
+        }
+
+        #[sqlx::test]
+        async fn update_reserve_of_open_room(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+            let now = Utc::now().trunc_subsecs(0);
+            let time = RoomTime::new(
+                Bound::Included(now - Duration::minutes(10)),
+                Bound::Excluded(now + Duration::hours(1)),
+            )
+            .expect("Invalid room time");
+
+            let (room, backend) = {
+                let mut conn = db.get_conn().await;
+
+                let backend = TestAgent::new("alpha", "janus", SVC_AUDIENCE);
+                let backend = factory::JanusBackend::new(
+                    backend.agent_id().to_owned(),
+                    HandleId::random(),
+                    SessionId::random(),
+                    "test".to_owned(),
+                )
+                .capacity(20)
+                .insert(&mut conn)
+                .await;
+
+                let room = factory::Room::new()
+                    .audience(USR_AUDIENCE)
+                    .time(time.into())
+                    .reserve(5)
+                    .rtc_sharing_policy(db::rtc::SharingPolicy::Shared)
+                    .backend_id(backend.id())
+                    .insert(&mut conn)
+                    .await;
+
+                // Another room on the same backend.
+                factory::Room::new()
+                    .audience(USR_AUDIENCE)
+                    .time(time.into())
+                    .reserve(10)
+                    .rtc_sharing_policy(db::rtc::SharingPolicy::Shared)
+                    .backend_id(backend.id())
+                    .insert(&mut conn)
+                    .await;
+
+                (room, backend)
+            };
 
-            // Allow user to create rooms.
-            let mut authz = TestAuthz::new();
             let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
-            authz.allow(agent.account_id(), vec!["classrooms"], "create");
+            let mut authz = TestAuthz::new();
+            let classroom_id = room.classroom_id().to_string();
+            authz.allow(
+                agent.account_id(),
+                vec!["classrooms", &classroom_id],
+                "update",
+            );
 
-            // Make room.create request.
             let mut context = TestContext::new(db, authz).await;
-            let time = (Bound::Unbounded, Bound::Unbounded);
-            let classroom_id = Uuid::new_v4();
 
-            let payload = CreateRequest {
-                time: time.clone(),
-                audience: USR_AUDIENCE.to_owned(),
-                backend: None,
-                rtc_sharing_policy: Some(db::rtc::SharingPolicy::Owned),
-                reserve: Some(123),
-                tags: Some(json!({ "foo": "bar" })),
-                classroom_id,
+            // The backend can't take the reserve on top of the other room.
+            let payload = UpdateRequest {
+                id: Some(room.id()),
+                time: None,
+                reserve: Some(Some(15)),
+                tags: None,
+                classroom_id: None,
+                host: None,
+                add_hosts: None,
+                remove_hosts: None,
+                audio_processing: None,
+                archive_messages: None,
+                composite_recording: None,
+                record_rtcs: None,
+                stream_max_duration: None,
+                metadata: None,
+                lobby: None,
+                active_speaker_events: None,
+                max_agents: None,
+                if_version: None,
             };
 
-            let messages = handle_request::<CreateHandler>(&mut context, &agent, payload)
+            let err = handle_request::<UpdateHandler>(&mut context, &agent, payload)
                 .await
-                .expect("Room creation failed");
+                .expect_err("Unexpected success on room update");
 
-            let (room, _, _) = find_response::<Room>(messages.as_slice());
+            assert_eq!(err.status(), ResponseStatus::UNPROCESSABLE_ENTITY);
+            assert_eq!(err.kind(), "reserve_exceeds_capacity");
 
-            let mut conn = context
-                .get_conn()
+            // But it fits exactly.
+            let payload = UpdateRequest {
+                id: Some(room.id()),
+                time: None,
+                reserve: Some(Some(10)),
+                tags: None,
+                classroom_id: None,
+                host: None,
+                add_hosts: None,
+                remove_hosts: None,
+                audio_processing: None,
+                archive_messages: None,
+                composite_recording: None,
+                record_rtcs: None,
+                stream_max_duration: None,
+                metadata: None,
+                lobby: None,
+                active_speaker_events: None,
+                max_agents: None,
+                if_version: None,
+            };
+
+            let messages = handle_request::<UpdateHandler>(&mut context, &agent, payload)
                 .await
-                .expect("failed to get db connection");
+                .expect("Room update failed");
 
-            let group_agent = db::group_agent::FindQuery::new(room.id())
-                .execute(&mut conn)
+            let (resp_room, _, _) = find_response::<Room>(messages.as_slice());
+            assert_eq!(resp_room.reserve(), Some(10));
+            assert_eq!(resp_room.backend_id(), Some(backend.id()));
+
+            // The change is audited.
+            let mut conn = context.get_conn().await.expect("Failed to get conn");
+
+            let audit = db::room_reserve_audit::list(room.id(), &mut conn)
                 .await
-                .expect("failed to get group");
+                .expect("Failed to list reserve audit");
 
-            let groups = Groups::new(vec![GroupItem::new(0, vec![])]);
-            assert_eq!(group_agent.groups(), groups);
+            assert_eq!(audit.len(), 1);
+            assert_eq!(audit[0].old_reserve, Some(5));
+            assert_eq!(audit[0].new_reserve, Some(10));
+            assert_eq!(&audit[0].changed_by, agent.agent_id());
         }
     }
 
-    mod read {
+    mod close {
+        use std::ops::Bound;
+
+        use chrono::{Duration, Utc};
+
         use crate::{
             db::room::Object as Room,
-            test_helpers::{db::TestDb, prelude::*},
+            test_helpers::{db::TestDb, find_event_by_predicate, prelude::*},
         };
 
         use super::super::*;
 
         #[sqlx::test]
-        async fn read_room(pool: sqlx::PgPool) {
+        async fn close_room(pool: sqlx::PgPool) {
             let db = TestDb::new(pool);
 
             let room = {
                 let mut conn = db.get_conn().await;
+
                 // Create room.
-                shared_helpers::insert_room(&mut conn).await
+                factory::Room::new()
+                    .audience(USR_AUDIENCE)
+                    .time((Bound::Unbounded, Bound::Unbounded))
+                    .rtc_sharing_policy(db::rtc::SharingPolicy::Shared)
+                    .insert(&mut conn)
+                    .await
             };
 
-            // Allow agent to read the room.
+            // Allow agent to update the room.
             let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
             let mut authz = TestAuthz::new();
             let classroom_id = room.classroom_id().to_string();
             authz.allow(
                 agent.account_id(),
                 vec!["classrooms", &classroom_id],
-                "read",
+                "update",
             );
 
-            // Make room.read request.
+            // Make room.update request.
             let mut context = TestContext::new(db, authz).await;
-            let payload = ReadRequest { id: room.id() };
 
-            let messages = handle_request::<ReadHandler>(&mut context, &agent, payload)
+            let payload = CloseRequest { id: room.id() };
+
+            let messages = handle_request::<CloseHandler>(&mut context, &agent, payload)
                 .await
-                .expect("Room reading failed");
+                .expect("Room close failed");
 
             // Assert response.
             let (resp_room, respp, _) = find_response::<Room>(messages.as_slice());
             assert_eq!(respp.status(), ResponseStatus::OK);
-            assert_eq!(resp_room.audience(), room.audience());
-            assert_eq!(resp_room.time(), room.time());
-            assert_eq!(resp_room.rtc_sharing_policy(), room.rtc_sharing_policy());
+            assert_eq!(resp_room.id(), room.id());
+            let end = match resp_room.time().end() {
+                Bound::Excluded(t) => t,
+                _ => unreachable!("Wrong end in room close"),
+            };
+            assert!(end < Utc::now());
+            assert_eq!(
+                resp_room.rtc_sharing_policy(),
+                db::rtc::SharingPolicy::Shared
+            );
+
+            // Assert summary notification.
+            let (summary, _, topic) =
+                find_event_by_predicate::<JsonValue, _>(messages.as_slice(), |evp, _, _| {
+                    evp.label() == "room.summary"
+                })
+                .expect("Failed to find room.summary event");
+
+            assert!(topic.ends_with(&format!("audiences/{}/events", USR_AUDIENCE)));
+            assert_eq!(summary["room_id"], room.id().to_string());
+            assert_eq!(summary["peak_concurrency"], 0);
         }
 
         #[sqlx::test]
-        async fn read_room_not_authorized(pool: sqlx::PgPool) {
+        async fn close_infinite_room(pool: sqlx::PgPool) {
             let db = TestDb::new(pool);
+
+            let room = {
+                let mut conn = db.get_conn().await;
+
+                // Create room.
+                factory::Room::new()
+                    .audience(USR_AUDIENCE)
+                    .time((Bound::Unbounded, Bound::Unbounded))
+                    .rtc_sharing_policy(db::rtc::SharingPolicy::Shared)
+                    .infinite()
+                    .insert(&mut conn)
+                    .await
+            };
+
+            // Allow agent to update the room.
             let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut authz = TestAuthz::new();
+            let classroom_id = room.classroom_id().to_string();
+            authz.allow(
+                agent.account_id(),
+                vec!["classrooms", &classroom_id],
+                "update",
+            );
+
+            // Make room.update request.
+            let mut context = TestContext::new(db, authz).await;
+
+            let payload = CloseRequest { id: room.id() };
+
+            handle_request::<CloseHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Room close succeeded even though it shouldn't");
+        }
+
+        #[sqlx::test]
+        async fn close_bounded_room(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
 
             let room = {
                 let mut conn = db.get_conn().await;
-                shared_helpers::insert_room(&mut conn).await
+
+                // Create room.
+                factory::Room::new()
+                    .audience(USR_AUDIENCE)
+                    .time((
+                        Bound::Included(Utc::now() - Duration::hours(10)),
+                        Bound::Excluded(Utc::now() + Duration::hours(10)),
+                    ))
+                    .rtc_sharing_policy(db::rtc::SharingPolicy::Shared)
+                    .insert(&mut conn)
+                    .await
             };
 
-            let mut context = TestContext::new(db, TestAuthz::new()).await;
-            let payload = ReadRequest { id: room.id() };
+            // Allow agent to update the room.
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut authz = TestAuthz::new();
+            let classroom_id = room.classroom_id().to_string();
+            authz.allow(
+                agent.account_id(),
+                vec!["classrooms", &classroom_id],
+                "update",
+            );
 
-            let err = handle_request::<ReadHandler>(&mut context, &agent, payload)
+            // Make room.update request.
+            let mut context = TestContext::new(db, authz).await;
+
+            let payload = CloseRequest { id: room.id() };
+
+            let messages = handle_request::<CloseHandler>(&mut context, &agent, payload)
                 .await
-                .expect_err("Unexpected success on room reading");
+                .expect("Room close failed");
 
-            assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
-            assert_eq!(err.kind(), "access_denied");
+            // Assert response.
+            let (resp_room, respp, _) = find_response::<Room>(messages.as_slice());
+            assert_eq!(respp.status(), ResponseStatus::OK);
+            assert_eq!(resp_room.id(), room.id());
+            let end = match resp_room.time().end() {
+                Bound::Excluded(t) => t,
+                _ => unreachable!("Wrong end in room close"),
+            };
+            assert!(end < Utc::now());
+            assert_eq!(
+                resp_room.rtc_sharing_policy(),
+                db::rtc::SharingPolicy::Shared
+            );
         }
 
         #[sqlx::test]
-        async fn read_room_missing(pool: sqlx::PgPool) {
+        async fn close_room_missing(pool: sqlx::PgPool) {
             let db = TestDb::new(pool);
 
             let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
             let mut context = TestContext::new(db, TestAuthz::new()).await;
-            let payload = ReadRequest {
+
+            let payload = CloseRequest {
                 id: db::room::Id::random(),
             };
 
-            let err = handle_request::<ReadHandler>(&mut context, &agent, payload)
+            let err = handle_request::<CloseHandler>(&mut context, &agent, payload)
                 .await
-                .expect_err("Unexpected success on room reading");
+                .expect_err("Unexpected success on room close");
 
             assert_eq!(err.status(), ResponseStatus::NOT_FOUND);
             assert_eq!(err.kind(), "room_not_found");
         }
-    }
 
-    mod update {
-        use std::ops::Bound;
+        #[sqlx::test]
+        async fn close_room_closed(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
 
-        use chrono::{Duration, SubsecRound, Utc};
-        use serde_json::json;
-        use uuid::Uuid;
+            let room = {
+                let mut conn = db.get_conn().await;
+
+                // Create closed room.
+                shared_helpers::insert_closed_room(&mut conn).await
+            };
+
+            let mut context = TestContext::new(db, TestAuthz::new()).await;
+
+            let payload = CloseRequest { id: room.id() };
+
+            let err = handle_request::<CloseHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on room close");
+
+            assert_eq!(err.status(), ResponseStatus::NOT_FOUND);
+            assert_eq!(err.kind(), "room_closed");
+        }
+    }
 
+    mod delete {
         use crate::{
-            db::room::Object as Room,
+            db::room::{FindQueryable, Object as Room},
             test_helpers::{db::TestDb, find_event_by_predicate, prelude::*},
         };
 
-        use super::super::*;
+        use super::super::*;
+
+        #[sqlx::test]
+        async fn delete_closed_room(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_closed_room(&mut conn).await;
+            let rtc = shared_helpers::insert_rtc_with_room(&mut conn, &room).await;
+
+            // Allow agent to delete the room.
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut authz = TestAuthz::new();
+            let classroom_id = room.classroom_id().to_string();
+            authz.allow(
+                agent.account_id(),
+                vec!["classrooms", &classroom_id],
+                "delete",
+            );
+
+            // Make room.delete request.
+            let mut context = TestContext::new(db, authz).await;
+            let payload = DeleteRequest { id: room.id() };
+
+            let messages = handle_request::<DeleteHandler>(&mut context, &agent, payload)
+                .await
+                .expect("Room deletion failed");
+
+            // Assert response.
+            let (resp_room, respp, _) = find_response::<Room>(messages.as_slice());
+            assert_eq!(respp.status(), ResponseStatus::OK);
+            assert_eq!(resp_room.id(), room.id());
+
+            // Assert notification.
+            let (_, _, topic) =
+                find_event_by_predicate::<Room, _>(messages.as_slice(), |evp, _, _| {
+                    evp.label() == "room.delete"
+                })
+                .expect("Failed to find room.delete event");
+
+            assert!(topic.ends_with(&format!("audiences/{}/events", USR_AUDIENCE)));
+
+            // Assert the room is gone along with its rtcs.
+            let db_room = db::room::FindQuery::new(room.id())
+                .execute(&mut conn)
+                .await
+                .expect("Failed to find room");
+
+            assert!(db_room.is_none());
+
+            let db_rtc = db::rtc::FindQuery::new(rtc.id())
+                .execute(&mut conn)
+                .await
+                .expect("Failed to find rtc");
+
+            assert!(db_rtc.is_none());
+        }
 
         #[sqlx::test]
-        async fn update_room(pool: sqlx::PgPool) {
+        async fn delete_open_room(pool: sqlx::PgPool) {
             let db = TestDb::new(pool);
-            let now = Utc::now().trunc_subsecs(0);
 
             let room = {
                 let mut conn = db.get_conn().await;
-
-                // Create room.
-                factory::Room::new()
-                    .audience(USR_AUDIENCE)
-                    .time((Bound::Unbounded, Bound::Unbounded))
-                    .rtc_sharing_policy(db::rtc::SharingPolicy::Shared)
-                    .insert(&mut conn)
-                    .await
+                shared_helpers::insert_room(&mut conn).await
             };
 
-            // Allow agent to update the room.
             let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
             let mut authz = TestAuthz::new();
             let classroom_id = room.classroom_id().to_string();
             authz.allow(
                 agent.account_id(),
                 vec!["classrooms", &classroom_id],
-                "update",
+                "delete",
             );
 
-            // Make room.update request.
             let mut context = TestContext::new(db, authz).await;
-            let classroom_id = Uuid::new_v4();
+            let payload = DeleteRequest { id: room.id() };
 
-            let time = (
-                Bound::Included(now + Duration::minutes(50)),
-                Bound::Unbounded,
-            );
+            let err = handle_request::<DeleteHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on open room deletion");
 
-            let payload = UpdateRequest {
-                id: room.id(),
-                time: Some(time),
-                reserve: Some(Some(123)),
-                tags: Some(json!({"foo": "bar"})),
-                classroom_id: Some(classroom_id),
-                host: Some(agent.agent_id().clone()),
+            assert_eq!(err.status(), ResponseStatus::CONFLICT);
+            assert_eq!(err.kind(), "conflict");
+        }
+
+        #[sqlx::test]
+        async fn delete_room_not_authorized(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+
+            let room = {
+                let mut conn = db.get_conn().await;
+                shared_helpers::insert_closed_room(&mut conn).await
             };
 
-            let messages = handle_request::<UpdateHandler>(&mut context, &agent, payload)
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut context = TestContext::new(db, TestAuthz::new()).await;
+            let payload = DeleteRequest { id: room.id() };
+
+            let err = handle_request::<DeleteHandler>(&mut context, &agent, payload)
                 .await
-                .expect("Room update failed");
+                .expect_err("Unexpected success on room deletion");
 
-            // Assert response.
-            let (resp_room, respp, _) = find_response::<Room>(messages.as_slice());
-            assert_eq!(respp.status(), ResponseStatus::OK);
-            assert_eq!(resp_room.id(), room.id());
-            assert_eq!(resp_room.audience(), room.audience());
-            assert_eq!(resp_room.time(), time);
-            assert_eq!(
-                resp_room.rtc_sharing_policy(),
-                db::rtc::SharingPolicy::Shared
-            );
-            assert_eq!(resp_room.reserve(), Some(123));
-            assert_eq!(resp_room.tags(), &json!({"foo": "bar"}));
-            assert_eq!(resp_room.classroom_id(), classroom_id);
-            assert_eq!(resp_room.host(), Some(agent.agent_id()));
+            assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+            assert_eq!(err.kind(), "access_denied");
         }
+    }
+
+    mod enter {
+        use std::ops::Bound;
+
+        use chrono::{Duration, Utc};
+
+        use crate::db::group_agent::{GroupItem, Groups};
+        use crate::test_helpers::{db::TestDb, prelude::*, test_deps::LocalDeps};
+
+        use super::super::*;
 
         #[sqlx::test]
-        async fn update_room_with_wrong_time(pool: sqlx::PgPool) {
+        async fn enter_room(pool: sqlx::PgPool) {
             let db = TestDb::new(pool);
-            let now = Utc::now().trunc_subsecs(0);
 
             let room = {
                 let mut conn = db.get_conn().await;
 
                 // Create room.
-                factory::Room::new()
-                    .audience(USR_AUDIENCE)
-                    .time((
-                        Bound::Included(now - Duration::hours(1)),
-                        Bound::Excluded(now + Duration::hours(2)),
-                    ))
-                    .rtc_sharing_policy(db::rtc::SharingPolicy::Shared)
-                    .insert(&mut conn)
-                    .await
+                shared_helpers::insert_room(&mut conn).await
             };
 
-            // Allow agent to update the room.
+            // Allow agent to subscribe to the rooms' events.
             let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
             let mut authz = TestAuthz::new();
             let classroom_id = room.classroom_id().to_string();
             authz.allow(
                 agent.account_id(),
                 vec!["classrooms", &classroom_id],
-                "update",
+                "read",
             );
-
-            // Make room.update request.
-            let mut context = TestContext::new(db, authz).await;
-
-            let time = (
-                Bound::Included(now + Duration::hours(3)),
-                Bound::Excluded(now - Duration::hours(2)),
+            authz.allow(
+                agent.account_id(),
+                vec!["classrooms", &classroom_id, "rtcs"],
+                "create",
             );
 
-            let payload = UpdateRequest {
-                id: room.id(),
-                time: Some(time),
-                reserve: Some(Some(123)),
-                tags: Some(json!({"foo": "bar"})),
-                classroom_id: None,
-                host: None,
+            // Make room.enter request.
+            let context = TestContext::new(db, authz).await;
+            let payload = EnterRequest {
+                room: helpers::RoomRef::Id(room.id()),
+                session_key: None,
             };
 
-            handle_request::<UpdateHandler>(&mut context, &agent, payload)
+            let reqp = RequestParams::Http {
+                agent_id: &agent.agent_id(),
+            };
+            EnterHandler::handle(Arc::new(context), payload, reqp, Utc::now())
                 .await
-                .expect_err("Room update succeeded when it should've failed");
+                .expect("Room entrance failed");
         }
 
         #[sqlx::test]
-        async fn update_and_close_room(pool: sqlx::PgPool) {
+        async fn enter_room_retry_keeps_ready(pool: sqlx::PgPool) {
             let db = TestDb::new(pool);
-            let now = Utc::now().trunc_subsecs(0);
 
             let room = {
                 let mut conn = db.get_conn().await;
-
-                // Create room.
-                factory::Room::new()
-                    .audience(USR_AUDIENCE)
-                    .time((
-                        Bound::Included(now - Duration::hours(1)),
-                        Bound::Excluded(now + Duration::hours(5)),
-                    ))
-                    .rtc_sharing_policy(db::rtc::SharingPolicy::Shared)
-                    .insert(&mut conn)
-                    .await
+                shared_helpers::insert_room(&mut conn).await
             };
 
-            // Allow agent to update the room.
             let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
             let mut authz = TestAuthz::new();
             let classroom_id = room.classroom_id().to_string();
             authz.allow(
                 agent.account_id(),
                 vec!["classrooms", &classroom_id],
-                "update",
+                "read",
             );
 
-            // Make room.update request.
-            let mut context = TestContext::new(db, authz).await;
-
-            let time = (
-                Bound::Included(now - Duration::hours(1)),
-                Bound::Excluded(now - Duration::seconds(5)),
-            );
+            let context = Arc::new(TestContext::new(db, authz).await);
 
-            let payload = UpdateRequest {
-                id: room.id(),
-                time: Some(time),
-                reserve: Some(Some(123)),
-                tags: Default::default(),
-                classroom_id: Default::default(),
-                host: None,
+            let reqp = RequestParams::Http {
+                agent_id: agent.agent_id(),
             };
 
-            let messages = handle_request::<UpdateHandler>(&mut context, &agent, payload)
+            // Enter twice as a client retrying a timed out request would.
+            for _ in 0..2 {
+                EnterHandler::handle(
+                    context.clone(),
+                    EnterRequest {
+                        room: helpers::RoomRef::Id(room.id()),
+                        session_key: None,
+                    },
+                    reqp,
+                    Utc::now(),
+                )
                 .await
-                .expect("Room update failed");
+                .expect("Room entrance failed");
+            }
 
-            assert_eq!(messages.len(), 4);
+            let mut conn = context.get_conn().await.expect("Failed to get conn");
 
-            let (closed_tenant_notification, _, _) =
-                find_event_by_predicate::<JsonValue, _>(messages.as_slice(), |evp, _, topic| {
-                    evp.label() == "room.close" && topic.contains("audiences")
-                })
-                .expect("Failed to find room.close event");
+            let agents = db::agent::ListQuery::new()
+                .agent_id(agent.agent_id())
+                .room_id(room.id())
+                .execute(&mut conn)
+                .await
+                .expect("Failed to list agents");
 
-            assert_eq!(
-                closed_tenant_notification
-                    .get("id")
-                    .and_then(|v| v.as_str()),
-                Some(room.id().to_string()).as_deref()
-            );
+            assert_eq!(agents.len(), 1);
+            assert_eq!(agents[0].status(), db::agent::Status::Ready);
 
-            let (closed_room_notification, _, _) =
-                find_event_by_predicate::<JsonValue, _>(messages.as_slice(), |evp, _, topic| {
-                    evp.label() == "room.close" && topic.contains("rooms")
-                })
-                .expect("Failed to find room.close event");
+            // A late `subscribed` transition doesn't step the agent back.
+            let updated = db::agent::UpdateQuery::new(agent.agent_id(), room.id())
+                .status(db::agent::Status::Subscribed)
+                .execute(&mut conn)
+                .await
+                .expect("Failed to update agent");
 
-            assert_eq!(
-                closed_room_notification.get("id").and_then(|v| v.as_str()),
-                Some(room.id().to_string()).as_deref()
-            );
+            assert!(updated.is_none());
         }
 
         #[sqlx::test]
-        async fn update_and_close_unbounded_room(pool: sqlx::PgPool) {
+        async fn enter_room_stitches_reconnect(pool: sqlx::PgPool) {
             let db = TestDb::new(pool);
-            let now = Utc::now().trunc_subsecs(0);
 
             let room = {
                 let mut conn = db.get_conn().await;
-
-                // Create room.
-                factory::Room::new()
-                    .audience(USR_AUDIENCE)
-                    .time((Bound::Included(now - Duration::hours(1)), Bound::Unbounded))
-                    .insert(&mut conn)
-                    .await
+                shared_helpers::insert_room(&mut conn).await
             };
 
-            // Allow agent to update the room.
             let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            // The same client after a reconnect gets another label.
+            let reconnected = TestAgent::new("web-1", "user123", USR_AUDIENCE);
             let mut authz = TestAuthz::new();
             let classroom_id = room.classroom_id().to_string();
             authz.allow(
                 agent.account_id(),
                 vec!["classrooms", &classroom_id],
-                "update",
+                "read",
             );
 
-            // Make room.update request.
-            let mut context = TestContext::new(db, authz).await;
+            let context = Arc::new(TestContext::new(db, authz).await);
+            let mut session_key = None;
 
-            let time = (
-                Bound::Included(now - Duration::hours(1)),
-                Bound::Excluded(now - Duration::seconds(5)),
-            );
+            for agent in [&agent, &reconnected] {
+                let reqp = RequestParams::Http {
+                    agent_id: agent.agent_id(),
+                };
 
-            let payload = UpdateRequest {
-                id: room.id(),
-                time: Some(time),
-                reserve: Default::default(),
-                tags: Default::default(),
-                classroom_id: Default::default(),
-                host: None,
-            };
+                let payload = EnterRequest {
+                    room: helpers::RoomRef::Id(room.id()),
+                    session_key,
+                };
 
-            handle_request::<UpdateHandler>(&mut context, &agent, payload)
-                .await
-                .expect("Room update failed");
-        }
+                let mut key = None;
 
-        #[sqlx::test]
-        async fn update_room_missing(pool: sqlx::PgPool) {
-            let db = TestDb::new(pool);
+                EnterHandler::handle(context.clone(), payload, reqp, Utc::now())
+                    .await
+                    .expect("Room entrance failed")
+                    .map_payload(|payload| {
+                        key = serde_json::from_value(payload["session_key"].take()).ok()
+                    });
 
-            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
-            let mut context = TestContext::new(db, TestAuthz::new()).await;
+                assert!(key.is_some());
 
-            let payload = UpdateRequest {
-                id: db::room::Id::random(),
-                time: Default::default(),
-                reserve: Default::default(),
-                tags: Default::default(),
-                classroom_id: Default::default(),
-                host: None,
-            };
+                if let Some(session_key) = session_key {
+                    assert_eq!(key, Some(session_key));
+                }
 
-            let err = handle_request::<UpdateHandler>(&mut context, &agent, payload)
+                session_key = key;
+            }
+
+            let mut conn = context.get_conn().await.expect("Failed to get conn");
+
+            // The stale agent is still ready when the reconnected one enters.
+            for agent in [&agent, &reconnected] {
+                db::agent::UpdateQuery::new(agent.agent_id(), room.id())
+                    .status(db::agent::Status::Ready)
+                    .execute(&mut conn)
+                    .await
+                    .expect("Failed to update agent");
+            }
+
+            db::room_summary::observe_concurrency(room.id(), &mut conn)
                 .await
-                .expect_err("Unexpected success on room update");
+                .expect("Failed to observe concurrency");
 
-            assert_eq!(err.status(), ResponseStatus::NOT_FOUND);
-            assert_eq!(err.kind(), "room_not_found");
+            let summary = db::room_summary::InsertQuery::new(room.id())
+                .execute(&mut conn)
+                .await
+                .expect("Failed to summarize room");
+
+            assert_eq!(summary.peak_concurrency, 1);
+            assert_eq!(summary.sessions_count, 1);
+            assert_eq!(summary.reconnects_count, 1);
         }
 
         #[sqlx::test]
-        async fn update_room_closed(pool: sqlx::PgPool) {
+        async fn enter_room_not_authorized(pool: sqlx::PgPool) {
             let db = TestDb::new(pool);
             let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
 
             let room = {
                 let mut conn = db.get_conn().await;
-
-                // Create closed room.
-                shared_helpers::insert_closed_room(&mut conn).await
+                shared_helpers::insert_room(&mut conn).await
             };
 
-            let mut context = TestContext::new(db, TestAuthz::new()).await;
+            let context = TestContext::new(db, TestAuthz::new()).await;
+            let payload = EnterRequest {
+                room: helpers::RoomRef::Id(room.id()),
+                session_key: None,
+            };
 
-            let payload = UpdateRequest {
-                id: room.id(),
-                time: Some((Bound::Included(Utc::now()), Bound::Excluded(Utc::now()))),
-                reserve: Default::default(),
-                tags: Default::default(),
-                classroom_id: Default::default(),
-                host: None,
+            let reqp = RequestParams::Http {
+                agent_id: &agent.agent_id(),
             };
-
-            let err = handle_request::<UpdateHandler>(&mut context, &agent, payload)
+            let err = EnterHandler::handle(Arc::new(context), payload, reqp, Utc::now())
                 .await
-                .expect_err("Unexpected success on room update");
+                .err()
+                .expect("Unexpected success on room entering");
 
-            assert_eq!(err.status(), ResponseStatus::NOT_FOUND);
-            assert_eq!(err.kind(), "room_closed");
+            assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+            assert_eq!(err.kind(), "access_denied");
         }
-    }
-
-    mod close {
-        use std::ops::Bound;
-
-        use chrono::{Duration, Utc};
-
-        use crate::{
-            db::room::Object as Room,
-            test_helpers::{db::TestDb, prelude::*},
-        };
-
-        use super::super::*;
 
         #[sqlx::test]
-        async fn close_room(pool: sqlx::PgPool) {
+        async fn enter_room_exceeding_connection_limit(pool: sqlx::PgPool) {
             let db = TestDb::new(pool);
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
 
             let room = {
                 let mut conn = db.get_conn().await;
+                let room = shared_helpers::insert_room(&mut conn).await;
 
-                // Create room.
-                factory::Room::new()
-                    .audience(USR_AUDIENCE)
-                    .time((Bound::Unbounded, Bound::Unbounded))
-                    .rtc_sharing_policy(db::rtc::SharingPolicy::Shared)
-                    .insert(&mut conn)
-                    .await
+                // The same account is already in the room from two other devices.
+                for label in ["phone", "tablet"] {
+                    let device = TestAgent::new(label, "user123", USR_AUDIENCE);
+                    shared_helpers::insert_agent(&mut conn, device.agent_id(), room.id()).await;
+                }
+
+                room
             };
 
-            // Allow agent to update the room.
-            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
             let mut authz = TestAuthz::new();
             let classroom_id = room.classroom_id().to_string();
             authz.allow(
                 agent.account_id(),
                 vec!["classrooms", &classroom_id],
-                "update",
+                "read",
             );
 
-            // Make room.update request.
             let mut context = TestContext::new(db, authz).await;
 
-            let payload = CloseRequest { id: room.id() };
+            context.config_mut().connection_limit.insert(
+                USR_AUDIENCE.to_owned(),
+                crate::config::ConnectionLimitConfig {
+                    max_agents: 2,
+                    ban_duration: std::time::Duration::from_secs(600),
+                    notify: true,
+                },
+            );
 
-            let messages = handle_request::<CloseHandler>(&mut context, &agent, payload)
-                .await
-                .expect("Room close failed");
+            let context = Arc::new(context);
 
-            // Assert response.
-            let (resp_room, respp, _) = find_response::<Room>(messages.as_slice());
-            assert_eq!(respp.status(), ResponseStatus::OK);
-            assert_eq!(resp_room.id(), room.id());
-            let end = match resp_room.time().1 {
-                Bound::Excluded(t) => t,
-                _ => unreachable!("Wrong end in room close"),
+            let payload = EnterRequest {
+                room: helpers::RoomRef::Id(room.id()),
+                session_key: None,
             };
-            assert!(end < Utc::now());
-            assert_eq!(
-                resp_room.rtc_sharing_policy(),
-                db::rtc::SharingPolicy::Shared
-            );
+
+            let reqp = RequestParams::Http {
+                agent_id: &agent.agent_id(),
+            };
+
+            let err = EnterHandler::handle(context.clone(), payload, reqp, Utc::now())
+                .await
+                .err()
+                .expect("Unexpected success on room entering");
+
+            assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+            assert_eq!(err.kind(), "connection_limit_exceeded");
+
+            // The account is banned in the classroom for a while.
+            let mut conn = context.get_conn().await.expect("Failed to get conn");
+
+            let ban =
+                db::classroom_ban::find_active(room.classroom_id(), agent.account_id(), &mut conn)
+                    .await
+                    .expect("Failed to find ban")
+                    .expect("Account not banned");
+
+            assert_eq!(ban.reason.as_deref(), Some("connection_limit_exceeded"));
+            assert!(ban.expires_at.is_some());
         }
 
         #[sqlx::test]
-        async fn close_infinite_room(pool: sqlx::PgPool) {
+        async fn enter_lobby_room_until_admitted(pool: sqlx::PgPool) {
             let db = TestDb::new(pool);
+            let host = TestAgent::new("web", "host", USR_AUDIENCE);
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
 
             let room = {
                 let mut conn = db.get_conn().await;
 
-                // Create room.
                 factory::Room::new()
                     .audience(USR_AUDIENCE)
-                    .time((Bound::Unbounded, Bound::Unbounded))
+                    .time((Bound::Included(Utc::now()), Bound::Unbounded))
                     .rtc_sharing_policy(db::rtc::SharingPolicy::Shared)
-                    .infinite()
+                    .lobby(true)
                     .insert(&mut conn)
                     .await
             };
 
-            // Allow agent to update the room.
-            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
             let mut authz = TestAuthz::new();
             let classroom_id = room.classroom_id().to_string();
             authz.allow(
                 agent.account_id(),
                 vec!["classrooms", &classroom_id],
+                "read",
+            );
+            authz.allow(
+                host.account_id(),
+                vec!["classrooms", &classroom_id],
                 "update",
             );
 
-            // Make room.update request.
+            let context = Arc::new(TestContext::new(db.clone(), authz.clone()).await);
+
+            let payload = EnterRequest {
+                room: helpers::RoomRef::Id(room.id()),
+                session_key: None,
+            };
+
+            let reqp = RequestParams::Http {
+                agent_id: agent.agent_id(),
+            };
+
+            let mut pending = None;
+
+            EnterHandler::handle(context.clone(), payload, reqp, Utc::now())
+                .await
+                .expect("Room entrance failed")
+                .map_payload(|payload| pending = payload["pending"].as_bool());
+
+            assert_eq!(pending, Some(true));
+
+            // The agent may not connect until a host admits it.
+            {
+                let mut conn = context.get_conn().await.expect("Failed to get conn");
+
+                let err = helpers::check_admission(&room, agent.agent_id(), &mut conn)
+                    .await
+                    .expect_err("Unexpected admission");
+
+                assert_eq!(err.kind(), "agent_not_admitted");
+            }
+
             let mut context = TestContext::new(db, authz).await;
 
-            let payload = CloseRequest { id: room.id() };
+            let payload = LobbyRequest {
+                id: room.id(),
+                agent_id: agent.agent_id().to_owned(),
+            };
 
-            handle_request::<CloseHandler>(&mut context, &agent, payload)
+            let messages = handle_request::<AdmitHandler>(&mut context, &host, payload)
                 .await
-                .expect_err("Room close succeeded even though it shouldn't");
+                .expect("Agent admission failed");
+
+            let (event, evp, topic) = find_event::<JsonValue>(messages.as_slice());
+            assert_eq!(evp.label(), "room.admit");
+            assert!(topic.contains(&agent.agent_id().to_string()));
+            assert_eq!(event["id"], json!(room.id()));
+
+            let mut conn = context.get_conn().await.expect("Failed to get conn");
+
+            let agents = db::agent::ListQuery::new()
+                .status(db::agent::Status::Ready)
+                .agent_id(agent.agent_id())
+                .room_id(room.id())
+                .execute(&mut conn)
+                .await
+                .expect("Failed to list agents");
+
+            assert_eq!(agents.len(), 1);
+
+            helpers::check_admission(&room, agent.agent_id(), &mut conn)
+                .await
+                .expect("Agent not admitted");
         }
 
         #[sqlx::test]
-        async fn close_bounded_room(pool: sqlx::PgPool) {
+        async fn enter_full_room(pool: sqlx::PgPool) {
             let db = TestDb::new(pool);
+            let occupant = TestAgent::new("web", "occupant", USR_AUDIENCE);
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
 
             let room = {
                 let mut conn = db.get_conn().await;
 
-                // Create room.
-                factory::Room::new()
+                let room = factory::Room::new()
                     .audience(USR_AUDIENCE)
-                    .time((
-                        Bound::Included(Utc::now() - Duration::hours(10)),
-                        Bound::Excluded(Utc::now() + Duration::hours(10)),
-                    ))
+                    .time((Bound::Included(Utc::now()), Bound::Unbounded))
                     .rtc_sharing_policy(db::rtc::SharingPolicy::Shared)
+                    .max_agents(1)
                     .insert(&mut conn)
-                    .await
+                    .await;
+
+                shared_helpers::insert_agent(&mut conn, occupant.agent_id(), room.id()).await;
+                room
             };
 
-            // Allow agent to update the room.
-            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
             let mut authz = TestAuthz::new();
             let classroom_id = room.classroom_id().to_string();
             authz.allow(
                 agent.account_id(),
                 vec!["classrooms", &classroom_id],
-                "update",
+                "read",
             );
 
-            // Make room.update request.
-            let mut context = TestContext::new(db, authz).await;
-
-            let payload = CloseRequest { id: room.id() };
-
-            let messages = handle_request::<CloseHandler>(&mut context, &agent, payload)
-                .await
-                .expect("Room close failed");
+            let context = Arc::new(TestContext::new(db, authz).await);
 
-            // Assert response.
-            let (resp_room, respp, _) = find_response::<Room>(messages.as_slice());
-            assert_eq!(respp.status(), ResponseStatus::OK);
-            assert_eq!(resp_room.id(), room.id());
-            let end = match resp_room.time().1 {
-                Bound::Excluded(t) => t,
-                _ => unreachable!("Wrong end in room close"),
+            let payload = EnterRequest {
+                room: helpers::RoomRef::Id(room.id()),
+                session_key: None,
             };
-            assert!(end < Utc::now());
-            assert_eq!(
-                resp_room.rtc_sharing_policy(),
-                db::rtc::SharingPolicy::Shared
-            );
-        }
-
-        #[sqlx::test]
-        async fn close_room_missing(pool: sqlx::PgPool) {
-            let db = TestDb::new(pool);
 
-            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
-            let mut context = TestContext::new(db, TestAuthz::new()).await;
-
-            let payload = CloseRequest {
-                id: db::room::Id::random(),
+            let reqp = RequestParams::Http {
+                agent_id: agent.agent_id(),
             };
 
-            let err = handle_request::<CloseHandler>(&mut context, &agent, payload)
+            let err = EnterHandler::handle(context.clone(), payload, reqp, Utc::now())
                 .await
-                .expect_err("Unexpected success on room close");
+                .expect_err("Unexpected success entering full room");
 
-            assert_eq!(err.status(), ResponseStatus::NOT_FOUND);
-            assert_eq!(err.kind(), "room_not_found");
-        }
-
-        #[sqlx::test]
-        async fn close_room_closed(pool: sqlx::PgPool) {
-            let db = TestDb::new(pool);
-            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            assert_eq!(err.status(), ResponseStatus::CONFLICT);
+            assert_eq!(err.kind(), "room_full");
 
-            let room = {
-                let mut conn = db.get_conn().await;
+            // Neither the agent nor its session took the seat.
+            let mut conn = context.get_conn().await.expect("Failed to get conn");
 
-                // Create closed room.
-                shared_helpers::insert_closed_room(&mut conn).await
-            };
+            let occupants = db::agent::count_occupants(room.id(), None, &mut conn)
+                .await
+                .expect("Failed to count occupants");
 
-            let mut context = TestContext::new(db, TestAuthz::new()).await;
+            assert_eq!(occupants, 1);
 
-            let payload = CloseRequest { id: room.id() };
+            helpers::check_room_capacity(&room, occupant.agent_id(), &mut conn)
+                .await
+                .expect("Occupant lost its seat");
 
-            let err = handle_request::<CloseHandler>(&mut context, &agent, payload)
+            let err = helpers::check_room_capacity(&room, agent.agent_id(), &mut conn)
                 .await
-                .expect_err("Unexpected success on room close");
+                .expect_err("Unexpected seat for the agent");
 
-            assert_eq!(err.status(), ResponseStatus::NOT_FOUND);
-            assert_eq!(err.kind(), "room_closed");
+            assert_eq!(err.kind(), "room_full");
         }
-    }
-
-    mod enter {
-        use chrono::{Duration, Utc};
-
-        use crate::db::group_agent::{GroupItem, Groups};
-        use crate::test_helpers::{db::TestDb, prelude::*, test_deps::LocalDeps};
-
-        use super::super::*;
 
         #[sqlx::test]
-        async fn enter_room(pool: sqlx::PgPool) {
+        async fn reject_agent_from_lobby(pool: sqlx::PgPool) {
             let db = TestDb::new(pool);
+            let host = TestAgent::new("web", "host", USR_AUDIENCE);
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
 
             let room = {
                 let mut conn = db.get_conn().await;
 
-                // Create room.
-                shared_helpers::insert_room(&mut conn).await
+                let room = factory::Room::new()
+                    .audience(USR_AUDIENCE)
+                    .time((Bound::Included(Utc::now()), Bound::Unbounded))
+                    .rtc_sharing_policy(db::rtc::SharingPolicy::Shared)
+                    .lobby(true)
+                    .insert(&mut conn)
+                    .await;
+
+                db::agent::InsertQuery::new(agent.agent_id(), room.id())
+                    .status(db::agent::Status::Pending)
+                    .execute(&mut conn)
+                    .await
+                    .expect("Failed to insert agent");
+
+                room
             };
 
-            // Allow agent to subscribe to the rooms' events.
-            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
             let mut authz = TestAuthz::new();
             let classroom_id = room.classroom_id().to_string();
             authz.allow(
-                agent.account_id(),
+                host.account_id(),
                 vec!["classrooms", &classroom_id],
-                "read",
-            );
-            authz.allow(
-                agent.account_id(),
-                vec!["classrooms", &classroom_id, "rtcs"],
-                "create",
+                "update",
             );
 
-            // Make room.enter request.
-            let context = TestContext::new(db, authz).await;
-            let payload = EnterRequest { id: room.id() };
+            let mut context = TestContext::new(db, authz).await;
 
-            let reqp = RequestParams::Http {
-                agent_id: &agent.agent_id(),
+            let payload = RejectRequest {
+                id: room.id(),
+                agent_id: agent.agent_id().to_owned(),
             };
-            EnterHandler::handle(Arc::new(context), payload, reqp, Utc::now())
+
+            let messages = handle_request::<RejectHandler>(&mut context, &host, payload)
                 .await
-                .expect("Room entrance failed");
-        }
+                .expect("Agent rejection failed");
 
-        #[sqlx::test]
-        async fn enter_room_not_authorized(pool: sqlx::PgPool) {
-            let db = TestDb::new(pool);
-            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let (_, evp, _) = find_event::<JsonValue>(messages.as_slice());
+            assert_eq!(evp.label(), "room.reject");
 
-            let room = {
-                let mut conn = db.get_conn().await;
-                shared_helpers::insert_room(&mut conn).await
-            };
+            let mut conn = context.get_conn().await.expect("Failed to get conn");
 
-            let context = TestContext::new(db, TestAuthz::new()).await;
-            let payload = EnterRequest { id: room.id() };
+            let agents = db::agent::ListQuery::new()
+                .agent_id(agent.agent_id())
+                .room_id(room.id())
+                .execute(&mut conn)
+                .await
+                .expect("Failed to list agents");
 
-            let reqp = RequestParams::Http {
-                agent_id: &agent.agent_id(),
+            assert!(agents.is_empty());
+
+            // Only agents waiting in the lobby can be rejected.
+            let payload = RejectRequest {
+                id: room.id(),
+                agent_id: agent.agent_id().to_owned(),
             };
-            let err = EnterHandler::handle(Arc::new(context), payload, reqp, Utc::now())
+
+            let err = handle_request::<RejectHandler>(&mut context, &host, payload)
                 .await
-                .err()
-                .expect("Unexpected success on room entering");
+                .expect_err("Unexpected success on rejecting absent agent");
 
-            assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
-            assert_eq!(err.kind(), "access_denied");
+            assert_eq!(err.kind(), "agent_not_entered_the_room");
         }
 
         #[sqlx::test]
@@ -1747,7 +4511,8 @@ mod test {
             let context = TestContext::new(db, TestAuthz::new()).await;
 
             let payload = EnterRequest {
-                id: db::room::Id::random(),
+                room: helpers::RoomRef::Id(db::room::Id::random()),
+                session_key: None,
             };
 
             let reqp = RequestParams::Http {
@@ -1784,7 +4549,10 @@ mod test {
 
             // Make room.enter request.
             let context = TestContext::new(db, authz).await;
-            let payload = EnterRequest { id: room.id() };
+            let payload = EnterRequest {
+                room: helpers::RoomRef::Id(room.id()),
+                session_key: None,
+            };
 
             let reqp = RequestParams::Http {
                 agent_id: &agent.agent_id(),
@@ -1825,7 +4593,10 @@ mod test {
 
             // Make room.enter request.
             let context = TestContext::new(db, authz).await;
-            let payload = EnterRequest { id: room.id() };
+            let payload = EnterRequest {
+                room: helpers::RoomRef::Id(room.id()),
+                session_key: None,
+            };
 
             let reqp = RequestParams::Http {
                 agent_id: &agent.agent_id(),
@@ -1869,7 +4640,10 @@ mod test {
 
             // Make room.enter request.
             let context = TestContext::new(db, authz).await;
-            let payload = EnterRequest { id: room.id() };
+            let payload = EnterRequest {
+                room: helpers::RoomRef::Id(room.id()),
+                session_key: None,
+            };
 
             let reqp = RequestParams::Http {
                 agent_id: &agent.agent_id(),
@@ -1908,7 +4682,10 @@ mod test {
 
             // Make room.enter request.
             let context = TestContext::new(db, authz).await;
-            let payload = EnterRequest { id: room.id() };
+            let payload = EnterRequest {
+                room: helpers::RoomRef::Id(room.id()),
+                session_key: None,
+            };
 
             let reqp = RequestParams::Http {
                 agent_id: &agent.agent_id(),
@@ -1959,7 +4736,10 @@ mod test {
 
             // Make room.enter request.
             let context = TestContext::new(db, authz).await;
-            let payload = EnterRequest { id: room.id() };
+            let payload = EnterRequest {
+                room: helpers::RoomRef::Id(room.id()),
+                session_key: None,
+            };
 
             let reqp = RequestParams::Http {
                 agent_id: &agent.agent_id(),
@@ -2031,7 +4811,10 @@ mod test {
             let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
             context.with_janus(tx);
 
-            let payload = EnterRequest { id: room.id() };
+            let payload = EnterRequest {
+                room: helpers::RoomRef::Id(room.id()),
+                session_key: None,
+            };
 
             let reqp = RequestParams::Http {
                 agent_id: &agent2.agent_id(),
@@ -0,0 +1,334 @@
+use std::{ops::Bound, sync::Arc};
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use axum::{
+    extract::{Extension, Path},
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+use svc_agent::{mqtt::ResponseStatus, Addressable};
+use svc_utils::extractors::AgentIdExtractor;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::{
+    app::{
+        context::{AppContext, Context},
+        endpoint::prelude::*,
+        message_handler::http,
+        metrics::HistogramExt,
+        room_policy,
+        room_schedule::Recurrence,
+        service_utils::{RequestParams, Response},
+    },
+    authz::AuthzObject,
+    db::{self, room::RoomTime, rtc::SharingPolicy as RtcSharingPolicy},
+};
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct CreateRequest {
+    audience: String,
+    classroom_id: Uuid,
+    /// RRULE-like recurrence, e.g. `FREQ=WEEKLY;BYDAY=MO,WE;COUNT=10`.
+    recurrence: String,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    starts_at: DateTime<Utc>,
+    /// Seconds each room lasts.
+    duration: i32,
+    /// Name of the audience's room template the rooms get created from.
+    #[serde(default)]
+    template: Option<String>,
+    #[serde(default)]
+    rtc_sharing_policy: Option<RtcSharingPolicy>,
+    #[serde(default)]
+    reserve: Option<i32>,
+    #[serde(default)]
+    tags: Option<JsonValue>,
+}
+
+pub async fn create(
+    Extension(ctx): Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Json(request): Json<CreateRequest>,
+) -> RequestResult {
+    http::handle::<CreateHandler>(&ctx, &agent_id, request).await
+}
+
+pub struct CreateHandler;
+
+#[async_trait]
+impl RequestHandler for CreateHandler {
+    type Payload = CreateRequest;
+    const ERROR_TITLE: &'static str = "Failed to create room schedule";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::Object {
+        object: &["classrooms"],
+        actions: &["create"],
+    });
+
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        // Scheduling rooms is the same as creating them one by one.
+        let authz_time = context
+            .authz()
+            .authorize(
+                payload.audience.clone(),
+                reqp,
+                AuthzObject::new(&["classrooms"]).into(),
+                "create".into(),
+            )
+            .await?;
+        context.metrics().observe_auth(authz_time);
+
+        if payload.duration <= 0 {
+            return Err(anyhow!("Duration must be positive")).error(AppErrorKind::InvalidPayload);
+        }
+
+        let first_occurrence = payload
+            .recurrence
+            .parse::<Recurrence>()
+            .error(AppErrorKind::InvalidPayload)?
+            .occurrences(payload.starts_at)
+            .next()
+            .ok_or_else(|| anyhow!("Recurrence has no occurrences"))
+            .error(AppErrorKind::InvalidPayload)?;
+
+        let time = RoomTime::new(
+            Bound::Included(first_occurrence),
+            Bound::Excluded(first_occurrence + Duration::seconds(payload.duration.into())),
+        )
+        .error(AppErrorKind::InvalidPayload)?;
+
+        let proposal = room_policy::Proposal {
+            method: "room_schedule.create",
+            audience: &payload.audience,
+            room_id: None,
+            time: Some(time),
+            previous_opened_at: None,
+            reserve: payload.reserve,
+            classroom_id: Some(payload.classroom_id),
+            tags: payload.tags.as_ref(),
+            metadata: None,
+        };
+
+        room_policy::check(context, &proposal).await?;
+
+        let schedule = {
+            let mut conn = context.get_conn().await?;
+
+            let mut q = db::room_schedule::InsertQuery::new(
+                &payload.audience,
+                payload.classroom_id,
+                &payload.recurrence,
+                payload.starts_at,
+                payload.duration,
+                reqp.as_agent_id(),
+            )
+            .next_occurrence_at(first_occurrence);
+
+            if let Some(ref template) = payload.template {
+                q = q.template(template);
+            }
+
+            if let Some(rtc_sharing_policy) = payload.rtc_sharing_policy {
+                q = q.rtc_sharing_policy(rtc_sharing_policy);
+            }
+
+            if let Some(reserve) = payload.reserve {
+                q = q.reserve(reserve);
+            }
+
+            if let Some(ref tags) = payload.tags {
+                q = q.tags(tags);
+            }
+
+            q.execute(&mut conn).await?
+        };
+
+        info!(
+            schedule_id = %schedule.id,
+            classroom_id = %schedule.classroom_id,
+            recurrence = %schedule.recurrence,
+            "Room schedule created"
+        );
+
+        context
+            .metrics()
+            .request_duration
+            .room_schedule_create
+            .observe_timestamp(context.start_timestamp());
+
+        Ok(Response::new(
+            ResponseStatus::CREATED,
+            schedule,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteRequest {
+    id: Uuid,
+}
+
+pub async fn delete(
+    Extension(ctx): Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(id): Path<Uuid>,
+) -> RequestResult {
+    let request = DeleteRequest { id };
+    http::handle::<DeleteHandler>(&ctx, &agent_id, request).await
+}
+
+pub struct DeleteHandler;
+
+#[async_trait]
+impl RequestHandler for DeleteHandler {
+    type Payload = DeleteRequest;
+    const ERROR_TITLE: &'static str = "Failed to delete room schedule";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::CLASSROOM_UPDATE);
+
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let schedule = {
+            let mut conn = context.get_conn().await?;
+
+            db::room_schedule::find(payload.id, &mut conn)
+                .await?
+                .ok_or_else(|| anyhow!("Room schedule not found"))
+                .error(AppErrorKind::RoomScheduleNotFound)?
+        };
+
+        let classroom_id = schedule.classroom_id.to_string();
+        let object = AuthzObject::new(&["classrooms", &classroom_id]).into();
+
+        let authz_time = context
+            .authz()
+            .authorize(schedule.audience.clone(), reqp, object, "update".into())
+            .await?;
+        context.metrics().observe_auth(authz_time);
+
+        // Rooms created already stay.
+        let schedule = {
+            let mut conn = context.get_conn().await?;
+
+            db::room_schedule::delete(schedule.id, &mut conn)
+                .await?
+                .ok_or_else(|| anyhow!("Room schedule not found"))
+                .error(AppErrorKind::RoomScheduleNotFound)?
+        };
+
+        info!(schedule_id = %schedule.id, "Room schedule deleted");
+
+        context
+            .metrics()
+            .request_duration
+            .room_schedule_delete
+            .observe_timestamp(context.start_timestamp());
+
+        Ok(Response::new(
+            ResponseStatus::OK,
+            schedule,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use crate::test_helpers::{db::TestDb, prelude::*};
+
+    use super::*;
+
+    fn create_request(recurrence: &str, starts_at: DateTime<Utc>) -> CreateRequest {
+        CreateRequest {
+            audience: USR_AUDIENCE.to_owned(),
+            classroom_id: Uuid::new_v4(),
+            recurrence: recurrence.to_owned(),
+            starts_at,
+            duration: 3600,
+            template: None,
+            rtc_sharing_policy: Some(RtcSharingPolicy::Owned),
+            reserve: None,
+            tags: None,
+        }
+    }
+
+    #[sqlx::test]
+    async fn schedule_lifecycle(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let mut authz = TestAuthz::new();
+        authz.allow(agent.account_id(), vec!["classrooms"], "create");
+
+        let starts_at = Utc::now() + Duration::days(1);
+        let payload = create_request("FREQ=WEEKLY;COUNT=3", starts_at);
+        let classroom_id = payload.classroom_id.to_string();
+        authz.allow(
+            agent.account_id(),
+            vec!["classrooms", &classroom_id],
+            "update",
+        );
+
+        let mut context = TestContext::new(db, authz).await;
+
+        let messages = handle_request::<CreateHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Room schedule creation failed");
+
+        let (schedule, respp, _) = find_response::<db::room_schedule::Object>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::CREATED);
+        assert_eq!(
+            schedule.next_occurrence_at.map(|t| t.timestamp()),
+            Some(starts_at.timestamp())
+        );
+
+        let payload = DeleteRequest { id: schedule.id };
+
+        handle_request::<DeleteHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Room schedule deletion failed");
+
+        let mut conn = context.get_conn().await.expect("Failed to get conn");
+        let schedule = db::room_schedule::find(schedule.id, &mut conn)
+            .await
+            .expect("Failed to find room schedule");
+
+        assert!(schedule.is_none());
+    }
+
+    #[sqlx::test]
+    async fn create_schedule_with_invalid_recurrence(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let mut authz = TestAuthz::new();
+        authz.allow(agent.account_id(), vec!["classrooms"], "create");
+        let mut context = TestContext::new(db, authz).await;
+
+        let payload = create_request("FREQ=HOURLY", Utc::now());
+
+        let err = handle_request::<CreateHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success creating room schedule");
+
+        assert_eq!(err.kind(), "invalid_payload");
+    }
+}
@@ -0,0 +1,228 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use axum::{extract::Extension, Json};
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
+use svc_agent::{mqtt::ResponseStatus, Addressable};
+use svc_utils::extractors::AgentIdExtractor;
+use tracing::info;
+
+use crate::{
+    app::{
+        context::{AppContext, Context},
+        endpoint::{prelude::*, room::validate_metadata},
+        message_handler::http,
+        metrics::HistogramExt,
+        service_utils::{RequestParams, Response},
+    },
+    authz::AuthzObject,
+    db::{
+        self,
+        room_template::{ReaderConfigDefaults, WriterConfigDefaults},
+        rtc::SharingPolicy as RtcSharingPolicy,
+    },
+};
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateRequest {
+    audience: String,
+    name: String,
+    #[serde(default)]
+    rtc_sharing_policy: Option<RtcSharingPolicy>,
+    #[serde(default)]
+    reserve: Option<i32>,
+    #[serde(default)]
+    metadata: Option<JsonValue>,
+    #[serde(default)]
+    writer_config: Option<WriterConfigDefaults>,
+    #[serde(default)]
+    reader_config: Option<ReaderConfigDefaults>,
+}
+
+pub async fn update(
+    Extension(ctx): Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Json(request): Json<UpdateRequest>,
+) -> RequestResult {
+    http::handle::<UpdateHandler>(&ctx, &agent_id, request).await
+}
+
+pub struct UpdateHandler;
+
+#[async_trait]
+impl RequestHandler for UpdateHandler {
+    type Payload = UpdateRequest;
+    const ERROR_TITLE: &'static str = "Failed to update room template";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::Object {
+        object: &["room_templates"],
+        actions: &["update"],
+    });
+
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let authz_time = context
+            .authz()
+            .authorize(
+                payload.audience.clone(),
+                reqp,
+                AuthzObject::new(&["room_templates"]).into(),
+                "update".into(),
+            )
+            .await?;
+        context.metrics().observe_auth(authz_time);
+
+        if let Some(ref metadata) = payload.metadata {
+            validate_metadata(metadata)?;
+        }
+
+        let template = {
+            let mut conn = context.get_conn().await?;
+
+            let mut q = db::room_template::UpsertQuery::new(
+                &payload.audience,
+                &payload.name,
+                payload.rtc_sharing_policy.unwrap_or(RtcSharingPolicy::None),
+                reqp.as_agent_id(),
+            );
+
+            if let Some(reserve) = payload.reserve {
+                q = q.reserve(reserve);
+            }
+
+            if let Some(ref metadata) = payload.metadata {
+                q = q.metadata(metadata);
+            }
+
+            if let Some(writer_config) = payload.writer_config {
+                q = q.writer_config(writer_config);
+            }
+
+            if let Some(reader_config) = payload.reader_config {
+                q = q.reader_config(reader_config);
+            }
+
+            q.execute(&mut conn).await?
+        };
+
+        info!(
+            audience = %template.audience,
+            name = %template.name,
+            updated_by = %template.updated_by,
+            "Room template updated"
+        );
+
+        context
+            .metrics()
+            .request_duration
+            .room_template_update
+            .observe_timestamp(context.start_timestamp());
+
+        Ok(Response::new(
+            ResponseStatus::OK,
+            template,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::test_helpers::{db::TestDb, prelude::*};
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn update_replaces_template(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let agent = TestAgent::new("web", "admin", USR_AUDIENCE);
+
+        let mut authz = TestAuthz::new();
+        authz.allow(agent.account_id(), vec!["room_templates"], "update");
+        let mut context = TestContext::new(db, authz).await;
+
+        let payload = UpdateRequest {
+            audience: USR_AUDIENCE.to_owned(),
+            name: "math".to_owned(),
+            rtc_sharing_policy: Some(RtcSharingPolicy::Owned),
+            reserve: Some(20),
+            metadata: Some(json!({ "layout": "grid" })),
+            writer_config: Some(WriterConfigDefaults {
+                send_video: Some(false),
+                ..Default::default()
+            }),
+            reader_config: None,
+        };
+
+        let messages = handle_request::<UpdateHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Room template update failed");
+
+        let (template, respp, _) = find_response::<db::room_template::Object>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert_eq!(template.reserve, Some(20));
+        assert_eq!(
+            template.writer_config().and_then(|c| c.send_video),
+            Some(false)
+        );
+
+        // Properties missing in the next update are dropped.
+        let payload = UpdateRequest {
+            audience: USR_AUDIENCE.to_owned(),
+            name: "math".to_owned(),
+            rtc_sharing_policy: Some(RtcSharingPolicy::Owned),
+            reserve: Some(30),
+            metadata: None,
+            writer_config: None,
+            reader_config: None,
+        };
+
+        handle_request::<UpdateHandler>(&mut context, &agent, payload)
+            .await
+            .expect("Room template update failed");
+
+        let mut conn = context.get_conn().await.expect("Failed to get conn");
+        let template = db::room_template::find(USR_AUDIENCE, "math", &mut conn)
+            .await
+            .expect("Failed to find room template")
+            .expect("Room template not found");
+
+        assert_eq!(template.reserve, Some(30));
+        assert_eq!(template.metadata, None);
+        assert_eq!(template.writer_config(), None);
+    }
+
+    #[sqlx::test]
+    async fn update_room_template_unauthorized(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let agent = TestAgent::new("web", "user", USR_AUDIENCE);
+        let mut context = TestContext::new(db, TestAuthz::new()).await;
+
+        let payload = UpdateRequest {
+            audience: USR_AUDIENCE.to_owned(),
+            name: "math".to_owned(),
+            rtc_sharing_policy: None,
+            reserve: None,
+            metadata: None,
+            writer_config: None,
+            reader_config: None,
+        };
+
+        let err = handle_request::<UpdateHandler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success updating room template");
+
+        assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+        assert_eq!(err.kind(), "access_denied");
+    }
+}
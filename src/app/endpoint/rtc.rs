@@ -4,37 +4,57 @@ use axum::{
     extract::{Extension, Path, Query},
     Json,
 };
-use chrono::Duration;
+use chrono::{DateTime, Duration, SubsecRound, Utc};
 
 use either::Either;
 use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
 use sqlx::Connection as SqlxConnection;
 use std::{fmt, sync::Arc};
-use svc_agent::{mqtt::ResponseStatus, Addressable, AgentId, Authenticable};
+use svc_agent::{
+    mqtt::{
+        OutgoingEvent, OutgoingEventProperties, OutgoingMessage, ResponseStatus,
+        ShortTermTimingProperties,
+    },
+    Addressable, AgentId, Authenticable,
+};
 use svc_utils::extractors::AgentIdExtractor;
 
-use tracing::{warn, Span};
+use tracing::{info, warn, Span};
+use uuid::Uuid;
 
 use crate::{
     app::{
         context::{AppContext, Context, GlobalContext, MessageContext},
         endpoint::prelude::*,
-        endpoint::{self, rtc_signal::start_rtc_stream},
+        endpoint::{self, agent_reader_config::ApplyStatus, rtc_signal::start_rtc_stream},
         handle_id::HandleId,
+        message_handler::http,
         metrics::HistogramExt,
+        room_authz, sdp,
         service_utils::{RequestParams, Response},
     },
     authz::AuthzObject,
-    backend::janus::client::{
-        create_handle::{CreateHandleRequest, OpaqueId},
-        create_stream::{
-            CreateStreamRequest, CreateStreamRequestBody, CreateStreamTransaction, ReaderConfig,
-            WriterConfig,
+    backend::janus::{
+        client::{
+            composite::{StartCompositeRequest, StartCompositeRequestBody},
+            create_handle::{CreateHandleRequest, OpaqueId},
+            create_stream::{
+                CreateStreamRequest, CreateStreamRequestBody, CreateStreamTransaction,
+                ReaderConfig, WriterConfig,
+            },
+            read_stream::{ReadStreamRequest, ReadStreamRequestBody, ReadStreamTransaction},
+            update_agent_reader_config::UpdateReaderConfigRequestBodyConfigItem,
+            Jsep, JsepType, JsonSdp,
         },
-        read_stream::{ReadStreamRequest, ReadStreamRequestBody, ReadStreamTransaction},
-        Jsep, JsonSdp,
+        connect_registry::Connect,
+        correlation, negotiation, JANUS_API_VERSION,
+    },
+    config::IceServer,
+    db::{
+        self, agent, agent_connection,
+        rtc::{Kind as RtcKind, SharingPolicy as RtcSharingPolicy},
     },
-    db::{self, agent, agent_connection, rtc::SharingPolicy as RtcSharingPolicy},
 };
 use tracing_attributes::instrument;
 
@@ -53,27 +73,54 @@ impl ConnectResponseData {
 
 ////////////////////////////////////////////////////////////////////////////////
 
+/// Limit of serialized `tags` of an rtc.
+const MAX_TAGS_SIZE: usize = 1024;
+
+fn validate_tags(tags: &JsonValue) -> Result<(), AppError> {
+    if !tags.is_object() {
+        return Err(anyhow!("Tags must be an object")).error(AppErrorKind::InvalidPayload);
+    }
+
+    if tags.to_string().len() > MAX_TAGS_SIZE {
+        return Err(anyhow!("Tags exceed {} bytes", MAX_TAGS_SIZE))
+            .error(AppErrorKind::InvalidPayload);
+    }
+
+    Ok(())
+}
+
 #[derive(Debug, Deserialize)]
 pub struct CreateRequest {
     room_id: db::room::Id,
+    #[serde(default)]
+    tags: Option<JsonValue>,
+    #[serde(default)]
+    kind: Option<RtcKind>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct CreateFields {
+    #[serde(default)]
+    tags: Option<JsonValue>,
+    #[serde(default)]
+    kind: Option<RtcKind>,
 }
 
 pub async fn create(
     Extension(ctx): Extension<Arc<AppContext>>,
     AgentIdExtractor(agent_id): AgentIdExtractor,
     Path(room_id): Path<db::room::Id>,
+    fields: Option<Json<CreateFields>>,
 ) -> RequestResult {
     tracing::Span::current().record("room_id", &tracing::field::display(room_id));
 
-    let request = CreateRequest { room_id };
-    CreateHandler::handle(
-        &mut ctx.start_message(),
-        request,
-        RequestParams::Http {
-            agent_id: &agent_id,
-        },
-    )
-    .await
+    let Json(fields) = fields.unwrap_or_default();
+    let request = CreateRequest {
+        room_id,
+        tags: fields.tags,
+        kind: fields.kind,
+    };
+    http::handle::<CreateHandler>(&ctx, &agent_id, request).await
 }
 
 pub struct CreateHandler;
@@ -82,12 +129,20 @@ pub struct CreateHandler;
 impl RequestHandler for CreateHandler {
     type Payload = CreateRequest;
     const ERROR_TITLE: &'static str = "Failed to create rtc";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::Object {
+        object: &["classrooms", "{classroom_id}", "rtcs"],
+        actions: &["create"],
+    });
 
     async fn handle<C: Context + Send + Sync>(
         context: &mut C,
         payload: Self::Payload,
         reqp: RequestParams<'_>,
     ) -> RequestResult {
+        if let Some(tags) = &payload.tags {
+            validate_tags(tags)?;
+        }
+
         let RtcCreateResult {
             rtc,
             authz_time,
@@ -97,6 +152,8 @@ impl RequestHandler for CreateHandler {
             ctx: context,
             room: Either::Right(payload.room_id),
             reqp,
+            tags: payload.tags,
+            kind: payload.kind,
         }
         .run()
         .await?;
@@ -131,6 +188,8 @@ pub struct RtcCreate<'a, C: ?Sized> {
     pub ctx: &'a C,
     pub room: Either<db::room::Object, db::room::Id>,
     pub reqp: RequestParams<'a>,
+    pub tags: Option<JsonValue>,
+    pub kind: Option<RtcKind>,
 }
 
 pub struct RtcCreateResult {
@@ -146,8 +205,13 @@ impl<'a, C: GlobalContext + ?Sized> RtcCreate<'a, C> {
             Either::Left(room) => room,
             Either::Right(room_id) => {
                 let mut conn = self.ctx.get_conn().await?;
-                helpers::find_room_by_id(room_id, helpers::RoomTimeRequirement::Open, &mut conn)
-                    .await?
+                helpers::find_room_by_id(
+                    room_id,
+                    helpers::RoomTimeRequirement::Open,
+                    helpers::AudienceScope::caller(self.ctx, &self.reqp),
+                    &mut conn,
+                )
+                .await?
             }
         };
 
@@ -168,9 +232,39 @@ impl<'a, C: GlobalContext + ?Sized> RtcCreate<'a, C> {
 
         // Create an rtc.
         let mut conn = self.ctx.get_conn().await?;
-        let rtc = db::rtc::InsertQuery::new(room.id(), self.reqp.as_agent_id())
-            .execute(&mut conn)
-            .await?;
+        let mut query = db::rtc::InsertQuery::new(room.id(), self.reqp.as_agent_id())
+            .record(room.record_rtcs());
+
+        if let Some(tags) = &self.tags {
+            query = query.tags(tags);
+        }
+
+        if let Some(kind) = self.kind {
+            query = query.kind(kind);
+        }
+
+        let rtc = query.execute(&mut conn).await?;
+
+        // Rooms created from a template start their rtcs with its writer config.
+        if let Some(defaults) =
+            db::room_template::find_writer_defaults(room.id(), &mut conn).await?
+        {
+            let mut query = db::rtc_writer_config::UpsertQuery::new(rtc.id());
+
+            if let Some(send_video) = defaults.send_video {
+                query = query.send_video(send_video);
+            }
+
+            if let Some(send_audio) = defaults.send_audio {
+                query = query.send_audio(send_audio);
+            }
+
+            if let Some(video_remb) = defaults.video_remb {
+                query = query.video_remb(video_remb);
+            }
+
+            query.execute(&mut conn).await?;
+        }
 
         let notification_topic = format!("rooms/{}/events", rtc.room_id());
         Ok(RtcCreateResult {
@@ -197,14 +291,7 @@ pub async fn read(
     tracing::Span::current().record("rtc_id", &tracing::field::display(rtc_id));
 
     let request = ReadRequest { id: rtc_id };
-    ReadHandler::handle(
-        &mut ctx.start_message(),
-        request,
-        RequestParams::Http {
-            agent_id: &agent_id,
-        },
-    )
-    .await
+    http::handle::<ReadHandler>(&ctx, &agent_id, request).await
 }
 
 pub struct ReadHandler;
@@ -213,6 +300,10 @@ pub struct ReadHandler;
 impl RequestHandler for ReadHandler {
     type Payload = ReadRequest;
     const ERROR_TITLE: &'static str = "Failed to read rtc";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::Object {
+        object: &["classrooms", "{classroom_id}", "rtcs", "{rtc_id}"],
+        actions: &["read"],
+    });
 
     #[instrument(skip(context, payload, reqp), fields(room_id = %payload.id))]
     async fn handle<C: Context + Send + Sync>(
@@ -222,8 +313,13 @@ impl RequestHandler for ReadHandler {
     ) -> RequestResult {
         let room = {
             let mut conn = context.get_conn().await?;
-            helpers::find_room_by_rtc_id(payload.id, helpers::RoomTimeRequirement::Open, &mut conn)
-                .await?
+            helpers::find_room_by_rtc_id(
+                payload.id,
+                helpers::RoomTimeRequirement::Open,
+                helpers::AudienceScope::caller(context, &reqp),
+                &mut conn,
+            )
+            .await?
         };
 
         tracing::Span::current().record("room_id", &tracing::field::display(room.id()));
@@ -268,17 +364,142 @@ impl RequestHandler for ReadHandler {
 
 ////////////////////////////////////////////////////////////////////////////////
 
-const MAX_LIMIT: i64 = 25;
+#[derive(Debug, Deserialize)]
+pub struct UpdateRequest {
+    id: db::rtc::Id,
+    #[serde(default)]
+    record: Option<bool>,
+    #[serde(default)]
+    tags: Option<JsonValue>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateFields {
+    #[serde(default)]
+    record: Option<bool>,
+    #[serde(default)]
+    tags: Option<JsonValue>,
+}
+
+pub async fn update(
+    Extension(ctx): Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(rtc_id): Path<db::rtc::Id>,
+    Json(request): Json<UpdateFields>,
+) -> RequestResult {
+    tracing::Span::current().record("rtc_id", tracing::field::display(rtc_id));
+
+    let request = UpdateRequest {
+        id: rtc_id,
+        record: request.record,
+        tags: request.tags,
+    };
+    http::handle::<UpdateHandler>(&ctx, &agent_id, request).await
+}
+
+pub struct UpdateHandler;
+
+#[async_trait]
+impl RequestHandler for UpdateHandler {
+    type Payload = UpdateRequest;
+    const ERROR_TITLE: &'static str = "Failed to update rtc";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::CreatorOrHost);
+
+    #[instrument(skip(context, payload, reqp), fields(rtc_id = %payload.id))]
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let room = {
+            let mut conn = context.get_conn().await?;
+            helpers::find_room_by_rtc_id(
+                payload.id,
+                helpers::RoomTimeRequirement::Open,
+                helpers::AudienceScope::caller(context, &reqp),
+                &mut conn,
+            )
+            .await?
+        };
+
+        tracing::Span::current().record("room_id", tracing::field::display(room.id()));
+
+        if payload.record.is_none() && payload.tags.is_none() {
+            return Err(anyhow!("Nothing to update")).error(AppErrorKind::InvalidPayload);
+        }
+
+        if let Some(tags) = &payload.tags {
+            validate_tags(tags)?;
+        }
+
+        let rtc = {
+            let mut conn = context.get_conn().await?;
+
+            db::rtc::FindQuery::new(payload.id)
+                .execute(&mut conn)
+                .await?
+                .context("RTC not found")
+                .error(AppErrorKind::RtcNotFound)?
+        };
+
+        // One labels own rtcs while recording is up to the host.
+        let authz_time = if payload.record.is_none() && rtc.created_by() == reqp.as_agent_id() {
+            None
+        } else {
+            helpers::authorize_host(context, &room, reqp).await?
+        };
+
+        // Recording takes effect on the next stream published to the rtc.
+        let rtc = {
+            let mut conn = context.get_conn().await?;
+
+            db::rtc::UpdateQuery::new(payload.id)
+                .record(payload.record)
+                .tags(payload.tags.as_ref())
+                .execute(&mut conn)
+                .await?
+                .context("RTC not found")
+                .error(AppErrorKind::RtcNotFound)?
+        };
+
+        let mut response = Response::new(
+            ResponseStatus::OK,
+            rtc.clone(),
+            context.start_timestamp(),
+            authz_time,
+        );
+
+        response.add_notification(
+            "rtc.update",
+            &format!("rooms/{}/events", room.id()),
+            rtc,
+            context.start_timestamp(),
+        );
+
+        Ok(response)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+const MAX_LIMIT: i64 = helpers::LIST_MAX_LIMIT;
 
 #[derive(Debug, Deserialize)]
 pub struct ListRequest {
-    room_id: db::room::Id,
+    #[serde(default)]
+    room_id: Option<db::room::Id>,
+    /// Addresses the classroom's room when `room_id` is missing.
+    #[serde(default)]
+    classroom_id: Option<Uuid>,
+    #[serde(default)]
+    kind: Option<RtcKind>,
     offset: Option<i64>,
     limit: Option<i64>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct ListParams {
+    kind: Option<RtcKind>,
     offset: Option<i64>,
     limit: Option<i64>,
 }
@@ -293,24 +514,21 @@ pub async fn list(
 
     let request = match query {
         Some(x) => ListRequest {
-            room_id,
+            room_id: Some(room_id),
+            classroom_id: None,
+            kind: x.kind,
             offset: x.offset,
             limit: x.limit,
         },
         None => ListRequest {
-            room_id,
+            room_id: Some(room_id),
+            classroom_id: None,
+            kind: None,
             offset: None,
             limit: None,
         },
     };
-    ListHandler::handle(
-        &mut ctx.start_message(),
-        request,
-        RequestParams::Http {
-            agent_id: &agent_id,
-        },
-    )
-    .await
+    http::handle::<ListHandler>(&ctx, &agent_id, request).await
 }
 
 pub struct ListHandler;
@@ -319,17 +537,24 @@ pub struct ListHandler;
 impl RequestHandler for ListHandler {
     type Payload = ListRequest;
     const ERROR_TITLE: &'static str = "Failed to list rtcs";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::Object {
+        object: &["classrooms", "{classroom_id}", "rtcs"],
+        actions: &["list"],
+    });
 
-    #[instrument(skip(context, payload, reqp), fields(room_id = %payload.room_id))]
+    #[instrument(skip(context, payload, reqp), fields(room_id = ?payload.room_id))]
     async fn handle<C: Context + Send + Sync>(
         context: &mut C,
         payload: Self::Payload,
         reqp: RequestParams<'_>,
     ) -> RequestResult {
+        let room_ref = helpers::RoomRef::new(payload.room_id, payload.classroom_id)?;
+
         let mut conn = context.get_conn().await?;
-        let room = helpers::find_room_by_id(
-            payload.room_id,
+        let room = helpers::find_room_by_ref(
+            room_ref,
             helpers::RoomTimeRequirement::Open,
+            helpers::AudienceScope::caller(context, &reqp),
             &mut conn,
         )
         .await?;
@@ -351,7 +576,11 @@ impl RequestHandler for ListHandler {
 
         // Return rtc list.
         let mut conn = context.get_conn().await?;
-        let mut query = db::rtc::ListQuery::new().room_id(payload.room_id);
+        let mut query = db::rtc::ListQuery::new().room_id(room.id());
+
+        if let Some(kind) = payload.kind {
+            query = query.kind(kind);
+        }
 
         if let Some(offset) = payload.offset {
             query = query.offset(offset);
@@ -386,6 +615,15 @@ pub enum ConnectIntent {
     Write,
 }
 
+impl ConnectIntent {
+    fn media_action(self) -> helpers::MediaAction {
+        match self {
+            Self::Read => helpers::MediaAction::Listen,
+            Self::Write => helpers::MediaAction::Publish,
+        }
+    }
+}
+
 impl fmt::Display for ConnectIntent {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -453,6 +691,32 @@ pub async fn connect_and_signal(
     ))
 }
 
+/// Connects the agent and negotiates its offer, see `whip::publish` and `whep::play`.
+pub(crate) async fn connect_with_offer<C: Context>(
+    ctx: &mut C,
+    rtc_id: db::rtc::Id,
+    intent: ConnectIntent,
+    agent_id: AgentId,
+    offer: String,
+    label: Option<String>,
+) -> Result<(HandleId, Option<JsonValue>), AppError> {
+    let result = ConnectAndSignal {
+        ctx,
+        rtc_id,
+        intent,
+        agent_id,
+        jsep: JsonSdp {
+            kind: JsepType::Offer,
+            sdp: offer,
+        },
+        label,
+    }
+    .run()
+    .await?;
+
+    Ok((result.handle_id, result.jsep))
+}
+
 struct ConnectAndSignal<'a, C> {
     ctx: &'a mut C,
     rtc_id: db::rtc::Id,
@@ -469,24 +733,16 @@ where
     async fn authz(&self, room: &db::room::Object) -> Result<(), AppError> {
         let rtc_id = self.rtc_id.to_string();
         let classroom_id = room.classroom_id().to_string();
-        let object = AuthzObject::new(&["classrooms", &classroom_id, "rtcs", &rtc_id]).into();
-
-        let action = match self.intent {
-            ConnectIntent::Read => "read",
-            ConnectIntent::Write => "update",
-        };
-
-        let authz_time = self
-            .ctx
-            .authz()
-            .authorize(
-                room.audience().into(),
-                self.agent_id.clone(),
-                object,
-                action.into(),
-            )
-            .await?;
-        self.ctx.metrics().observe_auth(authz_time);
+        let object = AuthzObject::new(&["classrooms", &classroom_id, "rtcs", &rtc_id]);
+
+        helpers::authorize_media(
+            &*self.ctx,
+            room,
+            self.agent_id.clone(),
+            object,
+            self.intent.media_action(),
+        )
+        .await?;
 
         Ok(())
     }
@@ -523,6 +779,49 @@ where
         Ok(())
     }
 
+    /// Starts the room composite on the backend of the first writer.
+    /// Janus failures are only logged so that they don't break publishing:
+    /// no row is stored in that case and the next writer retries.
+    async fn start_composite(
+        &self,
+        room: &db::room::Object,
+        backend: &db::janus_backend::Object,
+    ) -> Result<(), AppError> {
+        {
+            let mut conn = self.ctx.get_conn().await?;
+
+            if db::composite_recording::find(room.id(), &mut conn)
+                .await?
+                .is_some()
+            {
+                return Ok(());
+            }
+        }
+
+        let request = StartCompositeRequest {
+            session_id: backend.session_id(),
+            handle_id: backend.handle_id(),
+            body: StartCompositeRequestBody::new(room.id()),
+        };
+
+        let result = self
+            .ctx
+            .janus_clients()
+            .get_or_insert(backend)
+            .error(AppErrorKind::BackendClientCreationFailed)?
+            .start_composite(request)
+            .await;
+
+        if let Err(err) = result {
+            warn!(?err, room_id = %room.id(), "Failed to start composite recording");
+            return Ok(());
+        }
+
+        let mut conn = self.ctx.get_conn().await?;
+        db::composite_recording::insert(room.id(), backend.id(), &mut conn).await?;
+        Ok(())
+    }
+
     async fn fetch_reader_writer_configs(
         &self,
         handle_id: &HandleId,
@@ -534,6 +833,9 @@ where
         AppError,
     > {
         let mut conn = self.ctx.get_conn().await?;
+        db::rtc_reader_config::apply_room_defaults(handle_id.rtc_id(), &mut conn).await?;
+        // Readers who have chosen their set before the rtc appeared don't get its video.
+        db::rtc_reader_config::apply_subscribe_sets(handle_id.rtc_id(), &mut conn).await?;
         let reader_config =
             db::rtc_reader_config::read_config(handle_id.rtc_id(), &mut conn).await?;
         let writer_config =
@@ -546,8 +848,13 @@ where
         let payload_id = self.rtc_id;
         let room = {
             let mut conn = self.ctx.get_conn().await?;
-            helpers::find_room_by_rtc_id(payload_id, helpers::RoomTimeRequirement::Open, &mut conn)
-                .await?
+            helpers::find_room_by_rtc_id(
+                payload_id,
+                helpers::RoomTimeRequirement::Open,
+                helpers::AudienceScope::caller(&*self.ctx, &self.agent_id),
+                &mut conn,
+            )
+            .await?
         };
 
         tracing::Span::current().record("rtc_id", &tracing::field::display(self.rtc_id));
@@ -560,7 +867,12 @@ where
         tokio::try_join!(self.check_room_policy(&room), self.authz(&room))?;
 
         let mut conn = self.ctx.get_conn().await?;
+        helpers::check_ban(&room, &self.agent_id, &mut conn).await?;
+        helpers::check_admission(&room, &self.agent_id, &mut conn).await?;
+        helpers::check_room_capacity(&room, &self.agent_id, &mut conn).await?;
+
         let group = self.ctx.config().janus_group.clone();
+        let rtt_bucket = helpers::rtt_bucket(self.ctx, &room);
         // There are 4 cases:
         // 1. Connecting as a writer for a webinar for the first time. There's no `backend_id` in that case.
         //    Select the most loaded backend that is capable to host the room's reservation.
@@ -580,11 +892,11 @@ where
                     .context("No backend found for stream")
                     .error(AppErrorKind::BackendNotFound)?,
                 None if group.as_deref() == Some("minigroup") => {
-                    let b = db::janus_backend::least_loaded(room.id(), group.as_deref(), &mut conn).await.transpose();
+                    let b = db::janus_backend::least_loaded(room.id(), group.as_deref(), rtt_bucket, &mut conn).await.transpose();
                     let b = match b {
                         Some(b) => Some(b),
                         None => {
-                            db::janus_backend::most_loaded(room.id(), group.as_deref(), &mut conn).await.transpose()
+                            db::janus_backend::most_loaded(room.id(), group.as_deref(), rtt_bucket, &mut conn).await.transpose()
                         }
                     };
 
@@ -592,9 +904,9 @@ where
                         .context("No available backends")
                         .error(AppErrorKind::NoAvailableBackends)??
                 }
-                None => match db::janus_backend::most_loaded(room.id(), group.as_deref(), &mut conn).await? {
+                None => match db::janus_backend::most_loaded(room.id(), group.as_deref(), rtt_bucket, &mut conn).await? {
                     Some(backend) => backend,
-                    None => db::janus_backend::least_loaded(room.id(), group.as_deref(), &mut conn).await?
+                    None => db::janus_backend::least_loaded(room.id(), group.as_deref(), rtt_bucket, &mut conn).await?
                         .map(|backend| {
                             use sentry::protocol::{value::Value, Event, Level};
                             let backend_id = backend.id().to_string();
@@ -644,6 +956,16 @@ where
             }
         }
 
+        self.ctx
+            .janus_clients()
+            .throttle(room.id(), backend.id())
+            .await?;
+
+        let _handle_permit = self
+            .ctx
+            .janus_clients()
+            .reserve_handle(room.id(), backend.id())?;
+
         let rtc_stream_id = db::janus_rtc_stream::Id::random();
 
         let handle = self
@@ -665,6 +987,7 @@ where
         let agent_id = self.agent_id.clone();
         let handle_id = handle.id;
         let room_id = room.id();
+        let connected_at = self.ctx.start_timestamp();
 
         let mut conn = self.ctx.get_conn().await?;
         conn.transaction(|conn| {
@@ -681,6 +1004,7 @@ where
                 if let Some(agent) = maybe_agent.first() {
                     // Create agent connection in the DB.
                     agent_connection::UpsertQuery::new(agent.id(), payload_id, handle_id)
+                        .created_at(connected_at)
                         .execute(conn)
                         .await?;
 
@@ -703,10 +1027,13 @@ where
 
         let current_span = Span::current();
         current_span.record("sdp_type", "offer");
+        negotiation::check_sdp_size(&self.jsep.sdp, self.ctx.config().max_sdp_size)?;
+
         let is_recvonly = endpoint::rtc_signal::is_sdp_recvonly(self.jsep.sdp.as_str())
             .context("Invalid JSEP format")
             .error(AppErrorKind::InvalidJsepFormat)?;
 
+        let data_channel = sdp::has_data_channel(&self.jsep.sdp);
         let jsep = Jsep::OfferOrAnswer(self.jsep.clone());
 
         let answer = if is_recvonly {
@@ -726,32 +1053,34 @@ where
                             receive_video: r.receive_video(),
                         })
                         .collect(),
-                ),
+                )
+                .data_channel(data_channel),
                 handle_id: handle_id.janus_handle_id(),
                 session_id: handle_id.janus_session_id(),
                 jsep,
             };
 
-            let handle = self
+            let client = self
+                .ctx
+                .janus_clients()
+                .get_or_insert(&backend)
+                .error(AppErrorKind::BackendClientCreationFailed)?;
+            let correlation = self
                 .ctx
                 .janus_clients()
-                .stream_waitlist()
-                .register()
-                .error(AppErrorKind::JanusResponseTimeout)?;
+                .correlations()
+                .create(correlation::Path::Http);
 
             let transaction = ReadStreamTransaction::Http {
-                id: handle.id(),
+                id: correlation.id(),
                 replica_addr: self.ctx.janus_clients().own_ip_addr(),
             };
-            self.ctx
-                .janus_clients()
-                .get_or_insert(&backend)
-                .error(AppErrorKind::BackendClientCreationFailed)?
-                .read_stream(request, transaction)
+            let pending = correlation
+                .send(client.read_stream(request, transaction))
                 .await
-                .error(AppErrorKind::BackendRequestFailed)?;
+                .map_err(|err| negotiation::sdp_request_error(err, &self.jsep.sdp))?;
 
-            let resp = handle
+            let resp = pending
                 .wait(self.ctx.config().waitlist_timeout)
                 .await
                 .error(AppErrorKind::JanusResponseTimeout)??;
@@ -760,6 +1089,20 @@ where
         } else {
             // We've checked that the RTC is owned by the same agent earlier.
 
+            let (audio_processing, record) = {
+                let mut conn = self.ctx.get_conn().await?;
+                let audio_processing =
+                    helpers::audio_processing(&room, backend.id(), &mut conn).await?;
+
+                let rtc = db::rtc::FindQuery::new(handle_id.rtc_id())
+                    .execute(&mut conn)
+                    .await?
+                    .context("RTC not found")
+                    .error(AppErrorKind::RtcNotFound)?;
+
+                (audio_processing, rtc.record())
+            };
+
             let (_, (writer_config, reader_config)) = tokio::try_join!(
                 start_rtc_stream(self.ctx, &handle_id, &self.agent_id, &self.label, &room),
                 self.fetch_reader_writer_configs(&handle_id),
@@ -774,6 +1117,8 @@ where
                         send_video: w.send_video(),
                         send_audio: w.send_audio(),
                         video_remb: w.video_remb(),
+                        max_video_bitrate: w.max_video_bitrate(),
+                        max_audio_bitrate: w.max_audio_bitrate(),
                     }),
                     reader_config
                         .into_iter()
@@ -783,32 +1128,36 @@ where
                             receive_video: r.receive_video(),
                         })
                         .collect(),
-                ),
+                )
+                .audio_processing(audio_processing)
+                .record(record)
+                .data_channel(data_channel),
                 handle_id: handle_id.janus_handle_id(),
                 session_id: handle_id.janus_session_id(),
                 jsep,
             };
 
-            let handle = self
+            let client = self
                 .ctx
                 .janus_clients()
-                .stream_waitlist()
-                .register()
-                .error(AppErrorKind::JanusResponseTimeout)?;
+                .get_or_insert(&backend)
+                .error(AppErrorKind::BackendClientCreationFailed)?;
+            let correlation = self
+                .ctx
+                .janus_clients()
+                .correlations()
+                .create(correlation::Path::Http);
 
             let transaction = CreateStreamTransaction::Http {
-                id: handle.id(),
+                id: correlation.id(),
                 replica_addr: self.ctx.janus_clients().own_ip_addr(),
             };
-            self.ctx
-                .janus_clients()
-                .get_or_insert(&backend)
-                .error(AppErrorKind::BackendClientCreationFailed)?
-                .create_stream(request, transaction)
+            let pending = correlation
+                .send(client.create_stream(request, transaction))
                 .await
-                .error(AppErrorKind::BackendRequestFailed)?;
+                .map_err(|err| negotiation::sdp_request_error(err, &self.jsep.sdp))?;
 
-            let resp = handle
+            let resp = pending
                 .wait(self.ctx.config().waitlist_timeout)
                 .await
                 .error(AppErrorKind::JanusResponseTimeout)??;
@@ -825,9 +1174,15 @@ where
 
             conn.transaction::<_, _, AppError>(|conn| {
                 Box::pin(async move {
+                    // Rtcs excluded from recording get no entry for vacuum to wait for.
+                    let record = db::rtc::FindQuery::new(id)
+                        .execute(conn)
+                        .await?
+                        .is_some_and(|rtc| rtc.record());
+
                     let recording = db::recording::FindQuery::new(id).execute(conn).await?;
 
-                    if recording.is_none() {
+                    if record && recording.is_none() {
                         db::recording::InsertQuery::new(id).execute(conn).await?;
                     }
 
@@ -835,6 +1190,10 @@ where
                 })
             })
             .await?;
+
+            if room.composite_recording() {
+                self.start_composite(&room, &backend).await?;
+            }
         }
 
         self.ctx
@@ -876,14 +1235,7 @@ pub async fn connect(
         .map(|label| AgentId::new(label, agent_id.as_account_id().to_owned()))
         .unwrap_or(agent_id);
 
-    ConnectHandler::handle(
-        &mut ctx.start_message(),
-        request,
-        RequestParams::Http {
-            agent_id: &agent_id,
-        },
-    )
-    .await
+    http::handle::<ConnectHandler>(&ctx, &agent_id, request).await
 }
 
 pub struct ConnectHandler;
@@ -892,6 +1244,10 @@ pub struct ConnectHandler;
 impl RequestHandler for ConnectHandler {
     type Payload = ConnectRequest;
     const ERROR_TITLE: &'static str = "Failed to connect to rtc";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::Object {
+        object: &["classrooms", "{classroom_id}", "rtcs", "{rtc_id}"],
+        actions: &["read", "update", "listen", "publish"],
+    });
 
     #[instrument(skip(context, payload, reqp), fields(
         rtc_id = %payload.id,
@@ -904,8 +1260,13 @@ impl RequestHandler for ConnectHandler {
     ) -> RequestResult {
         let room = {
             let mut conn = context.get_conn().await?;
-            helpers::find_room_by_rtc_id(payload.id, helpers::RoomTimeRequirement::Open, &mut conn)
-                .await?
+            helpers::find_room_by_rtc_id(
+                payload.id,
+                helpers::RoomTimeRequirement::Open,
+                helpers::AudienceScope::caller(context, &reqp),
+                &mut conn,
+            )
+            .await?
         };
 
         tracing::Span::current().record("room_id", &tracing::field::display(room.id()));
@@ -944,23 +1305,59 @@ impl RequestHandler for ConnectHandler {
 
         let rtc_id = payload.id.to_string();
         let classroom_id = room.classroom_id().to_string();
-        let object = AuthzObject::new(&["classrooms", &classroom_id, "rtcs", &rtc_id]).into();
+        let object = AuthzObject::new(&["classrooms", &classroom_id, "rtcs", &rtc_id]);
 
-        let action = match payload.intent {
-            ConnectIntent::Read => "read",
-            ConnectIntent::Write => "update",
+        let class = match payload.intent {
+            ConnectIntent::Read => room_authz::ActionClass::Read,
+            ConnectIntent::Write => room_authz::ActionClass::Update,
         };
 
-        let authz_time = context
-            .authz()
-            .authorize(room.audience().into(), reqp, object, action.into())
+        // Agents that have just entered the room are authorized already unless the audience
+        // authorizes listening and publishing on their own.
+        if helpers::is_fine_grained_rtc_authz(&*context, &room) {
+            helpers::authorize_media(
+                &*context,
+                &room,
+                reqp,
+                object,
+                payload.intent.media_action(),
+            )
             .await?;
-        context.metrics().observe_auth(authz_time);
+        } else {
+            helpers::authorize_in_room(&*context, &room, reqp, object, class).await?;
+        }
         let room_id = room.id();
 
+        {
+            let mut conn = context.get_conn().await?;
+            helpers::check_ban(&room, reqp.as_agent_id(), &mut conn).await?;
+            helpers::check_connection_limit(context, &room, reqp.as_agent_id(), &mut conn).await?;
+            helpers::check_admission(&room, reqp.as_agent_id(), &mut conn).await?;
+            helpers::check_room_capacity(&room, reqp.as_agent_id(), &mut conn).await?;
+        }
+
+        // A repeated connect gets the handle of the pending one instead of creating another.
+        // If that one fails, the repeated connect goes on by itself.
+        let connect = loop {
+            match context
+                .janus_clients()
+                .connects()
+                .start(reqp.as_agent_id(), payload.id)
+            {
+                Connect::First(guard) => break guard,
+                Connect::Repeated(pending) => {
+                    if let Some(handle_id) = pending.wait().await {
+                        info!(%handle_id, "Coalesced repeated rtc.connect");
+                        return Ok(connect_response(context, handle_id));
+                    }
+                }
+            }
+        };
+
         // Choose backend to connect.
         let mut conn = context.get_conn().await?;
         let group = context.config().janus_group.clone();
+        let rtt_bucket = helpers::rtt_bucket(context, &room);
         // There are 4 cases:
         // 1. Connecting as a writer for a webinar for the first time. There's no `backend_id` in that case.
         //    Select the most loaded backend that is capable to host the room's reservation.
@@ -980,11 +1377,11 @@ impl RequestHandler for ConnectHandler {
                     .context("No backend found for stream")
                     .error(AppErrorKind::BackendNotFound)?,
                 None if group.as_deref() == Some("minigroup") => {
-                    let b = db::janus_backend::least_loaded(room.id(), group.as_deref(), &mut conn).await.transpose();
+                    let b = db::janus_backend::least_loaded(room.id(), group.as_deref(), rtt_bucket, &mut conn).await.transpose();
                     let b = match b {
                         Some(b) => Some(b),
                         None => {
-                            db::janus_backend::most_loaded(room.id(), group.as_deref(), &mut conn).await.transpose()
+                            db::janus_backend::most_loaded(room.id(), group.as_deref(), rtt_bucket, &mut conn).await.transpose()
                         }
                     };
 
@@ -992,9 +1389,9 @@ impl RequestHandler for ConnectHandler {
                         .context("No available backends")
                         .error(AppErrorKind::NoAvailableBackends)??
                 }
-                None => match db::janus_backend::most_loaded(room.id(), group.as_deref(), &mut conn).await? {
+                None => match db::janus_backend::most_loaded(room.id(), group.as_deref(), rtt_bucket, &mut conn).await? {
                     Some(backend) => backend,
-                    None => db::janus_backend::least_loaded(room.id(), group.as_deref(), &mut conn).await?
+                    None => db::janus_backend::least_loaded(room.id(), group.as_deref(), rtt_bucket, &mut conn).await?
                         .map(|backend| {
                             use sentry::protocol::{value::Value, Event, Level};
                             let backend_id = backend.id().to_string();
@@ -1044,6 +1441,15 @@ impl RequestHandler for ConnectHandler {
             }
         }
 
+        context
+            .janus_clients()
+            .throttle(room_id, backend.id())
+            .await?;
+
+        let _handle_permit = context
+            .janus_clients()
+            .reserve_handle(room_id, backend.id())?;
+
         let rtc_stream_id = db::janus_rtc_stream::Id::random();
 
         let handle = context
@@ -1063,6 +1469,7 @@ impl RequestHandler for ConnectHandler {
 
         let agent_id = reqp.as_agent_id().clone();
         let handle_id = handle.id;
+        let connected_at = context.start_timestamp();
 
         let mut conn = context.get_conn().await?;
         let payload_id = payload.id;
@@ -1080,6 +1487,7 @@ impl RequestHandler for ConnectHandler {
                 if let Some(agent) = maybe_agent.first() {
                     // Create agent connection in the DB.
                     agent_connection::UpsertQuery::new(agent.id(), payload_id, handle_id)
+                        .created_at(connected_at)
                         .execute(conn)
                         .await?;
 
@@ -1093,28 +1501,634 @@ impl RequestHandler for ConnectHandler {
         .await?;
 
         // Returning Real-Time connection handle
-        let resp = Response::new(
-            ResponseStatus::OK,
-            endpoint::rtc::ConnectResponseData::new(HandleId::new(
-                rtc_stream_id,
-                payload.id,
-                handle.id,
-                backend.session_id(),
-                backend.id().clone(),
-            )),
-            context.start_timestamp(),
-            None,
+        let handle_id = HandleId::new(
+            rtc_stream_id,
+            payload.id,
+            handle.id,
+            backend.session_id(),
+            backend.id().clone(),
         );
-        context
-            .metrics()
-            .request_duration
-            .rtc_connect
-            .observe_timestamp(context.start_timestamp());
 
-        Ok(resp)
+        connect.complete(&handle_id);
+        Ok(connect_response(context, handle_id))
     }
 }
 
+fn connect_response<C: Context>(context: &C, handle_id: HandleId) -> Response {
+    context
+        .metrics()
+        .request_duration
+        .rtc_connect
+        .observe_timestamp(context.start_timestamp());
+
+    Response::new(
+        ResponseStatus::OK,
+        ConnectResponseData::new(handle_id),
+        context.start_timestamp(),
+        None,
+    )
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Backend load relative to its capacity at which it's considered medium and high.
+const MEDIUM_LOAD_RATIO: f64 = 0.5;
+const HIGH_LOAD_RATIO: f64 = 0.8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LoadClass {
+    Low,
+    Medium,
+    High,
+}
+
+impl LoadClass {
+    fn new(load: i64, capacity: Option<i32>) -> Self {
+        let capacity = match capacity {
+            Some(capacity) if capacity > 0 => capacity,
+            Some(_) => return Self::High,
+            None => return Self::Low,
+        };
+
+        let ratio = load as f64 / capacity as f64;
+
+        if ratio >= HIGH_LOAD_RATIO {
+            Self::High
+        } else if ratio >= MEDIUM_LOAD_RATIO {
+            Self::Medium
+        } else {
+            Self::Low
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PreflightRequest {
+    id: db::rtc::Id,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PreflightResponseData {
+    region: Option<String>,
+    load_class: LoadClass,
+    ice_servers: Vec<IceServer>,
+    codecs: Vec<String>,
+}
+
+pub async fn preflight(
+    Extension(ctx): Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(rtc_id): Path<db::rtc::Id>,
+) -> RequestResult {
+    tracing::Span::current().record("rtc_id", tracing::field::display(rtc_id));
+
+    http::handle::<PreflightHandler>(&ctx, &agent_id, PreflightRequest { id: rtc_id }).await
+}
+
+pub struct PreflightHandler;
+
+#[async_trait]
+impl RequestHandler for PreflightHandler {
+    type Payload = PreflightRequest;
+    const ERROR_TITLE: &'static str = "Failed to preflight rtc";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::Object {
+        object: &["classrooms", "{classroom_id}", "rtcs", "{rtc_id}"],
+        actions: &["read"],
+    });
+
+    #[instrument(skip(context, payload, reqp), fields(rtc_id = %payload.id))]
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let room = {
+            let mut conn = context.get_conn().await?;
+            helpers::find_room_by_rtc_id(
+                payload.id,
+                helpers::RoomTimeRequirement::Open,
+                helpers::AudienceScope::caller(context, &reqp),
+                &mut conn,
+            )
+            .await?
+        };
+
+        tracing::Span::current().record("room_id", tracing::field::display(room.id()));
+        tracing::Span::current()
+            .record("classroom_id", tracing::field::display(room.classroom_id()));
+
+        if room.rtc_sharing_policy() == RtcSharingPolicy::None {
+            return Err(anyhow!(
+                "'rtc.connect' is not implemented for rtc_sharing_policy = '{}'",
+                room.rtc_sharing_policy(),
+            ))
+            .error(AppErrorKind::NotImplemented);
+        }
+
+        let rtc_id = payload.id.to_string();
+        let classroom_id = room.classroom_id().to_string();
+        let object = AuthzObject::new(&["classrooms", &classroom_id, "rtcs", &rtc_id]).into();
+
+        let authz_time = context
+            .authz()
+            .authorize(room.audience().into(), reqp, object, "read".into())
+            .await?;
+        context.metrics().observe_auth(authz_time);
+
+        // Pick the backend the same way `rtc.connect` does but neither bind it to the room
+        // nor create a handle on it.
+        let mut conn = context.get_conn().await?;
+        let group = context.config().janus_group.clone();
+        let rtt_bucket = helpers::rtt_bucket(context, &room);
+
+        let backend = match room.backend_id() {
+            Some(backend_id) => db::janus_backend::FindQuery::new(backend_id)
+                .execute(&mut conn)
+                .await?
+                .context("No backend found for stream")
+                .error(AppErrorKind::BackendNotFound)?,
+            None => {
+                let backend = if group.as_deref() == Some("minigroup") {
+                    match db::janus_backend::least_loaded(
+                        room.id(),
+                        group.as_deref(),
+                        rtt_bucket,
+                        &mut conn,
+                    )
+                    .await?
+                    {
+                        Some(backend) => Some(backend),
+                        None => {
+                            db::janus_backend::most_loaded(
+                                room.id(),
+                                group.as_deref(),
+                                rtt_bucket,
+                                &mut conn,
+                            )
+                            .await?
+                        }
+                    }
+                } else {
+                    match db::janus_backend::most_loaded(
+                        room.id(),
+                        group.as_deref(),
+                        rtt_bucket,
+                        &mut conn,
+                    )
+                    .await?
+                    {
+                        Some(backend) => Some(backend),
+                        None => {
+                            db::janus_backend::least_loaded(
+                                room.id(),
+                                group.as_deref(),
+                                rtt_bucket,
+                                &mut conn,
+                            )
+                            .await?
+                        }
+                    }
+                };
+
+                backend
+                    .context("No available backends")
+                    .error(AppErrorKind::NoAvailableBackends)?
+            }
+        };
+
+        let load = db::janus_backend::load(backend.id(), &mut conn).await?;
+        let capacity = backend.balancer_capacity.or(backend.capacity);
+        let config = &context.config().preflight;
+
+        let data = PreflightResponseData {
+            region: config
+                .regions
+                .get(backend.group().unwrap_or("default"))
+                .cloned(),
+            load_class: LoadClass::new(load, capacity),
+            ice_servers: config.ice_servers.clone(),
+            codecs: config.codecs.clone(),
+        };
+
+        Ok(Response::new(
+            ResponseStatus::OK,
+            data,
+            context.start_timestamp(),
+            None,
+        ))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct ConnectExplainRequest {
+    id: db::rtc::Id,
+}
+
+/// Which balancing query picks the backend, see `ConnectHandler`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BalancerStrategy {
+    /// The room is already bound to a backend.
+    Bound,
+    MostLoaded,
+    LeastLoaded,
+    /// No backend fits the room's reserve so the least loaded one is taken anyway.
+    LeastLoadedFallback,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExclusionReason {
+    /// Free capacity is less than the room's reserve.
+    Full,
+    WrongGroup,
+    ApiVersionMismatch,
+    /// The recordings disk is full and the room records.
+    DiskFull,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ConnectCandidate {
+    backend_id: AgentId,
+    group: Option<String>,
+    capacity: Option<i32>,
+    load: f64,
+    /// Ordering key of the strategy, the highest one wins.
+    score: f64,
+    excluded: Vec<ExclusionReason>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ConnectExplainResponseData {
+    strategy: BalancerStrategy,
+    selected: Option<AgentId>,
+    candidates: Vec<ConnectCandidate>,
+}
+
+/// Replays the backend selection of `rtc.connect` over the given backends. Ties are broken at
+/// random by the balancer and the least loaded one is picked among the top three so the actual
+/// choice may differ from `selected` among the equally fitting candidates.
+fn explain_connect(
+    room: &db::room::Object,
+    group: Option<&str>,
+    backends: Vec<db::janus_backend::BalancerCandidate>,
+) -> ConnectExplainResponseData {
+    let reserve = room.reserve().unwrap_or(1) as f64;
+    let records = room.record_rtcs() || room.composite_recording();
+    let minigroup = group == Some("minigroup");
+
+    let excluded = backends
+        .iter()
+        .map(|backend| {
+            let capacity = backend.capacity.unwrap_or(i32::MAX) as f64;
+            let mut reasons = vec![];
+
+            if backend.api_version != JANUS_API_VERSION {
+                reasons.push(ExclusionReason::ApiVersionMismatch);
+            }
+
+            if group.is_some() && backend.group.as_deref() != group {
+                reasons.push(ExclusionReason::WrongGroup);
+            }
+
+            if backend.disk_full && records {
+                reasons.push(ExclusionReason::DiskFull);
+            }
+
+            // Minigroups have a fixed size so the least loaded backend doesn't check the reserve.
+            if !minigroup && capacity - backend.load < reserve {
+                reasons.push(ExclusionReason::Full);
+            }
+
+            reasons
+        })
+        .collect::<Vec<_>>();
+
+    let strategy = if minigroup {
+        BalancerStrategy::LeastLoaded
+    } else if excluded.iter().any(|reasons| reasons.is_empty()) {
+        BalancerStrategy::MostLoaded
+    } else {
+        BalancerStrategy::LeastLoadedFallback
+    };
+
+    // The fallback takes the backends that don't fit the reserve.
+    let eligible = |reasons: &[ExclusionReason]| match strategy {
+        BalancerStrategy::LeastLoadedFallback => reasons
+            .iter()
+            .all(|reason| *reason == ExclusionReason::Full),
+        _ => reasons.is_empty(),
+    };
+
+    let mut candidates = backends
+        .into_iter()
+        .zip(excluded)
+        .map(|(backend, excluded)| {
+            let score = match strategy {
+                BalancerStrategy::MostLoaded | BalancerStrategy::Bound => backend.load,
+                BalancerStrategy::LeastLoaded | BalancerStrategy::LeastLoadedFallback => {
+                    backend.capacity.unwrap_or(i32::MAX) as f64 - backend.taken
+                }
+            };
+
+            ConnectCandidate {
+                backend_id: backend.id,
+                group: backend.group,
+                capacity: backend.capacity,
+                load: backend.load,
+                score,
+                excluded,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // Eligible candidates go first, the best scoring ones ahead.
+    candidates.sort_by(|a, b| {
+        eligible(&b.excluded)
+            .cmp(&eligible(&a.excluded))
+            .then(b.score.total_cmp(&a.score))
+    });
+
+    match room.backend_id() {
+        Some(backend_id) => ConnectExplainResponseData {
+            strategy: BalancerStrategy::Bound,
+            selected: Some(backend_id.to_owned()),
+            candidates,
+        },
+        None => ConnectExplainResponseData {
+            strategy,
+            selected: candidates
+                .first()
+                .filter(|candidate| eligible(&candidate.excluded))
+                .map(|candidate| candidate.backend_id.clone()),
+            candidates,
+        },
+    }
+}
+
+pub struct ConnectExplainHandler;
+
+#[async_trait]
+impl RequestHandler for ConnectExplainHandler {
+    type Payload = ConnectExplainRequest;
+    const ERROR_TITLE: &'static str = "Failed to explain rtc connect";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::SYSTEM_READ);
+
+    #[instrument(skip(context, payload, reqp), fields(rtc_id = %payload.id))]
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        // Authorization: only trusted subjects are allowed to perform operations with the system
+        let audience = context.agent_id().as_account_id().audience();
+
+        let authz_time = context
+            .authz()
+            .authorize(
+                audience.into(),
+                reqp,
+                AuthzObject::new(&["system"]).into(),
+                "read".into(),
+            )
+            .await?;
+        context.metrics().observe_auth(authz_time);
+
+        // Nothing gets allocated: the room stays unbound and no handle is created.
+        let mut conn = context.get_conn().await?;
+
+        let room = helpers::find_room_by_rtc_id(
+            payload.id,
+            helpers::RoomTimeRequirement::Open,
+            helpers::AudienceScope::caller(context, &reqp),
+            &mut conn,
+        )
+        .await?;
+
+        let backends = db::janus_backend::balancer_candidates(&mut conn).await?;
+        let group = context.config().janus_group.clone();
+        let data = explain_connect(&room, group.as_deref(), backends);
+
+        Ok(Response::new(
+            ResponseStatus::OK,
+            data,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Limit of RTCs one may receive video from by `rtc.subscribe_set`.
+const MAX_SUBSCRIBE_SET_LEN: usize = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct SubscribeSetRequest {
+    room_id: db::room::Id,
+    rtc_ids: Vec<db::rtc::Id>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SubscribeSetFields {
+    rtc_ids: Vec<db::rtc::Id>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SubscribeSetResponseData {
+    room_id: db::room::Id,
+    rtc_ids: Vec<db::rtc::Id>,
+    status: endpoint::agent_reader_config::ApplyStatus,
+}
+
+pub async fn subscribe_set(
+    Extension(ctx): Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<db::room::Id>,
+    Json(fields): Json<SubscribeSetFields>,
+) -> RequestResult {
+    tracing::Span::current().record("room_id", &tracing::field::display(room_id));
+
+    let request = SubscribeSetRequest {
+        room_id,
+        rtc_ids: fields.rtc_ids,
+    };
+    http::handle::<SubscribeSetHandler>(&ctx, &agent_id, request).await
+}
+
+pub struct SubscribeSetHandler;
+
+#[async_trait]
+impl RequestHandler for SubscribeSetHandler {
+    type Payload = SubscribeSetRequest;
+    const ERROR_TITLE: &'static str = "Failed to set rtc subscriptions";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::CLASSROOM_UPDATE);
+
+    #[instrument(skip(context, payload, reqp), fields(room_id = %payload.room_id))]
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        if payload.rtc_ids.len() > MAX_SUBSCRIBE_SET_LEN {
+            return Err(anyhow!("Too many items in `rtc_ids` list"))
+                .error(AppErrorKind::InvalidPayload)?;
+        }
+
+        let room = {
+            let mut conn = context.get_conn().await?;
+            let room = helpers::find_room_by_id(
+                payload.room_id,
+                helpers::RoomTimeRequirement::Open,
+                helpers::AudienceScope::caller(context, &reqp),
+                &mut conn,
+            )
+            .await?;
+
+            helpers::check_room_presence(&room, reqp.as_agent_id(), &mut conn).await?;
+
+            room
+        };
+
+        tracing::Span::current().record(
+            "classroom_id",
+            &tracing::field::display(room.classroom_id()),
+        );
+
+        if room.rtc_sharing_policy() != RtcSharingPolicy::Owned {
+            return Err(anyhow!(
+                "Subscribe set is available only for rooms with owned RTC sharing policy"
+            ))
+            .error(AppErrorKind::InvalidPayload)?;
+        }
+
+        // Authorize classrooms.update on the tenant the same way as reader configs.
+        let classroom_id = room.classroom_id().to_string();
+        let object = AuthzObject::new(&["classrooms", &classroom_id]).into();
+
+        let authz_time = context
+            .authz()
+            .authorize(room.audience().into(), reqp, object, "update".into())
+            .await?;
+        context.metrics().observe_auth(authz_time);
+
+        let mut conn = context.get_conn().await?;
+
+        let maybe_backend = match room.backend_id() {
+            None => None,
+            Some(backend_id) => {
+                db::janus_backend::FindQuery::new(backend_id)
+                    .execute(&mut conn)
+                    .await?
+            }
+        };
+
+        // Written as intents to be confirmed once Janus accepts them like
+        // `agent_reader_config.update` does.
+        let pending_since = context.start_timestamp().trunc_subsecs(6);
+        let reader_id = reqp.as_agent_id().clone();
+        let room_id = room.id();
+        let rtc_ids = payload.rtc_ids;
+
+        let (set, configs, room_rtcs_count) = conn
+            .transaction::<_, _, AppError>(|conn| {
+                Box::pin(async move {
+                    let room_rtc_ids = db::rtc::ListQuery::new()
+                        .room_id(room_id)
+                        .execute(conn)
+                        .await?
+                        .into_iter()
+                        .map(|rtc| rtc.id())
+                        .collect::<Vec<_>>();
+
+                    if let Some(rtc_id) = rtc_ids.iter().find(|id| !room_rtc_ids.contains(id)) {
+                        return Err(anyhow!("{} is not in the room", rtc_id))
+                            .error(AppErrorKind::InvalidPayload)?;
+                    }
+
+                    let set =
+                        db::rtc_subscribe_set::upsert(room_id, &reader_id, &rtc_ids, conn).await?;
+
+                    let configs = db::rtc_reader_config::apply_subscribe_set(
+                        room_id,
+                        &reader_id,
+                        pending_since,
+                        conn,
+                    )
+                    .await?;
+
+                    Ok((set, configs, room_rtc_ids.len()))
+                })
+            })
+            .await?;
+
+        let status = match maybe_backend {
+            Some(backend) if !configs.is_empty() => {
+                let items = configs
+                    .iter()
+                    .map(|config| UpdateReaderConfigRequestBodyConfigItem {
+                        reader_id: config.reader_id().to_owned(),
+                        stream_id: config.rtc_id(),
+                        receive_video: config.receive_video(),
+                        receive_audio: config.receive_audio(),
+                        spatial_layer: None,
+                        temporal_layer: None,
+                    })
+                    .collect::<Vec<_>>();
+
+                endpoint::agent_reader_config::push(context, &backend, items, room_rtcs_count)
+                    .await?
+            }
+            // Janus gets the configs from the database when the stream is read.
+            _ => ApplyStatus::Applied,
+        };
+
+        if status == ApplyStatus::Applied {
+            let rtc_ids = configs.iter().map(|c| c.rtc_id()).collect::<Vec<_>>();
+            let mut conn = context.get_conn().await?;
+
+            db::rtc_reader_config::confirm(reqp.as_agent_id(), &rtc_ids, pending_since, &mut conn)
+                .await?;
+        }
+
+        Ok(Response::new(
+            status.response_status(),
+            SubscribeSetResponseData {
+                room_id: set.room_id(),
+                rtc_ids: set.rtc_ids().to_owned(),
+                status,
+            },
+            context.start_timestamp(),
+            None,
+        ))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RenegotiateEventData {
+    rtc_ids: Vec<db::rtc::Id>,
+}
+
+pub type RenegotiateEvent = OutgoingMessage<RenegotiateEventData>;
+
+/// Asks agents of the room to connect to the RTCs again since their connections
+/// have been lost on the backend.
+pub fn renegotiate_event(
+    room_id: db::room::Id,
+    rtc_ids: Vec<db::rtc::Id>,
+    start_timestamp: DateTime<Utc>,
+) -> RenegotiateEvent {
+    let uri = format!("rooms/{room_id}/events");
+    let timing = ShortTermTimingProperties::until_now(start_timestamp);
+    let props = OutgoingEventProperties::new("rtc.renegotiate", timing);
+    OutgoingEvent::broadcast(RenegotiateEventData { rtc_ids }, props, &uri)
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 
 #[cfg(test)]
@@ -1145,7 +2159,11 @@ mod test {
 
             // Make rtc.create request.
             let mut context = TestContext::new(db, authz).await;
-            let payload = CreateRequest { room_id: room.id() };
+            let payload = CreateRequest {
+                room_id: room.id(),
+                tags: Some(serde_json::json!({ "label": "camera-front" })),
+                kind: None,
+            };
 
             let messages = handle_request::<CreateHandler>(&mut context, &agent, payload)
                 .await
@@ -1155,6 +2173,10 @@ mod test {
             let (rtc, respp, _) = find_response::<Rtc>(messages.as_slice());
             assert_eq!(respp.status(), ResponseStatus::CREATED);
             assert_eq!(rtc.room_id(), room.id());
+            assert_eq!(
+                rtc.tags(),
+                Some(&serde_json::json!({ "label": "camera-front" }))
+            );
 
             // Assert notification.
             let (rtc, evp, topic) = find_event::<Rtc>(messages.as_slice());
@@ -1163,6 +2185,37 @@ mod test {
             assert_eq!(rtc.room_id(), room.id());
         }
 
+        #[sqlx::test]
+        async fn create_rtc_with_invalid_tags(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+
+            let room = {
+                let mut conn = db.get_conn().await;
+                shared_helpers::insert_room(&mut conn).await
+            };
+
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut context = TestContext::new(db, TestAuthz::new()).await;
+
+            for tags in [
+                serde_json::json!(["camera-front"]),
+                serde_json::json!({ "label": "x".repeat(MAX_TAGS_SIZE) }),
+            ] {
+                let payload = CreateRequest {
+                    room_id: room.id(),
+                    tags: Some(tags),
+                    kind: None,
+                };
+
+                let err = handle_request::<CreateHandler>(&mut context, &agent, payload)
+                    .await
+                    .expect_err("Unexpected success on rtc creation");
+
+                assert_eq!(err.status(), ResponseStatus::BAD_REQUEST);
+                assert_eq!(err.kind(), "invalid_payload");
+            }
+        }
+
         #[sqlx::test]
         async fn create_rtc_missing_room(pool: sqlx::PgPool) {
             let db = TestDb::new(pool);
@@ -1171,6 +2224,8 @@ mod test {
             let mut context = TestContext::new(db, TestAuthz::new()).await;
             let payload = CreateRequest {
                 room_id: db::room::Id::random(),
+                tags: None,
+                kind: None,
             };
 
             let err = handle_request::<CreateHandler>(&mut context, &agent, payload)
@@ -1199,7 +2254,11 @@ mod test {
 
             // Make rtc.create request.
             let mut context = TestContext::new(db, authz).await;
-            let payload = CreateRequest { room_id: room.id() };
+            let payload = CreateRequest {
+                room_id: room.id(),
+                tags: None,
+                kind: None,
+            };
 
             let messages = handle_request::<CreateHandler>(&mut context, &agent, payload)
                 .await
@@ -1211,7 +2270,11 @@ mod test {
             assert_eq!(rtc.room_id(), room.id());
 
             // Make rtc.create request second time.
-            let payload = CreateRequest { room_id: room.id() };
+            let payload = CreateRequest {
+                room_id: room.id(),
+                tags: None,
+                kind: None,
+            };
             let err = handle_request::<CreateHandler>(&mut context, &agent, payload)
                 .await
                 .expect_err("Unexpected success on rtc creation");
@@ -1240,13 +2303,21 @@ mod test {
 
             // Make two rtc.create requests.
             let mut context = TestContext::new(db, authz).await;
-            let payload = CreateRequest { room_id: room.id() };
+            let payload = CreateRequest {
+                room_id: room.id(),
+                tags: None,
+                kind: None,
+            };
 
             let messages1 = handle_request::<CreateHandler>(&mut context, &agent1, payload)
                 .await
                 .expect("RTC creation failed");
 
-            let payload = CreateRequest { room_id: room.id() };
+            let payload = CreateRequest {
+                room_id: room.id(),
+                tags: None,
+                kind: None,
+            };
 
             let messages2 = handle_request::<CreateHandler>(&mut context, &agent2, payload)
                 .await
@@ -1281,7 +2352,11 @@ mod test {
 
             // Make the first rtc.create request.
             let mut context = TestContext::new(db, authz).await;
-            let payload = CreateRequest { room_id: room.id() };
+            let payload = CreateRequest {
+                room_id: room.id(),
+                tags: None,
+                kind: None,
+            };
 
             let messages = handle_request::<CreateHandler>(&mut context, &agent, payload)
                 .await
@@ -1294,7 +2369,11 @@ mod test {
             assert_eq!(rtc.created_by(), agent.agent_id());
 
             // Make the second rtc.create request and expect fail.
-            let payload = CreateRequest { room_id: room.id() };
+            let payload = CreateRequest {
+                room_id: room.id(),
+                tags: None,
+                kind: None,
+            };
 
             let err = handle_request::<CreateHandler>(&mut context, &agent, payload)
                 .await
@@ -1316,7 +2395,11 @@ mod test {
 
             // Make rtc.create request.
             let mut context = TestContext::new(db, TestAuthz::new()).await;
-            let payload = CreateRequest { room_id: room.id() };
+            let payload = CreateRequest {
+                room_id: room.id(),
+                tags: None,
+                kind: None,
+            };
 
             let err = handle_request::<CreateHandler>(&mut context, &agent, payload)
                 .await
@@ -1357,24 +2440,111 @@ mod test {
             let object = vec!["classrooms", &classroom_id, "rtcs", &rtc_id];
             authz.allow(agent.account_id(), object, "read");
 
-            // Make rtc.read request.
-            let mut context = TestContext::new(db, authz).await;
-            let payload = ReadRequest { id: rtc.id() };
+            // Make rtc.read request.
+            let mut context = TestContext::new(db, authz).await;
+            let payload = ReadRequest { id: rtc.id() };
+
+            let messages = handle_request::<ReadHandler>(&mut context, &agent, payload)
+                .await
+                .expect("RTC reading failed");
+
+            // Assert response.
+            let (resp_rtc, respp, _) = find_response::<Rtc>(messages.as_slice());
+            assert_eq!(respp.status(), ResponseStatus::OK);
+            assert_eq!(resp_rtc.room_id(), rtc.room_id());
+        }
+
+        #[sqlx::test]
+        async fn read_rtc_not_authorized(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+            let rtc = {
+                let mut conn = db.get_conn().await;
+                shared_helpers::insert_rtc(&mut conn).await
+            };
+
+            let mut context = TestContext::new(db, TestAuthz::new()).await;
+            let payload = ReadRequest { id: rtc.id() };
+
+            let err = handle_request::<ReadHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on rtc reading");
+
+            assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+            assert_eq!(err.kind(), "access_denied");
+        }
+
+        #[sqlx::test]
+        async fn read_rtc_missing(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let mut context = TestContext::new(db, TestAuthz::new()).await;
+            let payload = ReadRequest {
+                id: db::rtc::Id::random(),
+            };
+
+            let err = handle_request::<ReadHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on rtc reading");
+
+            assert_eq!(err.status(), ResponseStatus::NOT_FOUND);
+            assert_eq!(err.kind(), "room_not_found");
+        }
+    }
+
+    mod update {
+        use crate::{
+            db::rtc::Object as Rtc,
+            test_helpers::{db::TestDb, prelude::*},
+        };
+
+        use super::super::*;
+
+        #[sqlx::test]
+        async fn update_rtc_by_host(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+            let host = TestAgent::new("web", "host", USR_AUDIENCE);
+
+            let rtc = {
+                let mut conn = db.get_conn().await;
+                let room = shared_helpers::insert_room(&mut conn).await;
+
+                db::room_host::insert(room.id(), &[host.agent_id()], &mut conn)
+                    .await
+                    .expect("Failed to set room host");
 
-            let messages = handle_request::<ReadHandler>(&mut context, &agent, payload)
+                shared_helpers::insert_rtc_with_room(&mut conn, &room).await
+            };
+
+            assert!(rtc.record());
+
+            let mut context = TestContext::new(db, TestAuthz::new()).await;
+            let payload = UpdateRequest {
+                id: rtc.id(),
+                record: Some(false),
+                tags: None,
+            };
+
+            let messages = handle_request::<UpdateHandler>(&mut context, &host, payload)
                 .await
-                .expect("RTC reading failed");
+                .expect("RTC update failed");
 
-            // Assert response.
             let (resp_rtc, respp, _) = find_response::<Rtc>(messages.as_slice());
             assert_eq!(respp.status(), ResponseStatus::OK);
-            assert_eq!(resp_rtc.room_id(), rtc.room_id());
+            assert!(!resp_rtc.record());
+
+            let (event_rtc, evp, topic) = find_event::<Rtc>(messages.as_slice());
+            assert_eq!(evp.label(), "rtc.update");
+            assert!(topic.ends_with(&format!("/rooms/{}/events", rtc.room_id())));
+            assert!(!event_rtc.record());
         }
 
         #[sqlx::test]
-        async fn read_rtc_not_authorized(pool: sqlx::PgPool) {
+        async fn update_rtc_not_authorized(pool: sqlx::PgPool) {
             let db = TestDb::new(pool);
-
             let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
 
             let rtc = {
@@ -1383,32 +2553,64 @@ mod test {
             };
 
             let mut context = TestContext::new(db, TestAuthz::new()).await;
-            let payload = ReadRequest { id: rtc.id() };
+            let payload = UpdateRequest {
+                id: rtc.id(),
+                record: Some(false),
+                tags: None,
+            };
 
-            let err = handle_request::<ReadHandler>(&mut context, &agent, payload)
+            let err = handle_request::<UpdateHandler>(&mut context, &agent, payload)
                 .await
-                .expect_err("Unexpected success on rtc reading");
+                .expect_err("Unexpected success on rtc update");
 
             assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
-            assert_eq!(err.kind(), "access_denied");
         }
 
         #[sqlx::test]
-        async fn read_rtc_missing(pool: sqlx::PgPool) {
+        async fn update_rtc_tags_by_owner(pool: sqlx::PgPool) {
             let db = TestDb::new(pool);
-
             let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+            let rtc = {
+                let mut conn = db.get_conn().await;
+                let room = shared_helpers::insert_room(&mut conn).await;
+
+                factory::Rtc::new(room.id())
+                    .created_by(agent.agent_id().to_owned())
+                    .insert(&mut conn)
+                    .await
+            };
+
             let mut context = TestContext::new(db, TestAuthz::new()).await;
-            let payload = ReadRequest {
-                id: db::rtc::Id::random(),
+            let tags = serde_json::json!({ "label": "document-cam" });
+
+            let payload = UpdateRequest {
+                id: rtc.id(),
+                record: None,
+                tags: Some(tags.clone()),
             };
 
-            let err = handle_request::<ReadHandler>(&mut context, &agent, payload)
+            let messages = handle_request::<UpdateHandler>(&mut context, &agent, payload)
                 .await
-                .expect_err("Unexpected success on rtc reading");
+                .expect("RTC update failed");
 
-            assert_eq!(err.status(), ResponseStatus::NOT_FOUND);
-            assert_eq!(err.kind(), "room_not_found");
+            let (resp_rtc, respp, _) = find_response::<Rtc>(messages.as_slice());
+            assert_eq!(respp.status(), ResponseStatus::OK);
+            assert_eq!(resp_rtc.tags(), Some(&tags));
+            assert!(resp_rtc.record());
+
+            // Recording is still up to the host.
+            let payload = UpdateRequest {
+                id: rtc.id(),
+                record: Some(false),
+                tags: None,
+            };
+
+            let err = handle_request::<UpdateHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on rtc update");
+
+            assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
         }
     }
 
@@ -1446,7 +2648,9 @@ mod test {
             let mut context = TestContext::new(db, authz).await;
 
             let payload = ListRequest {
-                room_id: rtc.room_id(),
+                room_id: Some(rtc.room_id()),
+                classroom_id: None,
+                kind: None,
                 offset: None,
                 limit: None,
             };
@@ -1463,6 +2667,49 @@ mod test {
             assert_eq!(rtcs[0].room_id(), rtc.room_id());
         }
 
+        #[sqlx::test]
+        async fn list_rtcs_by_kind(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+            let (room, screen) = {
+                let mut conn = db.get_conn().await;
+                let room = shared_helpers::insert_room(&mut conn).await;
+                factory::Rtc::new(room.id()).insert(&mut conn).await;
+
+                let screen = factory::Rtc::new(room.id())
+                    .kind(RtcKind::Screen)
+                    .insert(&mut conn)
+                    .await;
+
+                (room, screen)
+            };
+
+            let mut authz = TestAuthz::new();
+            let classroom_id = room.classroom_id().to_string();
+            let object = vec!["classrooms", &classroom_id, "rtcs"];
+            authz.allow(agent.account_id(), object, "list");
+
+            let mut context = TestContext::new(db, authz).await;
+
+            let payload = ListRequest {
+                room_id: Some(room.id()),
+                classroom_id: None,
+                kind: Some(RtcKind::Screen),
+                offset: None,
+                limit: None,
+            };
+
+            let messages = handle_request::<ListHandler>(&mut context, &agent, payload)
+                .await
+                .expect("Rtc listing failed");
+
+            let (rtcs, _, _) = find_response::<Vec<Rtc>>(messages.as_slice());
+            assert_eq!(rtcs.len(), 1);
+            assert_eq!(rtcs[0].id(), screen.id());
+            assert_eq!(rtcs[0].kind(), RtcKind::Screen);
+        }
+
         #[sqlx::test]
         async fn list_rtcs_not_authorized(pool: sqlx::PgPool) {
             let db = TestDb::new(pool);
@@ -1476,7 +2723,9 @@ mod test {
             let mut context = TestContext::new(db, TestAuthz::new()).await;
 
             let payload = ListRequest {
-                room_id: room.id(),
+                room_id: Some(room.id()),
+                classroom_id: None,
+                kind: None,
                 offset: None,
                 limit: None,
             };
@@ -1497,7 +2746,9 @@ mod test {
             let mut context = TestContext::new(db, TestAuthz::new()).await;
 
             let payload = ListRequest {
-                room_id: db::room::Id::random(),
+                room_id: Some(db::room::Id::random()),
+                classroom_id: None,
+                kind: None,
                 offset: None,
                 limit: None,
             };
@@ -1514,8 +2765,8 @@ mod test {
     mod connect {
         use std::ops::Bound;
 
+        use ::http::StatusCode;
         use chrono::{Duration, Utc};
-        use http::StatusCode;
 
         use crate::{
             db::{agent::Status as AgentStatus, rtc::SharingPolicy as RtcSharingPolicy},
@@ -2743,4 +3994,319 @@ mod test {
             assert_eq!(err.kind(), "room_not_found");
         }
     }
+
+    mod preflight {
+        use std::ops::Bound;
+
+        use chrono::{Duration, SubsecRound, Utc};
+
+        use crate::{
+            backend::janus::client::{HandleId as JanusHandleId, SessionId},
+            test_helpers::{db::TestDb, prelude::*},
+        };
+
+        use super::super::*;
+
+        #[sqlx::test]
+        async fn preflight_busy_backend(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+            let mut authz = TestAuthz::new();
+            let mut conn = db.get_conn().await;
+            let now = Utc::now().trunc_subsecs(0);
+
+            let backend = factory::JanusBackend::new(
+                TestAgent::new("alpha", "janus", SVC_AUDIENCE)
+                    .agent_id()
+                    .to_owned(),
+                JanusHandleId::random(),
+                SessionId::random(),
+                // Nothing listens there so the test fails if a handle is requested.
+                "https://janus.example.org".to_owned(),
+            )
+            .capacity(10)
+            .insert(&mut conn)
+            .await;
+
+            let room = factory::Room::new()
+                .audience(USR_AUDIENCE)
+                .time((
+                    Bound::Included(now),
+                    Bound::Excluded(now + Duration::hours(1)),
+                ))
+                .rtc_sharing_policy(RtcSharingPolicy::Shared)
+                .backend_id(backend.id())
+                .reserve(9)
+                .insert(&mut conn)
+                .await;
+
+            let rtc = shared_helpers::insert_rtc_with_room(&mut conn, &room).await;
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+            let classroom_id = room.classroom_id().to_string();
+            let rtc_id = rtc.id().to_string();
+            let object = vec!["classrooms", &classroom_id, "rtcs", &rtc_id];
+            authz.allow(agent.account_id(), object, "read");
+
+            let mut context = TestContext::new(db, authz).await;
+
+            context.config_mut().preflight = serde_json::from_value(serde_json::json!({
+                "ice_servers": [{ "urls": ["stun:stun.example.org:3478"] }],
+                "codecs": ["opus", "vp8"],
+                "regions": { "default": "eu-central" },
+            }))
+            .expect("Failed to parse preflight config");
+
+            let payload = PreflightRequest { id: rtc.id() };
+
+            let messages = handle_request::<PreflightHandler>(&mut context, &agent, payload)
+                .await
+                .expect("RTC preflight failed");
+
+            let (data, respp, _) = find_response::<PreflightResponseData>(messages.as_slice());
+            assert_eq!(respp.status(), ResponseStatus::OK);
+            assert_eq!(data.region.as_deref(), Some("eu-central"));
+            assert_eq!(data.load_class, LoadClass::High);
+            assert_eq!(data.ice_servers[0].urls, vec!["stun:stun.example.org:3478"]);
+            assert_eq!(data.codecs, vec!["opus", "vp8"]);
+        }
+    }
+
+    mod connect_explain {
+        use std::ops::Bound;
+
+        use chrono::{Duration, SubsecRound, Utc};
+
+        use crate::{
+            backend::janus::client::{HandleId as JanusHandleId, SessionId},
+            test_helpers::{db::TestDb, prelude::*},
+        };
+
+        use super::super::*;
+
+        #[sqlx::test]
+        async fn explain_exclusions(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+            let mut authz = TestAuthz::new();
+            let mut conn = db.get_conn().await;
+            let now = Utc::now().trunc_subsecs(0);
+            let time = (
+                Bound::Included(now),
+                Bound::Excluded(now + Duration::hours(1)),
+            );
+
+            let mut backends = vec![];
+
+            for (label, group) in [
+                ("alpha", "webinar"),
+                ("beta", "webinar"),
+                ("gamma", "minigroup"),
+            ] {
+                let backend = factory::JanusBackend::new(
+                    TestAgent::new(label, "janus", SVC_AUDIENCE)
+                        .agent_id()
+                        .to_owned(),
+                    JanusHandleId::random(),
+                    SessionId::random(),
+                    "https://janus.example.org".to_owned(),
+                )
+                .capacity(10)
+                .group(group)
+                .insert(&mut conn)
+                .await;
+
+                backends.push(backend);
+            }
+
+            // Beta hosts a room that leaves no place for another reserve of 5.
+            factory::Room::new()
+                .audience(USR_AUDIENCE)
+                .time(time)
+                .rtc_sharing_policy(RtcSharingPolicy::Shared)
+                .backend_id(backends[1].id())
+                .reserve(8)
+                .insert(&mut conn)
+                .await;
+
+            let room = factory::Room::new()
+                .audience(USR_AUDIENCE)
+                .time(time)
+                .rtc_sharing_policy(RtcSharingPolicy::Shared)
+                .reserve(5)
+                .insert(&mut conn)
+                .await;
+
+            let rtc = shared_helpers::insert_rtc_with_room(&mut conn, &room).await;
+
+            let agent = TestAgent::new("alpha", "cron", SVC_AUDIENCE);
+            authz.set_audience(SVC_AUDIENCE);
+            authz.allow(agent.account_id(), vec!["system"], "read");
+
+            let mut context = TestContext::new(db, authz).await;
+            context.config_mut().janus_group = Some("webinar".to_owned());
+
+            let payload = ConnectExplainRequest { id: rtc.id() };
+
+            let messages = handle_request::<ConnectExplainHandler>(&mut context, &agent, payload)
+                .await
+                .expect("RTC connect explain failed");
+
+            let (data, respp, _) = find_response::<ConnectExplainResponseData>(messages.as_slice());
+
+            assert_eq!(respp.status(), ResponseStatus::OK);
+            assert_eq!(data.strategy, BalancerStrategy::MostLoaded);
+            assert_eq!(data.selected.as_ref(), Some(backends[0].id()));
+
+            let reasons = data
+                .candidates
+                .iter()
+                .map(|candidate| (&candidate.backend_id, candidate.excluded.as_slice()))
+                .collect::<Vec<_>>();
+
+            assert_eq!(
+                reasons,
+                vec![
+                    (backends[0].id(), &[][..]),
+                    (backends[1].id(), &[ExclusionReason::Full][..]),
+                    (backends[2].id(), &[ExclusionReason::WrongGroup][..]),
+                ]
+            );
+
+            // Nothing got allocated.
+            use db::room::FindQueryable;
+
+            let room = db::room::FindQuery::new(room.id())
+                .execute(&mut conn)
+                .await
+                .expect("Failed to find room")
+                .expect("Room not found");
+
+            assert_eq!(room.backend_id(), None);
+        }
+    }
+
+    mod subscribe_set {
+        use std::ops::Bound;
+
+        use chrono::Utc;
+
+        use crate::test_helpers::{db::TestDb, prelude::*};
+
+        use super::super::*;
+
+        #[sqlx::test]
+        async fn subscribe_set(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+            let mut conn = db.get_conn().await;
+            let reader = TestAgent::new("web", "reader", USR_AUDIENCE);
+
+            // No backend so the configs are taken from the database on reading.
+            let room = factory::Room::new()
+                .audience(USR_AUDIENCE)
+                .time((Bound::Included(Utc::now()), Bound::Unbounded))
+                .rtc_sharing_policy(RtcSharingPolicy::Owned)
+                .insert(&mut conn)
+                .await;
+
+            shared_helpers::insert_agent(&mut conn, reader.agent_id(), room.id()).await;
+
+            let mut rtcs = vec![];
+
+            for idx in 0..3 {
+                let writer = TestAgent::new("web", &format!("writer{idx}"), USR_AUDIENCE);
+
+                let rtc = factory::Rtc::new(room.id())
+                    .created_by(writer.agent_id().to_owned())
+                    .insert(&mut conn)
+                    .await;
+
+                rtcs.push(rtc);
+            }
+
+            let mut authz = TestAuthz::new();
+            let classroom_id = room.classroom_id().to_string();
+            authz.allow(
+                reader.account_id(),
+                vec!["classrooms", &classroom_id],
+                "update",
+            );
+
+            let mut context = TestContext::new(db, authz).await;
+
+            let payload = SubscribeSetRequest {
+                room_id: room.id(),
+                rtc_ids: vec![rtcs[0].id()],
+            };
+
+            let messages = handle_request::<SubscribeSetHandler>(&mut context, &reader, payload)
+                .await
+                .expect("RTC subscribe set failed");
+
+            let (data, respp, _) = find_response::<SubscribeSetResponseData>(messages.as_slice());
+            assert_eq!(respp.status(), ResponseStatus::OK);
+            assert_eq!(data.rtc_ids, vec![rtcs[0].id()]);
+            assert_eq!(data.status, ApplyStatus::Applied);
+
+            // Video of the rest is off and the configs are confirmed.
+            let mut conn = context.get_conn().await.expect("Failed to get conn");
+
+            for (rtc, receive_video) in rtcs.iter().zip([true, false, false]) {
+                let configs = db::rtc_reader_config::read_config(rtc.id(), &mut conn)
+                    .await
+                    .expect("Failed to read reader configs");
+
+                let video = configs
+                    .iter()
+                    .find(|c| c.reader_id() == reader.agent_id())
+                    .map(|c| c.receive_video())
+                    .unwrap_or(true);
+
+                assert_eq!(video, receive_video);
+            }
+
+            assert!(
+                !db::rtc_reader_config::has_pending(room.id(), reader.agent_id(), &mut conn)
+                    .await
+                    .expect("Failed to check pending configs")
+            );
+        }
+
+        #[sqlx::test]
+        async fn subscribe_set_foreign_rtc(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+            let mut conn = db.get_conn().await;
+            let reader = TestAgent::new("web", "reader", USR_AUDIENCE);
+
+            let room = factory::Room::new()
+                .audience(USR_AUDIENCE)
+                .time((Bound::Included(Utc::now()), Bound::Unbounded))
+                .rtc_sharing_policy(RtcSharingPolicy::Owned)
+                .insert(&mut conn)
+                .await;
+
+            shared_helpers::insert_agent(&mut conn, reader.agent_id(), room.id()).await;
+            let other_rtc = shared_helpers::insert_rtc(&mut conn).await;
+
+            let mut authz = TestAuthz::new();
+            let classroom_id = room.classroom_id().to_string();
+            authz.allow(
+                reader.account_id(),
+                vec!["classrooms", &classroom_id],
+                "update",
+            );
+
+            let mut context = TestContext::new(db, authz).await;
+
+            let payload = SubscribeSetRequest {
+                room_id: room.id(),
+                rtc_ids: vec![other_rtc.id()],
+            };
+
+            let err = handle_request::<SubscribeSetHandler>(&mut context, &reader, payload)
+                .await
+                .expect_err("Unexpected success on subscribing to a foreign rtc");
+
+            assert_eq!(err.status(), ResponseStatus::BAD_REQUEST);
+            assert_eq!(err.kind(), "invalid_payload");
+        }
+    }
 }
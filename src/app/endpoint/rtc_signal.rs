@@ -2,20 +2,26 @@ use crate::{
     app::{
         context::{AppContext, Context, MessageContext},
         endpoint,
-        endpoint::prelude::*,
+        endpoint::{helpers::MediaAction, prelude::*},
         handle_id::HandleId,
+        message_handler::http,
         metrics::HistogramExt,
+        sdp,
         service_utils::{RequestParams, Response},
+        signal_history,
     },
     authz::AuthzObject,
-    backend::janus::client::{
-        create_stream::{
-            CreateStreamRequest, CreateStreamRequestBody, CreateStreamTransaction, ReaderConfig,
-            WriterConfig,
+    backend::janus::{
+        client::{
+            create_stream::{
+                CreateStreamRequest, CreateStreamRequestBody, CreateStreamTransaction,
+                ReaderConfig, WriterConfig,
+            },
+            read_stream::{ReadStreamRequest, ReadStreamRequestBody, ReadStreamTransaction},
+            IceCandidateSdp, Jsep, JsepType, JsonSdp,
         },
-        read_stream::{ReadStreamRequest, ReadStreamRequestBody, ReadStreamTransaction},
-        trickle::TrickleRequest,
-        IceCandidateSdp, Jsep, JsepType, JsonSdp,
+        correlation::{self, CorrelationRef},
+        negotiation,
     },
     db,
 };
@@ -27,7 +33,7 @@ use chrono::{Duration, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value as JsonValue};
 use sqlx::Connection;
-use std::{ops::Bound, result::Result as StdResult, sync::Arc};
+use std::{result::Result as StdResult, sync::Arc};
 use svc_agent::{
     mqtt::{OutgoingResponse, ResponseStatus},
     Addressable, AgentId, Authenticable,
@@ -70,14 +76,7 @@ pub async fn create(
         .map(|label| AgentId::new(label, agent_id.as_account_id().to_owned()))
         .unwrap_or(agent_id);
 
-    CreateHandler::handle(
-        &mut ctx.start_message(),
-        payload,
-        RequestParams::Http {
-            agent_id: &agent_id,
-        },
-    )
-    .await
+    http::handle::<CreateHandler>(&ctx, &agent_id, payload).await
 }
 
 #[derive(Debug, Deserialize)]
@@ -115,11 +114,11 @@ pub async fn start_rtc_stream<C: Context>(
         Box::pin(async move {
             if let Some(max_room_duration) = max_room_duration {
                 if !room.infinite() {
-                    if let (start, Bound::Unbounded) = room.time() {
-                        let new_time = (
-                            start,
-                            Bound::Excluded(Utc::now() + Duration::hours(max_room_duration)),
-                        );
+                    let time = room.time();
+
+                    if time.closed_at().is_none() {
+                        let new_time =
+                            time.close_at(Utc::now() + Duration::hours(max_room_duration));
 
                         db::room::UpdateQuery::new(room.id())
                             .time(Some(new_time))
@@ -150,6 +149,10 @@ pub async fn start_rtc_stream<C: Context>(
 impl RequestHandler for CreateHandler {
     type Payload = CreateRequest;
     const ERROR_TITLE: &'static str = "Failed to create rtc";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::Object {
+        object: &["classrooms", "{classroom_id}", "rtcs", "{rtc_id}"],
+        actions: &["read", "update", "listen", "publish"],
+    });
 
     async fn handle<C: Context + Send + Sync>(
         context: &mut C,
@@ -172,6 +175,7 @@ impl RequestHandler for CreateHandler {
             let room = helpers::find_room_by_id(
                 rtc.room_id(),
                 helpers::RoomTimeRequirement::Open,
+                helpers::AudienceScope::caller(context, &reqp),
                 &mut conn,
             )
             .await?;
@@ -184,38 +188,7 @@ impl RequestHandler for CreateHandler {
 
             helpers::check_room_presence(&room, reqp.as_agent_id(), &mut conn).await?;
 
-            // Validate backend and janus session id.
-            if let Some(backend_id) = room.backend_id() {
-                if handle_id.backend_id() != backend_id {
-                    return Err(anyhow!("Backend id specified in the handle ID doesn't match the one from the room object"))
-                        .error(AppErrorKind::InvalidHandleId);
-                }
-            } else {
-                return Err(anyhow!("Room backend not set")).error(AppErrorKind::BackendNotFound);
-            }
-
-            let janus_backend = db::janus_backend::FindQuery::new(handle_id.backend_id())
-                .execute(&mut conn)
-                .await?
-                .context("Backend not found")
-                .error(AppErrorKind::BackendNotFound)?;
-
-            if handle_id.janus_session_id() != janus_backend.session_id() {
-                return Err(anyhow!("Backend session specified in the handle ID doesn't match the one from the backend object"))
-                    .error(AppErrorKind::InvalidHandleId)?;
-            }
-
-            // Validate agent connection and handle id.
-            let agent_connection = db::agent_connection::FindQuery::new(&agent_id, rtc.id())
-                .execute(&mut conn)
-                .await?
-                .context("Agent not connected")
-                .error(AppErrorKind::AgentNotConnected)?;
-
-            if handle_id.janus_handle_id() != agent_connection.handle_id() {
-                return Err(anyhow!("Janus handle ID specified in the handle ID doesn't match the one from the agent connection"))
-                    .error(AppErrorKind::InvalidHandleId)?;
-            }
+            let janus_backend = validate_handle(&handle_id, &agent_id, &room, &mut conn).await?;
 
             (room, rtc, janus_backend)
         };
@@ -226,10 +199,80 @@ impl RequestHandler for CreateHandler {
                     JsepType::Offer => {
                         let current_span = Span::current();
                         current_span.record("sdp_type", "offer");
+                        negotiation::check_sdp_size(sdp, context.config().max_sdp_size)?;
+                        sdp::validate_offer(sdp)?;
+
                         let is_recvonly = is_sdp_recvonly(sdp)
                             .context("Invalid JSEP format")
                             .error(AppErrorKind::InvalidJsepFormat)?;
 
+                        // Codecs the room's audience doesn't allow never get to Janus.
+                        let disallowed_codecs = context
+                            .config()
+                            .sdp
+                            .get(room.audience())
+                            .map(|config| config.disallowed_codecs.as_slice())
+                            .unwrap_or_default();
+
+                        let sdp = match sdp::strip_codecs(sdp, disallowed_codecs)? {
+                            Some(stripped) => {
+                                tracing::info!(
+                                    offered = %sdp::codecs(sdp).join(","),
+                                    left = %sdp::codecs(&stripped).join(","),
+                                    "Stripped disallowed codecs from the offer"
+                                );
+
+                                stripped
+                            }
+                            None => sdp.to_owned(),
+                        };
+
+                        let jsep = Jsep::OfferOrAnswer(JsonSdp {
+                            kind,
+                            sdp: sdp.clone(),
+                        });
+
+                        signal_history::record(
+                            context,
+                            room.id(),
+                            payload.handle_id.rtc_id(),
+                            reqp.as_agent_id(),
+                            db::rtc_signal_artifact::Kind::Offer,
+                            &sdp,
+                        );
+
+                        // Another offer to the connection renegotiates the existing handle,
+                        // e.g. a mobile client switching networks restarts ICE with new
+                        // credentials instead of connecting again.
+                        let ice_restart = {
+                            let ufrag = negotiation::ice_ufrag(&sdp);
+
+                            let previous_ufrag = match ufrag {
+                                Some(ufrag) => {
+                                    let mut conn = context.get_conn().await?;
+                                    db::agent_connection::swap_ice_ufrag(
+                                        payload.handle_id.janus_handle_id(),
+                                        payload.handle_id.rtc_id(),
+                                        ufrag,
+                                        &mut conn,
+                                    )
+                                    .await?
+                                }
+                                None => None,
+                            };
+
+                            negotiation::is_ice_restart(previous_ufrag.as_deref(), ufrag)
+                        };
+
+                        let data_channel = sdp::has_data_channel(&sdp);
+
+                        if ice_restart {
+                            tracing::info!(
+                                handle_id = %payload.handle_id,
+                                "ICE restart requested"
+                            );
+                        }
+
                         if is_recvonly {
                             current_span.record("intent", "read");
 
@@ -241,8 +284,19 @@ impl RequestHandler for CreateHandler {
                             .await?;
 
                             // Authorization
-                            let _authz_time =
-                                authorize(context, &payload.handle_id, reqp, "read", &room).await?;
+                            let _authz_time = authorize(
+                                context,
+                                &payload.handle_id,
+                                reqp,
+                                MediaAction::Listen,
+                                &room,
+                            )
+                            .await?;
+
+                            context
+                                .janus_clients()
+                                .throttle(room.id(), backend.id())
+                                .await?;
 
                             let request = ReadStreamRequest {
                                 body: ReadStreamRequestBody::new(
@@ -256,25 +310,38 @@ impl RequestHandler for CreateHandler {
                                             receive_video: r.receive_video(),
                                         })
                                         .collect(),
-                                ),
+                                )
+                                .ice_restart(ice_restart)
+                                .data_channel(data_channel),
                                 handle_id: payload.handle_id.janus_handle_id(),
                                 session_id: payload.handle_id.janus_session_id(),
-                                jsep: payload.jsep,
+                                jsep,
                             };
 
                             match reqp.as_mqtt_params() {
                                 Ok(mqtt_params) => {
+                                    let client = context
+                                        .janus_clients()
+                                        .get_or_insert(&backend)
+                                        .error(AppErrorKind::BackendClientCreationFailed)?;
+                                    let correlation = context
+                                        .janus_clients()
+                                        .correlations()
+                                        .create(correlation::Path::Mqtt);
+
                                     let transaction = ReadStreamTransaction::Mqtt {
                                         reqp: mqtt_params.clone(),
                                         start_timestamp: context.start_timestamp(),
+                                        handle_id: Some(payload.handle_id.clone()),
+                                        correlation: Some(CorrelationRef {
+                                            id: correlation.id(),
+                                            replica_addr: context.janus_clients().own_ip_addr(),
+                                        }),
                                     };
-                                    context
-                                        .janus_clients()
-                                        .get_or_insert(&backend)
-                                        .error(AppErrorKind::BackendClientCreationFailed)?
-                                        .read_stream(request, transaction)
+                                    correlation
+                                        .send(client.read_stream(request, transaction))
                                         .await
-                                        .error(AppErrorKind::BackendRequestFailed)?;
+                                        .map_err(|err| negotiation::sdp_request_error(err, &sdp))?;
 
                                     Ok(Response::new(
                                         ResponseStatus::NO_CONTENT,
@@ -284,29 +351,37 @@ impl RequestHandler for CreateHandler {
                                     ))
                                 }
                                 Err(_err) => {
-                                    let handle = context
+                                    let client = context
                                         .janus_clients()
-                                        .stream_waitlist()
-                                        .register()
-                                        .error(AppErrorKind::JanusResponseTimeout)?;
+                                        .get_or_insert(&backend)
+                                        .error(AppErrorKind::BackendClientCreationFailed)?;
+                                    let correlation = context
+                                        .janus_clients()
+                                        .correlations()
+                                        .create(correlation::Path::Http);
 
                                     let transaction = ReadStreamTransaction::Http {
-                                        id: handle.id(),
+                                        id: correlation.id(),
                                         replica_addr: context.janus_clients().own_ip_addr(),
                                     };
-                                    context
-                                        .janus_clients()
-                                        .get_or_insert(&backend)
-                                        .error(AppErrorKind::BackendClientCreationFailed)?
-                                        .read_stream(request, transaction)
+                                    let pending = correlation
+                                        .send(client.read_stream(request, transaction))
                                         .await
-                                        .error(AppErrorKind::BackendRequestFailed)?;
+                                        .map_err(|err| negotiation::sdp_request_error(err, &sdp))?;
 
-                                    let resp = handle
+                                    let resp = pending
                                         .wait(context.config().waitlist_timeout)
                                         .await
                                         .error(AppErrorKind::JanusResponseTimeout)??;
 
+                                    signal_history::record_answer(
+                                        context,
+                                        room.id(),
+                                        payload.handle_id.rtc_id(),
+                                        reqp.as_agent_id(),
+                                        resp.jsep.as_ref(),
+                                    );
+
                                     Ok(Response::new(
                                         ResponseStatus::OK,
                                         resp,
@@ -325,21 +400,62 @@ impl RequestHandler for CreateHandler {
                                     .error(AppErrorKind::AccessDenied);
                             }
 
-                            let _authz_time =
-                                authorize(context, &payload.handle_id, reqp, "update", &room)
-                                    .await?;
-
-                            // Updating the Real-Time Connection state
-                            start_rtc_stream(
+                            let _authz_time = authorize(
                                 context,
                                 &payload.handle_id,
-                                reqp.as_agent_id(),
-                                &payload.label,
+                                reqp,
+                                MediaAction::Publish,
                                 &room,
                             )
                             .await?;
 
+                            context
+                                .janus_clients()
+                                .throttle(room.id(), backend.id())
+                                .await?;
+
+                            let audio_processing = {
+                                let mut conn = context.get_conn().await?;
+                                helpers::audio_processing(&room, backend.id(), &mut conn).await?
+                            };
+
+                            // The stream of a renegotiated handle has been started by its
+                            // first offer already.
+                            let renegotiation = {
+                                let mut conn = context.get_conn().await?;
+                                db::janus_rtc_stream::get_rtc_stream(
+                                    &mut conn,
+                                    payload.handle_id.rtc_stream_id(),
+                                )
+                                .await?
+                                .is_some()
+                            };
+
+                            // Updating the Real-Time Connection state
+                            if !renegotiation {
+                                start_rtc_stream(
+                                    context,
+                                    &payload.handle_id,
+                                    reqp.as_agent_id(),
+                                    &payload.label,
+                                    &room,
+                                )
+                                .await?;
+                            }
+
                             let mut conn = context.get_conn().await?;
+                            db::rtc_reader_config::apply_room_defaults(
+                                payload.handle_id.rtc_id(),
+                                &mut conn,
+                            )
+                            .await?;
+
+                            db::rtc_reader_config::apply_subscribe_sets(
+                                payload.handle_id.rtc_id(),
+                                &mut conn,
+                            )
+                            .await?;
+
                             let reader_config = db::rtc_reader_config::read_config(
                                 payload.handle_id.rtc_id(),
                                 &mut conn,
@@ -361,6 +477,8 @@ impl RequestHandler for CreateHandler {
                                         send_video: w.send_video(),
                                         send_audio: w.send_audio(),
                                         video_remb: w.video_remb(),
+                                        max_video_bitrate: w.max_video_bitrate(),
+                                        max_audio_bitrate: w.max_audio_bitrate(),
                                     }),
                                     reader_config
                                         .into_iter()
@@ -370,25 +488,40 @@ impl RequestHandler for CreateHandler {
                                             receive_video: r.receive_video(),
                                         })
                                         .collect(),
-                                ),
+                                )
+                                .audio_processing(audio_processing)
+                                .record(rtc.record())
+                                .ice_restart(ice_restart)
+                                .data_channel(data_channel),
                                 handle_id: payload.handle_id.janus_handle_id(),
                                 session_id: payload.handle_id.janus_session_id(),
-                                jsep: payload.jsep,
+                                jsep,
                             };
 
                             match reqp.as_mqtt_params() {
                                 Ok(mqtt_params) => {
+                                    let client = context
+                                        .janus_clients()
+                                        .get_or_insert(&backend)
+                                        .error(AppErrorKind::BackendClientCreationFailed)?;
+                                    let correlation = context
+                                        .janus_clients()
+                                        .correlations()
+                                        .create(correlation::Path::Mqtt);
+
                                     let transaction = CreateStreamTransaction::Mqtt {
                                         reqp: mqtt_params.clone(),
                                         start_timestamp: context.start_timestamp(),
+                                        handle_id: Some(payload.handle_id.clone()),
+                                        correlation: Some(CorrelationRef {
+                                            id: correlation.id(),
+                                            replica_addr: context.janus_clients().own_ip_addr(),
+                                        }),
                                     };
-                                    context
-                                        .janus_clients()
-                                        .get_or_insert(&backend)
-                                        .error(AppErrorKind::BackendClientCreationFailed)?
-                                        .create_stream(request, transaction)
+                                    correlation
+                                        .send(client.create_stream(request, transaction))
                                         .await
-                                        .error(AppErrorKind::BackendRequestFailed)?;
+                                        .map_err(|err| negotiation::sdp_request_error(err, &sdp))?;
 
                                     Ok(Response::new(
                                         ResponseStatus::NO_CONTENT,
@@ -398,29 +531,37 @@ impl RequestHandler for CreateHandler {
                                     ))
                                 }
                                 Err(_err) => {
-                                    let handle = context
+                                    let client = context
                                         .janus_clients()
-                                        .stream_waitlist()
-                                        .register()
-                                        .error(AppErrorKind::JanusResponseTimeout)?;
+                                        .get_or_insert(&backend)
+                                        .error(AppErrorKind::BackendClientCreationFailed)?;
+                                    let correlation = context
+                                        .janus_clients()
+                                        .correlations()
+                                        .create(correlation::Path::Http);
 
                                     let transaction = CreateStreamTransaction::Http {
-                                        id: handle.id(),
+                                        id: correlation.id(),
                                         replica_addr: context.janus_clients().own_ip_addr(),
                                     };
-                                    context
-                                        .janus_clients()
-                                        .get_or_insert(&backend)
-                                        .error(AppErrorKind::BackendClientCreationFailed)?
-                                        .create_stream(request, transaction)
+                                    let pending = correlation
+                                        .send(client.create_stream(request, transaction))
                                         .await
-                                        .error(AppErrorKind::BackendRequestFailed)?;
+                                        .map_err(|err| negotiation::sdp_request_error(err, &sdp))?;
 
-                                    let resp = handle
+                                    let resp = pending
                                         .wait(context.config().waitlist_timeout)
                                         .await
                                         .error(AppErrorKind::JanusResponseTimeout)??;
 
+                                    signal_history::record_answer(
+                                        context,
+                                        room.id(),
+                                        payload.handle_id.rtc_id(),
+                                        reqp.as_agent_id(),
+                                        resp.jsep.as_ref(),
+                                    );
+
                                     Ok(Response::new(
                                         ResponseStatus::OK,
                                         resp,
@@ -435,24 +576,46 @@ impl RequestHandler for CreateHandler {
                         .error(AppErrorKind::InvalidSdpType)?,
                 }
             }
-            Jsep::IceCandidate(_) => {
+            Jsep::IceCandidate(candidates) => {
                 let current_span = Span::current();
                 current_span.record("sdp_type", "ice_candidate");
                 current_span.record("intent", "read");
 
-                let _authz_time =
-                    authorize(context, &payload.handle_id, reqp, "read", &room).await?;
+                let _authz_time = authorize(
+                    context,
+                    &payload.handle_id,
+                    reqp,
+                    MediaAction::Listen,
+                    &room,
+                )
+                .await?;
 
-                let request = TrickleRequest {
-                    candidate: payload.jsep,
-                    handle_id: payload.handle_id.janus_handle_id(),
-                    session_id: payload.handle_id.janus_session_id(),
-                };
                 context
                     .janus_clients()
+                    .throttle(room.id(), backend.id())
+                    .await?;
+
+                signal_history::record_candidates(
+                    context,
+                    room.id(),
+                    payload.handle_id.rtc_id(),
+                    reqp.as_agent_id(),
+                    &candidates,
+                );
+
+                let clients = context.janus_clients();
+                let client = clients
                     .get_or_insert(&backend)
-                    .error(AppErrorKind::BackendClientCreationFailed)?
-                    .trickle_request(request)
+                    .error(AppErrorKind::BackendClientCreationFailed)?;
+
+                clients
+                    .trickle_batcher()
+                    .trickle(
+                        &client,
+                        payload.handle_id.janus_session_id(),
+                        payload.handle_id.janus_handle_id(),
+                        candidates,
+                    )
                     .await
                     .error(AppErrorKind::BackendRequestFailed)?;
                 let response = Response::new(
@@ -509,6 +672,58 @@ pub async fn trickle(
     ))
 }
 
+/// Passes the candidates of the agent's handle on to Janus, see `whip::trickle`.
+pub(crate) async fn trickle_candidates<C: Context>(
+    ctx: &mut C,
+    handle_id: HandleId,
+    candidates: IceCandidateSdp,
+    agent_id: AgentId,
+) -> Result<(), AppError> {
+    Trickle {
+        ctx,
+        handle_id,
+        candidates,
+        agent_id,
+    }
+    .run()
+    .await
+}
+
+/// Trickle for the transports routing by method, i.e. WebSocket.
+pub struct TrickleHandler;
+
+#[async_trait]
+impl RequestHandler for TrickleHandler {
+    type Payload = TricklePayload;
+    const ERROR_TITLE: &'static str = "Failed to trickle candidates";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::Object {
+        object: &["classrooms", "{classroom_id}", "rtcs", "{rtc_id}"],
+        actions: &["read", "listen"],
+    });
+
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        Trickle {
+            ctx: context,
+            handle_id: payload.handle_id,
+            candidates: payload.candidates,
+            agent_id: reqp.as_agent_id().to_owned(),
+        }
+        .run()
+        .await?;
+
+        Ok(Response::new(
+            ResponseStatus::OK,
+            serde_json::json!({}),
+            context.start_timestamp(),
+            None,
+        ))
+    }
+}
+
 struct Trickle<'a, C> {
     ctx: &'a mut C,
     handle_id: HandleId,
@@ -524,11 +739,6 @@ impl<C: Context> Trickle<'_, C> {
             let handle_id = self.handle_id.clone();
 
             let mut conn = self.ctx.get_conn().await?;
-            let janus_backend = db::janus_backend::FindQuery::new(handle_id.backend_id())
-                .execute(&mut conn)
-                .await?
-                .context("Backend not found")
-                .error(AppErrorKind::BackendNotFound)?;
 
             let rtc = db::rtc::FindQuery::new(handle_id.rtc_id())
                 .execute(&mut conn)
@@ -536,21 +746,10 @@ impl<C: Context> Trickle<'_, C> {
                 .context("RTC not found")
                 .error(AppErrorKind::RtcNotFound)?;
 
-            // Validate agent connection and handle id.
-            let agent_connection = db::agent_connection::FindQuery::new(&agent_id, rtc.id())
-                .execute(&mut conn)
-                .await?
-                .context("Agent not connected")
-                .error(AppErrorKind::AgentNotConnected)?;
-
-            if handle_id.janus_handle_id() != agent_connection.handle_id() {
-                return Err(anyhow!("Janus handle ID specified in the handle ID doesn't match the one from the agent connection"))
-                    .error(AppErrorKind::InvalidHandleId)?;
-            }
-
             let room = helpers::find_room_by_id(
                 rtc.room_id(),
                 helpers::RoomTimeRequirement::Open,
+                helpers::AudienceScope::caller(&*self.ctx, &agent_id),
                 &mut conn,
             )
             .await?;
@@ -563,20 +762,7 @@ impl<C: Context> Trickle<'_, C> {
 
             helpers::check_room_presence(&room, &self.agent_id, &mut conn).await?;
 
-            // Validate backend and janus session id.
-            if let Some(backend_id) = room.backend_id() {
-                if handle_id.backend_id() != backend_id {
-                    return Err(anyhow!("Backend id specified in the handle ID doesn't match the one from the room object"))
-                        .error(AppErrorKind::InvalidHandleId);
-                }
-            } else {
-                return Err(anyhow!("Room backend not set")).error(AppErrorKind::BackendNotFound);
-            }
-
-            if handle_id.janus_session_id() != janus_backend.session_id() {
-                return Err(anyhow!("Backend session specified in the handle ID doesn't match the one from the backend object"))
-                    .error(AppErrorKind::InvalidHandleId)?;
-            }
+            let janus_backend = validate_handle(&handle_id, &agent_id, &room, &mut conn).await?;
 
             (room, janus_backend)
         };
@@ -585,20 +771,41 @@ impl<C: Context> Trickle<'_, C> {
         current_span.record("sdp_type", "ice_candidate");
         current_span.record("intent", "read");
 
-        let _authz_time =
-            authorize(self.ctx, &self.handle_id, self.agent_id, "read", &room).await?;
-        let jsep = Jsep::IceCandidate(self.candidates);
+        let _authz_time = authorize(
+            self.ctx,
+            &self.handle_id,
+            self.agent_id.clone(),
+            MediaAction::Listen,
+            &room,
+        )
+        .await?;
 
-        let request = TrickleRequest {
-            candidate: jsep,
-            handle_id: self.handle_id.janus_handle_id(),
-            session_id: self.handle_id.janus_session_id(),
-        };
         self.ctx
             .janus_clients()
+            .throttle(room.id(), backend.id())
+            .await?;
+
+        signal_history::record_candidates(
+            &*self.ctx,
+            room.id(),
+            self.handle_id.rtc_id(),
+            &self.agent_id,
+            &self.candidates,
+        );
+
+        let clients = self.ctx.janus_clients();
+        let client = clients
             .get_or_insert(&backend)
-            .error(AppErrorKind::BackendClientCreationFailed)?
-            .trickle_request(request)
+            .error(AppErrorKind::BackendClientCreationFailed)?;
+
+        clients
+            .trickle_batcher()
+            .trickle(
+                &client,
+                self.handle_id.janus_session_id(),
+                self.handle_id.janus_handle_id(),
+                self.candidates,
+            )
             .await
             .error(AppErrorKind::BackendRequestFailed)?;
 
@@ -612,11 +819,74 @@ impl<C: Context> Trickle<'_, C> {
     }
 }
 
-async fn authorize<A: Authenticable, C: Context>(
+/// Checks that the handle id sent by the client was issued to the agent: it must point to
+/// the agent's connection to the RTC on the room's backend within its current Janus session.
+pub(crate) async fn validate_handle(
+    handle_id: &HandleId,
+    agent_id: &AgentId,
+    room: &db::room::Object,
+    conn: &mut sqlx::PgConnection,
+) -> Result<db::janus_backend::Object, AppError> {
+    match room.backend_id() {
+        Some(backend_id) if handle_id.backend_id() != backend_id => {
+            return Err(anyhow!(
+                "Backend id specified in the handle ID doesn't match the one from the room object"
+            ))
+            .error(AppErrorKind::InvalidHandleId);
+        }
+        Some(_) => (),
+        None => return Err(anyhow!("Room backend not set")).error(AppErrorKind::BackendNotFound),
+    }
+
+    let janus_backend = db::janus_backend::FindQuery::new(handle_id.backend_id())
+        .execute(conn)
+        .await?
+        .context("Backend not found")
+        .error(AppErrorKind::BackendNotFound)?;
+
+    if handle_id.janus_session_id() != janus_backend.session_id() {
+        return Err(anyhow!(
+            "Backend session specified in the handle ID doesn't match the one from the backend object"
+        ))
+        .error(AppErrorKind::InvalidHandleId);
+    }
+
+    let agent_connection = db::agent_connection::FindQuery::new(agent_id, handle_id.rtc_id())
+        .execute(conn)
+        .await?
+        .context("Agent not connected")
+        .error(AppErrorKind::AgentNotConnected)?;
+
+    if handle_id.janus_handle_id() != agent_connection.handle_id() {
+        return Err(anyhow!(
+            "Janus handle ID specified in the handle ID doesn't match the one from the agent connection"
+        ))
+        .error(AppErrorKind::InvalidHandleId);
+    }
+
+    // The stream id is issued along with the handle so it may only refer to the agent's own stream.
+    let rtc_stream = db::janus_rtc_stream::get_rtc_stream(conn, handle_id.rtc_stream_id()).await?;
+
+    if let Some(rtc_stream) = rtc_stream {
+        if rtc_stream.sent_by() != agent_id
+            || rtc_stream.rtc_id() != handle_id.rtc_id()
+            || rtc_stream.handle_id() != handle_id.janus_handle_id()
+        {
+            return Err(anyhow!(
+                "Stream ID specified in the handle ID belongs to another connection"
+            ))
+            .error(AppErrorKind::InvalidHandleId);
+        }
+    }
+
+    Ok(janus_backend)
+}
+
+async fn authorize<A: Authenticable + Clone, C: Context>(
     context: &mut C,
     handle_id: &HandleId,
     reqp: A,
-    action: &str,
+    action: MediaAction,
     room: &db::room::Object,
 ) -> StdResult<Duration, AppError> {
     let rtc_id = handle_id.rtc_id();
@@ -632,22 +902,22 @@ async fn authorize<A: Authenticable, C: Context>(
 
     let classroom_id = room.classroom_id().to_string();
     let rtc_id = rtc_id.to_string();
-    let object = AuthzObject::new(&["classrooms", &classroom_id, "rtcs", &rtc_id]).into();
+    let object = AuthzObject::new(&["classrooms", &classroom_id, "rtcs", &rtc_id]);
 
-    let elapsed = context
-        .authz()
-        .authorize(room.audience().into(), reqp, object, action.into())
-        .await?;
-    context.metrics().observe_auth(elapsed);
-    Ok(elapsed)
+    helpers::authorize_media(&*context, room, reqp, object, action).await
 }
 
 pub fn is_sdp_recvonly(sdp: &str) -> anyhow::Result<bool> {
-    use webrtc_sdp::{attribute_type::SdpAttributeType, parse_sdp};
+    use webrtc_sdp::{attribute_type::SdpAttributeType, media_type::SdpMediaValue, parse_sdp};
     let sdp = parse_sdp(sdp, false).context("Invalid SDP")?;
 
-    // Returning true if all media section contains 'recvonly' attribute
+    // Returning true if all media section contains 'recvonly' attribute.
+    // Data channels have no direction so a reader may negotiate one along with its media.
     Ok(sdp.media.iter().all(|item| {
+        if let SdpMediaValue::Application = item.get_type() {
+            return true;
+        }
+
         let recvonly = item.get_attribute(SdpAttributeType::Recvonly).is_some();
         let sendonly = item.get_attribute(SdpAttributeType::Sendonly).is_some();
         let sendrecv = item.get_attribute(SdpAttributeType::Sendrecv).is_some();
@@ -656,10 +926,124 @@ pub fn is_sdp_recvonly(sdp: &str) -> anyhow::Result<bool> {
     }))
 }
 
+mod history;
+
+pub use history::Handler as HistoryHandler;
+
 ///////////////////////////////////////////////////////////////////////////////
 
 #[cfg(test)]
 mod test {
+    mod validate_handle {
+        use crate::{
+            app::handle_id::HandleId,
+            backend::janus::client::{HandleId as JanusHandleId, SessionId},
+            test_helpers::{db::TestDb, prelude::*},
+        };
+
+        use super::super::*;
+
+        #[sqlx::test]
+        async fn reject_handle_of_another_agent(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+            let mut conn = db.get_conn().await;
+
+            let backend = shared_helpers::insert_janus_backend(
+                &mut conn,
+                "test",
+                SessionId::random(),
+                JanusHandleId::random(),
+            )
+            .await;
+            let room = shared_helpers::insert_room_with_backend_id(&mut conn, backend.id()).await;
+            let rtc = shared_helpers::insert_rtc_with_room(&mut conn, &room).await;
+
+            let owner = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let intruder = TestAgent::new("web", "user456", USR_AUDIENCE);
+
+            let (_, connection) = shared_helpers::insert_connected_agent(
+                &mut conn,
+                owner.agent_id(),
+                room.id(),
+                rtc.id(),
+            )
+            .await;
+            shared_helpers::insert_agent(&mut conn, intruder.agent_id(), room.id()).await;
+
+            let handle_id = HandleId::new(
+                db::janus_rtc_stream::Id::random(),
+                rtc.id(),
+                connection.handle_id(),
+                backend.session_id(),
+                backend.id().to_owned(),
+            );
+
+            validate_handle(&handle_id, owner.agent_id(), &room, &mut conn)
+                .await
+                .expect("Owner's handle rejected");
+
+            let err = validate_handle(&handle_id, intruder.agent_id(), &room, &mut conn)
+                .await
+                .expect_err("Unexpected success validating another agent's handle");
+
+            assert_eq!(err.kind(), "agent_not_connected");
+        }
+
+        #[sqlx::test]
+        async fn reject_stream_of_another_agent(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+            let mut conn = db.get_conn().await;
+
+            let backend = shared_helpers::insert_janus_backend(
+                &mut conn,
+                "test",
+                SessionId::random(),
+                JanusHandleId::random(),
+            )
+            .await;
+            let room = shared_helpers::insert_room_with_backend_id(&mut conn, backend.id()).await;
+            let rtc = shared_helpers::insert_rtc_with_room(&mut conn, &room).await;
+
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let writer = TestAgent::new("web", "user456", USR_AUDIENCE);
+
+            let (_, connection) = shared_helpers::insert_connected_agent(
+                &mut conn,
+                agent.agent_id(),
+                room.id(),
+                rtc.id(),
+            )
+            .await;
+
+            let rtc_stream = db::janus_rtc_stream::InsertQuery::new(
+                db::janus_rtc_stream::Id::random(),
+                JanusHandleId::random(),
+                rtc.id(),
+                backend.id(),
+                "alpha",
+                writer.agent_id(),
+            )
+            .execute(&mut conn)
+            .await
+            .expect("Failed to insert rtc stream");
+
+            // The agent's own handle pointing at someone else's stream.
+            let handle_id = HandleId::new(
+                rtc_stream.id(),
+                rtc.id(),
+                connection.handle_id(),
+                backend.session_id(),
+                backend.id().to_owned(),
+            );
+
+            let err = validate_handle(&handle_id, agent.agent_id(), &room, &mut conn)
+                .await
+                .expect_err("Unexpected success validating another agent's stream");
+
+            assert_eq!(err.kind(), "invalid_handle_id");
+        }
+    }
+
     mod create {
         use std::ops::Bound;
 
@@ -825,6 +1209,87 @@ a=extmap:2 urn:ietf:params:rtp-hdrext:sdes:mid
             Ok(())
         }
 
+        #[sqlx::test]
+        async fn offer_ice_restart(pool: sqlx::PgPool) -> std::io::Result<()> {
+            let local_deps = LocalDeps::new();
+            let janus = local_deps.run_janus();
+            let db = TestDb::new(pool);
+
+            let (session_id, handle_id) = shared_helpers::init_janus(&janus.url).await;
+            let user_handle = shared_helpers::create_handle(&janus.url, session_id).await;
+            let mut authz = TestAuthz::new();
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+            let mut conn = db.get_conn().await;
+            let backend =
+                shared_helpers::insert_janus_backend(&mut conn, &janus.url, session_id, handle_id)
+                    .await;
+
+            let room = shared_helpers::insert_room_with_backend_id(&mut conn, backend.id()).await;
+            let rtc = shared_helpers::insert_rtc_with_room(&mut conn, &room).await;
+
+            let (_, agent_connection) = shared_helpers::insert_connected_to_handle_agent(
+                &mut conn,
+                agent.agent_id(),
+                rtc.room_id(),
+                rtc.id(),
+                user_handle,
+            )
+            .await;
+
+            let classroom_id = room.classroom_id().to_string();
+            let rtc_id = rtc.id().to_string();
+            let object = vec!["classrooms", &classroom_id, "rtcs", &rtc_id];
+            authz.allow(agent.account_id(), object, "update");
+
+            let mut context = TestContext::new(db, authz).await;
+            let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+            context.with_janus(tx);
+            let rtc_stream_id = db::janus_rtc_stream::Id::random();
+            let handle_id = HandleId::new(
+                rtc_stream_id,
+                rtc.id(),
+                agent_connection.handle_id(),
+                backend.session_id(),
+                backend.id().to_owned(),
+            );
+
+            // The client switches networks and offers again with new ICE credentials.
+            let restart_offer = SDP_OFFER.replace("a=ice-ufrag:074c6550", "a=ice-ufrag:5e1d3a09");
+
+            for sdp in [SDP_OFFER, restart_offer.as_str()] {
+                let jsep = serde_json::from_value::<Jsep>(json!({ "type": "offer", "sdp": sdp }))
+                    .expect("Failed to build JSEP");
+
+                let payload = CreateRequest {
+                    handle_id: handle_id.clone(),
+                    jsep,
+                    label: Some(String::from("whatever")),
+                    agent_label: None,
+                };
+
+                handle_request::<CreateHandler>(&mut context, &agent, payload)
+                    .await
+                    .expect("Rtc signal creation failed");
+                rx.recv().await.unwrap();
+            }
+
+            context.janus_clients().remove_client(&backend);
+
+            // The renegotiation goes to the same handle and keeps the stream.
+            let mut conn = context.get_conn().await.unwrap();
+            let rtc_streams = db::janus_rtc_stream::ListQuery::new()
+                .rtc_id(rtc.id())
+                .execute(&mut conn)
+                .await
+                .expect("Failed to list rtc streams");
+
+            assert_eq!(rtc_streams.len(), 1);
+            assert_eq!(rtc_streams[0].id(), rtc_stream_id);
+            assert_eq!(rtc_streams[0].handle_id(), handle_id.janus_handle_id());
+            Ok(())
+        }
+
         #[sqlx::test]
         async fn offer_unauthorized(pool: sqlx::PgPool) -> std::io::Result<()> {
             let db = TestDb::new(pool);
@@ -1768,7 +2233,7 @@ a=rtcp-fb:120 ccm fir
                 .await
                 .unwrap()
                 .unwrap();
-            assert_ne!(room.time().1, Bound::Unbounded);
+            assert_ne!(room.time().end(), Bound::Unbounded);
         }
     }
 }
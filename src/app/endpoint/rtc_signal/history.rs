@@ -0,0 +1,163 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+use svc_agent::{mqtt::ResponseStatus, AgentId};
+use svc_authn::Authenticable;
+use tracing_attributes::instrument;
+
+use crate::{
+    app::{
+        context::Context,
+        endpoint::prelude::*,
+        service_utils::{RequestParams, Response},
+    },
+    authz::AuthzObject,
+    db,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    rtc_id: db::rtc::Id,
+    #[serde(default)]
+    agent_id: Option<AgentId>,
+}
+
+pub struct Handler;
+
+#[async_trait]
+impl RequestHandler for Handler {
+    type Payload = Request;
+    const ERROR_TITLE: &'static str = "Failed to read signal history";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::SYSTEM_READ);
+
+    #[instrument(skip(context, reqp), fields(rtc_id = %payload.rtc_id))]
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        // Authorization: SDPs reveal network details of the participants so only trusted
+        // subjects may read them.
+        let audience = context.agent_id().as_account_id().audience();
+
+        let authz_time = context
+            .authz()
+            .authorize(
+                audience.into(),
+                reqp,
+                AuthzObject::new(&["system"]).into(),
+                "read".into(),
+            )
+            .await?;
+        context.metrics().observe_auth(authz_time);
+
+        let mut conn = context.get_conn().await?;
+        let artifacts =
+            db::rtc_signal_artifact::list(payload.rtc_id, payload.agent_id.as_ref(), &mut conn)
+                .await?;
+
+        Ok(Response::new(
+            ResponseStatus::OK,
+            artifacts,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        db::rtc_signal_artifact::Kind,
+        test_helpers::{db::TestDb, prelude::*},
+    };
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn read_capped_history(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let mut conn = db.get_conn().await;
+
+        let room = shared_helpers::insert_room(&mut conn).await;
+        let rtc = shared_helpers::insert_rtc_with_room(&mut conn, &room).await;
+        let user = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        // Nothing is kept until debugging is on.
+        let stored = db::rtc_signal_artifact::InsertQuery::new(
+            room.id(),
+            rtc.id(),
+            user.agent_id(),
+            Kind::Offer,
+            "v=0",
+        )
+        .execute(2, &mut conn)
+        .await
+        .expect("Failed to insert artifact");
+        assert!(!stored);
+
+        db::rtc_signal_artifact::enable(room.id(), &mut conn)
+            .await
+            .expect("Failed to enable debugging");
+
+        for (kind, body) in [
+            (Kind::Offer, "offer"),
+            (Kind::Candidates, "candidates"),
+            (Kind::Answer, "answer"),
+        ] {
+            db::rtc_signal_artifact::InsertQuery::new(
+                room.id(),
+                rtc.id(),
+                user.agent_id(),
+                kind,
+                body,
+            )
+            .execute(2, &mut conn)
+            .await
+            .expect("Failed to insert artifact");
+        }
+
+        let mut authz = TestAuthz::new();
+        authz.set_audience(SVC_AUDIENCE);
+        let admin = TestAgent::new("alpha", "support", SVC_AUDIENCE);
+        authz.allow(admin.account_id(), vec!["system"], "read");
+
+        let mut context = TestContext::new(db, authz).await;
+
+        let payload = Request {
+            rtc_id: rtc.id(),
+            agent_id: Some(user.agent_id().to_owned()),
+        };
+
+        let messages = handle_request::<Handler>(&mut context, &admin, payload)
+            .await
+            .expect("Signal history reading failed");
+
+        let (artifacts, respp, _) =
+            find_response::<Vec<db::rtc_signal_artifact::Object>>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+
+        let bodies = artifacts
+            .iter()
+            .map(|a| a.body.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(bodies, vec!["answer", "candidates"]);
+    }
+
+    #[sqlx::test]
+    async fn read_history_unauthorized(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let mut context = TestContext::new(db, TestAuthz::new()).await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let payload = Request {
+            rtc_id: db::rtc::Id::random(),
+            agent_id: None,
+        };
+
+        let err = handle_request::<Handler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success reading signal history");
+
+        assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+    }
+}
@@ -15,25 +15,32 @@ use crate::{
     app::{
         context::{AppContext, Context},
         endpoint::prelude::*,
+        message_handler::http,
         metrics::HistogramExt,
+        pseudonym::Pseudonyms,
         service_utils::{RequestParams, Response},
     },
     authz::AuthzObject,
     db,
 };
 use tracing_attributes::instrument;
+use uuid::Uuid;
 
 ////////////////////////////////////////////////////////////////////////////////
 
-const MAX_LIMIT: i64 = 25;
+const MAX_LIMIT: i64 = helpers::LIST_MAX_LIMIT;
 
 #[derive(Debug, Deserialize)]
 pub struct ListRequest {
-    room_id: db::room::Id,
+    #[serde(default)]
+    room_id: Option<db::room::Id>,
+    /// Addresses the classroom's room when `room_id` is missing.
+    #[serde(default)]
+    classroom_id: Option<Uuid>,
     rtc_id: Option<db::rtc::Id>,
     #[serde(default)]
     #[serde(with = "crate::serde::ts_seconds_option_bound_tuple")]
-    time: Option<db::room::Time>,
+    time: Option<db::janus_rtc_stream::Time>,
     offset: Option<i64>,
     limit: Option<i64>,
 }
@@ -43,7 +50,7 @@ pub struct ListParams {
     rtc_id: Option<db::rtc::Id>,
     #[serde(default)]
     #[serde(with = "crate::serde::ts_seconds_option_bound_tuple")]
-    time: Option<db::room::Time>,
+    time: Option<db::janus_rtc_stream::Time>,
     offset: Option<i64>,
     limit: Option<i64>,
 }
@@ -58,28 +65,23 @@ pub async fn list(
 
     let request = match query {
         Some(x) => ListRequest {
-            room_id,
+            room_id: Some(room_id),
+            classroom_id: None,
             rtc_id: x.rtc_id,
             time: x.time,
             offset: x.offset,
             limit: x.limit,
         },
         None => ListRequest {
-            room_id,
+            room_id: Some(room_id),
+            classroom_id: None,
             rtc_id: None,
             time: None,
             offset: None,
             limit: None,
         },
     };
-    ListHandler::handle(
-        &mut ctx.start_message(),
-        request,
-        RequestParams::Http {
-            agent_id: &agent_id,
-        },
-    )
-    .await
+    http::handle::<ListHandler>(&ctx, &agent_id, request).await
 }
 
 pub struct ListHandler;
@@ -88,18 +90,22 @@ pub struct ListHandler;
 impl RequestHandler for ListHandler {
     type Payload = ListRequest;
     const ERROR_TITLE: &'static str = "Failed to list rtc streams";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::CLASSROOM_READ);
 
-    #[instrument(skip(context, payload, reqp), fields(rtc_id = ?payload.rtc_id, room_id = %payload.room_id))]
+    #[instrument(skip(context, payload, reqp), fields(rtc_id = ?payload.rtc_id, room_id = ?payload.room_id))]
     async fn handle<C: Context + Send + Sync>(
         context: &mut C,
         payload: Self::Payload,
         reqp: RequestParams<'_>,
     ) -> RequestResult {
+        let room_ref = helpers::RoomRef::new(payload.room_id, payload.classroom_id)?;
+
         let room = {
             let mut conn = context.get_conn().await?;
-            helpers::find_room_by_id(
-                payload.room_id,
+            helpers::find_room_by_ref(
+                room_ref,
                 helpers::RoomTimeRequirement::Open,
+                helpers::AudienceScope::caller(context, &reqp),
                 &mut conn,
             )
             .await?
@@ -128,7 +134,7 @@ impl RequestHandler for ListHandler {
             .await?;
         context.metrics().observe_auth(authz_time);
 
-        let mut query = db::janus_rtc_stream::ListQuery::new().room_id(payload.room_id);
+        let mut query = db::janus_rtc_stream::ListQuery::new().room_id(room.id());
         if let Some(rtc_id) = payload.rtc_id {
             query = query.rtc_id(rtc_id);
         }
@@ -166,7 +172,13 @@ pub fn update_event(
     room_id: db::room::Id,
     object: db::janus_rtc_stream::Object,
     start_timestamp: DateTime<Utc>,
+    pseudonyms: &Pseudonyms,
 ) -> ObjectUpdateEvent {
+    let object = match pseudonyms.agent_id(object.sent_by()) {
+        Some(sent_by) => object.with_sent_by(sent_by),
+        None => object,
+    };
+
     let uri = format!("rooms/{room_id}/events");
     let timing = ShortTermTimingProperties::until_now(start_timestamp);
     let props = OutgoingEventProperties::new("rtc_stream.update", timing);
@@ -225,6 +237,7 @@ mod test {
                 let room = helpers::find_room_by_id(
                     rtc.room_id(),
                     helpers::RoomTimeRequirement::Open,
+                    helpers::AudienceScope::Unrestricted,
                     &mut conn,
                 )
                 .await
@@ -242,7 +255,8 @@ mod test {
             let mut context = TestContext::new(db, authz).await;
 
             let payload = ListRequest {
-                room_id: rtc.room_id(),
+                room_id: Some(rtc.room_id()),
+                classroom_id: None,
                 rtc_id: Some(rtc.id()),
                 time: None,
                 offset: None,
@@ -289,7 +303,8 @@ mod test {
             let mut context = TestContext::new(db, TestAuthz::new()).await;
 
             let payload = ListRequest {
-                room_id: room.id(),
+                room_id: Some(room.id()),
+                classroom_id: None,
                 rtc_id: None,
                 time: None,
                 offset: None,
@@ -312,7 +327,8 @@ mod test {
             let mut context = TestContext::new(db, TestAuthz::new()).await;
 
             let payload = ListRequest {
-                room_id: db::room::Id::random(),
+                room_id: Some(db::room::Id::random()),
+                classroom_id: None,
                 rtc_id: None,
                 time: None,
                 offset: None,
@@ -15,7 +15,7 @@ use svc_agent::{
 
 use crate::{
     app::{context::Context, endpoint::prelude::*, metrics::HistogramExt},
-    db::{self, room::FindQueryable},
+    db,
 };
 use tracing_attributes::instrument;
 
@@ -88,7 +88,7 @@ impl ResponseHandler for DeleteResponseHandler {
             let notification = helpers::build_notification(
                 "room.leave",
                 &format!("rooms/{room_id}/events"),
-                RoomEnterLeaveEvent::new(room_id, corr_data.subject.to_owned()),
+                RoomEnterLeaveEvent::new(room_id, leaving_agent(context, &corr_data.subject)),
                 corr_data.reqp.tracking(),
                 context.start_timestamp(),
             );
@@ -105,6 +105,45 @@ impl ResponseHandler for DeleteResponseHandler {
     }
 }
 
+#[derive(Debug, Deserialize)]
+pub struct CreateEventPayload {
+    subject: AgentId,
+    object: Vec<String>,
+}
+
+/// Confirms the broker subscription `room.enter` asked for, moving a `requested` agent
+/// to `subscribed`. Covers the case when the broker has subscribed the agent but the
+/// dynsub response never made it back to `room.enter`.
+pub struct CreateEventHandler;
+
+#[async_trait]
+impl EventHandler for CreateEventHandler {
+    type Payload = CreateEventPayload;
+
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        evp: &IncomingEventProperties,
+    ) -> MqttResult {
+        ensure_broker(context, evp)?;
+        let room_id = try_room_id(&payload.object)?;
+
+        let mut conn = context.get_conn().await?;
+        db::agent::UpdateQuery::new(&payload.subject, room_id)
+            .status(db::agent::Status::Subscribed)
+            .execute(&mut conn)
+            .await?;
+
+        context
+            .metrics()
+            .request_duration
+            .subscription_create
+            .observe_timestamp(context.start_timestamp());
+
+        Ok(Box::new(stream::empty()))
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct DeleteEventPayload {
     subject: AgentId,
@@ -126,7 +165,7 @@ impl EventHandler for DeleteEventHandler {
         let room_id = try_room_id(&payload.object)?;
         if leave_room(context, &payload.subject, room_id).await? {
             let outgoing_event_payload =
-                RoomEnterLeaveEvent::new(room_id, payload.subject.to_owned());
+                RoomEnterLeaveEvent::new(room_id, leaving_agent(context, &payload.subject));
             let short_term_timing = ShortTermTimingProperties::until_now(context.start_timestamp());
             let props = evp.to_event("room.leave", short_term_timing);
             let to_uri = format!("rooms/{room_id}/events");
@@ -156,7 +195,7 @@ fn ensure_broker<C: Context, A: Addressable>(
         Ok(())
     } else {
         Err(anyhow!(
-            "Expected subscription.delete event to be sent from the broker account '{}', got '{}'",
+            "Expected subscription message to be sent from the broker account '{}', got '{}'",
             context.config().broker_id,
             sender.as_account_id()
         ))
@@ -179,6 +218,14 @@ fn try_room_id(object: &[String]) -> StdResult<db::room::Id, AppError> {
     .error(AppErrorKind::InvalidSubscriptionObject)
 }
 
+/// The agent as other participants see it in `room.leave`.
+fn leaving_agent<C: Context>(context: &C, agent_id: &AgentId) -> AgentId {
+    context
+        .pseudonyms()
+        .agent_id(agent_id)
+        .unwrap_or_else(|| agent_id.to_owned())
+}
+
 #[instrument(skip(context))]
 async fn leave_room<C: Context>(
     context: &mut C,
@@ -221,11 +268,10 @@ async fn make_orphaned_if_host_left(
     agent_left: &AgentId,
     connection: &mut sqlx::PgConnection,
 ) -> sqlx::Result<()> {
-    let room = db::room::FindQuery::new(room_id)
-        .execute(connection)
-        .await?;
-
-    if room.as_ref().and_then(|x| x.host()) == Some(agent_left) {
+    // The room is left without hosts only when the last of them present leaves.
+    if db::room_host::is_host(room_id, agent_left, connection).await?
+        && !db::room_host::any_present(room_id, agent_left, connection).await?
+    {
         db::orphaned_room::upsert_room(room_id, Utc::now(), connection).await?;
     }
     Ok(())
@@ -240,7 +286,7 @@ mod tests {
 
         use crate::{
             app::API_VERSION,
-            db::agent::ListQuery as AgentListQuery,
+            db::agent::{ListQuery as AgentListQuery, Status as AgentStatus},
             test_helpers::{db::TestDb, prelude::*},
         };
 
@@ -311,6 +357,7 @@ mod tests {
                 .expect("Failed to get DB connection");
 
             let db_agents = AgentListQuery::new()
+                .status(AgentStatus::Ready)
                 .agent_id(agent.agent_id())
                 .room_id(room.id())
                 .execute(&mut conn)
@@ -386,9 +433,97 @@ mod tests {
         }
     }
 
+    mod create_event {
+        use crate::{
+            db::agent::{ListQuery as AgentListQuery, Status as AgentStatus},
+            test_helpers::{db::TestDb, prelude::*},
+        };
+
+        use super::super::*;
+
+        #[sqlx::test]
+        async fn create_subscription(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+            let mut conn = db.get_conn().await;
+
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+            let room = shared_helpers::insert_room(&mut conn).await;
+
+            factory::Agent::new()
+                .agent_id(agent.agent_id())
+                .room_id(room.id())
+                .status(AgentStatus::Requested)
+                .insert(&mut conn)
+                .await;
+
+            let mut context = TestContext::new(db, TestAuthz::new()).await;
+
+            let payload = CreateEventPayload {
+                subject: agent.agent_id().to_owned(),
+                object: vec![
+                    "rooms".to_string(),
+                    room.id().to_string(),
+                    "events".to_string(),
+                ],
+            };
+
+            let broker_account_label = context.config().broker_id.label();
+            let broker = TestAgent::new("alpha", broker_account_label, SVC_AUDIENCE);
+
+            let messages = handle_event::<CreateEventHandler>(&mut context, &broker, payload)
+                .await
+                .expect("Subscription creation failed");
+
+            assert!(messages.is_empty());
+
+            let mut conn = context
+                .get_conn()
+                .await
+                .expect("Failed to get DB connection");
+
+            let db_agents = AgentListQuery::new()
+                .agent_id(agent.agent_id())
+                .room_id(room.id())
+                .execute(&mut conn)
+                .await
+                .expect("Failed to execute agent list query");
+
+            assert_eq!(db_agents.len(), 1);
+            assert_eq!(db_agents[0].status(), AgentStatus::Subscribed);
+        }
+
+        #[sqlx::test]
+        async fn create_subscription_not_from_broker(pool: sqlx::PgPool) {
+            let db = TestDb::new(pool);
+            let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+            let room = {
+                let mut conn = db.get_conn().await;
+                shared_helpers::insert_room(&mut conn).await
+            };
+
+            let mut context = TestContext::new(db, TestAuthz::new()).await;
+
+            let payload = CreateEventPayload {
+                subject: agent.agent_id().to_owned(),
+                object: vec![
+                    "rooms".to_string(),
+                    room.id().to_string(),
+                    "events".to_string(),
+                ],
+            };
+
+            let err = handle_event::<CreateEventHandler>(&mut context, &agent, payload)
+                .await
+                .expect_err("Unexpected success on subscription creation");
+
+            assert_eq!(err.kind(), "access_denied");
+        }
+    }
+
     mod delete_event {
         use crate::{
-            db::agent::ListQuery as AgentListQuery,
+            db::agent::{ListQuery as AgentListQuery, Status as AgentStatus},
             test_helpers::{db::TestDb, prelude::*},
         };
 
@@ -440,6 +575,7 @@ mod tests {
                 .expect("Failed to get DB connection");
 
             let db_agents = AgentListQuery::new()
+                .status(AgentStatus::Ready)
                 .agent_id(agent.agent_id())
                 .room_id(room.id())
                 .execute(&mut conn)
@@ -449,6 +585,7 @@ mod tests {
             assert_eq!(db_agents.len(), 0);
 
             let db_agents = AgentListQuery::new()
+                .status(AgentStatus::Ready)
                 .agent_id(agent.agent_id())
                 .room_id(old_room.id())
                 .execute(&mut conn)
@@ -3,15 +3,25 @@ use crate::{
         context::Context,
         endpoint::prelude::*,
         error::Error as AppError,
+        room_upload,
         service_utils::{RequestParams, Response},
     },
     authz::AuthzObject,
-    backend::janus::client::upload_stream::{
-        UploadStreamRequest, UploadStreamRequestBody, UploadStreamTransaction,
+    backend::janus::{
+        self,
+        client::{
+            composite::{
+                UploadCompositeRequest, UploadCompositeRequestBody, UploadCompositeTransaction,
+            },
+            upload_stream::{
+                UploadStreamRequest, UploadStreamRequestBody, UploadStreamTransaction,
+            },
+        },
     },
-    config::UploadConfig,
+    config::{UploadConfig, VacuumConfig},
     db,
     db::{
+        janus_backend::Object as JanusBackend,
         recording::{Object as Recording, Status as RecordingStatus},
         room::Object as Room,
         rtc::SharingPolicy,
@@ -22,8 +32,8 @@ use async_trait::async_trait;
 use chrono::Utc;
 use futures::stream;
 use serde::{Deserialize, Serialize};
-use serde_json::json;
-use std::{ops::Bound, result::Result as StdResult};
+use serde_json::Value as JsonValue;
+use std::{collections::HashMap, result::Result as StdResult};
 use svc_agent::{
     mqtt::{
         IncomingEventProperties, OutgoingEvent, OutgoingEventProperties, OutgoingMessage,
@@ -33,7 +43,7 @@ use svc_agent::{
 };
 use svc_authn::Authenticable;
 
-use tracing::error;
+use tracing::{error, info};
 use tracing_attributes::instrument;
 
 use super::MqttResult;
@@ -44,6 +54,17 @@ use super::MqttResult;
 pub struct RoomUploadEventData {
     id: db::room::Id,
     rtcs: Vec<RtcUploadEventData>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    composite: Option<FileUploadEventData>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    messages: Option<FileUploadEventData>,
+}
+
+#[derive(Debug, Serialize)]
+struct FileUploadEventData {
+    status: RecordingStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uri: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -54,6 +75,12 @@ struct RtcUploadEventData {
     uri: Option<String>,
     created_by: AgentId,
     mjr_dumps_uris: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tags: Option<JsonValue>,
+    kind: db::rtc::Kind,
+    /// MD5 of the uploaded file if the backend has reported it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    checksum: Option<String>,
 }
 
 pub type RoomUploadEvent = OutgoingMessage<RoomUploadEventData>;
@@ -68,12 +95,19 @@ struct ClosedRoomNotification {
 #[derive(Debug, Deserialize)]
 pub struct VacuumRequest {}
 
+#[derive(Debug, Serialize)]
+struct VacuumResponseData {
+    /// Recordings marked as missing without an upload since no media has ever been streamed.
+    skipped_empty_recordings: usize,
+}
+
 pub struct VacuumHandler;
 
 #[async_trait]
 impl RequestHandler for VacuumHandler {
     type Payload = VacuumRequest;
     const ERROR_TITLE: &'static str = "Failed to vacuum system";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::SYSTEM_UPDATE);
 
     #[instrument(skip(context, _payload, reqp))]
     async fn handle<C: Context + Send + Sync>(
@@ -94,58 +128,84 @@ impl RequestHandler for VacuumHandler {
             )
             .await?;
 
+        let mut conn = context.get_conn().await?;
+        let rooms = db::room::finished_with_in_progress_recordings(
+            &mut conn,
+            context.config().janus_group.as_deref(),
+            None,
+        )
+        .await?;
+
+        let EmptyRecordings {
+            skipped,
+            empty_rooms,
+            rooms,
+        } = split_empty_recordings(rooms, &mut conn).await?;
+
         let mut response = Response::new(
-            ResponseStatus::NO_CONTENT,
-            json!({}),
+            ResponseStatus::OK,
+            VacuumResponseData {
+                skipped_empty_recordings: skipped,
+            },
             context.start_timestamp(),
             None,
         );
 
-        let mut conn = context.get_conn().await?;
-        let rooms = db::room::finished_with_in_progress_recordings(
-            &mut conn,
+        for room in empty_rooms {
+            close_empty_room(context, room, &mut response, &mut conn).await?;
+        }
+
+        let rooms = prioritize(rooms, &context.config().vacuum, |(room, _, _)| {
+            room.audience()
+        });
+
+        for (room, recording, backend) in rooms.into_iter() {
+            upload_recording(context, room, recording, backend, &mut response, &mut conn).await?;
+        }
+
+        let composites = db::composite_recording::finished_in_progress(
             context.config().janus_group.as_deref(),
+            &mut conn,
         )
         .await?;
 
-        for (room, recording, backend) in rooms.into_iter() {
-            db::agent::DeleteQuery::new()
-                .room_id(room.id())
-                .execute(&mut conn)
-                .await?;
+        for (room_id, backend) in composites {
+            let room = helpers::find_room_by_id(
+                room_id,
+                helpers::RoomTimeRequirement::Any,
+                helpers::AudienceScope::caller(context, &reqp),
+                &mut conn,
+            )
+            .await?;
 
             let config = upload_config(context, &room)?;
-            let request = UploadStreamRequest {
-                body: UploadStreamRequestBody::new(
-                    recording.rtc_id(),
+            let request = UploadCompositeRequest {
+                body: UploadCompositeRequestBody::new(
+                    room.id(),
                     &config.backend,
                     &config.bucket,
-                ),
+                    &composite_record_name(&room),
+                )
+                .storage_class(upload_storage_class(context, &room)),
                 handle_id: backend.handle_id(),
                 session_id: backend.session_id(),
             };
-            let transaction = UploadStreamTransaction {
-                rtc_id: recording.rtc_id(),
+            let transaction = UploadCompositeTransaction {
+                room_id: room.id(),
                 start_timestamp: context.start_timestamp(),
             };
-            // TODO: Send the error as an event to "app/${APP}/audiences/${AUD}" topic
             context
                 .janus_clients()
                 .get_or_insert(&backend)
                 .error(AppErrorKind::BackendClientCreationFailed)?
-                .upload_stream(request, transaction)
+                .upload_composite(request, transaction)
                 .await
                 .error(AppErrorKind::BackendRequestFailed)?;
-
-            // Publish room closed notification
-            response.add_notification(
-                "room.close",
-                &format!("rooms/{}/events", room.id()),
-                room,
-                context.start_timestamp(),
-            );
         }
 
+        let retained_after = Utc::now() - chrono::Duration::days(db::backend_error::RETENTION_DAYS);
+        db::backend_error::trim(db::backend_error::MAX_ROWS, retained_after, &mut conn).await?;
+
         Ok(response)
     }
 }
@@ -194,7 +254,7 @@ impl EventHandler for OrphanedRoomCloseHandler {
                 match room {
                     Some(room) if !room.is_closed() => {
                         let r = db::room::UpdateQuery::new(room.id())
-                            .time(Some((room.time().0, Bound::Excluded(Utc::now()))))
+                            .time(Some(room.time().close_now()))
                             .timed_out()
                             .execute(&mut conn)
                             .await;
@@ -202,6 +262,29 @@ impl EventHandler for OrphanedRoomCloseHandler {
                         match r {
                             Ok(room) => {
                                 closed_rooms.push(room.id());
+
+                                if let Err(err) = room_upload::schedule(
+                                    &context.config().room_upload,
+                                    &room,
+                                    &mut conn,
+                                )
+                                .await
+                                {
+                                    error!(?err, "Scheduling room upload failed");
+                                }
+
+                                if let Some(summary) =
+                                    helpers::summarize_room(room.id(), &mut conn).await
+                                {
+                                    notifications.push(helpers::build_notification(
+                                        "room.summary",
+                                        &format!("audiences/{}/events", room.audience()),
+                                        summary,
+                                        evp.tracking(),
+                                        context.start_timestamp(),
+                                    ));
+                                }
+
                                 notifications.push(helpers::build_notification(
                                     "room.close",
                                     &format!("rooms/{}/events", room.id()),
@@ -244,6 +327,8 @@ pub fn upload_event<C: Context, I>(
     context: &C,
     room: &db::room::Object,
     recordings: I,
+    composite: Option<db::composite_recording::Object>,
+    messages: Option<RecordingStatus>,
 ) -> StdResult<RoomUploadEvent, AppError>
 where
     I: Iterator<Item = (db::recording::Object, db::rtc::Object)>,
@@ -274,11 +359,49 @@ where
             uri,
             created_by: rtc.created_by().to_owned(),
             mjr_dumps_uris: recording.mjr_dumps_uris().cloned(),
+            tags: rtc.tags().cloned(),
+            kind: rtc.kind(),
+            checksum: recording.checksum().map(ToOwned::to_owned),
         };
 
         event_entries.push(entry);
     }
 
+    let composite = match composite {
+        Some(composite) => {
+            let uri = match composite.status() {
+                RecordingStatus::Ready => Some(format!(
+                    "s3://{}/{}",
+                    &upload_config(context, room)?.bucket,
+                    composite_record_name(room)
+                )),
+                _ => None,
+            };
+
+            Some(FileUploadEventData {
+                status: composite.status(),
+                uri,
+            })
+        }
+        None => None,
+    };
+
+    let messages = match messages {
+        Some(status) => {
+            let uri = match status {
+                RecordingStatus::Ready => Some(format!(
+                    "s3://{}/{}",
+                    &upload_config(context, room)?.bucket,
+                    messages_record_name(room)
+                )),
+                _ => None,
+            };
+
+            Some(FileUploadEventData { status, uri })
+        }
+        None => None,
+    };
+
     let uri = format!("audiences/{}/events", room.audience());
     let timing = ShortTermTimingProperties::until_now(context.start_timestamp());
     let props = OutgoingEventProperties::new("room.upload", timing);
@@ -286,12 +409,177 @@ where
     let event = RoomUploadEventData {
         id: room.id(),
         rtcs: event_entries,
+        composite,
+        messages,
     };
 
     Ok(OutgoingEvent::broadcast(event, props, &uri))
 }
 
-fn upload_config<'a, C: Context>(
+pub(crate) struct EmptyRecordings {
+    /// Number of recordings marked as missing.
+    pub skipped: usize,
+    /// Rooms with nothing left to upload.
+    pub empty_rooms: Vec<Room>,
+    /// Recordings to upload.
+    pub rooms: Vec<(Room, Recording, JanusBackend)>,
+}
+
+/// Marks recordings which have never got any media as missing and splits them off the rest.
+pub(crate) async fn split_empty_recordings(
+    rooms: Vec<(Room, Recording, JanusBackend)>,
+    conn: &mut sqlx::PgConnection,
+) -> StdResult<EmptyRecordings, AppError> {
+    // Uploading recordings which have never got any media is only a waste of the plugin's
+    // time so they're marked as missing right away and don't count against the caps.
+    let rtc_ids = rooms
+        .iter()
+        .map(|(_, recording, _)| recording.rtc_id())
+        .collect::<Vec<_>>();
+
+    let empty_rtc_ids = db::recording::mark_empty_as_missing(&rtc_ids, conn).await?;
+
+    let (empty, rooms): (Vec<_>, Vec<_>) = rooms
+        .into_iter()
+        .partition(|(_, recording, _)| empty_rtc_ids.contains(&recording.rtc_id()));
+
+    let skipped = empty.len();
+    let mut empty_rooms = HashMap::new();
+
+    for (room, _, _) in empty {
+        empty_rooms.entry(room.id()).or_insert(room);
+    }
+
+    Ok(EmptyRecordings {
+        skipped,
+        empty_rooms: empty_rooms.into_values().collect(),
+        rooms,
+    })
+}
+
+/// Finishes the room whose recordings are all empty without uploading anything.
+pub(crate) async fn close_empty_room<C: Context>(
+    context: &C,
+    room: Room,
+    response: &mut Response,
+    conn: &mut sqlx::PgConnection,
+) -> StdResult<(), AppError> {
+    info!(room_id = %room.id(), "Skipping upload of empty recordings");
+
+    db::agent::DeleteQuery::new()
+        .room_id(room.id())
+        .execute(conn)
+        .await?;
+
+    // Nothing is going to be uploaded for the room if all of its recordings are empty.
+    if let Some(event) = janus::room_upload_event(context, &room).await? {
+        response.add_message(Box::new(event));
+    }
+
+    notify_room_closed(context, room, response, conn).await;
+    Ok(())
+}
+
+/// Asks the backend to upload the recording of the finished room.
+pub(crate) async fn upload_recording<C: Context>(
+    context: &C,
+    room: Room,
+    recording: Recording,
+    backend: JanusBackend,
+    response: &mut Response,
+    conn: &mut sqlx::PgConnection,
+) -> StdResult<(), AppError> {
+    db::agent::DeleteQuery::new()
+        .room_id(room.id())
+        .execute(conn)
+        .await?;
+
+    let config = upload_config(context, &room)?;
+    let request = UploadStreamRequest {
+        body: UploadStreamRequestBody::new(recording.rtc_id(), &config.backend, &config.bucket)
+            .storage_class(upload_storage_class(context, &room)),
+        handle_id: backend.handle_id(),
+        session_id: backend.session_id(),
+    };
+    let transaction = UploadStreamTransaction {
+        rtc_id: recording.rtc_id(),
+        start_timestamp: context.start_timestamp(),
+    };
+    // TODO: Send the error as an event to "app/${APP}/audiences/${AUD}" topic
+    context
+        .janus_clients()
+        .get_or_insert(&backend)
+        .error(AppErrorKind::BackendClientCreationFailed)?
+        .upload_stream(request, transaction)
+        .await
+        .error(AppErrorKind::BackendRequestFailed)?;
+
+    notify_room_closed(context, room, response, conn).await;
+    Ok(())
+}
+
+async fn notify_room_closed<C: Context>(
+    context: &C,
+    room: Room,
+    response: &mut Response,
+    conn: &mut sqlx::PgConnection,
+) {
+    if let Some(summary) = helpers::summarize_room(room.id(), conn).await {
+        response.add_notification(
+            "room.summary",
+            &format!("audiences/{}/events", room.audience()),
+            summary,
+            context.start_timestamp(),
+        );
+    }
+
+    // Publish room closed notification
+    response.add_notification(
+        "room.close",
+        &format!("rooms/{}/events", room.id()),
+        room,
+        context.start_timestamp(),
+    );
+}
+
+/// Moves the uploads of audiences with a higher priority forward keeping the order within
+/// the same priority and leaves the ones over the audience's cap for the next run.
+fn prioritize<T>(
+    mut items: Vec<T>,
+    config: &VacuumConfig,
+    audience: impl Fn(&T) -> &str,
+) -> Vec<T> {
+    // The sort is stable so rooms closed earlier still go first.
+    items.sort_by_key(|item| std::cmp::Reverse(config.priority(audience(item))));
+
+    let cap = match config.max_uploads_per_audience {
+        Some(cap) => cap,
+        None => return items,
+    };
+
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    let total = items.len();
+
+    let items: Vec<T> = items
+        .into_iter()
+        .filter(|item| {
+            let count = counts.entry(audience(item).to_owned()).or_insert(0);
+            *count += 1;
+            *count <= cap
+        })
+        .collect();
+
+    if items.len() < total {
+        info!(
+            deferred = total - items.len(),
+            "Vacuum uploads deferred by audience cap"
+        );
+    }
+
+    items
+}
+
+pub(crate) fn upload_config<'a, C: Context>(
     context: &'a C,
     room: &Room,
 ) -> StdResult<&'a UploadConfig, AppError> {
@@ -312,24 +600,94 @@ fn upload_config<'a, C: Context>(
         .error(AppErrorKind::ConfigKeyMissing)
 }
 
+/// Storage class hint for uploads of the room from its audience's archive policy.
+pub(crate) fn upload_storage_class<'a, C: Context>(context: &'a C, room: &Room) -> Option<&'a str> {
+    context
+        .config()
+        .recording_archive
+        .policy
+        .get(room.audience())?
+        .upload_storage_class
+        .as_deref()
+}
+
+/// Ending of the object name of an RTC's recording following its id.
+pub const RECORD_NAME_SUFFIX: &str = ".source.webm";
+
 fn record_name(recording: &Recording, room: &Room) -> String {
-    let prefix = match room.rtc_sharing_policy() {
+    rtc_record_name(recording.rtc_id(), room)
+}
+
+pub(crate) fn rtc_record_name(rtc_id: db::rtc::Id, room: &Room) -> String {
+    format!("{}{}{}", record_prefix(room), rtc_id, RECORD_NAME_SUFFIX)
+}
+
+fn composite_record_name(room: &Room) -> String {
+    format!("{}{}.composite.webm", record_prefix(room), room.id())
+}
+
+pub(crate) fn messages_record_name(room: &Room) -> String {
+    format!("{}{}.messages.json", record_prefix(room), room.id())
+}
+
+fn record_prefix(room: &Room) -> String {
+    match room.rtc_sharing_policy() {
         SharingPolicy::Owned => {
             format!("{}/", room.classroom_id())
         }
         _ => String::from(""),
-    };
-
-    format!("{}{}.source.webm", prefix, recording.rtc_id())
+    }
 }
 
 ///////////////////////////////////////////////////////////////////////////////
 
 mod agent_cleanup;
 mod agent_connection_cleanup;
+mod agent_locate;
+mod backend_assignment_export;
+mod backend_errors_list;
+mod backend_group_decommission;
+mod backend_list;
+mod drain_start;
+mod event_capture_start;
+mod event_capture_stop;
+mod permission_matrix;
+mod reader_config_reconcile;
+mod recording_read;
+mod relay_usage_read;
+mod room_bulk_close;
+mod room_bulk_close_read;
+mod room_health_list;
+mod rtc_migrate;
+mod signal_debug_update;
+mod stage_replay;
+mod stage_status;
+mod tenant_usage_read;
+mod validate_backend;
 
 pub use agent_cleanup::Handler as AgentCleanupHandler;
 pub use agent_connection_cleanup::Handler as AgentConnectionCleanupHandler;
+pub use agent_locate::Handler as AgentLocateHandler;
+pub use backend_assignment_export::Handler as BackendAssignmentExportHandler;
+pub use backend_errors_list::Handler as BackendErrorsListHandler;
+pub use backend_group_decommission::Handler as BackendGroupDecommissionHandler;
+pub use backend_list::Handler as BackendListHandler;
+pub use drain_start::drain_start;
+pub use event_capture_start::Handler as EventCaptureStartHandler;
+pub use event_capture_stop::Handler as EventCaptureStopHandler;
+pub use permission_matrix::Handler as PermissionMatrixHandler;
+pub use reader_config_reconcile::Handler as ReaderConfigReconcileHandler;
+pub use recording_read::Handler as RecordingReadHandler;
+pub use relay_usage_read::Handler as RelayUsageReadHandler;
+pub use room_bulk_close::Handler as RoomBulkCloseHandler;
+pub use room_bulk_close_read::Handler as RoomBulkCloseReadHandler;
+pub use room_health_list::Handler as RoomHealthListHandler;
+pub use rtc_migrate::Handler as RtcMigrateHandler;
+pub use signal_debug_update::Handler as SignalDebugUpdateHandler;
+pub use stage_replay::Handler as StageReplayHandler;
+pub use stage_status::Handler as StageStatusHandler;
+pub use tenant_usage_read::Handler as TenantUsageReadHandler;
+pub use validate_backend::Handler as ValidateBackendHandler;
 
 ///////////////////////////////////////////////////////////////////////////////
 
@@ -346,6 +704,7 @@ mod test {
                 context::TestContext,
                 db::TestDb,
                 handle_event,
+                outgoing_envelope::OutgoingEnvelopeProperties,
                 prelude::{GlobalContext, TestAgent},
                 shared_helpers, SVC_AUDIENCE,
             },
@@ -394,8 +753,14 @@ mod test {
             .await
             .expect("System vacuum failed");
 
-            let rooms: Vec<db::room::Object> =
-                messages.into_iter().map(|ev| ev.payload()).collect();
+            let (summaries, closes): (Vec<_>, Vec<_>) =
+                messages.into_iter().partition(|ev| match ev.properties() {
+                    OutgoingEnvelopeProperties::Event(evp) => evp.label() == "room.summary",
+                    _ => false,
+                });
+            assert_eq!(summaries.len(), 1);
+
+            let rooms: Vec<db::room::Object> = closes.into_iter().map(|ev| ev.payload()).collect();
             assert_eq!(rooms.len(), 2);
             assert!(rooms[0].timed_out());
             assert_eq!(rooms[0].id(), opened_room.id());
@@ -410,6 +775,65 @@ mod test {
         }
     }
 
+    mod prioritize {
+        use std::collections::HashMap;
+
+        use crate::config::VacuumConfig;
+
+        use super::super::prioritize;
+
+        fn config(max_uploads_per_audience: Option<usize>) -> VacuumConfig {
+            let mut audience_priority = HashMap::new();
+            audience_priority.insert("premium".to_owned(), 10);
+            audience_priority.insert("bulk".to_owned(), -1);
+
+            VacuumConfig {
+                audience_priority,
+                max_uploads_per_audience,
+            }
+        }
+
+        #[test]
+        fn order_by_priority_then_age() {
+            // Already sorted by close time as the query returns them.
+            let items = vec![
+                ("bulk", 1),
+                ("other", 2),
+                ("premium", 3),
+                ("other", 4),
+                ("premium", 5),
+            ];
+
+            let ordered = prioritize(items, &config(None), |(audience, _)| audience);
+
+            assert_eq!(
+                ordered,
+                vec![
+                    ("premium", 3),
+                    ("premium", 5),
+                    ("other", 2),
+                    ("other", 4),
+                    ("bulk", 1),
+                ]
+            );
+        }
+
+        #[test]
+        fn cap_uploads_per_audience() {
+            let items = vec![
+                ("bulk", 1),
+                ("bulk", 2),
+                ("premium", 3),
+                ("bulk", 4),
+                ("premium", 5),
+            ];
+
+            let ordered = prioritize(items, &config(Some(1)), |(audience, _)| audience);
+
+            assert_eq!(ordered, vec![("premium", 3), ("bulk", 1)]);
+        }
+    }
+
     mod vacuum {
         use svc_agent::mqtt::ResponseStatus;
 
@@ -459,6 +883,16 @@ mod test {
             for rtc in rtcs.iter() {
                 shared_helpers::insert_agent(&mut conn, agent.agent_id(), rtc.room_id()).await;
                 shared_helpers::insert_recording(&mut conn, rtc).await;
+
+                // Recordings without any media are skipped.
+                let rtc_stream = factory::JanusRtcStream::new(USR_AUDIENCE)
+                    .rtc(rtc)
+                    .insert(&mut conn)
+                    .await;
+
+                db::janus_rtc_stream::start(rtc_stream.id(), &mut conn)
+                    .await
+                    .expect("Failed to start rtc stream");
             }
 
             let rtcs = rtcs.into_iter().map(|x| x.id()).collect::<Vec<_>>();
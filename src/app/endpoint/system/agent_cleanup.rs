@@ -25,6 +25,7 @@ pub struct Handler;
 impl RequestHandler for Handler {
     type Payload = Request;
     const ERROR_TITLE: &'static str = "Failed to cleanup agents";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::SYSTEM_UPDATE);
 
     #[instrument(skip(context, _payload, reqp))]
     async fn handle<C: Context + Send + Sync>(
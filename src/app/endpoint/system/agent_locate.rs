@@ -0,0 +1,181 @@
+use async_trait::async_trait;
+use chrono::{serde::ts_seconds, DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use svc_agent::{mqtt::ResponseStatus, AccountId, AgentId};
+use svc_authn::Authenticable;
+use tracing_attributes::instrument;
+use uuid::Uuid;
+
+use crate::{
+    app::{
+        context::Context,
+        endpoint::prelude::*,
+        service_utils::{RequestParams, Response},
+    },
+    authz::AuthzObject,
+    db,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    account_id: AccountId,
+}
+
+/// Where an agent of the account is present right now.
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct Location {
+    agent_id: AgentId,
+    room_id: db::room::Id,
+    classroom_id: Uuid,
+    status: db::agent::Status,
+    #[serde(with = "ts_seconds")]
+    entered_at: DateTime<Utc>,
+    backend_id: Option<AgentId>,
+    connections: Vec<Connection>,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq)]
+pub struct Connection {
+    rtc_id: db::rtc::Id,
+    status: db::agent_connection::Status,
+}
+
+pub struct Handler;
+
+#[async_trait]
+impl RequestHandler for Handler {
+    type Payload = Request;
+    const ERROR_TITLE: &'static str = "Failed to locate agent";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::SYSTEM_READ);
+
+    #[instrument(skip(context, payload, reqp))]
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        // Authorization: only trusted subjects are allowed to perform operations with the system
+        let audience = context.agent_id().as_account_id().audience();
+
+        let authz_time = context
+            .authz()
+            .authorize(
+                audience.into(),
+                reqp,
+                AuthzObject::new(&["system"]).into(),
+                "read".into(),
+            )
+            .await?;
+        context.metrics().observe_auth(authz_time);
+
+        let mut conn = context.get_conn().await?;
+        let rows = db::agent::locate(&payload.account_id, &mut conn).await?;
+
+        Ok(Response::new(
+            ResponseStatus::OK,
+            group(rows),
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+/// Rows come ordered by agent so its connections are adjacent.
+fn group(rows: Vec<db::agent::Location>) -> Vec<Location> {
+    let mut locations: Vec<Location> = vec![];
+
+    for row in rows {
+        let connection = match (row.rtc_id, row.connection_status) {
+            (Some(rtc_id), Some(status)) => Some(Connection { rtc_id, status }),
+            _ => None,
+        };
+
+        match locations.last_mut() {
+            Some(last) if last.agent_id == row.agent_id && last.room_id == row.room_id => {
+                last.connections.extend(connection);
+            }
+            _ => locations.push(Location {
+                agent_id: row.agent_id,
+                room_id: row.room_id,
+                classroom_id: row.classroom_id,
+                status: row.status,
+                entered_at: row.entered_at,
+                backend_id: row.backend_id,
+                connections: connection.into_iter().collect(),
+            }),
+        }
+    }
+
+    locations
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_helpers::{db::TestDb, prelude::*};
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn locate_agent(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let mut conn = db.get_conn().await;
+
+        let user = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let other = TestAgent::new("web", "user456", USR_AUDIENCE);
+
+        let room = shared_helpers::insert_room(&mut conn).await;
+        let closed_room = shared_helpers::insert_closed_room(&mut conn).await;
+        let rtc = shared_helpers::insert_rtc_with_room(&mut conn, &room).await;
+
+        shared_helpers::insert_connected_agent(&mut conn, user.agent_id(), room.id(), rtc.id())
+            .await;
+        shared_helpers::insert_agent(&mut conn, user.agent_id(), closed_room.id()).await;
+        shared_helpers::insert_agent(&mut conn, other.agent_id(), room.id()).await;
+
+        let mut authz = TestAuthz::new();
+        authz.set_audience(SVC_AUDIENCE);
+        let admin = TestAgent::new("alpha", "support", SVC_AUDIENCE);
+        authz.allow(admin.account_id(), vec!["system"], "read");
+
+        let mut context = TestContext::new(db, authz).await;
+
+        let payload = Request {
+            account_id: user.account_id().to_owned(),
+        };
+
+        let messages = handle_request::<Handler>(&mut context, &admin, payload)
+            .await
+            .expect("Agent locating failed");
+
+        let (locations, respp, _) = find_response::<Vec<Location>>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert_eq!(locations.len(), 1);
+        assert_eq!(&locations[0].agent_id, user.agent_id());
+        assert_eq!(locations[0].room_id, room.id());
+        assert_eq!(locations[0].classroom_id, room.classroom_id());
+        assert_eq!(
+            locations[0].connections,
+            vec![Connection {
+                rtc_id: rtc.id(),
+                status: db::agent_connection::Status::InProgress,
+            }]
+        );
+    }
+
+    #[sqlx::test]
+    async fn locate_agent_unauthorized(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let mut context = TestContext::new(db, TestAuthz::new()).await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let payload = Request {
+            account_id: agent.account_id().to_owned(),
+        };
+
+        let err = handle_request::<Handler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success locating agent");
+
+        assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+    }
+}
@@ -0,0 +1,163 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use svc_agent::mqtt::ResponseStatus;
+use svc_authn::Authenticable;
+use tracing_attributes::instrument;
+
+use crate::{
+    app::{
+        context::Context,
+        endpoint::prelude::*,
+        service_utils::{RequestParams, Response},
+    },
+    authz::AuthzObject,
+    db,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    #[serde(default, with = "chrono::serde::ts_seconds_option")]
+    since: Option<DateTime<Utc>>,
+    #[serde(default, with = "chrono::serde::ts_seconds_option")]
+    until: Option<DateTime<Utc>>,
+}
+
+/// Weekly assignments of a backend group along with the region the group is in.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Entry {
+    #[serde(flatten)]
+    assignments: db::room_backend_assignment::WeeklyObject,
+    region: Option<String>,
+}
+
+pub struct Handler;
+
+#[async_trait]
+impl RequestHandler for Handler {
+    type Payload = Request;
+    const ERROR_TITLE: &'static str = "Failed to export backend assignments";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::SYSTEM_READ);
+
+    #[instrument(skip(context, payload, reqp))]
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        // Authorization: only trusted subjects are allowed to perform operations with the system
+        let audience = context.agent_id().as_account_id().audience();
+
+        let authz_time = context
+            .authz()
+            .authorize(
+                audience.into(),
+                reqp,
+                AuthzObject::new(&["system"]).into(),
+                "read".into(),
+            )
+            .await?;
+        context.metrics().observe_auth(authz_time);
+
+        let mut conn = context.get_conn().await?;
+        let weekly =
+            db::room_backend_assignment::weekly(payload.since, payload.until, &mut conn).await?;
+
+        // Regions are configured by group the same way `rtc.preflight` reports them.
+        let regions = &context.config().preflight.regions;
+
+        let entries = weekly
+            .into_iter()
+            .map(|assignments| Entry {
+                region: regions
+                    .get(assignments.group.as_deref().unwrap_or("default"))
+                    .cloned(),
+                assignments,
+            })
+            .collect::<Vec<_>>();
+
+        Ok(Response::new(
+            ResponseStatus::OK,
+            entries,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        backend::janus::client::{HandleId, SessionId},
+        test_helpers::{db::TestDb, prelude::*},
+    };
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn export_backend_assignments(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let mut conn = db.get_conn().await;
+
+        let backend = shared_helpers::insert_janus_backend_with_group(
+            &mut conn,
+            "test",
+            SessionId::random(),
+            HandleId::random(),
+            "webinar",
+        )
+        .await;
+
+        let other_backend = shared_helpers::insert_janus_backend_with_group(
+            &mut conn,
+            "test",
+            SessionId::random(),
+            HandleId::random(),
+            "webinar",
+        )
+        .await;
+
+        let room = shared_helpers::insert_room_with_backend_id(&mut conn, backend.id()).await;
+        shared_helpers::insert_room_with_backend_id(&mut conn, backend.id()).await;
+        // Rooms without a backend aren't assignments.
+        shared_helpers::insert_room(&mut conn).await;
+
+        // Move the first room to the other backend.
+        db::room::UpdateQuery::new(room.id())
+            .backend_id(Some(other_backend.id()))
+            .execute(&mut conn)
+            .await
+            .expect("Failed to reassign room");
+
+        let mut authz = TestAuthz::new();
+        authz.set_audience(SVC_AUDIENCE);
+        let agent = TestAgent::new("alpha", "cron", SVC_AUDIENCE);
+        authz.allow(agent.account_id(), vec!["system"], "read");
+
+        let mut context = TestContext::new(db, authz).await;
+
+        context.config_mut().preflight.regions =
+            [("webinar".to_owned(), "eu-central".to_owned())].into();
+
+        let payload = Request {
+            since: None,
+            until: None,
+        };
+
+        let messages = handle_request::<Handler>(&mut context, &agent, payload)
+            .await
+            .expect("Backend assignments export failed");
+
+        let (entries, respp, _) = find_response::<Vec<Entry>>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert_eq!(entries.len(), 1);
+
+        let entry = &entries[0];
+        assert_eq!(entry.assignments.group.as_deref(), Some("webinar"));
+        assert_eq!(entry.region.as_deref(), Some("eu-central"));
+        assert_eq!(entry.assignments.assignments, 2);
+        assert_eq!(entry.assignments.reassignments, 1);
+        assert_eq!(entry.assignments.rooms, 2);
+        assert_eq!(entry.assignments.backends, 2);
+    }
+}
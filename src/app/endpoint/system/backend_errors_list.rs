@@ -0,0 +1,179 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use svc_agent::{mqtt::ResponseStatus, AgentId};
+use svc_authn::Authenticable;
+use tracing_attributes::instrument;
+
+use crate::{
+    app::{
+        context::Context,
+        endpoint::prelude::*,
+        service_utils::{RequestParams, Response},
+    },
+    authz::AuthzObject,
+    db,
+};
+
+const MAX_LIMIT: i64 = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    backend_id: Option<AgentId>,
+    kind: Option<String>,
+    room_id: Option<db::room::Id>,
+    #[serde(default, with = "chrono::serde::ts_seconds_option")]
+    created_after: Option<DateTime<Utc>>,
+    offset: Option<i64>,
+    limit: Option<i64>,
+}
+
+pub struct Handler;
+
+#[async_trait]
+impl RequestHandler for Handler {
+    type Payload = Request;
+    const ERROR_TITLE: &'static str = "Failed to list backend errors";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::SYSTEM_READ);
+
+    #[instrument(skip(context, payload, reqp))]
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        // Authorization: only trusted subjects are allowed to perform operations with the system
+        let audience = context.agent_id().as_account_id().audience();
+
+        let authz_time = context
+            .authz()
+            .authorize(
+                audience.into(),
+                reqp,
+                AuthzObject::new(&["system"]).into(),
+                "read".into(),
+            )
+            .await?;
+        context.metrics().observe_auth(authz_time);
+
+        let mut query = db::backend_error::ListQuery::new()
+            .offset(payload.offset.unwrap_or(0))
+            .limit(std::cmp::min(payload.limit.unwrap_or(MAX_LIMIT), MAX_LIMIT));
+
+        if let Some(ref backend_id) = payload.backend_id {
+            query = query.backend_id(backend_id);
+        }
+
+        if let Some(ref kind) = payload.kind {
+            query = query.kind(kind);
+        }
+
+        if let Some(room_id) = payload.room_id {
+            query = query.room_id(room_id);
+        }
+
+        if let Some(created_after) = payload.created_after {
+            query = query.created_after(created_after);
+        }
+
+        let mut conn = context.get_conn().await?;
+        let errors = query.execute(&mut conn).await?;
+
+        Ok(Response::new(
+            ResponseStatus::OK,
+            errors,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value as JsonValue};
+
+    use crate::{
+        backend::janus::client::{HandleId, SessionId},
+        test_helpers::{db::TestDb, prelude::*},
+    };
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn list_backend_errors(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let mut conn = db.get_conn().await;
+
+        let backend = shared_helpers::insert_janus_backend(
+            &mut conn,
+            "test",
+            SessionId::random(),
+            HandleId::random(),
+        )
+        .await;
+
+        db::backend_error::InsertQuery::new(
+            backend.session_id(),
+            "create_stream",
+            json!({ "status": "500" }),
+        )
+        .execute(&mut conn)
+        .await
+        .expect("Failed to insert backend error");
+
+        db::backend_error::InsertQuery::new(backend.session_id(), "read_stream", json!({}))
+            .execute(&mut conn)
+            .await
+            .expect("Failed to insert backend error");
+
+        let mut authz = TestAuthz::new();
+        authz.set_audience(SVC_AUDIENCE);
+        let agent = TestAgent::new("alpha", "cron", SVC_AUDIENCE);
+        authz.allow(agent.account_id(), vec!["system"], "read");
+
+        let mut context = TestContext::new(db, authz).await;
+
+        let payload = Request {
+            backend_id: Some(backend.id().to_owned()),
+            kind: Some("create_stream".to_owned()),
+            room_id: None,
+            created_after: None,
+            offset: None,
+            limit: None,
+        };
+
+        let messages = handle_request::<Handler>(&mut context, &agent, payload)
+            .await
+            .expect("Backend errors listing failed");
+
+        let (errors, respp, _) = find_response::<Vec<JsonValue>>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0]["payload"]["status"], "500");
+    }
+
+    #[sqlx::test]
+    async fn list_backend_errors_unauthorized(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let mut authz = TestAuthz::new();
+        authz.set_audience(SVC_AUDIENCE);
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let mut context = TestContext::new(db, authz).await;
+
+        let payload = Request {
+            backend_id: None,
+            kind: None,
+            room_id: None,
+            created_after: None,
+            offset: None,
+            limit: None,
+        };
+
+        let err = handle_request::<Handler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success listing backend errors");
+
+        assert_eq!(err.status(), ResponseStatus::FORBIDDEN);
+    }
+}
@@ -0,0 +1,372 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::Connection;
+use svc_agent::{mqtt::ResponseStatus, AgentId};
+use svc_authn::Authenticable;
+use tracing::warn;
+use tracing_attributes::instrument;
+
+use crate::{
+    app::{
+        context::Context,
+        endpoint::{prelude::*, rtc, rtc_stream},
+        service_utils::{RequestParams, Response},
+    },
+    authz::AuthzObject,
+    db::{self, agent_connection, janus_backend, janus_rtc_stream},
+};
+
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    group: String,
+    /// Group to move the rooms to. Any remaining backend is fine when omitted.
+    target_group: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Report {
+    backends: Vec<AgentId>,
+    reassigned: Vec<Reassignment>,
+    closed: Vec<db::room::Id>,
+    disconnected: usize,
+    stopped_streams: usize,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Reassignment {
+    room_id: db::room::Id,
+    backend_id: AgentId,
+}
+
+struct Outcome {
+    backends: Vec<janus_backend::Object>,
+    reassigned: Vec<(db::room::Object, Vec<db::rtc::Id>)>,
+    closed: Vec<db::room::Object>,
+    disconnected: usize,
+    stopped_streams: Vec<janus_rtc_stream::StreamWithRoomId>,
+}
+
+pub struct Handler;
+
+#[async_trait]
+impl RequestHandler for Handler {
+    type Payload = Request;
+    const ERROR_TITLE: &'static str = "Failed to decommission backend group";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::SYSTEM_UPDATE);
+
+    #[instrument(skip(context, reqp), fields(group = %payload.group))]
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        // Authorization: only trusted subjects are allowed to perform operations with the system
+        let audience = context.agent_id().as_account_id().audience();
+
+        let authz_time = context
+            .authz()
+            .authorize(
+                audience.into(),
+                reqp,
+                AuthzObject::new(&["system"]).into(),
+                "update".into(),
+            )
+            .await?;
+        context.metrics().observe_auth(authz_time);
+
+        let mut conn = context.get_conn().await?;
+        let outcome = conn
+            .transaction(|conn| Box::pin(async move { decommission(&payload, conn).await }))
+            .await?;
+
+        let janus_clients = context.janus_clients();
+
+        for backend in &outcome.backends {
+            janus_clients.remove_client(backend);
+        }
+
+        let report = Report {
+            backends: outcome.backends.iter().map(|b| b.id().to_owned()).collect(),
+            reassigned: outcome
+                .reassigned
+                .iter()
+                .filter_map(|(room, _)| {
+                    Some(Reassignment {
+                        room_id: room.id(),
+                        backend_id: room.backend_id()?.to_owned(),
+                    })
+                })
+                .collect(),
+            closed: outcome.closed.iter().map(|room| room.id()).collect(),
+            disconnected: outcome.disconnected,
+            stopped_streams: outcome.stopped_streams.len(),
+        };
+
+        warn!(
+            backends = report.backends.len(),
+            reassigned = report.reassigned.len(),
+            closed = report.closed.len(),
+            "Backend group decommissioned"
+        );
+
+        let mut response = Response::new(
+            ResponseStatus::OK,
+            report,
+            context.start_timestamp(),
+            Some(authz_time),
+        );
+
+        for stream in outcome.stopped_streams {
+            response.add_message(Box::new(rtc_stream::update_event(
+                stream.room_id,
+                stream.janus_rtc_stream(),
+                context.start_timestamp(),
+                &context.pseudonyms(),
+            )));
+        }
+
+        for (room, rtc_ids) in outcome.reassigned {
+            if !rtc_ids.is_empty() {
+                response.add_message(Box::new(rtc::renegotiate_event(
+                    room.id(),
+                    rtc_ids,
+                    context.start_timestamp(),
+                )));
+            }
+        }
+
+        for room in outcome.closed {
+            if let Some(summary) = helpers::summarize_room(room.id(), &mut conn).await {
+                response.add_notification(
+                    "room.summary",
+                    &format!("audiences/{}/events", room.audience()),
+                    summary,
+                    context.start_timestamp(),
+                );
+            }
+
+            response.add_notification(
+                "room.close",
+                &format!("rooms/{}/events", room.id()),
+                room.clone(),
+                context.start_timestamp(),
+            );
+
+            response.add_notification(
+                "room.close",
+                &format!("audiences/{}/events", room.audience()),
+                room,
+                context.start_timestamp(),
+            );
+        }
+
+        Ok(response)
+    }
+}
+
+/// Removes the group's backends with their connections and streams, then moves every open room
+/// they hosted to the least loaded of the remaining backends. Rooms with nowhere to go get closed.
+async fn decommission(payload: &Request, conn: &mut sqlx::PgConnection) -> sqlx::Result<Outcome> {
+    let backends = janus_backend::list_by_group(&payload.group, conn).await?;
+    let rooms = db::room::open_by_backend_group(&payload.group, conn).await?;
+
+    let mut disconnected_rtcs = HashMap::<db::room::Id, Vec<db::rtc::Id>>::new();
+    let mut disconnected = 0;
+    let mut stopped_streams = vec![];
+
+    for backend in &backends {
+        for rtc in agent_connection::disconnect_by_backend(backend.id(), conn).await? {
+            disconnected += 1;
+            let rtc_ids = disconnected_rtcs.entry(rtc.room_id).or_default();
+
+            if !rtc_ids.contains(&rtc.rtc_id) {
+                rtc_ids.push(rtc.rtc_id);
+            }
+        }
+
+        stopped_streams
+            .extend(janus_rtc_stream::stop_running_streams_by_backend(backend.id(), conn).await?);
+
+        janus_backend::DeleteQuery::new(backend.id(), backend.session_id(), backend.handle_id())
+            .execute(conn)
+            .await?;
+    }
+
+    let mut reassigned = vec![];
+    let mut closed = vec![];
+
+    for room in rooms {
+        let target =
+            janus_backend::least_loaded(room.id(), payload.target_group.as_deref(), None, conn)
+                .await?;
+
+        match target {
+            Some(target) => {
+                let room = db::room::UpdateQuery::new(room.id())
+                    .backend_id(Some(target.id()))
+                    .execute(conn)
+                    .await?;
+
+                let rtc_ids = disconnected_rtcs.remove(&room.id()).unwrap_or_default();
+                reassigned.push((room, rtc_ids));
+            }
+            None => {
+                let room = db::room::UpdateQuery::new(room.id())
+                    .time(Some(room.time().close_now()))
+                    .execute(conn)
+                    .await?;
+
+                closed.push(room);
+            }
+        }
+    }
+
+    Ok(Outcome {
+        backends,
+        reassigned,
+        closed,
+        disconnected,
+        stopped_streams,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value as JsonValue};
+
+    use crate::{
+        backend::janus::client::{HandleId, SessionId},
+        db::room::FindQueryable,
+        test_helpers::{db::TestDb, find_event_by_predicate, prelude::*},
+    };
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn decommission_backend_group(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let mut conn = db.get_conn().await;
+
+        let retired = shared_helpers::insert_janus_backend_with_group(
+            &mut conn,
+            "test",
+            SessionId::random(),
+            HandleId::random(),
+            "retired",
+        )
+        .await;
+
+        let replacement = shared_helpers::insert_janus_backend_with_group(
+            &mut conn,
+            "test",
+            SessionId::random(),
+            HandleId::random(),
+            "current",
+        )
+        .await;
+
+        let room = shared_helpers::insert_room_with_backend_id(&mut conn, retired.id()).await;
+        let rtc = shared_helpers::insert_rtc_with_room(&mut conn, &room).await;
+        let user = TestAgent::new("web", "user", USR_AUDIENCE);
+        shared_helpers::insert_connected_agent(&mut conn, user.agent_id(), room.id(), rtc.id())
+            .await;
+
+        let mut authz = TestAuthz::new();
+        authz.set_audience(SVC_AUDIENCE);
+        let agent = TestAgent::new("alpha", "cron", SVC_AUDIENCE);
+        authz.allow(agent.account_id(), vec!["system"], "update");
+
+        let mut context = TestContext::new(db, authz).await;
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+        context.with_grouped_janus("retired", tx);
+
+        let payload = Request {
+            group: "retired".to_owned(),
+            target_group: Some("current".to_owned()),
+        };
+
+        let messages = handle_request::<Handler>(&mut context, &agent, payload)
+            .await
+            .expect("Backend group decommission failed");
+
+        let (report, respp, _) = find_response::<Report>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert_eq!(report.backends, vec![retired.id().to_owned()]);
+        assert_eq!(report.reassigned.len(), 1);
+        assert_eq!(report.reassigned[0].room_id, room.id());
+        assert_eq!(&report.reassigned[0].backend_id, replacement.id());
+        assert!(report.closed.is_empty());
+        assert_eq!(report.disconnected, 1);
+
+        let (evt, _, _) = find_event::<JsonValue>(messages.as_slice());
+        assert_eq!(evt["rtc_ids"], json!([rtc.id()]));
+
+        let mut conn = context.get_conn().await.expect("Failed to get conn");
+
+        let backend = janus_backend::FindQuery::new(retired.id())
+            .execute(&mut conn)
+            .await
+            .expect("Failed to find backend");
+
+        assert!(backend.is_none());
+    }
+
+    #[sqlx::test]
+    async fn close_rooms_without_target(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let mut conn = db.get_conn().await;
+
+        let retired = shared_helpers::insert_janus_backend_with_group(
+            &mut conn,
+            "test",
+            SessionId::random(),
+            HandleId::random(),
+            "retired",
+        )
+        .await;
+
+        let room = shared_helpers::insert_room_with_backend_id(&mut conn, retired.id()).await;
+
+        let mut authz = TestAuthz::new();
+        authz.set_audience(SVC_AUDIENCE);
+        let agent = TestAgent::new("alpha", "cron", SVC_AUDIENCE);
+        authz.allow(agent.account_id(), vec!["system"], "update");
+
+        let mut context = TestContext::new(db, authz).await;
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+        context.with_grouped_janus("retired", tx);
+
+        let payload = Request {
+            group: "retired".to_owned(),
+            target_group: None,
+        };
+
+        let messages = handle_request::<Handler>(&mut context, &agent, payload)
+            .await
+            .expect("Backend group decommission failed");
+
+        let (report, _, _) = find_response::<Report>(messages.as_slice());
+        assert!(report.reassigned.is_empty());
+        assert_eq!(report.closed, vec![room.id()]);
+
+        let (closed_room, _, _) =
+            find_event_by_predicate::<JsonValue, _>(messages.as_slice(), |evp, _, topic| {
+                evp.label() == "room.close" && topic.contains("/rooms/")
+            })
+            .expect("Failed to find room.close event");
+
+        assert_eq!(closed_room["id"], json!(room.id()));
+
+        let mut conn = context.get_conn().await.expect("Failed to get conn");
+
+        let room = db::room::FindQuery::new(room.id())
+            .execute(&mut conn)
+            .await
+            .expect("Failed to find room")
+            .expect("Room not found");
+
+        assert!(room.is_closed());
+    }
+}
@@ -0,0 +1,141 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use svc_agent::{mqtt::ResponseStatus, AgentId};
+use svc_authn::Authenticable;
+use tracing_attributes::instrument;
+
+use crate::{
+    app::{
+        context::Context,
+        endpoint::prelude::*,
+        service_utils::{RequestParams, Response},
+    },
+    authz::AuthzObject,
+    backend::janus::rtt::{self, GroupRtt},
+    db,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    /// Returns only the backends of the group.
+    group: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct BackendList {
+    backends: Vec<Backend>,
+    groups: Vec<GroupRtt>,
+}
+
+#[derive(Debug, Serialize)]
+struct Backend {
+    id: AgentId,
+    group: Option<String>,
+    /// Round trip of the last service ping, missing if the backend hasn't answered it.
+    rtt_ms: Option<i32>,
+}
+
+pub struct Handler;
+
+#[async_trait]
+impl RequestHandler for Handler {
+    type Payload = Request;
+    const ERROR_TITLE: &'static str = "Failed to list backends";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::SYSTEM_READ);
+
+    #[instrument(skip(context, payload, reqp))]
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        // Authorization: only trusted subjects are allowed to perform operations with the system
+        let audience = context.agent_id().as_account_id().audience();
+
+        let authz_time = context
+            .authz()
+            .authorize(
+                audience.into(),
+                reqp,
+                AuthzObject::new(&["system"]).into(),
+                "read".into(),
+            )
+            .await?;
+        context.metrics().observe_auth(authz_time);
+
+        let mut rtts = {
+            let mut conn = context.get_conn().await?;
+            db::janus_backend::rtt_for_each_backend(&mut conn).await?
+        };
+
+        if let Some(group) = payload.group.as_deref() {
+            rtts.retain(|rtt| rtt.group.as_deref() == Some(group));
+        }
+
+        let groups = rtt::by_group(&rtts);
+
+        let backends = rtts
+            .into_iter()
+            .map(|rtt| Backend {
+                id: rtt.backend_id,
+                group: rtt.group,
+                rtt_ms: rtt.rtt_ms,
+            })
+            .collect();
+
+        Ok(Response::new(
+            ResponseStatus::OK,
+            BackendList { backends, groups },
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value as JsonValue;
+
+    use crate::{
+        backend::janus::client::{HandleId, SessionId},
+        test_helpers::{db::TestDb, prelude::*},
+    };
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn list_backends(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let mut conn = db.get_conn().await;
+
+        let backend = shared_helpers::insert_janus_backend(
+            &mut conn,
+            "test",
+            SessionId::random(),
+            HandleId::random(),
+        )
+        .await;
+
+        db::janus_backend::set_rtt(backend.id(), Some(12), &mut conn)
+            .await
+            .expect("Failed to set backend rtt");
+
+        let mut authz = TestAuthz::new();
+        authz.set_audience(SVC_AUDIENCE);
+        let agent = TestAgent::new("alpha", "cron", SVC_AUDIENCE);
+        authz.allow(agent.account_id(), vec!["system"], "read");
+
+        let mut context = TestContext::new(db, authz).await;
+        let payload = Request { group: None };
+
+        let messages = handle_request::<Handler>(&mut context, &agent, payload)
+            .await
+            .expect("Backends listing failed");
+
+        let (list, respp, _) = find_response::<JsonValue>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert_eq!(list["backends"][0]["id"], backend.id().to_string());
+        assert_eq!(list["backends"][0]["rtt_ms"], 12);
+        assert_eq!(list["groups"][0]["avg_ms"], 12);
+    }
+}
@@ -0,0 +1,139 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use axum::{Extension, Json};
+use serde::Deserialize;
+use svc_agent::mqtt::ResponseStatus;
+use svc_authn::Authenticable;
+use svc_utils::extractors::AgentIdExtractor;
+use tracing::info;
+use tracing_attributes::instrument;
+
+use crate::{
+    app::{
+        context::{AppContext, Context},
+        drain,
+        endpoint::prelude::*,
+        message_handler::http,
+        service_utils::{RequestParams, Response},
+    },
+    authz::AuthzObject,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    /// Set to `false` to take new requests again, e.g. when the deploy is cancelled.
+    #[serde(default = "Request::default_draining")]
+    draining: bool,
+}
+
+impl Request {
+    fn default_draining() -> bool {
+        true
+    }
+}
+
+impl Default for Request {
+    fn default() -> Self {
+        Self {
+            draining: Self::default_draining(),
+        }
+    }
+}
+
+/// Draining concerns the instance called so it's served over HTTP only: an MQTT request would
+/// reach a random instance of the shared subscription.
+pub async fn drain_start(
+    Extension(ctx): Extension<Arc<AppContext>>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    payload: Option<Json<Request>>,
+) -> RequestResult {
+    let request = payload.map(|Json(request)| request).unwrap_or_default();
+    http::handle::<Handler>(&ctx, &agent_id, request).await
+}
+
+pub struct Handler;
+
+#[async_trait]
+impl RequestHandler for Handler {
+    type Payload = Request;
+    const ERROR_TITLE: &'static str = "Failed to start draining";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::SYSTEM_UPDATE);
+    const SERVES_WHILE_DRAINING: bool = true;
+
+    #[instrument(skip(context, payload, reqp))]
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        // Authorization: only trusted subjects are allowed to perform operations with the system
+        let audience = context.agent_id().as_account_id().audience();
+
+        let authz_time = context
+            .authz()
+            .authorize(
+                audience.into(),
+                reqp,
+                AuthzObject::new(&["system"]).into(),
+                "update".into(),
+            )
+            .await?;
+        context.metrics().observe_auth(authz_time);
+
+        context.metrics().drain.set_draining(payload.draining);
+        info!(draining = payload.draining, "Drain toggled");
+
+        let status = drain::status(context).await?;
+
+        Ok(Response::new(
+            ResponseStatus::OK,
+            status,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_helpers::{db::TestDb, prelude::*};
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn start_and_stop_draining(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+
+        let mut authz = TestAuthz::new();
+        authz.set_audience(SVC_AUDIENCE);
+        let agent = TestAgent::new("alpha", "cron", SVC_AUDIENCE);
+        authz.allow(agent.account_id(), vec!["system"], "update");
+
+        let mut context = TestContext::new(db, authz).await;
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+        context.with_janus(tx);
+
+        let messages = handle_request::<Handler>(&mut context, &agent, Request::default())
+            .await
+            .expect("Drain start failed");
+
+        let (status, respp, _) = find_response::<drain::Status>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert!(status.draining);
+        assert_eq!(status.janus_transactions, 0);
+        assert_eq!(status.outbox_backlog, 0);
+        assert!(context.metrics().drain.is_draining());
+
+        // Cancelled deploy.
+        let payload = Request { draining: false };
+
+        let messages = handle_request::<Handler>(&mut context, &agent, payload)
+            .await
+            .expect("Drain stop failed");
+
+        let (status, _, _) = find_response::<drain::Status>(messages.as_slice());
+        assert!(!status.draining);
+        assert!(!context.metrics().drain.is_draining());
+    }
+}
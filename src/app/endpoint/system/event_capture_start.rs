@@ -0,0 +1,141 @@
+use std::time::Duration;
+
+use anyhow::Context as AnyhowContext;
+use async_trait::async_trait;
+use chrono::Utc;
+use serde::Deserialize;
+use svc_agent::mqtt::ResponseStatus;
+use svc_authn::Authenticable;
+use tracing_attributes::instrument;
+
+use crate::{
+    app::{
+        context::Context,
+        endpoint::prelude::*,
+        service_utils::{RequestParams, Response},
+    },
+    authz::AuthzObject,
+    backend::janus::event_capture,
+    db,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    room_id: db::room::Id,
+    /// Seconds to capture for, capped and defaulting to the configured maximum.
+    duration: Option<u64>,
+}
+
+pub struct Handler;
+
+#[async_trait]
+impl RequestHandler for Handler {
+    type Payload = Request;
+    const ERROR_TITLE: &'static str = "Failed to start event capture";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::SYSTEM_UPDATE);
+
+    #[instrument(skip(context, reqp), fields(room_id = %payload.room_id))]
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        // Authorization: only trusted subjects are allowed to perform operations with the system
+        let audience = context.agent_id().as_account_id().audience();
+
+        let authz_time = context
+            .authz()
+            .authorize(
+                audience.into(),
+                reqp,
+                AuthzObject::new(&["system"]).into(),
+                "update".into(),
+            )
+            .await?;
+        context.metrics().observe_auth(authz_time);
+
+        let mut conn = context.get_conn().await?;
+
+        let room = helpers::find_room_by_id(
+            payload.room_id,
+            helpers::RoomTimeRequirement::NotClosed,
+            helpers::AudienceScope::caller(context, &reqp),
+            &mut conn,
+        )
+        .await?;
+
+        let duration = event_capture::capture_duration(
+            &context.config().janus_event_capture,
+            payload.duration.map(Duration::from_secs),
+        );
+
+        let expires_at = Utc::now()
+            + chrono::Duration::from_std(duration)
+                .context("Invalid capture duration")
+                .error(AppErrorKind::InvalidPayload)?;
+
+        let capture = db::janus_event::start_capture(room.id(), expires_at, &mut conn).await?;
+        context.janus_clients().event_capture().invalidate().await;
+
+        Ok(Response::new(
+            ResponseStatus::OK,
+            capture,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_helpers::{db::TestDb, prelude::*};
+
+    use super::{super::event_capture_stop, *};
+
+    #[sqlx::test]
+    async fn start_and_stop_capture(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let mut conn = db.get_conn().await;
+        let room = shared_helpers::insert_room(&mut conn).await;
+
+        let mut authz = TestAuthz::new();
+        authz.set_audience(SVC_AUDIENCE);
+        let agent = TestAgent::new("alpha", "cron", SVC_AUDIENCE);
+        authz.allow(agent.account_id(), vec!["system"], "update");
+
+        let mut context = TestContext::new(db, authz).await;
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+        context.with_janus(tx);
+
+        // Longer captures are cut to the configured maximum.
+        let payload = Request {
+            room_id: room.id(),
+            duration: Some(7 * 24 * 60 * 60),
+        };
+
+        let messages = handle_request::<Handler>(&mut context, &agent, payload)
+            .await
+            .expect("Event capture start failed");
+
+        let (capture, respp, _) = find_response::<db::janus_event::Capture>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert_eq!(capture.room_id, room.id());
+        assert!(capture.expires_at <= Utc::now() + chrono::Duration::hours(1));
+
+        let rooms = db::janus_event::capturing_rooms(&mut conn)
+            .await
+            .expect("Failed to list captures");
+        assert_eq!(rooms, vec![room.id()]);
+
+        let payload = event_capture_stop::Request::new(room.id());
+
+        handle_request::<event_capture_stop::Handler>(&mut context, &agent, payload)
+            .await
+            .expect("Event capture stop failed");
+
+        let rooms = db::janus_event::capturing_rooms(&mut conn)
+            .await
+            .expect("Failed to list captures");
+        assert!(rooms.is_empty());
+    }
+}
@@ -0,0 +1,82 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use svc_agent::mqtt::ResponseStatus;
+use svc_authn::Authenticable;
+use tracing_attributes::instrument;
+
+use crate::{
+    app::{
+        context::Context,
+        endpoint::prelude::*,
+        service_utils::{RequestParams, Response},
+    },
+    authz::AuthzObject,
+    db,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    room_id: db::room::Id,
+}
+
+#[cfg(test)]
+impl Request {
+    pub fn new(room_id: db::room::Id) -> Self {
+        Self { room_id }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct StopResponse {
+    room_id: db::room::Id,
+    /// Events captured for the room so far. They stay until the retention expires.
+    events: i64,
+}
+
+pub struct Handler;
+
+#[async_trait]
+impl RequestHandler for Handler {
+    type Payload = Request;
+    const ERROR_TITLE: &'static str = "Failed to stop event capture";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::SYSTEM_UPDATE);
+
+    #[instrument(skip(context, reqp), fields(room_id = %payload.room_id))]
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        // Authorization: only trusted subjects are allowed to perform operations with the system
+        let audience = context.agent_id().as_account_id().audience();
+
+        let authz_time = context
+            .authz()
+            .authorize(
+                audience.into(),
+                reqp,
+                AuthzObject::new(&["system"]).into(),
+                "update".into(),
+            )
+            .await?;
+        context.metrics().observe_auth(authz_time);
+
+        let mut conn = context.get_conn().await?;
+
+        // Stopping a capture that has already expired is fine.
+        db::janus_event::stop_capture(payload.room_id, &mut conn).await?;
+        context.janus_clients().event_capture().invalidate().await;
+
+        let events = db::janus_event::count(payload.room_id, &mut conn).await?;
+
+        Ok(Response::new(
+            ResponseStatus::OK,
+            StopResponse {
+                room_id: payload.room_id,
+                events,
+            },
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
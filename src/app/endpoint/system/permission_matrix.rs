@@ -0,0 +1,124 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use svc_agent::mqtt::ResponseStatus;
+use svc_authn::Authenticable;
+use tracing_attributes::instrument;
+
+use crate::{
+    app::{
+        context::Context,
+        endpoint::{self, prelude::*},
+        service_utils::{RequestParams, Response},
+    },
+    authz::AuthzObject,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct Request {}
+
+#[derive(Debug, Serialize)]
+struct Entry {
+    method: &'static str,
+    authz: Option<AuthzIntent>,
+}
+
+pub struct Handler;
+
+#[async_trait]
+impl RequestHandler for Handler {
+    type Payload = Request;
+    const ERROR_TITLE: &'static str = "Failed to read permission matrix";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::SYSTEM_READ);
+
+    #[instrument(skip(context, _payload, reqp))]
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        _payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        // Authorization: only trusted subjects are allowed to perform operations with the system
+        let audience = context.agent_id().as_account_id().audience();
+
+        let authz_time = context
+            .authz()
+            .authorize(
+                audience.into(),
+                reqp,
+                AuthzObject::new(&["system"]).into(),
+                "read".into(),
+            )
+            .await?;
+        context.metrics().observe_auth(authz_time);
+
+        let matrix = endpoint::permission_matrix()
+            .into_iter()
+            .map(|(method, authz)| Entry { method, authz })
+            .collect::<Vec<_>>();
+
+        Ok(Response::new(
+            ResponseStatus::OK,
+            matrix,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value as JsonValue;
+
+    use crate::test_helpers::{db::TestDb, prelude::*};
+
+    use super::*;
+
+    #[test]
+    fn every_handler_declares_authz() {
+        let missing = endpoint::permission_matrix()
+            .into_iter()
+            .filter(|(_, authz)| authz.is_none())
+            .map(|(method, _)| method)
+            .collect::<Vec<_>>();
+
+        assert!(missing.is_empty(), "No authz declared for {:?}", missing);
+    }
+
+    #[sqlx::test]
+    async fn read_permission_matrix(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+
+        let mut authz = TestAuthz::new();
+        authz.set_audience(SVC_AUDIENCE);
+        let agent = TestAgent::new("alpha", "cron", SVC_AUDIENCE);
+        authz.allow(agent.account_id(), vec!["system"], "read");
+
+        let mut context = TestContext::new(db, authz).await;
+
+        let messages = handle_request::<Handler>(&mut context, &agent, Request {})
+            .await
+            .expect("Permission matrix reading failed");
+
+        let (matrix, respp, _) = find_response::<JsonValue>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+
+        let entry = matrix
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|entry| entry["method"] == "room.read")
+            .expect("No room.read in the matrix");
+
+        assert_eq!(entry["authz"]["kind"], "object");
+        assert_eq!(entry["authz"]["object"][0], "classrooms");
+        assert_eq!(entry["authz"]["actions"][0], "read");
+
+        let entry = matrix
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|entry| entry["method"] == "rtc.update")
+            .expect("No rtc.update in the matrix");
+
+        assert_eq!(entry["authz"]["kind"], "creator_or_host");
+    }
+}
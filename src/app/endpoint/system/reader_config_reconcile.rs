@@ -0,0 +1,349 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use svc_agent::{mqtt::ResponseStatus, AgentId};
+use svc_authn::Authenticable;
+use tracing::warn;
+use tracing_attributes::instrument;
+
+use crate::{
+    app::{
+        context::Context,
+        endpoint::prelude::*,
+        service_utils::{RequestParams, Response},
+    },
+    authz::AuthzObject,
+    backend::janus::client::update_agent_reader_config::{
+        UpdateReaderConfigRequest, UpdateReaderConfigRequestBody,
+        UpdateReaderConfigRequestBodyConfigItem,
+    },
+    db::{self, rtc_reader_config::PendingObject},
+};
+
+const MAX_LIMIT: i64 = 1000;
+
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    /// Failed pushes after which the intent is rolled back to the last applied values.
+    #[serde(default = "default_max_attempts")]
+    max_attempts: i32,
+    /// Intents younger than this may still be pushed by the request which wrote them.
+    #[serde(default = "default_grace_period")]
+    grace_period: i64,
+    limit: Option<i64>,
+}
+
+fn default_max_attempts() -> i32 {
+    5
+}
+
+fn default_grace_period() -> i64 {
+    30
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Report {
+    applied: usize,
+    pending: usize,
+    rolled_back: usize,
+}
+
+pub struct Handler;
+
+#[async_trait]
+impl RequestHandler for Handler {
+    type Payload = Request;
+    const ERROR_TITLE: &'static str = "Failed to reconcile reader configs";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::SYSTEM_UPDATE);
+
+    #[instrument(skip(context, reqp))]
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        // Authorization: only trusted subjects are allowed to perform operations with the system
+        let audience = context.agent_id().as_account_id().audience();
+
+        let authz_time = context
+            .authz()
+            .authorize(
+                audience.into(),
+                reqp,
+                AuthzObject::new(&["system"]).into(),
+                "update".into(),
+            )
+            .await?;
+        context.metrics().observe_auth(authz_time);
+
+        let mut conn = context.get_conn().await?;
+
+        let pending = db::rtc_reader_config::list_pending(
+            Utc::now() - Duration::seconds(payload.grace_period),
+            std::cmp::min(payload.limit.unwrap_or(MAX_LIMIT), MAX_LIMIT),
+            &mut conn,
+        )
+        .await?;
+
+        let mut by_backend = HashMap::<Option<AgentId>, Vec<PendingObject>>::new();
+
+        for config in pending {
+            by_backend
+                .entry(config.backend_id.clone())
+                .or_default()
+                .push(config);
+        }
+
+        let mut report = Report::default();
+
+        for (backend_id, configs) in by_backend {
+            let backend = match backend_id {
+                Some(backend_id) => {
+                    db::janus_backend::FindQuery::new(&backend_id)
+                        .execute(&mut conn)
+                        .await?
+                }
+                None => None,
+            };
+
+            // Without a backend there's nothing to diverge from: Janus gets the configs from
+            // the database when the stream is read.
+            let backend = match backend {
+                Some(backend) => backend,
+                None => {
+                    for config in &configs {
+                        confirm(config, &mut conn).await?;
+                    }
+
+                    report.applied += configs.len();
+                    continue;
+                }
+            };
+
+            let client = context
+                .janus_clients()
+                .get_or_insert(&backend)
+                .error(AppErrorKind::BackendClientCreationFailed)?;
+
+            let request = UpdateReaderConfigRequest {
+                session_id: backend.session_id(),
+                handle_id: backend.handle_id(),
+                body: UpdateReaderConfigRequestBody::new(
+                    configs
+                        .iter()
+                        .map(|c| config_item(c, c.receive_video, c.receive_audio))
+                        .collect(),
+                ),
+            };
+
+            match client.reader_update(request).await {
+                Ok(()) => {
+                    for config in &configs {
+                        confirm(config, &mut conn).await?;
+                    }
+
+                    report.applied += configs.len();
+                }
+                Err(err) => {
+                    warn!(?err, backend = %backend.id(), "Reader config reconciliation failed");
+                    let mut rolled_back = vec![];
+
+                    for config in &configs {
+                        let attempts =
+                            db::rtc_reader_config::record_failed_push(config, &mut conn).await?;
+
+                        let restored = match attempts {
+                            Some(attempts) if attempts >= payload.max_attempts => {
+                                db::rtc_reader_config::rollback(config, &mut conn).await?
+                            }
+                            Some(_) => {
+                                report.pending += 1;
+                                None
+                            }
+                            // Overwritten by a newer intent meanwhile.
+                            None => None,
+                        };
+
+                        if let Some(restored) = restored {
+                            rolled_back.push(config_item(
+                                config,
+                                restored.receive_video(),
+                                restored.receive_audio(),
+                            ));
+                        }
+                    }
+
+                    report.rolled_back += rolled_back.len();
+
+                    if !rolled_back.is_empty() {
+                        warn!(
+                            backend = %backend.id(),
+                            count = rolled_back.len(),
+                            "Reader configs rolled back"
+                        );
+
+                        // Janus may have applied the intent despite the error so it gets the
+                        // restored values too. It's most likely unreachable though.
+                        let request = UpdateReaderConfigRequest {
+                            session_id: backend.session_id(),
+                            handle_id: backend.handle_id(),
+                            body: UpdateReaderConfigRequestBody::new(rolled_back),
+                        };
+
+                        if let Err(err) = client.reader_update(request).await {
+                            warn!(?err, backend = %backend.id(), "Failed to push rolled back configs");
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(Response::new(
+            ResponseStatus::OK,
+            report,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+async fn confirm(config: &PendingObject, conn: &mut sqlx::PgConnection) -> sqlx::Result<u64> {
+    db::rtc_reader_config::confirm(
+        &config.reader_id,
+        &[config.rtc_id],
+        config.pending_since,
+        conn,
+    )
+    .await
+}
+
+fn config_item(
+    config: &PendingObject,
+    receive_video: bool,
+    receive_audio: bool,
+) -> UpdateReaderConfigRequestBodyConfigItem {
+    UpdateReaderConfigRequestBodyConfigItem {
+        reader_id: config.reader_id.clone(),
+        stream_id: config.rtc_id,
+        receive_video,
+        receive_audio,
+        // Layers aren't stored so the reader keeps the ones Janus has.
+        spatial_layer: None,
+        temporal_layer: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        backend::janus::client::{HandleId, SessionId},
+        test_helpers::{db::TestDb, prelude::*},
+    };
+
+    use super::*;
+
+    async fn insert_pending_config(
+        conn: &mut sqlx::PgConnection,
+        rtc: &db::rtc::Object,
+        reader: &TestAgent,
+    ) {
+        db::rtc_reader_config::UpsertQuery::new(rtc.id(), reader.agent_id())
+            .receive_video(false)
+            .pending_since(Utc::now() - Duration::minutes(1))
+            .execute(conn)
+            .await
+            .expect("Failed to insert reader config");
+    }
+
+    fn system_authz() -> (TestAgent, TestAuthz) {
+        let mut authz = TestAuthz::new();
+        authz.set_audience(SVC_AUDIENCE);
+        let agent = TestAgent::new("alpha", "cron", SVC_AUDIENCE);
+        authz.allow(agent.account_id(), vec!["system"], "update");
+        (agent, authz)
+    }
+
+    #[sqlx::test]
+    async fn apply_without_backend(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let mut conn = db.get_conn().await;
+        let room = shared_helpers::insert_room(&mut conn).await;
+        let rtc = shared_helpers::insert_rtc_with_room(&mut conn, &room).await;
+        let reader = TestAgent::new("web", "user", USR_AUDIENCE);
+        insert_pending_config(&mut conn, &rtc, &reader).await;
+
+        let (agent, authz) = system_authz();
+        let mut context = TestContext::new(db, authz).await;
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+        context.with_janus(tx);
+
+        let payload = Request {
+            max_attempts: 5,
+            grace_period: 0,
+            limit: None,
+        };
+
+        let messages = handle_request::<Handler>(&mut context, &agent, payload)
+            .await
+            .expect("Reader config reconciliation failed");
+
+        let (report, _, _) = find_response::<Report>(messages.as_slice());
+        assert_eq!(report.applied, 1);
+
+        let has_pending =
+            db::rtc_reader_config::has_pending(room.id(), reader.agent_id(), &mut conn)
+                .await
+                .expect("Failed to check pending configs");
+
+        assert!(!has_pending);
+    }
+
+    #[sqlx::test]
+    async fn roll_back_after_max_attempts(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let mut conn = db.get_conn().await;
+
+        let backend = shared_helpers::insert_janus_backend(
+            &mut conn,
+            "http://127.0.0.1:1/janus",
+            SessionId::random(),
+            HandleId::random(),
+        )
+        .await;
+
+        let room = shared_helpers::insert_room_with_backend_id(&mut conn, backend.id()).await;
+        let rtc = shared_helpers::insert_rtc_with_room(&mut conn, &room).await;
+        let reader = TestAgent::new("web", "user", USR_AUDIENCE);
+        insert_pending_config(&mut conn, &rtc, &reader).await;
+
+        let (agent, authz) = system_authz();
+        let mut context = TestContext::new(db, authz).await;
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+        context.with_janus(tx);
+
+        let payload = Request {
+            max_attempts: 1,
+            grace_period: 0,
+            limit: None,
+        };
+
+        let messages = handle_request::<Handler>(&mut context, &agent, payload)
+            .await
+            .expect("Reader config reconciliation failed");
+
+        let (report, _, _) = find_response::<Report>(messages.as_slice());
+        assert_eq!(report.applied, 0);
+        assert_eq!(report.rolled_back, 1);
+
+        let configs = db::rtc_reader_config::read_config(rtc.id(), &mut conn)
+            .await
+            .expect("Failed to read reader configs");
+
+        assert_eq!(configs.len(), 1);
+        assert!(configs[0].receive_video());
+
+        context.janus_clients().remove_client(&backend);
+    }
+}
@@ -0,0 +1,127 @@
+use anyhow::Context as AnyhowContext;
+use async_trait::async_trait;
+use serde::Deserialize;
+use svc_agent::mqtt::ResponseStatus;
+use svc_authn::Authenticable;
+use tracing_attributes::instrument;
+
+use crate::{
+    app::{
+        context::Context,
+        endpoint::prelude::*,
+        service_utils::{RequestParams, Response},
+    },
+    authz::AuthzObject,
+    db,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    rtc_id: db::rtc::Id,
+}
+
+/// The RTC's recording including whether the storage has confirmed its upload.
+pub struct Handler;
+
+#[async_trait]
+impl RequestHandler for Handler {
+    type Payload = Request;
+    const ERROR_TITLE: &'static str = "Failed to read recording";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::SYSTEM_READ);
+
+    #[instrument(skip(context, payload, reqp), fields(rtc_id = %payload.rtc_id))]
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        // Authorization: only trusted subjects are allowed to perform operations with the system
+        let audience = context.agent_id().as_account_id().audience();
+
+        let authz_time = context
+            .authz()
+            .authorize(
+                audience.into(),
+                reqp,
+                AuthzObject::new(&["system"]).into(),
+                "read".into(),
+            )
+            .await?;
+        context.metrics().observe_auth(authz_time);
+
+        let mut conn = context.get_conn().await?;
+        let recording = db::recording::FindQuery::new(payload.rtc_id)
+            .execute(&mut conn)
+            .await?
+            .context("Recording not found")
+            .error(AppErrorKind::RecordingNotFound)?;
+
+        Ok(Response::new(
+            ResponseStatus::OK,
+            recording,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value as JsonValue;
+
+    use crate::test_helpers::{db::TestDb, prelude::*};
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn read_verified_recording(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let mut conn = db.get_conn().await;
+
+        let rtc = shared_helpers::insert_rtc(&mut conn).await;
+        shared_helpers::insert_recording(&mut conn, &rtc).await;
+
+        db::recording::set_verified(rtc.id(), 1024, None, &mut conn)
+            .await
+            .expect("Failed to verify recording");
+
+        let mut authz = TestAuthz::new();
+        authz.set_audience(SVC_AUDIENCE);
+        let agent = TestAgent::new("alpha", "cron", SVC_AUDIENCE);
+        authz.allow(agent.account_id(), vec!["system"], "read");
+
+        let mut context = TestContext::new(db, authz).await;
+
+        let messages =
+            handle_request::<Handler>(&mut context, &agent, Request { rtc_id: rtc.id() })
+                .await
+                .expect("Recording reading failed");
+
+        let (recording, respp, _) = find_response::<JsonValue>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert_eq!(recording["rtc_id"], rtc.id().to_string());
+        assert_eq!(recording["object_size"], 1024);
+        assert!(recording["verified_at"].is_i64());
+    }
+
+    #[sqlx::test]
+    async fn read_missing_recording(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let mut conn = db.get_conn().await;
+        let rtc = shared_helpers::insert_rtc(&mut conn).await;
+
+        let mut authz = TestAuthz::new();
+        authz.set_audience(SVC_AUDIENCE);
+        let agent = TestAgent::new("alpha", "cron", SVC_AUDIENCE);
+        authz.allow(agent.account_id(), vec!["system"], "read");
+
+        let mut context = TestContext::new(db, authz).await;
+
+        let err = handle_request::<Handler>(&mut context, &agent, Request { rtc_id: rtc.id() })
+            .await
+            .expect_err("Unexpected success reading recording");
+
+        assert_eq!(err.status(), ResponseStatus::NOT_FOUND);
+        assert_eq!(err.kind(), "recording_not_found");
+    }
+}
@@ -0,0 +1,139 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use svc_agent::mqtt::ResponseStatus;
+use svc_authn::Authenticable;
+use tracing_attributes::instrument;
+
+use crate::{
+    app::{
+        context::Context,
+        endpoint::prelude::*,
+        service_utils::{RequestParams, Response},
+    },
+    authz::AuthzObject,
+    db,
+};
+
+const MAX_LIMIT: i64 = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    audience: String,
+    offset: Option<i64>,
+    limit: Option<i64>,
+}
+
+/// TURN usage of the audience in total and by its rooms.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RelayUsage {
+    audience: String,
+    #[serde(flatten)]
+    total: db::relay_usage::Total,
+    rooms: Vec<db::relay_usage::Object>,
+}
+
+pub struct Handler;
+
+#[async_trait]
+impl RequestHandler for Handler {
+    type Payload = Request;
+    const ERROR_TITLE: &'static str = "Failed to read relay usage";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::SYSTEM_READ);
+
+    #[instrument(skip(context, payload, reqp))]
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        // Authorization: only trusted subjects are allowed to perform operations with the system
+        let audience = context.agent_id().as_account_id().audience();
+
+        let authz_time = context
+            .authz()
+            .authorize(
+                audience.into(),
+                reqp,
+                AuthzObject::new(&["system"]).into(),
+                "read".into(),
+            )
+            .await?;
+        context.metrics().observe_auth(authz_time);
+
+        let mut conn = context.get_conn().await?;
+        let total = db::relay_usage::total(&payload.audience, &mut conn).await?;
+
+        let rooms = db::relay_usage::ListQuery::new(&payload.audience)
+            .offset(payload.offset.unwrap_or(0))
+            .limit(std::cmp::min(payload.limit.unwrap_or(MAX_LIMIT), MAX_LIMIT))
+            .execute(&mut conn)
+            .await?;
+
+        let usage = RelayUsage {
+            audience: payload.audience,
+            total,
+            rooms,
+        };
+
+        Ok(Response::new(
+            ResponseStatus::OK,
+            usage,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_helpers::{db::TestDb, prelude::*};
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn read_relay_usage(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let mut conn = db.get_conn().await;
+
+        let room = shared_helpers::insert_room(&mut conn).await;
+        let other_room = shared_helpers::insert_room(&mut conn).await;
+
+        for &(room_id, relayed) in &[
+            (room.id(), true),
+            (room.id(), false),
+            (other_room.id(), true),
+            (other_room.id(), true),
+        ] {
+            db::room_summary::increment_connection_count(room_id, relayed, &mut conn)
+                .await
+                .expect("Failed to count connection");
+        }
+
+        let mut authz = TestAuthz::new();
+        authz.set_audience(SVC_AUDIENCE);
+        let agent = TestAgent::new("alpha", "cron", SVC_AUDIENCE);
+        authz.allow(agent.account_id(), vec!["system"], "read");
+
+        let mut context = TestContext::new(db, authz).await;
+
+        let payload = Request {
+            audience: USR_AUDIENCE.to_owned(),
+            offset: None,
+            limit: None,
+        };
+
+        let messages = handle_request::<Handler>(&mut context, &agent, payload)
+            .await
+            .expect("Relay usage reading failed");
+
+        let (usage, respp, _) = find_response::<RelayUsage>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert_eq!(usage.audience, USR_AUDIENCE);
+        assert_eq!(usage.total.relayed, 3);
+        assert_eq!(usage.total.direct, 1);
+        assert_eq!(usage.rooms.len(), 2);
+        assert_eq!(usage.rooms[0].room_id, other_room.id());
+        assert_eq!(usage.rooms[0].relayed, 2);
+        assert_eq!(usage.rooms[1].direct, 1);
+    }
+}
@@ -0,0 +1,271 @@
+use anyhow::{anyhow, Context as AnyhowContext};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use sqlx::Connection;
+use svc_agent::{mqtt::ResponseStatus, Addressable};
+use svc_authn::Authenticable;
+use tracing::info;
+use tracing_attributes::instrument;
+use uuid::Uuid;
+
+use crate::{
+    app::{
+        context::Context,
+        endpoint::prelude::*,
+        error::Error as AppError,
+        service_utils::{RequestParams, Response},
+        stage::{self, room_bulk_close::RoomBulkClose},
+    },
+    authz::AuthzObject,
+    db, outbox,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    audience: Option<String>,
+    classroom_ids: Option<Vec<Uuid>>,
+    #[serde(default, with = "chrono::serde::ts_seconds_option")]
+    closed_before: Option<DateTime<Utc>>,
+}
+
+pub struct Handler;
+
+#[async_trait]
+impl RequestHandler for Handler {
+    type Payload = Request;
+    const ERROR_TITLE: &'static str = "Failed to start room bulk close";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::SYSTEM_UPDATE);
+
+    #[instrument(skip(context, payload, reqp))]
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        // Authorization: only trusted subjects are allowed to perform operations with the system
+        let svc_audience = context.agent_id().as_account_id().audience();
+
+        let authz_time = context
+            .authz()
+            .authorize(
+                svc_audience.into(),
+                reqp,
+                AuthzObject::new(&["system"]).into(),
+                "update".into(),
+            )
+            .await?;
+        context.metrics().observe_auth(authz_time);
+
+        // Closing every room of every tenant is never intended.
+        let Request {
+            audience,
+            classroom_ids,
+            closed_before,
+        } = payload;
+
+        let classroom_ids = classroom_ids.filter(|ids| !ids.is_empty());
+
+        if audience.is_none() && classroom_ids.is_none() {
+            return Err(anyhow!(
+                "Either audience or classroom ids must be specified"
+            ))
+            .error(AppErrorKind::InvalidPayload);
+        }
+
+        let created_by = reqp.as_agent_id().to_owned();
+        let try_wake_interval = context.config().outbox.try_wake_interval;
+
+        let mut conn = context.get_conn().await?;
+        let job = conn
+            .transaction::<_, _, AppError>(|conn| {
+                Box::pin(async move {
+                    let mut query = db::room_bulk_close::InsertQuery::new(&created_by);
+
+                    if let Some(audience) = &audience {
+                        query = query.audience(audience);
+                    }
+
+                    if let Some(classroom_ids) = &classroom_ids {
+                        query = query.classroom_ids(classroom_ids);
+                    }
+
+                    if let Some(closed_before) = closed_before {
+                        query = query.closed_before(closed_before);
+                    }
+
+                    let job = query.execute(conn).await?;
+
+                    let stage = serde_json::to_value(RoomBulkClose::init(job.id()))
+                        .context("serialization failed")
+                        .error(AppErrorKind::OutboxStageSerializationFailed)?;
+
+                    // The outbox handler picks the job up on its next run.
+                    outbox::db::sqlx::InsertQuery::new(
+                        stage::room_bulk_close::ENTITY_TYPE,
+                        stage,
+                        outbox::util::delivery_deadline_from_now(try_wake_interval),
+                        stage::room_bulk_close::STARTED_OPERATION,
+                    )
+                    .execute(conn)
+                    .await?;
+
+                    Ok(job)
+                })
+            })
+            .await?;
+
+        info!(job_id = %job.id(), total = job.total, "Room bulk close started");
+
+        Ok(Response::new(
+            ResponseStatus::ACCEPTED,
+            job,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use chrono::Duration;
+    use serde_json::Value as JsonValue;
+
+    use crate::{
+        app::context::GlobalContext,
+        db::room::FindQueryable,
+        outbox::StageHandle,
+        test_helpers::{db::TestDb, prelude::*},
+    };
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn bulk_close_rooms(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+
+        let (room, other_room, infinite_room, later_room) = {
+            let mut conn = db.get_conn().await;
+
+            let room = shared_helpers::insert_room(&mut conn).await;
+            let other_room = shared_helpers::insert_room(&mut conn).await;
+
+            let infinite_room = factory::Room::new()
+                .audience(USR_AUDIENCE)
+                .time((
+                    std::ops::Bound::Included(Utc::now() - Duration::hours(1)),
+                    std::ops::Bound::Unbounded,
+                ))
+                .infinite()
+                .insert(&mut conn)
+                .await;
+
+            // Closes on its own after the horizon.
+            let later_room = factory::Room::new()
+                .audience(USR_AUDIENCE)
+                .time((
+                    std::ops::Bound::Included(Utc::now() - Duration::hours(1)),
+                    std::ops::Bound::Excluded(Utc::now() + Duration::days(30)),
+                ))
+                .insert(&mut conn)
+                .await;
+
+            (room, other_room, infinite_room, later_room)
+        };
+
+        let mut authz = TestAuthz::new();
+        authz.set_audience(SVC_AUDIENCE);
+        let agent = TestAgent::new("alpha", "cron", SVC_AUDIENCE);
+        authz.allow(agent.account_id(), vec!["system"], "update");
+
+        let mut context = TestContext::new(db, authz).await;
+
+        let payload = Request {
+            audience: None,
+            classroom_ids: Some(vec![
+                room.classroom_id(),
+                infinite_room.classroom_id(),
+                later_room.classroom_id(),
+            ]),
+            closed_before: Some(Utc::now() + Duration::days(7)),
+        };
+
+        let messages = handle_request::<Handler>(&mut context, &agent, payload)
+            .await
+            .expect("Room bulk close failed");
+
+        let (job, respp, _) = find_response::<JsonValue>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::ACCEPTED);
+        assert_eq!(job["total"], 1);
+
+        let job_id: db::room_bulk_close::Id =
+            serde_json::from_value(job["id"].clone()).expect("Invalid job id");
+
+        let ctx = Arc::new(context.clone()) as Arc<dyn GlobalContext + Send + Sync>;
+        let next_stage = RoomBulkClose {
+            job_id,
+            after: None,
+        }
+        .handle(
+            &ctx,
+            &("room_bulk_close".to_string(), "started".to_string(), 1).into(),
+        )
+        .await
+        .expect("Stage failed");
+
+        assert!(next_stage.is_none());
+
+        let mut conn = context.get_conn().await.expect("Failed to get conn");
+
+        let job = db::room_bulk_close::find(job_id, &mut conn)
+            .await
+            .expect("Failed to find job")
+            .expect("Job not found");
+
+        assert_eq!((job.total, job.closed, job.failed), (1, 1, 0));
+        assert!(job.finished_at.is_some());
+
+        for (room, closed) in [
+            (room, true),
+            (other_room, false),
+            (infinite_room, false),
+            (later_room, false),
+        ] {
+            let room = db::room::FindQuery::new(room.id())
+                .execute(&mut conn)
+                .await
+                .expect("Failed to find room")
+                .expect("Room not found");
+
+            assert_eq!(room.time().is_closed_at(Utc::now()), closed);
+            assert_eq!(room.closed_by.is_some(), closed);
+        }
+    }
+
+    #[sqlx::test]
+    async fn bulk_close_without_filters(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+
+        let mut authz = TestAuthz::new();
+        authz.set_audience(SVC_AUDIENCE);
+        let agent = TestAgent::new("alpha", "cron", SVC_AUDIENCE);
+        authz.allow(agent.account_id(), vec!["system"], "update");
+
+        let mut context = TestContext::new(db, authz).await;
+
+        let payload = Request {
+            audience: None,
+            classroom_ids: Some(vec![]),
+            closed_before: None,
+        };
+
+        let err = handle_request::<Handler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected success closing every room");
+
+        assert_eq!(err.status(), ResponseStatus::BAD_REQUEST);
+        assert_eq!(err.kind(), "invalid_payload");
+    }
+}
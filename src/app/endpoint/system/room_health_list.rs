@@ -0,0 +1,125 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+use svc_agent::mqtt::ResponseStatus;
+use svc_authn::Authenticable;
+use tracing_attributes::instrument;
+
+use crate::{
+    app::{
+        context::Context,
+        endpoint::prelude::*,
+        service_utils::{RequestParams, Response},
+    },
+    authz::AuthzObject,
+    db,
+};
+
+const MAX_LIMIT: i64 = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    min_score: Option<f32>,
+    offset: Option<i64>,
+    limit: Option<i64>,
+}
+
+pub struct Handler;
+
+#[async_trait]
+impl RequestHandler for Handler {
+    type Payload = Request;
+    const ERROR_TITLE: &'static str = "Failed to list room health";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::SYSTEM_READ);
+
+    #[instrument(skip(context, payload, reqp))]
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        // Authorization: only trusted subjects are allowed to perform operations with the system
+        let audience = context.agent_id().as_account_id().audience();
+
+        let authz_time = context
+            .authz()
+            .authorize(
+                audience.into(),
+                reqp,
+                AuthzObject::new(&["system"]).into(),
+                "read".into(),
+            )
+            .await?;
+        context.metrics().observe_auth(authz_time);
+
+        let mut query = db::room_health::ListQuery::new()
+            .offset(payload.offset.unwrap_or(0))
+            .limit(std::cmp::min(payload.limit.unwrap_or(MAX_LIMIT), MAX_LIMIT));
+
+        if let Some(min_score) = payload.min_score {
+            query = query.min_score(min_score);
+        }
+
+        let mut conn = context.get_conn().await?;
+        let rooms = query.execute(&mut conn).await?;
+
+        Ok(Response::new(
+            ResponseStatus::OK,
+            rooms,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_helpers::{db::TestDb, prelude::*};
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn list_room_health(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let mut conn = db.get_conn().await;
+
+        let healthy_room = shared_helpers::insert_room(&mut conn).await;
+        let troubled_room = shared_helpers::insert_room(&mut conn).await;
+
+        db::room_health::UpsertQuery::new(healthy_room.id(), 0., &[])
+            .execute(&mut conn)
+            .await
+            .expect("Failed to insert room health");
+
+        let reasons = vec!["slow_links".to_owned()];
+
+        db::room_health::UpsertQuery::new(troubled_room.id(), 12., &reasons)
+            .rates(0., 12., 0.)
+            .execute(&mut conn)
+            .await
+            .expect("Failed to insert room health");
+
+        let mut authz = TestAuthz::new();
+        authz.set_audience(SVC_AUDIENCE);
+        let agent = TestAgent::new("alpha", "cron", SVC_AUDIENCE);
+        authz.allow(agent.account_id(), vec!["system"], "read");
+
+        let mut context = TestContext::new(db, authz).await;
+
+        let payload = Request {
+            min_score: Some(1.),
+            offset: None,
+            limit: None,
+        };
+
+        let messages = handle_request::<Handler>(&mut context, &agent, payload)
+            .await
+            .expect("Room health listing failed");
+
+        let (rooms, respp, _) = find_response::<Vec<db::room_health::Object>>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert_eq!(rooms.len(), 1);
+        assert_eq!(rooms[0].room_id, troubled_room.id());
+        assert_eq!(rooms[0].classroom_id, troubled_room.classroom_id());
+        assert_eq!(rooms[0].reasons, reasons);
+    }
+}
@@ -0,0 +1,395 @@
+use std::collections::HashSet;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::Connection;
+use svc_agent::{mqtt::ResponseStatus, AgentId};
+use svc_authn::Authenticable;
+use tracing::{error, warn};
+use tracing_attributes::instrument;
+
+use crate::{
+    app::{
+        context::Context,
+        endpoint::{prelude::*, rtc, rtc_stream},
+        service_utils::{RequestParams, Response},
+    },
+    authz::AuthzObject,
+    backend::janus::client::{detach_handle::DetachHandleRequest, HandleId},
+    db::{self, agent_connection, janus_backend, janus_rtc_stream},
+};
+
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    rtc_id: db::rtc::Id,
+    /// Backend to move the publisher to.
+    backend_id: AgentId,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Report {
+    room_id: db::room::Id,
+    rtc_id: db::rtc::Id,
+    source_backend_id: AgentId,
+    target_backend_id: AgentId,
+    disconnected: usize,
+    stopped_streams: usize,
+}
+
+struct Outcome {
+    room: db::room::Object,
+    connections: Vec<agent_connection::Object>,
+    stopped_streams: Vec<janus_rtc_stream::Object>,
+}
+
+pub struct Handler;
+
+#[async_trait]
+impl RequestHandler for Handler {
+    type Payload = Request;
+    const ERROR_TITLE: &'static str = "Failed to migrate rtc";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::SYSTEM_UPDATE);
+
+    #[instrument(skip(context, reqp), fields(rtc_id = %payload.rtc_id))]
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        // Authorization: only trusted subjects are allowed to perform operations with the system
+        let audience = context.agent_id().as_account_id().audience();
+
+        let authz_time = context
+            .authz()
+            .authorize(
+                audience.into(),
+                reqp,
+                AuthzObject::new(&["system"]).into(),
+                "update".into(),
+            )
+            .await?;
+        context.metrics().observe_auth(authz_time);
+
+        let mut conn = context.get_conn().await?;
+
+        let room = helpers::find_room_by_rtc_id(
+            payload.rtc_id,
+            helpers::RoomTimeRequirement::Open,
+            helpers::AudienceScope::Unrestricted,
+            &mut conn,
+        )
+        .await?;
+
+        let source_id = room
+            .backend_id()
+            .ok_or_else(|| anyhow!("Room {} isn't hosted by any backend", room.id()))
+            .error(AppErrorKind::InvalidPayload)?
+            .to_owned();
+
+        if source_id == payload.backend_id {
+            return Err(anyhow!(
+                "Room {} is already hosted by the backend",
+                room.id()
+            ))
+            .error(AppErrorKind::InvalidPayload)?;
+        }
+
+        let source = janus_backend::FindQuery::new(&source_id)
+            .execute(&mut conn)
+            .await?;
+
+        janus_backend::FindQuery::new(&payload.backend_id)
+            .execute(&mut conn)
+            .await?
+            .ok_or_else(|| anyhow!("Backend {} not found", payload.backend_id))
+            .error(AppErrorKind::BackendNotFound)?;
+
+        let publishing = janus_rtc_stream::ListQuery::new()
+            .rtc_id(payload.rtc_id)
+            .active(true)
+            .limit(1)
+            .execute(&mut conn)
+            .await?;
+
+        if publishing.is_empty() {
+            return Err(anyhow!("Rtc {} has no active publisher", payload.rtc_id))
+                .error(AppErrorKind::InvalidPayload)?;
+        }
+
+        let room_id = room.id();
+        let target_id = payload.backend_id.clone();
+
+        let outcome = conn
+            .transaction(|conn| Box::pin(async move { migrate(room_id, &target_id, conn).await }))
+            .await?;
+
+        // Janus doesn't relay streams between backends so readers of the room follow the
+        // publisher. Their new handles are made on the target when they connect again.
+        if let Some(source) = source {
+            let handles = outcome
+                .connections
+                .iter()
+                .map(|connection| connection.handle_id())
+                .chain(
+                    outcome
+                        .stopped_streams
+                        .iter()
+                        .map(|stream| stream.handle_id()),
+                );
+
+            detach_handles(context, &source, handles).await;
+        }
+
+        let mut rtc_ids = vec![payload.rtc_id];
+
+        for connection in &outcome.connections {
+            if !rtc_ids.contains(&connection.rtc_id()) {
+                rtc_ids.push(connection.rtc_id());
+            }
+        }
+
+        let report = Report {
+            room_id,
+            rtc_id: payload.rtc_id,
+            source_backend_id: source_id,
+            target_backend_id: payload.backend_id,
+            disconnected: outcome.connections.len(),
+            stopped_streams: outcome.stopped_streams.len(),
+        };
+
+        warn!(
+            room_id = %report.room_id,
+            source = %report.source_backend_id,
+            target = %report.target_backend_id,
+            disconnected = report.disconnected,
+            "Rtc migrated"
+        );
+
+        let mut response = Response::new(
+            ResponseStatus::OK,
+            report,
+            context.start_timestamp(),
+            Some(authz_time),
+        );
+
+        for stream in outcome.stopped_streams {
+            response.add_message(Box::new(rtc_stream::update_event(
+                room_id,
+                stream,
+                context.start_timestamp(),
+                &context.pseudonyms(),
+            )));
+        }
+
+        // Clients renegotiate on this event and get routed to the room's new backend.
+        response.add_message(Box::new(rtc::renegotiate_event(
+            outcome.room.id(),
+            rtc_ids,
+            context.start_timestamp(),
+        )));
+
+        Ok(response)
+    }
+}
+
+/// Moves the room to the target backend, drops its connections and stops its running streams.
+/// Reader configs stay in the database and reach the target with the stream on reconnection.
+async fn migrate(
+    room_id: db::room::Id,
+    target_id: &AgentId,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Outcome> {
+    let room = db::room::UpdateQuery::new(room_id)
+        .backend_id(Some(target_id))
+        .execute(conn)
+        .await?;
+
+    let connections = agent_connection::disconnect_by_room(room_id, conn).await?;
+
+    let running = janus_rtc_stream::ListQuery::new()
+        .room_id(room_id)
+        .active(true)
+        .execute(conn)
+        .await?;
+
+    let mut stopped_streams = Vec::with_capacity(running.len());
+
+    for stream in running {
+        if let Some(stream) = janus_rtc_stream::stop(stream.id(), conn).await? {
+            stopped_streams.push(stream);
+        }
+    }
+
+    Ok(Outcome {
+        room,
+        connections,
+        stopped_streams,
+    })
+}
+
+/// Best effort: handles left on the source get cleaned up by Janus once their peers hang up.
+async fn detach_handles<C: Context>(
+    context: &C,
+    source: &janus_backend::Object,
+    handles: impl Iterator<Item = HandleId>,
+) {
+    let client = match context.janus_clients().get_or_insert(source) {
+        Ok(client) => client,
+        Err(err) => {
+            error!(?err, backend = %source.id(), "Failed to get client of the source backend");
+            return;
+        }
+    };
+
+    for handle_id in handles.collect::<HashSet<_>>() {
+        let request = DetachHandleRequest {
+            session_id: source.session_id(),
+            handle_id,
+        };
+
+        if let Err(err) = client.detach_handle(request).await {
+            error!(?err, %handle_id, backend = %source.id(), "Failed to detach the handle");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value as JsonValue};
+
+    use crate::{
+        backend::janus::client::SessionId,
+        db::room::FindQueryable,
+        test_helpers::{db::TestDb, find_event_by_predicate, prelude::*},
+    };
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn migrate_rtc(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let mut conn = db.get_conn().await;
+
+        let source = shared_helpers::insert_janus_backend(
+            &mut conn,
+            "test",
+            SessionId::random(),
+            HandleId::random(),
+        )
+        .await;
+
+        let target = shared_helpers::insert_janus_backend(
+            &mut conn,
+            "test",
+            SessionId::random(),
+            HandleId::random(),
+        )
+        .await;
+
+        let room = shared_helpers::insert_room_with_backend_id(&mut conn, source.id()).await;
+        let rtc = shared_helpers::insert_rtc_with_room(&mut conn, &room).await;
+
+        let stream = factory::JanusRtcStream::new(USR_AUDIENCE)
+            .backend(&source)
+            .rtc(&rtc)
+            .insert(&mut conn)
+            .await;
+
+        janus_rtc_stream::start(stream.id(), &mut conn)
+            .await
+            .expect("Failed to start rtc stream");
+
+        let user = TestAgent::new("web", "user", USR_AUDIENCE);
+        shared_helpers::insert_connected_agent(&mut conn, user.agent_id(), room.id(), rtc.id())
+            .await;
+
+        let mut authz = TestAuthz::new();
+        authz.set_audience(SVC_AUDIENCE);
+        let agent = TestAgent::new("alpha", "cron", SVC_AUDIENCE);
+        authz.allow(agent.account_id(), vec!["system"], "update");
+
+        let mut context = TestContext::new(db, authz).await;
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+        context.with_janus(tx);
+
+        let payload = Request {
+            rtc_id: rtc.id(),
+            backend_id: target.id().to_owned(),
+        };
+
+        let messages = handle_request::<Handler>(&mut context, &agent, payload)
+            .await
+            .expect("Rtc migration failed");
+
+        let (report, respp, _) = find_response::<Report>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert_eq!(report.room_id, room.id());
+        assert_eq!(&report.source_backend_id, source.id());
+        assert_eq!(&report.target_backend_id, target.id());
+        assert_eq!(report.disconnected, 1);
+        assert_eq!(report.stopped_streams, 1);
+
+        let (evt, _, _) =
+            find_event_by_predicate::<JsonValue, _>(messages.as_slice(), |evp, _, _| {
+                evp.label() == "rtc.renegotiate"
+            })
+            .expect("No renegotiate event");
+
+        assert_eq!(evt["rtc_ids"], json!([rtc.id()]));
+
+        let mut conn = context.get_conn().await.expect("Failed to get conn");
+
+        let room = db::room::FindQuery::new(room.id())
+            .execute(&mut conn)
+            .await
+            .expect("Failed to find room")
+            .expect("Room not found");
+
+        assert_eq!(room.backend_id(), Some(target.id()));
+    }
+
+    #[sqlx::test]
+    async fn migrate_rtc_without_publisher(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let mut conn = db.get_conn().await;
+
+        let source = shared_helpers::insert_janus_backend(
+            &mut conn,
+            "test",
+            SessionId::random(),
+            HandleId::random(),
+        )
+        .await;
+
+        let target = shared_helpers::insert_janus_backend(
+            &mut conn,
+            "test",
+            SessionId::random(),
+            HandleId::random(),
+        )
+        .await;
+
+        let room = shared_helpers::insert_room_with_backend_id(&mut conn, source.id()).await;
+        let rtc = shared_helpers::insert_rtc_with_room(&mut conn, &room).await;
+
+        let mut authz = TestAuthz::new();
+        authz.set_audience(SVC_AUDIENCE);
+        let agent = TestAgent::new("alpha", "cron", SVC_AUDIENCE);
+        authz.allow(agent.account_id(), vec!["system"], "update");
+
+        let mut context = TestContext::new(db, authz).await;
+
+        let payload = Request {
+            rtc_id: rtc.id(),
+            backend_id: target.id().to_owned(),
+        };
+
+        let err = handle_request::<Handler>(&mut context, &agent, payload)
+            .await
+            .expect_err("Unexpected rtc migration success");
+
+        assert_eq!(err.status(), ResponseStatus::BAD_REQUEST);
+        assert_eq!(err.kind(), "invalid_payload");
+    }
+}
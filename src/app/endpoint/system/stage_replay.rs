@@ -0,0 +1,185 @@
+use anyhow::anyhow;
+use async_trait::async_trait;
+use serde::Deserialize;
+use svc_agent::mqtt::ResponseStatus;
+use svc_authn::Authenticable;
+use tracing::info;
+use tracing_attributes::instrument;
+use uuid::Uuid;
+
+use crate::{
+    app::{
+        context::Context,
+        endpoint::prelude::*,
+        service_utils::{RequestParams, Response},
+    },
+    authz::AuthzObject,
+    outbox,
+};
+
+/// Maximum number of sequence ids to replay at once.
+const MAX_RANGE: i64 = 1000;
+
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    classroom_id: Uuid,
+    from_sequence_id: i64,
+    to_sequence_id: i64,
+}
+
+pub struct Handler;
+
+#[async_trait]
+impl RequestHandler for Handler {
+    type Payload = Request;
+    const ERROR_TITLE: &'static str = "Failed to replay stages";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::SYSTEM_UPDATE);
+
+    #[instrument(skip(context, payload, reqp), fields(classroom_id = %payload.classroom_id))]
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        // Authorization: only trusted subjects are allowed to perform operations with the system
+        let audience = context.agent_id().as_account_id().audience();
+
+        let authz_time = context
+            .authz()
+            .authorize(
+                audience.into(),
+                reqp,
+                AuthzObject::new(&["system"]).into(),
+                "update".into(),
+            )
+            .await?;
+        context.metrics().observe_auth(authz_time);
+
+        let range = payload.to_sequence_id - payload.from_sequence_id;
+
+        if !(0..MAX_RANGE).contains(&range) {
+            return Err(anyhow!(
+                "Invalid sequence id range, at most {} ids could be replayed",
+                MAX_RANGE
+            ))
+            .error(AppErrorKind::InvalidPayload);
+        }
+
+        let mut conn = context.get_conn().await?;
+
+        // The outbox handler picks the events up on its next run.
+        let replayed = outbox::db::sqlx::ReplayQuery::new(
+            payload.classroom_id,
+            payload.from_sequence_id,
+            payload.to_sequence_id,
+        )
+        .execute(&mut conn)
+        .await?;
+
+        info!(count = replayed.len(), "Stages replayed");
+
+        Ok(Response::new(
+            ResponseStatus::OK,
+            replayed,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use serde_json::{json, Value as JsonValue};
+    use svc_events::EventId;
+
+    use crate::test_helpers::{db::TestDb, prelude::*};
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn replay_stages(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let classroom_id = Uuid::new_v4();
+        let stage = json!({ "name": "VideoGroupSendMqttNotification", "room_id": Uuid::new_v4() });
+
+        let (done_id, pending_id) = {
+            let mut conn = db.get_conn().await;
+
+            let done_id: EventId = ("video_group".to_string(), "updated".to_string(), 1).into();
+            outbox::db::sqlx::HistoryUpsertQuery::new(
+                &done_id,
+                &stage,
+                outbox::db::sqlx::HISTORY_STATUS_DONE,
+            )
+            .classroom_id(Some(classroom_id))
+            .execute(&mut conn)
+            .await
+            .expect("Failed to record history");
+
+            // This one is still in the outbox so it mustn't be enqueued twice.
+            let pending_id = outbox::db::sqlx::InsertQuery::new(
+                "video_group",
+                stage.clone(),
+                Utc::now(),
+                "updated",
+            )
+            .sequence_id(2)
+            .execute(&mut conn)
+            .await
+            .expect("Failed to insert stage");
+
+            outbox::db::sqlx::HistoryUpsertQuery::new(
+                &pending_id,
+                &stage,
+                outbox::db::sqlx::HISTORY_STATUS_FAILED,
+            )
+            .classroom_id(Some(classroom_id))
+            .error_kind("backend_request_failed")
+            .execute(&mut conn)
+            .await
+            .expect("Failed to record history");
+
+            (done_id, pending_id)
+        };
+
+        let mut authz = TestAuthz::new();
+        authz.set_audience(SVC_AUDIENCE);
+        let agent = TestAgent::new("alpha", "cron", SVC_AUDIENCE);
+        authz.allow(agent.account_id(), vec!["system"], "update");
+
+        let mut context = TestContext::new(db, authz).await;
+
+        let payload = Request {
+            classroom_id,
+            from_sequence_id: 1,
+            to_sequence_id: 2,
+        };
+
+        let messages = handle_request::<Handler>(&mut context, &agent, payload)
+            .await
+            .expect("Stages replay failed");
+
+        let (replayed, respp, _) = find_response::<Vec<JsonValue>>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert_eq!(replayed.len(), 1);
+        assert_eq!(replayed[0]["id"], done_id.sequence_id());
+        assert_eq!(replayed[0]["status"], "replayed");
+
+        let mut conn = context.get_conn().await.expect("Failed to get conn");
+
+        outbox::db::sqlx::FindQuery::new(&done_id)
+            .execute(&mut conn)
+            .await
+            .expect("Replayed stage is missing in the outbox");
+
+        let history = outbox::db::sqlx::HistoryFindQuery::new(&pending_id)
+            .execute(&mut conn)
+            .await
+            .expect("Failed to find history")
+            .expect("History is missing");
+
+        assert_eq!(history.status(), "failed");
+        assert_eq!(history.replay_count(), 0);
+    }
+}
@@ -0,0 +1,70 @@
+use anyhow::anyhow;
+use async_trait::async_trait;
+use serde::Deserialize;
+use svc_agent::mqtt::ResponseStatus;
+use svc_authn::Authenticable;
+use svc_events::EventId;
+use tracing_attributes::instrument;
+
+use crate::{
+    app::{
+        context::Context,
+        endpoint::prelude::*,
+        service_utils::{RequestParams, Response},
+    },
+    authz::AuthzObject,
+    outbox,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    entity_type: String,
+    operation: String,
+    sequence_id: i64,
+}
+
+pub struct Handler;
+
+#[async_trait]
+impl RequestHandler for Handler {
+    type Payload = Request;
+    const ERROR_TITLE: &'static str = "Failed to read stage status";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::SYSTEM_READ);
+
+    #[instrument(skip(context, payload, reqp))]
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        // Authorization: only trusted subjects are allowed to perform operations with the system
+        let audience = context.agent_id().as_account_id().audience();
+
+        let authz_time = context
+            .authz()
+            .authorize(
+                audience.into(),
+                reqp,
+                AuthzObject::new(&["system"]).into(),
+                "read".into(),
+            )
+            .await?;
+        context.metrics().observe_auth(authz_time);
+
+        let id: EventId = (payload.entity_type, payload.operation, payload.sequence_id).into();
+
+        let mut conn = context.get_conn().await?;
+        let history = outbox::db::sqlx::HistoryFindQuery::new(&id)
+            .execute(&mut conn)
+            .await?
+            .ok_or_else(|| anyhow!("Stage history not found"))
+            .error(AppErrorKind::StageNotFound)?;
+
+        Ok(Response::new(
+            ResponseStatus::OK,
+            history,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
@@ -0,0 +1,138 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use svc_agent::mqtt::ResponseStatus;
+use svc_authn::Authenticable;
+use tracing_attributes::instrument;
+
+use crate::{
+    app::{
+        context::Context,
+        endpoint::prelude::*,
+        service_utils::{RequestParams, Response},
+    },
+    authz::AuthzObject,
+    db,
+};
+
+const MAX_LIMIT: i64 = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    audience: Option<String>,
+    #[serde(default, with = "chrono::serde::ts_seconds_option")]
+    since: Option<DateTime<Utc>>,
+    offset: Option<i64>,
+    limit: Option<i64>,
+}
+
+pub struct Handler;
+
+#[async_trait]
+impl RequestHandler for Handler {
+    type Payload = Request;
+    const ERROR_TITLE: &'static str = "Failed to read tenant usage";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::SYSTEM_READ);
+
+    #[instrument(skip(context, payload, reqp))]
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        // Authorization: only trusted subjects are allowed to perform operations with the system
+        let audience = context.agent_id().as_account_id().audience();
+
+        let authz_time = context
+            .authz()
+            .authorize(
+                audience.into(),
+                reqp,
+                AuthzObject::new(&["system"]).into(),
+                "read".into(),
+            )
+            .await?;
+        context.metrics().observe_auth(authz_time);
+
+        let mut query = db::tenant_usage::ListQuery::new()
+            .offset(payload.offset.unwrap_or(0))
+            .limit(std::cmp::min(payload.limit.unwrap_or(MAX_LIMIT), MAX_LIMIT));
+
+        if let Some(ref audience) = payload.audience {
+            query = query.audience(audience);
+        }
+
+        if let Some(since) = payload.since {
+            query = query.since(since);
+        }
+
+        let mut conn = context.get_conn().await?;
+        let usage = query.execute(&mut conn).await?;
+
+        Ok(Response::new(
+            ResponseStatus::OK,
+            usage,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        app::tenant_usage::Counters,
+        test_helpers::{db::TestDb, prelude::*},
+    };
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn read_tenant_usage(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let mut conn = db.get_conn().await;
+
+        let counters = Counters {
+            requests: 2,
+            db_time_ms: 30,
+            janus_calls: 1,
+            bytes_published: 512,
+        };
+
+        // Both flushes land in the same hour.
+        for _ in 0..2 {
+            db::tenant_usage::add(USR_AUDIENCE, &counters, &mut conn)
+                .await
+                .expect("Failed to store tenant usage");
+        }
+
+        db::tenant_usage::add("other.example.org", &counters, &mut conn)
+            .await
+            .expect("Failed to store tenant usage");
+
+        let mut authz = TestAuthz::new();
+        authz.set_audience(SVC_AUDIENCE);
+        let agent = TestAgent::new("alpha", "cron", SVC_AUDIENCE);
+        authz.allow(agent.account_id(), vec!["system"], "read");
+
+        let mut context = TestContext::new(db, authz).await;
+
+        let payload = Request {
+            audience: Some(USR_AUDIENCE.to_owned()),
+            since: None,
+            offset: None,
+            limit: None,
+        };
+
+        let messages = handle_request::<Handler>(&mut context, &agent, payload)
+            .await
+            .expect("Tenant usage reading failed");
+
+        let (usage, respp, _) = find_response::<Vec<db::tenant_usage::Object>>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert_eq!(usage.len(), 1);
+        assert_eq!(usage[0].audience, USR_AUDIENCE);
+        assert_eq!(usage[0].requests, 4);
+        assert_eq!(usage[0].bytes_published, 1024);
+    }
+}
@@ -0,0 +1,109 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+use svc_agent::mqtt::ResponseStatus;
+use svc_authn::Authenticable;
+use tracing::warn;
+use tracing_attributes::instrument;
+
+use crate::{
+    app::{
+        context::Context,
+        endpoint::prelude::*,
+        service_utils::{RequestParams, Response},
+    },
+    authz::AuthzObject,
+    backend::janus::client::{compat, JanusClient},
+};
+
+#[derive(Debug, Deserialize)]
+pub struct Request {
+    janus_url: String,
+    /// Group the backend is going to join. Picks the HTTP transport configured for it.
+    group: Option<String>,
+}
+
+pub struct Handler;
+
+#[async_trait]
+impl RequestHandler for Handler {
+    type Payload = Request;
+    const ERROR_TITLE: &'static str = "Failed to validate backend";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::SYSTEM_UPDATE);
+
+    #[instrument(skip(context, reqp), fields(janus_url = %payload.janus_url))]
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        // Authorization: only trusted subjects are allowed to perform operations with the system
+        let audience = context.agent_id().as_account_id().audience();
+
+        let authz_time = context
+            .authz()
+            .authorize(
+                audience.into(),
+                reqp,
+                AuthzObject::new(&["system"]).into(),
+                "update".into(),
+            )
+            .await?;
+        context.metrics().observe_auth(authz_time);
+
+        let transport = context.janus_clients().transport(payload.group.as_deref());
+
+        let client = JanusClient::with_http(transport, &payload.janus_url)
+            .error(AppErrorKind::InvalidPayload)?;
+
+        let report = compat::validate(&client).await;
+
+        if !report.ok {
+            warn!(?report, "Backend failed compatibility check");
+        }
+
+        Ok(Response::new(
+            ResponseStatus::OK,
+            report,
+            context.start_timestamp(),
+            Some(authz_time),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value as JsonValue};
+
+    use crate::test_helpers::{db::TestDb, prelude::*};
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn validate_unreachable_backend(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+
+        let mut authz = TestAuthz::new();
+        authz.set_audience(SVC_AUDIENCE);
+        let agent = TestAgent::new("alpha", "cron", SVC_AUDIENCE);
+        authz.allow(agent.account_id(), vec!["system"], "update");
+
+        let mut context = TestContext::new(db, authz).await;
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+        context.with_janus(tx);
+
+        let payload = Request {
+            janus_url: "http://127.0.0.1:1/janus".to_owned(),
+            group: None,
+        };
+
+        let messages = handle_request::<Handler>(&mut context, &agent, payload)
+            .await
+            .expect("Backend validation failed");
+
+        let (report, respp, _) = find_response::<JsonValue>(messages.as_slice());
+        assert_eq!(respp.status(), ResponseStatus::OK);
+        assert_eq!(report["ok"], json!(false));
+        assert_eq!(report["checks"][0]["name"], json!("create_session"));
+        assert!(report["checks"][0]["error"].is_string());
+    }
+}
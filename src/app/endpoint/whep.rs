@@ -0,0 +1,130 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context as AnyhowContext};
+use axum::{
+    extract::Path,
+    response::{IntoResponse, Response as HttpResponse},
+    Extension,
+};
+use http::{HeaderMap, StatusCode};
+use serde_json::json;
+use svc_agent::{mqtt::ResponseStatus, AccountId, AgentId};
+use svc_utils::extractors::AccountIdExtractor;
+
+use crate::{
+    app::{
+        context::{AppContext, GlobalContext, MessageContext},
+        endpoint::{
+            prelude::{AppError, AppErrorKind, ErrorExt},
+            rtc::ConnectIntent,
+            rtc_signal,
+            whip::{self, SDP, SDP_FRAG},
+        },
+        handle_id::HandleId,
+        message_handler::http::admit,
+        service_utils::Response,
+    },
+    db,
+};
+
+const LABEL_PREFIX: &str = "whep-";
+
+/// Subscribes the WHEP player to the rtc with its receive-only offer and responds with the answer.
+pub async fn play(
+    Extension(ctx): Extension<Arc<AppContext>>,
+    AccountIdExtractor(account_id): AccountIdExtractor,
+    Path((room_id, rtc_id)): Path<(db::room::Id, db::rtc::Id)>,
+    headers: HeaderMap,
+    offer: String,
+) -> Result<HttpResponse, AppError> {
+    tracing::Span::current().record("room_id", tracing::field::display(room_id));
+    tracing::Span::current().record("rtc_id", tracing::field::display(rtc_id));
+
+    let _in_flight = admit(&ctx, false)?;
+    whip::check_content_type(&headers, SDP)?;
+
+    // Otherwise the offer would publish into the rtc with read permissions only.
+    let is_recvonly = rtc_signal::is_sdp_recvonly(&offer)
+        .context("Invalid SDP offer")
+        .error(AppErrorKind::InvalidJsepFormat)?;
+
+    if !is_recvonly {
+        return Err(anyhow!("WHEP offer must be receive-only")).error(AppErrorKind::InvalidSdpType);
+    }
+
+    let ctx = &mut ctx.start_message();
+
+    {
+        let mut conn = ctx.get_conn().await?;
+        db::rtc::FindQuery::new(rtc_id)
+            .execute(&mut conn)
+            .await?
+            .filter(|rtc| rtc.room_id() == room_id)
+            .context("RTC not found")
+            .error(AppErrorKind::RtcNotFound)?;
+    }
+
+    let label = format!("{}{}", LABEL_PREFIX, uuid::Uuid::new_v4().simple());
+    let agent_id = AgentId::new(&label, account_id);
+
+    let (handle_id, answer) =
+        whip::negotiate(ctx, rtc_id, ConnectIntent::Read, &agent_id, room_id, offer).await?;
+
+    let response = Response::new(
+        ResponseStatus::CREATED,
+        json!({}),
+        ctx.start_timestamp(),
+        None,
+    );
+
+    let location = format!("/api/v1/whep/{}/{}", label, handle_id);
+    Ok(whip::sdp_response(response, location, answer))
+}
+
+/// Passes trickled candidates of the player on to Janus.
+pub async fn trickle(
+    Extension(ctx): Extension<Arc<AppContext>>,
+    AccountIdExtractor(account_id): AccountIdExtractor,
+    Path((label, handle_id)): Path<(String, HandleId)>,
+    headers: HeaderMap,
+    sdp_frag: String,
+) -> Result<HttpResponse, AppError> {
+    tracing::Span::current().record("rtc_id", tracing::field::display(handle_id.rtc_id()));
+
+    let _in_flight = admit(&ctx, false)?;
+    whip::check_content_type(&headers, SDP_FRAG)?;
+
+    let agent_id = player(&label, account_id)?;
+    let candidates = whip::parse_sdp_frag(&sdp_frag)?;
+    let ctx = &mut ctx.start_message();
+
+    rtc_signal::trickle_candidates(ctx, handle_id, candidates, agent_id).await?;
+
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+/// Stops the playback: detaches the handle and removes the player from the room.
+pub async fn stop(
+    Extension(ctx): Extension<Arc<AppContext>>,
+    AccountIdExtractor(account_id): AccountIdExtractor,
+    Path((label, handle_id)): Path<(String, HandleId)>,
+) -> Result<HttpResponse, AppError> {
+    tracing::Span::current().record("rtc_id", tracing::field::display(handle_id.rtc_id()));
+
+    // Teardown releases resources so it's served while draining.
+    let _in_flight = admit(&ctx, true)?;
+
+    let agent_id = player(&label, account_id)?;
+    let ctx = &mut ctx.start_message();
+
+    whip::teardown(ctx, handle_id, &agent_id).await
+}
+
+/// The resource carries the label of the player's agent, the handle must have been issued to it.
+fn player(label: &str, account_id: AccountId) -> Result<AgentId, AppError> {
+    if !label.starts_with(LABEL_PREFIX) {
+        return Err(anyhow!("Not a WHEP session")).error(AppErrorKind::AccessDenied);
+    }
+
+    Ok(AgentId::new(label, account_id))
+}
@@ -0,0 +1,365 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context as AnyhowContext};
+use axum::{
+    extract::Path,
+    response::{IntoResponse, Response as HttpResponse},
+    Extension,
+};
+use either::Either;
+use futures::StreamExt;
+use http::{header, HeaderMap, StatusCode};
+use serde_json::{json, Value as JsonValue};
+use svc_agent::{mqtt::ResponseStatus, AccountId, AgentId, Authenticable};
+use svc_utils::extractors::AccountIdExtractor;
+
+use crate::{
+    app::{
+        context::{AppContext, Context, GlobalContext, MessageContext},
+        endpoint::{
+            helpers,
+            prelude::{AppError, AppErrorKind, ErrorExt},
+            rtc::{self, ConnectIntent, RtcCreate, RtcCreateResult},
+            rtc_signal,
+        },
+        handle_id::HandleId,
+        message_handler::http::admit,
+        service_utils::{RequestParams, Response},
+    },
+    backend::janus::{
+        self,
+        client::{create_handle::OpaqueId, IceCandidateSdp},
+    },
+    db,
+};
+
+pub(super) const SDP: &str = "application/sdp";
+pub(super) const SDP_FRAG: &str = "application/trickle-ice-sdpfrag";
+
+/// Publishes the WHIP (RFC 9725) offer of the broadcaster and responds with the answer.
+pub async fn publish(
+    Extension(ctx): Extension<Arc<AppContext>>,
+    AccountIdExtractor(account_id): AccountIdExtractor,
+    Path(room_id): Path<db::room::Id>,
+    headers: HeaderMap,
+    offer: String,
+) -> Result<HttpResponse, AppError> {
+    tracing::Span::current().record("room_id", tracing::field::display(room_id));
+
+    let _in_flight = admit(&ctx, false)?;
+    check_content_type(&headers, SDP)?;
+
+    let agent_id = AgentId::new(
+        format!("whip-{}", uuid::Uuid::new_v4().simple()),
+        account_id,
+    );
+    let ctx = &mut ctx.start_message();
+
+    let RtcCreateResult {
+        rtc,
+        authz_time,
+        notification_label,
+        notification_topic,
+    } = RtcCreate {
+        ctx: &*ctx,
+        room: Either::Right(room_id),
+        reqp: RequestParams::Http {
+            agent_id: &agent_id,
+        },
+        tags: None,
+        kind: None,
+    }
+    .run()
+    .await?;
+
+    let (handle_id, answer) = negotiate(
+        ctx,
+        rtc.id(),
+        ConnectIntent::Write,
+        &agent_id,
+        room_id,
+        offer,
+    )
+    .await?;
+
+    let mut response = Response::new(
+        ResponseStatus::CREATED,
+        json!({}),
+        ctx.start_timestamp(),
+        Some(authz_time),
+    );
+
+    response.add_notification(
+        notification_label,
+        &notification_topic,
+        rtc,
+        ctx.start_timestamp(),
+    );
+
+    let location = format!("/api/v1/whip/{}", handle_id);
+    Ok(sdp_response(response, location, answer))
+}
+
+/// Passes trickled candidates of the broadcaster on to Janus.
+pub async fn trickle(
+    Extension(ctx): Extension<Arc<AppContext>>,
+    AccountIdExtractor(account_id): AccountIdExtractor,
+    Path(handle_id): Path<HandleId>,
+    headers: HeaderMap,
+    sdp_frag: String,
+) -> Result<HttpResponse, AppError> {
+    tracing::Span::current().record("rtc_id", tracing::field::display(handle_id.rtc_id()));
+
+    let _in_flight = admit(&ctx, false)?;
+    check_content_type(&headers, SDP_FRAG)?;
+
+    let candidates = parse_sdp_frag(&sdp_frag)?;
+    let ctx = &mut ctx.start_message();
+    let agent_id = find_publisher(ctx, &handle_id, &account_id).await?;
+
+    rtc_signal::trickle_candidates(ctx, handle_id, candidates, agent_id).await?;
+
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+/// Tears the broadcast down: detaches the handle and removes the broadcaster from the room.
+pub async fn stop(
+    Extension(ctx): Extension<Arc<AppContext>>,
+    AccountIdExtractor(account_id): AccountIdExtractor,
+    Path(handle_id): Path<HandleId>,
+) -> Result<HttpResponse, AppError> {
+    tracing::Span::current().record("rtc_id", tracing::field::display(handle_id.rtc_id()));
+
+    // Teardown releases resources so it's served while draining.
+    let _in_flight = admit(&ctx, true)?;
+
+    let ctx = &mut ctx.start_message();
+    let agent_id = find_publisher(ctx, &handle_id, &account_id).await?;
+
+    teardown(ctx, handle_id, &agent_id).await
+}
+
+/// The agent never enters the room over MQTT so it's registered as present for the time
+/// of negotiation and stays there until the session is torn down.
+pub(super) async fn negotiate<C: Context>(
+    ctx: &mut C,
+    rtc_id: db::rtc::Id,
+    intent: ConnectIntent,
+    agent_id: &AgentId,
+    room_id: db::room::Id,
+    offer: String,
+) -> Result<(HandleId, String), AppError> {
+    {
+        let mut conn = ctx.get_conn().await?;
+        db::agent::InsertQuery::new(agent_id, room_id)
+            .status(db::agent::Status::Ready)
+            .execute(&mut conn)
+            .await?;
+    }
+
+    let result = rtc::connect_with_offer(ctx, rtc_id, intent, agent_id.clone(), offer, None).await;
+
+    let answer = match result {
+        Ok((handle_id, Some(answer))) => answer
+            .get("sdp")
+            .and_then(JsonValue::as_str)
+            .map(|sdp| (handle_id, sdp.to_owned()))
+            .context("No SDP in the answer")
+            .error(AppErrorKind::MessageParsingFailed),
+        Ok((_, None)) => {
+            Err(anyhow!("Janus returned no answer")).error(AppErrorKind::BackendRequestFailed)
+        }
+        Err(err) => Err(err),
+    };
+
+    if answer.is_err() {
+        leave(ctx, agent_id, room_id).await?;
+    }
+
+    answer
+}
+
+pub(super) fn sdp_response(response: Response, location: String, answer: String) -> HttpResponse {
+    let headers = [
+        (header::CONTENT_TYPE, SDP.to_owned()),
+        (header::LOCATION, location),
+    ];
+
+    response.into_raw_response((headers, answer))
+}
+
+/// Detaches the handle of the session and removes its agent from the room.
+pub(super) async fn teardown<C: Context>(
+    ctx: &mut C,
+    handle_id: HandleId,
+    agent_id: &AgentId,
+) -> Result<HttpResponse, AppError> {
+    let room = {
+        let mut conn = ctx.get_conn().await?;
+        let room = helpers::find_room_by_rtc_id(
+            handle_id.rtc_id(),
+            helpers::RoomTimeRequirement::Any,
+            helpers::AudienceScope::caller(&*ctx, agent_id),
+            &mut conn,
+        )
+        .await?;
+
+        rtc_signal::validate_handle(&handle_id, agent_id, &room, &mut conn).await?;
+        room
+    };
+
+    let opaque_id = OpaqueId {
+        stream_id: handle_id.rtc_stream_id(),
+        room_id: room.id(),
+    };
+
+    let messages = janus::detach_handle(ctx, opaque_id, handle_id)
+        .await?
+        .collect::<Vec<_>>()
+        .await;
+
+    leave(ctx, agent_id, room.id()).await?;
+
+    let mut response = Response::new(
+        ResponseStatus::NO_CONTENT,
+        json!({}),
+        ctx.start_timestamp(),
+        None,
+    );
+
+    for message in messages {
+        response.add_message(message);
+    }
+
+    Ok(response.into_raw_response(()))
+}
+
+pub(super) fn check_content_type(headers: &HeaderMap, expected: &str) -> Result<(), AppError> {
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .map(str::trim);
+
+    match content_type {
+        Some(content_type) if content_type.eq_ignore_ascii_case(expected) => Ok(()),
+        _ => Err(anyhow!("Expected {} content type", expected)).error(AppErrorKind::InvalidPayload),
+    }
+}
+
+/// The rtc behind the handle must have been published by the caller's account,
+/// its creator is the broadcaster's agent.
+async fn find_publisher<C: Context>(
+    ctx: &mut C,
+    handle_id: &HandleId,
+    account_id: &AccountId,
+) -> Result<AgentId, AppError> {
+    let mut conn = ctx.get_conn().await?;
+    let rtc = db::rtc::FindQuery::new(handle_id.rtc_id())
+        .execute(&mut conn)
+        .await?
+        .context("RTC not found")
+        .error(AppErrorKind::RtcNotFound)?;
+
+    let agent_id = rtc.created_by();
+
+    if agent_id.as_account_id() != account_id || !agent_id.label().starts_with("whip-") {
+        return Err(anyhow!("The broadcast belongs to another agent"))
+            .error(AppErrorKind::AccessDenied);
+    }
+
+    Ok(agent_id.to_owned())
+}
+
+async fn leave<C: GlobalContext>(
+    ctx: &C,
+    agent_id: &AgentId,
+    room_id: db::room::Id,
+) -> Result<(), AppError> {
+    let mut conn = ctx.get_conn().await?;
+    db::agent::DeleteQuery::new()
+        .agent_id(agent_id)
+        .room_id(room_id)
+        .execute(&mut conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Translates a trickle-ice-sdpfrag (RFC 8840) into Janus trickle candidates.
+/// Candidates belong to the media section they follow, `a=end-of-candidates` completes them.
+pub(super) fn parse_sdp_frag(sdp_frag: &str) -> Result<IceCandidateSdp, AppError> {
+    let mut items = Vec::new();
+    let mut m_line_index: Option<u16> = None;
+    let mut mid = None;
+
+    for line in sdp_frag.lines().map(str::trim) {
+        if line.starts_with("m=") {
+            m_line_index = Some(m_line_index.map_or(0, |index| index + 1));
+            mid = None;
+        } else if let Some(value) = line.strip_prefix("a=mid:") {
+            mid = Some(value.to_owned());
+        } else if let Some(candidate) = line.strip_prefix("a=") {
+            if candidate.starts_with("candidate:") {
+                items.push(json!({
+                    "sdpMid": mid.clone().unwrap_or_else(|| "0".to_owned()),
+                    "sdpMLineIndex": m_line_index.unwrap_or(0),
+                    "candidate": candidate,
+                }));
+            } else if candidate == "end-of-candidates" {
+                items.push(json!({ "completed": true }));
+            }
+        }
+    }
+
+    if items.is_empty() {
+        return Err(anyhow!("No candidates in the SDP fragment"))
+            .error(AppErrorKind::InvalidPayload);
+    }
+
+    serde_json::from_value(JsonValue::Array(items))
+        .context("Malformed SDP fragment")
+        .error(AppErrorKind::InvalidPayload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_trickle_sdp_frag() {
+        let sdp_frag = "a=ice-ufrag:EsAw\r\n\
+            a=ice-pwd:P2uYro0UCOQ4zxjKXaWCBui1\r\n\
+            m=audio 9 RTP/AVP 0\r\n\
+            a=mid:0\r\n\
+            a=candidate:1387637174 1 udp 2122260223 192.0.2.1 61764 typ host generation 0\r\n\
+            m=video 9 RTP/AVP 96\r\n\
+            a=mid:1\r\n\
+            a=candidate:3471623853 1 udp 2122194687 198.51.100.2 61765 typ host\r\n\
+            a=end-of-candidates\r\n";
+
+        let candidates = parse_sdp_frag(sdp_frag).expect("Failed to parse SDP fragment");
+        let candidates = serde_json::to_value(candidates).expect("Failed to serialize candidates");
+
+        assert_eq!(
+            candidates,
+            json!([
+                {
+                    "sdpMid": "0",
+                    "sdpMLineIndex": 0,
+                    "candidate": "candidate:1387637174 1 udp 2122260223 192.0.2.1 61764 typ host generation 0",
+                    "usernameFragment": null,
+                },
+                {
+                    "sdpMid": "1",
+                    "sdpMLineIndex": 1,
+                    "candidate": "candidate:3471623853 1 udp 2122194687 198.51.100.2 61765 typ host",
+                    "usernameFragment": null,
+                },
+                { "completed": true },
+            ])
+        );
+
+        parse_sdp_frag("a=ice-ufrag:EsAw\r\n").expect_err("Empty fragment accepted");
+    }
+}
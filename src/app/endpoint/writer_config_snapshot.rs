@@ -11,6 +11,7 @@ use svc_utils::extractors::AgentIdExtractor;
 use crate::app::endpoint::prelude::*;
 use crate::app::{
     context::{AppContext, Context},
+    message_handler::http,
     service_utils::{RequestParams, Response},
 };
 use crate::db;
@@ -30,14 +31,7 @@ pub async fn read(
     tracing::Span::current().record("room_id", &tracing::field::display(room_id));
 
     let request = ReadRequest { room_id };
-    ReadHandler::handle(
-        &mut ctx.start_message(),
-        request,
-        RequestParams::Http {
-            agent_id: &agent_id,
-        },
-    )
-    .await
+    http::handle::<ReadHandler>(&ctx, &agent_id, request).await
 }
 
 pub struct ReadHandler;
@@ -46,6 +40,7 @@ pub struct ReadHandler;
 impl RequestHandler for ReadHandler {
     type Payload = ReadRequest;
     const ERROR_TITLE: &'static str = "Failed to read writer config snapshots";
+    const AUTHZ: Option<AuthzIntent> = Some(AuthzIntent::Dispatcher);
 
     async fn handle<C: Context + Send + Sync>(
         context: &mut C,
@@ -59,6 +54,7 @@ impl RequestHandler for ReadHandler {
         let room = helpers::find_room_by_id(
             payload.room_id,
             helpers::RoomTimeRequirement::Any,
+            helpers::AudienceScope::caller(context, &reqp),
             &mut conn,
         )
         .await?;
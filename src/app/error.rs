@@ -1,6 +1,9 @@
 use std::{error::Error as StdError, fmt, sync::Arc};
 
-use crate::outbox::error::PipelineError;
+use crate::{
+    db::backend_error::Id as BackendErrorId,
+    outbox::error::{ErrorKind as PipelineErrorKind, PipelineError},
+};
 use enum_iterator::IntoEnumIterator;
 use svc_agent::mqtt::ResponseStatus;
 use svc_error::{extension::sentry, Error as SvcError};
@@ -19,8 +22,12 @@ struct ErrorKindProperties {
 )]
 pub enum ErrorKind {
     AccessDenied,
+    AgentBanned,
+    AgentNotAdmitted,
     AgentNotConnected,
     AgentNotEnteredTheRoom,
+    AmbiguousClassroom,
+    AudioProcessingUnsupported,
     AuthorizationFailed,
     AuthenticationFailed,
     BackendRecordingMissing,
@@ -28,9 +35,14 @@ pub enum ErrorKind {
     BackendClientCreationFailed,
     _BackendRequestTimedOut,
     BackendNotFound,
+    BackendRequestsThrottled,
+    BanNotFound,
     BrokerRequestFailed,
     CapacityExceeded,
+    CodecMismatch,
     ConfigKeyMissing,
+    Conflict,
+    ConnectionLimitExceeded,
     DbConnAcquisitionFailed,
     DbQueryFailed,
     InvalidHandleId,
@@ -39,19 +51,35 @@ pub enum ErrorKind {
     InvalidSdpType,
     InvalidSubscriptionObject,
     InvalidPayload,
+    MalformedSdp,
     MessageBuildingFailed,
     MessageHandlingFailed,
     MessageReceivingFailed,
     MessageParsingFailed,
+    MessageTooLarge,
     NoAvailableBackends,
     NotImplemented,
+    PollClosed,
+    PollNotFound,
     PublishFailed,
+    RecordingNotFound,
+    ReserveExceedsCapacity,
     ResubscriptionFailed,
+    RoomBulkCloseNotFound,
     RoomClosed,
+    RoomFull,
     RoomNotFound,
+    RoomPolicyCheckFailed,
+    RoomPolicyViolation,
+    RoomScheduleNotFound,
+    RoomTemplateNotFound,
     RoomTimeChangingForbidden,
     RtcNotFound,
-    MethodNotSupported,
+    SdpTooLarge,
+    ServiceDraining,
+    StageNotFound,
+    TooManyPolls,
+    UnknownMethod,
     JanusResponseTimeout,
     OutboxStageSerializationFailed,
     MqttPublishFailed,
@@ -80,6 +108,11 @@ impl ErrorKind {
         let properties: ErrorKindProperties = self.into();
         properties.is_notify_sentry
     }
+
+    /// Looks the variant up by its `kind` string, e.g. the one an outbox stage has failed with.
+    pub fn from_kind(kind: &str) -> Option<Self> {
+        Self::into_enum_iter().find(|k| k.kind() == kind)
+    }
 }
 
 impl fmt::Display for ErrorKind {
@@ -98,6 +131,18 @@ impl From<ErrorKind> for ErrorKindProperties {
                 title: "Access denied",
                 is_notify_sentry: false,
             },
+            ErrorKind::AgentBanned => ErrorKindProperties {
+                status: ResponseStatus::FORBIDDEN,
+                kind: "agent_banned",
+                title: "Agent banned",
+                is_notify_sentry: false,
+            },
+            ErrorKind::AgentNotAdmitted => ErrorKindProperties {
+                status: ResponseStatus::FORBIDDEN,
+                kind: "agent_not_admitted",
+                title: "Agent not admitted to the room",
+                is_notify_sentry: false,
+            },
             ErrorKind::AgentNotConnected => ErrorKindProperties {
                 status: ResponseStatus::UNPROCESSABLE_ENTITY,
                 kind: "agent_not_connected",
@@ -110,6 +155,18 @@ impl From<ErrorKind> for ErrorKindProperties {
                 title: "Agent not entered the room",
                 is_notify_sentry: false,
             },
+            ErrorKind::AmbiguousClassroom => ErrorKindProperties {
+                status: ResponseStatus::CONFLICT,
+                kind: "ambiguous_classroom",
+                title: "Classroom has several open rooms",
+                is_notify_sentry: false,
+            },
+            ErrorKind::AudioProcessingUnsupported => ErrorKindProperties {
+                status: ResponseStatus::UNPROCESSABLE_ENTITY,
+                kind: "audio_processing_unsupported",
+                title: "Audio processing unsupported",
+                is_notify_sentry: false,
+            },
             ErrorKind::AuthorizationFailed => ErrorKindProperties {
                 status: ResponseStatus::UNPROCESSABLE_ENTITY,
                 kind: "authorization_failed",
@@ -146,6 +203,18 @@ impl From<ErrorKind> for ErrorKindProperties {
                 title: "Backend not found",
                 is_notify_sentry: true,
             },
+            ErrorKind::BackendRequestsThrottled => ErrorKindProperties {
+                status: ResponseStatus::TOO_MANY_REQUESTS,
+                kind: "backend_requests_throttled",
+                title: "Too many requests to the backend",
+                is_notify_sentry: false,
+            },
+            ErrorKind::BanNotFound => ErrorKindProperties {
+                status: ResponseStatus::NOT_FOUND,
+                kind: "ban_not_found",
+                title: "Ban not found",
+                is_notify_sentry: false,
+            },
             ErrorKind::BrokerRequestFailed => ErrorKindProperties {
                 status: ResponseStatus::UNPROCESSABLE_ENTITY,
                 kind: "broker_request_failed",
@@ -164,12 +233,30 @@ impl From<ErrorKind> for ErrorKindProperties {
                 title: "Capacity exceeded",
                 is_notify_sentry: true,
             },
+            ErrorKind::CodecMismatch => ErrorKindProperties {
+                status: ResponseStatus::UNSUPPORTED_MEDIA_TYPE,
+                kind: "codec_mismatch",
+                title: "Codec mismatch",
+                is_notify_sentry: false,
+            },
+            ErrorKind::ConnectionLimitExceeded => ErrorKindProperties {
+                status: ResponseStatus::FORBIDDEN,
+                kind: "connection_limit_exceeded",
+                title: "Connection limit exceeded",
+                is_notify_sentry: false,
+            },
             ErrorKind::DbConnAcquisitionFailed => ErrorKindProperties {
                 status: ResponseStatus::UNPROCESSABLE_ENTITY,
                 kind: "database_connection_acquisition_failed",
                 title: "Database connection acquisition failed",
                 is_notify_sentry: true,
             },
+            ErrorKind::Conflict => ErrorKindProperties {
+                status: ResponseStatus::CONFLICT,
+                kind: "conflict",
+                title: "Conflict",
+                is_notify_sentry: false,
+            },
             ErrorKind::DbQueryFailed => ErrorKindProperties {
                 status: ResponseStatus::UNPROCESSABLE_ENTITY,
                 kind: "database_query_failed",
@@ -212,6 +299,12 @@ impl From<ErrorKind> for ErrorKindProperties {
                 title: "Invalid subscription object",
                 is_notify_sentry: true,
             },
+            ErrorKind::MalformedSdp => ErrorKindProperties {
+                status: ResponseStatus::BAD_REQUEST,
+                kind: "malformed_sdp",
+                title: "Malformed SDP",
+                is_notify_sentry: false,
+            },
             ErrorKind::MessageBuildingFailed => ErrorKindProperties {
                 status: ResponseStatus::UNPROCESSABLE_ENTITY,
                 kind: "message_building_failed",
@@ -230,6 +323,12 @@ impl From<ErrorKind> for ErrorKindProperties {
                 title: "Message parsing failed",
                 is_notify_sentry: true,
             },
+            ErrorKind::MessageTooLarge => ErrorKindProperties {
+                status: ResponseStatus::PAYLOAD_TOO_LARGE,
+                kind: "message_too_large",
+                title: "Message too large",
+                is_notify_sentry: false,
+            },
             ErrorKind::NoAvailableBackends => ErrorKindProperties {
                 status: ResponseStatus::SERVICE_UNAVAILABLE,
                 kind: "no_available_backends",
@@ -242,53 +341,137 @@ impl From<ErrorKind> for ErrorKindProperties {
                 title: "Not implemented",
                 is_notify_sentry: true,
             },
+            ErrorKind::PollClosed => ErrorKindProperties {
+                status: ResponseStatus::UNPROCESSABLE_ENTITY,
+                kind: "poll_closed",
+                title: "Poll closed",
+                is_notify_sentry: false,
+            },
+            ErrorKind::PollNotFound => ErrorKindProperties {
+                status: ResponseStatus::NOT_FOUND,
+                kind: "poll_not_found",
+                title: "Poll not found",
+                is_notify_sentry: false,
+            },
             ErrorKind::PublishFailed => ErrorKindProperties {
                 status: ResponseStatus::UNPROCESSABLE_ENTITY,
                 kind: "publish_failed",
                 title: "Publish failed",
                 is_notify_sentry: true,
             },
+            ErrorKind::ReserveExceedsCapacity => ErrorKindProperties {
+                status: ResponseStatus::UNPROCESSABLE_ENTITY,
+                kind: "reserve_exceeds_capacity",
+                title: "Reserve exceeds backend capacity",
+                is_notify_sentry: false,
+            },
             ErrorKind::ResubscriptionFailed => ErrorKindProperties {
                 status: ResponseStatus::INTERNAL_SERVER_ERROR,
                 kind: "resubscription_failed",
                 title: "Resubscription failed",
                 is_notify_sentry: true,
             },
+            ErrorKind::RoomBulkCloseNotFound => ErrorKindProperties {
+                status: ResponseStatus::NOT_FOUND,
+                kind: "room_bulk_close_not_found",
+                title: "Room bulk close job not found",
+                is_notify_sentry: false,
+            },
             ErrorKind::RoomClosed => ErrorKindProperties {
                 status: ResponseStatus::NOT_FOUND,
                 kind: "room_closed",
                 title: "Room closed",
                 is_notify_sentry: false,
             },
+            ErrorKind::RoomFull => ErrorKindProperties {
+                status: ResponseStatus::CONFLICT,
+                kind: "room_full",
+                title: "Room is full",
+                is_notify_sentry: false,
+            },
             ErrorKind::RoomNotFound => ErrorKindProperties {
                 status: ResponseStatus::NOT_FOUND,
                 kind: "room_not_found",
                 title: "Room not found",
                 is_notify_sentry: false,
             },
+            ErrorKind::RoomPolicyCheckFailed => ErrorKindProperties {
+                status: ResponseStatus::FAILED_DEPENDENCY,
+                kind: "room_policy_check_failed",
+                title: "Room policy check failed",
+                is_notify_sentry: true,
+            },
+            ErrorKind::RoomPolicyViolation => ErrorKindProperties {
+                status: ResponseStatus::UNPROCESSABLE_ENTITY,
+                kind: "room_policy_violation",
+                title: "Room policy violation",
+                is_notify_sentry: false,
+            },
+            ErrorKind::RoomScheduleNotFound => ErrorKindProperties {
+                status: ResponseStatus::NOT_FOUND,
+                kind: "room_schedule_not_found",
+                title: "Room schedule not found",
+                is_notify_sentry: false,
+            },
+            ErrorKind::RoomTemplateNotFound => ErrorKindProperties {
+                status: ResponseStatus::NOT_FOUND,
+                kind: "room_template_not_found",
+                title: "Room template not found",
+                is_notify_sentry: false,
+            },
             ErrorKind::RoomTimeChangingForbidden => ErrorKindProperties {
                 status: ResponseStatus::UNPROCESSABLE_ENTITY,
                 kind: "room_time_changing_forbidden",
                 title: "Room time changing forbidden",
                 is_notify_sentry: false,
             },
+            ErrorKind::RecordingNotFound => ErrorKindProperties {
+                status: ResponseStatus::NOT_FOUND,
+                kind: "recording_not_found",
+                title: "Recording not found",
+                is_notify_sentry: false,
+            },
             ErrorKind::RtcNotFound => ErrorKindProperties {
                 status: ResponseStatus::NOT_FOUND,
                 kind: "rtc_not_found",
                 title: "RTC not found",
                 is_notify_sentry: false,
             },
+            ErrorKind::SdpTooLarge => ErrorKindProperties {
+                status: ResponseStatus::PAYLOAD_TOO_LARGE,
+                kind: "sdp_too_large",
+                title: "SDP too large",
+                is_notify_sentry: false,
+            },
+            ErrorKind::ServiceDraining => ErrorKindProperties {
+                status: ResponseStatus::SERVICE_UNAVAILABLE,
+                kind: "service_draining",
+                title: "Service draining",
+                is_notify_sentry: false,
+            },
             ErrorKind::MessageReceivingFailed => ErrorKindProperties {
                 status: ResponseStatus::INTERNAL_SERVER_ERROR,
                 kind: "message_receiving_failed",
                 title: "Message receiving failed",
                 is_notify_sentry: true,
             },
-            ErrorKind::MethodNotSupported => ErrorKindProperties {
+            ErrorKind::StageNotFound => ErrorKindProperties {
+                status: ResponseStatus::NOT_FOUND,
+                kind: "stage_not_found",
+                title: "Stage not found",
+                is_notify_sentry: false,
+            },
+            ErrorKind::TooManyPolls => ErrorKindProperties {
+                status: ResponseStatus::TOO_MANY_REQUESTS,
+                kind: "too_many_polls",
+                title: "Too many event polls",
+                is_notify_sentry: false,
+            },
+            ErrorKind::UnknownMethod => ErrorKindProperties {
                 status: ResponseStatus::METHOD_NOT_ALLOWED,
-                kind: "method_not_supported",
-                title: "Method not supported",
-                is_notify_sentry: true,
+                kind: "unknown_method",
+                title: "Unknown method",
+                is_notify_sentry: false,
             },
             ErrorKind::AuthenticationFailed => ErrorKindProperties {
                 status: ResponseStatus::UNAUTHORIZED,
@@ -328,7 +511,7 @@ impl From<ErrorKind> for ErrorKindProperties {
             },
             ErrorKind::OutboxPipelineError => ErrorKindProperties {
                 status: ResponseStatus::FAILED_DEPENDENCY,
-                kind: "outbox pipeline error",
+                kind: "outbox_pipeline_error",
                 title: "Outbox pipeline error",
                 is_notify_sentry: true,
             },
@@ -341,6 +524,8 @@ impl From<ErrorKind> for ErrorKindProperties {
 pub struct Error {
     kind: ErrorKind,
     source: Option<Arc<anyhow::Error>>,
+    backend_error_id: Option<BackendErrorId>,
+    extras: Vec<(&'static str, String)>,
 }
 
 impl Error {
@@ -348,9 +533,25 @@ impl Error {
         Self {
             kind,
             source: Some(Arc::new(source.into())),
+            backend_error_id: None,
+            extras: vec![],
         }
     }
 
+    /// Links the error to the stored raw backend error so support could look it up.
+    pub fn with_backend_error(self, id: BackendErrorId) -> Self {
+        Self {
+            backend_error_id: Some(id),
+            ..self
+        }
+    }
+
+    /// Attaches a machine-readable hint to the error payload.
+    pub fn with_extra(mut self, key: &'static str, value: impl Into<String>) -> Self {
+        self.extras.push((key, value.into()));
+        self
+    }
+
     pub fn status(&self) -> ResponseStatus {
         self.kind.status()
     }
@@ -368,19 +569,34 @@ impl Error {
     }
 
     pub fn detail(&self) -> String {
-        match &self.source {
+        let detail = match &self.source {
             Some(s) => s.to_string(),
             None => String::new(),
+        };
+
+        match self.backend_error_id {
+            Some(id) => format!("{detail} (backend_error_id = {id})"),
+            None => detail,
         }
     }
 
     pub fn to_svc_error(&self) -> SvcError {
         let properties: ErrorKindProperties = self.kind.into();
-        SvcError::builder()
+        let mut error = SvcError::builder()
             .status(properties.status)
             .kind(properties.kind, properties.title)
             .detail(&self.detail())
-            .build()
+            .build();
+
+        if let Some(id) = self.backend_error_id {
+            error.set_extra("backend_error_id", &id.to_string());
+        }
+
+        for (key, value) in &self.extras {
+            error.set_extra(key, value);
+        }
+
+        error
     }
 
     pub fn notify_sentry(&self) {
@@ -401,6 +617,8 @@ impl fmt::Debug for Error {
         f.debug_struct("Error")
             .field("kind", &self.kind)
             .field("source", &self.source)
+            .field("backend_error_id", &self.backend_error_id)
+            .field("extras", &self.extras)
             .finish()
     }
 }
@@ -430,6 +648,8 @@ impl From<svc_authz::Error> for Error {
         Self {
             kind,
             source: Some(Arc::new(anyhow::Error::from(source))),
+            backend_error_id: None,
+            extras: vec![],
         }
     }
 }
@@ -439,6 +659,8 @@ impl From<sqlx::Error> for Error {
         Self {
             kind: ErrorKind::DbQueryFailed,
             source: Some(Arc::new(anyhow::anyhow!(value))),
+            backend_error_id: None,
+            extras: vec![],
         }
     }
 }
@@ -455,8 +677,260 @@ impl<T, E: Into<anyhow::Error>> ErrorExt<T> for Result<T, E> {
     }
 }
 
+/// Outbox pipelines have their own error kinds. Stage errors carry the kind of the app error
+/// the stage has failed with so it's restored instead of reporting a generic pipeline error.
 impl From<PipelineError> for Error {
     fn from(error: PipelineError) -> Self {
-        Error::new(ErrorKind::OutboxPipelineError, error)
+        let kind = match &error.kind {
+            PipelineErrorKind::StageError(kind) => {
+                ErrorKind::from_kind(kind).unwrap_or(ErrorKind::OutboxPipelineError)
+            }
+            PipelineErrorKind::DbConnAcquisitionFailed => ErrorKind::DbConnAcquisitionFailed,
+            PipelineErrorKind::DbQueryFailed => ErrorKind::DbQueryFailed,
+            PipelineErrorKind::SerializationFailed => ErrorKind::OutboxStageSerializationFailed,
+            PipelineErrorKind::LoadStagesFailed
+            | PipelineErrorKind::DeserializationFailed
+            | PipelineErrorKind::DeleteStageFailed
+            | PipelineErrorKind::UpdateStageFailed
+            | PipelineErrorKind::InsertStageFailed
+            | PipelineErrorKind::RecordHistoryFailed => ErrorKind::OutboxPipelineError,
+        };
+
+        Error::new(kind, error)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::outbox::error::StageError;
+
+    use super::*;
+
+    /// Kinds and statuses are part of the public API: clients branch on them, so any change
+    /// here must be deliberate and documented.
+    const GOLDEN: &[(ErrorKind, u16, &str)] = &[
+        (ErrorKind::AccessDenied, 403, "access_denied"),
+        (ErrorKind::AgentBanned, 403, "agent_banned"),
+        (ErrorKind::AgentNotAdmitted, 403, "agent_not_admitted"),
+        (ErrorKind::AgentNotConnected, 422, "agent_not_connected"),
+        (
+            ErrorKind::AgentNotEnteredTheRoom,
+            404,
+            "agent_not_entered_the_room",
+        ),
+        (ErrorKind::AmbiguousClassroom, 409, "ambiguous_classroom"),
+        (
+            ErrorKind::AudioProcessingUnsupported,
+            422,
+            "audio_processing_unsupported",
+        ),
+        (
+            ErrorKind::AuthenticationFailed,
+            401,
+            "authentication_failed",
+        ),
+        (ErrorKind::AuthorizationFailed, 422, "authorization_failed"),
+        (
+            ErrorKind::BackendClientCreationFailed,
+            424,
+            "backend_client_creation_failed",
+        ),
+        (ErrorKind::BackendNotFound, 404, "backend_not_found"),
+        (
+            ErrorKind::BackendRecordingMissing,
+            422,
+            "backend_recording_missing",
+        ),
+        (
+            ErrorKind::BackendRequestFailed,
+            424,
+            "backend_request_failed",
+        ),
+        (
+            ErrorKind::_BackendRequestTimedOut,
+            424,
+            "backend_request_timed_out",
+        ),
+        (
+            ErrorKind::BackendRequestsThrottled,
+            429,
+            "backend_requests_throttled",
+        ),
+        (ErrorKind::BanNotFound, 404, "ban_not_found"),
+        (ErrorKind::BrokerRequestFailed, 422, "broker_request_failed"),
+        (ErrorKind::CapacityExceeded, 503, "capacity_exceeded"),
+        (ErrorKind::CodecMismatch, 415, "codec_mismatch"),
+        (ErrorKind::ConfigKeyMissing, 422, "config_key_missing"),
+        (ErrorKind::Conflict, 409, "conflict"),
+        (
+            ErrorKind::ConnectionLimitExceeded,
+            403,
+            "connection_limit_exceeded",
+        ),
+        (
+            ErrorKind::DbConnAcquisitionFailed,
+            422,
+            "database_connection_acquisition_failed",
+        ),
+        (ErrorKind::DbQueryFailed, 422, "database_query_failed"),
+        (ErrorKind::InvalidHandleId, 400, "invalid_handle_id"),
+        (ErrorKind::InvalidJsepFormat, 400, "invalid_jsep_format"),
+        (ErrorKind::InvalidPayload, 400, "invalid_payload"),
+        (ErrorKind::InvalidRoomTime, 400, "invalid_room_time"),
+        (ErrorKind::InvalidSdpType, 400, "invalid_sdp_type"),
+        (
+            ErrorKind::InvalidSubscriptionObject,
+            400,
+            "invalid_subscription_object",
+        ),
+        (
+            ErrorKind::JanusResponseTimeout,
+            424,
+            "janus_response_timeout",
+        ),
+        (ErrorKind::MalformedSdp, 400, "malformed_sdp"),
+        (
+            ErrorKind::MessageBuildingFailed,
+            422,
+            "message_building_failed",
+        ),
+        (
+            ErrorKind::MessageHandlingFailed,
+            422,
+            "message_handling_failed",
+        ),
+        (
+            ErrorKind::MessageParsingFailed,
+            400,
+            "message_parsing_failed",
+        ),
+        (
+            ErrorKind::MessageReceivingFailed,
+            500,
+            "message_receiving_failed",
+        ),
+        (ErrorKind::MessageTooLarge, 413, "message_too_large"),
+        (ErrorKind::MqttPublishFailed, 422, "mqtt_publish_failed"),
+        (ErrorKind::NatsClientNotFound, 424, "nats_client_not_found"),
+        (ErrorKind::NatsPublishFailed, 422, "nats_publish_failed"),
+        (ErrorKind::NoAvailableBackends, 503, "no_available_backends"),
+        (ErrorKind::NotImplemented, 500, "not_implemented"),
+        (ErrorKind::OutboxPipelineError, 424, "outbox_pipeline_error"),
+        (
+            ErrorKind::OutboxStageSerializationFailed,
+            422,
+            "outbox_stage_serialization_failed",
+        ),
+        (ErrorKind::PollClosed, 422, "poll_closed"),
+        (ErrorKind::PollNotFound, 404, "poll_not_found"),
+        (ErrorKind::PublishFailed, 422, "publish_failed"),
+        (ErrorKind::RecordingNotFound, 404, "recording_not_found"),
+        (
+            ErrorKind::ReserveExceedsCapacity,
+            422,
+            "reserve_exceeds_capacity",
+        ),
+        (
+            ErrorKind::ResubscriptionFailed,
+            500,
+            "resubscription_failed",
+        ),
+        (
+            ErrorKind::RoomBulkCloseNotFound,
+            404,
+            "room_bulk_close_not_found",
+        ),
+        (ErrorKind::RoomClosed, 404, "room_closed"),
+        (ErrorKind::RoomFull, 409, "room_full"),
+        (ErrorKind::RoomNotFound, 404, "room_not_found"),
+        (
+            ErrorKind::RoomPolicyCheckFailed,
+            424,
+            "room_policy_check_failed",
+        ),
+        (ErrorKind::RoomPolicyViolation, 422, "room_policy_violation"),
+        (
+            ErrorKind::RoomScheduleNotFound,
+            404,
+            "room_schedule_not_found",
+        ),
+        (
+            ErrorKind::RoomTemplateNotFound,
+            404,
+            "room_template_not_found",
+        ),
+        (
+            ErrorKind::RoomTimeChangingForbidden,
+            422,
+            "room_time_changing_forbidden",
+        ),
+        (ErrorKind::RtcNotFound, 404, "rtc_not_found"),
+        (ErrorKind::SdpTooLarge, 413, "sdp_too_large"),
+        (ErrorKind::ServiceDraining, 503, "service_draining"),
+        (ErrorKind::StageNotFound, 404, "stage_not_found"),
+        (ErrorKind::TooManyPolls, 429, "too_many_polls"),
+        (ErrorKind::UnknownMethod, 405, "unknown_method"),
+    ];
+
+    #[test]
+    fn kinds_and_statuses_are_stable() {
+        assert_eq!(GOLDEN.len(), ErrorKind::into_enum_iter().count());
+
+        for (kind, status, kind_str) in GOLDEN {
+            assert_eq!(kind.status().as_u16(), *status, "{:?}", kind);
+            assert_eq!(kind.kind(), *kind_str, "{:?}", kind);
+            assert_eq!(ErrorKind::from_kind(kind_str), Some(*kind));
+        }
+    }
+
+    #[test]
+    fn kinds_are_unique() {
+        let kinds = ErrorKind::into_enum_iter()
+            .map(|k| k.kind())
+            .collect::<HashSet<_>>();
+
+        assert_eq!(kinds.len(), ErrorKind::into_enum_iter().count());
+    }
+
+    #[test]
+    fn kinds_are_documented() {
+        let docs = include_str!("../../docs/src/api/errors.md");
+
+        for kind in ErrorKind::into_enum_iter() {
+            let entry = format!("`{}`", kind.kind());
+            assert!(docs.contains(&entry), "{} is not documented", kind.kind());
+        }
+    }
+
+    #[test]
+    fn pipeline_stage_error_keeps_kind() {
+        let stage_error = StageError::new(
+            ErrorKind::RoomClosed.kind().to_owned(),
+            Box::new(Error::new(ErrorKind::RoomClosed, anyhow::anyhow!("closed"))),
+        );
+
+        let error = Error::from(PipelineError::from(stage_error));
+        assert_eq!(error.error_kind(), ErrorKind::RoomClosed);
+
+        let error = Error::from(PipelineError::new(
+            PipelineErrorKind::StageError("unheard_of".to_owned()),
+            Box::new(Error::new(ErrorKind::RoomClosed, anyhow::anyhow!("closed"))),
+        ));
+        assert_eq!(error.error_kind(), ErrorKind::OutboxPipelineError);
+    }
+
+    #[test]
+    fn svc_error_carries_extras() {
+        let error = Error::new(ErrorKind::RoomClosed, anyhow::anyhow!("closed"))
+            .with_extra("room_id", "123");
+
+        let svc_error = error.to_svc_error();
+        assert_eq!(svc_error.kind(), "room_closed");
+        assert_eq!(svc_error.status_code(), ResponseStatus::NOT_FOUND);
+        assert_eq!(svc_error.extras()["room_id"], "123");
     }
 }
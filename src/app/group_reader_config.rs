@@ -5,16 +5,39 @@ use tracing::warn;
 use crate::db::group_agent::Groups;
 use crate::db::{self, rtc::Id};
 
-/// Creates/updates `rtc_reader_configs` based on `group_agents`.
+/// Result of [`update`].
+#[derive(Debug)]
+pub struct Update {
+    /// Configs which differ from the stored ones, `(receive_video, receive_audio)`.
+    /// Only they have to be pushed to Janus.
+    pub delta: HashMap<(Id, AgentId), (bool, bool)>,
+    /// Number of configs in the whole matrix for the room's groups.
+    pub full_size: usize,
+}
+
+/// Creates/updates `rtc_reader_configs` based on `group_agents`. Video of the room's hosts is
+/// received by everyone regardless of the groups.
 ///
 /// Note: This function should be run within a database transaction.
 pub async fn update(
     conn: &mut sqlx::PgConnection,
     room_id: db::room::Id,
     groups: Groups,
-) -> sqlx::Result<HashMap<(Id, AgentId), bool>> {
+) -> sqlx::Result<Update> {
     let agent_ids = groups.iter().flat_map(|g| g.agents()).collect::<Vec<_>>();
 
+    let stored = db::rtc_reader_config::ListWithRtcQuery::new(room_id, &agent_ids)
+        .execute(conn)
+        .await?
+        .into_iter()
+        .map(|(config, _rtc)| {
+            (
+                (config.rtc_id(), config.reader_id().to_owned()),
+                (config.receive_video(), config.receive_audio()),
+            )
+        })
+        .collect::<HashMap<_, _>>();
+
     let rtcs = db::rtc::ListQuery::new()
         .room_id(room_id)
         .created_by(&agent_ids)
@@ -26,6 +49,8 @@ pub async fn update(
         .map(|rtc| (rtc.created_by(), rtc.id()))
         .collect::<HashMap<_, _>>();
 
+    let hosts = db::room_host::list(room_id, conn).await?;
+
     // Use HashMap to avoid duplicated configs in cases
     // where a teacher can be in several groups at the same time
     let mut configs = HashMap::new();
@@ -88,26 +113,35 @@ pub async fn update(
                 }
             }
 
+            let same_group = group1 == group2;
+
             configs
                 .entry((*rtc_id, agent1.to_owned()))
-                .or_insert(group1 == group2);
+                .or_insert((same_group || hosts.contains(agent2), same_group));
         }
     }
 
+    let full_size = configs.len();
+
+    configs.retain(|key, value| stored.get(key) != Some(value));
+
     let (mut rtc_ids, mut agent_ids, mut receive_video, mut receive_audio) =
         (vec![], vec![], vec![], vec![]);
 
-    for ((rtc_id, agent_id), value) in configs.iter() {
+    for ((rtc_id, agent_id), (video, audio)) in configs.iter() {
         rtc_ids.push(*rtc_id);
         agent_ids.push(agent_id);
-        receive_video.push(*value);
-        receive_audio.push(*value);
+        receive_video.push(*video);
+        receive_audio.push(*audio);
     }
 
     db::rtc_reader_config::batch_insert(conn, &rtc_ids, &agent_ids, &receive_video, &receive_audio)
         .await?;
 
-    Ok(configs)
+    Ok(Update {
+        delta: configs,
+        full_size,
+    })
 }
 
 #[cfg(test)]
@@ -188,10 +222,21 @@ mod tests {
         }
 
         // First distribution by groups
-        let _ = update(&mut conn, room.id(), groups)
+        let result = update(&mut conn, room.id(), groups.clone())
+            .await
+            .expect("group reader config update failed");
+
+        assert_eq!(result.delta.len(), 20);
+        assert_eq!(result.full_size, 20);
+
+        // Nothing changes on the same distribution
+        let result = update(&mut conn, room.id(), groups)
             .await
             .expect("group reader config update failed");
 
+        assert!(result.delta.is_empty());
+        assert_eq!(result.full_size, 20);
+
         let agents = agents.iter().map(|a| a).collect::<Vec<_>>();
         let reader_configs = db::rtc_reader_config::ListWithRtcQuery::new(room.id(), &agents)
             .execute(&mut conn)
@@ -305,4 +350,59 @@ mod tests {
         let agent4_agent2_cfg = agent4_configs.get(agent2.agent_id()).unwrap();
         assert!(!agent4_agent2_cfg.receive_video());
     }
+
+    #[sqlx::test]
+    async fn hosts_video_received_across_groups(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+
+        let host = TestAgent::new("web", "host", USR_AUDIENCE);
+        let student = TestAgent::new("web", "student", USR_AUDIENCE);
+
+        let mut conn = db.get_conn().await;
+
+        let room = factory::Room::new()
+            .audience(USR_AUDIENCE)
+            .time((Bound::Included(Utc::now()), Bound::Unbounded))
+            .rtc_sharing_policy(RtcSharingPolicy::Owned)
+            .insert(&mut conn)
+            .await;
+
+        db::room_host::insert(room.id(), &[host.agent_id()], &mut conn)
+            .await
+            .expect("Failed to insert room host");
+
+        let groups = Groups::new(vec![
+            GroupItem::new(0, vec![host.agent_id().to_owned()]),
+            GroupItem::new(1, vec![student.agent_id().to_owned()]),
+        ]);
+
+        let host_rtc = factory::Rtc::new(room.id())
+            .created_by(host.agent_id().to_owned())
+            .insert(&mut conn)
+            .await;
+
+        let student_rtc = factory::Rtc::new(room.id())
+            .created_by(student.agent_id().to_owned())
+            .insert(&mut conn)
+            .await;
+
+        let result = update(&mut conn, room.id(), groups)
+            .await
+            .expect("group reader config update failed");
+
+        // The student sees the host but doesn't hear them from another group.
+        assert_eq!(
+            result
+                .delta
+                .get(&(host_rtc.id(), student.agent_id().to_owned())),
+            Some(&(true, false))
+        );
+
+        assert_eq!(
+            result
+                .delta
+                .get(&(student_rtc.id(), host.agent_id().to_owned())),
+            Some(&(false, false))
+        );
+    }
 }
@@ -0,0 +1,360 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value as JsonValue};
+use svc_agent::{AccountId, AgentId};
+use svc_authn::{jose::ConfigMap, token::jws_compact::extract::decode_jws_compact_with_config};
+use tonic::{Request, Response, Status};
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::{
+    app::{
+        context::AppContext,
+        dispatcher::Dispatcher,
+        endpoint::{self, RequestHandler},
+        message_handler::grpc,
+    },
+    db::{
+        self,
+        rtc::{Kind as RtcKind, SharingPolicy as RtcSharingPolicy},
+    },
+};
+
+use proto::internal_server::{Internal, InternalServer};
+
+pub mod proto {
+    tonic::include_proto!("conference.v1");
+}
+
+/// Agent label of the callers not telling it in `x-agent-label`.
+const DEFAULT_AGENT_LABEL: &str = "grpc";
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Internal API served by the request handlers of the MQTT counterparts.
+#[derive(Clone)]
+pub struct Service {
+    context: Arc<AppContext>,
+    dispatcher: Dispatcher,
+    authn: Arc<ConfigMap>,
+}
+
+impl Service {
+    pub fn new(context: Arc<AppContext>, dispatcher: Dispatcher, authn: ConfigMap) -> Self {
+        Self {
+            context,
+            dispatcher,
+            authn: Arc::new(authn),
+        }
+    }
+
+    /// The agent of the service token in the `authorization` metadata.
+    fn authenticate<T>(&self, request: &Request<T>) -> Result<AgentId, Status> {
+        let metadata = request.metadata();
+
+        let token = metadata
+            .get("authorization")
+            .and_then(|x| x.to_str().ok())
+            .and_then(|x| x.strip_prefix("Bearer "))
+            .ok_or_else(|| Status::unauthenticated("Missing bearer token"))?;
+
+        let account_id = decode_jws_compact_with_config::<String>(token, &self.authn)
+            .map(|jws| AccountId::new(jws.claims.subject(), jws.claims.audience()))
+            .map_err(|err| Status::unauthenticated(err.to_string()))?;
+
+        let label = metadata
+            .get("x-agent-label")
+            .and_then(|x| x.to_str().ok())
+            .unwrap_or(DEFAULT_AGENT_LABEL);
+
+        Ok(AgentId::new(label, account_id))
+    }
+
+    /// Handles the request with the payload shaped as in MQTT and dispatches the notifications.
+    async fn call<H: RequestHandler, T>(
+        &self,
+        request: &Request<T>,
+        payload: JsonValue,
+    ) -> Result<JsonValue, Status> {
+        let agent_id = self.authenticate(request)?;
+        let payload = parse::<H::Payload>(payload)?;
+        let (payload, notifications) = grpc::handle::<H>(&self.context, &agent_id, payload).await?;
+
+        for notification in notifications {
+            self.dispatcher.dispatch(None, notification.into_message());
+        }
+
+        Ok(payload)
+    }
+}
+
+#[tonic::async_trait]
+impl Internal for Service {
+    async fn create_room(
+        &self,
+        request: Request<proto::CreateRoomRequest>,
+    ) -> Result<Response<proto::Room>, Status> {
+        let req = request.get_ref();
+        let time = req.time.clone().unwrap_or_default();
+
+        let tags = req
+            .tags
+            .as_deref()
+            .map(serde_json::from_str::<JsonValue>)
+            .transpose()
+            .map_err(|err| Status::invalid_argument(format!("Invalid tags: {err}")))?;
+
+        let metadata = req
+            .metadata
+            .as_deref()
+            .map(serde_json::from_str::<JsonValue>)
+            .transpose()
+            .map_err(|err| Status::invalid_argument(format!("Invalid metadata: {err}")))?;
+
+        let payload = json!({
+            "time": [time.start, time.end],
+            "audience": req.audience,
+            "rtc_sharing_policy": req.rtc_sharing_policy,
+            "reserve": req.reserve,
+            "tags": tags,
+            "classroom_id": req.classroom_id,
+            "archive_messages": req.archive_messages,
+            "composite_recording": req.composite_recording,
+            "record_rtcs": req.record_rtcs,
+            "stream_max_duration": req.stream_max_duration,
+            "metadata": metadata,
+            "lobby": req.lobby,
+            "active_speaker_events": req.active_speaker_events,
+            "max_agents": req.max_agents,
+        });
+
+        let room = self
+            .call::<endpoint::room::CreateHandler, _>(&request, payload)
+            .await?;
+
+        Ok(Response::new(decode::<Room>(room)?.into()))
+    }
+
+    async fn close_room(
+        &self,
+        request: Request<proto::CloseRoomRequest>,
+    ) -> Result<Response<proto::Room>, Status> {
+        let payload = json!({ "id": request.get_ref().id });
+
+        let room = self
+            .call::<endpoint::room::CloseHandler, _>(&request, payload)
+            .await?;
+
+        Ok(Response::new(decode::<Room>(room)?.into()))
+    }
+
+    async fn list_rtcs(
+        &self,
+        request: Request<proto::ListRtcsRequest>,
+    ) -> Result<Response<proto::ListRtcsResponse>, Status> {
+        let req = request.get_ref();
+
+        let payload = json!({
+            "room_id": req.room_id,
+            "offset": req.offset,
+            "limit": req.limit,
+        });
+
+        let rtcs = self
+            .call::<endpoint::rtc::ListHandler, _>(&request, payload)
+            .await?;
+
+        let rtcs = decode::<Vec<Rtc>>(rtcs)?;
+
+        Ok(Response::new(proto::ListRtcsResponse {
+            rtcs: rtcs.into_iter().map(Into::into).collect(),
+        }))
+    }
+
+    async fn vacuum(
+        &self,
+        request: Request<proto::VacuumRequest>,
+    ) -> Result<Response<proto::VacuumResponse>, Status> {
+        let result = self
+            .call::<endpoint::system::VacuumHandler, _>(&request, json!({}))
+            .await?;
+
+        let result = decode::<VacuumResult>(result)?;
+
+        Ok(Response::new(proto::VacuumResponse {
+            skipped_empty_recordings: result.skipped_empty_recordings,
+        }))
+    }
+}
+
+/// Serves the API until the shutdown signal.
+pub async fn serve(
+    bind_address: SocketAddr,
+    service: Service,
+    shutdown: impl std::future::Future<Output = ()>,
+) {
+    info!(%bind_address, "Serving gRPC API");
+
+    let result = tonic::transport::Server::builder()
+        .add_service(InternalServer::new(service))
+        .serve_with_shutdown(bind_address, shutdown)
+        .await;
+
+    if let Err(err) = result {
+        error!(?err, "gRPC server failed");
+    }
+}
+
+fn parse<T: DeserializeOwned>(payload: JsonValue) -> Result<T, Status> {
+    serde_json::from_value(payload).map_err(|err| Status::invalid_argument(err.to_string()))
+}
+
+/// Reads the JSON payload of the response for building the protobuf one.
+fn decode<T: DeserializeOwned>(payload: JsonValue) -> Result<T, Status> {
+    serde_json::from_value(payload).map_err(|err| Status::internal(err.to_string()))
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// A room as it's serialized in responses.
+#[derive(Deserialize)]
+struct Room {
+    id: db::room::Id,
+    time: (Option<i64>, Option<i64>),
+    audience: String,
+    created_at: i64,
+    rtc_sharing_policy: RtcSharingPolicy,
+    reserve: Option<i32>,
+    tags: JsonValue,
+    backend_id: Option<AgentId>,
+    classroom_id: Uuid,
+    host: Option<AgentId>,
+    timed_out: bool,
+    closed_by: Option<AgentId>,
+    #[serde(default)]
+    stream_max_duration: Option<i32>,
+    #[serde(default)]
+    metadata: Option<JsonValue>,
+    #[serde(default)]
+    lobby: bool,
+    #[serde(default)]
+    active_speaker_events: bool,
+    #[serde(default)]
+    max_agents: Option<i32>,
+}
+
+impl From<Room> for proto::Room {
+    fn from(room: Room) -> Self {
+        let (start, end) = room.time;
+
+        Self {
+            id: room.id.to_string(),
+            time: Some(proto::RoomTime { start, end }),
+            audience: room.audience,
+            created_at: room.created_at,
+            rtc_sharing_policy: sharing_policy(room.rtc_sharing_policy),
+            reserve: room.reserve,
+            tags: room.tags.to_string(),
+            backend_id: room.backend_id.map(|id| id.to_string()),
+            classroom_id: room.classroom_id.to_string(),
+            host: room.host.map(|id| id.to_string()),
+            timed_out: room.timed_out,
+            closed_by: room.closed_by.map(|id| id.to_string()),
+            stream_max_duration: room.stream_max_duration,
+            metadata: room.metadata.map(|metadata| metadata.to_string()),
+            lobby: room.lobby,
+            active_speaker_events: room.active_speaker_events,
+            max_agents: room.max_agents,
+        }
+    }
+}
+
+/// An rtc as it's serialized in responses.
+#[derive(Deserialize)]
+struct Rtc {
+    id: db::rtc::Id,
+    room_id: db::room::Id,
+    created_at: i64,
+    created_by: AgentId,
+    record: bool,
+    #[serde(default)]
+    tags: Option<JsonValue>,
+    #[serde(default)]
+    kind: RtcKind,
+}
+
+impl From<Rtc> for proto::Rtc {
+    fn from(rtc: Rtc) -> Self {
+        Self {
+            id: rtc.id.to_string(),
+            room_id: rtc.room_id.to_string(),
+            created_at: rtc.created_at,
+            created_by: rtc.created_by.to_string(),
+            record: rtc.record,
+            tags: rtc.tags.map(|tags| tags.to_string()),
+            kind: rtc_kind(rtc.kind),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct VacuumResult {
+    skipped_empty_recordings: u64,
+}
+
+// `Display` of the policy is its JSON with the quotes.
+fn sharing_policy(policy: RtcSharingPolicy) -> String {
+    match policy {
+        RtcSharingPolicy::None => "none",
+        RtcSharingPolicy::Shared => "shared",
+        RtcSharingPolicy::Owned => "owned",
+    }
+    .to_owned()
+}
+
+fn rtc_kind(kind: RtcKind) -> String {
+    match kind {
+        RtcKind::Camera => "camera",
+        RtcKind::Screen => "screen",
+    }
+    .to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn convert_room() {
+        let room = serde_json::from_value::<Room>(json!({
+            "id": "5a1eb08e-7b0e-4b2d-a2d0-8ef1a4a6ce1c",
+            "time": [1690000000, null],
+            "audience": "example.org",
+            "created_at": 1689999000,
+            "backend": "janus",
+            "tags": { "webinar": true },
+            "rtc_sharing_policy": "shared",
+            "classroom_id": "7d5bd5b0-38f3-4e7c-a6f6-6c7c2b44c7b8",
+            "host": null,
+            "timed_out": false,
+            "closed_by": null,
+            "version": 1,
+        }))
+        .expect("Failed to parse room");
+
+        let room = proto::Room::from(room);
+
+        assert_eq!(
+            room.time,
+            Some(proto::RoomTime {
+                start: Some(1690000000),
+                end: None,
+            })
+        );
+
+        assert_eq!(room.rtc_sharing_policy, "shared");
+        assert_eq!(room.tags, r#"{"webinar":true}"#);
+        assert_eq!(room.backend_id, None);
+    }
+}
@@ -132,3 +132,124 @@ mod serde {
         }
     }
 }
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use rand::{distributions::Alphanumeric, seq::SliceRandom, Rng};
+
+    use super::*;
+    use crate::{
+        backend::janus::client::{HandleId as JanusHandleId, SessionId},
+        test_helpers::prelude::*,
+    };
+
+    const FUZZ_ROUNDS: usize = 10_000;
+
+    fn valid_handle_id() -> HandleId {
+        HandleId::new(
+            db::janus_rtc_stream::Id::random(),
+            db::rtc::Id::random(),
+            JanusHandleId::random(),
+            SessionId::random(),
+            TestAgent::new("alpha", "janus", SVC_AUDIENCE)
+                .agent_id()
+                .to_owned(),
+        )
+    }
+
+    #[test]
+    fn round_trip() {
+        let handle_id = valid_handle_id();
+        let parsed: HandleId = handle_id.to_string().parse().expect("Failed to parse");
+        assert_eq!(parsed.to_string(), handle_id.to_string());
+
+        let json = serde_json::to_value(&handle_id).expect("Failed to serialize");
+        let parsed: HandleId = serde_json::from_value(json).expect("Failed to deserialize");
+        assert_eq!(parsed.to_string(), handle_id.to_string());
+    }
+
+    #[test]
+    fn reject_non_positive_janus_ids() {
+        let handle_id = valid_handle_id();
+
+        for (handle, session) in [("0", "1"), ("1", "0"), ("-1", "1"), ("1", "-42")] {
+            let val = format!(
+                "{}.{}.{}.{}.{}",
+                handle_id.rtc_stream_id(),
+                handle_id.rtc_id(),
+                handle,
+                session,
+                handle_id.backend_id()
+            );
+
+            assert!(val.parse::<HandleId>().is_err(), "accepted {}", val);
+        }
+
+        assert!(serde_json::from_str::<JanusHandleId>("0").is_err());
+        assert!(serde_json::from_str::<SessionId>("-1").is_err());
+        assert!(serde_json::from_str::<SessionId>("1").is_ok());
+    }
+
+    #[test]
+    fn fuzz_mutated_handle_ids() {
+        let mut rng = rand::thread_rng();
+        let alphabet: Vec<char> = "0123456789abcdef.-+ ".chars().collect();
+
+        for _ in 0..FUZZ_ROUNDS {
+            let original = valid_handle_id().to_string();
+            let mut chars: Vec<char> = original.chars().collect();
+
+            for _ in 0..rng.gen_range(1..4) {
+                let idx = rng.gen_range(0..chars.len());
+
+                match rng.gen_range(0..3) {
+                    0 => chars[idx] = *alphabet.choose(&mut rng).unwrap(),
+                    1 => {
+                        chars.remove(idx);
+                    }
+                    _ => chars.insert(idx, *alphabet.choose(&mut rng).unwrap()),
+                }
+
+                if chars.is_empty() {
+                    break;
+                }
+            }
+
+            let mutated: String = chars.into_iter().collect();
+
+            // Whatever gets through has to be a well-formed id with valid Janus ids.
+            if let Ok(parsed) = mutated.parse::<HandleId>() {
+                let reparsed: HandleId = parsed.to_string().parse().expect("Failed to reparse");
+                assert_eq!(reparsed.to_string(), parsed.to_string());
+                assert!(parsed.janus_handle_id().to_string().parse::<i64>().unwrap() > 0);
+                assert!(
+                    parsed
+                        .janus_session_id()
+                        .to_string()
+                        .parse::<i64>()
+                        .unwrap()
+                        > 0
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn fuzz_random_strings() {
+        let mut rng = rand::thread_rng();
+
+        for _ in 0..FUZZ_ROUNDS {
+            let len = rng.gen_range(0..128);
+            let val: String = (&mut rng)
+                .sample_iter(&Alphanumeric)
+                .take(len)
+                .map(char::from)
+                .collect();
+
+            assert!(val.parse::<HandleId>().is_err(), "accepted {}", val);
+            assert!(serde_json::from_value::<HandleId>(serde_json::json!(val)).is_err());
+        }
+    }
+}
@@ -5,15 +5,16 @@ use std::{
 };
 
 use axum::{
+    extract::FromRequestParts,
     response::{IntoResponse, Response},
-    routing::{get, post},
+    routing::{delete, get, patch, post},
     Extension, Router,
 };
 use futures::future::BoxFuture;
-use http::{Method, Request};
+use http::{header, HeaderValue, Method, Request};
 use hyper::{body::HttpBody, Body};
-use svc_agent::mqtt::{Agent, IntoPublishableMessage};
-use svc_utils::middleware::MeteredRoute;
+use svc_agent::Authenticable;
+use svc_utils::{extractors::AccountIdExtractor, middleware::MeteredRoute};
 use tower::{layer::layer_fn, Service};
 use tower_http::trace::TraceLayer;
 use tracing::{
@@ -22,16 +23,41 @@ use tracing::{
     info, Span,
 };
 
-use super::{context::AppContext, endpoint};
-use crate::app::message_handler::publish_message;
+use super::{
+    context::{AppContext, GlobalContext},
+    cors::{self, Verdict},
+    dispatcher::Dispatcher,
+    drain, endpoint,
+    error::{Error as AppError, ErrorKind as AppErrorKind},
+    long_poll::{self, EventJournal},
+    service_utils::Notification,
+    tenant_usage::{self, Counters},
+    ws::{self, Sockets},
+};
 
 pub fn build_router(
     context: Arc<AppContext>,
-    agent: Agent,
+    dispatcher: Dispatcher,
+    sockets: Sockets,
+    journal: EventJournal,
     authn: svc_authn::jose::ConfigMap,
 ) -> Router {
+    let authn = Arc::new(authn);
+    let drain_context = context.clone();
+    // The socket lives past the response so it's kept out of the per-request middlewares.
+    let ws_router = Router::new()
+        .metered_route("/api/v1/ws", get(ws::upgrade))
+        .layer(Extension(context.clone()))
+        .layer(Extension(dispatcher.clone()))
+        .layer(Extension(sockets))
+        .layer(Extension(authn.clone()))
+        .layer(axum::middleware::from_fn(ws::token_from_query));
     let router = Router::new()
         .metered_route("/rooms/:id/agents", get(endpoint::agent::list))
+        .metered_route(
+            "/rooms/:id/agents/preferences",
+            patch(endpoint::agent::update_preferences),
+        )
         .metered_route(
             "/rooms/:id/configs/reader",
             get(endpoint::agent_reader_config::read).post(endpoint::agent_reader_config::update),
@@ -41,22 +67,62 @@ pub fn build_router(
             get(endpoint::agent_writer_config::read).post(endpoint::agent_writer_config::update),
         )
         .metered_route("/rooms/:id/enter", post(endpoint::room::enter))
+        .metered_route(
+            "/classrooms/:classroom_id/enter",
+            post(endpoint::room::enter_classroom),
+        )
+        .metered_route("/rooms/:id/events", get(long_poll::poll))
+        .metered_route("/rooms/:id/limits", get(endpoint::room::limits_read))
         .metered_route("/rooms/:id/close", post(endpoint::room::close))
-        .metered_route("/rooms", post(endpoint::room::create))
+        .metered_route("/rooms/:id/admit", post(endpoint::room::admit))
+        .metered_route("/rooms/:id/reject", post(endpoint::room::reject))
+        .metered_route(
+            "/rooms",
+            get(endpoint::room::list).post(endpoint::room::create),
+        )
+        .metered_route(
+            "/rooms/from_template",
+            post(endpoint::room::create_from_template),
+        )
+        .metered_route("/room_templates", post(endpoint::room_template::update))
+        .metered_route("/room_schedules", post(endpoint::room_schedule::create))
+        .metered_route(
+            "/room_schedules/:id",
+            delete(endpoint::room_schedule::delete),
+        )
         .metered_route(
             "/rooms/:id",
-            get(endpoint::room::read).patch(endpoint::room::update),
+            get(endpoint::room::read)
+                .patch(endpoint::room::update)
+                .delete(endpoint::room::delete),
         )
         .metered_route(
             "/rooms/:id/rtcs",
             get(endpoint::rtc::list).post(endpoint::rtc::create),
         )
+        .metered_route(
+            "/rooms/:id/rtcs/subscribe_set",
+            post(endpoint::rtc::subscribe_set),
+        )
         .metered_route(
             "/rooms/:id/groups",
             get(endpoint::group::list).post(endpoint::group::update),
         )
-        .metered_route("/rtcs/:id", get(endpoint::rtc::read))
+        .metered_route(
+            "/rooms/:id/bans",
+            get(endpoint::ban::list).post(endpoint::ban::create),
+        )
+        .metered_route("/rooms/:id/bans/:account_id", delete(endpoint::ban::delete))
+        .metered_route("/rooms/:id/polls", post(endpoint::poll::create))
+        .metered_route("/polls/:id", get(endpoint::poll::results))
+        .metered_route("/polls/:id/votes", post(endpoint::poll::vote))
+        .metered_route("/polls/:id/close", post(endpoint::poll::close))
+        .metered_route(
+            "/rtcs/:id",
+            get(endpoint::rtc::read).patch(endpoint::rtc::update),
+        )
         .metered_route("/rtcs/:id/streams", post(endpoint::rtc::connect))
+        .metered_route("/rtcs/:id/preflight", get(endpoint::rtc::preflight))
         .metered_route("/rooms/:id/streams", get(endpoint::rtc_stream::list))
         .metered_route("/streams/signal", post(endpoint::rtc_signal::create))
         .metered_route("/rtcs/:id/signal", post(endpoint::rtc::connect_and_signal))
@@ -65,11 +131,30 @@ pub fn build_router(
             "/rooms/:id/configs/writer/snapshot",
             get(endpoint::writer_config_snapshot::read),
         )
+        .metered_route("/rooms/:id/whip", post(endpoint::whip::publish))
+        .metered_route(
+            "/whip/:handle_id",
+            patch(endpoint::whip::trickle).delete(endpoint::whip::stop),
+        )
+        .metered_route("/rooms/:id/rtcs/:rtc_id/whep", post(endpoint::whep::play))
+        .metered_route(
+            "/whep/:label/:handle_id",
+            patch(endpoint::whep::trickle).delete(endpoint::whep::stop),
+        )
+        .metered_route("/system/drain_start", post(endpoint::system::drain_start))
+        .layer(layer_fn(|inner| PseudonymsMiddleware { inner }))
         .layer(layer_fn(|inner| NotificationsMiddleware { inner }))
-        .layer(Extension(context))
-        .layer(Extension(agent))
-        .layer(Extension(Arc::new(authn)))
-        .layer(svc_utils::middleware::CorsLayer);
+        .layer(layer_fn(|inner| TenantUsageMiddleware { inner }))
+        .layer(Extension(context.clone()))
+        .layer(Extension(dispatcher))
+        .layer(Extension(journal))
+        .layer(Extension(authn.clone()))
+        .layer(svc_utils::middleware::CorsLayer)
+        .layer(layer_fn(move |inner| CorsMiddleware {
+            inner,
+            context: context.clone(),
+            authn: authn.clone(),
+        }));
     let router = Router::new().nest("/api/v1", router);
 
     let pingz_router = Router::new().metered_route(
@@ -77,7 +162,14 @@ pub fn build_router(
         get(|| async { Response::builder().body(Body::from("pong")).unwrap() }),
     );
 
-    let router = router.merge(pingz_router);
+    let drain_router = Router::new()
+        .metered_route("/drain_status", get(drain_status))
+        .layer(Extension(drain_context));
+
+    let router = router
+        .merge(pingz_router)
+        .merge(drain_router)
+        .merge(ws_router);
 
     router.layer(
         TraceLayer::new_for_http()
@@ -116,14 +208,20 @@ pub fn build_router(
     )
 }
 
+/// Polled by the deploy tooling until the instance is idle. Responds with 503 till then so
+/// a plain `curl --fail` loop does.
+async fn drain_status(Extension(ctx): Extension<Arc<AppContext>>) -> Response {
+    match drain::status(ctx.as_ref()).await {
+        Ok(status) if status.is_idle() => axum::Json(status).into_response(),
+        Ok(status) => (http::StatusCode::SERVICE_UNAVAILABLE, axum::Json(status)).into_response(),
+        Err(err) => err.into_response(),
+    }
+}
+
 impl IntoResponse for super::error::Error {
     fn into_response(self) -> Response {
         let detail = self.detail();
-        let err = svc_error::Error::builder()
-            .status(self.status())
-            .kind(self.kind(), self.title())
-            .detail(&detail)
-            .build();
+        let err = self.to_svc_error();
 
         let span = Span::current();
         span.record("kind", self.kind());
@@ -166,14 +264,11 @@ where
         let mut inner = std::mem::replace(&mut self.inner, clone);
 
         Box::pin(async move {
-            let mut agent = req.extensions().get::<Agent>().cloned().unwrap();
+            let dispatcher = req.extensions().get::<Dispatcher>().cloned().unwrap();
             let mut res: Response<ResBody> = inner.call(req).await?;
-            if let Some(notifications) = res
-                .extensions_mut()
-                .remove::<Vec<Box<dyn IntoPublishableMessage + Send + Sync + 'static>>>()
-            {
+            if let Some(notifications) = res.extensions_mut().remove::<Vec<Notification>>() {
                 for notification in notifications {
-                    publish_message(&mut agent, notification)
+                    dispatcher.dispatch(None, notification.into_message())
                 }
             }
 
@@ -181,3 +276,229 @@ where
         })
     }
 }
+
+/// Replaces agent ids of the opted-in audiences in room events and in the response body unless
+/// the caller is a service account.
+#[derive(Clone)]
+struct PseudonymsMiddleware<S> {
+    inner: S,
+}
+
+impl<S, ReqBody> Service<Request<ReqBody>> for PseudonymsMiddleware<S>
+where
+    S: Service<Request<ReqBody>, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    ReqBody: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        Box::pin(async move {
+            let (mut parts, body) = req.into_parts();
+
+            let pseudonyms = match parts.extensions.get::<Arc<AppContext>>() {
+                Some(context) if context.pseudonyms().is_enabled() => context.pseudonyms(),
+                _ => return inner.call(Request::from_parts(parts, body)).await,
+            };
+
+            let service_audience = parts
+                .extensions
+                .get::<Arc<AppContext>>()
+                .map(|context| context.agent_id().as_account_id().audience().to_owned())
+                .unwrap_or_default();
+
+            // Anonymous callers are not privileged either.
+            let hide_from_caller = AccountIdExtractor::from_request_parts(&mut parts, &())
+                .await
+                .map_or(true, |AccountIdExtractor(account_id)| {
+                    pseudonyms.hides_from(&account_id, &service_audience)
+                });
+
+            let mut res = inner.call(Request::from_parts(parts, body)).await?;
+
+            if let Some(notifications) = res.extensions_mut().get_mut::<Vec<Notification>>() {
+                for notification in notifications {
+                    notification.pseudonymize(&pseudonyms);
+                }
+            }
+
+            if !hide_from_caller {
+                return Ok(res);
+            }
+
+            let (mut parts, body) = res.into_parts();
+            parts.headers.remove(http::header::CONTENT_LENGTH);
+
+            let bytes = match hyper::body::to_bytes(body).await {
+                Ok(bytes) => bytes,
+                Err(err) => {
+                    error!(?err, "Failed to read response body");
+                    return Ok(Response::from_parts(
+                        parts,
+                        axum::body::boxed(Body::empty()),
+                    ));
+                }
+            };
+
+            let bytes = match serde_json::from_slice::<serde_json::Value>(&bytes) {
+                Ok(mut payload) => {
+                    pseudonyms.apply(&mut payload);
+                    serde_json::to_vec(&payload).map_or(bytes, Into::into)
+                }
+                Err(_) => bytes,
+            };
+
+            Ok(Response::from_parts(
+                parts,
+                axum::body::boxed(Body::from(bytes)),
+            ))
+        })
+    }
+}
+
+/// Accounts HTTP requests to the audience of the caller. Anonymous requests are not accounted.
+#[derive(Clone)]
+struct TenantUsageMiddleware<S> {
+    inner: S,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for TenantUsageMiddleware<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    ReqBody: Send + 'static,
+    ResBody: HttpBody + Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        Box::pin(async move {
+            let (mut parts, body) = req.into_parts();
+            let context = parts.extensions.get::<Arc<AppContext>>().cloned();
+            let account_id = AccountIdExtractor::from_request_parts(&mut parts, &())
+                .await
+                .ok();
+            let req = Request::from_parts(parts, body);
+
+            let (context, AccountIdExtractor(account_id)) = match (context, account_id) {
+                (Some(context), Some(account_id)) => (context, account_id),
+                _ => return inner.call(req).await,
+            };
+
+            let usage = context.metrics().tenant_usage.clone();
+
+            usage
+                .scope(account_id.audience(), async move {
+                    let res = inner.call(req).await?;
+
+                    tenant_usage::record(Counters {
+                        bytes_published: res.body().size_hint().exact().unwrap_or(0) as i64,
+                        ..Default::default()
+                    });
+
+                    Ok(res)
+                })
+                .await
+        })
+    }
+}
+
+/// Rejects browser requests from origins not allowed for the audience and echoes the allowed ones
+/// instead of `*`. Sits outside of `CorsLayer` so preflights get checked as well.
+#[derive(Clone)]
+struct CorsMiddleware<S> {
+    inner: S,
+    context: Arc<AppContext>,
+    authn: Arc<svc_authn::jose::ConfigMap>,
+}
+
+impl<S> Service<Request<Body>> for CorsMiddleware<S>
+where
+    S: Service<Request<Body>, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let context = self.context.clone();
+        let authn = self.authn.clone();
+
+        Box::pin(async move {
+            let config = &context.config().cors;
+
+            let origin = match req.headers().get(header::ORIGIN) {
+                Some(origin) if !config.is_empty() => origin.clone(),
+                _ => return inner.call(req).await,
+            };
+
+            let (mut parts, body) = req.into_parts();
+
+            // `AccountIdExtractor` looks for the authn config among the extensions which are
+            // only added further down the stack.
+            parts.extensions.insert(authn);
+
+            let caller = AccountIdExtractor::from_request_parts(&mut parts, &())
+                .await
+                .ok()
+                .map(|AccountIdExtractor(account_id)| account_id);
+
+            let req = Request::from_parts(parts, body);
+
+            let audience =
+                match cors::resolve_audience(context.as_ref(), caller.as_ref(), req.uri().path())
+                    .await
+                {
+                    Ok(Some(audience)) => audience,
+                    Ok(None) => return inner.call(req).await,
+                    Err(err) => return Ok(err.into_response()),
+                };
+
+            match cors::check(config, &audience, origin.to_str().unwrap_or_default()) {
+                Verdict::Unrestricted => inner.call(req).await,
+                Verdict::Allowed => {
+                    let mut res = inner.call(req).await?;
+                    let headers = res.headers_mut();
+                    headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+                    headers.append(header::VARY, HeaderValue::from_static("origin"));
+                    Ok(res)
+                }
+                Verdict::Rejected { audience } => {
+                    context.metrics().observe_cors_rejection(&audience);
+
+                    let err = AppError::new(
+                        AppErrorKind::AccessDenied,
+                        anyhow::anyhow!("Origin is not allowed for {}", audience),
+                    );
+
+                    Ok(err.into_response())
+                }
+            }
+        })
+    }
+}
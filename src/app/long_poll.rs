@@ -0,0 +1,475 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use anyhow::anyhow;
+use axum::extract::{Extension, Path, Query};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use svc_agent::{
+    mqtt::{PublishableMessage, ResponseStatus},
+    AgentId,
+};
+use svc_utils::extractors::AgentIdExtractor;
+use tokio::sync::Notify;
+use tracing::warn;
+
+use crate::{
+    app::{
+        context::{AppContext, GlobalContext},
+        endpoint::{helpers, RequestResult},
+        error::{Error as AppError, ErrorKind as AppErrorKind},
+        message_handler::ws::OutgoingFrame,
+        service_utils::{RequestParams, Response},
+        ws::{event_frame, Target},
+    },
+    config::LongPollConfig,
+    db,
+};
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Event {
+    seq: u64,
+    label: Option<String>,
+    payload: JsonValue,
+    #[serde(skip)]
+    recorded_at: Instant,
+}
+
+/// Events following the client's `since_seq`.
+#[derive(Debug, Serialize)]
+pub struct Batch {
+    events: Vec<Event>,
+    /// The cursor to pass as `since_seq` to the next poll.
+    last_seq: u64,
+    /// Some events after `since_seq` are not kept anymore, so the client should re-read
+    /// the room's state.
+    missed: bool,
+}
+
+struct Journal {
+    events: VecDeque<Event>,
+    last_seq: u64,
+    notify: Arc<Notify>,
+}
+
+impl Journal {
+    fn new() -> Self {
+        Self {
+            events: VecDeque::new(),
+            last_seq: 0,
+            notify: Arc::new(Notify::new()),
+        }
+    }
+
+    fn prune(&mut self, config: &LongPollConfig, now: Instant) {
+        while self.events.len() > config.capacity {
+            self.events.pop_front();
+        }
+
+        while self
+            .events
+            .front()
+            .is_some_and(|e| now.duration_since(e.recorded_at) > config.retention)
+        {
+            self.events.pop_front();
+        }
+    }
+
+    fn batch(&self, since_seq: u64) -> Batch {
+        // The seq is unknown to this instance, e.g. the client has come from another one.
+        if since_seq > self.last_seq {
+            return Batch {
+                events: vec![],
+                last_seq: self.last_seq,
+                missed: true,
+            };
+        }
+
+        let first_seq = self
+            .events
+            .front()
+            .map(|e| e.seq)
+            .unwrap_or(self.last_seq + 1);
+
+        Batch {
+            events: self
+                .events
+                .iter()
+                .filter(|e| e.seq > since_seq)
+                .cloned()
+                .collect(),
+            last_seq: self.last_seq,
+            missed: since_seq + 1 < first_seq,
+        }
+    }
+}
+
+struct Inner {
+    journals: HashMap<db::room::Id, Journal>,
+    polls: HashMap<AgentId, usize>,
+    total_polls: usize,
+    swept_at: Instant,
+}
+
+/// Journals of the rooms having events on this instance along with the polls waiting for them.
+#[derive(Clone)]
+pub struct EventJournal {
+    inner: Arc<Mutex<Inner>>,
+    config: LongPollConfig,
+}
+
+impl EventJournal {
+    pub fn new(config: LongPollConfig) -> Self {
+        let inner = Inner {
+            journals: HashMap::new(),
+            polls: HashMap::new(),
+            total_polls: 0,
+            swept_at: Instant::now(),
+        };
+
+        Self {
+            inner: Arc::new(Mutex::new(inner)),
+            config,
+        }
+    }
+
+    /// Appends the event published by the dispatcher to the journal of its room and wakes
+    /// the polls waiting for it. Events addressed to agents are not kept.
+    pub fn record(&self, message: &PublishableMessage) {
+        if !matches!(message, PublishableMessage::Event(_)) {
+            return;
+        }
+
+        let room_id = match Target::parse(message.topic()) {
+            Some(Target::Room(room_id)) => room_id,
+            _ => return,
+        };
+
+        let (label, payload) = match event_frame(message.payload()) {
+            Ok(OutgoingFrame::Event { label, payload }) => (label, payload),
+            Ok(OutgoingFrame::Response { .. }) => return,
+            Err(err) => {
+                warn!(
+                    ?err,
+                    topic = message.topic(),
+                    "Failed to parse event for journal"
+                );
+                return;
+            }
+        };
+
+        let now = Instant::now();
+        let mut inner = self.lock();
+        self.sweep(&mut inner, now);
+
+        let journal = inner.journals.entry(room_id).or_insert_with(Journal::new);
+        journal.last_seq += 1;
+
+        journal.events.push_back(Event {
+            seq: journal.last_seq,
+            label,
+            payload,
+            recorded_at: now,
+        });
+
+        journal.prune(&self.config, now);
+        journal.notify.notify_waiters();
+    }
+
+    /// Takes a slot for a poll of the agent. The slot is freed when the permit is dropped.
+    fn admit(&self, agent_id: &AgentId) -> Result<PollPermit, AppError> {
+        let mut inner = self.lock();
+        let agent_polls = inner.polls.get(agent_id).copied().unwrap_or(0);
+
+        if inner.total_polls >= self.config.max_polls
+            || agent_polls >= self.config.max_polls_per_agent
+        {
+            return Err(AppError::new(
+                AppErrorKind::TooManyPolls,
+                anyhow!("Agent {} has {} polls waiting", agent_id, agent_polls),
+            ));
+        }
+
+        inner.total_polls += 1;
+        *inner.polls.entry(agent_id.to_owned()).or_insert(0) += 1;
+
+        Ok(PollPermit {
+            journal: self.clone(),
+            agent_id: agent_id.to_owned(),
+        })
+    }
+
+    /// Responds with the room's events after `since_seq` right away if there are any,
+    /// otherwise waits for them until the timeout. Without `since_seq` only the events recorded
+    /// after the poll has started are returned.
+    async fn poll(&self, room_id: db::room::Id, since_seq: Option<u64>) -> Batch {
+        let deadline = tokio::time::Instant::now() + self.config.timeout;
+
+        // Holding the notify keeps the journal from being swept while the poll waits.
+        let (notify, since_seq) = {
+            let mut inner = self.lock();
+            let journal = inner.journals.entry(room_id).or_insert_with(Journal::new);
+            (
+                journal.notify.clone(),
+                since_seq.unwrap_or(journal.last_seq),
+            )
+        };
+
+        loop {
+            // Registered before looking into the journal so that an event recorded
+            // in between wakes the poll.
+            let notified = notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            let batch = {
+                let mut inner = self.lock();
+                let journal = inner.journals.entry(room_id).or_insert_with(Journal::new);
+                journal.prune(&self.config, Instant::now());
+                journal.batch(since_seq)
+            };
+
+            if !batch.events.is_empty() || batch.missed {
+                return batch;
+            }
+
+            if tokio::time::timeout_at(deadline, notified).await.is_err() {
+                return batch;
+            }
+        }
+    }
+
+    /// Drops the journals of the rooms that had no events for the retention period.
+    fn sweep(&self, inner: &mut Inner, now: Instant) {
+        if now.duration_since(inner.swept_at) < self.config.retention {
+            return;
+        }
+
+        for journal in inner.journals.values_mut() {
+            journal.prune(&self.config, now);
+        }
+
+        inner.journals.retain(|_, journal| {
+            !journal.events.is_empty() || Arc::strong_count(&journal.notify) > 1
+        });
+
+        inner.swept_at = now;
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        self.inner.lock().expect("Event journal mutex poisoned")
+    }
+}
+
+struct PollPermit {
+    journal: EventJournal,
+    agent_id: AgentId,
+}
+
+impl Drop for PollPermit {
+    fn drop(&mut self) {
+        let mut inner = self.journal.lock();
+        inner.total_polls = inner.total_polls.saturating_sub(1);
+
+        if let Some(polls) = inner.polls.get_mut(&self.agent_id) {
+            *polls = polls.saturating_sub(1);
+
+            if *polls == 0 {
+                inner.polls.remove(&self.agent_id);
+            }
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize)]
+pub struct PollParams {
+    since_seq: Option<u64>,
+}
+
+pub async fn poll(
+    Extension(ctx): Extension<Arc<AppContext>>,
+    Extension(journal): Extension<EventJournal>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    Path(room_id): Path<db::room::Id>,
+    Query(params): Query<PollParams>,
+) -> RequestResult {
+    tracing::Span::current().record("room_id", &tracing::field::display(room_id));
+
+    let reqp = RequestParams::Http {
+        agent_id: &agent_id,
+    };
+
+    // The same as subscribing a socket to the room: only agents in the room get its events.
+    {
+        let mut conn = ctx.get_conn().await?;
+
+        let room = helpers::find_room_by_id(
+            room_id,
+            helpers::RoomTimeRequirement::Open,
+            helpers::AudienceScope::caller(ctx.as_ref(), &reqp),
+            &mut conn,
+        )
+        .await?;
+
+        helpers::check_room_presence(&room, &agent_id, &mut conn).await?;
+    }
+
+    let _permit = journal.admit(&agent_id)?;
+    let batch = journal.poll(room_id, params.since_seq).await;
+
+    Ok(Response::new(ResponseStatus::OK, batch, Utc::now(), None))
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use svc_agent::{
+        mqtt::{
+            Address, IntoPublishableMessage, OutgoingEvent, OutgoingEventProperties,
+            ShortTermTimingProperties,
+        },
+        AccountId,
+    };
+
+    use super::*;
+
+    fn config() -> LongPollConfig {
+        LongPollConfig {
+            timeout: Duration::from_millis(100),
+            capacity: 3,
+            max_polls: 2,
+            max_polls_per_agent: 1,
+            ..LongPollConfig::default()
+        }
+    }
+
+    fn event(room_id: db::room::Id, n: i32) -> PublishableMessage {
+        let address = Address::new(
+            AgentId::new(
+                "instance01",
+                AccountId::new("conference", "svc.example.org"),
+            ),
+            "v1",
+        );
+
+        let timing = ShortTermTimingProperties::new(Utc::now());
+        let props = OutgoingEventProperties::new("room.update", timing);
+        let path = format!("rooms/{room_id}/events");
+        let event = OutgoingEvent::broadcast(serde_json::json!({ "n": n }), props, &path);
+
+        Box::new(event)
+            .into_dump(&address)
+            .expect("Failed to dump event")
+    }
+
+    fn numbers(batch: &Batch) -> Vec<JsonValue> {
+        batch
+            .events
+            .iter()
+            .map(|e| e.payload["n"].clone())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn poll_recorded_events() {
+        let journal = EventJournal::new(config());
+        let room_id = db::room::Id::random();
+
+        for n in 1..=2 {
+            journal.record(&event(room_id, n));
+        }
+
+        let batch = journal.poll(room_id, Some(0)).await;
+        assert_eq!(numbers(&batch), [1, 2]);
+        assert_eq!(batch.last_seq, 2);
+        assert!(!batch.missed);
+
+        let batch = journal.poll(room_id, Some(1)).await;
+        assert_eq!(numbers(&batch), [2]);
+
+        // Nothing new comes until the timeout.
+        let batch = journal.poll(room_id, Some(2)).await;
+        assert!(batch.events.is_empty());
+        assert_eq!(batch.last_seq, 2);
+
+        // Events of other rooms are not there.
+        let batch = journal.poll(db::room::Id::random(), Some(0)).await;
+        assert!(batch.events.is_empty());
+    }
+
+    #[tokio::test]
+    async fn poll_missed_events() {
+        let journal = EventJournal::new(config());
+        let room_id = db::room::Id::random();
+
+        for n in 1..=5 {
+            journal.record(&event(room_id, n));
+        }
+
+        let batch = journal.poll(room_id, Some(1)).await;
+        assert_eq!(numbers(&batch), [3, 4, 5]);
+        assert!(batch.missed);
+
+        // The seq of another instance.
+        let batch = journal.poll(room_id, Some(10)).await;
+        assert!(batch.events.is_empty());
+        assert_eq!(batch.last_seq, 5);
+        assert!(batch.missed);
+    }
+
+    #[tokio::test]
+    async fn wake_waiting_poll() {
+        let journal = EventJournal::new(LongPollConfig {
+            timeout: Duration::from_secs(10),
+            ..config()
+        });
+
+        let room_id = db::room::Id::random();
+        journal.record(&event(room_id, 1));
+
+        let poll = tokio::spawn({
+            let journal = journal.clone();
+            async move { journal.poll(room_id, None).await }
+        });
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        journal.record(&event(room_id, 2));
+
+        let batch = tokio::time::timeout(Duration::from_secs(1), poll)
+            .await
+            .expect("Poll not woken")
+            .expect("Poll failed");
+
+        assert_eq!(numbers(&batch), [2]);
+    }
+
+    #[test]
+    fn limit_polls() {
+        let journal = EventJournal::new(config());
+        let agent = AgentId::new("web", AccountId::new("user1", "usr.example.org"));
+        let another = AgentId::new("web", AccountId::new("user2", "usr.example.org"));
+        let third = AgentId::new("web", AccountId::new("user3", "usr.example.org"));
+
+        let permit = journal.admit(&agent).expect("Poll not admitted");
+
+        let err = journal.admit(&agent).err().expect("Agent limit ignored");
+        assert_eq!(err.kind(), "too_many_polls");
+
+        let _another = journal.admit(&another).expect("Poll not admitted");
+        assert!(journal.admit(&third).is_err());
+
+        drop(permit);
+        journal.admit(&third).expect("Poll not admitted");
+    }
+}
@@ -0,0 +1,73 @@
+use std::collections::HashSet;
+
+use serde_json::Value as JsonValue;
+
+use crate::config::MessageArchiveConfig;
+
+/// Hook applied to `message.broadcast` payloads before they get archived, e.g. to strip PII.
+/// The payload delivered to the room is not affected.
+pub trait Scrubber {
+    fn scrub(&self, data: &mut JsonValue);
+}
+
+/// Drops object fields with the given names at any depth.
+pub struct KeyScrubber<'a> {
+    keys: &'a HashSet<String>,
+}
+
+impl<'a> KeyScrubber<'a> {
+    pub fn new(keys: &'a HashSet<String>) -> Self {
+        Self { keys }
+    }
+}
+
+impl Scrubber for KeyScrubber<'_> {
+    fn scrub(&self, data: &mut JsonValue) {
+        match data {
+            JsonValue::Object(map) => {
+                map.retain(|key, _| !self.keys.contains(key));
+                map.values_mut().for_each(|value| self.scrub(value));
+            }
+            JsonValue::Array(items) => items.iter_mut().for_each(|item| self.scrub(item)),
+            _ => (),
+        }
+    }
+}
+
+/// Returns the payload as it should be stored in the archive.
+pub fn scrub(config: &MessageArchiveConfig, data: &JsonValue) -> JsonValue {
+    let scrubbers: [&dyn Scrubber; 1] = [&KeyScrubber::new(&config.scrub_keys)];
+    let mut data = data.clone();
+
+    for scrubber in scrubbers {
+        scrubber.scrub(&mut data);
+    }
+
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn scrub_keys() {
+        let config = MessageArchiveConfig {
+            scrub_keys: vec!["email".to_owned()].into_iter().collect(),
+            ..Default::default()
+        };
+
+        let data = json!({
+            "text": "hello",
+            "email": "john@example.org",
+            "attachments": [{ "name": "cv.pdf", "email": "john@example.org" }],
+        });
+
+        assert_eq!(
+            scrub(&config, &data),
+            json!({ "text": "hello", "attachments": [{ "name": "cv.pdf" }] })
+        );
+    }
+}
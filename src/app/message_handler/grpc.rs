@@ -0,0 +1,84 @@
+use http::StatusCode;
+use serde_json::Value as JsonValue;
+use svc_agent::{AgentId, Authenticable};
+use tonic::{Code, Status};
+
+use crate::app::{
+    context::{AppContext, Context},
+    endpoint::RequestHandler,
+    error::{Error as AppError, ErrorExt, ErrorKind as AppErrorKind},
+    service_utils::{Notification, RequestParams, Response},
+};
+
+use super::{handle_request, Transport};
+
+/// Requests of the internal gRPC API, the service converts their payloads from and to protobuf.
+pub struct Grpc;
+
+/// Handles a request of the authenticated service agent.
+pub async fn handle<H: RequestHandler>(
+    context: &AppContext,
+    agent_id: &AgentId,
+    payload: H::Payload,
+) -> Result<(JsonValue, Vec<Notification>), Status> {
+    handle_request::<Grpc, H, _>(&mut context.start_message(), agent_id, payload).await
+}
+
+impl Transport for Grpc {
+    type Request = AgentId;
+    type Reply = Result<(JsonValue, Vec<Notification>), Status>;
+
+    const NAME: &'static str = "grpc";
+
+    fn params(request: &Self::Request) -> RequestParams<'_> {
+        RequestParams::Http { agent_id: request }
+    }
+
+    fn reply<C: Context>(
+        context: &C,
+        request: &Self::Request,
+        response: Response,
+    ) -> Result<Self::Reply, AppError> {
+        let pseudonyms = context.pseudonyms();
+        let hide_from_reader = pseudonyms.hides_from(
+            request.as_account_id(),
+            context.agent_id().as_account_id().audience(),
+        );
+
+        let (status, payload, notifications) = response
+            .pseudonymize(&pseudonyms, hide_from_reader)
+            .into_parts();
+
+        let payload = match status {
+            StatusCode::NO_CONTENT => JsonValue::Null,
+            _ => payload.error(AppErrorKind::InvalidPayload)?,
+        };
+
+        Ok(Ok((payload, notifications)))
+    }
+
+    fn reply_error<C: Context>(
+        _context: &C,
+        _request: &Self::Request,
+        err: AppError,
+    ) -> Self::Reply {
+        Err(status(&err))
+    }
+}
+
+/// Maps the error to the closest gRPC code keeping its kind in the message.
+pub fn status(err: &AppError) -> Status {
+    let code = match err.status() {
+        StatusCode::BAD_REQUEST | StatusCode::UNPROCESSABLE_ENTITY => Code::InvalidArgument,
+        StatusCode::UNAUTHORIZED => Code::Unauthenticated,
+        StatusCode::FORBIDDEN => Code::PermissionDenied,
+        StatusCode::NOT_FOUND => Code::NotFound,
+        StatusCode::CONFLICT => Code::FailedPrecondition,
+        StatusCode::TOO_MANY_REQUESTS => Code::ResourceExhausted,
+        StatusCode::SERVICE_UNAVAILABLE => Code::Unavailable,
+        StatusCode::GATEWAY_TIMEOUT | StatusCode::REQUEST_TIMEOUT => Code::DeadlineExceeded,
+        _ => Code::Internal,
+    };
+
+    Status::new(code, format!("{}: {}", err.kind(), err.detail()))
+}
@@ -0,0 +1,60 @@
+use svc_agent::AgentId;
+
+use crate::app::{
+    context::{AppContext, Context, GlobalContext},
+    drain::InFlight,
+    endpoint::{RequestHandler, RequestResult},
+    error::Error as AppError,
+    service_utils::{RequestParams, Response},
+};
+
+use super::{handle_request, Transport};
+
+/// Requests of the HTTP API taken by the axum extractors of the endpoints.
+pub struct Http;
+
+/// Handles a request of the authenticated agent.
+pub async fn handle<H: RequestHandler>(
+    context: &AppContext,
+    agent_id: &AgentId,
+    payload: H::Payload,
+) -> RequestResult {
+    handle_request::<Http, H, _>(&mut context.start_message(), agent_id, payload).await
+}
+
+/// Counts in flight a request of the endpoints which don't go through [`handle`].
+pub fn admit(context: &AppContext, serves_while_draining: bool) -> Result<InFlight, AppError> {
+    context
+        .metrics()
+        .drain
+        .admit(Http::NAME, serves_while_draining)
+}
+
+impl Transport for Http {
+    /// Requests are told apart only by the agent as the rest is up to the extractors.
+    type Request = AgentId;
+    type Reply = RequestResult;
+
+    const NAME: &'static str = "http";
+    const SCOPES_TENANT_USAGE: bool = false;
+
+    fn params(request: &Self::Request) -> RequestParams<'_> {
+        RequestParams::Http { agent_id: request }
+    }
+
+    fn reply<C: Context>(
+        _context: &C,
+        _request: &Self::Request,
+        response: Response,
+    ) -> Result<Self::Reply, AppError> {
+        Ok(Ok(response))
+    }
+
+    fn reply_error<C: Context>(
+        _context: &C,
+        _request: &Self::Request,
+        err: AppError,
+    ) -> Self::Reply {
+        Err(err)
+    }
+}
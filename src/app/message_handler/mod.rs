@@ -1,48 +1,55 @@
 use crate::{
     app::{
+        broker::BrokerAgent,
         context::{AppMessageContext, Context, GlobalContext, MessageContext},
+        dispatcher::Dispatcher,
         endpoint,
         error::{Error as AppError, ErrorKind as AppErrorKind},
-        service_utils::RequestParams,
-        API_VERSION,
+        service_utils::{RequestParams, Response},
+        tenant_usage,
     },
     backend::{janus, janus::handle_event},
 };
 use anyhow::anyhow;
 use anyhow::Context as AnyhowContext;
-use chrono::{DateTime, Utc};
+use chrono::Utc;
 use futures::{stream, Stream, StreamExt};
 use std::{future::Future, pin::Pin};
 use svc_agent::{
     mqtt::{
-        Agent, IncomingEvent, IncomingMessage, IncomingRequest, IncomingRequestProperties,
-        IncomingResponse, IntoPublishableMessage, OutgoingResponse, ResponseStatus,
-        ShortTermTimingProperties,
+        IncomingEvent, IncomingMessage, IncomingRequest, IncomingResponse, IntoPublishableMessage,
     },
     Addressable, Authenticable,
 };
-use svc_error::Error as SvcError;
 use tracing::{error, field::display, info, warn, Span};
 use tracing_attributes::instrument;
 use uuid::Uuid;
 
+pub mod grpc;
+pub mod http;
+pub mod mqtt;
+pub mod nats;
+pub mod ws;
+
 pub type MessageStream =
     Box<dyn Stream<Item = Box<dyn IntoPublishableMessage + Send + Sync + 'static>> + Send + Unpin>;
 
 pub struct MessageHandler<C: GlobalContext> {
-    agent: Agent,
+    agent: BrokerAgent,
+    dispatcher: Dispatcher,
     global_context: C,
 }
 
 impl<C: GlobalContext + Sync> MessageHandler<C> {
-    pub fn new(agent: Agent, global_context: C) -> Self {
+    pub fn new(agent: BrokerAgent, dispatcher: Dispatcher, global_context: C) -> Self {
         Self {
             agent,
+            dispatcher,
             global_context,
         }
     }
 
-    pub fn agent(&self) -> &Agent {
+    pub fn agent(&self) -> &BrokerAgent {
         &self.agent
     }
 
@@ -84,7 +91,7 @@ impl<C: GlobalContext + Sync> MessageHandler<C> {
 
         let messages = handle_event(&mut msg_context, message).await;
 
-        self.publish_outgoing_messages(messages).await;
+        self.publish_outgoing_messages(None, messages).await;
         info!("Janus notifications sent");
     }
 
@@ -116,18 +123,15 @@ impl<C: GlobalContext + Sync> MessageHandler<C> {
         let outgoing_message_stream = endpoint::route_request(msg_context, request, topic)
             .await
             .unwrap_or_else(|| {
-                error_response(
-                    ResponseStatus::METHOD_NOT_ALLOWED,
-                    "about:blank",
-                    "Unknown method",
-                    "Unknown method",
-                    request.properties(),
-                    msg_context.start_timestamp(),
-                )
+                let err = AppError::new(AppErrorKind::UnknownMethod, anyhow!("Unknown method"));
+                mqtt::error_response(&err, request.properties(), msg_context.start_timestamp())
             });
 
-        self.publish_outgoing_messages(outgoing_message_stream)
-            .await;
+        self.publish_outgoing_messages(
+            Some(request.properties().method()),
+            outgoing_message_stream,
+        )
+        .await;
         info!("Response sent");
     }
 
@@ -147,7 +151,7 @@ impl<C: GlobalContext + Sync> MessageHandler<C> {
         let outgoing_message_stream =
             endpoint::route_response(msg_context, response, raw_corr_data, topic).await;
 
-        self.publish_outgoing_messages(outgoing_message_stream)
+        self.publish_outgoing_messages(None, outgoing_message_stream)
             .await;
         info!("Notification sent");
     }
@@ -171,121 +175,109 @@ impl<C: GlobalContext + Sync> MessageHandler<C> {
                 Box::new(stream::empty())
             });
 
-        self.publish_outgoing_messages(outgoing_message_stream)
+        self.publish_outgoing_messages(None, outgoing_message_stream)
             .await;
         info!("Notifications sent");
     }
 
-    async fn publish_outgoing_messages(&self, mut message_stream: MessageStream) {
-        let mut agent = self.agent.clone();
-
+    async fn publish_outgoing_messages(
+        &self,
+        method: Option<&str>,
+        mut message_stream: MessageStream,
+    ) {
         while let Some(message) = message_stream.next().await {
-            publish_message(&mut agent, message);
+            self.dispatcher.dispatch(method, message);
         }
     }
 }
 
-fn error_response(
-    status: ResponseStatus,
-    kind: &str,
-    title: &str,
-    detail: &str,
-    reqp: &IncomingRequestProperties,
-    start_timestamp: DateTime<Utc>,
-) -> MessageStream {
-    let err = SvcError::builder()
-        .status(status)
-        .kind(kind, title)
-        .detail(detail)
-        .build();
-
-    let timing = ShortTermTimingProperties::until_now(start_timestamp);
-    let props = reqp.to_response(status, timing);
-    let resp = OutgoingResponse::unicast(err, props, reqp, API_VERSION);
-    let boxed_resp = Box::new(resp) as Box<dyn IntoPublishableMessage + Send + Sync + 'static>;
-    Box::new(stream::once(std::future::ready(boxed_resp)))
-}
+///////////////////////////////////////////////////////////////////////////////
 
-pub fn publish_message(agent: &mut Agent, message: Box<dyn IntoPublishableMessage>) {
-    if let Err(err) = agent.publish_publishable(message) {
-        error!(?err, "Failed to publish message");
-        AppError::new(AppErrorKind::MqttPublishFailed, err).notify_sentry();
-    }
-}
+/// A transport requests come over. Its adapter turns the handler results into replies of the
+/// transport so the request handlers don't depend on it. Parsing the payload is up to the
+/// adapter as well since transports carry it differently.
+pub trait Transport {
+    /// A request as received from the transport.
+    type Request: ?Sized + Sync;
+    /// What goes back over the transport, be it a response or an error.
+    type Reply: Send;
 
-///////////////////////////////////////////////////////////////////////////////
+    /// Requests in flight are counted under this name.
+    const NAME: &'static str;
 
-// These auto-traits are being defined on all request/event handlers.
-// They do parsing of the envelope and payload, call the handler and perform error handling.
-// So we don't implement these generic things in each handler.
-// We just need to specify the payload type and specific logic.
+    /// Whether requests get accounted to the tenant here rather than by the transport itself.
+    const SCOPES_TENANT_USAGE: bool = true;
 
-pub trait RequestEnvelopeHandler<'async_trait> {
-    fn handle_envelope<C: Context + Send + Sync>(
-        context: &'async_trait mut C,
-        req: &'async_trait IncomingRequest<String>,
-    ) -> Pin<Box<dyn Future<Output = MessageStream> + Send + 'async_trait>>;
+    fn params(request: &Self::Request) -> RequestParams<'_>;
+
+    fn reply<C: Context>(
+        context: &C,
+        request: &Self::Request,
+        response: Response,
+    ) -> Result<Self::Reply, AppError>;
+
+    fn reply_error<C: Context>(context: &C, request: &Self::Request, err: AppError) -> Self::Reply;
 }
 
-// Can't use `#[async_trait]` macro here because it's not smart enough to add `'async_trait`
-// lifetime to `H` type parameter. The creepy stuff around the actual implementation is what
-// this macro expands to based on https://github.com/dtolnay/async-trait#explanation.
-impl<'async_trait, H: 'async_trait + Sync + endpoint::RequestHandler>
-    RequestEnvelopeHandler<'async_trait> for H
+/// Calls the handler with the payload parsed by the adapter of the transport `T` and replies
+/// with the result. Accounts the request to the tenant and observes its outcome on the way.
+/// New requests are refused while the instance is draining.
+pub async fn handle_request<T, H, C>(
+    context: &mut C,
+    request: &T::Request,
+    payload: H::Payload,
+) -> T::Reply
+where
+    T: Transport,
+    H: endpoint::RequestHandler,
+    C: Context + Send + Sync,
 {
-    fn handle_envelope<C: Context + Send + Sync>(
-        context: &'async_trait mut C,
-        req: &'async_trait IncomingRequest<String>,
-    ) -> Pin<Box<dyn Future<Output = MessageStream> + Send + 'async_trait>>
-    where
-        Self: Sync + 'async_trait,
-    {
-        // The actual implementation.
-        async fn handle_envelope<H: endpoint::RequestHandler, C: Context + Sync + Send>(
-            context: &mut C,
-            req: &IncomingRequest<String>,
-        ) -> MessageStream {
-            // Parse the envelope with the payload type specified in the handler.
-            let payload = IncomingRequest::convert_payload::<H::Payload>(req);
-            let reqp = req.properties();
-
-            match payload {
-                // Call handler.
-                Ok(payload) => {
-                    let app_result =
-                        H::handle(context, payload, RequestParams::MqttParams(reqp)).await;
-                    context.metrics().observe_app_result(&app_result);
-                    app_result
-                        .and_then(|r| r.into_mqtt_messages(reqp))
-                        .unwrap_or_else(|err| {
-                            error!(?err, "Failed to handle request");
-                            err.notify_sentry();
-                            error_response(
-                                err.status(),
-                                err.kind(),
-                                err.title(),
-                                &err.detail(),
-                                reqp,
-                                context.start_timestamp(),
-                            )
-                        })
-                }
-                // Bad envelope or payload format => 400.
-                Err(err) => error_response(
-                    ResponseStatus::BAD_REQUEST,
-                    reqp.method(),
-                    H::ERROR_TITLE,
-                    &err.to_string(),
-                    reqp,
-                    context.start_timestamp(),
-                ),
-            }
+    let reqp = T::params(request);
+    let metrics = context.metrics();
+
+    let app_result = match metrics.drain.admit(T::NAME, H::SERVES_WHILE_DRAINING) {
+        Ok(_in_flight) if T::SCOPES_TENANT_USAGE => {
+            let audience = reqp.as_account_id().audience().to_owned();
+
+            metrics
+                .tenant_usage
+                .scope(&audience, async {
+                    let result = H::handle(context, payload, reqp).await;
+
+                    if let Ok(ref response) = result {
+                        tenant_usage::record(tenant_usage::Counters {
+                            bytes_published: response.payload_size() as i64,
+                            ..Default::default()
+                        });
+                    }
+
+                    result
+                })
+                .await
         }
-
-        Box::pin(handle_envelope::<H, C>(context, req))
-    }
+        Ok(_in_flight) => H::handle(context, payload, reqp).await,
+        Err(err) => Err(err),
+    };
+
+    metrics.observe_app_result(&app_result);
+
+    app_result
+        .and_then(|response| T::reply(context, request, response))
+        .unwrap_or_else(|err| {
+            error!(?err, "Failed to handle request");
+            err.notify_sentry();
+            T::reply_error(context, request, err)
+        })
 }
 
+///////////////////////////////////////////////////////////////////////////////
+
+// These auto-traits are being defined on all response/event handlers.
+// They do parsing of the envelope and payload, call the handler and perform error handling.
+// So we don't implement these generic things in each handler.
+// We just need to specify the payload type and specific logic.
+// Requests go through `handle_request` instead since they come over several transports.
+
 pub trait ResponseEnvelopeHandler<'async_trait, CD> {
     fn handle_envelope<C: Context + Send + Sync>(
         context: &'async_trait mut C,
@@ -391,3 +383,113 @@ impl endpoint::CorrelationData {
         serde_json::from_str::<Self>(raw_corr_data).context("Failed to parse correlation data")
     }
 }
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use axum::response::IntoResponse;
+    use serde_json::Value as JsonValue;
+    use svc_agent::mqtt::ResponseStatus;
+
+    use crate::{
+        app::endpoint::room,
+        test_helpers::{db::TestDb, parse_messages, prelude::*},
+    };
+
+    use super::*;
+
+    async fn mqtt_reply(err: &AppError) -> (ResponseStatus, JsonValue) {
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let reqp = build_reqp(agent.agent_id(), "room.read");
+        let messages = parse_messages(mqtt::error_response(err, &reqp, Utc::now())).await;
+        let (payload, respp, _) = find_response::<JsonValue>(messages.as_slice());
+        (respp.status(), payload)
+    }
+
+    fn ws_reply(err: &AppError) -> (u16, JsonValue) {
+        match ws::OutgoingFrame::error("1", err) {
+            ws::OutgoingFrame::Response {
+                status, payload, ..
+            } => (status, payload),
+            frame => panic!("Unexpected frame: {:?}", frame),
+        }
+    }
+
+    async fn http_reply(err: AppError) -> (u16, JsonValue) {
+        let response = err.into_response();
+        let status = response.status().as_u16();
+        let body = hyper::body::to_bytes(response.into_body())
+            .await
+            .expect("Failed to read body");
+        let payload = serde_json::from_slice(&body).expect("Failed to parse body");
+        (status, payload)
+    }
+
+    #[tokio::test]
+    async fn transports_render_errors_alike() {
+        let err = AppError::new(AppErrorKind::RoomClosed, anyhow!("Room is closed"))
+            .with_extra("room_id", "123");
+
+        let (mqtt_status, mqtt_payload) = mqtt_reply(&err).await;
+        let (ws_status, ws_payload) = ws_reply(&err);
+        let (http_status, http_payload) = http_reply(err).await;
+
+        assert_eq!(mqtt_status, ResponseStatus::NOT_FOUND);
+        assert_eq!(ws_status, 404);
+        assert_eq!(http_status, 404);
+
+        assert_eq!(mqtt_payload["type"], "room_closed");
+        assert_eq!(mqtt_payload["room_id"], "123");
+        assert_eq!(mqtt_payload, ws_payload);
+        assert_eq!(mqtt_payload, http_payload);
+    }
+
+    #[tokio::test]
+    async fn unknown_method_is_reported_alike() {
+        let err = AppError::new(AppErrorKind::UnknownMethod, anyhow!("Unknown method"));
+        let (mqtt_status, mqtt_payload) = mqtt_reply(&err).await;
+        assert_eq!(mqtt_status, ResponseStatus::METHOD_NOT_ALLOWED);
+        assert_eq!(mqtt_payload["type"], "unknown_method");
+
+        let (ws_status, ws_payload) = match ws::OutgoingFrame::unknown_method("1") {
+            ws::OutgoingFrame::Response {
+                status, payload, ..
+            } => (status, payload),
+            frame => panic!("Unexpected frame: {:?}", frame),
+        };
+
+        assert_eq!(ws_status, 405);
+        assert_eq!(ws_payload, mqtt_payload);
+    }
+
+    #[sqlx::test]
+    async fn invalid_payload_is_reported_by_kind(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let mut context = TestContext::new(db, TestAuthz::new()).await;
+
+        let request = ws::Request {
+            agent_id: agent.agent_id().to_owned(),
+            frame: ws::IncomingFrame {
+                id: "1".to_owned(),
+                method: "room.read".to_owned(),
+                payload: serde_json::json!({ "id": "not a uuid" }),
+            },
+        };
+
+        let (status, payload) =
+            match ws::handle_frame::<room::ReadHandler, _>(&mut context, &request).await {
+                (
+                    ws::OutgoingFrame::Response {
+                        status, payload, ..
+                    },
+                    _,
+                ) => (status, payload),
+                frame => panic!("Unexpected frame: {:?}", frame),
+            };
+
+        assert_eq!(status, 400);
+        assert_eq!(payload["type"], "invalid_payload");
+    }
+}
@@ -0,0 +1,132 @@
+use chrono::{DateTime, Utc};
+use futures::stream;
+use std::{future::Future, pin::Pin};
+use svc_agent::{
+    mqtt::{
+        IncomingRequest, IncomingRequestProperties, IntoPublishableMessage, OutgoingResponse,
+        ShortTermTimingProperties,
+    },
+    Authenticable,
+};
+
+use crate::app::{
+    compat,
+    context::Context,
+    endpoint,
+    error::{Error as AppError, ErrorKind as AppErrorKind},
+    service_utils::{RequestParams, Response},
+    API_VERSION,
+};
+
+use super::{handle_request, MessageStream, Transport};
+
+/// Requests arriving as envelopes of the broker.
+pub struct Mqtt;
+
+impl Transport for Mqtt {
+    type Request = IncomingRequest<String>;
+    type Reply = MessageStream;
+
+    const NAME: &'static str = "mqtt";
+
+    fn params(request: &Self::Request) -> RequestParams<'_> {
+        RequestParams::MqttParams(request.properties())
+    }
+
+    fn reply<C: Context>(
+        context: &C,
+        request: &Self::Request,
+        response: Response,
+    ) -> Result<Self::Reply, AppError> {
+        let reqp = request.properties();
+        let shape = compat::Shape::detect(&context.config().compat, reqp);
+
+        let pseudonyms = context.pseudonyms();
+        let hide_from_reader = pseudonyms.hides_from(
+            reqp.as_account_id(),
+            context.agent_id().as_account_id().audience(),
+        );
+
+        response
+            .pseudonymize(&pseudonyms, hide_from_reader)
+            .map_payload(|p| shape.adapt_response(reqp.method(), p))
+            .into_mqtt_messages(reqp)
+    }
+
+    fn reply_error<C: Context>(context: &C, request: &Self::Request, err: AppError) -> Self::Reply {
+        error_response(&err, request.properties(), context.start_timestamp())
+    }
+}
+
+pub(super) fn error_response(
+    err: &AppError,
+    reqp: &IncomingRequestProperties,
+    start_timestamp: DateTime<Utc>,
+) -> MessageStream {
+    let status = err.status();
+    let err = err.to_svc_error();
+
+    let timing = ShortTermTimingProperties::until_now(start_timestamp);
+    let props = reqp.to_response(status, timing);
+    let resp = OutgoingResponse::unicast(err, props, reqp, API_VERSION);
+    let boxed_resp = Box::new(resp) as Box<dyn IntoPublishableMessage + Send + Sync + 'static>;
+    Box::new(stream::once(std::future::ready(boxed_resp)))
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+// This auto-trait is being defined on all request handlers to parse the envelope and its
+// payload before passing it to `handle_request`.
+
+pub trait RequestEnvelopeHandler<'async_trait> {
+    fn handle_envelope<C: Context + Send + Sync>(
+        context: &'async_trait mut C,
+        req: &'async_trait IncomingRequest<String>,
+    ) -> Pin<Box<dyn Future<Output = MessageStream> + Send + 'async_trait>>;
+}
+
+// Can't use `#[async_trait]` macro here because it's not smart enough to add `'async_trait`
+// lifetime to `H` type parameter. The creepy stuff around the actual implementation is what
+// this macro expands to based on https://github.com/dtolnay/async-trait#explanation.
+impl<'async_trait, H: 'async_trait + Sync + endpoint::RequestHandler>
+    RequestEnvelopeHandler<'async_trait> for H
+{
+    fn handle_envelope<C: Context + Send + Sync>(
+        context: &'async_trait mut C,
+        req: &'async_trait IncomingRequest<String>,
+    ) -> Pin<Box<dyn Future<Output = MessageStream> + Send + 'async_trait>>
+    where
+        Self: Sync + 'async_trait,
+    {
+        // The actual implementation.
+        async fn handle_envelope<H: endpoint::RequestHandler, C: Context + Sync + Send>(
+            context: &mut C,
+            req: &IncomingRequest<String>,
+        ) -> MessageStream {
+            let reqp = req.properties();
+            let shape = compat::Shape::detect(&context.config().compat, reqp);
+
+            // Parse the envelope with the payload type specified in the handler.
+            let payload = match shape {
+                compat::Shape::V1 => {
+                    compat::v1::parse_request::<H::Payload>(reqp.method(), req.payload())
+                        .map_err(anyhow::Error::from)
+                }
+                compat::Shape::Current => {
+                    IncomingRequest::convert_payload::<H::Payload>(req).map_err(anyhow::Error::from)
+                }
+            };
+
+            match payload {
+                Ok(payload) => handle_request::<Mqtt, H, C>(context, req, payload).await,
+                // Bad envelope or payload format => 400.
+                Err(err) => {
+                    let err = AppError::new(AppErrorKind::InvalidPayload, err);
+                    error_response(&err, reqp, context.start_timestamp())
+                }
+            }
+        }
+
+        Box::pin(handle_envelope::<H, C>(context, req))
+    }
+}
@@ -0,0 +1,94 @@
+use std::convert::TryFrom;
+
+use anyhow::{anyhow, Context as AnyhowContext};
+use svc_agent::{mqtt::ResponseStatus, AgentId};
+use svc_nats_client::{
+    consumer::{FailureKind, HandleMessageFailure},
+    Headers, Message,
+};
+
+use crate::app::{
+    context::Context,
+    endpoint::RequestHandler,
+    error::{Error as AppError, ErrorKind as AppErrorKind},
+    service_utils::{RequestParams, Response},
+};
+
+use super::{handle_request, Transport};
+
+/// Messages consumed from NATS, the sender is taken from the message headers.
+pub struct Nats;
+
+/// Handles a message of the consumer. Errors which may pass on redelivery are transient so
+/// that the message gets redelivered, the rest are permanent.
+pub async fn handle<H, C>(
+    context: &mut C,
+    message: &Message,
+) -> Result<Response, HandleMessageFailure<anyhow::Error>>
+where
+    H: RequestHandler,
+    C: Context + Send + Sync,
+{
+    let headers = message
+        .headers
+        .clone()
+        .ok_or_else(|| anyhow!("missing headers"))
+        .permanent()?;
+
+    let headers = Headers::try_from(headers)
+        .map_err(|err| anyhow!(err))
+        .permanent()?;
+
+    let payload = serde_json::from_slice::<H::Payload>(&message.payload)
+        .context("failed to parse payload")
+        .permanent()?;
+
+    handle_request::<Nats, H, _>(context, headers.sender_id(), payload).await
+}
+
+impl Transport for Nats {
+    type Request = AgentId;
+    type Reply = Result<Response, HandleMessageFailure<anyhow::Error>>;
+
+    const NAME: &'static str = "nats";
+    const SCOPES_TENANT_USAGE: bool = false;
+
+    fn params(request: &Self::Request) -> RequestParams<'_> {
+        RequestParams::Http { agent_id: request }
+    }
+
+    fn reply<C: Context>(
+        _context: &C,
+        _request: &Self::Request,
+        response: Response,
+    ) -> Result<Self::Reply, AppError> {
+        Ok(Ok(response))
+    }
+
+    fn reply_error<C: Context>(
+        _context: &C,
+        _request: &Self::Request,
+        err: AppError,
+    ) -> Self::Reply {
+        Err(failure(err))
+    }
+}
+
+fn failure(err: AppError) -> HandleMessageFailure<anyhow::Error> {
+    let transient = match err.error_kind() {
+        AppErrorKind::DbConnAcquisitionFailed | AppErrorKind::DbQueryFailed => true,
+        _ => {
+            let status = err.status();
+
+            status.is_server_error()
+                || status == ResponseStatus::FAILED_DEPENDENCY
+                || status == ResponseStatus::TOO_MANY_REQUESTS
+        }
+    };
+
+    if transient {
+        HandleMessageFailure::Transient(anyhow!(err))
+    } else {
+        HandleMessageFailure::Permanent(anyhow!(err))
+    }
+}
@@ -0,0 +1,128 @@
+use anyhow::anyhow;
+use http::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use svc_agent::{AgentId, Authenticable};
+
+use crate::app::{
+    context::Context,
+    endpoint::RequestHandler,
+    error::{Error as AppError, ErrorExt, ErrorKind as AppErrorKind},
+    service_utils::{Notification, RequestParams, Response},
+};
+
+use super::{handle_request, Transport};
+
+/// Requests arriving as JSON frames over the WebSocket of the agent.
+pub struct Ws;
+
+/// A request frame sent by the client.
+#[derive(Debug, Deserialize)]
+pub struct IncomingFrame {
+    pub id: String,
+    pub method: String,
+    #[serde(default)]
+    pub payload: JsonValue,
+}
+
+/// A frame sent to the client: either a reply to its request or an event it's subscribed to.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OutgoingFrame {
+    Response {
+        id: String,
+        status: u16,
+        payload: JsonValue,
+    },
+    Event {
+        label: Option<String>,
+        payload: JsonValue,
+    },
+}
+
+impl OutgoingFrame {
+    pub fn response(id: &str, status: StatusCode, payload: JsonValue) -> Self {
+        Self::Response {
+            id: id.to_owned(),
+            status: status.as_u16(),
+            payload,
+        }
+    }
+
+    pub fn error(id: &str, err: &AppError) -> Self {
+        let payload = serde_json::to_value(err.to_svc_error()).unwrap_or_default();
+        Self::response(id, err.status(), payload)
+    }
+
+    pub fn unknown_method(id: &str) -> Self {
+        let err = AppError::new(AppErrorKind::UnknownMethod, anyhow!("Unknown method"));
+        Self::error(id, &err)
+    }
+}
+
+/// A request frame of the agent the socket is authenticated as.
+pub struct Request {
+    pub agent_id: AgentId,
+    pub frame: IncomingFrame,
+}
+
+/// Parses the payload of the frame for the handler and handles the request.
+pub async fn handle_frame<H: RequestHandler, C: Context + Send + Sync>(
+    context: &mut C,
+    request: &Request,
+) -> (OutgoingFrame, Vec<Notification>) {
+    match serde_json::from_value::<H::Payload>(request.frame.payload.clone()) {
+        Ok(payload) => handle_request::<Ws, H, C>(context, request, payload).await,
+        // Bad payload format => 400.
+        Err(err) => {
+            let err = AppError::new(AppErrorKind::InvalidPayload, anyhow::Error::from(err));
+            (OutgoingFrame::error(&request.frame.id, &err), vec![])
+        }
+    }
+}
+
+impl Transport for Ws {
+    type Request = Request;
+    type Reply = (OutgoingFrame, Vec<Notification>);
+
+    const NAME: &'static str = "ws";
+
+    fn params(request: &Self::Request) -> RequestParams<'_> {
+        // Like HTTP there's no envelope, so the handlers respond with the result synchronously.
+        RequestParams::Http {
+            agent_id: &request.agent_id,
+        }
+    }
+
+    fn reply<C: Context>(
+        context: &C,
+        request: &Self::Request,
+        response: Response,
+    ) -> Result<Self::Reply, AppError> {
+        let pseudonyms = context.pseudonyms();
+        let hide_from_reader = pseudonyms.hides_from(
+            request.agent_id.as_account_id(),
+            context.agent_id().as_account_id().audience(),
+        );
+
+        let (status, payload, notifications) = response
+            .pseudonymize(&pseudonyms, hide_from_reader)
+            .into_parts();
+
+        let payload = match status {
+            StatusCode::NO_CONTENT => JsonValue::Null,
+            _ => payload.error(AppErrorKind::InvalidPayload)?,
+        };
+
+        let frame = OutgoingFrame::response(&request.frame.id, status, payload);
+        Ok((frame, notifications))
+    }
+
+    fn reply_error<C: Context>(
+        _context: &C,
+        request: &Self::Request,
+        err: AppError,
+    ) -> Self::Reply {
+        (OutgoingFrame::error(&request.frame.id, &err), vec![])
+    }
+}
@@ -3,11 +3,14 @@ use std::{collections::HashMap, sync::Arc, time::Duration};
 use chrono::{DateTime, Utc};
 use enum_iterator::IntoEnumIterator;
 use prometheus::{
-    Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+    Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts,
+    Registry,
 };
 use prometheus_static_metric::make_static_metric;
 
-use super::{endpoint, error::ErrorKind};
+use super::{
+    dispatcher::Priority, drain::Drain, endpoint, error::ErrorKind, tenant_usage::TenantUsage,
+};
 
 pub trait HistogramExt {
     fn observe_timestamp(&self, start: DateTime<Utc>);
@@ -32,6 +35,7 @@ make_static_metric! {
     struct RequestDuration: Histogram {
         "method" => {
             agent_list,
+            agent_preferences_update,
             agent_reader_config_read,
             agent_reader_config_update,
             agent_writer_config_read,
@@ -42,11 +46,19 @@ make_static_metric! {
             message_callback,
             message_unicast_request,
             message_unicast_response,
+            room_admit,
             room_close,
             room_create,
+            room_create_from_template,
+            room_delete,
             room_enter,
             room_leave,
+            room_list,
             room_read,
+            room_reject,
+            room_schedule_create,
+            room_schedule_delete,
+            room_template_update,
             room_update,
             rtc_connect,
             rtc_create,
@@ -71,10 +83,22 @@ pub struct Metrics {
     pub mqtt_reconnection: IntCounter,
     pub mqtt_disconnect: IntCounter,
     pub mqtt_connection_error: IntCounter,
+    pub mqtt_failover: IntCounter,
+    pub mqtt_active_broker: IntGauge,
     pub total_requests: IntCounter,
     pub authorization_time: Histogram,
     pub running_requests_total: IntGauge,
     pub outbox_errors: HashMap<String, IntCounter>,
+    pub outgoing_queue_depth: HashMap<Priority, IntGauge>,
+    pub tenant_usage: TenantUsage,
+    pub drain: Drain,
+    pub reader_config_push_delta: IntCounter,
+    pub reader_config_push_full: IntCounter,
+    pub storage_object_empty: IntCounter,
+    pub storage_object_checksum_mismatch: IntCounter,
+    pub storage_object_unknown: IntCounter,
+    pub stream_start_latency: HistogramVec,
+    pub cors_rejected_origins: IntCounterVec,
 }
 
 impl Metrics {
@@ -94,15 +118,58 @@ impl Metrics {
             Opts::new("mqtt_messages", "Mqtt message types"),
             &["status"],
         )?;
+        let mqtt_active_broker = IntGauge::new(
+            "mqtt_active_broker",
+            "Index of the MQTT broker in use, 0 is the primary one",
+        )?;
+        let reader_config_push = IntCounterVec::new(
+            Opts::new(
+                "reader_config_push_items",
+                "Reader configs pushed to Janus (delta) vs the whole matrix they're part of (full)",
+            ),
+            &["kind"],
+        )?;
+        let storage_object_mismatches = IntCounterVec::new(
+            Opts::new(
+                "storage_object_mismatches",
+                "Uploaded recording objects that are empty, corrupted or have no recording",
+            ),
+            &["kind"],
+        )?;
+        let stream_start_latency = HistogramVec::new(
+            HistogramOpts::new(
+                "stream_start_latency",
+                "Time from rtc.connect to WebRtcUp of the connection",
+            )
+            .buckets(vec![0.25, 0.5, 1., 2., 3., 5., 7.5, 10., 15., 30.]),
+            &["audience", "backend"],
+        )?;
+        let cors_rejected_origins = IntCounterVec::new(
+            Opts::new(
+                "cors_rejected_origins",
+                "HTTP requests rejected for an origin not allowed for the audience",
+            ),
+            &["audience"],
+        )?;
         let outbox_stats =
             IntCounterVec::new(Opts::new("outbox_stats", "Outbox stats"), &["kind"])?;
+        let outgoing_queue_depth = IntGaugeVec::new(
+            Opts::new("outgoing_queue_depth", "Outgoing messages queue depth"),
+            &["class"],
+        )?;
         registry.register(Box::new(mqtt_errors.clone()))?;
+        registry.register(Box::new(mqtt_active_broker.clone()))?;
         registry.register(Box::new(request_duration.clone()))?;
         registry.register(Box::new(request_stats.clone()))?;
         registry.register(Box::new(total_requests.clone()))?;
         registry.register(Box::new(authorization_time.clone()))?;
         registry.register(Box::new(running_requests_total.clone()))?;
         registry.register(Box::new(outbox_stats.clone()))?;
+        registry.register(Box::new(reader_config_push.clone()))?;
+        registry.register(Box::new(storage_object_mismatches.clone()))?;
+        registry.register(Box::new(outgoing_queue_depth.clone()))?;
+        registry.register(Box::new(stream_start_latency.clone()))?;
+        registry.register(Box::new(cors_rejected_origins.clone()))?;
         Ok(Self {
             request_duration: RequestDuration::from(&request_duration),
             total_requests,
@@ -121,6 +188,8 @@ impl Metrics {
                 .get_metric_with_label_values(&["connection_error"])?,
             mqtt_disconnect: mqtt_errors.get_metric_with_label_values(&["disconnect"])?,
             mqtt_reconnection: mqtt_errors.get_metric_with_label_values(&["reconnect"])?,
+            mqtt_failover: mqtt_errors.get_metric_with_label_values(&["failover"])?,
+            mqtt_active_broker,
             outbox_errors: ErrorKind::into_enum_iter()
                 .map(|kind| {
                     let kind = kind.kind();
@@ -130,6 +199,27 @@ impl Metrics {
                     ))
                 })
                 .collect::<anyhow::Result<_>>()?,
+            outgoing_queue_depth: Priority::into_enum_iter()
+                .map(|priority| {
+                    Ok((
+                        priority,
+                        outgoing_queue_depth.get_metric_with_label_values(&[priority.as_str()])?,
+                    ))
+                })
+                .collect::<anyhow::Result<_>>()?,
+            tenant_usage: TenantUsage::new(registry)?,
+            drain: Drain::new(registry)?,
+            reader_config_push_delta: reader_config_push
+                .get_metric_with_label_values(&["delta"])?,
+            reader_config_push_full: reader_config_push.get_metric_with_label_values(&["full"])?,
+            storage_object_empty: storage_object_mismatches
+                .get_metric_with_label_values(&["empty"])?,
+            storage_object_checksum_mismatch: storage_object_mismatches
+                .get_metric_with_label_values(&["checksum"])?,
+            storage_object_unknown: storage_object_mismatches
+                .get_metric_with_label_values(&["unknown"])?,
+            stream_start_latency,
+            cors_rejected_origins,
         })
     }
 
@@ -140,6 +230,23 @@ impl Metrics {
         }
     }
 
+    pub fn observe_reader_config_push(&self, delta: usize, full: usize) {
+        self.reader_config_push_delta.inc_by(delta as u64);
+        self.reader_config_push_full.inc_by(full as u64);
+    }
+
+    pub fn observe_stream_start(&self, audience: &str, backend: &str, latency: Duration) {
+        self.stream_start_latency
+            .with_label_values(&[audience, backend])
+            .observe(duration_to_seconds(latency));
+    }
+
+    pub fn observe_cors_rejection(&self, audience: &str) {
+        self.cors_rejected_origins
+            .with_label_values(&[audience])
+            .inc();
+    }
+
     /// This is helpful with HTTP.
     pub fn observe_app_ok(&self) {
         self.app_result_ok.inc();
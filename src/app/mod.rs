@@ -2,15 +2,22 @@ use std::{net::SocketAddr, sync::Arc, time::Duration};
 
 use crate::{
     app::{
+        broker::BrokerAgent,
         error::{Error as AppError, ErrorKind as AppErrorKind},
         http::build_router,
+        pseudonym::Pseudonyms,
+    },
+    backend::janus::{
+        client_pool::Clients, event_capture::EventCapture, handle_pool::HandlePool,
+        online_handler::start_internal_api, poll_scheduler::PollScheduler, rate_limit::RateLimiter,
+        rtt, transport::Transports, trickle_batch::TrickleBatcher, JANUS_API_VERSION,
     },
-    backend::janus::{client_pool::Clients, online_handler::start_internal_api, JANUS_API_VERSION},
     client::{conference::ConferenceHttpClient, mqtt_gateway::MqttGatewayHttpClient},
     config::{self, Config},
 };
 use anyhow::{Context as AnyhowContext, Result};
 use context::{AppContext, GlobalContext};
+use dispatcher::Dispatcher;
 use futures::StreamExt;
 use hyper::{
     service::{make_service_fn, service_fn},
@@ -20,7 +27,7 @@ use message_handler::MessageHandler;
 use prometheus::{Encoder, Registry, TextEncoder};
 use signal_hook::consts::TERM_SIGNALS;
 use svc_agent::{
-    mqtt::{Agent, AgentBuilder, AgentNotification, ConnectionMode, QoS},
+    mqtt::{AgentNotification, QoS},
     AgentId, Authenticable, SharedGroup, Subscription,
 };
 use svc_authn::token::jws_compact;
@@ -51,13 +58,24 @@ pub async fn run(
         .build()
         .context("Error creating an id token")?;
 
-    let mut agent_config = config.mqtt.clone();
-    agent_config.set_password(&token);
+    //metrics
+    let metrics_registry = Registry::new();
+    let metrics = crate::app::metrics::Metrics::new(&metrics_registry)?;
+    let janus_metrics = crate::backend::janus::metrics::Metrics::new(&metrics_registry)?;
 
-    let (mut agent, mut rx) = AgentBuilder::new(agent_id.clone(), API_VERSION)
-        .connection_mode(ConnectionMode::Service)
-        .start(&agent_config)
-        .context("Failed to create an agent")?;
+    let (mut agent, mut rx) = broker::start(
+        agent_id.clone(),
+        API_VERSION,
+        brokers(&config, &token),
+        config
+            .mqtt_failover
+            .as_ref()
+            .map(|f| f.max_connection_errors),
+        broker::FailoverMetrics {
+            failover: metrics.mqtt_failover.clone(),
+            active_broker: metrics.mqtt_active_broker.clone(),
+        },
+    )?;
 
     // Authz
     let authz = svc_authz::ClientMap::new(
@@ -71,11 +89,6 @@ pub async fn run(
     if let Some(sentry_config) = config.sentry.as_ref() {
         svc_error::extension::sentry::init(sentry_config);
     }
-    //metrics
-    let metrics_registry = Registry::new();
-    let metrics = crate::app::metrics::Metrics::new(&metrics_registry)?;
-    let janus_metrics = crate::backend::janus::metrics::Metrics::new(&metrics_registry)?;
-
     let replica_label =
         std::env::var("APP_AGENT_LABEL").expect("APP_AGENT_LABEL must be specified");
     let own_ip_addr = cluster_ip::get_ip(&replica_label).await?;
@@ -87,7 +100,28 @@ pub async fn run(
         config.waitlist_epoch_duration,
         own_ip_addr,
         Some(agent.clone()),
-    );
+    )
+    .with_transports(
+        Transports::new(&config.janus_transport).context("Failed to build janus transports")?,
+    )
+    .with_rate_limiter(
+        RateLimiter::new(config.janus_rate_limit.clone(), &metrics_registry)
+            .context("Failed to build janus rate limiter")?,
+    )
+    .with_handle_pool(
+        HandlePool::new(config.janus_handle_pool.clone(), &metrics_registry)
+            .context("Failed to build janus handle pool")?,
+    )
+    .with_pseudonyms(Pseudonyms::new(&config.pseudonymize))
+    .with_event_capture(EventCapture::new(
+        db.clone(),
+        config.janus_event_capture.clone(),
+    ))
+    .with_poll_scheduler(
+        PollScheduler::new(config.janus_poll.clone(), &metrics_registry)
+            .context("Failed to build janus poll scheduler")?,
+    )
+    .with_trickle_batcher(TrickleBatcher::new(config.janus_trickle.clone()));
 
     task::spawn({
         let db = db.clone();
@@ -95,6 +129,18 @@ pub async fn run(
         let clients = clients.clone();
         janus_metrics.start_collector(db, clients, collect_interval)
     });
+    if let Some(janus_rtt) = config.janus_rtt.clone() {
+        task::spawn(rtt::start_prober(clients.clone(), db.clone(), janus_rtt));
+    }
+    task::spawn(room_health::start_aggregator(
+        db.clone(),
+        config.room_health.clone(),
+    ));
+    task::spawn(tenant_usage::start_flusher(
+        db.clone(),
+        metrics.tenant_usage.clone(),
+        config.tenant_usage.clone(),
+    ));
     task::spawn(start_metrics_collector(
         metrics_registry,
         config.metrics.http.bind_address,
@@ -111,6 +157,15 @@ pub async fn run(
 
     let mqtt_client = crate::client::mqtt::new(agent.clone());
 
+    let sockets = ws::Sockets::new();
+    let journal = long_poll::EventJournal::new(config.long_poll.clone());
+    let (dispatcher, _dispatcher_task) = Dispatcher::start(
+        agent.clone(),
+        metrics.outgoing_queue_depth.clone(),
+        sockets.clone(),
+        journal.clone(),
+    );
+
     let context = AppContext::new(
         config.clone(),
         authz,
@@ -122,16 +177,19 @@ pub async fn run(
         mqtt_client,
     );
 
-    let context = match &config.nats {
+    let (context, nats_client) = match &config.nats {
         Some(cfg) => {
             let nats_client = svc_nats_client::Client::new(cfg.clone())
                 .await
                 .context("nats client")?;
             info!("Connected to nats");
 
-            context.add_nats_client(nats_client)
+            (
+                context.add_nats_client(nats_client.clone()),
+                Some(nats_client),
+            )
         }
-        None => context,
+        None => (context, None),
     };
 
     let reg_handler = tokio::spawn(start_internal_api(
@@ -141,6 +199,20 @@ pub async fn run(
         config.authn.clone(),
     ));
 
+    #[cfg(feature = "janus_stub")]
+    if let Some(stub) = &config.janus_stub {
+        crate::backend::janus::online_handler::register_stub(
+            stub,
+            config.janus_group.clone(),
+            context.janus_clients(),
+            context.db().clone(),
+        )
+        .await
+        .context("Failed to register stub janus")?;
+
+        warn!(agent_id = %stub.agent_id, "Registered stub janus, no media is going to flow");
+    }
+
     let context = match redis_pool {
         Some(pool) => context.add_redis_pool(pool),
         None => context,
@@ -152,7 +224,9 @@ pub async fn run(
             .serve(
                 build_router(
                     Arc::new(context.clone()),
-                    agent.clone(),
+                    dispatcher.clone(),
+                    sockets,
+                    journal,
                     config.authn.clone(),
                 )
                 .into_make_service(),
@@ -162,11 +236,86 @@ pub async fn run(
             }),
     );
 
+    if let Some(grpc_config) = &config.grpc {
+        let service = grpc::Service::new(
+            Arc::new(context.clone()),
+            dispatcher.clone(),
+            config.authn.clone(),
+        );
+
+        let mut shutdown_grpc_rx = graceful_rx.clone();
+        task::spawn(grpc::serve(grpc_config.bind_address, service, async move {
+            shutdown_grpc_rx.changed().await.ok();
+        }));
+    }
+
+    task::spawn(stream_limit::start_enforcer(
+        context.clone(),
+        dispatcher.clone(),
+        config.stream_limit.clone(),
+    ));
+
+    task::spawn(recording_archive::start_announcer(
+        context.clone(),
+        dispatcher.clone(),
+        config.recording_archive.clone(),
+    ));
+
+    task::spawn(room_schedule::start_scheduler(
+        context.clone(),
+        dispatcher.clone(),
+        config.room_schedule.clone(),
+    ));
+
+    task::spawn(room_upload::start_worker(
+        context.clone(),
+        dispatcher.clone(),
+        config.room_upload.clone(),
+    ));
+
     let ctx: Arc<dyn GlobalContext + Send + Sync> = Arc::new(context.clone());
-    let outbox_handler = outbox_handler::run(ctx, graceful_rx.clone())?;
+
+    if let Some(quality_hint) = config.quality_hint.clone() {
+        task::spawn(quality_hint::start_controller(ctx.clone(), quality_hint));
+    }
+
+    task::spawn(active_speaker::start_publisher(ctx.clone()));
+
+    let outbox_handler = outbox_handler::run(ctx.clone(), graceful_rx.clone())?;
+
+    let storage_events_consumer = match &config.storage_events {
+        Some(cfg) => {
+            let nats_client = svc_nats_client::Client::new(cfg.nats.clone())
+                .await
+                .context("storage events nats client")?;
+            let ctx = ctx.clone();
+
+            Some(svc_nats_client::consumer::run(
+                nats_client,
+                cfg.consumer.clone(),
+                graceful_rx.clone(),
+                move |msg| storage_events::handle_message(ctx.clone(), msg),
+            ))
+        }
+        None => None,
+    };
+
+    let config_sync_consumer = match (nats_client, &config.nats_consumer) {
+        (Some(nats_client), Some(cfg)) => {
+            let context = context.clone();
+
+            Some(svc_nats_client::consumer::run(
+                nats_client,
+                cfg.clone(),
+                graceful_rx.clone(),
+                move |msg| config_sync::handle_message(context.clone(), msg),
+            ))
+        }
+        _ => None,
+    };
 
     // Message handler
-    let message_handler = Arc::new(MessageHandler::new(agent.clone(), context));
+    let message_handler = Arc::new(MessageHandler::new(agent.clone(), dispatcher, context));
     {
         let message_handler = message_handler.clone();
         tokio::spawn(async move {
@@ -204,6 +353,18 @@ pub async fn run(
         error!(%err, "failed to await outbox handler completion");
     }
 
+    if let Some(consumer) = config_sync_consumer {
+        if let Err(err) = consumer.await {
+            error!(%err, "failed to await config sync consumer completion");
+        }
+    }
+
+    if let Some(consumer) = storage_events_consumer {
+        if let Err(err) = consumer.await {
+            error!(%err, "failed to await storage events consumer completion");
+        }
+    }
+
     tokio::time::sleep(Duration::from_secs(3)).await;
     info!(
         requests_left = metrics.running_requests_total.get(),
@@ -256,7 +417,31 @@ fn handle_message(message: AgentNotification, message_handler: Arc<MessageHandle
     });
 }
 
-fn subscribe(agent: &mut Agent, agent_id: &AgentId, config: &Config) -> Result<()> {
+fn brokers(config: &Config, token: &str) -> Vec<broker::Broker> {
+    let primary = broker::Broker {
+        name: "primary".to_owned(),
+        config: config.mqtt.clone(),
+    };
+
+    let fallbacks = config
+        .mqtt_failover
+        .iter()
+        .flat_map(|f| f.brokers.iter())
+        .map(|b| broker::Broker {
+            name: b.name.clone(),
+            config: b.agent.clone(),
+        });
+
+    std::iter::once(primary)
+        .chain(fallbacks)
+        .map(|mut broker| {
+            broker.config.set_password(token);
+            broker
+        })
+        .collect()
+}
+
+fn subscribe(agent: &mut BrokerAgent, agent_id: &AgentId, config: &Config) -> Result<()> {
     let group = SharedGroup::new("loadbalancer", agent_id.as_account_id().clone());
 
     // Multicast requests
@@ -289,7 +474,7 @@ fn subscribe(agent: &mut Agent, agent_id: &AgentId, config: &Config) -> Result<(
     Ok(())
 }
 
-fn unsubscribe(agent: &mut Agent, agent_id: &AgentId, config: &Config) -> anyhow::Result<()> {
+fn unsubscribe(agent: &mut BrokerAgent, agent_id: &AgentId, config: &Config) -> anyhow::Result<()> {
     let group = SharedGroup::new("loadbalancer", agent_id.as_account_id().clone());
 
     // Multicast requests
@@ -319,7 +504,7 @@ fn unsubscribe(agent: &mut Agent, agent_id: &AgentId, config: &Config) -> anyhow
     Ok(())
 }
 
-fn resubscribe(agent: &mut Agent, agent_id: &AgentId, config: &Config) {
+fn resubscribe(agent: &mut BrokerAgent, agent_id: &AgentId, config: &Config) {
     if let Err(err) = subscribe(agent, agent_id, config) {
         let err = err.context("Failed to resubscribe after reconnection");
         error!(?err, "Resubscription error");
@@ -370,15 +555,42 @@ async fn start_metrics_collector(registry: Registry, bind_addr: SocketAddr) -> a
     Ok(())
 }
 
+pub mod active_speaker;
+pub mod audio_level;
+pub mod broker;
 mod cluster_ip;
+pub mod compat;
+pub mod config_sync;
 pub mod context;
+pub mod cors;
+pub mod dispatcher;
+pub mod drain;
+pub mod encrypted_message;
 pub mod endpoint;
 pub mod error;
+pub mod grpc;
 pub mod handle_id;
 pub mod http;
+pub mod long_poll;
+pub mod message_archive;
+pub mod message_export;
 pub mod message_handler;
 pub mod metrics;
+pub mod pseudonym;
+pub mod quality_hint;
+pub mod recording_archive;
+pub mod room_authz;
+pub mod room_health;
+pub mod room_policy;
+pub mod room_schedule;
+pub mod room_upload;
+pub mod sdp;
 pub mod service_utils;
+pub mod signal_history;
+pub mod storage_events;
+pub mod stream_limit;
+pub mod tenant_usage;
+pub mod ws;
 
 mod group_reader_config;
 mod outbox_handler;
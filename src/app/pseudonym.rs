@@ -0,0 +1,189 @@
+use std::{collections::HashMap, sync::Arc};
+
+use hmac::{Hmac, Mac};
+use serde_json::Value as JsonValue;
+use sha2::Sha256;
+use svc_agent::{AccountId, AgentId, Authenticable};
+use tracing::error;
+
+use crate::config::PseudonymizeConfigMap;
+
+const ACCOUNT_LABEL_PREFIX: &str = "anon-";
+
+/// Bytes of the digest kept in the account label.
+const DIGEST_LEN: usize = 12;
+
+/// Shows agents of opted-in audiences as `<label>.anon-<digest>.<audience>`.
+#[derive(Clone, Default)]
+pub struct Pseudonyms {
+    keys: Arc<HashMap<String, Hmac<Sha256>>>,
+}
+
+impl Pseudonyms {
+    pub fn new(config: &PseudonymizeConfigMap) -> Self {
+        let keys = config
+            .iter()
+            .filter_map(|(audience, config)| {
+                match Hmac::<Sha256>::new_from_slice(config.secret.as_bytes()) {
+                    Ok(key) => Some((audience.to_owned(), key)),
+                    Err(err) => {
+                        error!(%audience, %err, "Invalid pseudonymization secret");
+                        None
+                    }
+                }
+            })
+            .collect();
+
+        Self {
+            keys: Arc::new(keys),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.keys.is_empty()
+    }
+
+    /// Whether `reader` gets pseudonyms instead of real ids. Only the service accounts see
+    /// through them.
+    pub fn hides_from(&self, reader: &AccountId, service_audience: &str) -> bool {
+        self.is_enabled() && reader.audience() != service_audience
+    }
+
+    /// Returns the pseudonym of the agent or `None` when its audience hasn't opted in.
+    pub fn agent_id(&self, agent_id: &AgentId) -> Option<AgentId> {
+        let account_id = agent_id.as_account_id();
+        let key = self.keys.get(account_id.audience())?;
+
+        if account_id.label().starts_with(ACCOUNT_LABEL_PREFIX) {
+            return None;
+        }
+
+        let mut mac = key.clone();
+        mac.update(account_id.to_string().as_bytes());
+        let digest = mac.finalize().into_bytes();
+
+        let label = digest[..DIGEST_LEN].iter().fold(
+            String::from(ACCOUNT_LABEL_PREFIX),
+            |mut label, byte| {
+                label.push_str(&format!("{:02x}", byte));
+                label
+            },
+        );
+
+        let account_id = AccountId::new(&label, account_id.audience());
+        Some(AgentId::new(agent_id.label(), account_id))
+    }
+
+    /// Replaces every agent id of the opted-in audiences in the payload, both in values and in
+    /// object keys.
+    pub fn apply(&self, payload: &mut JsonValue) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        match payload {
+            JsonValue::String(s) => {
+                if let Some(pseudonym) = self.pseudonymize_str(s) {
+                    *s = pseudonym;
+                }
+            }
+            JsonValue::Array(items) => {
+                for item in items {
+                    self.apply(item);
+                }
+            }
+            JsonValue::Object(map) => {
+                let renamed = map
+                    .keys()
+                    .filter_map(|k| Some((k.to_owned(), self.pseudonymize_str(k)?)))
+                    .collect::<Vec<_>>();
+
+                for (key, pseudonym) in renamed {
+                    if let Some(value) = map.remove(&key) {
+                        map.insert(pseudonym, value);
+                    }
+                }
+
+                for value in map.values_mut() {
+                    self.apply(value);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn pseudonymize_str(&self, s: &str) -> Option<String> {
+        let agent_id = s.parse::<AgentId>().ok()?;
+        self.agent_id(&agent_id)
+            .map(|pseudonym| pseudonym.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::config::PseudonymizeConfig;
+
+    use super::*;
+
+    fn pseudonyms() -> Pseudonyms {
+        let mut config = PseudonymizeConfigMap::new();
+
+        config.insert(
+            "private.example.org".to_owned(),
+            PseudonymizeConfig {
+                secret: "secret".to_owned(),
+            },
+        );
+
+        Pseudonyms::new(&config)
+    }
+
+    #[test]
+    fn stable_pseudonyms() {
+        let pseudonyms = pseudonyms();
+        let agent_id = "web.john.private.example.org".parse::<AgentId>().unwrap();
+        let other_device = "ios.john.private.example.org".parse::<AgentId>().unwrap();
+
+        let pseudonym = pseudonyms.agent_id(&agent_id).expect("No pseudonym");
+        assert_eq!(pseudonym.label(), "web");
+        assert!(pseudonym.as_account_id().label().starts_with("anon-"));
+        assert_eq!(pseudonym.as_account_id().audience(), "private.example.org");
+        assert_eq!(pseudonyms.agent_id(&agent_id), Some(pseudonym.clone()));
+
+        let other_pseudonym = pseudonyms.agent_id(&other_device).expect("No pseudonym");
+        assert_eq!(other_pseudonym.as_account_id(), pseudonym.as_account_id());
+
+        // Pseudonyms are not pseudonymized again.
+        assert_eq!(pseudonyms.agent_id(&pseudonym), None);
+    }
+
+    #[test]
+    fn apply_to_payload() {
+        let pseudonyms = pseudonyms();
+        let agent_id = "web.john.private.example.org".parse::<AgentId>().unwrap();
+        let pseudonym = pseudonyms.agent_id(&agent_id).unwrap().to_string();
+
+        let mut payload = json!({
+            "agent_id": "web.john.private.example.org",
+            "created_by": "alpha.conference.svc.example.org",
+            "agents": ["web.john.private.example.org", "web.jane.example.org"],
+            "configs": {"web.john.private.example.org": {"receive_video": true}},
+            "label": "a.b",
+        });
+
+        pseudonyms.apply(&mut payload);
+
+        assert_eq!(
+            payload,
+            json!({
+                "agent_id": pseudonym,
+                "created_by": "alpha.conference.svc.example.org",
+                "agents": [pseudonym, "web.jane.example.org"],
+                "configs": {pseudonym.clone(): {"receive_video": true}},
+                "label": "a.b",
+            })
+        );
+    }
+}
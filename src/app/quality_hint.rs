@@ -0,0 +1,217 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+use chrono::Utc;
+use serde::Serialize;
+use svc_agent::AgentId;
+use tracing::error;
+
+use crate::{
+    app::context::GlobalContext,
+    config::{QualityHintConfig, QualityHintThresholds},
+    db,
+};
+
+const QUALITY_HINT_LABEL: &str = "room.quality_hint";
+/// Share of the threshold the backend's rate must fall below to lift the hint.
+const RECOVERY_SHARE: f32 = 0.5;
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct QualityHint {
+    room_id: db::room::Id,
+    backend_id: AgentId,
+    slow_link_rate: f32,
+    /// Null once the backend has recovered and publishers may restore their quality.
+    caps: Option<Caps>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+struct Caps {
+    max_height: u32,
+    max_fps: u32,
+    max_bitrate: u32,
+}
+
+impl From<&QualityHintThresholds> for Caps {
+    fn from(thresholds: &QualityHintThresholds) -> Self {
+        Self {
+            max_height: thresholds.max_height,
+            max_fps: thresholds.max_fps,
+            max_bitrate: thresholds.max_bitrate,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Action {
+    Reduce,
+    Lift,
+}
+
+/// Periodically checks the slow link rates of backends and asks their rooms to reduce
+/// the publishers' quality while the backend is overloaded.
+pub async fn start_controller(
+    ctx: Arc<dyn GlobalContext + Send + Sync>,
+    config: QualityHintConfig,
+) {
+    loop {
+        if let Err(err) = check(ctx.as_ref(), &config).await {
+            error!(?err, "Failed to check backends for quality hints");
+        }
+
+        tokio::time::sleep(config.interval).await;
+    }
+}
+
+async fn check<C: GlobalContext + ?Sized>(
+    ctx: &C,
+    config: &QualityHintConfig,
+) -> anyhow::Result<Vec<QualityHint>> {
+    let mut conn = ctx.get_conn().await?;
+    let resend_before = Utc::now() - chrono::Duration::from_std(config.resend_interval)?;
+
+    let loads = db::room_quality_hint::backend_loads(&mut conn)
+        .await
+        .context("Failed to fetch backend loads")?;
+
+    let mut hints = vec![];
+
+    for load in loads {
+        let thresholds = config.thresholds(&load.audience);
+
+        // Other instances run the controller too so the hint is sent by whoever records it.
+        let caps = match decide(load.slow_link_rate, thresholds, load.hint_sent_at.is_some()) {
+            Some(Action::Reduce) => {
+                if !db::room_quality_hint::claim(load.room_id, resend_before, &mut conn).await? {
+                    continue;
+                }
+
+                Some(Caps::from(thresholds))
+            }
+            Some(Action::Lift) => {
+                if !db::room_quality_hint::release(load.room_id, &mut conn).await? {
+                    continue;
+                }
+
+                None
+            }
+            None => continue,
+        };
+
+        let hint = QualityHint {
+            room_id: load.room_id,
+            backend_id: load.backend_id,
+            slow_link_rate: load.slow_link_rate,
+            caps,
+        };
+
+        let topic = format!("rooms/{}/events", hint.room_id);
+        let payload = serde_json::to_value(&hint)?;
+
+        if let Err(err) =
+            ctx.mqtt_client()
+                .lock()
+                .publish_payload(QUALITY_HINT_LABEL, &topic, payload)
+        {
+            error!(?err, room_id = %hint.room_id, "Failed to publish quality hint");
+        }
+
+        hints.push(hint);
+    }
+
+    Ok(hints)
+}
+
+/// Hysteresis keeps rooms from flapping around the threshold.
+fn decide(slow_link_rate: f32, thresholds: &QualityHintThresholds, hinted: bool) -> Option<Action> {
+    if slow_link_rate >= thresholds.slow_link_rate {
+        Some(Action::Reduce)
+    } else if hinted && slow_link_rate < thresholds.slow_link_rate * RECOVERY_SHARE {
+        Some(Action::Lift)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Bound;
+
+    use super::*;
+    use crate::{
+        backend::janus::client::{HandleId as JanusHandleId, SessionId},
+        test_helpers::{db::TestDb, prelude::*},
+    };
+
+    #[test]
+    fn decide_with_hysteresis() {
+        let thresholds = QualityHintThresholds {
+            slow_link_rate: 10.,
+            ..Default::default()
+        };
+
+        assert_eq!(decide(12., &thresholds, false), Some(Action::Reduce));
+        assert_eq!(decide(12., &thresholds, true), Some(Action::Reduce));
+        assert_eq!(decide(7., &thresholds, false), None);
+        assert_eq!(decide(7., &thresholds, true), None);
+        assert_eq!(decide(3., &thresholds, true), Some(Action::Lift));
+        assert_eq!(decide(3., &thresholds, false), None);
+    }
+
+    #[sqlx::test]
+    async fn hint_rooms_of_overloaded_backend(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let mut conn = db.get_conn().await;
+
+        let backend = shared_helpers::insert_janus_backend(
+            &mut conn,
+            "https://janus.example.org",
+            SessionId::random(),
+            JanusHandleId::random(),
+        )
+        .await;
+
+        let now = Utc::now();
+        let room = factory::Room::new()
+            .audience(USR_AUDIENCE)
+            .time((Bound::Included(now), Bound::Unbounded))
+            .rtc_sharing_policy(db::rtc::SharingPolicy::Shared)
+            .backend_id(backend.id())
+            .insert(&mut conn)
+            .await;
+
+        db::room_health::UpsertQuery::new(room.id(), 20., &[])
+            .rates(0., 20., 0.)
+            .execute(&mut conn)
+            .await
+            .expect("Failed to store room health");
+
+        let context = TestContext::new(db.clone(), TestAuthz::new()).await;
+        let config = QualityHintConfig {
+            default: QualityHintThresholds {
+                slow_link_rate: 10.,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let hints = check(&context, &config).await.expect("Check failed");
+        assert_eq!(hints.len(), 1);
+        assert_eq!(hints[0].room_id, room.id());
+        assert!(hints[0].caps.is_some());
+
+        // Already hinted rooms wait for the resend interval.
+        let hints = check(&context, &config).await.expect("Check failed");
+        assert!(hints.is_empty());
+
+        db::room_health::UpsertQuery::new(room.id(), 2., &[])
+            .rates(0., 2., 0.)
+            .execute(&mut conn)
+            .await
+            .expect("Failed to store room health");
+
+        let hints = check(&context, &config).await.expect("Check failed");
+        assert_eq!(hints.len(), 1);
+        assert!(hints[0].caps.is_none());
+    }
+}
@@ -0,0 +1,111 @@
+use anyhow::Context as AnyhowContext;
+use chrono::{serde::ts_seconds, DateTime, Duration, Utc};
+use serde::Serialize;
+use svc_agent::mqtt::{OutgoingEvent, OutgoingEventProperties, ShortTermTimingProperties};
+use tracing::error;
+use uuid::Uuid;
+
+use crate::{
+    app::{
+        context::{AppContext, GlobalContext, MessageContext},
+        dispatcher::Dispatcher,
+        endpoint::system,
+    },
+    config::{RecordingArchiveConfig, RecordingArchivePolicy},
+    db::{self, room::FindQueryable},
+};
+
+const ARCHIVE_DUE_LABEL: &str = "recording.archive_due";
+
+#[derive(Debug, Serialize)]
+struct ArchiveDue {
+    room_id: db::room::Id,
+    classroom_id: Uuid,
+    rtc_id: db::rtc::Id,
+    uri: String,
+    #[serde(with = "ts_seconds")]
+    room_closed_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    storage_class: Option<String>,
+}
+
+/// Periodically announces recordings due for archiving by the policies of their audiences.
+pub async fn start_announcer(
+    ctx: AppContext,
+    dispatcher: Dispatcher,
+    config: RecordingArchiveConfig,
+) {
+    loop {
+        for (audience, policy) in &config.policy {
+            if let Err(err) = announce(&ctx, &dispatcher, &config, audience, policy).await {
+                error!(?err, %audience, "Failed to announce recordings due for archiving");
+            }
+        }
+
+        tokio::time::sleep(config.interval).await;
+    }
+}
+
+async fn announce(
+    ctx: &AppContext,
+    dispatcher: &Dispatcher,
+    config: &RecordingArchiveConfig,
+    audience: &str,
+    policy: &RecordingArchivePolicy,
+) -> anyhow::Result<()> {
+    let archive_after = match policy.archive_after {
+        Some(archive_after) => {
+            Duration::from_std(archive_after).context("Invalid archive_after")?
+        }
+        None => return Ok(()),
+    };
+
+    let mut conn = ctx.get_conn().await?;
+
+    let due = db::recording::mark_archive_due(
+        audience,
+        Utc::now() - archive_after,
+        config.batch_size,
+        &mut conn,
+    )
+    .await
+    .context("Failed to mark recordings due for archiving")?;
+
+    let msg_ctx = ctx.start_message();
+    let path = format!("audiences/{audience}/events");
+
+    for recording in due {
+        let room = db::room::FindQuery::new(recording.room_id)
+            .execute(&mut conn)
+            .await?
+            .context("Room not found")?;
+
+        let bucket = match system::upload_config(&msg_ctx, &room) {
+            Ok(upload_config) => &upload_config.bucket,
+            Err(err) => {
+                error!(?err, rtc_id = %recording.rtc_id, "No upload config for the recording");
+                continue;
+            }
+        };
+
+        let payload = ArchiveDue {
+            room_id: room.id(),
+            classroom_id: room.classroom_id(),
+            rtc_id: recording.rtc_id,
+            uri: format!(
+                "s3://{}/{}",
+                bucket,
+                system::rtc_record_name(recording.rtc_id, &room)
+            ),
+            room_closed_at: recording.room_closed_at,
+            storage_class: policy.archive_storage_class.clone(),
+        };
+
+        let timing = ShortTermTimingProperties::until_now(msg_ctx.start_timestamp());
+        let props = OutgoingEventProperties::new(ARCHIVE_DUE_LABEL, timing);
+        let event = OutgoingEvent::broadcast(payload, props, &path);
+        dispatcher.dispatch(None, Box::new(event));
+    }
+
+    Ok(())
+}
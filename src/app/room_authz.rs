@@ -0,0 +1,177 @@
+use std::{collections::HashMap, sync::Arc, time::Instant};
+
+use parking_lot::Mutex;
+use svc_agent::{AccountId, AgentId, Authenticable};
+
+use crate::{config::RoomAuthzCacheConfig, db};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ActionClass {
+    Read,
+    Update,
+}
+
+impl ActionClass {
+    pub fn action(self) -> &'static str {
+        match self {
+            ActionClass::Read => "read",
+            ActionClass::Update => "update",
+        }
+    }
+}
+
+type Key = (AgentId, db::room::Id, ActionClass);
+
+/// Successful decisions kept for `ttl` in memory of the instance which made them.
+#[derive(Clone, Default)]
+pub struct RoomAuthzCache {
+    config: RoomAuthzCacheConfig,
+    decisions: Arc<Mutex<HashMap<Key, Instant>>>,
+}
+
+impl RoomAuthzCache {
+    pub fn new(config: RoomAuthzCacheConfig) -> Self {
+        Self {
+            config,
+            decisions: Arc::default(),
+        }
+    }
+
+    /// Whether the agent has been allowed the class of actions in the room within the TTL.
+    pub fn is_allowed(
+        &self,
+        agent_id: &AgentId,
+        room_id: db::room::Id,
+        class: ActionClass,
+        now: Instant,
+    ) -> bool {
+        let mut decisions = self.decisions.lock();
+        let key = (agent_id.to_owned(), room_id, class);
+
+        match decisions.get(&key) {
+            Some(expires_at) if *expires_at > now => true,
+            Some(_) => {
+                decisions.remove(&key);
+                false
+            }
+            None => false,
+        }
+    }
+
+    pub fn allow(
+        &self,
+        agent_id: &AgentId,
+        room_id: db::room::Id,
+        class: ActionClass,
+        now: Instant,
+    ) {
+        if self.config.ttl.is_zero() {
+            return;
+        }
+
+        let mut decisions = self.decisions.lock();
+
+        if decisions.len() >= self.config.capacity {
+            decisions.retain(|_, expires_at| *expires_at > now);
+
+            // Too many agents are authorized at once, they go to the authz service as usual.
+            if decisions.len() >= self.config.capacity {
+                return;
+            }
+        }
+
+        decisions.insert((agent_id.to_owned(), room_id, class), now + self.config.ttl);
+    }
+
+    /// Drops the decisions of the room, e.g. its access rules might have changed.
+    pub fn forget_room(&self, room_id: db::room::Id) {
+        self.decisions.lock().retain(|(_, id, _), _| *id != room_id);
+    }
+
+    /// Drops the decisions of the account's agents in any room, e.g. it has been banned.
+    pub fn forget_account(&self, account_id: &AccountId) {
+        self.decisions
+            .lock()
+            .retain(|(agent_id, _, _), _| agent_id.as_account_id() != account_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::test_helpers::prelude::*;
+
+    use super::*;
+
+    fn cache() -> RoomAuthzCache {
+        RoomAuthzCache::new(RoomAuthzCacheConfig {
+            ttl: Duration::from_secs(30),
+            capacity: 2,
+        })
+    }
+
+    #[test]
+    fn reuse_decision_within_ttl() {
+        let cache = cache();
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let room_id = db::room::Id::random();
+        let now = Instant::now();
+
+        cache.allow(agent.agent_id(), room_id, ActionClass::Read, now);
+
+        assert!(cache.is_allowed(agent.agent_id(), room_id, ActionClass::Read, now));
+        assert!(!cache.is_allowed(agent.agent_id(), room_id, ActionClass::Update, now));
+        assert!(!cache.is_allowed(
+            agent.agent_id(),
+            db::room::Id::random(),
+            ActionClass::Read,
+            now
+        ));
+
+        let expired = now + Duration::from_secs(30);
+        assert!(!cache.is_allowed(agent.agent_id(), room_id, ActionClass::Read, expired));
+    }
+
+    #[test]
+    fn forget_decisions() {
+        let cache = cache();
+        let alice = TestAgent::new("web", "alice", USR_AUDIENCE);
+        let bob = TestAgent::new("web", "bob", USR_AUDIENCE);
+        let room_id = db::room::Id::random();
+        let now = Instant::now();
+
+        cache.allow(alice.agent_id(), room_id, ActionClass::Read, now);
+        cache.allow(bob.agent_id(), room_id, ActionClass::Read, now);
+        cache.forget_account(bob.account_id());
+
+        assert!(cache.is_allowed(alice.agent_id(), room_id, ActionClass::Read, now));
+        assert!(!cache.is_allowed(bob.agent_id(), room_id, ActionClass::Read, now));
+
+        cache.forget_room(room_id);
+        assert!(!cache.is_allowed(alice.agent_id(), room_id, ActionClass::Read, now));
+    }
+
+    #[test]
+    fn skip_decisions_over_capacity() {
+        let cache = cache();
+        let room_id = db::room::Id::random();
+        let now = Instant::now();
+
+        let agents = ["alice", "bob", "carol"]
+            .iter()
+            .map(|name| TestAgent::new("web", name, USR_AUDIENCE))
+            .collect::<Vec<_>>();
+
+        for agent in &agents {
+            cache.allow(agent.agent_id(), room_id, ActionClass::Read, now);
+        }
+
+        assert!(!cache.is_allowed(agents[2].agent_id(), room_id, ActionClass::Read, now));
+
+        // Expired decisions make room for new ones.
+        let later = now + Duration::from_secs(60);
+        cache.allow(agents[2].agent_id(), room_id, ActionClass::Read, later);
+        assert!(cache.is_allowed(agents[2].agent_id(), room_id, ActionClass::Read, later));
+    }
+}
@@ -0,0 +1,196 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use chrono::Utc;
+use tracing::error;
+
+use crate::{config::RoomHealthConfig, db};
+
+/// Score weights of the rates per minute.
+const ERROR_WEIGHT: f32 = 5.;
+const SLOW_LINK_WEIGHT: f32 = 1.;
+const HANGUP_WEIGHT: f32 = 2.;
+/// Score added when the room's backend has gone offline.
+const BACKEND_OFFLINE_SCORE: f32 = 100.;
+/// Share of the previous rate kept on each assessment.
+const SMOOTHING: f32 = 0.5;
+/// Rate per minute above which it's reported as a reason.
+const REASON_THRESHOLD: f32 = 1.;
+
+#[derive(Debug)]
+struct Health {
+    score: f32,
+    reasons: Vec<String>,
+    error_rate: f32,
+    slow_link_rate: f32,
+    hangup_rate: f32,
+}
+
+/// Periodically assesses open rooms and stores the results to `room_health`.
+pub async fn start_aggregator(db: sqlx::PgPool, config: RoomHealthConfig) {
+    loop {
+        if let Err(err) = aggregate(&db, config.interval).await {
+            error!(?err, "Failed to aggregate room health");
+        }
+
+        tokio::time::sleep(config.interval).await;
+    }
+}
+
+async fn aggregate(db: &sqlx::PgPool, interval: Duration) -> anyhow::Result<()> {
+    let mut conn = db.acquire().await?;
+    let now = Utc::now();
+    let interval_ch = chrono::Duration::from_std(interval)?;
+
+    // Other replicas run the aggregator too so rooms they've just assessed get skipped.
+    let samples = db::room_health::samples(now - interval_ch, now - interval_ch / 2, &mut conn)
+        .await
+        .context("Failed to sample rooms")?;
+
+    for sample in samples {
+        let health = assess(&sample, interval);
+
+        db::room_health::UpsertQuery::new(sample.room_id, health.score, &health.reasons)
+            .rates(health.error_rate, health.slow_link_rate, health.hangup_rate)
+            .counters(sample.slow_link_count, sample.hangup_count)
+            .execute(&mut conn)
+            .await
+            .context("Failed to store room health")?;
+    }
+
+    db::room_health::delete_closed(&mut conn)
+        .await
+        .context("Failed to delete closed rooms health")?;
+
+    Ok(())
+}
+
+fn assess(sample: &db::room_health::Sample, interval: Duration) -> Health {
+    let minutes = (interval.as_secs_f32() / 60.).max(f32::EPSILON);
+
+    let rate = |delta: i64, prev: Option<f32>| {
+        let current = delta.max(0) as f32 / minutes;
+
+        match prev {
+            Some(prev) => prev * SMOOTHING + current * (1. - SMOOTHING),
+            None => current,
+        }
+    };
+
+    // A room seen for the first time has no baseline for the counters so they don't count.
+    let slow_links = sample.slow_link_count
+        - sample
+            .prev_slow_link_count
+            .unwrap_or(sample.slow_link_count);
+    let hangups = sample.hangup_count - sample.prev_hangup_count.unwrap_or(sample.hangup_count);
+
+    let error_rate = rate(sample.errors, sample.prev_error_rate);
+    let slow_link_rate = rate(slow_links.into(), sample.prev_slow_link_rate);
+    let hangup_rate = rate(hangups.into(), sample.prev_hangup_rate);
+
+    let mut score =
+        error_rate * ERROR_WEIGHT + slow_link_rate * SLOW_LINK_WEIGHT + hangup_rate * HANGUP_WEIGHT;
+
+    let mut reasons = vec![];
+
+    if !sample.backend_online {
+        score += BACKEND_OFFLINE_SCORE;
+        reasons.push("backend_offline".to_owned());
+    }
+
+    for (reason, rate) in [
+        ("backend_errors", error_rate),
+        ("slow_links", slow_link_rate),
+        ("reconnect_churn", hangup_rate),
+    ] {
+        if rate >= REASON_THRESHOLD {
+            reasons.push(reason.to_owned());
+        }
+    }
+
+    Health {
+        score,
+        reasons,
+        error_rate,
+        slow_link_rate,
+        hangup_rate,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::prelude::*;
+
+    fn sample() -> db::room_health::Sample {
+        db::room_health::Sample {
+            room_id: db::room::Id::random(),
+            errors: 0,
+            slow_link_count: 10,
+            hangup_count: 4,
+            backend_online: true,
+            prev_error_rate: None,
+            prev_slow_link_rate: None,
+            prev_hangup_rate: None,
+            prev_slow_link_count: None,
+            prev_hangup_count: None,
+        }
+    }
+
+    #[test]
+    fn assess_new_room() {
+        let health = assess(&sample(), Duration::from_secs(60));
+        assert_eq!(health.score, 0.);
+        assert!(health.reasons.is_empty());
+    }
+
+    #[test]
+    fn assess_troubled_room() {
+        let sample = db::room_health::Sample {
+            errors: 1,
+            slow_link_count: 16,
+            backend_online: false,
+            prev_error_rate: Some(0.),
+            prev_slow_link_rate: Some(2.),
+            prev_hangup_rate: Some(0.),
+            prev_slow_link_count: Some(10),
+            prev_hangup_count: Some(4),
+            ..sample()
+        };
+
+        let health = assess(&sample, Duration::from_secs(60));
+
+        assert_eq!(health.error_rate, 0.5);
+        assert_eq!(health.slow_link_rate, 4.);
+        assert_eq!(health.hangup_rate, 0.);
+        assert_eq!(
+            health.score,
+            0.5 * ERROR_WEIGHT + 4. + BACKEND_OFFLINE_SCORE
+        );
+        assert_eq!(health.reasons, vec!["backend_offline", "slow_links"]);
+    }
+
+    #[sqlx::test]
+    async fn aggregate_offline_backend(pool: sqlx::PgPool) {
+        let mut conn = pool.acquire().await.expect("Failed to get connection");
+        let backend = TestAgent::new("alpha", "janus", SVC_AUDIENCE);
+        let healthy_room = shared_helpers::insert_room(&mut conn).await;
+        let orphaned_room =
+            shared_helpers::insert_room_with_backend_id(&mut conn, backend.agent_id()).await;
+
+        aggregate(&pool, Duration::from_secs(30))
+            .await
+            .expect("Failed to aggregate room health");
+
+        let rooms = db::room_health::ListQuery::new()
+            .execute(&mut conn)
+            .await
+            .expect("Failed to list room health");
+
+        assert_eq!(rooms.len(), 2);
+        assert_eq!(rooms[0].room_id, orphaned_room.id());
+        assert_eq!(rooms[0].reasons, vec!["backend_offline"]);
+        assert_eq!(rooms[1].room_id, healthy_room.id());
+        assert_eq!(rooms[1].score, 0.);
+    }
+}
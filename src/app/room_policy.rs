@@ -0,0 +1,266 @@
+use anyhow::anyhow;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use uuid::Uuid;
+
+use crate::{
+    app::{
+        context::GlobalContext,
+        error::{Error as AppError, ErrorKind as AppErrorKind},
+    },
+    config::RoomPolicyConfig,
+    db::{self, room::RoomTime},
+};
+
+/// Room fields set by the request being checked.
+#[derive(Debug, Serialize)]
+pub struct Proposal<'a> {
+    pub method: &'static str,
+    pub audience: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub room_id: Option<db::room::Id>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time: Option<RoomTime>,
+    /// Opening of the room before the update. Lead time is not required when it stays the same.
+    #[serde(skip)]
+    pub previous_opened_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reserve: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub classroom_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<&'a JsonValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<&'a JsonValue>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Violation {
+    /// Name of the broken rule, e.g. `min_lead_time`.
+    pub rule: String,
+    pub message: String,
+}
+
+impl Violation {
+    fn new(rule: &str, message: String) -> Self {
+        Self {
+            rule: rule.to_owned(),
+            message,
+        }
+    }
+}
+
+/// Response of the policy endpoint. An empty list allows the request.
+#[derive(Debug, Deserialize)]
+struct EndpointResponse {
+    #[serde(default)]
+    violations: Vec<Violation>,
+}
+
+/// Fails with `room_policy_violation` listing the broken rules if the audience's policy
+/// doesn't allow the proposal. Audiences without a policy allow anything.
+pub async fn check<C: GlobalContext + ?Sized>(
+    context: &C,
+    proposal: &Proposal<'_>,
+) -> Result<(), AppError> {
+    let config = match context.config().room_policy.get(proposal.audience) {
+        Some(config) => config,
+        None => return Ok(()),
+    };
+
+    let mut violations = violations(config, proposal, Utc::now());
+
+    if violations.is_empty() {
+        if let Some(endpoint) = &config.endpoint {
+            violations = ask_endpoint(endpoint, config.timeout, proposal).await?;
+        }
+    }
+
+    if violations.is_empty() {
+        return Ok(());
+    }
+
+    let rules = violations
+        .iter()
+        .map(|v| v.rule.as_str())
+        .collect::<Vec<_>>()
+        .join(",");
+
+    let messages = violations
+        .iter()
+        .map(|v| v.message.as_str())
+        .collect::<Vec<_>>()
+        .join("; ");
+
+    Err(
+        AppError::new(AppErrorKind::RoomPolicyViolation, anyhow!(messages))
+            .with_extra("rules", rules),
+    )
+}
+
+fn violations(
+    config: &RoomPolicyConfig,
+    proposal: &Proposal<'_>,
+    now: DateTime<Utc>,
+) -> Vec<Violation> {
+    let mut violations = vec![];
+
+    let new_opening = proposal
+        .time
+        .and_then(|t| t.opened_at())
+        .filter(|opened_at| Some(*opened_at) != proposal.previous_opened_at);
+
+    if let (Some(min_lead_time), Some(opened_at)) = (config.min_lead_time, new_opening) {
+        let min_lead_time =
+            Duration::from_std(min_lead_time).unwrap_or_else(|_| Duration::max_value());
+
+        if opened_at < now + min_lead_time {
+            violations.push(Violation::new(
+                "min_lead_time",
+                format!(
+                    "Room must open at least {} seconds from now",
+                    min_lead_time.num_seconds()
+                ),
+            ));
+        }
+    }
+
+    if let (Some(max_reserve), Some(reserve)) = (config.max_reserve, proposal.reserve) {
+        if reserve > max_reserve {
+            violations.push(Violation::new(
+                "max_reserve",
+                format!("Reserve must not exceed {max_reserve}"),
+            ));
+        }
+    }
+
+    if let (Some(prefix), Some(classroom_id)) = (&config.classroom_id_prefix, proposal.classroom_id)
+    {
+        if !classroom_id.to_string().starts_with(prefix.as_str()) {
+            violations.push(Violation::new(
+                "classroom_id_prefix",
+                format!("Classroom id must start with '{prefix}'"),
+            ));
+        }
+    }
+
+    violations
+}
+
+async fn ask_endpoint(
+    endpoint: &reqwest::Url,
+    timeout: std::time::Duration,
+    proposal: &Proposal<'_>,
+) -> Result<Vec<Violation>, AppError> {
+    let result = async {
+        reqwest::Client::new()
+            .post(endpoint.clone())
+            .timeout(timeout)
+            .json(proposal)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<EndpointResponse>()
+            .await
+    };
+
+    result
+        .await
+        .map(|response| response.violations)
+        .map_err(|err| {
+            AppError::new(
+                AppErrorKind::RoomPolicyCheckFailed,
+                anyhow!("Policy endpoint failed: {err}"),
+            )
+            .with_extra("endpoint", endpoint.to_string())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Bound;
+
+    use super::*;
+
+    fn config() -> RoomPolicyConfig {
+        serde_json::from_value(serde_json::json!({
+            "min_lead_time": "1 hour",
+            "max_reserve": 100,
+            "classroom_id_prefix": "00000000",
+        }))
+        .expect("Failed to parse policy")
+    }
+
+    fn proposal(opened_at: DateTime<Utc>) -> Proposal<'static> {
+        Proposal {
+            method: "room.create",
+            audience: "example.org",
+            room_id: None,
+            time: Some(
+                RoomTime::new(Bound::Included(opened_at), Bound::Unbounded)
+                    .expect("Invalid room time"),
+            ),
+            previous_opened_at: None,
+            reserve: Some(50),
+            classroom_id: Some(Uuid::nil()),
+            tags: None,
+            metadata: None,
+        }
+    }
+
+    fn rules(violations: Vec<Violation>) -> Vec<String> {
+        violations.into_iter().map(|v| v.rule).collect()
+    }
+
+    #[test]
+    fn allow_room_following_rules() {
+        let now = Utc::now();
+        let proposal = proposal(now + Duration::hours(2));
+        assert!(violations(&config(), &proposal, now).is_empty());
+    }
+
+    #[test]
+    fn reject_room_breaking_rules() {
+        let now = Utc::now();
+
+        let proposal = Proposal {
+            reserve: Some(200),
+            classroom_id: Some(Uuid::from_u128(u128::MAX)),
+            ..proposal(now + Duration::minutes(10))
+        };
+
+        let rules = rules(violations(&config(), &proposal, now));
+        assert_eq!(
+            rules,
+            ["min_lead_time", "max_reserve", "classroom_id_prefix"]
+        );
+    }
+
+    #[test]
+    fn keep_opening_on_update() {
+        let now = Utc::now();
+        let opened_at = now - Duration::minutes(10);
+
+        // The room has opened already and the update changes its closing only.
+        let update = Proposal {
+            method: "room.update",
+            previous_opened_at: Some(opened_at),
+            ..proposal(opened_at)
+        };
+
+        assert!(violations(&config(), &update, now).is_empty());
+
+        // Moving the opening needs the lead time though.
+        let update = Proposal {
+            method: "room.update",
+            previous_opened_at: Some(opened_at),
+            ..proposal(now + Duration::minutes(10))
+        };
+
+        assert_eq!(
+            rules(violations(&config(), &update, now)),
+            ["min_lead_time"]
+        );
+    }
+}
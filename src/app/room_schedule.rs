@@ -0,0 +1,438 @@
+use std::{ops::Bound, str::FromStr};
+
+use anyhow::{anyhow, bail, Context as AnyhowContext};
+use chrono::{DateTime, Datelike, Duration, NaiveDateTime, TimeZone, Utc, Weekday};
+use sqlx::Connection;
+use svc_agent::mqtt::{OutgoingEvent, OutgoingEventProperties, ShortTermTimingProperties};
+use tracing::{error, info, warn};
+
+use crate::{
+    app::{
+        context::{AppContext, GlobalContext},
+        dispatcher::Dispatcher,
+    },
+    config::RoomScheduleConfig,
+    db::{
+        self,
+        group_agent::{GroupItem, Groups},
+        room::{FindQueryable, RoomTime},
+    },
+};
+
+const ROOM_OPEN_LABEL: &str = "room.open";
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Frequency {
+    Daily,
+    Weekly,
+}
+
+/// Subset of iCalendar RRULE: `FREQ`, `INTERVAL`, `BYDAY`, `COUNT` and `UNTIL`, in UTC.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Recurrence {
+    frequency: Frequency,
+    interval: u32,
+    by_day: Vec<Weekday>,
+    count: Option<usize>,
+    until: Option<DateTime<Utc>>,
+}
+
+impl FromStr for Recurrence {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut frequency = None;
+        let mut interval = 1;
+        let mut by_day = vec![];
+        let mut count = None;
+        let mut until = None;
+
+        for part in s.trim_start_matches("RRULE:").split(';') {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Malformed rule part '{part}'"))?;
+
+            match key {
+                "FREQ" => {
+                    frequency = match value {
+                        "DAILY" => Some(Frequency::Daily),
+                        "WEEKLY" => Some(Frequency::Weekly),
+                        _ => bail!("Unsupported frequency '{value}'"),
+                    }
+                }
+                "INTERVAL" => {
+                    interval = value
+                        .parse()
+                        .ok()
+                        .filter(|i| *i > 0)
+                        .ok_or_else(|| anyhow!("Invalid interval '{value}'"))?;
+                }
+                "BYDAY" => {
+                    by_day = value
+                        .split(',')
+                        .map(parse_weekday)
+                        .collect::<anyhow::Result<Vec<_>>>()?;
+                }
+                "COUNT" => {
+                    count = value
+                        .parse()
+                        .ok()
+                        .filter(|c| *c > 0)
+                        .map(Some)
+                        .ok_or_else(|| anyhow!("Invalid count '{value}'"))?;
+                }
+                "UNTIL" => {
+                    let until_naive = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%SZ")
+                        .with_context(|| format!("Invalid until '{value}'"))?;
+
+                    until = Some(Utc.from_utc_datetime(&until_naive));
+                }
+                _ => bail!("Unsupported rule part '{key}'"),
+            }
+        }
+
+        let frequency = frequency.ok_or_else(|| anyhow!("Missing FREQ"))?;
+
+        if frequency != Frequency::Weekly && !by_day.is_empty() {
+            bail!("BYDAY is only supported for weekly rules");
+        }
+
+        by_day.sort_by_key(|d| d.num_days_from_monday());
+        by_day.dedup();
+
+        Ok(Self {
+            frequency,
+            interval,
+            by_day,
+            count,
+            until,
+        })
+    }
+}
+
+fn parse_weekday(value: &str) -> anyhow::Result<Weekday> {
+    let weekday = match value {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        _ => bail!("Invalid weekday '{value}'"),
+    };
+
+    Ok(weekday)
+}
+
+impl Recurrence {
+    /// Occurrences of the rule starting with `starts_at` in chronological order.
+    pub fn occurrences(
+        &self,
+        starts_at: DateTime<Utc>,
+    ) -> impl Iterator<Item = DateTime<Utc>> + '_ {
+        (0_i64..)
+            .map_while(move |period| self.period(starts_at, period))
+            .flatten()
+            .filter(move |t| *t >= starts_at)
+            .take_while(move |t| self.until.map_or(true, |until| *t <= until))
+            .take(self.count.unwrap_or(usize::MAX))
+    }
+
+    /// The first occurrence after `after`, if any.
+    pub fn next_after(
+        &self,
+        starts_at: DateTime<Utc>,
+        after: DateTime<Utc>,
+    ) -> Option<DateTime<Utc>> {
+        self.occurrences(starts_at).find(|t| *t > after)
+    }
+
+    fn period(&self, starts_at: DateTime<Utc>, period: i64) -> Option<Vec<DateTime<Utc>>> {
+        let step = period.checked_mul(self.interval.into())?;
+
+        match self.frequency {
+            Frequency::Daily => Some(vec![starts_at.checked_add_signed(Duration::days(step))?]),
+            Frequency::Weekly => {
+                let week_start = starts_at
+                    - Duration::days(starts_at.weekday().num_days_from_monday().into())
+                    + Duration::weeks(step);
+
+                if self.by_day.is_empty() {
+                    let offset = starts_at.weekday().num_days_from_monday();
+                    return Some(vec![week_start + Duration::days(offset.into())]);
+                }
+
+                let days = self
+                    .by_day
+                    .iter()
+                    .map(|d| week_start + Duration::days(d.num_days_from_monday().into()))
+                    .collect();
+
+                Some(days)
+            }
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Periodically creates rooms of upcoming occurrences and announces the ones which have opened.
+pub async fn start_scheduler(ctx: AppContext, dispatcher: Dispatcher, config: RoomScheduleConfig) {
+    loop {
+        if let Err(err) = create_rooms(&ctx, &dispatcher, &config).await {
+            error!(?err, "Failed to create scheduled rooms");
+        }
+
+        if let Err(err) = announce_opened(&ctx, &dispatcher, &config).await {
+            error!(?err, "Failed to announce opened scheduled rooms");
+        }
+
+        tokio::time::sleep(config.interval).await;
+    }
+}
+
+async fn create_rooms(
+    ctx: &AppContext,
+    dispatcher: &Dispatcher,
+    config: &RoomScheduleConfig,
+) -> anyhow::Result<()> {
+    let lead = Duration::from_std(config.lead).context("Invalid lead")?;
+
+    let schedules = {
+        let mut conn = ctx.get_conn().await?;
+
+        db::room_schedule::list_due(Utc::now() + lead, config.batch_size, &mut conn)
+            .await
+            .context("Failed to list due schedules")?
+    };
+
+    for schedule in schedules {
+        let schedule_id = schedule.id;
+
+        if let Err(err) = create_room(ctx, dispatcher, schedule).await {
+            error!(?err, %schedule_id, "Failed to create scheduled room");
+        }
+    }
+
+    Ok(())
+}
+
+async fn create_room(
+    ctx: &AppContext,
+    dispatcher: &Dispatcher,
+    schedule: db::room_schedule::Object,
+) -> anyhow::Result<()> {
+    let occurrence = match schedule.next_occurrence_at {
+        Some(occurrence) => occurrence,
+        None => return Ok(()),
+    };
+
+    let next = schedule
+        .recurrence
+        .parse::<Recurrence>()?
+        .next_after(schedule.starts_at, occurrence);
+
+    let time = RoomTime::new(
+        Bound::Included(occurrence),
+        Bound::Excluded(occurrence + Duration::seconds(schedule.duration.into())),
+    )?;
+
+    let mut conn = ctx.get_conn().await?;
+    let mut txn = conn.begin().await?;
+
+    // Another instance has already taken the occurrence.
+    if !db::room_schedule::advance(schedule.id, occurrence, next, &mut txn).await? {
+        return Ok(());
+    }
+
+    let template = match schedule.template {
+        Some(ref name) => {
+            let template = db::room_template::find(&schedule.audience, name, &mut txn).await?;
+
+            if template.is_none() {
+                warn!(
+                    schedule_id = %schedule.id,
+                    template = %name,
+                    "Room template of the schedule is missing"
+                );
+            }
+
+            template
+        }
+        None => None,
+    };
+
+    let rtc_sharing_policy = template
+        .as_ref()
+        .map_or(schedule.rtc_sharing_policy, |t| t.rtc_sharing_policy);
+
+    let mut q = db::room::InsertQuery::new(
+        time,
+        &schedule.audience,
+        rtc_sharing_policy,
+        schedule.classroom_id,
+    );
+
+    if let Some(reserve) = schedule
+        .reserve
+        .or_else(|| template.as_ref().and_then(|t| t.reserve))
+    {
+        q = q.reserve(reserve);
+    }
+
+    if let Some(ref tags) = schedule.tags {
+        q = q.tags(tags);
+    }
+
+    if let Some(metadata) = template.as_ref().and_then(|t| t.metadata.as_ref()) {
+        q = q.metadata(metadata);
+    }
+
+    let room = q.execute(&mut txn).await?;
+
+    if let Some(ref template) = template {
+        db::room_template::copy_defaults(room.id(), template, &mut txn).await?;
+    }
+
+    // Create a default group for minigroups
+    if room.rtc_sharing_policy() == db::rtc::SharingPolicy::Owned {
+        let groups = Groups::new(vec![GroupItem::new(0, vec![])]);
+        db::group_agent::UpsertQuery::new(room.id(), &groups)
+            .execute(&mut txn)
+            .await?;
+    }
+
+    db::room_schedule::insert_occurrence(room.id(), schedule.id, occurrence, &mut txn).await?;
+    txn.commit().await?;
+
+    info!(
+        schedule_id = %schedule.id,
+        room_id = %room.id(),
+        %occurrence,
+        "Scheduled room created"
+    );
+
+    let timing = ShortTermTimingProperties::new(Utc::now());
+    let props = OutgoingEventProperties::new("room.create", timing);
+    let path = format!("audiences/{}/events", room.audience());
+    let event = OutgoingEvent::broadcast(room, props, &path);
+    dispatcher.dispatch(None, Box::new(event));
+    Ok(())
+}
+
+async fn announce_opened(
+    ctx: &AppContext,
+    dispatcher: &Dispatcher,
+    config: &RoomScheduleConfig,
+) -> anyhow::Result<()> {
+    let mut conn = ctx.get_conn().await?;
+
+    let room_ids = db::room_schedule::claim_opened(Utc::now(), config.batch_size, &mut conn)
+        .await
+        .context("Failed to claim opened rooms")?;
+
+    for room_id in room_ids {
+        let room = match db::room::FindQuery::new(room_id).execute(&mut conn).await? {
+            Some(room) => room,
+            None => continue,
+        };
+
+        // Closed ahead of time by a host.
+        if room.is_closed() {
+            continue;
+        }
+
+        for path in [
+            format!("audiences/{}/events", room.audience()),
+            format!("rooms/{}/events", room.id()),
+        ] {
+            let timing = ShortTermTimingProperties::new(Utc::now());
+            let props = OutgoingEventProperties::new(ROOM_OPEN_LABEL, timing);
+            let event = OutgoingEvent::broadcast(room.clone(), props, &path);
+            dispatcher.dispatch(None, Box::new(event));
+        }
+    }
+
+    Ok(())
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s)
+            .expect("Invalid time")
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn parse_rule() {
+        let rule = "RRULE:FREQ=WEEKLY;INTERVAL=2;BYDAY=WE,MO;COUNT=4"
+            .parse::<Recurrence>()
+            .expect("Failed to parse rule");
+
+        assert_eq!(rule.frequency, Frequency::Weekly);
+        assert_eq!(rule.interval, 2);
+        assert_eq!(rule.by_day, vec![Weekday::Mon, Weekday::Wed]);
+        assert_eq!(rule.count, Some(4));
+
+        assert!("FREQ=MONTHLY".parse::<Recurrence>().is_err());
+        assert!("FREQ=DAILY;BYDAY=MO".parse::<Recurrence>().is_err());
+        assert!("FREQ=DAILY;INTERVAL=0".parse::<Recurrence>().is_err());
+        assert!("INTERVAL=1".parse::<Recurrence>().is_err());
+    }
+
+    #[test]
+    fn weekly_occurrences() {
+        let rule = "FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,WE;COUNT=4"
+            .parse::<Recurrence>()
+            .expect("Failed to parse rule");
+
+        // Wednesday, so Monday of the first week is skipped.
+        let starts_at = at("2023-08-09T10:00:00Z");
+
+        let occurrences = rule.occurrences(starts_at).collect::<Vec<_>>();
+
+        assert_eq!(
+            occurrences,
+            vec![
+                at("2023-08-09T10:00:00Z"),
+                at("2023-08-21T10:00:00Z"),
+                at("2023-08-23T10:00:00Z"),
+                at("2023-09-04T10:00:00Z"),
+            ]
+        );
+
+        assert_eq!(
+            rule.next_after(starts_at, at("2023-08-21T10:00:00Z")),
+            Some(at("2023-08-23T10:00:00Z"))
+        );
+        assert_eq!(rule.next_after(starts_at, at("2023-09-04T10:00:00Z")), None);
+    }
+
+    #[test]
+    fn daily_occurrences_until() {
+        let rule = "FREQ=DAILY;UNTIL=20230812T000000Z"
+            .parse::<Recurrence>()
+            .expect("Failed to parse rule");
+
+        let occurrences = rule
+            .occurrences(at("2023-08-09T18:30:00Z"))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            occurrences,
+            vec![
+                at("2023-08-09T18:30:00Z"),
+                at("2023-08-10T18:30:00Z"),
+                at("2023-08-11T18:30:00Z"),
+            ]
+        );
+    }
+}
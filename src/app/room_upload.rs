@@ -0,0 +1,155 @@
+use anyhow::Context as AnyhowContext;
+use chrono::{Duration, Utc};
+use serde_json::json;
+use svc_agent::mqtt::ResponseStatus;
+use tracing::{error, info, warn};
+
+use crate::{
+    app::{
+        context::{AppContext, GlobalContext, MessageContext},
+        dispatcher::Dispatcher,
+        endpoint::system::{self, EmptyRecordings},
+        error::Error as AppError,
+        service_utils::Response,
+    },
+    config::RoomUploadConfig,
+    db,
+};
+
+/// Schedules the upload of the closed room's recordings. Rooms which have never got a backend
+/// have nothing to upload.
+pub async fn schedule(
+    config: &RoomUploadConfig,
+    room: &db::room::Object,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<()> {
+    if config.settle_delay.is_zero() || room.backend_id().is_none() {
+        return Ok(());
+    }
+
+    let due_at = Utc::now() + settle_delay(config);
+    db::room_upload_job::enqueue(room.id(), due_at, 0, conn).await
+}
+
+fn settle_delay(config: &RoomUploadConfig) -> Duration {
+    Duration::from_std(config.settle_delay).expect("Room upload settle delay misconfigured")
+}
+
+/// Periodically uploads recordings of the rooms whose settle delay has passed.
+pub async fn start_worker(ctx: AppContext, dispatcher: Dispatcher, config: RoomUploadConfig) {
+    if config.settle_delay.is_zero() {
+        return;
+    }
+
+    loop {
+        if let Err(err) = run(&ctx, &dispatcher, &config).await {
+            error!(?err, "Failed to take due room uploads");
+        }
+
+        tokio::time::sleep(config.interval).await;
+    }
+}
+
+async fn run(
+    ctx: &AppContext,
+    dispatcher: &Dispatcher,
+    config: &RoomUploadConfig,
+) -> anyhow::Result<()> {
+    let jobs = {
+        let mut conn = ctx.get_conn().await?;
+
+        db::room_upload_job::take_due(
+            ctx.config().janus_group.as_deref(),
+            config.batch_size,
+            &mut conn,
+        )
+        .await
+        .context("Failed to take due room uploads")?
+    };
+
+    for job in jobs {
+        let room_id = job.room_id;
+
+        if let Err(err) = upload(ctx, dispatcher, config, job).await {
+            error!(?err, %room_id, "Failed to upload room recordings");
+        }
+    }
+
+    Ok(())
+}
+
+async fn upload(
+    ctx: &AppContext,
+    dispatcher: &Dispatcher,
+    config: &RoomUploadConfig,
+    job: db::room_upload_job::Object,
+) -> Result<(), AppError> {
+    let msg_ctx = ctx.start_message();
+    let mut conn = msg_ctx.get_conn().await?;
+
+    let active_streams = db::janus_rtc_stream::ListQuery::new()
+        .room_id(job.room_id)
+        .active(true)
+        .limit(1)
+        .execute(&mut conn)
+        .await?;
+
+    if !active_streams.is_empty() {
+        let attempts = job.attempts + 1;
+
+        if attempts >= config.max_attempts {
+            warn!(
+                room_id = %job.room_id,
+                attempts,
+                "Room still has streams, leaving its upload for vacuum"
+            );
+
+            return Ok(());
+        }
+
+        let due_at = Utc::now() + settle_delay(config);
+        db::room_upload_job::enqueue(job.room_id, due_at, attempts, &mut conn).await?;
+        return Ok(());
+    }
+
+    let rooms = db::room::finished_with_in_progress_recordings(
+        &mut conn,
+        msg_ctx.config().janus_group.as_deref(),
+        Some(job.room_id),
+    )
+    .await?;
+
+    if rooms.is_empty() {
+        return Ok(());
+    }
+
+    info!(room_id = %job.room_id, "Uploading recordings of the closed room");
+
+    let EmptyRecordings {
+        empty_rooms, rooms, ..
+    } = system::split_empty_recordings(rooms, &mut conn).await?;
+
+    let mut response = Response::new(
+        ResponseStatus::OK,
+        json!({}),
+        msg_ctx.start_timestamp(),
+        None,
+    );
+
+    for room in empty_rooms {
+        system::close_empty_room(&msg_ctx, room, &mut response, &mut conn).await?;
+    }
+
+    for (room, recording, backend) in rooms {
+        system::upload_recording(&msg_ctx, room, recording, backend, &mut response, &mut conn)
+            .await?;
+    }
+
+    let (_, _, notifications) = response.into_parts();
+
+    for notification in notifications {
+        dispatcher.dispatch(None, notification.into_message());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,274 @@
+use std::{borrow::Cow, collections::HashSet};
+
+use anyhow::anyhow;
+use webrtc_sdp::{attribute_type::SdpAttributeType, parse_sdp, SdpSession};
+
+use crate::app::error::{Error as AppError, ErrorKind as AppErrorKind};
+
+/// Rejects an offer that can't be parsed or lacks what any WebRTC connection needs.
+pub fn validate_offer(sdp: &str) -> Result<(), AppError> {
+    let session = parse_sdp(sdp, false).map_err(|err| malformed(format!("{}", err)))?;
+
+    if session.media.is_empty() {
+        return Err(malformed("The offer has no media sections".to_owned()));
+    }
+
+    if !has_attribute(&session, SdpAttributeType::IceUfrag)
+        || !has_attribute(&session, SdpAttributeType::IcePwd)
+    {
+        return Err(malformed("The offer has no ICE credentials".to_owned()));
+    }
+
+    if !has_attribute(&session, SdpAttributeType::Fingerprint) {
+        return Err(malformed("The offer has no DTLS fingerprint".to_owned()));
+    }
+
+    Ok(())
+}
+
+/// Names of the codecs in the SDP in lowercase, retransmissions aside.
+pub fn codecs(sdp: &str) -> Vec<String> {
+    let mut codecs = Vec::new();
+
+    for line in sdp.lines() {
+        if let Some((_, codec)) = rtpmap(line) {
+            if codec != "rtx" && !codecs.contains(&codec) {
+                codecs.push(codec);
+            }
+        }
+    }
+
+    codecs
+}
+
+/// Whether the SDP has a media section for WebRTC data channels.
+pub fn has_data_channel(sdp: &str) -> bool {
+    sdp.lines().any(|line| {
+        line.strip_prefix("m=application ").map_or(false, |media| {
+            media.contains("webrtc-datachannel") || media.contains("DTLS/SCTP")
+        })
+    })
+}
+
+/// Removes the disallowed codecs along with their retransmissions from the media sections.
+/// Returns `None` if the SDP has none of them. A media section left without codecs fails
+/// the offer with `codec_mismatch`.
+pub fn strip_codecs(sdp: &str, disallowed: &[String]) -> Result<Option<String>, AppError> {
+    if disallowed.is_empty() {
+        return Ok(None);
+    }
+
+    let disallowed = disallowed
+        .iter()
+        .map(|codec| codec.to_lowercase())
+        .collect::<HashSet<_>>();
+
+    let eol = if sdp.contains("\r\n") { "\r\n" } else { "\n" };
+    let lines = sdp.lines().collect::<Vec<_>>();
+
+    // Media sections start with their m-lines, the session description precedes them.
+    let starts = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| line.starts_with("m="))
+        .map(|(idx, _)| idx)
+        .collect::<Vec<_>>();
+
+    let session_end = starts.first().copied().unwrap_or(lines.len());
+    let mut output = lines[..session_end]
+        .iter()
+        .map(|line| Cow::Borrowed(*line))
+        .collect::<Vec<_>>();
+    let mut stripped = false;
+
+    for (n, &start) in starts.iter().enumerate() {
+        let end = starts.get(n + 1).copied().unwrap_or(lines.len());
+        let section = &lines[start + 1..end];
+
+        let removed = removed_payload_types(section, &disallowed);
+        stripped |= !removed.is_empty();
+
+        output.push(strip_media_line(lines[start], &removed)?);
+        output.extend(
+            section
+                .iter()
+                .filter(|line| !describes_payload_type(line, &removed))
+                .map(|line| Cow::Borrowed(*line)),
+        );
+    }
+
+    if !stripped {
+        return Ok(None);
+    }
+
+    let mut sdp = output.join(eol);
+    sdp.push_str(eol);
+    Ok(Some(sdp))
+}
+
+fn removed_payload_types(section: &[&str], disallowed: &HashSet<String>) -> HashSet<String> {
+    let mut removed = section
+        .iter()
+        .filter_map(|line| rtpmap(line))
+        .filter(|(_, codec)| disallowed.contains(codec))
+        .map(|(payload_type, _)| payload_type.to_owned())
+        .collect::<HashSet<_>>();
+
+    // Retransmissions refer to the payload type they repeat with `apt`.
+    let rtx = section
+        .iter()
+        .filter_map(|line| {
+            let (payload_type, params) = line.strip_prefix("a=fmtp:")?.split_once(' ')?;
+            let apt = params
+                .split(';')
+                .find_map(|param| param.trim().strip_prefix("apt="))?;
+            removed.contains(apt).then(|| payload_type.to_owned())
+        })
+        .collect::<Vec<_>>();
+
+    removed.extend(rtx);
+    removed
+}
+
+fn strip_media_line<'a>(
+    line: &'a str,
+    removed: &HashSet<String>,
+) -> Result<Cow<'a, str>, AppError> {
+    if removed.is_empty() {
+        return Ok(line.into());
+    }
+
+    let mut tokens = line.split_whitespace();
+    let header = tokens.by_ref().take(3).collect::<Vec<_>>();
+    let formats = tokens
+        .filter(|format| !removed.contains(*format))
+        .collect::<Vec<_>>();
+
+    if formats.is_empty() {
+        let err = anyhow!(
+            "None of the codecs offered for '{}' is allowed",
+            header.first().copied().unwrap_or_default()
+        );
+
+        return Err(AppError::new(AppErrorKind::CodecMismatch, err));
+    }
+
+    Ok(format!("{} {}", header.join(" "), formats.join(" ")).into())
+}
+
+fn describes_payload_type(line: &str, payload_types: &HashSet<String>) -> bool {
+    ["a=rtpmap:", "a=fmtp:", "a=rtcp-fb:"].iter().any(|prefix| {
+        line.strip_prefix(prefix)
+            .and_then(|rest| rest.split_whitespace().next())
+            .map_or(false, |payload_type| payload_types.contains(payload_type))
+    })
+}
+
+/// Payload type and lowercase codec name of an `a=rtpmap` line.
+fn rtpmap(line: &str) -> Option<(&str, String)> {
+    let (payload_type, encoding) = line.strip_prefix("a=rtpmap:")?.split_once(' ')?;
+    let codec = encoding.split('/').next()?.trim().to_lowercase();
+    Some((payload_type, codec))
+}
+
+fn has_attribute(session: &SdpSession, kind: SdpAttributeType) -> bool {
+    session.get_attribute(kind.clone()).is_some()
+        || session
+            .media
+            .iter()
+            .any(|media| media.get_attribute(kind.clone()).is_some())
+}
+
+fn malformed(detail: String) -> AppError {
+    AppError::new(AppErrorKind::MalformedSdp, anyhow!(detail))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OFFER: &str = "v=0\r\n\
+        o=- 20518 0 IN IP4 0.0.0.0\r\n\
+        s=-\r\n\
+        t=0 0\r\n\
+        a=group:BUNDLE audio video\r\n\
+        a=fingerprint:sha-256 19:E2:1C:3B:4B:9F:81:E6:B8:5C:F4:A5:A8:D8:73:04:BB:05:2F:70:9F:04:A9:0E:05:E9:26:33:E8:70:88:A2\r\n\
+        m=audio 9 UDP/TLS/RTP/SAVPF 111\r\n\
+        c=IN IP4 0.0.0.0\r\n\
+        a=mid:audio\r\n\
+        a=sendrecv\r\n\
+        a=ice-ufrag:074c6550\r\n\
+        a=ice-pwd:a28a397a4c3f31747d1ee3474af08a068\r\n\
+        a=rtpmap:111 opus/48000/2\r\n\
+        m=video 9 UDP/TLS/RTP/SAVPF 96 97 98\r\n\
+        c=IN IP4 0.0.0.0\r\n\
+        a=mid:video\r\n\
+        a=sendrecv\r\n\
+        a=rtpmap:96 H264/90000\r\n\
+        a=fmtp:96 profile-level-id=4d0028;packetization-mode=1\r\n\
+        a=rtcp-fb:96 nack\r\n\
+        a=rtpmap:97 rtx/90000\r\n\
+        a=fmtp:97 apt=96\r\n\
+        a=rtpmap:98 VP8/90000\r\n";
+
+    #[test]
+    fn accept_offer() {
+        validate_offer(OFFER).expect("Valid offer rejected");
+        assert_eq!(codecs(OFFER), vec!["opus", "h264", "vp8"]);
+    }
+
+    #[test]
+    fn reject_malformed_offer() {
+        let err = validate_offer("hello").expect_err("Garbage accepted");
+        assert_eq!(err.kind(), "malformed_sdp");
+
+        let no_media = OFFER.split("m=audio").next().unwrap();
+        let err = validate_offer(no_media).expect_err("Offer without media accepted");
+        assert_eq!(err.detail(), "The offer has no media sections");
+
+        let no_ice = OFFER.replace("a=ice-ufrag:074c6550\r\n", "");
+        let err = validate_offer(&no_ice).expect_err("Offer without ICE accepted");
+        assert_eq!(err.detail(), "The offer has no ICE credentials");
+    }
+
+    #[test]
+    fn strip_disallowed_codecs() {
+        assert!(strip_codecs(OFFER, &[]).unwrap().is_none());
+        assert!(strip_codecs(OFFER, &["av1".to_owned()]).unwrap().is_none());
+
+        let sdp = strip_codecs(OFFER, &["h264".to_owned()])
+            .unwrap()
+            .expect("Codec not stripped");
+
+        assert!(sdp.contains("m=video 9 UDP/TLS/RTP/SAVPF 98\r\n"));
+        assert!(!sdp.contains("a=rtpmap:96"));
+        assert!(!sdp.contains("a=fmtp:96"));
+        assert!(!sdp.contains("a=rtcp-fb:96"));
+        assert!(!sdp.contains("a=rtpmap:97"));
+        assert!(sdp.contains("a=rtpmap:111 opus/48000/2\r\n"));
+        assert_eq!(codecs(&sdp), vec!["opus", "vp8"]);
+        validate_offer(&sdp).expect("Stripped offer rejected");
+    }
+
+    #[test]
+    fn detect_data_channel() {
+        assert!(!has_data_channel(OFFER));
+
+        let offer = format!(
+            "{}m=application 9 UDP/DTLS/SCTP webrtc-datachannel\r\n\
+             a=mid:data\r\n\
+             a=sctp-port:5000\r\n",
+            OFFER
+        );
+
+        assert!(has_data_channel(&offer));
+        validate_offer(&offer).expect("Offer with data channel rejected");
+        assert_eq!(codecs(&offer), vec!["opus", "h264", "vp8"]);
+    }
+
+    #[test]
+    fn reject_offer_without_allowed_codecs() {
+        let err = strip_codecs(OFFER, &["OPUS".to_owned()]).expect_err("Audio left without codecs");
+        assert_eq!(err.kind(), "codec_mismatch");
+    }
+}
@@ -1,3 +1,5 @@
+use std::fmt;
+
 use axum::response::IntoResponse;
 use chrono::{DateTime, Duration, Utc};
 use futures::{stream, Stream};
@@ -12,12 +14,61 @@ use svc_agent::{
     Addressable, AgentId, Authenticable,
 };
 
-use crate::app::{endpoint::helpers, error::ErrorExt};
+use crate::app::{endpoint::helpers, error::ErrorExt, pseudonym::Pseudonyms};
 
 use super::error;
 
+/// A notification to publish along with the response. Broadcast events keep their payload as
+/// JSON until publishing so it still can be rewritten.
+pub enum Notification {
+    Event {
+        path: String,
+        payload: Value,
+        props: Box<OutgoingEventProperties>,
+    },
+    Message(Box<dyn IntoPublishableMessage + Send + Sync + 'static>),
+}
+
+impl fmt::Debug for Notification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Notification::Event { path, payload, .. } => f
+                .debug_struct("Event")
+                .field("path", path)
+                .field("payload", payload)
+                .finish(),
+            // Publishable messages can't be looked into.
+            Notification::Message(_) => f.write_str("Message"),
+        }
+    }
+}
+
+impl Notification {
+    pub fn into_message(self) -> Box<dyn IntoPublishableMessage + Send + Sync + 'static> {
+        match self {
+            Notification::Event {
+                path,
+                payload,
+                props,
+            } => Box::new(OutgoingEvent::broadcast(payload, *props, &path)),
+            Notification::Message(message) => message,
+        }
+    }
+
+    /// Room events are seen by every participant so they never carry real ids of the opted-in
+    /// audiences.
+    pub fn pseudonymize(&mut self, pseudonyms: &Pseudonyms) {
+        if let Notification::Event { path, payload, .. } = self {
+            if path.starts_with("rooms/") {
+                pseudonyms.apply(payload);
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
 pub struct Response {
-    notifications: Vec<Box<dyn IntoPublishableMessage + Send + Sync + 'static>>,
+    notifications: Vec<Notification>,
     status: StatusCode,
     start_timestamp: DateTime<Utc>,
     authz_time: Option<Duration>,
@@ -40,6 +91,42 @@ impl Response {
         }
     }
 
+    /// Rewrites the payload in place, e.g. to adapt its shape for the client.
+    pub fn map_payload(mut self, f: impl FnOnce(&mut Value)) -> Self {
+        if let Ok(ref mut payload) = self.payload {
+            f(payload);
+        }
+
+        self
+    }
+
+    /// Replaces agent ids of the opted-in audiences in room events and, when the reader must not
+    /// see them, in the payload too.
+    pub fn pseudonymize(mut self, pseudonyms: &Pseudonyms, hide_from_reader: bool) -> Self {
+        if !pseudonyms.is_enabled() {
+            return self;
+        }
+
+        for notification in &mut self.notifications {
+            notification.pseudonymize(pseudonyms);
+        }
+
+        if hide_from_reader {
+            self.map_payload(|payload| pseudonyms.apply(payload))
+        } else {
+            self
+        }
+    }
+
+    /// Size of the serialized payload in bytes.
+    pub fn payload_size(&self) -> usize {
+        self.payload
+            .as_ref()
+            .ok()
+            .and_then(|p| serde_json::to_vec(p).ok())
+            .map_or(0, |p| p.len())
+    }
+
     pub fn into_mqtt_messages(
         self,
         reqp: &IncomingRequestProperties,
@@ -51,7 +138,12 @@ impl Response {
         >,
         error::Error,
     > {
-        let mut notifications = self.notifications;
+        let mut notifications = self
+            .notifications
+            .into_iter()
+            .map(Notification::into_message)
+            .collect::<Vec<_>>();
+
         if self.status != StatusCode::NO_CONTENT {
             let payload = self.payload.error(error::ErrorKind::InvalidPayload)?;
             let response = helpers::build_response(
@@ -66,6 +158,17 @@ impl Response {
         Ok(Box::new(stream::iter(notifications)))
     }
 
+    /// Splits the response for transports that deliver the payload and notifications apart.
+    pub fn into_parts(
+        self,
+    ) -> (
+        StatusCode,
+        Result<Value, serde_json::Error>,
+        Vec<Notification>,
+    ) {
+        (self.status, self.payload, self.notifications)
+    }
+
     pub fn add_notification(
         &mut self,
         label: &'static str,
@@ -75,20 +178,40 @@ impl Response {
     ) {
         let timing = ShortTermTimingProperties::until_now(start_timestamp);
         let props = OutgoingEventProperties::new(label, timing);
-        self.notifications
-            .push(Box::new(OutgoingEvent::broadcast(payload, props, path)))
+
+        let notification = match serde_json::to_value(&payload) {
+            Ok(payload) => Notification::Event {
+                path: path.to_owned(),
+                payload,
+                props: Box::new(props),
+            },
+            Err(_) => {
+                Notification::Message(Box::new(OutgoingEvent::broadcast(payload, props, path)))
+            }
+        };
+
+        self.notifications.push(notification)
     }
 
     pub fn add_message(
         &mut self,
         message: Box<dyn IntoPublishableMessage + Send + Sync + 'static>,
     ) {
-        self.notifications.push(message)
+        self.notifications.push(Notification::Message(message))
     }
 
     pub fn set_authz_time(&mut self, authz_time: Duration) {
         self.authz_time = Some(authz_time);
     }
+
+    /// Responds over HTTP with the given headers and body instead of the JSON payload,
+    /// e.g. SDP for WHIP. Notifications get published as usual.
+    pub fn into_raw_response(self, raw: impl IntoResponse) -> axum::response::Response {
+        let mut r = (self.status, raw).into_response();
+        r.extensions_mut().insert(self.notifications);
+
+        r
+    }
 }
 
 impl IntoResponse for Response {
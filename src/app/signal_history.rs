@@ -0,0 +1,165 @@
+use std::net::IpAddr;
+
+use serde_json::Value as JsonValue;
+use svc_agent::AgentId;
+use tracing::warn;
+
+use crate::{
+    app::context::GlobalContext,
+    backend::janus::client::IceCandidateSdp,
+    db::{self, rtc_signal_artifact::Kind},
+};
+
+/// Stores the artifact in the background unless the history is not configured or the room has
+/// debugging off.
+pub fn record<C: GlobalContext + ?Sized>(
+    context: &C,
+    room_id: db::room::Id,
+    rtc_id: db::rtc::Id,
+    agent_id: &AgentId,
+    kind: Kind,
+    body: &str,
+) {
+    let config = match &context.config().rtc_signal_history {
+        Some(config) => config.clone(),
+        None => return,
+    };
+
+    let body = if config.redact_ips {
+        redact_ips(body)
+    } else {
+        body.to_owned()
+    };
+
+    let db = context.db().clone();
+    let agent_id = agent_id.to_owned();
+
+    tokio::spawn(async move {
+        let result = async {
+            let mut conn = db.acquire().await?;
+
+            db::rtc_signal_artifact::InsertQuery::new(room_id, rtc_id, &agent_id, kind, &body)
+                .execute(config.max_artifacts, &mut conn)
+                .await
+        };
+
+        if let Err(err) = result.await {
+            warn!(?err, %rtc_id, "Failed to store rtc signal artifact");
+        }
+    });
+}
+
+/// Stores the SDP of an answer carried by the response.
+pub fn record_answer<C: GlobalContext + ?Sized>(
+    context: &C,
+    room_id: db::room::Id,
+    rtc_id: db::rtc::Id,
+    agent_id: &AgentId,
+    jsep: Option<&JsonValue>,
+) {
+    if let Some(sdp) = jsep.and_then(|j| j.get("sdp")).and_then(|s| s.as_str()) {
+        record(context, room_id, rtc_id, agent_id, Kind::Answer, sdp);
+    }
+}
+
+/// Stores candidate lines of a trickle request one per line.
+pub fn record_candidates<C: GlobalContext + ?Sized>(
+    context: &C,
+    room_id: db::room::Id,
+    rtc_id: db::rtc::Id,
+    agent_id: &AgentId,
+    candidates: &IceCandidateSdp,
+) {
+    if context.config().rtc_signal_history.is_none() {
+        return;
+    }
+
+    let summary = candidates_summary(candidates);
+    record(
+        context,
+        room_id,
+        rtc_id,
+        agent_id,
+        Kind::Candidates,
+        &summary,
+    );
+}
+
+fn candidates_summary(candidates: &IceCandidateSdp) -> String {
+    let value = serde_json::to_value(candidates).unwrap_or_default();
+
+    let items = match value {
+        JsonValue::Array(items) => items,
+        item => vec![item],
+    };
+
+    items
+        .iter()
+        .map(
+            |item| match item.get("candidate").and_then(|c| c.as_str()) {
+                Some(candidate) => candidate.to_owned(),
+                None if item.get("completed").is_some() => "completed".to_owned(),
+                None => "null".to_owned(),
+            },
+        )
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Replaces every IP address in SDP or candidate lines with an unspecified one of its family.
+fn redact_ips(text: &str) -> String {
+    text.split('\n')
+        .map(|line| {
+            line.split(' ')
+                .map(
+                    |token| match token.trim_end_matches('\r').parse::<IpAddr>() {
+                        Ok(IpAddr::V4(_)) => {
+                            token.replacen(token.trim_end_matches('\r'), "0.0.0.0", 1)
+                        }
+                        Ok(IpAddr::V6(_)) => token.replacen(token.trim_end_matches('\r'), "::", 1),
+                        Err(_) => token.to_owned(),
+                    },
+                )
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redact_sdp_addresses() {
+        let sdp = "v=0\r\no=- 1 2 IN IP4 192.168.1.10\r\nc=IN IP6 2001:db8::1\r\n\
+            a=candidate:1 1 udp 2122260223 10.0.0.5 54321 typ srflx raddr 172.16.0.1 rport 9\r\n\
+            a=candidate:2 1 udp 2122260223 abc.local 54322 typ host\r\n";
+
+        assert_eq!(
+            redact_ips(sdp),
+            "v=0\r\no=- 1 2 IN IP4 0.0.0.0\r\nc=IN IP6 ::\r\n\
+            a=candidate:1 1 udp 2122260223 0.0.0.0 54321 typ srflx raddr 0.0.0.0 rport 9\r\n\
+            a=candidate:2 1 udp 2122260223 abc.local 54322 typ host\r\n"
+        );
+    }
+
+    #[test]
+    fn summarize_candidates() {
+        let candidates = serde_json::from_value::<IceCandidateSdp>(serde_json::json!([
+            {
+                "sdpMid": "0",
+                "sdpMLineIndex": 0,
+                "candidate": "candidate:1 1 udp 2122260223 10.0.0.5 54321 typ host",
+            },
+            { "completed": true },
+        ]))
+        .expect("Failed to parse candidates");
+
+        assert_eq!(
+            candidates_summary(&candidates),
+            "candidate:1 1 udp 2122260223 10.0.0.5 54321 typ host\ncompleted"
+        );
+    }
+}
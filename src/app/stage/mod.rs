@@ -2,6 +2,7 @@ use crate::{
     app::{
         context::GlobalContext,
         error::Error,
+        stage::room_bulk_close::RoomBulkClose,
         stage::video_group::{
             VideoGroupSendMqttNotification, VideoGroupSendNatsNotification,
             VideoGroupUpdateJanusConfig,
@@ -12,7 +13,9 @@ use crate::{
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use svc_events::EventId;
+use uuid::Uuid;
 
+pub mod room_bulk_close;
 pub mod video_group;
 
 #[allow(clippy::enum_variant_names)]
@@ -22,6 +25,7 @@ pub enum AppStage {
     VideoGroupUpdateJanusConfig(VideoGroupUpdateJanusConfig),
     VideoGroupSendNatsNotification(VideoGroupSendNatsNotification),
     VideoGroupSendMqttNotification(VideoGroupSendMqttNotification),
+    RoomBulkClose(RoomBulkClose),
 }
 
 #[async_trait::async_trait]
@@ -34,6 +38,16 @@ impl StageHandle for AppStage {
             AppStage::VideoGroupUpdateJanusConfig(s) => s.handle(ctx, id).await,
             AppStage::VideoGroupSendNatsNotification(s) => s.handle(ctx, id).await,
             AppStage::VideoGroupSendMqttNotification(s) => s.handle(ctx, id).await,
+            AppStage::RoomBulkClose(s) => s.handle(ctx, id).await,
+        }
+    }
+
+    fn classroom_id(&self) -> Option<Uuid> {
+        match self {
+            AppStage::VideoGroupUpdateJanusConfig(s) => Some(s.classroom_id()),
+            AppStage::VideoGroupSendNatsNotification(s) => Some(s.classroom_id),
+            AppStage::VideoGroupSendMqttNotification(_) => None,
+            AppStage::RoomBulkClose(_) => None,
         }
     }
 }
@@ -0,0 +1,149 @@
+use crate::{
+    app::{
+        context::GlobalContext,
+        endpoint::room::{close_notifications, close_room},
+        error::{Error as AppError, ErrorExt, ErrorKind},
+        stage::AppStage,
+    },
+    db::{self, room::FindQueryable},
+    outbox::{error::StageError, StageHandle},
+};
+use anyhow::anyhow;
+use async_trait::async_trait;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use svc_agent::AgentId;
+use svc_events::EventId;
+use tracing::{error, info};
+
+pub const ENTITY_TYPE: &str = "room_bulk_close";
+pub const STARTED_OPERATION: &str = "started";
+
+/// Closes the next batch of rooms matching the job. Rooms are taken in id order so the ones
+/// failed to close aren't retried over and over.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RoomBulkClose {
+    pub job_id: db::room_bulk_close::Id,
+    #[serde(default)]
+    pub after: Option<db::room::Id>,
+}
+
+impl RoomBulkClose {
+    pub fn init(job_id: db::room_bulk_close::Id) -> AppStage {
+        AppStage::RoomBulkClose(Self {
+            job_id,
+            after: None,
+        })
+    }
+}
+
+#[async_trait]
+impl StageHandle for RoomBulkClose {
+    type Context = Arc<dyn GlobalContext + Send + Sync>;
+    type Stage = AppStage;
+
+    async fn handle(
+        &self,
+        ctx: &Self::Context,
+        _id: &EventId,
+    ) -> Result<Option<Self::Stage>, StageError> {
+        let config = ctx.config().room_bulk_close.clone();
+
+        let (job, room_ids) = {
+            let mut conn = ctx.get_conn().await?;
+
+            let job = db::room_bulk_close::find(self.job_id, &mut conn)
+                .await
+                .error(ErrorKind::DbQueryFailed)?
+                .ok_or_else(|| anyhow!("Room bulk close job not found"))
+                .error(ErrorKind::DbQueryFailed)?;
+
+            let room_ids =
+                db::room_bulk_close::list_rooms(job.id(), self.after, config.batch_size, &mut conn)
+                    .await
+                    .error(ErrorKind::DbQueryFailed)?;
+
+            (job, room_ids)
+        };
+
+        let (mut closed, mut failed) = (0, 0);
+
+        for (idx, room_id) in room_ids.iter().enumerate() {
+            if idx > 0 {
+                tokio::time::sleep(config.pause).await;
+            }
+
+            match close_and_notify(ctx.as_ref(), *room_id, job.created_by()).await {
+                Ok(true) => closed += 1,
+                Ok(false) => (),
+                Err(err) => {
+                    failed += 1;
+                    error!(%room_id, job_id = %job.id(), ?err, "Failed to close room in bulk");
+                    err.notify_sentry();
+                }
+            }
+        }
+
+        let finished = (room_ids.len() as i64) < config.batch_size;
+
+        {
+            let mut conn = ctx.get_conn().await?;
+
+            db::room_bulk_close::add_progress(job.id(), closed, failed, finished, &mut conn)
+                .await
+                .error(ErrorKind::DbQueryFailed)?;
+        }
+
+        if finished {
+            info!(job_id = %job.id(), "Room bulk close finished");
+            return Ok(None);
+        }
+
+        let next_stage = AppStage::RoomBulkClose(Self {
+            job_id: self.job_id,
+            after: room_ids.last().copied(),
+        });
+
+        Ok(Some(next_stage))
+    }
+}
+
+/// Closes the room the same way `room.close` does. Returns `false` when the room got closed
+/// by someone else in the meantime.
+async fn close_and_notify(
+    ctx: &(dyn GlobalContext + Send + Sync),
+    room_id: db::room::Id,
+    agent_id: &AgentId,
+) -> Result<bool, AppError> {
+    let (room, summary) = {
+        let mut conn = ctx.get_conn().await?;
+
+        let room = db::room::FindQuery::new(room_id)
+            .execute(&mut conn)
+            .await?
+            .ok_or_else(|| anyhow!("Room not found"))
+            .error(ErrorKind::RoomNotFound)?;
+
+        if room.time().is_closed_at(Utc::now()) {
+            return Ok(false);
+        }
+
+        close_room(room.id(), agent_id, &ctx.config().room_upload, &mut conn).await?
+    };
+
+    let pseudonyms = ctx.pseudonyms();
+
+    for (label, path, mut payload) in close_notifications(&room, summary) {
+        if path.starts_with("rooms/") {
+            pseudonyms.apply(&mut payload);
+        }
+
+        ctx.mqtt_client()
+            .lock()
+            .publish_payload(label, &path, payload)
+            .error(ErrorKind::MqttPublishFailed)?;
+    }
+
+    Ok(true)
+}
@@ -26,6 +26,9 @@ pub struct VideoGroupUpdateJanusConfig {
     event: Event,
     backend_id: AgentId,
     configs: Vec<UpdateReaderConfigRequestBodyConfigItem>,
+    /// Size of the whole config matrix `configs` is a delta of.
+    #[serde(default)]
+    full_size: usize,
 }
 
 impl VideoGroupUpdateJanusConfig {
@@ -35,6 +38,7 @@ impl VideoGroupUpdateJanusConfig {
         room_id: db::room::Id,
         backend_id: AgentId,
         configs: Vec<UpdateReaderConfigRequestBodyConfigItem>,
+        full_size: usize,
     ) -> AppStage {
         let stage = Self {
             room_id,
@@ -42,10 +46,15 @@ impl VideoGroupUpdateJanusConfig {
             event,
             backend_id,
             configs,
+            full_size,
         };
 
         AppStage::VideoGroupUpdateJanusConfig(stage)
     }
+
+    pub fn classroom_id(&self) -> Uuid {
+        self.classroom_id
+    }
 }
 
 #[async_trait]
@@ -58,6 +67,25 @@ impl StageHandle for VideoGroupUpdateJanusConfig {
         ctx: &Self::Context,
         _id: &EventId,
     ) -> Result<Option<Self::Stage>, StageError> {
+        if !self.configs.is_empty() {
+            self.push_configs(ctx).await?;
+        }
+
+        let next_stage = AppStage::VideoGroupSendNatsNotification(VideoGroupSendNatsNotification {
+            room_id: self.room_id.to_owned(),
+            classroom_id: self.classroom_id.to_owned(),
+            event: self.event.to_owned(),
+        });
+
+        Ok(Some(next_stage))
+    }
+}
+
+impl VideoGroupUpdateJanusConfig {
+    async fn push_configs(
+        &self,
+        ctx: &Arc<dyn GlobalContext + Send + Sync>,
+    ) -> Result<(), StageError> {
         let mut conn = ctx.get_conn().await?;
 
         let janus_backend = db::janus_backend::FindQuery::new(&self.backend_id)
@@ -67,10 +95,29 @@ impl StageHandle for VideoGroupUpdateJanusConfig {
             .ok_or_else(|| anyhow!("Janus backend not found"))
             .error(ErrorKind::BackendNotFound)?;
 
+        // Hosts may have changed since the stage was created but their video is received
+        // by everyone in any case.
+        let host_rtc_ids = db::room_host::list_rtc_ids(self.room_id, &mut conn)
+            .await
+            .error(ErrorKind::DbQueryFailed)?;
+
+        let configs = self
+            .configs
+            .iter()
+            .cloned()
+            .map(|mut config| {
+                if host_rtc_ids.contains(&config.stream_id) {
+                    config.receive_video = true;
+                }
+
+                config
+            })
+            .collect();
+
         let request = UpdateReaderConfigRequest {
             session_id: janus_backend.session_id(),
             handle_id: janus_backend.handle_id(),
-            body: UpdateReaderConfigRequestBody::new(self.configs.clone()),
+            body: UpdateReaderConfigRequestBody::new(configs),
         };
 
         ctx.janus_clients()
@@ -81,12 +128,9 @@ impl StageHandle for VideoGroupUpdateJanusConfig {
             .context("Reader update")
             .error(ErrorKind::BackendRequestFailed)?;
 
-        let next_stage = AppStage::VideoGroupSendNatsNotification(VideoGroupSendNatsNotification {
-            room_id: self.room_id.to_owned(),
-            classroom_id: self.classroom_id.to_owned(),
-            event: self.event.to_owned(),
-        });
+        ctx.metrics()
+            .observe_reader_config_push(self.configs.len(), self.full_size);
 
-        Ok(Some(next_stage))
+        Ok(())
     }
 }
@@ -0,0 +1,365 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context as AnyhowContext};
+use serde::{Deserialize, Serialize};
+use svc_nats_client::{
+    consumer::{FailureKind, HandleMessageFailure},
+    Message,
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    app::{context::GlobalContext, endpoint::system::RECORD_NAME_SUFFIX},
+    db::{self, room::FindQueryable},
+};
+
+const INTEGRITY_FAILED_LABEL: &str = "recording.integrity_failed";
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// S3 event notification as sent by the storage, e.g. by MinIO's NATS target.
+#[derive(Debug, Deserialize)]
+struct Notification {
+    #[serde(rename = "Records", default)]
+    records: Vec<Record>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Record {
+    event_name: String,
+    s3: S3Entity,
+}
+
+#[derive(Debug, Deserialize)]
+struct S3Entity {
+    bucket: S3Bucket,
+    object: S3Object,
+}
+
+#[derive(Debug, Deserialize)]
+struct S3Bucket {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct S3Object {
+    key: String,
+    #[serde(default)]
+    size: i64,
+    #[serde(rename = "eTag", default)]
+    e_tag: Option<String>,
+}
+
+impl Record {
+    // AWS names the event `ObjectCreated:Put` while MinIO prefixes it with `s3:`.
+    fn is_object_created(&self) -> bool {
+        self.event_name
+            .trim_start_matches("s3:")
+            .starts_with("ObjectCreated:")
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+pub async fn handle_message(
+    ctx: Arc<dyn GlobalContext + Send + Sync>,
+    message: Arc<Message>,
+) -> Result<(), HandleMessageFailure<anyhow::Error>> {
+    let notification = serde_json::from_slice::<Notification>(&message.payload)
+        .context("failed to parse storage notification")
+        .permanent()?;
+
+    for record in notification
+        .records
+        .iter()
+        .filter(|r| r.is_object_created())
+    {
+        verify_object(ctx.as_ref(), &record.s3.bucket.name, &record.s3.object).await?;
+    }
+
+    Ok(())
+}
+
+async fn verify_object<C: GlobalContext + ?Sized>(
+    ctx: &C,
+    bucket: &str,
+    object: &S3Object,
+) -> Result<(), HandleMessageFailure<anyhow::Error>> {
+    let upload = &ctx.config().upload;
+    let known_bucket = upload
+        .shared
+        .values()
+        .chain(upload.owned.values())
+        .any(|c| c.bucket == bucket);
+
+    // Composite recordings, dumps and other buckets' objects aren't matched against rows.
+    let rtc_id = match recording_rtc_id(&object.key) {
+        Some(rtc_id) if known_bucket => rtc_id,
+        _ => return Ok(()),
+    };
+
+    let mut conn = ctx
+        .get_conn()
+        .await
+        .map_err(|err| anyhow!(err))
+        .transient()?;
+
+    let recording =
+        db::recording::set_verified(rtc_id, object.size, object.e_tag.as_deref(), &mut conn)
+            .await
+            .context("failed to update recording")
+            .transient()?;
+
+    match recording {
+        None => {
+            ctx.metrics().storage_object_unknown.inc();
+            warn!(%rtc_id, bucket, key = %object.key, "Uploaded object has no recording");
+        }
+        Some(_) if object.size == 0 => {
+            ctx.metrics().storage_object_empty.inc();
+            warn!(%rtc_id, bucket, key = %object.key, "Uploaded recording object is empty");
+        }
+        Some(recording) if recording.checksum_mismatch() => {
+            let room = db::room::FindByRtcIdQuery::new(rtc_id)
+                .execute(&mut conn)
+                .await
+                .context("failed to find room")
+                .transient()?;
+
+            if let Some(room) = room {
+                notify_integrity_failed(ctx, &room, &recording);
+            }
+        }
+        Some(_) => {
+            info!(%rtc_id, size = object.size, "Verified recording object");
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+struct IntegrityFailed<'a> {
+    room_id: db::room::Id,
+    rtc_id: db::rtc::Id,
+    checksum: Option<&'a str>,
+    object_checksum: Option<&'a str>,
+}
+
+/// Tells the room's audience that the object in the storage isn't what the backend uploaded.
+/// Whichever of the upload confirmation and the storage notification comes last reports it.
+pub fn notify_integrity_failed<C: GlobalContext + ?Sized>(
+    ctx: &C,
+    room: &db::room::Object,
+    recording: &db::recording::Object,
+) {
+    ctx.metrics().storage_object_checksum_mismatch.inc();
+
+    let payload = IntegrityFailed {
+        room_id: room.id(),
+        rtc_id: recording.rtc_id(),
+        checksum: recording.checksum(),
+        object_checksum: recording.object_checksum.as_deref(),
+    };
+
+    warn!(
+        ?payload,
+        "Uploaded recording object doesn't match its checksum"
+    );
+
+    let topic = format!("audiences/{}/events", room.audience());
+
+    let result = ctx.mqtt_client().lock().publish_payload(
+        INTEGRITY_FAILED_LABEL,
+        &topic,
+        serde_json::json!(payload),
+    );
+
+    if let Err(err) = result {
+        error!(?err, rtc_id = %recording.rtc_id(), "Failed to publish integrity failure");
+    }
+}
+
+/// Parses `[<classroom_id>/]<rtc_id>.source.webm`. Keys in notifications are URL-encoded.
+fn recording_rtc_id(key: &str) -> Option<db::rtc::Id> {
+    let key = key.replace("%2F", "/");
+    let name = key.rsplit('/').next()?;
+    name.strip_suffix(RECORD_NAME_SUFFIX)?.parse().ok()
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::{db::TestDb, prelude::*};
+
+    #[test]
+    fn parse_recording_key() {
+        let rtc_id = db::rtc::Id::random();
+
+        let key = format!("{rtc_id}.source.webm");
+        assert_eq!(recording_rtc_id(&key), Some(rtc_id));
+
+        let key = format!("{}%2F{rtc_id}.source.webm", db::room::Id::random());
+        assert_eq!(recording_rtc_id(&key), Some(rtc_id));
+
+        let key = format!("{rtc_id}.composite.webm");
+        assert_eq!(recording_rtc_id(&key), None);
+    }
+
+    #[test]
+    fn parse_notification() {
+        let json = serde_json::json!({
+            "EventName": "s3:ObjectCreated:Put",
+            "Key": "origin.webinar.example.net/rtc.source.webm",
+            "Records": [{
+                "eventName": "s3:ObjectCreated:Put",
+                "s3": {
+                    "bucket": { "name": "origin.webinar.example.net" },
+                    "object": { "key": "rtc.source.webm", "size": 1024 }
+                }
+            }]
+        });
+
+        let notification: Notification =
+            serde_json::from_value(json).expect("Failed to parse notification");
+
+        assert_eq!(notification.records.len(), 1);
+        assert!(notification.records[0].is_object_created());
+        assert_eq!(notification.records[0].s3.object.size, 1024);
+    }
+
+    #[sqlx::test]
+    async fn verify_recording_object(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let mut conn = db.get_conn().await;
+
+        let rtc = shared_helpers::insert_rtc(&mut conn).await;
+        shared_helpers::insert_recording(&mut conn, &rtc).await;
+
+        let context = TestContext::new(db, TestAuthz::new()).await;
+        let bucket = context
+            .config()
+            .upload
+            .shared
+            .values()
+            .next()
+            .expect("No upload config")
+            .bucket
+            .clone();
+
+        let object = |size| S3Object {
+            key: format!("{}.source.webm", rtc.id()),
+            size,
+            e_tag: None,
+        };
+
+        verify_object(&context, &bucket, &object(0))
+            .await
+            .expect("Failed to verify object");
+
+        let recording = db::recording::FindQuery::new(rtc.id())
+            .execute(&mut conn)
+            .await
+            .expect("Failed to find recording")
+            .expect("Recording not found");
+
+        assert_eq!(recording.verified_at(), None);
+        assert_eq!(recording.object_size, Some(0));
+
+        verify_object(&context, &bucket, &object(1024))
+            .await
+            .expect("Failed to verify object");
+
+        let recording = db::recording::FindQuery::new(rtc.id())
+            .execute(&mut conn)
+            .await
+            .expect("Failed to find recording")
+            .expect("Recording not found");
+
+        assert!(recording.verified_at().is_some());
+    }
+
+    async fn find_recording(
+        conn: &mut sqlx::PgConnection,
+        rtc_id: db::rtc::Id,
+    ) -> db::recording::Object {
+        db::recording::FindQuery::new(rtc_id)
+            .execute(conn)
+            .await
+            .expect("Failed to find recording")
+            .expect("Recording not found")
+    }
+
+    #[sqlx::test]
+    async fn verify_corrupted_recording_object(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let mut conn = db.get_conn().await;
+
+        let rtc = shared_helpers::insert_rtc(&mut conn).await;
+        shared_helpers::insert_recording(&mut conn, &rtc).await;
+
+        db::recording::UpdateQuery::new(rtc.id())
+            .status(db::recording::Status::Ready)
+            .checksum(Some("9e107d9d372bb6826bd81d3542a419d6".to_owned()))
+            .execute(&mut conn)
+            .await
+            .expect("Failed to update recording");
+
+        let context = TestContext::new(db, TestAuthz::new()).await;
+        let bucket = context
+            .config()
+            .upload
+            .shared
+            .values()
+            .next()
+            .expect("No upload config")
+            .bucket
+            .clone();
+
+        let object = |e_tag: &str| S3Object {
+            key: format!("{}.source.webm", rtc.id()),
+            size: 1024,
+            e_tag: Some(e_tag.to_owned()),
+        };
+
+        verify_object(
+            &context,
+            &bucket,
+            &object("\"9E107D9D372BB6826BD81D3542A419D6\""),
+        )
+        .await
+        .expect("Failed to verify object");
+
+        assert!(!find_recording(&mut conn, rtc.id())
+            .await
+            .checksum_mismatch());
+
+        verify_object(
+            &context,
+            &bucket,
+            &object("e4d909c290d0fb1ca068ffaddf22cbd0"),
+        )
+        .await
+        .expect("Failed to verify object");
+
+        assert!(find_recording(&mut conn, rtc.id())
+            .await
+            .checksum_mismatch());
+
+        // ETags of multipart uploads are not comparable.
+        verify_object(
+            &context,
+            &bucket,
+            &object("e4d909c290d0fb1ca068ffaddf22cbd0-3"),
+        )
+        .await
+        .expect("Failed to verify object");
+
+        assert!(!find_recording(&mut conn, rtc.id())
+            .await
+            .checksum_mismatch());
+    }
+}
@@ -0,0 +1,115 @@
+use anyhow::Context as AnyhowContext;
+use chrono::{serde::ts_seconds, DateTime, Utc};
+use futures::StreamExt;
+use serde::Serialize;
+use svc_agent::mqtt::{OutgoingEvent, OutgoingEventProperties, ShortTermTimingProperties};
+use tracing::{error, warn};
+
+use crate::{
+    app::{
+        context::{AppContext, GlobalContext, MessageContext},
+        dispatcher::Dispatcher,
+        error::Error as AppError,
+        handle_id::HandleId,
+        API_VERSION,
+    },
+    backend::janus::{self, client::create_handle::OpaqueId},
+    config::StreamLimitConfig,
+    db::{self, janus_rtc_stream::ExpiredStream},
+};
+
+const STREAM_EXPIRED_LABEL: &str = "rtc_stream.expired";
+
+#[derive(Debug, Serialize)]
+struct StreamExpired {
+    room_id: db::room::Id,
+    rtc_id: db::rtc::Id,
+    #[serde(with = "ts_seconds")]
+    started_at: DateTime<Utc>,
+    max_duration: i32,
+}
+
+/// Periodically stops streams of owned rtcs lasting longer than their room allows.
+pub async fn start_enforcer(ctx: AppContext, dispatcher: Dispatcher, config: StreamLimitConfig) {
+    loop {
+        if let Err(err) = enforce(&ctx, &dispatcher).await {
+            error!(?err, "Failed to enforce stream max duration");
+        }
+
+        tokio::time::sleep(config.interval).await;
+    }
+}
+
+async fn enforce(ctx: &AppContext, dispatcher: &Dispatcher) -> anyhow::Result<()> {
+    let streams = {
+        let mut conn = ctx.get_conn().await?;
+
+        db::janus_rtc_stream::expired_in_owned_rooms(&mut conn)
+            .await
+            .context("Failed to find expired streams")?
+    };
+
+    for stream in streams {
+        let stream_id = stream.id;
+
+        if let Err(err) = stop(ctx, dispatcher, stream).await {
+            error!(?err, %stream_id, "Failed to stop expired stream");
+        }
+    }
+
+    Ok(())
+}
+
+async fn stop(
+    ctx: &AppContext,
+    dispatcher: &Dispatcher,
+    stream: ExpiredStream,
+) -> Result<(), AppError> {
+    warn!(
+        room_id = %stream.room_id,
+        rtc_id = %stream.rtc_id,
+        max_duration = stream.max_duration,
+        "Stopping the stream that has outlived the room's max duration"
+    );
+
+    let mut msg_ctx = ctx.start_message();
+
+    let opaque_id = OpaqueId {
+        stream_id: stream.id,
+        room_id: stream.room_id,
+    };
+
+    let handle_id = HandleId::new(
+        stream.id,
+        stream.rtc_id,
+        stream.handle_id,
+        stream.session_id,
+        stream.backend_id.clone(),
+    );
+
+    // Stopping closes the stream's time range right now so the recording segment ends where
+    // the media actually stopped. Another instance picking the stream at the same time fails
+    // to detach it and ends up just republishing the update.
+    let messages = janus::detach_handle(&mut msg_ctx, opaque_id, handle_id)
+        .await?
+        .collect::<Vec<_>>()
+        .await;
+
+    for message in messages {
+        dispatcher.dispatch(None, message);
+    }
+
+    let payload = StreamExpired {
+        room_id: stream.room_id,
+        rtc_id: stream.rtc_id,
+        started_at: stream.started_at,
+        max_duration: stream.max_duration,
+    };
+
+    let timing = ShortTermTimingProperties::until_now(msg_ctx.start_timestamp());
+    let props = OutgoingEventProperties::new(STREAM_EXPIRED_LABEL, timing);
+    let event = OutgoingEvent::multicast(payload, props, &stream.sent_by, API_VERSION);
+
+    dispatcher.dispatch(None, Box::new(event));
+    Ok(())
+}
@@ -0,0 +1,283 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    ops::{Deref, DerefMut},
+    sync::{Arc, Mutex, PoisonError},
+    time::Instant,
+};
+
+use anyhow::Context;
+use prometheus::{IntCounterVec, Opts, Registry};
+use sqlx::{pool::PoolConnection, Connection, Postgres};
+use tracing::error;
+
+use crate::{config::TenantUsageConfig, db};
+
+tokio::task_local! {
+    static SCOPE: Scope;
+}
+
+/// Resources spent on behalf of a tenant.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Counters {
+    pub requests: i64,
+    pub db_time_ms: i64,
+    pub janus_calls: i64,
+    /// Size of the response payloads, so it doesn't include notifications and MQTT overhead.
+    pub bytes_published: i64,
+}
+
+impl Counters {
+    fn add(&mut self, other: Counters) {
+        self.requests += other.requests;
+        self.db_time_ms += other.db_time_ms;
+        self.janus_calls += other.janus_calls;
+        self.bytes_published += other.bytes_published;
+    }
+}
+
+/// Per-audience usage accumulated since the last flush.
+#[derive(Clone, Default)]
+pub struct TenantUsage {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    counters: Mutex<HashMap<String, Counters>>,
+    metrics: Option<UsageMetrics>,
+}
+
+struct UsageMetrics {
+    requests: IntCounterVec,
+    db_time_ms: IntCounterVec,
+    janus_calls: IntCounterVec,
+    bytes_published: IntCounterVec,
+}
+
+#[derive(Clone)]
+struct Scope {
+    usage: TenantUsage,
+    audience: String,
+}
+
+impl TenantUsage {
+    pub fn new(registry: &Registry) -> anyhow::Result<Self> {
+        let counter = |name: &str, help: &str| -> anyhow::Result<IntCounterVec> {
+            let counter = IntCounterVec::new(Opts::new(name, help), &["audience"])?;
+            registry.register(Box::new(counter.clone()))?;
+            Ok(counter)
+        };
+
+        let metrics = UsageMetrics {
+            requests: counter("tenant_requests_total", "Requests handled for the tenant")?,
+            db_time_ms: counter(
+                "tenant_db_time_milliseconds_total",
+                "Time DB connections were held for the tenant",
+            )?,
+            janus_calls: counter("tenant_janus_calls_total", "Janus requests for the tenant")?,
+            bytes_published: counter(
+                "tenant_published_bytes_total",
+                "Approximate size of responses to the tenant",
+            )?,
+        };
+
+        Ok(Self {
+            inner: Arc::new(Inner {
+                counters: Mutex::default(),
+                metrics: Some(metrics),
+            }),
+        })
+    }
+
+    /// Runs the request handler accounting everything it spends to the audience.
+    pub async fn scope<F: Future>(&self, audience: &str, f: F) -> F::Output {
+        self.add(
+            audience,
+            Counters {
+                requests: 1,
+                ..Default::default()
+            },
+        );
+
+        let scope = Scope {
+            usage: self.clone(),
+            audience: audience.to_owned(),
+        };
+
+        SCOPE.scope(scope, f).await
+    }
+
+    pub fn add(&self, audience: &str, counters: Counters) {
+        if let Some(metrics) = &self.inner.metrics {
+            for (metric, value) in [
+                (&metrics.requests, counters.requests),
+                (&metrics.db_time_ms, counters.db_time_ms),
+                (&metrics.janus_calls, counters.janus_calls),
+                (&metrics.bytes_published, counters.bytes_published),
+            ] {
+                if value > 0 {
+                    metric.with_label_values(&[audience]).inc_by(value as u64);
+                }
+            }
+        }
+
+        self.inner
+            .counters
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(audience.to_owned())
+            .or_default()
+            .add(counters);
+    }
+
+    /// Takes the usage accumulated so far.
+    pub fn drain(&self) -> HashMap<String, Counters> {
+        let mut counters = self
+            .inner
+            .counters
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        std::mem::take(&mut *counters)
+    }
+
+    /// Puts the drained usage back when it couldn't be stored.
+    fn restore(&self, drained: HashMap<String, Counters>) {
+        let mut counters = self
+            .inner
+            .counters
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        for (audience, usage) in drained {
+            counters.entry(audience).or_default().add(usage);
+        }
+    }
+}
+
+/// Accounts the usage to the tenant of the request being handled if any.
+pub fn record(counters: Counters) {
+    let _ = SCOPE.try_with(|scope| scope.usage.add(&scope.audience, counters));
+}
+
+pub fn record_janus_call() {
+    record(Counters {
+        janus_calls: 1,
+        ..Default::default()
+    });
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// DB connection which accounts the time it's been held to the tenant of the request.
+pub struct MeteredConnection {
+    conn: PoolConnection<Postgres>,
+    acquired_at: Instant,
+    scope: Option<Scope>,
+}
+
+impl MeteredConnection {
+    pub fn new(conn: PoolConnection<Postgres>) -> Self {
+        Self {
+            conn,
+            acquired_at: Instant::now(),
+            scope: SCOPE.try_with(Clone::clone).ok(),
+        }
+    }
+}
+
+impl Deref for MeteredConnection {
+    type Target = PoolConnection<Postgres>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.conn
+    }
+}
+
+impl DerefMut for MeteredConnection {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.conn
+    }
+}
+
+impl Drop for MeteredConnection {
+    fn drop(&mut self) {
+        if let Some(scope) = &self.scope {
+            let counters = Counters {
+                db_time_ms: self.acquired_at.elapsed().as_millis() as i64,
+                ..Default::default()
+            };
+
+            scope.usage.add(&scope.audience, counters);
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Periodically moves the accumulated usage to `tenant_usage`.
+pub async fn start_flusher(db: sqlx::PgPool, usage: TenantUsage, config: TenantUsageConfig) {
+    loop {
+        tokio::time::sleep(config.flush_interval).await;
+
+        let drained = usage.drain();
+
+        if let Err(err) = flush(&db, &drained).await {
+            error!(?err, "Failed to flush tenant usage");
+            usage.restore(drained);
+        }
+    }
+}
+
+async fn flush(db: &sqlx::PgPool, drained: &HashMap<String, Counters>) -> anyhow::Result<()> {
+    if drained.is_empty() {
+        return Ok(());
+    }
+
+    // All or nothing since the usage gets restored on failure.
+    let mut conn = db.acquire().await?;
+    let mut txn = conn.begin().await?;
+
+    for (audience, counters) in drained {
+        db::tenant_usage::add(audience, counters, &mut txn)
+            .await
+            .context("Failed to store tenant usage")?;
+    }
+
+    txn.commit().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn scoped_usage() {
+        let usage = TenantUsage::default();
+
+        usage
+            .scope("example.org", async {
+                record_janus_call();
+                record_janus_call();
+            })
+            .await;
+
+        // Outside of a request nothing is accounted.
+        record_janus_call();
+
+        let drained = usage.drain();
+
+        assert_eq!(
+            drained.get("example.org"),
+            Some(&Counters {
+                requests: 1,
+                janus_calls: 2,
+                ..Default::default()
+            })
+        );
+
+        assert!(usage.drain().is_empty());
+    }
+}
@@ -0,0 +1,440 @@
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use axum::{
+    extract::ws::{Message, WebSocket, WebSocketUpgrade},
+    middleware::Next,
+    response::Response as HttpResponse,
+    Extension,
+};
+use futures::{SinkExt, StreamExt};
+use http::{header, HeaderValue, Request as HttpRequest};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use svc_agent::{
+    mqtt::{PublishableMessage, ResponseStatus},
+    Addressable, AgentId,
+};
+use svc_utils::extractors::AgentIdExtractor;
+use tokio::sync::mpsc;
+use tracing::{error, warn};
+use uuid::Uuid;
+
+use crate::{
+    app::{
+        context::{AppContext, Context},
+        dispatcher::Dispatcher,
+        endpoint::{self, helpers, RequestHandler, RequestResult},
+        message_handler::ws::{self, IncomingFrame, OutgoingFrame},
+        service_utils::{RequestParams, Response},
+    },
+    db,
+};
+
+////////////////////////////////////////////////////////////////////////////////
+
+struct Socket {
+    id: Uuid,
+    tx: mpsc::UnboundedSender<OutgoingFrame>,
+    rooms: HashSet<db::room::Id>,
+}
+
+/// Open sockets by agent. An agent has at most one socket, a new one replaces the previous.
+#[derive(Clone, Default)]
+pub struct Sockets {
+    inner: Arc<Mutex<HashMap<AgentId, Socket>>>,
+}
+
+impl Sockets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn register(&self, agent_id: AgentId) -> (Uuid, mpsc::UnboundedReceiver<OutgoingFrame>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let id = Uuid::new_v4();
+
+        let socket = Socket {
+            id,
+            tx,
+            rooms: HashSet::new(),
+        };
+
+        // Dropping the sender of the previous socket makes its writer close it.
+        self.lock().insert(agent_id, socket);
+        (id, rx)
+    }
+
+    fn unregister(&self, agent_id: &AgentId, id: Uuid) {
+        let mut sockets = self.lock();
+
+        if sockets.get(agent_id).map(|s| s.id) == Some(id) {
+            sockets.remove(agent_id);
+        }
+    }
+
+    fn subscribe(&self, agent_id: &AgentId, room_id: db::room::Id) {
+        if let Some(socket) = self.lock().get_mut(agent_id) {
+            socket.rooms.insert(room_id);
+        }
+    }
+
+    fn unsubscribe(&self, agent_id: &AgentId, room_id: db::room::Id) {
+        if let Some(socket) = self.lock().get_mut(agent_id) {
+            socket.rooms.remove(&room_id);
+        }
+    }
+
+    fn send(&self, agent_id: &AgentId, frame: OutgoingFrame) {
+        if let Some(socket) = self.lock().get(agent_id) {
+            socket.tx.send(frame).ok();
+        }
+    }
+
+    /// Pushes the event published by the dispatcher to the sockets it's addressed to.
+    pub fn deliver(&self, message: &PublishableMessage) {
+        if !matches!(message, PublishableMessage::Event(_)) || self.lock().is_empty() {
+            return;
+        }
+
+        let target = match Target::parse(message.topic()) {
+            Some(target) => target,
+            None => return,
+        };
+
+        let frame = match event_frame(message.payload()) {
+            Ok(frame) => frame,
+            Err(err) => {
+                warn!(
+                    ?err,
+                    topic = message.topic(),
+                    "Failed to parse event for sockets"
+                );
+                return;
+            }
+        };
+
+        let sockets = self.lock();
+
+        match target {
+            Target::Agent(agent_id) => {
+                if let Some(socket) = sockets.get(&agent_id) {
+                    socket.tx.send(frame).ok();
+                }
+            }
+            Target::Room(room_id) => {
+                for socket in sockets.values().filter(|s| s.rooms.contains(&room_id)) {
+                    socket.tx.send(frame.clone()).ok();
+                }
+            }
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<AgentId, Socket>> {
+        self.inner.lock().expect("Sockets mutex poisoned")
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Target {
+    Agent(AgentId),
+    Room(db::room::Id),
+}
+
+impl Target {
+    /// Events go either to `agents/{agent_id}/api/{version}/in/{app}` or to
+    /// `apps/{app}/api/{version}/rooms/{room_id}/events`.
+    pub(crate) fn parse(topic: &str) -> Option<Self> {
+        let segments = topic.split('/').collect::<Vec<_>>();
+
+        match segments.as_slice() {
+            ["agents", agent_id, ..] => AgentId::from_str(agent_id).ok().map(Self::Agent),
+            [.., "rooms", room_id, "events"] => {
+                db::room::Id::from_str(room_id).ok().map(Self::Room)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct EventEnvelope {
+    payload: String,
+    properties: EventProperties,
+}
+
+#[derive(Deserialize)]
+struct EventProperties {
+    label: Option<String>,
+}
+
+pub(crate) fn event_frame(dump: &str) -> anyhow::Result<OutgoingFrame> {
+    let envelope = serde_json::from_str::<EventEnvelope>(dump)?;
+    let payload = serde_json::from_str::<JsonValue>(&envelope.payload)?;
+
+    Ok(OutgoingFrame::Event {
+        label: envelope.properties.label,
+        payload,
+    })
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Browsers can't set headers on a WebSocket handshake so the token may come in the query.
+pub async fn token_from_query<B>(mut req: HttpRequest<B>, next: Next<B>) -> HttpResponse {
+    let token = req.uri().query().and_then(|query| {
+        query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(key, _)| *key == "access_token")
+            .map(|(_, token)| token.to_owned())
+    });
+
+    if let Some(token) = token {
+        if !req.headers().contains_key(header::AUTHORIZATION) {
+            if let Ok(value) = HeaderValue::from_str(&format!("Bearer {}", token)) {
+                req.headers_mut().insert(header::AUTHORIZATION, value);
+            }
+        }
+    }
+
+    next.run(req).await
+}
+
+/// Upgrades the connection to a socket carrying the signaling of the authenticated agent.
+pub async fn upgrade(
+    Extension(ctx): Extension<Arc<AppContext>>,
+    Extension(sockets): Extension<Sockets>,
+    Extension(dispatcher): Extension<Dispatcher>,
+    AgentIdExtractor(agent_id): AgentIdExtractor,
+    upgrade: WebSocketUpgrade,
+) -> HttpResponse {
+    upgrade.on_upgrade(move |socket| serve(ctx, sockets, dispatcher, agent_id, socket))
+}
+
+async fn serve(
+    ctx: Arc<AppContext>,
+    sockets: Sockets,
+    dispatcher: Dispatcher,
+    agent_id: AgentId,
+    socket: WebSocket,
+) {
+    let (mut sink, mut stream) = socket.split();
+    let (id, mut rx) = sockets.register(agent_id.clone());
+
+    tokio::spawn(async move {
+        while let Some(frame) = rx.recv().await {
+            let text = match serde_json::to_string(&frame) {
+                Ok(text) => text,
+                Err(err) => {
+                    error!(?err, "Failed to serialize socket frame");
+                    continue;
+                }
+            };
+
+            if sink.send(Message::Text(text)).await.is_err() {
+                break;
+            }
+        }
+
+        sink.close().await.ok();
+    });
+
+    while let Some(Ok(message)) = stream.next().await {
+        let text = match message {
+            Message::Text(text) => text,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+
+        let frame = match serde_json::from_str::<IncomingFrame>(&text) {
+            Ok(frame) => frame,
+            Err(err) => {
+                warn!(?err, %agent_id, "Failed to parse socket frame");
+                continue;
+            }
+        };
+
+        let request = ws::Request {
+            agent_id: agent_id.clone(),
+            frame,
+        };
+
+        // Signaling may wait for the backend so frames are handled independently.
+        tokio::spawn(handle(
+            ctx.clone(),
+            sockets.clone(),
+            dispatcher.clone(),
+            request,
+        ));
+    }
+
+    sockets.unregister(&agent_id, id);
+}
+
+async fn handle(
+    ctx: Arc<AppContext>,
+    sockets: Sockets,
+    dispatcher: Dispatcher,
+    request: ws::Request,
+) {
+    let mut context = ctx.start_message();
+    let agent_id = &request.agent_id;
+
+    let reply = match request.frame.method.as_str() {
+        "room.subscribe" => {
+            let reply = ws::handle_frame::<SubscribeHandler, _>(&mut context, &request).await;
+
+            if let Some(room_id) = subscribed_room(&request.frame, &reply.0) {
+                sockets.subscribe(agent_id, room_id);
+            }
+
+            Some(reply)
+        }
+        "room.unsubscribe" => {
+            let reply = ws::handle_frame::<UnsubscribeHandler, _>(&mut context, &request).await;
+
+            if let Some(room_id) = subscribed_room(&request.frame, &reply.0) {
+                sockets.unsubscribe(agent_id, room_id);
+            }
+
+            Some(reply)
+        }
+        "rtc_signal.trickle" => Some(
+            ws::handle_frame::<endpoint::rtc_signal::TrickleHandler, _>(&mut context, &request)
+                .await,
+        ),
+        _ => endpoint::route_ws_request(&mut context, &request).await,
+    };
+
+    let (frame, notifications) =
+        reply.unwrap_or_else(|| (OutgoingFrame::unknown_method(&request.frame.id), vec![]));
+
+    sockets.send(agent_id, frame);
+
+    for notification in notifications {
+        dispatcher.dispatch(None, notification.into_message());
+    }
+}
+
+fn subscribed_room(frame: &IncomingFrame, reply: &OutgoingFrame) -> Option<db::room::Id> {
+    match reply {
+        OutgoingFrame::Response { status, .. } if *status == ResponseStatus::OK.as_u16() => {
+            serde_json::from_value::<SubscribeRequest>(frame.payload.clone())
+                .ok()
+                .map(|p| p.room_id)
+        }
+        _ => None,
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SubscribeRequest {
+    room_id: db::room::Id,
+}
+
+/// Subscribes the socket to the events of the room the agent has entered.
+pub struct SubscribeHandler;
+
+#[async_trait]
+impl RequestHandler for SubscribeHandler {
+    type Payload = SubscribeRequest;
+    const ERROR_TITLE: &'static str = "Failed to subscribe to room events";
+
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        let mut conn = context.get_conn().await?;
+
+        let room = helpers::find_room_by_id(
+            payload.room_id,
+            helpers::RoomTimeRequirement::Open,
+            helpers::AudienceScope::caller(context, &reqp),
+            &mut conn,
+        )
+        .await?;
+
+        helpers::check_room_presence(&room, reqp.as_agent_id(), &mut conn).await?;
+
+        Ok(Response::new(
+            ResponseStatus::OK,
+            payload,
+            context.start_timestamp(),
+            None,
+        ))
+    }
+}
+
+pub struct UnsubscribeHandler;
+
+#[async_trait]
+impl RequestHandler for UnsubscribeHandler {
+    type Payload = SubscribeRequest;
+    const ERROR_TITLE: &'static str = "Failed to unsubscribe from room events";
+    const SERVES_WHILE_DRAINING: bool = true;
+
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        payload: Self::Payload,
+        _reqp: RequestParams<'_>,
+    ) -> RequestResult {
+        Ok(Response::new(
+            ResponseStatus::OK,
+            payload,
+            context.start_timestamp(),
+            None,
+        ))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_targets() {
+        let room_id = db::room::Id::random();
+        let topic = format!(
+            "apps/conference.svc.example.org/api/v1/rooms/{}/events",
+            room_id
+        );
+        assert_eq!(Target::parse(&topic), Some(Target::Room(room_id)));
+
+        let topic = "agents/web.user123.usr.example.org/api/v1/in/conference.svc.example.org";
+        assert_eq!(
+            Target::parse(topic),
+            Some(Target::Agent(
+                AgentId::from_str("web.user123.usr.example.org").unwrap()
+            ))
+        );
+
+        assert_eq!(
+            Target::parse("apps/conference.svc.example.org/api/v1/audiences/x/events"),
+            None
+        );
+    }
+
+    #[test]
+    fn parse_event_frame() {
+        let dump =
+            r#"{"payload":"{\"id\":1}","properties":{"type":"event","label":"room.update"}}"#;
+
+        match event_frame(dump).expect("Failed to parse event") {
+            OutgoingFrame::Event { label, payload } => {
+                assert_eq!(label.as_deref(), Some("room.update"));
+                assert_eq!(payload, serde_json::json!({"id": 1}));
+            }
+            _ => panic!("Expected event frame"),
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use super::{
+    create_handle::CreateHandleRequest,
+    detach_handle::DetachHandleRequest,
+    service_ping::{ServicePingRequest, ServicePingRequestBody},
+    transactions::TransactionKind,
+    IncomingEvent, JanusClient, PollResult,
+};
+
+/// How long to wait for the plugin to answer the ping.
+const EVENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Requests and events of the backend checked against the payload schema of the service.
+#[derive(Debug, Default, Serialize)]
+pub struct Report {
+    pub ok: bool,
+    pub checks: Vec<Check>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub name: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl Report {
+    fn record<T>(&mut self, name: &'static str, result: anyhow::Result<T>) -> Option<T> {
+        match result {
+            Ok(value) => {
+                self.checks.push(Check { name, error: None });
+                Some(value)
+            }
+            Err(err) => {
+                self.checks.push(Check {
+                    name,
+                    error: Some(format!("{:#}", err)),
+                });
+                None
+            }
+        }
+    }
+
+    async fn run(&mut self, client: &JanusClient) {
+        let session = match self.record("create_session", client.create_session().await) {
+            Some(session) => session,
+            None => return,
+        };
+
+        let request = CreateHandleRequest {
+            session_id: session.id,
+            opaque_id: None,
+        };
+
+        let handle = match self.record("attach", client.create_handle(request).await) {
+            Some(handle) => handle,
+            None => return,
+        };
+
+        let request = ServicePingRequest {
+            session_id: session.id,
+            handle_id: handle.id,
+            body: ServicePingRequestBody::new(),
+        };
+
+        if self
+            .record("service_ping", client.service_ping(request).await)
+            .is_some()
+        {
+            let events = wait_for_ping(client, session.id).await;
+            self.record("service_ping_event", events);
+        }
+
+        let request = DetachHandleRequest {
+            session_id: session.id,
+            handle_id: handle.id,
+        };
+
+        self.record("detach", client.detach_handle(request).await);
+    }
+}
+
+/// Runs the battery against the backend. The session is left to expire on the Janus side.
+pub async fn validate(client: &JanusClient) -> Report {
+    let mut report = Report::default();
+    report.run(client).await;
+    report.ok = report.checks.iter().all(|check| check.error.is_none());
+    report
+}
+
+/// Polls the session until the plugin answers the ping. Every event received meanwhile must
+/// deserialize as well.
+async fn wait_for_ping(client: &JanusClient, session_id: super::SessionId) -> anyhow::Result<()> {
+    let deadline = Instant::now() + EVENT_TIMEOUT;
+
+    loop {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+
+        let events = match tokio::time::timeout(timeout, client.poll(session_id)).await {
+            Ok(Ok(PollResult::Events(events))) => events,
+            Ok(Ok(PollResult::SessionNotFound)) => anyhow::bail!("Session not found"),
+            Ok(Err(err)) => return Err(err),
+            Err(_) => anyhow::bail!("No ping event in {:?}", EVENT_TIMEOUT),
+        };
+
+        for event in events {
+            if let Some(true) = parse_event(event)? {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Returns whether the event answers the ping. Keepalives yield `None`.
+fn parse_event(event: Value) -> anyhow::Result<Option<bool>> {
+    if event.get("janus").and_then(|x| x.as_str()) == Some("keepalive") {
+        return Ok(None);
+    }
+
+    let raw = event.to_string();
+    let event: IncomingEvent =
+        serde_json::from_value(event).map_err(|err| anyhow::anyhow!("{}: {}", err, raw))?;
+
+    let is_ping = match event {
+        IncomingEvent::Event(resp) => {
+            matches!(resp.transaction.kind, Some(TransactionKind::ServicePing))
+        }
+        _ => false,
+    };
+
+    Ok(Some(is_ping))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{
+        create_handle::CreateHandleResponse, create_session::CreateSessionResponse, AckResponse,
+        JanusResponse, SuccessResponse,
+    };
+    use super::*;
+
+    macro_rules! fixture {
+        ($name:literal) => {
+            serde_json::from_str::<Value>(include_str!(concat!("fixtures/", $name, ".json")))
+                .expect("Invalid fixture")
+        };
+    }
+
+    #[test]
+    fn parse_responses() {
+        let session: JanusResponse<CreateSessionResponse> =
+            serde_json::from_value(fixture!("create_session")).expect("create");
+        assert_eq!(session.data.id.to_string(), "4738289016351234");
+
+        let handle: JanusResponse<CreateHandleResponse> =
+            serde_json::from_value(fixture!("attach")).expect("attach");
+        assert_eq!(handle.data.id.to_string(), "2195735126482937");
+
+        serde_json::from_value::<AckResponse>(fixture!("ack")).expect("ack");
+        serde_json::from_value::<SuccessResponse>(fixture!("detach")).expect("detach");
+    }
+
+    #[test]
+    fn parse_events() {
+        let events = vec![
+            fixture!("event_service_ping"),
+            fixture!("event_agent_speaking"),
+            fixture!("event_update_reader_config"),
+            fixture!("webrtcup"),
+            fixture!("media"),
+            fixture!("slowlink"),
+            fixture!("hangup"),
+            fixture!("detached"),
+            fixture!("timeout"),
+        ];
+
+        let pings = events
+            .into_iter()
+            .map(|event| parse_event(event).expect("Failed to parse event"))
+            .filter(|is_ping| *is_ping == Some(true))
+            .count();
+
+        assert_eq!(pings, 1);
+    }
+
+    #[test]
+    fn skip_keepalive() {
+        let event = serde_json::json!({"janus": "keepalive", "session_id": 1});
+        assert_eq!(parse_event(event).expect("Failed to parse keepalive"), None);
+    }
+
+    #[test]
+    fn report_renamed_field() {
+        let mut event = fixture!("hangup");
+        let reason = event["reason"].take();
+        event["cause"] = reason;
+        event.as_object_mut().unwrap().remove("reason");
+
+        let err = parse_event(event).expect_err("Must fail on renamed field");
+        assert!(err.to_string().contains("reason"));
+    }
+}
@@ -0,0 +1,76 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::db;
+
+use super::{HandleId, SessionId};
+
+/// Starts mixing every publisher of the room into a single recording.
+#[derive(Debug, Serialize)]
+pub struct StartCompositeRequest {
+    pub session_id: SessionId,
+    pub handle_id: HandleId,
+    pub body: StartCompositeRequestBody,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StartCompositeRequestBody {
+    method: &'static str,
+    id: db::room::Id,
+}
+
+impl StartCompositeRequestBody {
+    pub fn new(id: db::room::Id) -> Self {
+        Self {
+            method: "composite.start",
+            id,
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Stops mixing the room and uploads the composite file.
+#[derive(Debug, Serialize)]
+pub struct UploadCompositeRequest {
+    pub session_id: SessionId,
+    pub handle_id: HandleId,
+    pub body: UploadCompositeRequestBody,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct UploadCompositeTransaction {
+    pub room_id: db::room::Id,
+    pub start_timestamp: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UploadCompositeRequestBody {
+    method: &'static str,
+    id: db::room::Id,
+    backend: String,
+    bucket: String,
+    object: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    storage_class: Option<String>,
+}
+
+impl UploadCompositeRequestBody {
+    pub fn new(id: db::room::Id, backend: &str, bucket: &str, object: &str) -> Self {
+        Self {
+            method: "composite.upload",
+            id,
+            backend: backend.to_owned(),
+            bucket: bucket.to_owned(),
+            object: object.to_owned(),
+            storage_class: None,
+        }
+    }
+
+    pub fn storage_class(self, storage_class: Option<&str>) -> Self {
+        Self {
+            storage_class: storage_class.map(ToOwned::to_owned),
+            ..self
+        }
+    }
+}
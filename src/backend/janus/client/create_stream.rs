@@ -20,6 +20,11 @@ pub enum CreateStreamTransaction {
     Mqtt {
         reqp: IncomingRequestProperties,
         start_timestamp: DateTime<Utc>,
+        // Missing in transactions sent before the field has been added.
+        #[serde(default)]
+        handle_id: Option<crate::app::handle_id::HandleId>,
+        #[serde(default)]
+        correlation: Option<crate::backend::janus::correlation::CorrelationRef>,
     },
     Http {
         id: usize,
@@ -33,6 +38,10 @@ pub struct WriterConfig {
     pub send_audio: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub video_remb: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_video_bitrate: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_audio_bitrate: Option<i64>,
 }
 
 #[derive(Serialize, Debug)]
@@ -49,6 +58,17 @@ pub struct CreateStreamRequestBody {
     agent_id: AgentId,
     writer_config: Option<WriterConfig>,
     reader_configs: Option<Vec<ReaderConfig>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    audio_processing: Option<db::room::AudioProcessing>,
+    /// The plugin records every stream unless told otherwise.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    record: Option<bool>,
+    /// The offer renegotiates the handle's connection with new ICE credentials.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ice_restart: Option<bool>,
+    /// The offer has a data channel to be relayed between the agents of the room.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data_channel: Option<bool>,
 }
 
 impl CreateStreamRequestBody {
@@ -68,6 +88,38 @@ impl CreateStreamRequestBody {
             } else {
                 Some(reader_configs)
             },
+            audio_processing: None,
+            record: None,
+            ice_restart: None,
+            data_channel: None,
+        }
+    }
+
+    pub fn audio_processing(self, audio_processing: Option<db::room::AudioProcessing>) -> Self {
+        Self {
+            audio_processing,
+            ..self
+        }
+    }
+
+    pub fn record(self, record: bool) -> Self {
+        Self {
+            record: Some(record),
+            ..self
+        }
+    }
+
+    pub fn ice_restart(self, ice_restart: bool) -> Self {
+        Self {
+            ice_restart: Some(ice_restart).filter(|restart| *restart),
+            ..self
+        }
+    }
+
+    pub fn data_channel(self, data_channel: bool) -> Self {
+        Self {
+            data_channel: Some(data_channel).filter(|enabled| *enabled),
+            ..self
         }
     }
 }
@@ -28,6 +28,29 @@ pub struct WebRtcUpEvent {
     pub sender: HandleId,
     #[serde(with = "super::serialize_as_base64")]
     pub opaque_id: OpaqueId,
+    // ICE pair the connection has settled on as formatted by Janus:
+    // `10.0.0.1:5000 [host,udp] <-> 1.2.3.4:3478 [relay,udp]`.
+    // Only sent by backends built with the selected pair reporting.
+    #[serde(default)]
+    pub selected_pair: Option<String>,
+}
+
+impl WebRtcUpEvent {
+    /// Whether either side of the selected pair is a TURN relay candidate.
+    /// `None` when the backend hasn't reported the pair or it can't be parsed.
+    pub fn relayed(&self) -> Option<bool> {
+        let pair = self.selected_pair.as_deref()?;
+        let mut kinds = pair.split("<->").map(|side| {
+            let start = side.find('[')?;
+            let end = side[start..].find(']')? + start;
+            side[start + 1..end].split(',').next().map(str::trim)
+        });
+
+        match (kinds.next()??, kinds.next()??) {
+            ("relay", _) | (_, "relay") => Some(true),
+            _ => Some(false),
+        }
+    }
 }
 
 // A RTCPeerConnection closed for a DTLS alert (normal shutdown).
@@ -79,3 +102,39 @@ pub struct DetachedEvent {
     #[serde(with = "super::serialize_as_base64")]
     pub opaque_id: OpaqueId,
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn webrtcup(selected_pair: Option<&str>) -> WebRtcUpEvent {
+        serde_json::from_value(json!({
+            "session_id": 1,
+            "sender": 2,
+            "opaque_id": "eyJzdHJlYW1faWQiOiI3ODFlYjJmYy0xN2Y2LTQ4NmMtOWEzYi1lN2M0NzlkZDlhYzAiLCJyb29tX2lkIjoiNjFkMmU4ZGQtNTNmYi00ZTc0LWIxOWUtNDcxMDg2OGEwZWZlIn0=",
+            "selected_pair": selected_pair,
+        }))
+        .expect("Failed to parse webrtcup event")
+    }
+
+    #[test]
+    fn relayed_from_selected_pair() {
+        let event = webrtcup(Some(
+            "10.0.0.1:5000 [host,udp] <-> 1.2.3.4:3478 [relay,udp]",
+        ));
+        assert_eq!(event.relayed(), Some(true));
+
+        let event = webrtcup(Some(
+            "10.0.0.1:5000 [srflx,udp] <-> 1.2.3.4:50000 [host,udp]",
+        ));
+        assert_eq!(event.relayed(), Some(false));
+
+        let event = webrtcup(Some("garbage"));
+        assert_eq!(event.relayed(), None);
+
+        let event = webrtcup(None);
+        assert_eq!(event.relayed(), None);
+    }
+}
@@ -1,9 +1,11 @@
-use crate::trace_id::TraceId;
+use crate::{app::tenant_usage, trace_id::TraceId};
 
 use self::{
+    composite::{StartCompositeRequest, UploadCompositeRequest, UploadCompositeTransaction},
     create_handle::{CreateHandleRequest, CreateHandleResponse, OpaqueId},
     create_session::CreateSessionResponse,
     create_stream::{CreateStreamRequest, CreateStreamTransaction},
+    detach_handle::DetachHandleRequest,
     events::{
         DetachedEvent, EventResponse, HangUpEvent, MediaEvent, SlowLinkEvent, TimeoutEvent,
         WebRtcUpEvent,
@@ -22,14 +24,19 @@ use reqwest::{Client, StatusCode, Url};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::Value;
 
-use derive_more::{Display, FromStr};
+use derive_more::Display;
 
+pub mod compat;
+pub mod composite;
 pub mod create_handle;
 pub mod create_session;
 pub mod create_stream;
+pub mod detach_handle;
 pub mod events;
 pub mod read_stream;
 pub mod service_ping;
+#[cfg(feature = "janus_stub")]
+pub mod stub;
 pub mod transactions;
 pub mod trickle;
 pub mod update_agent_reader_config;
@@ -40,17 +47,50 @@ pub mod upload_stream;
 pub struct JanusClient {
     http: Client,
     janus_url: Url,
+    #[cfg(feature = "janus_stub")]
+    stub: Option<stub::StubJanus>,
 }
 
 impl JanusClient {
     pub fn new(janus_url: &str) -> anyhow::Result<Self> {
+        Self::with_http(Client::new(), janus_url)
+    }
+
+    pub fn with_http(http: Client, janus_url: &str) -> anyhow::Result<Self> {
+        let janus_url: Url = janus_url.parse()?;
+
+        // The HTTP client can't connect over unix sockets yet, so a proxy is needed there.
+        if janus_url.scheme() == "unix" {
+            anyhow::bail!("Unix socket transport is not supported: {}", janus_url);
+        }
+
+        #[cfg(feature = "janus_stub")]
+        let stub = match janus_url.scheme() {
+            "stub" => Some(stub::StubJanus::instance(
+                janus_url.host_str().unwrap_or_default(),
+            )),
+            _ => None,
+        };
+
+        #[cfg(not(feature = "janus_stub"))]
+        if janus_url.scheme() == "stub" {
+            anyhow::bail!("Stub janus requires the janus_stub feature: {}", janus_url);
+        }
+
         Ok(Self {
-            http: Client::new(),
-            janus_url: janus_url.parse()?,
+            http,
+            janus_url,
+            #[cfg(feature = "janus_stub")]
+            stub,
         })
     }
 
     pub async fn poll(&self, session_id: SessionId) -> anyhow::Result<PollResult> {
+        #[cfg(feature = "janus_stub")]
+        if let Some(stub) = &self.stub {
+            return stub.poll(session_id).await;
+        }
+
         let response = self
             .http
             .get(format!("{}/{}?maxev=5", self.janus_url, session_id))
@@ -75,6 +115,22 @@ impl JanusClient {
         Ok(())
     }
 
+    pub async fn start_composite(&self, request: StartCompositeRequest) -> anyhow::Result<()> {
+        let _response: AckResponse = self.send_request(start_composite(request)).await?;
+        Ok(())
+    }
+
+    pub async fn upload_composite(
+        &self,
+        request: UploadCompositeRequest,
+        transaction: UploadCompositeTransaction,
+    ) -> anyhow::Result<()> {
+        let _response: AckResponse = self
+            .send_request(upload_composite(request, transaction))
+            .await?;
+        Ok(())
+    }
+
     pub async fn reader_update(&self, request: UpdateReaderConfigRequest) -> anyhow::Result<()> {
         let _response: AckResponse = self.send_request(update_reader(request)).await?;
         Ok(())
@@ -120,6 +176,11 @@ impl JanusClient {
         Ok(response.data)
     }
 
+    pub async fn detach_handle(&self, request: DetachHandleRequest) -> anyhow::Result<()> {
+        let _response: SuccessResponse = self.send_request(detach_handle(request)).await?;
+        Ok(())
+    }
+
     pub async fn create_session(&self) -> anyhow::Result<CreateSessionResponse> {
         let response: JanusResponse<CreateSessionResponse> =
             self.send_request(create_session()).await?;
@@ -132,19 +193,40 @@ impl JanusClient {
     }
 
     async fn send_request<R: DeserializeOwned>(&self, body: impl Serialize) -> anyhow::Result<R> {
+        tenant_usage::record_janus_call();
+
+        #[cfg(feature = "janus_stub")]
+        if let Some(stub) = &self.stub {
+            let response = stub.handle(serde_json::to_value(&body)?)?;
+            return Ok(serde_json::from_value(response)?);
+        }
+
         let body = serde_json::to_vec(&body)?;
+
+        let size = body.len();
+
         let response = self
             .http
             .post(self.janus_url.clone())
             .body(body)
             .send()
-            .await?
-            .text()
             .await?;
+
+        // The HTTP server in front of Janus rejects huge bodies before the plugin sees them.
+        if response.status() == StatusCode::PAYLOAD_TOO_LARGE {
+            return Err(PayloadTooLarge(size).into());
+        }
+
+        let response = response.text().await?;
         serde_json::from_str(&response).context(response)
     }
 }
 
+/// Janus refused the request body for its size, which happens with SDPs of many m-lines.
+#[derive(Debug, thiserror::Error)]
+#[error("janus rejected the request as too large: {0} bytes")]
+pub struct PayloadTooLarge(pub usize);
+
 #[derive(Debug)]
 pub enum PollResult {
     SessionNotFound,
@@ -207,38 +289,75 @@ pub enum IceCandidateSdp {
     List(Vec<IceCandidateSdpItem>),
 }
 
-#[derive(
-    Debug, Deserialize, Serialize, Display, Copy, Clone, Hash, PartialEq, Eq, FromStr, sqlx::Type,
-)]
+impl IceCandidateSdp {
+    pub fn into_items(self) -> Vec<IceCandidateSdpItem> {
+        match self {
+            Self::Single(item) => vec![item],
+            Self::List(items) => items,
+        }
+    }
+}
+
+/// Janus only issues positive ids. Both id types reject anything else when deserialized or
+/// parsed so a client can't slip an arbitrary number through a handle id.
+#[derive(Debug, thiserror::Error)]
+#[error("invalid janus id: {0}")]
+pub struct InvalidJanusId(i64);
+
+macro_rules! janus_id {
+    ($name:ident) => {
+        impl std::convert::TryFrom<i64> for $name {
+            type Error = InvalidJanusId;
+
+            fn try_from(value: i64) -> Result<Self, Self::Error> {
+                if value > 0 {
+                    Ok(Self(value))
+                } else {
+                    Err(InvalidJanusId(value))
+                }
+            }
+        }
+
+        impl std::str::FromStr for $name {
+            type Err = anyhow::Error;
+
+            fn from_str(value: &str) -> Result<Self, Self::Err> {
+                use std::convert::TryFrom;
+
+                Ok(Self::try_from(value.parse::<i64>()?)?)
+            }
+        }
+
+        impl $name {
+            #[cfg(test)]
+            pub fn random() -> Self {
+                use rand::Rng;
+                Self(rand::thread_rng().gen_range(1..i64::MAX))
+            }
+        }
+    };
+}
+
+#[derive(Debug, Deserialize, Serialize, Display, Copy, Clone, Hash, PartialEq, Eq, sqlx::Type)]
+#[serde(try_from = "i64")]
 #[sqlx(transparent)]
 pub struct HandleId(i64);
 
+janus_id!(HandleId);
+
 impl HandleId {
     #[cfg(test)]
     pub fn stub_id() -> Self {
         Self(123)
     }
-
-    #[cfg(test)]
-    pub fn random() -> Self {
-        use rand::Rng;
-        Self(rand::thread_rng().gen())
-    }
 }
 
-#[derive(
-    Debug, Deserialize, Serialize, Display, Copy, Clone, Hash, PartialEq, Eq, FromStr, sqlx::Type,
-)]
+#[derive(Debug, Deserialize, Serialize, Display, Copy, Clone, Hash, PartialEq, Eq, sqlx::Type)]
+#[serde(try_from = "i64")]
 #[sqlx(transparent)]
 pub struct SessionId(i64);
 
-impl SessionId {
-    #[cfg(test)]
-    pub fn random() -> Self {
-        use rand::Rng;
-        Self(rand::thread_rng().gen())
-    }
-}
+janus_id!(SessionId);
 
 /////////////////////////////////////////////////
 
@@ -273,7 +392,11 @@ impl IncomingEvent {
                 Some(TransactionKind::UpdateWriterConfig) => "UpdateWriterConfig",
                 Some(TransactionKind::UploadStream(_)) => "UploadStream",
                 Some(TransactionKind::AgentSpeaking) => "AgentSpeaking",
+                Some(TransactionKind::AgentAudioLevel) => "AgentAudioLevel",
                 Some(TransactionKind::ServicePing) => "ServicePing",
+                Some(TransactionKind::StartComposite) => "StartComposite",
+                Some(TransactionKind::UploadComposite(_)) => "UploadComposite",
+                Some(TransactionKind::Unknown(_)) => "Unknown",
                 None => "EmptyTran",
             },
         }
@@ -318,6 +441,12 @@ enum Success {
     Success,
 }
 
+#[derive(Deserialize, Debug)]
+struct SuccessResponse {
+    #[allow(dead_code)]
+    janus: Success,
+}
+
 #[derive(Deserialize, Debug)]
 struct JanusResponse<T> {
     data: T,
@@ -354,6 +483,15 @@ fn create_handle(request: CreateHandleRequest) -> JanusRequest<CreateHandleReque
     }
 }
 
+fn detach_handle(request: DetachHandleRequest) -> JanusRequest<DetachHandleRequest> {
+    JanusRequest {
+        transaction: Transaction::only_id(),
+        janus: "detach",
+        plugin: None,
+        data: request,
+    }
+}
+
 fn trickle(request: TrickleRequest) -> JanusRequest<TrickleRequest> {
     JanusRequest {
         transaction: Transaction::only_id(),
@@ -417,6 +555,27 @@ fn upload_stream(
     }
 }
 
+fn start_composite(request: StartCompositeRequest) -> JanusRequest<StartCompositeRequest> {
+    JanusRequest {
+        transaction: Transaction::new(TransactionKind::StartComposite),
+        janus: "message",
+        plugin: None,
+        data: request,
+    }
+}
+
+fn upload_composite(
+    request: UploadCompositeRequest,
+    transaction: UploadCompositeTransaction,
+) -> JanusRequest<UploadCompositeRequest> {
+    JanusRequest {
+        transaction: Transaction::new(TransactionKind::UploadComposite(transaction)),
+        janus: "message",
+        plugin: None,
+        data: request,
+    }
+}
+
 fn service_ping(request: ServicePingRequest) -> JanusRequest<ServicePingRequest> {
     JanusRequest {
         transaction: Transaction::new(TransactionKind::ServicePing),
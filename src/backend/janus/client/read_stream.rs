@@ -22,6 +22,11 @@ pub enum ReadStreamTransaction {
     Mqtt {
         reqp: IncomingRequestProperties,
         start_timestamp: DateTime<Utc>,
+        // Missing in transactions sent before the field has been added.
+        #[serde(default)]
+        handle_id: Option<crate::app::handle_id::HandleId>,
+        #[serde(default)]
+        correlation: Option<crate::backend::janus::correlation::CorrelationRef>,
     },
     Http {
         id: usize,
@@ -35,6 +40,12 @@ pub struct ReadStreamRequestBody {
     id: db::rtc::Id,
     agent_id: AgentId,
     reader_configs: Option<Vec<ReaderConfig>>,
+    /// The offer renegotiates the handle's connection with new ICE credentials.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ice_restart: Option<bool>,
+    /// The offer has a data channel to be relayed between the agents of the room.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data_channel: Option<bool>,
 }
 
 impl ReadStreamRequestBody {
@@ -48,6 +59,22 @@ impl ReadStreamRequestBody {
             } else {
                 Some(reader_configs)
             },
+            ice_restart: None,
+            data_channel: None,
+        }
+    }
+
+    pub fn ice_restart(self, ice_restart: bool) -> Self {
+        Self {
+            ice_restart: Some(ice_restart).filter(|restart| *restart),
+            ..self
+        }
+    }
+
+    pub fn data_channel(self, data_channel: bool) -> Self {
+        Self {
+            data_channel: Some(data_channel).filter(|enabled| *enabled),
+            ..self
         }
     }
 }
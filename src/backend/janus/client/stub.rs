@@ -0,0 +1,376 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Arc, Mutex, OnceLock, PoisonError,
+    },
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Context};
+use serde_json::{json, Value};
+use tokio::sync::Notify;
+
+use super::{create_handle::OpaqueId, PollResult, SessionId};
+use crate::util::from_base64;
+
+const PLUGIN: &str = "janus.plugin.conference";
+const MAX_EVENTS: usize = 5;
+const POLL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Stub backends by the host of their `stub://` URL so every client of one shares its state.
+static INSTANCES: OnceLock<Mutex<HashMap<String, StubJanus>>> = OnceLock::new();
+
+/// In-process stand-in for Janus with the conference plugin, no media flows.
+#[derive(Clone, Debug, Default)]
+pub struct StubJanus {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    next_id: AtomicI64,
+    sessions: Mutex<HashMap<i64, Session>>,
+}
+
+#[derive(Debug, Default)]
+struct Session {
+    // Opaque ids as sent on attach, base64 encoded.
+    handles: HashMap<i64, Value>,
+    events: VecDeque<Value>,
+    notify: Arc<Notify>,
+}
+
+impl Session {
+    fn push(&mut self, event: Value) {
+        self.events.push_back(event);
+        self.notify.notify_one();
+    }
+}
+
+impl StubJanus {
+    pub fn instance(name: &str) -> Self {
+        INSTANCES
+            .get_or_init(Default::default)
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(name.to_owned())
+            .or_default()
+            .clone()
+    }
+
+    /// Handles a request as Janus would respond to it synchronously.
+    pub fn handle(&self, request: Value) -> anyhow::Result<Value> {
+        let transaction = request["transaction"].clone();
+        let kind = request["janus"]
+            .as_str()
+            .context("Missing 'janus' in the request")?;
+
+        match kind {
+            "create" => {
+                let id = self.next_id();
+
+                self.sessions().insert(id, Session::default());
+                Ok(json!({ "janus": "success", "transaction": transaction, "data": { "id": id } }))
+            }
+            "attach" => {
+                let id = self.next_id();
+
+                self.with_session(&request, |session| {
+                    session.handles.insert(id, request["opaque_id"].clone());
+                })?;
+
+                Ok(json!({ "janus": "success", "transaction": transaction, "data": { "id": id } }))
+            }
+            "detach" => {
+                let handle_id = id_of(&request, "handle_id")?;
+
+                self.with_session(&request, |session| {
+                    let opaque_id = session.handles.remove(&handle_id);
+
+                    if let Some(opaque_id) = opaque_id.filter(is_stream) {
+                        let event = |kind| {
+                            json!({
+                                "janus": kind,
+                                "session_id": request["session_id"],
+                                "sender": handle_id,
+                                "opaque_id": opaque_id,
+                                "reason": "Close PC",
+                            })
+                        };
+
+                        session.push(event("hangup"));
+                        session.push(event("detached"));
+                    }
+                })?;
+
+                Ok(json!({ "janus": "success", "transaction": transaction }))
+            }
+            "trickle" => {
+                self.with_session(&request, |_| ())?;
+                Ok(json!({ "janus": "ack", "transaction": transaction }))
+            }
+            "message" => {
+                let handle_id = id_of(&request, "handle_id")?;
+
+                self.with_session(&request, |session| {
+                    let opaque_id = session
+                        .handles
+                        .get(&handle_id)
+                        .cloned()
+                        .unwrap_or(Value::Null);
+
+                    let (data, jsep) = plugin_response(&request);
+                    let answered = jsep.is_some();
+
+                    session.push(json!({
+                        "janus": "event",
+                        "transaction": transaction,
+                        "session_id": request["session_id"],
+                        "sender": handle_id,
+                        "opaque_id": opaque_id,
+                        "plugindata": { "plugin": PLUGIN, "data": data },
+                        "jsep": jsep,
+                    }));
+
+                    if answered && is_stream(&opaque_id) {
+                        session.push(json!({
+                            "janus": "webrtcup",
+                            "session_id": request["session_id"],
+                            "sender": handle_id,
+                            "opaque_id": opaque_id,
+                        }));
+                    }
+                })?;
+
+                Ok(json!({ "janus": "ack", "transaction": transaction }))
+            }
+            other => bail!("Unsupported request: {}", other),
+        }
+    }
+
+    /// Long-polls the session's events like Janus does returning a keepalive on timeout.
+    pub async fn poll(&self, session_id: SessionId) -> anyhow::Result<PollResult> {
+        let id = serde_json::to_value(session_id)?
+            .as_i64()
+            .context("Invalid session id")?;
+
+        let timeout = tokio::time::sleep(POLL_TIMEOUT);
+        tokio::pin!(timeout);
+
+        loop {
+            let notify = {
+                let mut sessions = self.sessions();
+
+                let session = match sessions.get_mut(&id) {
+                    Some(session) => session,
+                    None => return Ok(PollResult::SessionNotFound),
+                };
+
+                if !session.events.is_empty() {
+                    let count = session.events.len().min(MAX_EVENTS);
+                    return Ok(PollResult::Events(session.events.drain(..count).collect()));
+                }
+
+                session.notify.clone()
+            };
+
+            tokio::select! {
+                _ = notify.notified() => {}
+                _ = &mut timeout => {
+                    return Ok(PollResult::Events(vec![json!({ "janus": "keepalive" })]));
+                }
+            }
+        }
+    }
+
+    fn next_id(&self) -> i64 {
+        self.inner.next_id.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    fn sessions(&self) -> std::sync::MutexGuard<'_, HashMap<i64, Session>> {
+        self.inner
+            .sessions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn with_session<R>(
+        &self,
+        request: &Value,
+        f: impl FnOnce(&mut Session) -> R,
+    ) -> anyhow::Result<R> {
+        let id = id_of(request, "session_id")?;
+        let mut sessions = self.sessions();
+        let session = sessions
+            .get_mut(&id)
+            .ok_or_else(|| anyhow!("No such session: {}", id))?;
+
+        Ok(f(session))
+    }
+}
+
+fn id_of(request: &Value, key: &str) -> anyhow::Result<i64> {
+    request[key]
+        .as_i64()
+        .with_context(|| format!("Missing '{}' in the request", key))
+}
+
+/// Whether the handle has been attached for an RTC stream rather than as a service one.
+fn is_stream(opaque_id: &Value) -> bool {
+    opaque_id
+        .as_str()
+        .and_then(|opaque_id| from_base64::<Option<OpaqueId>>(opaque_id).ok())
+        .flatten()
+        .is_some()
+}
+
+/// Plugin data and JSEP the conference plugin responds to a message with.
+fn plugin_response(request: &Value) -> (Value, Option<Value>) {
+    let body = &request["body"];
+
+    match body["method"].as_str() {
+        Some("stream.create") | Some("stream.read") => {
+            let jsep = request["jsep"]["sdp"]
+                .as_str()
+                .filter(|_| request["jsep"]["type"] == "offer")
+                .map(|offer| json!({ "type": "answer", "sdp": answer(offer) }));
+
+            (json!({ "status": "200" }), jsep)
+        }
+        Some("stream.upload") => {
+            let uri = format!(
+                "s3://{}/{}.mjr",
+                body["bucket"].as_str().unwrap_or_default(),
+                body["id"].as_str().unwrap_or_default(),
+            );
+
+            let data = json!({ "status": "200", "id": body["id"], "mjr_dumps_uris": [uri] });
+            (data, None)
+        }
+        _ => (json!({ "status": "200" }), None),
+    }
+}
+
+/// Mirrors the offer enough for a browser to accept it as an answer.
+fn answer(offer: &str) -> String {
+    offer
+        .lines()
+        .filter(|line| !line.starts_with("a=candidate") && !line.starts_with("a=end-of-candidates"))
+        .map(|line| match line {
+            "a=sendonly" => "a=recvonly",
+            "a=recvonly" => "a=sendonly",
+            "a=setup:actpass" => "a=setup:active",
+            line => line,
+        })
+        .map(|line| format!("{}\r\n", line))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        backend::janus::client::{
+            create_handle::CreateHandleRequest, create_stream::CreateStreamTransaction,
+            IncomingEvent, JanusClient,
+        },
+        util::to_base64,
+    };
+
+    #[tokio::test]
+    async fn negotiate_and_detach() {
+        let client = JanusClient::new("stub://negotiate").expect("Failed to create client");
+        let session = client
+            .create_session()
+            .await
+            .expect("Failed to create session");
+
+        let opaque_id = OpaqueId {
+            stream_id: crate::db::janus_rtc_stream::Id::random(),
+            room_id: crate::db::room::Id::random(),
+        };
+
+        let handle = client
+            .create_handle(CreateHandleRequest {
+                session_id: session.id,
+                opaque_id: Some(opaque_id),
+            })
+            .await
+            .expect("Failed to create handle");
+
+        let stub = StubJanus::instance("negotiate");
+        let transaction = json!(super::super::Transaction::new(
+            super::super::TransactionKind::CreateStream(CreateStreamTransaction::Http {
+                id: 1,
+                replica_addr: std::net::Ipv4Addr::LOCALHOST.into(),
+            })
+        ))
+        .to_string();
+
+        stub.handle(json!({
+            "janus": "message",
+            "transaction": transaction,
+            "session_id": session.id,
+            "handle_id": handle.id,
+            "body": { "method": "stream.create" },
+            "jsep": { "type": "offer", "sdp": "v=0\r\na=sendonly\r\na=setup:actpass\r\n" },
+        }))
+        .expect("Failed to send message");
+
+        let events = match client.poll(session.id).await.expect("Failed to poll") {
+            PollResult::Events(events) => events,
+            PollResult::SessionNotFound => panic!("Session not found"),
+        };
+
+        let events = events
+            .into_iter()
+            .map(|event| serde_json::from_value::<IncomingEvent>(event).expect("Invalid event"))
+            .collect::<Vec<_>>();
+
+        match &events[..] {
+            [IncomingEvent::Event(response), IncomingEvent::WebRtcUp(_)] => {
+                let sdp = response.jsep.as_ref().expect("Missing jsep")["sdp"]
+                    .as_str()
+                    .expect("Missing sdp")
+                    .to_owned();
+
+                assert_eq!(sdp, "v=0\r\na=recvonly\r\na=setup:active\r\n");
+            }
+            other => panic!("Unexpected events: {:?}", other),
+        }
+
+        stub.handle(json!({
+            "janus": "detach",
+            "transaction": "",
+            "session_id": session.id,
+            "handle_id": handle.id,
+        }))
+        .expect("Failed to detach");
+
+        match client.poll(session.id).await.expect("Failed to poll") {
+            PollResult::Events(events) => {
+                let kinds = events
+                    .iter()
+                    .map(|e| e["janus"].clone())
+                    .collect::<Vec<_>>();
+                assert_eq!(kinds, vec![json!("hangup"), json!("detached")]);
+            }
+            PollResult::SessionNotFound => panic!("Session not found"),
+        }
+
+        // Service handles don't report anything.
+        assert!(!is_stream(&json!(to_base64(&None::<OpaqueId>).unwrap())));
+    }
+
+    #[tokio::test]
+    async fn unknown_session() {
+        let client = JanusClient::new("stub://unknown").expect("Failed to create client");
+        let session_id: SessionId = "42".parse().expect("Invalid session id");
+
+        assert!(matches!(
+            client.poll(session_id).await,
+            Ok(PollResult::SessionNotFound)
+        ));
+    }
+}
@@ -1,16 +1,25 @@
 use crate::trace_id::TraceId;
 
 use super::{
-    create_stream::CreateStreamTransaction, read_stream::ReadStreamTransaction,
-    upload_stream::UploadStreamTransaction,
+    composite::UploadCompositeTransaction, create_stream::CreateStreamTransaction,
+    read_stream::ReadStreamTransaction, upload_stream::UploadStreamTransaction,
 };
 use serde::{Deserialize, Serialize};
 
+/// Version of the transaction schema written by this build.
+///
+/// Transactions come back from Janus to any instance of the service so during a rolling upgrade
+/// they may be handled by a build of another version. Bump it on incompatible changes to the
+/// payload of an existing kind. Transactions without a version were written before it appeared.
+pub const TRANSACTION_VERSION: u16 = 1;
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Transaction {
     #[serde(skip_serializing_if = "Option::is_none")]
     trace_id: Option<TraceId>,
-    #[serde(with = "super::serialize_as_base64")]
+    #[serde(default)]
+    version: u16,
+    #[serde(with = "kind_serde")]
     pub kind: Option<TransactionKind>,
 }
 
@@ -18,6 +27,7 @@ impl Transaction {
     pub fn new(kind: TransactionKind) -> Self {
         Self {
             trace_id: TraceId::get(),
+            version: TRANSACTION_VERSION,
             kind: Some(kind),
         }
     }
@@ -25,6 +35,7 @@ impl Transaction {
     pub fn only_id() -> Self {
         Self {
             trace_id: TraceId::get(),
+            version: TRANSACTION_VERSION,
             kind: None,
         }
     }
@@ -32,6 +43,10 @@ impl Transaction {
     pub fn trace_id(&self) -> Option<&TraceId> {
         self.trace_id.as_ref()
     }
+
+    pub fn version(&self) -> u16 {
+        self.version
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -43,5 +58,116 @@ pub enum TransactionKind {
     UpdateWriterConfig,
     UploadStream(UploadStreamTransaction),
     AgentSpeaking,
+    AgentAudioLevel,
     ServicePing,
+    StartComposite,
+    UploadComposite(UploadCompositeTransaction),
+    /// A kind this build can't parse, most likely written by another version.
+    /// Holds the name of the kind.
+    #[serde(skip)]
+    Unknown(String),
+}
+
+impl TransactionKind {
+    fn from_value(value: serde_json::Value) -> Self {
+        if let Ok(kind) = serde_json::from_value(value.clone()) {
+            return kind;
+        }
+
+        let name = match value {
+            serde_json::Value::String(name) => name,
+            serde_json::Value::Object(map) => map
+                .into_iter()
+                .next()
+                .map(|(name, _)| name)
+                .unwrap_or_default(),
+            other => other.to_string(),
+        };
+
+        Self::Unknown(name)
+    }
+}
+
+mod kind_serde {
+    use serde::{de, ser};
+
+    use super::TransactionKind;
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<TransactionKind>, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let value: Option<serde_json::Value> =
+            super::super::serialize_as_base64::deserialize(deserializer)?;
+
+        Ok(value.map(TransactionKind::from_value))
+    }
+
+    pub fn serialize<S>(kind: &Option<TransactionKind>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        super::super::serialize_as_base64::serialize(kind, serializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::util::to_base64;
+
+    fn parse(kind: serde_json::Value, version: Option<u16>) -> Transaction {
+        let mut transaction = json!({ "kind": to_base64(&kind).unwrap() });
+
+        if let Some(version) = version {
+            transaction["version"] = json!(version);
+        }
+
+        serde_json::from_value(transaction).expect("Failed to parse transaction")
+    }
+
+    #[test]
+    fn known_kinds() {
+        let transaction = parse(json!("ServicePing"), None);
+        assert_eq!(transaction.version(), 0);
+        assert!(matches!(
+            transaction.kind,
+            Some(TransactionKind::ServicePing)
+        ));
+
+        let transaction = parse(json!(null), Some(TRANSACTION_VERSION));
+        assert!(transaction.kind.is_none());
+
+        let serialized = serde_json::to_value(Transaction::new(TransactionKind::AgentSpeaking))
+            .expect("Failed to serialize transaction");
+        assert_eq!(serialized["version"], json!(TRANSACTION_VERSION));
+
+        let transaction: Transaction =
+            serde_json::from_value(serialized).expect("Failed to parse transaction");
+        assert!(matches!(
+            transaction.kind,
+            Some(TransactionKind::AgentSpeaking)
+        ));
+    }
+
+    #[test]
+    fn unknown_kinds() {
+        let transaction = parse(json!("SomethingNew"), Some(TRANSACTION_VERSION + 1));
+        assert_eq!(transaction.version(), TRANSACTION_VERSION + 1);
+
+        match transaction.kind {
+            Some(TransactionKind::Unknown(name)) => assert_eq!(name, "SomethingNew"),
+            other => panic!("Unexpected kind: {:?}", other),
+        }
+
+        // A known kind with a payload of an incompatible shape.
+        let transaction = parse(json!({ "UploadStream": 42 }), Some(TRANSACTION_VERSION + 1));
+
+        match transaction.kind {
+            Some(TransactionKind::Unknown(name)) => assert_eq!(name, "UploadStream"),
+            other => panic!("Unexpected kind: {:?}", other),
+        }
+    }
 }
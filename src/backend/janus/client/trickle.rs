@@ -1,10 +1,63 @@
 use serde::Serialize;
 
-use super::{HandleId, Jsep, SessionId};
+use super::{HandleId, IceCandidateSdpItem, SessionId};
 
 #[derive(Serialize, Debug)]
 pub struct TrickleRequest {
     pub session_id: SessionId,
     pub handle_id: HandleId,
-    pub candidate: Jsep,
+    #[serde(flatten)]
+    pub candidates: TrickleCandidates,
+}
+
+/// Janus takes a single candidate as `candidate` and a batch of them as `candidates`.
+#[derive(Serialize, Debug)]
+pub enum TrickleCandidates {
+    #[serde(rename = "candidate")]
+    Single(IceCandidateSdpItem),
+    #[serde(rename = "candidates")]
+    List(Vec<IceCandidateSdpItem>),
+}
+
+impl From<Vec<IceCandidateSdpItem>> for TrickleCandidates {
+    fn from(mut candidates: Vec<IceCandidateSdpItem>) -> Self {
+        if candidates.len() == 1 {
+            Self::Single(candidates.remove(0))
+        } else {
+            Self::List(candidates)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn candidate(n: usize) -> IceCandidateSdpItem {
+        serde_json::from_value(json!({
+            "sdpMid": "0",
+            "sdpMLineIndex": 0,
+            "candidate": format!("candidate:{} 1 UDP 2113667327 198.51.100.7 49203 typ host", n),
+        }))
+        .expect("Failed to parse candidate")
+    }
+
+    #[test]
+    fn serialize_single_and_batch() {
+        let request = |candidates: Vec<IceCandidateSdpItem>| TrickleRequest {
+            session_id: "1".parse().unwrap(),
+            handle_id: "2".parse().unwrap(),
+            candidates: candidates.into(),
+        };
+
+        let single = serde_json::to_value(request(vec![candidate(0)])).unwrap();
+        assert_eq!(single["candidate"]["sdpMid"], "0");
+        assert!(single.get("candidates").is_none());
+
+        let batch = serde_json::to_value(request(vec![candidate(0), candidate(1)])).unwrap();
+        assert_eq!(batch["candidates"].as_array().map(Vec::len), Some(2));
+        assert!(batch.get("candidate").is_none());
+    }
 }
@@ -33,4 +33,10 @@ pub struct UpdateReaderConfigRequestBodyConfigItem {
     pub stream_id: db::rtc::Id,
     pub receive_video: bool,
     pub receive_audio: bool,
+    /// Simulcast substream to relay to the reader, the best one available if missing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spatial_layer: Option<u8>,
+    /// Temporal layer of the substream, the best one available if missing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temporal_layer: Option<u8>,
 }
@@ -32,4 +32,8 @@ pub struct UpdateWriterConfigRequestBodyConfigItem {
     pub send_audio: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub video_remb: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_video_bitrate: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_audio_bitrate: Option<u32>,
 }
@@ -23,6 +23,10 @@ pub struct UploadStreamRequestBody {
     id: db::rtc::Id,
     backend: String,
     bucket: String,
+    /// Algorithm of the uploaded file's checksum the plugin reports back in `checksum`.
+    checksum: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    storage_class: Option<String>,
 }
 
 impl UploadStreamRequestBody {
@@ -32,6 +36,15 @@ impl UploadStreamRequestBody {
             id,
             backend: backend.to_owned(),
             bucket: bucket.to_owned(),
+            checksum: "md5",
+            storage_class: None,
+        }
+    }
+
+    pub fn storage_class(self, storage_class: Option<&str>) -> Self {
+        Self {
+            storage_class: storage_class.map(ToOwned::to_owned),
+            ..self
         }
     }
 }
@@ -8,23 +8,33 @@ use std::{
         atomic::{AtomicBool, Ordering},
         Arc, PoisonError, RwLock,
     },
-    time::Duration,
+    time::Instant,
 };
-use svc_agent::mqtt::Agent;
+use svc_agent::AgentId;
 use tokio::sync::mpsc::UnboundedSender;
 use tracing::{error, warn};
 
 use crate::{
     app::{
-        endpoint::{rtc_signal::CreateResponseData, rtc_stream},
-        error::Error,
+        broker::BrokerAgent,
+        endpoint::{rtc, rtc_signal::CreateResponseData, rtc_stream},
+        error::{Error, ErrorKind},
+        pseudonym::Pseudonyms,
     },
-    db::{agent_connection, janus_backend, janus_rtc_stream},
+    db::{self, agent_connection, janus_backend, janus_rtc_stream},
 };
 
 use super::{
-    client::{IncomingEvent, JanusClient, PollResult, SessionId},
-    waitlist::WaitList,
+    client::{HandleId, IncomingEvent, JanusClient, PollResult, SessionId},
+    connect_registry::ConnectRegistry,
+    correlation::CorrelationDispatcher,
+    event_capture::EventCapture,
+    handle_pool::{HandlePermit, HandlePool},
+    online_handler::init_session,
+    poll_scheduler::PollScheduler,
+    rate_limit::RateLimiter,
+    transport::Transports,
+    trickle_batch::TrickleBatcher,
 };
 
 #[derive(Clone)]
@@ -33,9 +43,17 @@ pub struct Clients {
     events_sink: UnboundedSender<IncomingEvent>,
     group: Option<String>,
     db: sqlx::PgPool,
-    stream_waitlist: WaitList<Result<CreateResponseData, Error>>,
+    correlations: CorrelationDispatcher<Result<CreateResponseData, Error>>,
     ip_addr: IpAddr,
-    mqtt_agent: Option<Agent>,
+    mqtt_agent: Option<BrokerAgent>,
+    transports: Transports,
+    rate_limiter: RateLimiter,
+    handle_pool: HandlePool,
+    pseudonyms: Pseudonyms,
+    event_capture: EventCapture,
+    connects: ConnectRegistry,
+    poll_scheduler: PollScheduler,
+    trickle_batcher: TrickleBatcher,
 }
 
 impl Clients {
@@ -43,21 +61,136 @@ impl Clients {
         events_sink: UnboundedSender<IncomingEvent>,
         group: Option<String>,
         db: sqlx::PgPool,
-        waitlist_epoch_duration: std::time::Duration,
+        correlation_ttl: std::time::Duration,
         ip_addr: IpAddr,
-        mqtt_agent: Option<Agent>,
+        mqtt_agent: Option<BrokerAgent>,
     ) -> Self {
         Self {
             clients: Arc::new(RwLock::new(HashMap::new())),
             events_sink,
             group,
             db,
-            stream_waitlist: WaitList::new(waitlist_epoch_duration),
+            correlations: CorrelationDispatcher::new(correlation_ttl),
             ip_addr,
             mqtt_agent,
+            transports: Transports::default(),
+            rate_limiter: RateLimiter::default(),
+            handle_pool: HandlePool::default(),
+            pseudonyms: Pseudonyms::default(),
+            event_capture: EventCapture::default(),
+            connects: ConnectRegistry::default(),
+            poll_scheduler: PollScheduler::default(),
+            trickle_batcher: TrickleBatcher::default(),
         }
     }
 
+    pub fn with_transports(self, transports: Transports) -> Self {
+        Self { transports, ..self }
+    }
+
+    pub fn with_rate_limiter(self, rate_limiter: RateLimiter) -> Self {
+        Self {
+            rate_limiter,
+            ..self
+        }
+    }
+
+    pub fn with_handle_pool(self, handle_pool: HandlePool) -> Self {
+        Self {
+            handle_pool,
+            ..self
+        }
+    }
+
+    /// Pseudonyms for the events published on behalf of the backends themselves.
+    pub fn with_pseudonyms(self, pseudonyms: Pseudonyms) -> Self {
+        Self { pseudonyms, ..self }
+    }
+
+    pub fn with_event_capture(self, event_capture: EventCapture) -> Self {
+        Self {
+            event_capture,
+            ..self
+        }
+    }
+
+    pub fn with_poll_scheduler(self, poll_scheduler: PollScheduler) -> Self {
+        Self {
+            poll_scheduler,
+            ..self
+        }
+    }
+
+    pub fn with_trickle_batcher(self, trickle_batcher: TrickleBatcher) -> Self {
+        Self {
+            trickle_batcher,
+            ..self
+        }
+    }
+
+    pub fn event_capture(&self) -> &EventCapture {
+        &self.event_capture
+    }
+
+    /// Candidates trickled to the handles waiting to be sent.
+    pub fn trickle_batcher(&self) -> &TrickleBatcher {
+        &self.trickle_batcher
+    }
+
+    /// `rtc.connect` calls creating handles right now.
+    pub fn connects(&self) -> &ConnectRegistry {
+        &self.connects
+    }
+
+    /// Reserves a slot in the backend's handle pool for the room.
+    pub fn reserve_handle(
+        &self,
+        room_id: db::room::Id,
+        backend_id: &AgentId,
+    ) -> Result<HandlePermit, Error> {
+        self.handle_pool
+            .try_acquire(room_id, backend_id)
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::BackendRequestsThrottled,
+                    anyhow!("Too many handles being created on the backend for the room"),
+                )
+                .with_extra("room_id", room_id.to_string())
+                .with_extra("backend_id", backend_id.to_string())
+            })
+    }
+
+    /// Waits until the room is allowed to send one more request to the backend.
+    pub async fn throttle(&self, room_id: db::room::Id, backend_id: &AgentId) -> Result<(), Error> {
+        if self.rate_limiter.acquire(room_id, backend_id).await {
+            return Ok(());
+        }
+
+        let err = Error::new(
+            ErrorKind::BackendRequestsThrottled,
+            anyhow!("Too many requests to the backend for the room"),
+        )
+        .with_extra("room_id", room_id.to_string())
+        .with_extra("backend_id", backend_id.to_string());
+
+        Err(err)
+    }
+
+    /// HTTP client configured for backends of the group.
+    pub fn transport(&self, group: Option<&str>) -> reqwest::Client {
+        self.transports.client(group)
+    }
+
+    /// Backends polled by this instance along with their clients.
+    pub fn backends(&self) -> Vec<(janus_backend::Object, JanusClient)> {
+        self.clients
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|(backend, handle)| (backend.clone(), handle.client.clone()))
+            .collect()
+    }
+
     pub fn clients_count(&self) -> usize {
         self.clients
             .read()
@@ -65,8 +198,13 @@ impl Clients {
             .len()
     }
 
+    /// Whether the backend belongs to the group served by this instance.
+    pub fn owns(&self, backend: &janus_backend::Object) -> bool {
+        backend.group() == self.group.as_deref()
+    }
+
     pub fn get_or_insert(&self, backend: &janus_backend::Object) -> anyhow::Result<JanusClient> {
-        if backend.group() != self.group.as_deref() {
+        if !self.owns(backend) {
             return Err(anyhow!(
                 "Wrong backend_group. Expected: {:?}. Got: {:?}",
                 self.group,
@@ -89,8 +227,8 @@ impl Clients {
             Entry::Occupied(o) => Ok(o.get().client.clone()),
             Entry::Vacant(v) => {
                 let this = self.clone();
-                let mqtt_agent = self.mqtt_agent.clone();
-                let client = JanusClient::new(backend.janus_url())?;
+                let client =
+                    JanusClient::with_http(self.transport(backend.group()), backend.janus_url())?;
                 let session_id = backend.session_id();
                 let is_cancelled = Arc::new(AtomicBool::new(false));
                 v.insert(ClientHandle {
@@ -100,23 +238,14 @@ impl Clients {
                 });
                 tokio::task::spawn({
                     let client = client.clone();
-                    let db = self.db.clone();
                     async move {
                         let sink = this.events_sink.clone();
                         let _guard = PollerGuard {
                             clients: &this,
                             backend: &backend,
                         };
-                        start_polling(
-                            client,
-                            session_id,
-                            sink,
-                            db,
-                            &is_cancelled,
-                            &backend,
-                            mqtt_agent,
-                        )
-                        .await;
+                        start_polling(client, session_id, sink, &is_cancelled, &backend, &this)
+                            .await;
                     }
                 });
                 Ok(client)
@@ -138,8 +267,86 @@ impl Clients {
         }
     }
 
-    pub fn stream_waitlist(&self) -> &WaitList<Result<CreateResponseData, Error>> {
-        &self.stream_waitlist
+    /// Moves the backend to a new session after Janus has restarted with the same agent id.
+    /// Connections and streams of the old session are gone on Janus, so they get dropped
+    /// and the agents are asked to connect again.
+    /// Returns `None` if the backend has already been moved or removed.
+    pub async fn restart_backend(
+        &self,
+        backend: &janus_backend::Object,
+        session_id: SessionId,
+        handle_id: HandleId,
+    ) -> anyhow::Result<Option<janus_backend::Object>> {
+        let mut conn = self.db.acquire().await?;
+        let result = conn
+            .transaction(|conn| {
+                let id = backend.id().clone();
+                let old_session_id = backend.session_id();
+                Box::pin(async move {
+                    let backend = janus_backend::SwapSessionQuery::new(
+                        &id,
+                        old_session_id,
+                        session_id,
+                        handle_id,
+                    )
+                    .execute(conn)
+                    .await?;
+
+                    let backend = match backend {
+                        Some(backend) => backend,
+                        None => return Ok(None),
+                    };
+
+                    let disconnected_rtcs =
+                        agent_connection::disconnect_by_backend(&id, conn).await?;
+                    let stopped_streams =
+                        janus_rtc_stream::stop_running_streams_by_backend(&id, conn).await?;
+
+                    Ok::<_, sqlx::Error>(Some((backend, disconnected_rtcs, stopped_streams)))
+                })
+            })
+            .await?;
+
+        let (new_backend, disconnected_rtcs, stopped_streams) = match result {
+            Some(result) => result,
+            None => return Ok(None),
+        };
+
+        warn!(
+            backend = ?new_backend,
+            old_session_id = %backend.session_id(),
+            disconnected = disconnected_rtcs.len(),
+            "Janus restarted, backend moved to a new session"
+        );
+
+        if let Some(mut agent) = self.mqtt_agent.clone() {
+            publish_stopped_streams(&new_backend, stopped_streams, &self.pseudonyms, &mut agent);
+
+            let mut rtcs_by_room = HashMap::<db::room::Id, Vec<db::rtc::Id>>::new();
+
+            for rtc in disconnected_rtcs {
+                let rtc_ids = rtcs_by_room.entry(rtc.room_id).or_default();
+
+                if !rtc_ids.contains(&rtc.rtc_id) {
+                    rtc_ids.push(rtc.rtc_id);
+                }
+            }
+
+            for (room_id, rtc_ids) in rtcs_by_room {
+                let evt = rtc::renegotiate_event(room_id, rtc_ids, Utc::now());
+
+                if let Err(err) = agent.publish(evt) {
+                    error!(backend = ?new_backend, ?err, "Failed to publish rtc.renegotiate evt");
+                }
+            }
+        }
+
+        Ok(Some(new_backend))
+    }
+
+    /// Requests to the backends awaiting the transaction to come back.
+    pub fn correlations(&self) -> &CorrelationDispatcher<Result<CreateResponseData, Error>> {
+        &self.correlations
     }
 
     pub fn own_ip_addr(&self) -> IpAddr {
@@ -163,71 +370,191 @@ struct PollerGuard<'a> {
 
 impl<'a> Drop for PollerGuard<'a> {
     fn drop(&mut self) {
-        self.clients.remove_client(self.backend)
+        self.clients.remove_client(self.backend);
+        self.clients.poll_scheduler.forget(self.backend.id());
     }
 }
 
+/// Polls the backend in its own task so a slow or failing backend doesn't hold the others up,
+/// see `PollScheduler`.
 async fn start_polling(
     janus_client: JanusClient,
     session_id: SessionId,
     sink: UnboundedSender<IncomingEvent>,
-    db: sqlx::PgPool,
     is_cancelled: &AtomicBool,
     janus_backend: &janus_backend::Object,
-    mqtt_agent: Option<Agent>,
+    clients: &Clients,
 ) {
-    let mut fail_retries_count = 5;
+    let scheduler = &clients.poll_scheduler;
+    let mut failures = 0;
     loop {
-        if fail_retries_count == 0 {
-            if let Err(err) = remove_backend(janus_backend, db, mqtt_agent).await {
-                error!(backend = ?janus_backend, ?err, "Error removing backend");
-            }
+        if failures >= scheduler.max_failures() {
+            // Janus may be restarting, give it the last chance to come back.
+            recover_or_remove(&janus_client, janus_backend, clients).await;
             break;
         }
         if is_cancelled.load(Ordering::SeqCst) {
             break;
         }
-        let poll_result = janus_client.poll(session_id).await;
+
+        let poll_result =
+            {
+                let _slot = scheduler.slot().await;
+                let started_at = Instant::now();
+
+                let poll_result =
+                    match tokio::time::timeout(scheduler.timeout(), janus_client.poll(session_id))
+                        .await
+                    {
+                        Ok(poll_result) => poll_result,
+                        Err(_) => Err(anyhow!("Poll timed out")),
+                    };
+
+                scheduler.observe(
+                    janus_backend.id(),
+                    started_at.elapsed(),
+                    poll_result.is_ok(),
+                );
+
+                poll_result
+            };
+
         match poll_result {
             Ok(PollResult::SessionNotFound) => {
                 warn!(?janus_backend, "Session not found");
-                if let Err(err) = remove_backend(janus_backend, db, mqtt_agent).await {
-                    error!(backend = ?janus_backend, ?err, "Error removing backend");
-                }
+                recover_or_remove(&janus_client, janus_backend, clients).await;
                 break;
             }
             Ok(PollResult::Events(events)) => {
-                fail_retries_count = 5;
-                if let [event] = events.as_slice() {
-                    let keep_alive = event.get("janus").and_then(|x| x.as_str());
-                    if Some("keepalive") == keep_alive {
-                        continue;
-                    }
-                }
-                for event in events {
-                    match serde_json::from_value(event) {
-                        Ok(event) => {
-                            sink.send(event).expect("Receiver must exist");
-                        }
-                        Err(err) => {
-                            warn!(?err, ?janus_backend, "Got unknown event");
+                failures = 0;
+                let is_keep_alive = match events.as_slice() {
+                    [event] => event.get("janus").and_then(|x| x.as_str()) == Some("keepalive"),
+                    _ => false,
+                };
+                if !is_keep_alive {
+                    for event in events {
+                        clients
+                            .event_capture
+                            .capture(&janus_backend.id, &event)
+                            .await;
+
+                        match serde_json::from_value(event) {
+                            Ok(event) => {
+                                sink.send(event).expect("Receiver must exist");
+                            }
+                            Err(err) => {
+                                warn!(?err, ?janus_backend, "Got unknown event");
+                            }
                         }
                     }
                 }
+
+                let pause = scheduler.pause();
+                if !pause.is_zero() {
+                    tokio::time::sleep(pause).await;
+                }
             }
             Err(err) => {
-                error!(?err, ?janus_backend, "Polling error");
-                tokio::time::sleep(Duration::from_millis(500)).await;
-                fail_retries_count -= 1;
+                failures += 1;
+                error!(?err, ?janus_backend, failures, "Polling error");
+                tokio::time::sleep(scheduler.backoff(failures)).await;
+            }
+        }
+    }
+}
+
+/// Janus restarted with the same agent id keeps serving under a fresh session. Moves the backend
+/// to a new session and starts polling it. Removes the backend if Janus can't be reached.
+async fn recover_or_remove(
+    janus_client: &JanusClient,
+    backend: &janus_backend::Object,
+    clients: &Clients,
+) {
+    match recover_session(janus_client, backend, clients).await {
+        Ok(Some(new_backend)) => {
+            if let Err(err) = clients.get_or_insert(&new_backend) {
+                error!(backend = ?new_backend, ?err, "Failed to start polling recovered backend");
+            }
+        }
+        Ok(None) => {
+            // Either the backend has registered again by itself or it's been removed.
+        }
+        Err(err) => {
+            warn!(?backend, ?err, "Failed to recover janus session");
+
+            if let Err(err) = remove_backend(
+                backend,
+                clients.db.clone(),
+                clients.mqtt_agent.clone(),
+                &clients.pseudonyms,
+            )
+            .await
+            {
+                error!(?backend, ?err, "Error removing backend");
             }
         }
     }
 }
 
+async fn recover_session(
+    janus_client: &JanusClient,
+    backend: &janus_backend::Object,
+    clients: &Clients,
+) -> anyhow::Result<Option<janus_backend::Object>> {
+    let current_backend = {
+        let mut conn = clients.db.acquire().await?;
+        janus_backend::FindQuery::new(backend.id())
+            .execute(&mut conn)
+            .await?
+    };
+
+    if current_backend.map(|b| b.session_id()) != Some(backend.session_id()) {
+        return Ok(None);
+    }
+
+    let (session_id, handle_id) = init_session(janus_client).await?;
+
+    clients
+        .restart_backend(backend, session_id, handle_id)
+        .await
+}
+
+fn publish_stopped_streams(
+    backend: &janus_backend::Object,
+    streams: Vec<janus_rtc_stream::StreamWithRoomId>,
+    pseudonyms: &Pseudonyms,
+    agent: &mut BrokerAgent,
+) {
+    let now = Utc::now();
+    for stream in streams {
+        let end_time = match stream
+            .time
+            .as_ref()
+            .map(|t| crate::db::janus_rtc_stream::Time::from(t.clone()))
+        {
+            Some((_start, end)) => match end {
+                std::ops::Bound::Included(t) | std::ops::Bound::Excluded(t) => t,
+                std::ops::Bound::Unbounded => continue,
+            },
+            None => now,
+        };
+        let update_evt = rtc_stream::update_event(
+            stream.room_id,
+            stream.janus_rtc_stream(),
+            end_time,
+            pseudonyms,
+        );
+        if let Err(err) = agent.publish(update_evt) {
+            error!(backend = ?backend, ?err, "Failed to publish rtc_stream.update evt");
+        }
+    }
+}
+
 async fn remove_backend(
     backend: &janus_backend::Object,
     db: sqlx::PgPool,
-    agent: Option<Agent>,
+    agent: Option<BrokerAgent>,
+    pseudonyms: &Pseudonyms,
 ) -> anyhow::Result<()> {
     let mut conn = db.acquire().await?;
     let result = conn
@@ -255,25 +582,7 @@ async fn remove_backend(
 
     match (result, agent) {
         (stopped_rtcs_streams, Some(mut agent)) => {
-            let now = Utc::now();
-            for stream in stopped_rtcs_streams {
-                let end_time = match stream
-                    .time
-                    .as_ref()
-                    .map(|t| crate::db::room::Time::from(t.clone()))
-                {
-                    Some((_start, end)) => match end {
-                        std::ops::Bound::Included(t) | std::ops::Bound::Excluded(t) => t,
-                        std::ops::Bound::Unbounded => continue,
-                    },
-                    None => now,
-                };
-                let update_evt =
-                    rtc_stream::update_event(stream.room_id, stream.janus_rtc_stream(), end_time);
-                if let Err(err) = agent.publish(update_evt) {
-                    error!(backend = ?backend, ?err, "Failed to publish rtc_stream.update evt");
-                }
-            }
+            publish_stopped_streams(backend, stopped_rtcs_streams, pseudonyms, &mut agent);
         }
         (_streams, None) => {
             // not sending events since no agent provided
@@ -282,3 +591,66 @@ async fn remove_backend(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc::unbounded_channel;
+
+    use super::*;
+    use crate::test_helpers::{authz::TestAuthz, context::TestContext, db::TestDb, prelude::*};
+
+    #[sqlx::test]
+    async fn restart_backend(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let agent = TestAgent::new("web", "user", USR_AUDIENCE);
+        let mut conn = db.get_conn().await;
+
+        let backend = shared_helpers::insert_janus_backend(
+            &mut conn,
+            "test",
+            SessionId::random(),
+            HandleId::random(),
+        )
+        .await;
+
+        let room = shared_helpers::insert_room_with_backend_id(&mut conn, backend.id()).await;
+        let rtc = shared_helpers::insert_rtc_with_room(&mut conn, &room).await;
+        shared_helpers::insert_connected_agent(&mut conn, agent.agent_id(), room.id(), rtc.id())
+            .await;
+
+        let mut context = TestContext::new(db, TestAuthz::new()).await;
+        let (tx, _rx) = unbounded_channel();
+        context.with_janus(tx);
+
+        let session_id = SessionId::random();
+        let handle_id = HandleId::random();
+
+        let new_backend = context
+            .janus_clients()
+            .restart_backend(&backend, session_id, handle_id)
+            .await
+            .expect("Failed to restart backend")
+            .expect("Backend not restarted");
+
+        assert_eq!(new_backend.id(), backend.id());
+        assert_eq!(new_backend.session_id(), session_id);
+        assert_eq!(new_backend.handle_id(), handle_id);
+
+        // Connections of the old session are dropped.
+        let connection = agent_connection::FindQuery::new(agent.agent_id(), rtc.id())
+            .execute(&mut conn)
+            .await
+            .expect("Failed to find agent connection");
+
+        assert!(connection.is_none());
+
+        // The backend has already been moved so the old session can't be restarted again.
+        let result = context
+            .janus_clients()
+            .restart_backend(&backend, SessionId::random(), HandleId::random())
+            .await
+            .expect("Failed to restart backend");
+
+        assert!(result.is_none());
+    }
+}
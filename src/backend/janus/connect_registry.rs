@@ -0,0 +1,169 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use svc_agent::AgentId;
+use tokio::sync::watch;
+
+use crate::{app::handle_id::HandleId, db};
+
+type Key = (AgentId, db::rtc::Id);
+
+/// `rtc.connect` calls being handled on this instance. A connect repeated by the same agent
+/// while the first one is still creating the handle waits for it and gets the same handle,
+/// so double clicks don't leave an unused handle on the backend.
+#[derive(Clone, Default)]
+pub struct ConnectRegistry {
+    pending: Arc<Mutex<HashMap<Key, watch::Receiver<Option<HandleId>>>>>,
+}
+
+/// The role of a connect in the registry.
+pub enum Connect {
+    /// No other connect is pending so this one creates the handle.
+    First(ConnectGuard),
+    /// Another connect of the agent to the rtc is in progress.
+    Repeated(PendingConnect),
+}
+
+impl ConnectRegistry {
+    pub fn start(&self, agent_id: &AgentId, rtc_id: db::rtc::Id) -> Connect {
+        let key = (agent_id.to_owned(), rtc_id);
+        let mut pending = self.pending.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if let Some(rx) = pending.get(&key) {
+            return Connect::Repeated(PendingConnect { rx: rx.clone() });
+        }
+
+        let (tx, rx) = watch::channel(None);
+        pending.insert(key.clone(), rx);
+
+        Connect::First(ConnectGuard {
+            registry: self.clone(),
+            key,
+            tx,
+        })
+    }
+
+    fn remove(&self, key: &Key) {
+        self.pending
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(key);
+    }
+}
+
+/// Keeps the connect registered until it's dropped. Repeated connects waiting for a guard
+/// dropped without completing get nothing and go on by themselves.
+pub struct ConnectGuard {
+    registry: ConnectRegistry,
+    key: Key,
+    tx: watch::Sender<Option<HandleId>>,
+}
+
+impl ConnectGuard {
+    /// Hands the created handle over to the repeated connects.
+    pub fn complete(self, handle_id: &HandleId) {
+        self.tx.send_replace(Some(handle_id.clone()));
+    }
+}
+
+impl Drop for ConnectGuard {
+    fn drop(&mut self) {
+        self.registry.remove(&self.key);
+    }
+}
+
+pub struct PendingConnect {
+    rx: watch::Receiver<Option<HandleId>>,
+}
+
+impl PendingConnect {
+    /// The handle of the first connect or `None` if it has failed.
+    pub async fn wait(mut self) -> Option<HandleId> {
+        loop {
+            if let Some(handle_id) = self.rx.borrow_and_update().clone() {
+                return Some(handle_id);
+            }
+
+            self.rx.changed().await.ok()?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        backend::janus::client::{HandleId as JanusHandleId, SessionId},
+        test_helpers::prelude::*,
+    };
+
+    fn handle_id(rtc_id: db::rtc::Id, backend: &TestAgent) -> HandleId {
+        HandleId::new(
+            db::janus_rtc_stream::Id::random(),
+            rtc_id,
+            JanusHandleId::random(),
+            SessionId::random(),
+            backend.agent_id().to_owned(),
+        )
+    }
+
+    #[tokio::test]
+    async fn repeated_connect_gets_the_same_handle() {
+        let registry = ConnectRegistry::default();
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let backend = TestAgent::new("alpha", "janus", SVC_AUDIENCE);
+        let rtc_id = db::rtc::Id::random();
+
+        let guard = match registry.start(agent.agent_id(), rtc_id) {
+            Connect::First(guard) => guard,
+            Connect::Repeated(_) => panic!("Nothing is pending yet"),
+        };
+
+        let pending = match registry.start(agent.agent_id(), rtc_id) {
+            Connect::Repeated(pending) => pending,
+            Connect::First(_) => panic!("The first connect is still pending"),
+        };
+
+        // Connects of other rtcs are on their own.
+        assert!(matches!(
+            registry.start(agent.agent_id(), db::rtc::Id::random()),
+            Connect::First(_)
+        ));
+
+        let handle_id = handle_id(rtc_id, &backend);
+        guard.complete(&handle_id);
+
+        let received = pending.wait().await.expect("No handle received");
+        assert_eq!(received.janus_handle_id(), handle_id.janus_handle_id());
+
+        // Completed connects are not pending anymore.
+        assert!(matches!(
+            registry.start(agent.agent_id(), rtc_id),
+            Connect::First(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn repeated_connect_after_failure() {
+        let registry = ConnectRegistry::default();
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let rtc_id = db::rtc::Id::random();
+
+        let guard = registry.start(agent.agent_id(), rtc_id);
+
+        let pending = match registry.start(agent.agent_id(), rtc_id) {
+            Connect::Repeated(pending) => pending,
+            Connect::First(_) => panic!("The first connect is still pending"),
+        };
+
+        drop(guard);
+        assert!(pending.wait().await.is_none());
+
+        assert!(matches!(
+            registry.start(agent.agent_id(), rtc_id),
+            Connect::First(_)
+        ));
+    }
+}
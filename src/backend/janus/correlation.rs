@@ -0,0 +1,449 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex, PoisonError, Weak,
+    },
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
+use tracing::warn;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    /// No response has come back within the timeout.
+    Expired,
+    /// The correlation has already completed, expired or never existed.
+    Unknown(usize),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// How the response gets to the requester.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Path {
+    /// The response is published to the requester by whoever handles the transaction.
+    Mqtt,
+    /// The handler waits for the response to reply itself.
+    Http,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    Created,
+    Sent,
+}
+
+/// Points to the correlation from a Janus transaction.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct CorrelationRef {
+    pub id: usize,
+    pub replica_addr: IpAddr,
+}
+
+/// Counters since start, exported by the Janus metrics collector.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub in_flight: u64,
+    pub completed: u64,
+    pub expired: u64,
+    pub abandoned: u64,
+    pub unknown: u64,
+}
+
+struct Entry<T> {
+    path: Path,
+    state: State,
+    created_at: Instant,
+    waiter: Option<oneshot::Sender<T>>,
+}
+
+#[derive(Default)]
+struct Counters {
+    completed: AtomicU64,
+    expired: AtomicU64,
+    abandoned: AtomicU64,
+    unknown: AtomicU64,
+}
+
+struct Inner<T> {
+    next_id: AtomicUsize,
+    entries: Mutex<HashMap<usize, Entry<T>>>,
+    ttl: Duration,
+    counters: Counters,
+}
+
+impl<T> Inner<T> {
+    fn entries(&self) -> std::sync::MutexGuard<'_, HashMap<usize, Entry<T>>> {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn mark_sent(&self, id: usize) {
+        // The response may have already completed the correlation.
+        if let Some(entry) = self.entries().get_mut(&id) {
+            entry.state = State::Sent;
+        }
+    }
+
+    fn abandon(&self, id: usize) {
+        if self.entries().remove(&id).is_some() {
+            self.counters.abandoned.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn expire(&self, id: usize) {
+        if self.entries().remove(&id).is_some() {
+            self.counters.expired.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn expire_stale(&self) {
+        let mut entries = self.entries();
+        let before = entries.len();
+
+        entries.retain(|id, entry| {
+            let stale = entry.created_at.elapsed() >= self.ttl;
+
+            if stale {
+                warn!(
+                    %id,
+                    path = ?entry.path,
+                    state = ?entry.state,
+                    "Janus transaction expired without a response"
+                );
+            }
+
+            !stale
+        });
+
+        let expired = before - entries.len();
+        self.counters
+            .expired
+            .fetch_add(expired as u64, Ordering::Relaxed);
+    }
+}
+
+/// Tracks requests sent to Janus until the transaction comes back.
+///
+/// A correlation is created before the request is sent, becomes sent once Janus has acked it and
+/// either completes with the response or expires. Correlations whose request has failed or whose
+/// waiter has gone are dropped right away so nothing is left behind on errors.
+pub struct CorrelationDispatcher<T> {
+    inner: Arc<Inner<T>>,
+}
+
+// Not deriving Clone because T may be not Cloneable but
+// the dispatcher is always possible to clone.
+impl<T> Clone for CorrelationDispatcher<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T: Send + 'static> CorrelationDispatcher<T> {
+    /// Correlations live for `ttl` at least and are expired no later than twice that.
+    pub fn new(ttl: Duration) -> Self {
+        let inner = Arc::new(Inner {
+            next_id: AtomicUsize::new(0),
+            entries: Mutex::new(HashMap::new()),
+            ttl,
+            counters: Counters::default(),
+        });
+
+        tokio::task::spawn(sweep(Arc::downgrade(&inner), ttl));
+
+        Self { inner }
+    }
+
+    pub fn create(&self, path: Path) -> Correlation<T> {
+        let id = self.inner.next_id.fetch_add(1, Ordering::SeqCst);
+
+        let (waiter, receiver) = match path {
+            Path::Mqtt => (None, None),
+            Path::Http => {
+                let (sender, receiver) = oneshot::channel();
+                (Some(sender), Some(receiver))
+            }
+        };
+
+        self.inner.entries().insert(
+            id,
+            Entry {
+                path,
+                state: State::Created,
+                created_at: Instant::now(),
+                waiter,
+            },
+        );
+
+        Correlation {
+            id,
+            inner: Some(self.inner.clone()),
+            receiver,
+        }
+    }
+
+    /// The response is here, hands it over to the waiter if there's one.
+    pub fn complete(&self, id: usize, response: T) -> Result<(), Error> {
+        let entry = self.inner.entries().remove(&id);
+
+        let entry = match entry {
+            Some(entry) => entry,
+            None => {
+                self.inner.counters.unknown.fetch_add(1, Ordering::Relaxed);
+                return Err(Error::Unknown(id));
+            }
+        };
+
+        self.inner
+            .counters
+            .completed
+            .fetch_add(1, Ordering::Relaxed);
+
+        if let Some(waiter) = entry.waiter {
+            if waiter.send(response).is_err() {
+                warn!(%id, "requester is not waiting for the janus response anymore");
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn stats(&self) -> Stats {
+        let counters = &self.inner.counters;
+
+        Stats {
+            in_flight: self.inner.entries().len() as u64,
+            completed: counters.completed.load(Ordering::Relaxed),
+            expired: counters.expired.load(Ordering::Relaxed),
+            abandoned: counters.abandoned.load(Ordering::Relaxed),
+            unknown: counters.unknown.load(Ordering::Relaxed),
+        }
+    }
+}
+
+async fn sweep<T>(inner: Weak<Inner<T>>, period: Duration) {
+    let mut interval = tokio::time::interval(period);
+    // It's ok to miss some ticks.
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+    loop {
+        interval.tick().await;
+
+        match inner.upgrade() {
+            Some(inner) => inner.expire_stale(),
+            None => break,
+        }
+    }
+}
+
+/// A correlation whose request hasn't been sent yet. Dropping it abandons the correlation.
+pub struct Correlation<T> {
+    id: usize,
+    inner: Option<Arc<Inner<T>>>,
+    receiver: Option<oneshot::Receiver<T>>,
+}
+
+impl<T> Correlation<T> {
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Sends the request. The correlation is abandoned if it fails.
+    pub async fn send<F, R, E>(mut self, request: F) -> Result<Pending<T>, E>
+    where
+        F: Future<Output = Result<R, E>>,
+    {
+        request.await?;
+
+        let inner = self.inner.take().expect("Correlation is sent once");
+        inner.mark_sent(self.id);
+
+        Ok(Pending {
+            id: self.id,
+            inner,
+            receiver: self.receiver.take(),
+        })
+    }
+}
+
+impl<T> Drop for Correlation<T> {
+    fn drop(&mut self) {
+        if let Some(inner) = self.inner.take() {
+            inner.abandon(self.id);
+        }
+    }
+}
+
+/// A correlation whose request has reached Janus.
+///
+/// Dropping an MQTT one leaves it to complete or expire. Dropping an HTTP one before the response
+/// has come abandons it since nobody is going to reply.
+pub struct Pending<T> {
+    id: usize,
+    inner: Arc<Inner<T>>,
+    receiver: Option<oneshot::Receiver<T>>,
+}
+
+impl<T> Pending<T> {
+    pub async fn wait(mut self, timeout: Duration) -> Result<T, Error> {
+        let receiver = match self.receiver.take() {
+            Some(receiver) => receiver,
+            None => return Err(Error::Unknown(self.id)),
+        };
+
+        match tokio::time::timeout(timeout, receiver).await {
+            Ok(Ok(response)) => Ok(response),
+            // The sweeper has dropped the waiter.
+            Ok(Err(_)) => Err(Error::Expired),
+            Err(_) => {
+                self.inner.expire(self.id);
+                Err(Error::Expired)
+            }
+        }
+    }
+}
+
+impl<T> Drop for Pending<T> {
+    fn drop(&mut self) {
+        if self.receiver.is_some() {
+            self.inner.abandon(self.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GOOD_ENOUGH: Duration = Duration::from_millis(10);
+
+    async fn ok() -> Result<(), ()> {
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn simple() {
+        let dispatcher = CorrelationDispatcher::new(GOOD_ENOUGH * 100);
+        let correlation = dispatcher.create(Path::Http);
+        let id = correlation.id();
+        let pending = correlation.send(ok()).await.unwrap();
+
+        dispatcher.complete(id, ()).unwrap();
+        assert_eq!(pending.wait(GOOD_ENOUGH).await, Ok(()));
+
+        let stats = dispatcher.stats();
+        assert_eq!(stats.in_flight, 0);
+        assert_eq!(stats.completed, 1);
+    }
+
+    #[tokio::test]
+    async fn wrong_id() {
+        let dispatcher: CorrelationDispatcher<()> = CorrelationDispatcher::new(GOOD_ENOUGH * 100);
+        let correlation = dispatcher.create(Path::Http);
+        let id = correlation.id();
+        let pending = correlation.send(ok()).await.unwrap();
+
+        assert_eq!(dispatcher.complete(id + 1, ()), Err(Error::Unknown(id + 1)));
+        assert_eq!(pending.wait(GOOD_ENOUGH).await, Err(Error::Expired));
+
+        let stats = dispatcher.stats();
+        assert_eq!(stats.in_flight, 0);
+        assert_eq!(stats.expired, 1);
+        assert_eq!(stats.unknown, 1);
+    }
+
+    #[tokio::test]
+    async fn failed_request_abandons_correlation() {
+        let dispatcher: CorrelationDispatcher<()> = CorrelationDispatcher::new(GOOD_ENOUGH * 100);
+        let correlation = dispatcher.create(Path::Http);
+        let id = correlation.id();
+
+        let result = correlation.send(async { Err::<(), _>("boom") }).await;
+        assert!(result.is_err());
+
+        let stats = dispatcher.stats();
+        assert_eq!(stats.in_flight, 0);
+        assert_eq!(stats.abandoned, 1);
+
+        // A late response isn't matched with anything.
+        assert_eq!(dispatcher.complete(id, ()), Err(Error::Unknown(id)));
+    }
+
+    #[tokio::test]
+    async fn dropped_waiter_abandons_correlation() {
+        let dispatcher: CorrelationDispatcher<()> = CorrelationDispatcher::new(GOOD_ENOUGH * 100);
+
+        drop(dispatcher.create(Path::Http));
+
+        let pending = dispatcher.create(Path::Http).send(ok()).await.unwrap();
+        drop(pending);
+
+        let stats = dispatcher.stats();
+        assert_eq!(stats.in_flight, 0);
+        assert_eq!(stats.abandoned, 2);
+    }
+
+    #[tokio::test]
+    async fn mqtt_correlation_outlives_pending() {
+        let dispatcher = CorrelationDispatcher::new(GOOD_ENOUGH * 100);
+        let correlation = dispatcher.create(Path::Mqtt);
+        let id = correlation.id();
+
+        drop(correlation.send(ok()).await.unwrap());
+        assert_eq!(dispatcher.stats().in_flight, 1);
+
+        dispatcher.complete(id, 1000).unwrap();
+
+        let stats = dispatcher.stats();
+        assert_eq!(stats.in_flight, 0);
+        assert_eq!(stats.completed, 1);
+    }
+
+    #[tokio::test]
+    async fn stale_correlations_expire() {
+        let dispatcher: CorrelationDispatcher<()> = CorrelationDispatcher::new(GOOD_ENOUGH);
+        let correlation = dispatcher.create(Path::Mqtt);
+        let id = correlation.id();
+
+        drop(correlation.send(ok()).await.unwrap());
+        tokio::time::sleep(GOOD_ENOUGH * 3).await;
+
+        let stats = dispatcher.stats();
+        assert_eq!(stats.in_flight, 0);
+        assert_eq!(stats.expired, 1);
+        assert_eq!(dispatcher.complete(id, ()), Err(Error::Unknown(id)));
+    }
+
+    #[tokio::test]
+    async fn correct_data() {
+        let dispatcher: CorrelationDispatcher<usize> =
+            CorrelationDispatcher::new(GOOD_ENOUGH * 100);
+
+        let correlation1 = dispatcher.create(Path::Http);
+        let id1 = correlation1.id();
+        let pending1 = correlation1.send(ok()).await.unwrap();
+
+        let correlation2 = dispatcher.create(Path::Http);
+        let id2 = correlation2.id();
+        let pending2 = correlation2.send(ok()).await.unwrap();
+
+        dispatcher.complete(id1, 10).unwrap();
+        dispatcher.complete(id2, 1000).unwrap();
+
+        assert_eq!(pending2.wait(GOOD_ENOUGH).await, Ok(1000));
+        assert_eq!(pending1.wait(GOOD_ENOUGH).await, Ok(10));
+    }
+}
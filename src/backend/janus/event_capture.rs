@@ -0,0 +1,194 @@
+use std::{
+    collections::HashSet,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use serde_json::Value as JsonValue;
+use svc_agent::AgentId;
+use tokio::sync::Mutex;
+use tracing::warn;
+
+use crate::{config::JanusEventCaptureConfig, db, util::from_base64};
+
+use super::client::create_handle::OpaqueId;
+
+/// Stores raw poll events of the rooms with a running capture, see `system.event_capture_start`.
+/// Captures are kept in the DB so the pollers of every instance learn about them within
+/// the refresh interval. Does nothing when not configured.
+#[derive(Clone, Default)]
+pub struct EventCapture {
+    inner: Option<Arc<Inner>>,
+}
+
+struct Inner {
+    db: sqlx::PgPool,
+    config: JanusEventCaptureConfig,
+    rooms: Mutex<Rooms>,
+}
+
+#[derive(Default)]
+struct Rooms {
+    ids: HashSet<db::room::Id>,
+    refreshed_at: Option<Instant>,
+}
+
+impl EventCapture {
+    pub fn new(db: sqlx::PgPool, config: JanusEventCaptureConfig) -> Self {
+        Self {
+            inner: Some(Arc::new(Inner {
+                db,
+                config,
+                rooms: Mutex::new(Rooms::default()),
+            })),
+        }
+    }
+
+    /// Stores the event if it belongs to a room being captured. Failures are only logged
+    /// so that polling goes on.
+    pub async fn capture(&self, backend_id: &AgentId, event: &JsonValue) {
+        let inner = match &self.inner {
+            Some(inner) => inner,
+            None => return,
+        };
+
+        let room_id = match room_id(event) {
+            Some(room_id) => room_id,
+            None => return,
+        };
+
+        if let Err(err) = inner.capture(room_id, backend_id, event).await {
+            warn!(?err, %room_id, "Failed to capture janus event");
+        }
+    }
+
+    /// Makes this instance pick up a started or stopped capture right away.
+    pub async fn invalidate(&self) {
+        if let Some(inner) = &self.inner {
+            inner.rooms.lock().await.refreshed_at = None;
+        }
+    }
+}
+
+impl Inner {
+    async fn capture(
+        &self,
+        room_id: db::room::Id,
+        backend_id: &AgentId,
+        event: &JsonValue,
+    ) -> sqlx::Result<()> {
+        if !self.is_capturing(room_id).await? {
+            return Ok(());
+        }
+
+        let mut conn = self.db.acquire().await?;
+        db::janus_event::insert(room_id, backend_id, event, &mut conn).await
+    }
+
+    async fn is_capturing(&self, room_id: db::room::Id) -> sqlx::Result<bool> {
+        let mut rooms = self.rooms.lock().await;
+        let is_fresh =
+            |refreshed_at: Instant| refreshed_at.elapsed() < self.config.refresh_interval;
+
+        if !rooms.refreshed_at.is_some_and(is_fresh) {
+            let mut conn = self.db.acquire().await?;
+
+            // Trimming goes along with refreshing to keep the table capped without
+            // a separate job.
+            let retained_after = chrono::Utc::now()
+                - chrono::Duration::from_std(self.config.retention)
+                    .unwrap_or_else(|_| chrono::Duration::days(1));
+            db::janus_event::trim(self.config.max_rows, retained_after, &mut conn).await?;
+
+            rooms.ids = db::janus_event::capturing_rooms(&mut conn)
+                .await?
+                .into_iter()
+                .collect();
+            rooms.refreshed_at = Some(Instant::now());
+        }
+
+        Ok(rooms.ids.contains(&room_id))
+    }
+}
+
+/// Handle events carry the room in their opaque id, the rest can't be attributed to a room.
+fn room_id(event: &JsonValue) -> Option<db::room::Id> {
+    let opaque_id = event.get("opaque_id")?.as_str()?;
+    from_base64::<OpaqueId>(opaque_id)
+        .ok()
+        .map(|opaque_id| opaque_id.room_id)
+}
+
+/// Longest capture allowed by the config for the requested duration.
+pub fn capture_duration(config: &JanusEventCaptureConfig, requested: Option<Duration>) -> Duration {
+    requested
+        .unwrap_or(config.max_duration)
+        .min(config.max_duration)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        test_helpers::{db::TestDb, prelude::*},
+        util::to_base64,
+    };
+
+    #[sqlx::test]
+    async fn capture_room_events(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let mut conn = db.get_conn().await;
+
+        let room = shared_helpers::insert_room(&mut conn).await;
+        let other_room = shared_helpers::insert_room(&mut conn).await;
+        let backend_id = TestAgent::new("alpha", "janus", SVC_AUDIENCE)
+            .agent_id()
+            .to_owned();
+
+        db::janus_event::start_capture(
+            room.id(),
+            chrono::Utc::now() + chrono::Duration::minutes(5),
+            &mut conn,
+        )
+        .await
+        .expect("Failed to start capture");
+
+        let capture = EventCapture::new(db.pool.clone(), Default::default());
+
+        let event = |room_id| {
+            let opaque_id = OpaqueId {
+                stream_id: db::janus_rtc_stream::Id::random(),
+                room_id,
+            };
+
+            json!({
+                "janus": "webrtcup",
+                "session_id": 123,
+                "sender": 456,
+                "opaque_id": to_base64(&opaque_id).expect("Failed to encode opaque id"),
+            })
+        };
+
+        capture.capture(&backend_id, &event(room.id())).await;
+        capture.capture(&backend_id, &event(other_room.id())).await;
+        capture
+            .capture(
+                &backend_id,
+                &json!({ "janus": "timeout", "session_id": 123 }),
+            )
+            .await;
+
+        // Only the captured room's handle events are stored.
+        let count = db::janus_event::count(room.id(), &mut conn)
+            .await
+            .expect("Failed to count events");
+        assert_eq!(count, 1);
+
+        let count = db::janus_event::count(other_room.id(), &mut conn)
+            .await
+            .expect("Failed to count events");
+        assert_eq!(count, 0);
+    }
+}
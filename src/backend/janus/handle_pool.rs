@@ -0,0 +1,249 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use prometheus::{IntCounter, IntGauge, IntGaugeVec, Opts, Registry};
+use svc_agent::AgentId;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::warn;
+
+use crate::{config::JanusHandlePoolConfig, db};
+
+/// Number of room slots after which the idle ones get dropped.
+const PRUNE_THRESHOLD: usize = 1024;
+
+/// Slots for handles being created on backends. Each backend's capacity is split into shards
+/// and rooms are spread over them by id, so a room reconnecting in a loop can exhaust
+/// its own shard at most. Within a shard every room is capped as well.
+/// Does nothing when the pool is not configured.
+#[derive(Clone, Default)]
+pub struct HandlePool {
+    inner: Option<Arc<Inner>>,
+}
+
+struct Inner {
+    config: JanusHandlePoolConfig,
+    shards: Mutex<HashMap<(AgentId, usize), Arc<Semaphore>>>,
+    rooms: Mutex<HashMap<(AgentId, db::room::Id), Arc<Semaphore>>>,
+    metrics: Option<Metrics>,
+}
+
+struct Metrics {
+    in_use: IntGaugeVec,
+    rejected: IntCounter,
+}
+
+/// Holds the slot until the handle is created and bound to the agent.
+pub struct HandlePermit {
+    _slots: Option<(OwnedSemaphorePermit, OwnedSemaphorePermit)>,
+    in_use: Option<IntGauge>,
+}
+
+impl Drop for HandlePermit {
+    fn drop(&mut self) {
+        if let Some(in_use) = &self.in_use {
+            in_use.dec();
+        }
+    }
+}
+
+impl HandlePool {
+    pub fn new(config: Option<JanusHandlePoolConfig>, registry: &Registry) -> anyhow::Result<Self> {
+        let config = match config {
+            Some(config) => config,
+            None => return Ok(Self::default()),
+        };
+
+        let in_use = IntGaugeVec::new(
+            Opts::new(
+                "janus_handle_pool_shard_in_use",
+                "Handles being created in a shard of the backend's pool",
+            ),
+            &["backend", "shard"],
+        )?;
+        registry.register(Box::new(in_use.clone()))?;
+
+        let rejected = IntCounter::new(
+            "janus_handle_pool_rejected_total",
+            "Handle creations rejected because the room or its shard is exhausted",
+        )?;
+        registry.register(Box::new(rejected.clone()))?;
+
+        let metrics = Metrics { in_use, rejected };
+
+        Ok(Self {
+            inner: Some(Arc::new(Inner::new(config, Some(metrics)))),
+        })
+    }
+
+    /// Takes a slot to create a handle for the room on the backend.
+    /// Returns `None` if either the room or its shard has run out of slots.
+    pub fn try_acquire(&self, room_id: db::room::Id, backend_id: &AgentId) -> Option<HandlePermit> {
+        let inner = match &self.inner {
+            Some(inner) => inner,
+            None => {
+                return Some(HandlePermit {
+                    _slots: None,
+                    in_use: None,
+                })
+            }
+        };
+
+        let shard = inner.shard(room_id);
+
+        match inner.try_acquire(room_id, backend_id, shard) {
+            Some(slots) => {
+                let in_use = inner.metrics.as_ref().map(|m| {
+                    let gauge = m
+                        .in_use
+                        .with_label_values(&[&backend_id.to_string(), &shard.to_string()]);
+
+                    gauge.inc();
+                    gauge
+                });
+
+                Some(HandlePermit {
+                    _slots: Some(slots),
+                    in_use,
+                })
+            }
+            None => {
+                if let Some(metrics) = &inner.metrics {
+                    metrics.rejected.inc();
+                }
+
+                warn!(%room_id, %backend_id, shard, "Handle pool exhausted");
+                None
+            }
+        }
+    }
+}
+
+impl Inner {
+    fn new(config: JanusHandlePoolConfig, metrics: Option<Metrics>) -> Self {
+        Self {
+            config,
+            shards: Mutex::default(),
+            rooms: Mutex::default(),
+            metrics,
+        }
+    }
+
+    fn shard(&self, room_id: db::room::Id) -> usize {
+        let mut hasher = DefaultHasher::new();
+        room_id.hash(&mut hasher);
+        (hasher.finish() % self.config.shards.max(1) as u64) as usize
+    }
+
+    fn try_acquire(
+        &self,
+        room_id: db::room::Id,
+        backend_id: &AgentId,
+        shard: usize,
+    ) -> Option<(OwnedSemaphorePermit, OwnedSemaphorePermit)> {
+        let room_slots = {
+            let mut rooms = self.rooms.lock().unwrap_or_else(PoisonError::into_inner);
+
+            if rooms.len() > PRUNE_THRESHOLD {
+                rooms.retain(|_, slots| Arc::strong_count(slots) > 1);
+            }
+
+            rooms
+                .entry((backend_id.to_owned(), room_id))
+                .or_insert_with(|| Arc::new(Semaphore::new(self.config.room_cap)))
+                .clone()
+        };
+
+        let shard_slots = self
+            .shards
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry((backend_id.to_owned(), shard))
+            .or_insert_with(|| Arc::new(Semaphore::new(self.config.shard_size)))
+            .clone();
+
+        let room_permit = room_slots.try_acquire_owned().ok()?;
+        let shard_permit = shard_slots.try_acquire_owned().ok()?;
+        Some((room_permit, shard_permit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::prelude::*;
+
+    fn pool(shards: usize, shard_size: usize, room_cap: usize) -> HandlePool {
+        let config = JanusHandlePoolConfig {
+            shards,
+            shard_size,
+            room_cap,
+        };
+
+        HandlePool {
+            inner: Some(Arc::new(Inner::new(config, None))),
+        }
+    }
+
+    fn room_in_shard(pool: &HandlePool, shard: usize) -> db::room::Id {
+        let inner = pool.inner.as_ref().expect("Pool not configured");
+
+        loop {
+            let room_id = db::room::Id::random();
+
+            if inner.shard(room_id) == shard {
+                break room_id;
+            }
+        }
+    }
+
+    #[test]
+    fn room_and_shard_caps() {
+        let pool = pool(2, 3, 2);
+        let backend = TestAgent::new("alpha", "janus", SVC_AUDIENCE);
+        let backend_id = backend.agent_id();
+        let noisy_room = room_in_shard(&pool, 0);
+        let neighbour_room = room_in_shard(&pool, 0);
+        let other_room = room_in_shard(&pool, 1);
+
+        // The room can't take more than its cap.
+        let noisy_permits = (0..2)
+            .map(|_| pool.try_acquire(noisy_room, backend_id))
+            .collect::<Option<Vec<_>>>()
+            .expect("Handle pool exhausted");
+
+        assert!(pool.try_acquire(noisy_room, backend_id).is_none());
+
+        // The rest of the shard is left for its neighbours.
+        let neighbour_permit = pool
+            .try_acquire(neighbour_room, backend_id)
+            .expect("Handle pool exhausted");
+
+        assert!(pool
+            .try_acquire(room_in_shard(&pool, 0), backend_id)
+            .is_none());
+
+        // Rooms of other shards are not affected.
+        assert!(pool.try_acquire(other_room, backend_id).is_some());
+
+        // Slots get back to the pool along with the permits.
+        drop(noisy_permits);
+        drop(neighbour_permit);
+        assert!(pool.try_acquire(noisy_room, backend_id).is_some());
+    }
+
+    #[test]
+    fn unconfigured() {
+        let pool = HandlePool::default();
+        let backend = TestAgent::new("alpha", "janus", SVC_AUDIENCE);
+        let room_id = db::room::Id::random();
+
+        let permits = (0..100)
+            .map(|_| pool.try_acquire(room_id, backend.agent_id()))
+            .collect::<Option<Vec<_>>>();
+
+        assert!(permits.is_some());
+    }
+}
@@ -6,7 +6,7 @@ use tracing::error;
 
 use crate::db::agent_connection;
 
-use super::client_pool::Clients;
+use super::{client_pool::Clients, rtt};
 
 pub struct Metrics {
     online: IntGauge,
@@ -14,6 +14,10 @@ pub struct Metrics {
     connected_agents: IntGauge,
     load: IntGaugeVec,
     polling_janusses: IntGauge,
+    correlations: IntGaugeVec,
+    disk: IntGaugeVec,
+    rtt: IntGaugeVec,
+    group_rtt: IntGaugeVec,
 }
 
 impl Metrics {
@@ -32,14 +36,50 @@ impl Metrics {
             Opts::new("janus_load", "Janus load metrics"),
             &["kind", "agent"],
         )?;
+        let correlations = IntGaugeVec::new(
+            Opts::new(
+                "janus_correlations",
+                "Requests to Janus in flight and their outcomes since start",
+            ),
+            &["state"],
+        )?;
+        let disk = IntGaugeVec::new(
+            Opts::new(
+                "janus_disk",
+                "Recordings disk usage in percent and whether it's over the watermark",
+            ),
+            &["kind", "agent"],
+        )?;
+        let rtt = IntGaugeVec::new(
+            Opts::new(
+                "janus_rtt",
+                "Round trip of service pings to the backend in milliseconds",
+            ),
+            &["agent"],
+        )?;
+        let group_rtt = IntGaugeVec::new(
+            Opts::new(
+                "janus_group_rtt",
+                "Average and maximum round trips to the backends of the group in milliseconds",
+            ),
+            &["kind", "group"],
+        )?;
         registry.register(Box::new(janus_basic_metrics))?;
         registry.register(Box::new(load.clone()))?;
+        registry.register(Box::new(correlations.clone()))?;
+        registry.register(Box::new(disk.clone()))?;
+        registry.register(Box::new(rtt.clone()))?;
+        registry.register(Box::new(group_rtt.clone()))?;
         Ok(Self {
             online,
             total,
             connected_agents,
             load,
             polling_janusses,
+            correlations,
+            disk,
+            rtt,
+            group_rtt,
         })
     }
 
@@ -90,8 +130,61 @@ impl Metrics {
             agent_load.set(backend_load.taken);
         }
 
+        // Alert on `over_watermark`: recordings on such backends are about to get truncated.
+        let disk_usage = crate::db::janus_backend::disk_usage_for_each_backend(&mut conn)
+            .await
+            .context("Failed to get janus backends disk usage")?;
+        for backend in disk_usage {
+            let label = backend.backend_id.label();
+
+            if let Some(usage) = backend.disk_usage {
+                self.disk
+                    .get_metric_with_label_values(&["usage_percent", label])?
+                    .set((usage * 100.0).round() as i64);
+            }
+
+            self.disk
+                .get_metric_with_label_values(&["over_watermark", label])?
+                .set(backend.disk_full as i64);
+        }
+
+        // Backends which haven't answered the last probe report -1.
+        let rtts = crate::db::janus_backend::rtt_for_each_backend(&mut conn)
+            .await
+            .context("Failed to get janus backends rtt")?;
+        for backend in &rtts {
+            self.rtt
+                .get_metric_with_label_values(&[backend.backend_id.label()])?
+                .set(backend.rtt_ms.map_or(-1, i64::from));
+        }
+
+        for group in rtt::by_group(&rtts) {
+            let label = group.group.as_deref().unwrap_or_default();
+
+            for (kind, value) in [("avg", group.avg_ms), ("max", group.max_ms)] {
+                self.group_rtt
+                    .get_metric_with_label_values(&[kind, label])?
+                    .set(value.map_or(-1, i64::from));
+            }
+        }
+
         self.polling_janusses.set(clients.clients_count() as i64);
 
+        // Expired and abandoned correlations growing steadily point to leaking requests.
+        let stats = clients.correlations().stats();
+
+        for (state, value) in [
+            ("in_flight", stats.in_flight),
+            ("completed", stats.completed),
+            ("expired", stats.expired),
+            ("abandoned", stats.abandoned),
+            ("unknown", stats.unknown),
+        ] {
+            self.correlations
+                .get_metric_with_label_values(&[state])?
+                .set(value as i64);
+        }
+
         Ok(())
     }
 }
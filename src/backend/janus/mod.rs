@@ -1,55 +1,35 @@
-use anyhow::{anyhow, Context as AnyhowContext, Result};
+use std::time::Instant;
+
+use anyhow::anyhow;
+use chrono::Utc;
 use futures::stream;
-use serde::{Deserialize, Serialize};
-use svc_agent::{
-    mqtt::{
-        IncomingRequestProperties, IntoPublishableMessage, OutgoingEvent, OutgoingEventProperties,
-        OutgoingResponse, ResponseStatus, ShortTermTimingProperties,
-    },
-    Addressable, AgentId,
-};
-use svc_error::Error as SvcError;
-use tracing::{error, info, Span};
+use svc_agent::mqtt::IntoPublishableMessage;
+use tracing::{error, info, warn};
 
 use self::client::{
-    create_handle::OpaqueId, transactions::TransactionKind, HandleId, IncomingEvent,
+    create_handle::OpaqueId, detach_handle::DetachHandleRequest, events::WebRtcUpEvent, HandleId,
+    IncomingEvent,
 };
 use crate::{
     app::{
         context::Context,
         endpoint,
         error::{Error as AppError, ErrorExt, ErrorKind as AppErrorKind},
+        handle_id::HandleId as AppHandleId,
+        message_export,
         message_handler::MessageStream,
-        metrics::HistogramExt,
-        API_VERSION,
     },
-    client::conference::ConferenceClient,
-    db::{self, agent_connection, janus_rtc_stream, recording, rtc},
+    db::{self, agent_connection, janus_rtc_stream, room::FindQueryable, rtc},
 };
 
 ////////////////////////////////////////////////////////////////////////////////
 
 pub const JANUS_API_VERSION: &str = "v1";
 
-const ALREADY_RUNNING_STATE: &str = "already_running";
-
-fn handle_response_error<C: Context>(
+pub async fn handle_event<C: Context + Send + Sync>(
     context: &mut C,
-    reqp: &IncomingRequestProperties,
-    err: AppError,
+    event: IncomingEvent,
 ) -> MessageStream {
-    error!(?err, "Failed to handle a response from janus",);
-    let svc_error: SvcError = err.to_svc_error();
-    err.notify_sentry();
-
-    let timing = ShortTermTimingProperties::until_now(context.start_timestamp());
-    let respp = reqp.to_response(svc_error.status_code(), timing);
-    let resp = OutgoingResponse::unicast(svc_error, respp, reqp, API_VERSION);
-    let boxed_resp = Box::new(resp) as Box<dyn IntoPublishableMessage + Send + Sync + 'static>;
-    Box::new(stream::once(std::future::ready(boxed_resp)))
-}
-
-pub async fn handle_event<C: Context>(context: &mut C, event: IncomingEvent) -> MessageStream {
     handle_event_impl(context, event)
         .await
         .unwrap_or_else(|err| {
@@ -59,7 +39,7 @@ pub async fn handle_event<C: Context>(context: &mut C, event: IncomingEvent) ->
         })
 }
 
-async fn handle_event_impl<C: Context>(
+async fn handle_event_impl<C: Context + Send + Sync>(
     context: &mut C,
     payload: IncomingEvent,
 ) -> Result<MessageStream, AppError> {
@@ -67,9 +47,31 @@ async fn handle_event_impl<C: Context>(
         IncomingEvent::WebRtcUp(inev) => {
             let mut conn = context.get_conn().await?;
 
-            agent_connection::UpdateQuery::new(inev.sender, agent_connection::Status::Connected)
-                .execute(&mut conn)
+            let relayed = inev.relayed();
+
+            let maybe_connection = agent_connection::UpdateQuery::new(
+                inev.sender,
+                agent_connection::Status::Connected,
+            )
+            .relayed(relayed)
+            .execute(&mut conn)
+            .await?;
+
+            if let Some(connection) = maybe_connection {
+                if let Err(err) = observe_stream_start(context, &inev, &connection, &mut conn).await
+                {
+                    warn!(?err, "Failed to observe stream start latency");
+                }
+            }
+
+            if let Some(relayed) = relayed {
+                db::room_summary::increment_connection_count(
+                    inev.opaque_id.room_id,
+                    relayed,
+                    &mut conn,
+                )
                 .await?;
+            }
 
             let maybe_rtc_stream =
                 janus_rtc_stream::start(inev.opaque_id.stream_id, &mut conn).await?;
@@ -83,12 +85,17 @@ async fn handle_event_impl<C: Context>(
                 let room = endpoint::helpers::find_room_by_rtc_id(
                     rtc_stream.rtc_id(),
                     endpoint::helpers::RoomTimeRequirement::Open,
+                    endpoint::helpers::AudienceScope::Unrestricted,
                     &mut conn,
                 )
                 .await?;
 
-                let event =
-                    endpoint::rtc_stream::update_event(room.id(), rtc_stream, start_timestamp);
+                let event = endpoint::rtc_stream::update_event(
+                    room.id(),
+                    rtc_stream,
+                    start_timestamp,
+                    &context.pseudonyms(),
+                );
 
                 Ok(Box::new(stream::once(std::future::ready(
                     Box::new(event) as Box<dyn IntoPublishableMessage + Send + Sync + 'static>
@@ -103,339 +110,157 @@ async fn handle_event_impl<C: Context>(
         IncomingEvent::Detached(inev) => {
             handle_hangup_detach(context, inev.opaque_id, inev.sender).await
         }
-        IncomingEvent::Media(_) | IncomingEvent::Timeout(_) | IncomingEvent::SlowLink(_) => {
-            // Ignore these kinds of events.
+        IncomingEvent::SlowLink(inev) => {
+            let mut conn = context.get_conn().await?;
+            db::room_summary::increment_slow_link_count(inev.opaque_id.room_id, &mut conn).await?;
+
             Ok(Box::new(stream::empty()))
         }
-        IncomingEvent::Event(resp) => {
-            match resp.transaction.kind {
-                Some(TransactionKind::AgentLeave) => Ok(Box::new(stream::empty())),
-                Some(TransactionKind::CreateStream(tn)) => {
-                    let jsep = resp.jsep;
-                    let response_data = resp
-                        .plugindata
-                        .data
-                        .as_ref()
-                        .context("Missing 'data' in the response")
-                        .error(AppErrorKind::MessageParsingFailed)?
-                        .get("status")
-                        .context("Missing 'status' in the response")
-                        .error(AppErrorKind::MessageParsingFailed)
-                        .and_then(|status| {
-                            if status == "200" {
-                                Ok(())
-                            } else {
-                                Err(anyhow!("Received {} status", status))
-                                    .error(AppErrorKind::BackendRequestFailed)
-                            }
-                        })
-                        .and_then(|_| {
-                            // Getting answer (as JSEP)
-                            let jsep = jsep
-                                .context("Missing 'jsep' in the response")
-                                .error(AppErrorKind::MessageParsingFailed)?;
-                            Ok(endpoint::rtc_signal::CreateResponseData::new(Some(jsep)))
-                        });
-
-                    match tn {
-                        client::create_stream::CreateStreamTransaction::Mqtt {
-                            reqp,
-                            start_timestamp,
-                        } => match response_data {
-                            Ok(payload) => {
-                                let timing =
-                                    ShortTermTimingProperties::until_now(context.start_timestamp());
-
-                                let resp = endpoint::rtc_signal::CreateResponse::unicast(
-                                    payload,
-                                    reqp.to_response(ResponseStatus::OK, timing),
-                                    reqp.as_agent_id(),
-                                    JANUS_API_VERSION,
-                                );
-
-                                context
-                                    .metrics()
-                                    .request_duration
-                                    .rtc_signal_create
-                                    .observe_timestamp(start_timestamp);
-
-                                let boxed_resp = Box::new(resp)
-                                    as Box<dyn IntoPublishableMessage + Send + Sync + 'static>;
-                                Ok(Box::new(stream::once(std::future::ready(boxed_resp)))
-                                    as MessageStream)
-                            }
-                            Err(err) => Ok(handle_response_error(context, &reqp, err)),
-                        },
-                        client::create_stream::CreateStreamTransaction::Http {
-                            id,
-                            replica_addr,
-                        } => {
-                            let own_ip_addr = context.janus_clients().own_ip_addr();
-
-                            if own_ip_addr == replica_addr {
-                                if let Err(err) = context
-                                    .janus_clients()
-                                    .stream_waitlist()
-                                    .fire(id, response_data)
-                                {
-                                    error!(?err, "failed to fire the response to waitlist");
-                                }
-                            } else if let Err(err) = context
-                                .conference_client()
-                                .stream_callback(replica_addr, response_data, id)
-                                .await
-                            {
-                                error!(?err, "failed to callback replica {}", replica_addr,);
-                            }
-
-                            Ok(Box::new(stream::empty()))
-                        }
-                    }
-                }
-                Some(TransactionKind::ReadStream(tn)) => {
-                    let jsep = resp.jsep;
-                    let response_data = resp
-                        .plugindata
-                        .data
-                        .as_ref()
-                        .context("Missing 'data' in the response")
-                        .error(AppErrorKind::MessageParsingFailed)?
-                        .get("status")
-                        .context("Missing 'status' in the response")
-                        .error(AppErrorKind::MessageParsingFailed)
-                        // We fail if the status isn't equal to 200
-                        .and_then(|status| {
-                            if status == "200" {
-                                Ok(())
-                            } else if status == "503" {
-                                Err(anyhow!("Too many agents on Janus instance"))
-                                    .error(AppErrorKind::CapacityExceeded)
-                            } else {
-                                Err(anyhow!("Received {} status", status))
-                                    .error(AppErrorKind::BackendRequestFailed)
-                            }
-                        })
-                        .and_then(|_| {
-                            // Getting answer (as JSEP)
-                            let jsep = jsep
-                                .context("Missing 'jsep' in the response")
-                                .error(AppErrorKind::MessageParsingFailed)?;
-
-                            Ok(endpoint::rtc_signal::CreateResponseData::new(Some(jsep)))
-                        });
-
-                    match tn {
-                        client::read_stream::ReadStreamTransaction::Mqtt {
-                            reqp,
-                            start_timestamp,
-                        } => match response_data {
-                            Ok(payload) => {
-                                let timing =
-                                    ShortTermTimingProperties::until_now(context.start_timestamp());
-
-                                let resp = endpoint::rtc_signal::CreateResponse::unicast(
-                                    payload,
-                                    reqp.to_response(ResponseStatus::OK, timing),
-                                    reqp.as_agent_id(),
-                                    JANUS_API_VERSION,
-                                );
-
-                                let boxed_resp = Box::new(resp)
-                                    as Box<dyn IntoPublishableMessage + Send + Sync + 'static>;
-                                context
-                                    .metrics()
-                                    .request_duration
-                                    .rtc_signal_read
-                                    .observe_timestamp(start_timestamp);
-                                Ok(Box::new(stream::once(std::future::ready(boxed_resp)))
-                                    as MessageStream)
-                            }
-                            Err(err) => Ok(handle_response_error(context, &reqp, err)),
-                        },
-                        client::read_stream::ReadStreamTransaction::Http { id, replica_addr } => {
-                            let own_ip_addr = context.janus_clients().own_ip_addr();
-
-                            if own_ip_addr == replica_addr {
-                                if let Err(err) = context
-                                    .janus_clients()
-                                    .stream_waitlist()
-                                    .fire(id, response_data)
-                                {
-                                    error!(?err, "failed to fire the response to waitlist");
-                                }
-                            } else if let Err(err) = context
-                                .conference_client()
-                                .stream_callback(replica_addr, response_data, id)
-                                .await
-                            {
-                                error!(?err, "failed to callback replica {}", replica_addr,);
-                            }
-
-                            Ok(Box::new(stream::empty()))
-                        }
-                    }
-                }
-                Some(TransactionKind::UpdateReaderConfig) => Ok(Box::new(stream::empty())),
-                Some(TransactionKind::UpdateWriterConfig) => Ok(Box::new(stream::empty())),
-                Some(TransactionKind::ServicePing) => Ok(Box::new(stream::empty())),
-                // Conference Stream has been uploaded to a storage backend (a confirmation)
-                Some(TransactionKind::UploadStream(ref tn)) => {
-                    Span::current().record("rtc_id", tn.rtc_id.to_string().as_str());
-
-                    let plugin_data = resp
-                        .plugindata
-                        .data
-                        .context("Missing 'data' in the response")
-                        .error(AppErrorKind::MessageParsingFailed)?;
-
-                    let upload_stream = async {
-                        let status = plugin_data
-                            .get("status")
-                            .context("Missing 'status' in the response")
-                            .error(AppErrorKind::MessageParsingFailed)?;
-                        match status {
-                            val if val == "200" => Ok(()),
-                            val if val == "404" => {
-                                let mut conn = context.get_conn().await?;
-                                recording::UpdateQuery::new(tn.rtc_id)
-                                    .status(recording::Status::Missing)
-                                    .execute(&mut conn)
-                                    .await?;
-
-                                Err(anyhow!("Janus is missing recording"))
-                                    .error(AppErrorKind::BackendRecordingMissing)
-                            }
-                            _ => Err(anyhow!("Received {} status", status))
-                                .error(AppErrorKind::BackendRequestFailed),
-                        }?;
-                        let rtc_id = plugin_data
-                            .get("id")
-                            .context("Missing 'id' in response")
-                            .error(AppErrorKind::MessageParsingFailed)
-                            .and_then(|val| {
-                                serde_json::from_value::<db::rtc::Id>(val.clone())
-                                    .context("Invalid value for 'id'")
-                                    .error(AppErrorKind::MessageParsingFailed)
-                            })?;
-
-                        // if vacuuming was already started by previous request - just do nothing
-                        let maybe_already_running =
-                            plugin_data.get("state").and_then(|v| v.as_str())
-                                == Some(ALREADY_RUNNING_STATE);
-                        if maybe_already_running {
-                            return Ok(Box::new(stream::empty()) as MessageStream);
-                        }
-
-                        let mjr_dumps_uris = plugin_data
-                            .get("mjr_dumps_uris")
-                            .context("Missing 'mjr_dumps_uris' in response")
-                            .error(AppErrorKind::MessageParsingFailed)
-                            .and_then(|dumps| {
-                                serde_json::from_value::<Vec<String>>(dumps.clone())
-                                    .context("Invalid value for 'dumps_uris'")
-                                    .error(AppErrorKind::MessageParsingFailed)
-                            })?;
-
-                        let mut conn = context.get_conn().await?;
-                        let rtc = rtc::FindQuery::new(rtc_id)
-                            .execute(&mut conn)
-                            .await?
-                            .context("RTC not found")
-                            .error(AppErrorKind::RtcNotFound)?;
-
-                        let room = endpoint::helpers::find_room_by_rtc_id(
-                            rtc.id(),
-                            endpoint::helpers::RoomTimeRequirement::Any,
-                            &mut conn,
-                        )
-                        .await?;
-
-                        recording::UpdateQuery::new(rtc_id)
-                            .status(recording::Status::Ready)
-                            .mjr_dumps_uris(mjr_dumps_uris)
-                            .execute(&mut conn)
-                            .await?;
-
-                        let mut conn = context.get_conn().await?;
-                        let rtcs_with_recs = rtc::ListWithRecordingQuery::new(room.id())
-                            .execute(&mut conn)
-                            .await?;
-
-                        // Ensure that all rtcs with a recording have ready recording.
-                        let room_done = rtcs_with_recs.iter().all(|(_rtc, maybe_recording)| {
-                            match maybe_recording {
-                                None => true,
-                                Some(recording) => {
-                                    recording.status() == db::recording::Status::Ready
-                                }
-                            }
-                        });
-
-                        if !room_done {
-                            return Ok(Box::new(stream::empty()) as MessageStream);
-                        }
-
-                        let recs_with_rtcs =
-                            rtcs_with_recs
-                                .into_iter()
-                                .filter_map(|(rtc, maybe_recording)| {
-                                    let recording = maybe_recording?;
-                                    matches!(recording.status(), db::recording::Status::Ready)
-                                        .then(|| (recording, rtc))
-                                });
-
-                        info!(
-                            class_id = %room.classroom_id(),
-                            room_id = %room.id(),
-                            "sending room.upload event"
-                        );
-                        // Send room.upload event.
-                        let event = endpoint::system::upload_event(context, &room, recs_with_rtcs)?;
-
-                        let event_box = Box::new(event)
-                            as Box<dyn IntoPublishableMessage + Send + Sync + 'static>;
-
-                        Ok(Box::new(stream::once(std::future::ready(event_box))) as MessageStream)
-                    };
-                    let response = upload_stream.await;
-                    context
-                        .metrics()
-                        .request_duration
-                        .upload_stream
-                        .observe_timestamp(tn.start_timestamp);
-                    response
-                }
-                Some(TransactionKind::AgentSpeaking) => {
-                    let data = resp
-                        .plugindata
-                        .data
-                        .context("Missing data int response")
-                        .error(AppErrorKind::MessageParsingFailed)?;
-                    let notification: SpeakingNotification =
-                        serde_json::from_value(data).error(AppErrorKind::MessageParsingFailed)?;
-                    let opaque_id = resp
-                        .opaque_id
-                        .context("Missing opaque id")
-                        .error(AppErrorKind::MessageParsingFailed)?;
-                    let uri = format!("rooms/{}/events", opaque_id.room_id);
-                    let timing = ShortTermTimingProperties::until_now(context.start_timestamp());
-                    let props = OutgoingEventProperties::new("rtc_stream.agent_speaking", timing);
-                    let event = OutgoingEvent::broadcast(notification, props, &uri);
-
-                    Ok(Box::new(stream::once(std::future::ready(
-                        Box::new(event) as Box<dyn IntoPublishableMessage + Send + Sync + 'static>
-                    ))) as MessageStream)
-                }
-                None => Ok(Box::new(stream::empty()) as MessageStream),
-            }
+        IncomingEvent::Media(_) | IncomingEvent::Timeout(_) => {
+            // Ignore these kinds of events.
+            Ok(Box::new(stream::empty()))
         }
+        IncomingEvent::Event(resp) => response::handle_response(context, resp).await,
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-struct SpeakingNotification {
-    speaking: bool,
-    agent_id: AgentId,
+/// Measures how long the connection took to come up since the agent's `rtc.connect`.
+async fn observe_stream_start<C: Context>(
+    context: &C,
+    inev: &WebRtcUpEvent,
+    connection: &agent_connection::Object,
+    conn: &mut sqlx::PgConnection,
+) -> Result<(), AppError> {
+    let latency = match (Utc::now() - connection.created_at()).to_std() {
+        Ok(latency) => latency,
+        // Clock skew between instances.
+        Err(_) => return Ok(()),
+    };
+
+    let room = db::room::FindQuery::new(inev.opaque_id.room_id)
+        .execute(conn)
+        .await?
+        .ok_or_else(|| anyhow!("Room not found"))
+        .error(AppErrorKind::RoomNotFound)?;
+
+    let backend = room
+        .backend_id()
+        .map(|backend_id| backend_id.to_string())
+        .unwrap_or_default();
+
+    context
+        .metrics()
+        .observe_stream_start(room.audience(), &backend, latency);
+
+    if latency >= context.config().backend.stream_start_outlier {
+        warn!(
+            room_id = %room.id(),
+            classroom_id = %room.classroom_id(),
+            audience = room.audience(),
+            rtc_id = %connection.rtc_id(),
+            stream_id = %inev.opaque_id.stream_id,
+            %backend,
+            session_id = %inev.session_id,
+            handle_id = %inev.sender,
+            selected_pair = ?inev.selected_pair,
+            latency_ms = latency.as_millis() as u64,
+            "Slow stream start"
+        );
+    }
+
+    Ok(())
+}
+
+/// Builds `room.upload` event if none of the recordings of the room is in progress anymore.
+pub async fn room_upload_event<C: Context>(
+    context: &C,
+    room: &db::room::Object,
+) -> Result<Option<endpoint::system::RoomUploadEvent>, AppError> {
+    let mut conn = context.get_conn().await?;
+    // Rtcs excluded from recording are left out even if they were recorded before a host
+    // excluded them: vacuum doesn't upload their recordings.
+    let rtcs_with_recs = rtc::ListWithRecordingQuery::new(room.id())
+        .execute(&mut conn)
+        .await?
+        .into_iter()
+        .filter(|(rtc, _recording)| rtc.record())
+        .collect::<Vec<_>>();
+
+    let composite = if room.composite_recording() {
+        db::composite_recording::find(room.id(), &mut conn).await?
+    } else {
+        None
+    };
+
+    // Ensure that all rtcs with a recording are either uploaded or known to be missing.
+    let rtcs_done = rtcs_with_recs
+        .iter()
+        .all(|(_rtc, maybe_recording)| match maybe_recording {
+            None => true,
+            Some(recording) => recording.status() != db::recording::Status::InProgress,
+        });
+
+    let composite_done = match composite {
+        Some(ref composite) => composite.status() != db::recording::Status::InProgress,
+        None => true,
+    };
+
+    if !rtcs_done || !composite_done {
+        return Ok(None);
+    }
+
+    let recs_with_rtcs = rtcs_with_recs
+        .into_iter()
+        .filter_map(|(rtc, maybe_recording)| {
+            let recording = maybe_recording?;
+            matches!(recording.status(), db::recording::Status::Ready).then(|| (recording, rtc))
+        });
+
+    info!(
+        class_id = %room.classroom_id(),
+        room_id = %room.id(),
+        "sending room.upload event"
+    );
+    let messages = message_export::export(context, room).await;
+
+    // Send room.upload event.
+    let event = endpoint::system::upload_event(context, room, recs_with_rtcs, composite, messages)?;
+
+    Ok(Some(event))
+}
+
+/// Detaches the handle on its backend and stops the stream created for it.
+pub(crate) async fn detach_handle<C: Context>(
+    context: &mut C,
+    opaque_id: OpaqueId,
+    handle_id: AppHandleId,
+) -> Result<MessageStream, AppError> {
+    let backend = {
+        let mut conn = context.get_conn().await?;
+
+        db::janus_backend::FindQuery::new(handle_id.backend_id())
+            .execute(&mut conn)
+            .await?
+    };
+
+    if let Some(backend) = backend {
+        let request = DetachHandleRequest {
+            session_id: handle_id.janus_session_id(),
+            handle_id: handle_id.janus_handle_id(),
+        };
+
+        let result = match context.janus_clients().get_or_insert(&backend) {
+            Ok(client) => client.detach_handle(request).await,
+            Err(err) => Err(err),
+        };
+
+        if let Err(err) = result {
+            error!(?err, %handle_id, "Failed to detach the handle");
+        }
+    }
+
+    // Janus sends `detached` event as well but we don't rely on it to free the stream.
+    handle_hangup_detach(context, opaque_id, handle_id.janus_handle_id()).await
 }
 
 async fn handle_hangup_detach<C: Context>(
@@ -447,8 +272,20 @@ async fn handle_hangup_detach<C: Context>(
     // we will find the corresponding stream and send an event w/ updated stream object
     // to the room's topic.
     let mut conn = context.get_conn().await?;
+    db::room_summary::increment_hangup_count(opaque_id.room_id, &mut conn).await?;
+
     let stop_stream_evt = match janus_rtc_stream::stop(opaque_id.stream_id, &mut conn).await? {
         Some(rtc_stream) => {
+            context.active_speakers().silence(
+                opaque_id.room_id,
+                rtc_stream.sent_by(),
+                Instant::now(),
+            );
+
+            context
+                .audio_levels()
+                .forget(opaque_id.room_id, rtc_stream.sent_by());
+
             let start_timestamp = context.start_timestamp();
             let mut conn = context.get_conn().await?;
 
@@ -465,6 +302,7 @@ async fn handle_hangup_detach<C: Context>(
                     opaque_id.room_id,
                     rtc_stream,
                     start_timestamp,
+                    &context.pseudonyms(),
                 );
 
                 let boxed_event =
@@ -491,6 +329,16 @@ async fn handle_hangup_detach<C: Context>(
 ////////////////////////////////////////////////////////////////////////////////
 pub mod client;
 pub mod client_pool;
+pub mod connect_registry;
+pub mod correlation;
+pub mod event_capture;
+pub mod handle_pool;
 pub mod metrics;
+pub mod negotiation;
 pub mod online_handler;
-mod waitlist;
+pub mod poll_scheduler;
+pub mod rate_limit;
+mod response;
+pub mod rtt;
+pub mod transport;
+pub mod trickle_batch;
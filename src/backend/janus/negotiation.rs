@@ -0,0 +1,196 @@
+use anyhow::anyhow;
+use serde_json::Value as JsonValue;
+
+use crate::app::error::{Error as AppError, ErrorKind as AppErrorKind};
+
+use super::client::PayloadTooLarge;
+
+/// The conference plugin responds with this status when it can't pick a codec for the offer.
+const CODEC_MISMATCH_STATUS: &str = "415";
+
+/// Recognizes a codec negotiation failure in the conference plugin response data.
+///
+/// Besides the status the plugin may put `reason`, `offered_codecs` and `supported_codecs`
+/// to the response. Older plugin versions respond with a generic status and only mention
+/// the codec in the `reason` so it's checked as well.
+pub fn codec_mismatch(data: &JsonValue) -> Option<AppError> {
+    let reason = data
+        .get("reason")
+        .and_then(JsonValue::as_str)
+        .unwrap_or_default();
+
+    let is_mismatch = data
+        .get("status")
+        .is_some_and(|s| s == CODEC_MISMATCH_STATUS)
+        || reason.to_lowercase().contains("codec");
+
+    if !is_mismatch {
+        return None;
+    }
+
+    let offered = codecs(data, "offered_codecs");
+    let supported = codecs(data, "supported_codecs");
+    let mut detail = String::from("None of the offered codecs is supported by the backend");
+
+    if !offered.is_empty() {
+        detail.push_str(&format!(", offered = [{}]", offered.join(", ")));
+    }
+
+    if !supported.is_empty() {
+        detail.push_str(&format!(", supported = [{}]", supported.join(", ")));
+    }
+
+    if !reason.is_empty() {
+        detail.push_str(&format!(", reason = {}", reason));
+    }
+
+    let err = AppError::new(AppErrorKind::CodecMismatch, anyhow!(detail))
+        .with_extra("offered_codecs", offered.join(","))
+        .with_extra("supported_codecs", supported.join(","));
+
+    Some(err)
+}
+
+/// Rejects an SDP larger than `max_size` bytes before it's sent to Janus.
+pub fn check_sdp_size(sdp: &str, max_size: Option<usize>) -> Result<(), AppError> {
+    match max_size {
+        Some(max_size) if sdp.len() > max_size => {
+            let detail = format!(
+                "SDP of {} bytes exceeds the limit of {} bytes",
+                sdp.len(),
+                max_size
+            );
+
+            Err(sdp_too_large(detail, sdp))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// ICE username fragment of the offer. Bundled media sections share the session's
+/// credentials so the first one found stands for the whole offer.
+pub fn ice_ufrag(sdp: &str) -> Option<&str> {
+    sdp.lines()
+        .find_map(|line| line.trim_end().strip_prefix("a=ice-ufrag:"))
+        .filter(|ufrag| !ufrag.is_empty())
+}
+
+/// An agent restarts ICE by sending an offer with credentials other than the previous one's.
+pub fn is_ice_restart(previous_ufrag: Option<&str>, ufrag: Option<&str>) -> bool {
+    matches!((previous_ufrag, ufrag), (Some(previous), Some(current)) if previous != current)
+}
+
+/// Maps a failed request to Janus carrying an SDP, telling a too large body apart from
+/// other failures.
+pub fn sdp_request_error(err: anyhow::Error, sdp: &str) -> AppError {
+    match err.downcast_ref::<PayloadTooLarge>() {
+        Some(PayloadTooLarge(size)) => {
+            let detail = format!("The backend rejected a request of {} bytes", size);
+            sdp_too_large(detail, sdp)
+        }
+        None => AppError::new(AppErrorKind::BackendRequestFailed, err),
+    }
+}
+
+fn sdp_too_large(detail: String, sdp: &str) -> AppError {
+    let m_lines = sdp.lines().filter(|line| line.starts_with("m=")).count();
+
+    let detail = format!(
+        "{}, m-lines = {}; remove unused transceivers to trim the offer",
+        detail, m_lines
+    );
+
+    AppError::new(AppErrorKind::SdpTooLarge, anyhow!(detail))
+        .with_extra("sdp_size", sdp.len().to_string())
+        .with_extra("m_lines", m_lines.to_string())
+}
+
+fn codecs(data: &JsonValue, key: &str) -> Vec<String> {
+    data.get(key)
+        .and_then(JsonValue::as_array)
+        .map(|codecs| {
+            codecs
+                .iter()
+                .filter_map(|codec| codec.as_str().map(ToOwned::to_owned))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    const SDP: &str =
+        "v=0\r\nm=audio 9 UDP/TLS/RTP/SAVPF 111\r\nm=video 9 UDP/TLS/RTP/SAVPF 96\r\n";
+
+    #[test]
+    fn reject_large_sdp() {
+        assert!(check_sdp_size(SDP, None).is_ok());
+        assert!(check_sdp_size(SDP, Some(SDP.len())).is_ok());
+
+        let err = check_sdp_size(SDP, Some(16)).expect_err("Large SDP not rejected");
+        assert_eq!(err.kind(), "sdp_too_large");
+        assert_eq!(
+            err.status(),
+            svc_agent::mqtt::ResponseStatus::PAYLOAD_TOO_LARGE
+        );
+    }
+
+    #[test]
+    fn detect_payload_too_large() {
+        let err = sdp_request_error(PayloadTooLarge(1 << 20).into(), SDP);
+        assert_eq!(err.kind(), "sdp_too_large");
+
+        let err = sdp_request_error(anyhow!("Connection refused"), SDP);
+        assert_eq!(err.kind(), "backend_request_failed");
+    }
+
+    #[test]
+    fn detect_ice_restart() {
+        let offer = "v=0\r\nm=audio 9 UDP/TLS/RTP/SAVPF 111\r\na=ice-ufrag:8hhY\r\n";
+        let restart = "v=0\r\nm=audio 9 UDP/TLS/RTP/SAVPF 111\r\na=ice-ufrag:ZaK1\r\n";
+
+        assert_eq!(ice_ufrag(offer), Some("8hhY"));
+        assert_eq!(ice_ufrag(SDP), None);
+
+        assert!(!is_ice_restart(None, ice_ufrag(offer)));
+        assert!(!is_ice_restart(Some("8hhY"), ice_ufrag(offer)));
+        assert!(is_ice_restart(Some("8hhY"), ice_ufrag(restart)));
+    }
+
+    #[test]
+    fn detect_codec_mismatch() {
+        let data = json!({
+            "status": "415",
+            "reason": "No compatible video codec",
+            "offered_codecs": ["h265", "av1"],
+            "supported_codecs": ["vp8", "h264", "opus"],
+        });
+
+        let err = codec_mismatch(&data).expect("Codec mismatch not detected");
+        assert_eq!(err.kind(), "codec_mismatch");
+        assert_eq!(
+            err.detail(),
+            "None of the offered codecs is supported by the backend, offered = [h265, av1], \
+            supported = [vp8, h264, opus], reason = No compatible video codec"
+        );
+
+        let svc_error = err.to_svc_error();
+        assert_eq!(svc_error.extras()["supported_codecs"], "vp8,h264,opus");
+    }
+
+    #[test]
+    fn detect_codec_mismatch_by_reason() {
+        let data = json!({ "status": "500", "reason": "Failed to negotiate codec" });
+        assert!(codec_mismatch(&data).is_some());
+    }
+
+    #[test]
+    fn skip_other_errors() {
+        let data = json!({ "status": "500", "reason": "Internal error" });
+        assert!(codec_mismatch(&data).is_none());
+    }
+}
@@ -5,7 +5,7 @@ use crate::{
     backend::janus::client::{
         create_handle::CreateHandleRequest,
         service_ping::{ServicePingRequest, ServicePingRequestBody},
-        JanusClient,
+        HandleId, JanusClient, SessionId,
     },
     config::JanusRegistry,
     db,
@@ -30,6 +30,7 @@ struct Online {
     group: Option<String>,
     janus_url: String,
     agent_id: AgentId,
+    capabilities: Option<Vec<String>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -150,9 +151,10 @@ pub async fn start_internal_api(
                                 &hyper::body::to_bytes(req.into_body()).await?,
                             )?;
 
-                            clients
-                                .stream_waitlist()
-                                .fire(callback.id, callback.response.map_err(|err| err.into()))?;
+                            clients.correlations().complete(
+                                callback.id,
+                                callback.response.map_err(|err| err.into()),
+                            )?;
 
                             Ok::<_, anyhow::Error>(Response::builder().body(Body::empty())?)
                         };
@@ -187,8 +189,9 @@ async fn handle_online(event: Online, clients: Clients, db: sqlx::PgPool) -> Res
         .execute(&mut conn)
         .await?;
 
-    let janus_client = JanusClient::new(&event.janus_url)?;
-    if let Some(backend) = existing_backend {
+    let janus_client =
+        JanusClient::with_http(clients.transport(event.group.as_deref()), &event.janus_url)?;
+    if let Some(backend) = &existing_backend {
         let ping_response = janus_client
             .service_ping(ServicePingRequest {
                 session_id: backend.session_id(),
@@ -197,35 +200,26 @@ async fn handle_online(event: Online, clients: Clients, db: sqlx::PgPool) -> Res
             })
             .await;
         if ping_response.is_ok() {
-            clients.get_or_insert(&backend)?;
+            clients.get_or_insert(backend)?;
             return Ok(());
         }
     }
 
-    let session = janus_client
-        .create_session()
-        .await
-        .context("CreateSession")?;
-    let handle = janus_client
-        .create_handle(CreateHandleRequest {
-            session_id: session.id,
-            opaque_id: None,
-        })
-        .await
-        .context("Create first handle")?;
-    janus_client
-        .service_ping(ServicePingRequest {
-            session_id: session.id,
-            handle_id: handle.id,
-            body: ServicePingRequestBody::new(),
-        })
-        .await?;
+    let (session_id, handle_id) = init_session(&janus_client).await?;
+
+    // The backend is known but its session is gone, so Janus has restarted
+    // and everything bound to the old session has to be set up again.
+    if let Some(backend) = &existing_backend {
+        clients
+            .restart_backend(backend, session_id, handle_id)
+            .await?;
+    }
 
     let mut conn = db.acquire().await?;
     let mut q = db::janus_backend::UpsertQuery::new(
         &event.agent_id,
-        handle.id,
-        session.id,
+        handle_id,
+        session_id,
         &event.janus_url,
     );
 
@@ -241,12 +235,60 @@ async fn handle_online(event: Online, clients: Clients, db: sqlx::PgPool) -> Res
         q = q.group(group);
     }
 
+    if let Some(capabilities) = event.capabilities.as_deref() {
+        q = q.capabilities(capabilities);
+    }
+
     let backend = q.execute(&mut conn).await?;
 
     clients.get_or_insert(&backend)?;
     Ok(())
 }
 
+/// Registers the in-process stub backend of the `janus_stub` feature as if it came online.
+#[cfg(feature = "janus_stub")]
+pub async fn register_stub(
+    config: &crate::config::JanusStubConfig,
+    group: Option<String>,
+    clients: Clients,
+    db: sqlx::PgPool,
+) -> Result<()> {
+    let event = Online {
+        capacity: config.capacity,
+        balancer_capacity: None,
+        group,
+        janus_url: format!("stub://{}", config.agent_id.label()),
+        agent_id: config.agent_id.clone(),
+        capabilities: None,
+    };
+
+    handle_online(event, clients, db).await
+}
+
+/// Opens a session on Janus along with the service handle used to ping it.
+pub(super) async fn init_session(janus_client: &JanusClient) -> Result<(SessionId, HandleId)> {
+    let session = janus_client
+        .create_session()
+        .await
+        .context("CreateSession")?;
+    let handle = janus_client
+        .create_handle(CreateHandleRequest {
+            session_id: session.id,
+            opaque_id: None,
+        })
+        .await
+        .context("Create first handle")?;
+    janus_client
+        .service_ping(ServicePingRequest {
+            session_id: session.id,
+            handle_id: handle.id,
+            body: ServicePingRequestBody::new(),
+        })
+        .await?;
+
+    Ok((session.id, handle.id))
+}
+
 #[cfg(test)]
 mod test {
     use std::time::Duration;
@@ -294,6 +336,7 @@ mod test {
             balancer_capacity: Some(2),
             group: None,
             janus_url: janus.url.clone(),
+            capabilities: None,
         };
 
         handle_online(event, context.janus_clients(), context.db().clone()).await?;
@@ -341,6 +384,7 @@ mod test {
             balancer_capacity: Some(2),
             group: None,
             janus_url: janus.url.clone(),
+            capabilities: None,
         };
 
         handle_online(event, context.janus_clients(), context.db().clone()).await?;
@@ -0,0 +1,168 @@
+use std::{sync::Arc, time::Duration};
+
+use prometheus::{HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry};
+use rand::Rng;
+use svc_agent::AgentId;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::config::JanusPollConfig;
+
+/// Paces the long polls of the backends polled by the instance. Each backend is polled by its
+/// own task and at most `concurrency` polls are in flight at once. Tasks waiting for a slot get
+/// it in the order they've asked so a busy backend can't starve the others. Pauses and backoffs
+/// are jittered to keep the tasks from polling in lockstep.
+#[derive(Clone, Default)]
+pub struct PollScheduler {
+    config: JanusPollConfig,
+    slots: Option<Arc<Semaphore>>,
+    metrics: Option<Metrics>,
+}
+
+#[derive(Clone)]
+struct Metrics {
+    duration: HistogramVec,
+    failures: IntCounterVec,
+}
+
+impl PollScheduler {
+    pub fn new(config: JanusPollConfig, registry: &Registry) -> anyhow::Result<Self> {
+        let duration = HistogramVec::new(
+            HistogramOpts::new(
+                "janus_poll_duration_seconds",
+                "Duration of long polls of the backend",
+            )
+            .buckets(vec![0.05, 0.25, 1.0, 5.0, 15.0, 30.0, 60.0]),
+            &["agent"],
+        )?;
+        let failures = IntCounterVec::new(
+            Opts::new(
+                "janus_poll_failures_total",
+                "Failed or timed out long polls of the backend",
+            ),
+            &["agent"],
+        )?;
+        registry.register(Box::new(duration.clone()))?;
+        registry.register(Box::new(failures.clone()))?;
+
+        let slots = config
+            .concurrency
+            .map(|concurrency| Arc::new(Semaphore::new(concurrency.max(1))));
+
+        Ok(Self {
+            config,
+            slots,
+            metrics: Some(Metrics { duration, failures }),
+        })
+    }
+
+    /// Waits for a slot to poll a backend, `None` when polls aren't limited.
+    pub async fn slot(&self) -> Option<OwnedSemaphorePermit> {
+        let slots = self.slots.clone()?;
+        slots.acquire_owned().await.ok()
+    }
+
+    /// A poll hanging for longer fails so the slot goes to other backends.
+    pub fn timeout(&self) -> Duration {
+        self.config.timeout
+    }
+
+    /// Failures in a row after which the backend is considered gone.
+    pub fn max_failures(&self) -> u32 {
+        self.config.max_failures
+    }
+
+    pub fn observe(&self, backend_id: &AgentId, elapsed: Duration, succeeded: bool) {
+        if let Some(metrics) = &self.metrics {
+            let agent = backend_id.to_string();
+
+            metrics
+                .duration
+                .with_label_values(&[&agent])
+                .observe(elapsed.as_secs_f64());
+
+            if !succeeded {
+                metrics.failures.with_label_values(&[&agent]).inc();
+            }
+        }
+    }
+
+    /// Drops the metrics of a backend that is no longer polled.
+    pub fn forget(&self, backend_id: &AgentId) {
+        if let Some(metrics) = &self.metrics {
+            let agent = backend_id.to_string();
+            let _ = metrics.duration.remove_label_values(&[&agent]);
+            let _ = metrics.failures.remove_label_values(&[&agent]);
+        }
+    }
+
+    /// Pause before the next poll of a backend.
+    pub fn pause(&self) -> Duration {
+        self.config.interval + self.jitter()
+    }
+
+    /// Pause after the given number of failed polls in a row, growing with each of them.
+    pub fn backoff(&self, failures: u32) -> Duration {
+        self.config.error_backoff * failures.max(1) + self.jitter()
+    }
+
+    fn jitter(&self) -> Duration {
+        if self.config.jitter.is_zero() {
+            return Duration::ZERO;
+        }
+
+        rand::thread_rng().gen_range(Duration::ZERO..=self.config.jitter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> JanusPollConfig {
+        JanusPollConfig {
+            concurrency: Some(1),
+            interval: Duration::from_millis(100),
+            jitter: Duration::from_millis(50),
+            ..JanusPollConfig::default()
+        }
+    }
+
+    #[test]
+    fn jitter_pauses() {
+        let scheduler = PollScheduler::new(config(), &Registry::new()).unwrap();
+
+        for failures in 1..=3 {
+            let pause = scheduler.pause();
+            assert!(pause >= Duration::from_millis(100));
+            assert!(pause <= Duration::from_millis(150));
+
+            let backoff = scheduler.backoff(failures);
+            assert!(backoff >= Duration::from_millis(500) * failures);
+            assert!(backoff <= Duration::from_millis(500) * failures + Duration::from_millis(50));
+        }
+    }
+
+    #[tokio::test]
+    async fn hand_slots_out_in_order() {
+        let scheduler = PollScheduler::new(config(), &Registry::new()).unwrap();
+        let slot = scheduler.slot().await.expect("No slot");
+
+        let waiting = tokio::spawn({
+            let scheduler = scheduler.clone();
+            async move { scheduler.slot().await.is_some() }
+        });
+
+        tokio::task::yield_now().await;
+        assert!(!waiting.is_finished());
+
+        drop(slot);
+        assert!(waiting.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn unlimited_by_default() {
+        let scheduler = PollScheduler::default();
+        assert!(scheduler.slot().await.is_none());
+        assert_eq!(scheduler.max_failures(), 5);
+    }
+}
@@ -0,0 +1,174 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use prometheus::{IntCounter, Registry};
+use svc_agent::AgentId;
+use tracing::warn;
+
+use crate::{config::JanusRateLimitConfig, db};
+
+/// Number of buckets after which the idle ones get dropped.
+const PRUNE_THRESHOLD: usize = 1024;
+
+type Key = (db::room::Id, AgentId);
+
+/// Token buckets for requests to backends, one for each room and backend pair.
+/// Does nothing when the limit is not configured.
+#[derive(Clone, Default)]
+pub struct RateLimiter {
+    inner: Option<Arc<Inner>>,
+}
+
+struct Inner {
+    config: JanusRateLimitConfig,
+    buckets: Mutex<HashMap<Key, Bucket>>,
+    shed: Option<IntCounter>,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(config: Option<JanusRateLimitConfig>, registry: &Registry) -> anyhow::Result<Self> {
+        let config = match config {
+            Some(config) => config,
+            None => return Ok(Self::default()),
+        };
+
+        let shed = IntCounter::new(
+            "janus_shed_requests_total",
+            "Requests to backends rejected by the rate limit",
+        )?;
+        registry.register(Box::new(shed.clone()))?;
+
+        Ok(Self {
+            inner: Some(Arc::new(Inner::new(config, Some(shed)))),
+        })
+    }
+
+    /// Waits for a token to send a request to the backend on behalf of the room.
+    /// Returns `false` if too many requests are already waiting so this one has to be dropped.
+    pub async fn acquire(&self, room_id: db::room::Id, backend_id: &AgentId) -> bool {
+        let inner = match &self.inner {
+            Some(inner) => inner,
+            None => return true,
+        };
+
+        match inner.reserve((room_id, backend_id.to_owned()), Instant::now()) {
+            Some(delay) => {
+                if !delay.is_zero() {
+                    tokio::time::sleep(delay).await;
+                }
+
+                true
+            }
+            None => {
+                if let Some(shed) = &inner.shed {
+                    shed.inc();
+                }
+
+                warn!(%room_id, %backend_id, "Shedding the request to the backend");
+                false
+            }
+        }
+    }
+}
+
+impl Inner {
+    fn new(config: JanusRateLimitConfig, shed: Option<IntCounter>) -> Self {
+        Self {
+            config,
+            buckets: Mutex::default(),
+            shed,
+        }
+    }
+
+    /// Takes a token from the bucket and returns how long to wait before sending the request.
+    /// The bucket may go into debt up to the queue size, waiting is paying the debt off.
+    fn reserve(&self, key: Key, now: Instant) -> Option<Duration> {
+        let rate = f64::from(self.config.rate.max(1));
+        let burst = f64::from(self.config.burst.max(1));
+        let queue_size = f64::from(self.config.queue_size);
+
+        let mut buckets = self.buckets.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if buckets.len() > PRUNE_THRESHOLD {
+            buckets.retain(|_, bucket| {
+                let elapsed = now.saturating_duration_since(bucket.updated_at);
+                bucket.tokens + elapsed.as_secs_f64() * rate < burst
+            });
+        }
+
+        let bucket = buckets.entry(key).or_insert(Bucket {
+            tokens: burst,
+            updated_at: now,
+        });
+
+        let elapsed = now.saturating_duration_since(bucket.updated_at);
+        bucket.tokens = (bucket.tokens + elapsed.as_secs_f64() * rate).min(burst);
+        bucket.updated_at = now;
+
+        if bucket.tokens - 1.0 < -queue_size {
+            return None;
+        }
+
+        bucket.tokens -= 1.0;
+
+        if bucket.tokens >= 0.0 {
+            Some(Duration::ZERO)
+        } else {
+            Some(Duration::from_secs_f64(-bucket.tokens / rate))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::prelude::*;
+
+    fn limiter(rate: u32, burst: u32, queue_size: u32) -> Inner {
+        let config = JanusRateLimitConfig {
+            rate,
+            burst,
+            queue_size,
+        };
+
+        Inner::new(config, None)
+    }
+
+    #[test]
+    fn queue_and_shed() {
+        let limiter = limiter(10, 2, 2);
+        let backend = TestAgent::new("alpha", "janus", SVC_AUDIENCE);
+        let key = (db::room::Id::random(), backend.agent_id().to_owned());
+        let now = Instant::now();
+
+        // Burst goes through immediately.
+        for _ in 0..2 {
+            assert_eq!(limiter.reserve(key.clone(), now), Some(Duration::ZERO));
+        }
+
+        // Then requests get queued.
+        let delay = limiter.reserve(key.clone(), now).expect("Request shed");
+        assert_eq!(delay.as_millis(), 100);
+        let delay = limiter.reserve(key.clone(), now).expect("Request shed");
+        assert_eq!(delay.as_millis(), 200);
+
+        // The queue is full.
+        assert_eq!(limiter.reserve(key.clone(), now), None);
+
+        // Other rooms are not affected.
+        let other_key = (db::room::Id::random(), backend.agent_id().to_owned());
+        assert_eq!(limiter.reserve(other_key, now), Some(Duration::ZERO));
+
+        // The bucket refills with time.
+        let later = now + Duration::from_millis(500);
+        assert_eq!(limiter.reserve(key, later), Some(Duration::ZERO));
+    }
+}
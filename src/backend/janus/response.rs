@@ -0,0 +1,1030 @@
+use std::{net::IpAddr, time::Instant};
+
+use anyhow::{anyhow, Context as AnyhowContext};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::stream;
+use prometheus::Histogram;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use svc_agent::{
+    mqtt::{
+        IncomingRequestProperties, IntoPublishableMessage, OutgoingEvent, OutgoingEventProperties,
+        OutgoingResponse, ResponseStatus, ShortTermTimingProperties,
+    },
+    Addressable, AgentId,
+};
+use svc_error::Error as SvcError;
+use tracing::{error, info, warn, Span};
+
+use super::{
+    client::{
+        composite::UploadCompositeTransaction, create_handle::OpaqueId,
+        create_stream::CreateStreamTransaction, events::EventResponse,
+        read_stream::ReadStreamTransaction, transactions::TransactionKind,
+        upload_stream::UploadStreamTransaction, SessionId,
+    },
+    correlation::CorrelationRef,
+    negotiation, JANUS_API_VERSION,
+};
+use crate::{
+    app::{
+        context::Context,
+        endpoint::{self, rtc_signal::CreateResponseData},
+        error::{Error as AppError, ErrorExt, ErrorKind as AppErrorKind},
+        handle_id::HandleId as AppHandleId,
+        message_handler::MessageStream,
+        metrics::{HistogramExt, Metrics},
+        sdp, signal_history, storage_events, API_VERSION,
+    },
+    client::conference::ConferenceClient,
+    db::{self, recording, rtc},
+    trace_id::TraceId,
+};
+
+////////////////////////////////////////////////////////////////////////////////
+
+const ALREADY_RUNNING_STATE: &str = "already_running";
+
+/// The response without its transaction which is passed to the handler separately.
+struct Response {
+    session_id: SessionId,
+    opaque_id: Option<OpaqueId>,
+    trace_id: Option<TraceId>,
+    data: Option<JsonValue>,
+    jsep: Option<JsonValue>,
+}
+
+/// Handles the response to a transaction of its kind.
+#[async_trait]
+trait TransactionHandler {
+    type Transaction: Send;
+
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        transaction: Self::Transaction,
+        response: Response,
+    ) -> Result<MessageStream, AppError>;
+}
+
+pub async fn handle_response<C: Context + Send + Sync>(
+    context: &mut C,
+    resp: EventResponse,
+) -> Result<MessageStream, AppError> {
+    let EventResponse {
+        transaction,
+        session_id,
+        opaque_id,
+        plugindata,
+        jsep,
+    } = resp;
+
+    let trace_id = transaction.trace_id().cloned();
+    let backend_error = transaction
+        .kind
+        .as_ref()
+        .and_then(backend_error_kind)
+        .map(|kind| {
+            backend_error_query(
+                kind,
+                session_id,
+                opaque_id.as_ref(),
+                trace_id.as_ref(),
+                plugindata.data.as_ref(),
+            )
+        });
+
+    let response = Response {
+        session_id,
+        opaque_id,
+        trace_id: trace_id.clone(),
+        data: plugindata.data,
+        jsep,
+    };
+
+    let result: Result<MessageStream, AppError> = match transaction.kind {
+        Some(TransactionKind::CreateStream(tn)) => {
+            CreateStreamHandler::handle(context, tn, response).await
+        }
+        Some(TransactionKind::ReadStream(tn)) => {
+            ReadStreamHandler::handle(context, tn, response).await
+        }
+        Some(TransactionKind::ServicePing) => {
+            ServicePingHandler::handle(context, (), response).await
+        }
+        Some(TransactionKind::UploadStream(tn)) => {
+            UploadStreamHandler::handle(context, tn, response).await
+        }
+        Some(TransactionKind::UploadComposite(tn)) => {
+            UploadCompositeHandler::handle(context, tn, response).await
+        }
+        Some(TransactionKind::AgentSpeaking) => {
+            AgentSpeakingHandler::handle(context, (), response).await
+        }
+        Some(TransactionKind::AgentAudioLevel) => {
+            AgentAudioLevelHandler::handle(context, (), response).await
+        }
+        Some(TransactionKind::AgentLeave)
+        | Some(TransactionKind::UpdateReaderConfig)
+        | Some(TransactionKind::UpdateWriterConfig)
+        | Some(TransactionKind::StartComposite) => acknowledge(response.data.as_ref()),
+        None => Ok(Box::new(stream::empty())),
+        Some(TransactionKind::Unknown(ref kind)) => {
+            // Nothing can be done about it but it must not stop the events processing.
+            warn!(
+                %kind,
+                version = transaction.version(),
+                "Skipping transaction of unknown kind"
+            );
+
+            Ok(Box::new(stream::empty()))
+        }
+    };
+
+    match backend_error {
+        Some(backend_error) => store_backend_error(context, backend_error, result).await,
+        None => result,
+    }
+}
+
+/// Kind of the failed transaction to store the Janus payload under.
+///
+/// Signaling requests store their failures themselves as those are answered to the agent before
+/// the handler returns. Plugin events aren't transactions of the service.
+fn backend_error_kind(kind: &TransactionKind) -> Option<&'static str> {
+    match kind {
+        TransactionKind::AgentLeave => Some("agent_leave"),
+        TransactionKind::UpdateReaderConfig => Some("update_reader_config"),
+        TransactionKind::UpdateWriterConfig => Some("update_writer_config"),
+        TransactionKind::UploadStream(_) => Some("upload_stream"),
+        TransactionKind::StartComposite => Some("start_composite"),
+        TransactionKind::UploadComposite(_) => Some("upload_composite"),
+        TransactionKind::CreateStream(_)
+        | TransactionKind::ReadStream(_)
+        | TransactionKind::AgentSpeaking
+        | TransactionKind::AgentAudioLevel
+        | TransactionKind::ServicePing
+        | TransactionKind::Unknown(_) => None,
+    }
+}
+
+/// Nothing waits for the result of these requests so only the status is checked.
+fn acknowledge(data: Option<&JsonValue>) -> Result<MessageStream, AppError> {
+    match data.and_then(|data| data.get("status")) {
+        Some(status) if status != "200" => {
+            Err(anyhow!("Received {} status", status)).error(AppErrorKind::BackendRequestFailed)
+        }
+        _ => Ok(Box::new(stream::empty())),
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+fn plugin_data(data: Option<&JsonValue>) -> Result<&JsonValue, AppError> {
+    data.context("Missing 'data' in the response")
+        .error(AppErrorKind::MessageParsingFailed)
+}
+
+fn status(data: &JsonValue) -> Result<&JsonValue, AppError> {
+    data.get("status")
+        .context("Missing 'status' in the response")
+        .error(AppErrorKind::MessageParsingFailed)
+}
+
+/// Both signaling requests answer the offer of the agent.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Signal {
+    Create,
+    Read,
+}
+
+impl Signal {
+    fn kind(self) -> &'static str {
+        match self {
+            Signal::Create => "create_stream",
+            Signal::Read => "read_stream",
+        }
+    }
+
+    fn request_duration(self, metrics: &Metrics) -> &Histogram {
+        match self {
+            Signal::Create => &metrics.request_duration.rtc_signal_create,
+            Signal::Read => &metrics.request_duration.rtc_signal_read,
+        }
+    }
+}
+
+/// Parses the answer to the offer or the error to send to the agent instead.
+fn signal_answer(
+    signal: Signal,
+    data: &JsonValue,
+    jsep: Option<JsonValue>,
+) -> Result<CreateResponseData, AppError> {
+    let status = status(data)?;
+
+    if status != "200" {
+        return Err(signal_error(signal, data, status));
+    }
+
+    // Getting answer (as JSEP)
+    let jsep = jsep
+        .context("Missing 'jsep' in the response")
+        .error(AppErrorKind::MessageParsingFailed)?;
+
+    if let Some(answer) = jsep.get("sdp").and_then(JsonValue::as_str) {
+        info!(
+            signal = signal.kind(),
+            codecs = %sdp::codecs(answer).join(","),
+            "Negotiated codecs"
+        );
+    }
+
+    Ok(CreateResponseData::new(Some(jsep)))
+}
+
+fn signal_error(signal: Signal, data: &JsonValue, status: &JsonValue) -> AppError {
+    // Only readers are limited by the number of agents on the instance.
+    if signal == Signal::Read && status == "503" {
+        return AppError::new(
+            AppErrorKind::CapacityExceeded,
+            anyhow!("Too many agents on Janus instance"),
+        );
+    }
+
+    if let Some(err) = negotiation::codec_mismatch(data) {
+        return err;
+    }
+
+    AppError::new(
+        AppErrorKind::BackendRequestFailed,
+        anyhow!("Received {} status", status),
+    )
+}
+
+/// Transaction of either signaling request.
+enum SignalTransaction {
+    Mqtt {
+        reqp: IncomingRequestProperties,
+        start_timestamp: DateTime<Utc>,
+        handle_id: Option<AppHandleId>,
+        correlation: Option<CorrelationRef>,
+    },
+    Http {
+        id: usize,
+        replica_addr: IpAddr,
+    },
+}
+
+impl SignalTransaction {
+    fn correlation_id(&self) -> String {
+        match self {
+            SignalTransaction::Mqtt { reqp, .. } => reqp.correlation_data().to_owned(),
+            SignalTransaction::Http { id, .. } => id.to_string(),
+        }
+    }
+}
+
+impl From<CreateStreamTransaction> for SignalTransaction {
+    fn from(tn: CreateStreamTransaction) -> Self {
+        match tn {
+            CreateStreamTransaction::Mqtt {
+                reqp,
+                start_timestamp,
+                handle_id,
+                correlation,
+            } => SignalTransaction::Mqtt {
+                reqp,
+                start_timestamp,
+                handle_id,
+                correlation,
+            },
+            CreateStreamTransaction::Http { id, replica_addr } => {
+                SignalTransaction::Http { id, replica_addr }
+            }
+        }
+    }
+}
+
+impl From<ReadStreamTransaction> for SignalTransaction {
+    fn from(tn: ReadStreamTransaction) -> Self {
+        match tn {
+            ReadStreamTransaction::Mqtt {
+                reqp,
+                start_timestamp,
+                handle_id,
+                correlation,
+            } => SignalTransaction::Mqtt {
+                reqp,
+                start_timestamp,
+                handle_id,
+                correlation,
+            },
+            ReadStreamTransaction::Http { id, replica_addr } => {
+                SignalTransaction::Http { id, replica_addr }
+            }
+        }
+    }
+}
+
+async fn handle_signal<C: Context>(
+    context: &mut C,
+    signal: Signal,
+    tn: SignalTransaction,
+    response: Response,
+) -> Result<MessageStream, AppError> {
+    let backend_error = backend_error_query(
+        signal.kind(),
+        response.session_id,
+        response.opaque_id.as_ref(),
+        response.trace_id.as_ref(),
+        response.data.as_ref(),
+    )
+    .correlation_id(tn.correlation_id());
+
+    let data = plugin_data(response.data.as_ref())?;
+    let response_data = signal_answer(signal, data, response.jsep);
+    let response_data = store_backend_error(context, backend_error, response_data).await;
+
+    match tn {
+        SignalTransaction::Mqtt {
+            reqp,
+            start_timestamp,
+            handle_id,
+            correlation,
+        } => {
+            if let Some(correlation) = correlation {
+                let response = clone_response_data(&response_data);
+                complete_correlation(context, correlation, response);
+            }
+
+            if requester_left(context, &reqp, response.opaque_id.as_ref()).await? {
+                return release_handle(context, response.opaque_id, handle_id).await;
+            }
+
+            match response_data {
+                Ok(payload) => {
+                    record_answer(
+                        context,
+                        response.opaque_id.as_ref(),
+                        handle_id.as_ref(),
+                        &reqp,
+                        &payload,
+                    );
+
+                    let timing = ShortTermTimingProperties::until_now(context.start_timestamp());
+
+                    let resp = endpoint::rtc_signal::CreateResponse::unicast(
+                        payload,
+                        reqp.to_response(ResponseStatus::OK, timing),
+                        reqp.as_agent_id(),
+                        JANUS_API_VERSION,
+                    );
+
+                    signal
+                        .request_duration(&context.metrics())
+                        .observe_timestamp(start_timestamp);
+
+                    let boxed_resp =
+                        Box::new(resp) as Box<dyn IntoPublishableMessage + Send + Sync + 'static>;
+                    Ok(Box::new(stream::once(std::future::ready(boxed_resp))) as MessageStream)
+                }
+                Err(err) => Ok(handle_response_error(context, &reqp, err)),
+            }
+        }
+        SignalTransaction::Http { id, replica_addr } => {
+            let correlation = CorrelationRef { id, replica_addr };
+            complete_correlation(context, correlation, response_data);
+
+            Ok(Box::new(stream::empty()))
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+struct CreateStreamHandler;
+
+#[async_trait]
+impl TransactionHandler for CreateStreamHandler {
+    type Transaction = CreateStreamTransaction;
+
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        transaction: Self::Transaction,
+        response: Response,
+    ) -> Result<MessageStream, AppError> {
+        handle_signal(context, Signal::Create, transaction.into(), response).await
+    }
+}
+
+struct ReadStreamHandler;
+
+#[async_trait]
+impl TransactionHandler for ReadStreamHandler {
+    type Transaction = ReadStreamTransaction;
+
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        transaction: Self::Transaction,
+        response: Response,
+    ) -> Result<MessageStream, AppError> {
+        handle_signal(context, Signal::Read, transaction.into(), response).await
+    }
+}
+
+struct ServicePingHandler;
+
+#[async_trait]
+impl TransactionHandler for ServicePingHandler {
+    type Transaction = ();
+
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        _transaction: Self::Transaction,
+        response: Response,
+    ) -> Result<MessageStream, AppError> {
+        // Plugins reporting disk usage send `disk_usage` as the share of the
+        // recordings disk used.
+        let disk_usage = response
+            .data
+            .as_ref()
+            .and_then(|data| data.get("disk_usage"))
+            .and_then(|usage| usage.as_f64());
+
+        if let Some(disk_usage) = disk_usage {
+            store_disk_usage(context, response.session_id, disk_usage as f32).await?;
+        }
+
+        Ok(Box::new(stream::empty()))
+    }
+}
+
+/// Conference Stream has been uploaded to a storage backend (a confirmation).
+struct UploadStreamHandler;
+
+#[async_trait]
+impl TransactionHandler for UploadStreamHandler {
+    type Transaction = UploadStreamTransaction;
+
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        transaction: Self::Transaction,
+        response: Response,
+    ) -> Result<MessageStream, AppError> {
+        Span::current().record("rtc_id", transaction.rtc_id.to_string().as_str());
+
+        let plugin_data = plugin_data(response.data.as_ref())?;
+        let result = upload_stream(context, &transaction, plugin_data).await;
+
+        context
+            .metrics()
+            .request_duration
+            .upload_stream
+            .observe_timestamp(transaction.start_timestamp);
+
+        result
+    }
+}
+
+async fn upload_stream<C: Context>(
+    context: &mut C,
+    tn: &UploadStreamTransaction,
+    plugin_data: &JsonValue,
+) -> Result<MessageStream, AppError> {
+    let status = status(plugin_data)?;
+
+    match status {
+        val if val == "200" => Ok(()),
+        val if val == "404" => {
+            let mut conn = context.get_conn().await?;
+            recording::UpdateQuery::new(tn.rtc_id)
+                .status(recording::Status::Missing)
+                .execute(&mut conn)
+                .await?;
+
+            Err(anyhow!("Janus is missing recording")).error(AppErrorKind::BackendRecordingMissing)
+        }
+        _ => Err(anyhow!("Received {} status", status)).error(AppErrorKind::BackendRequestFailed),
+    }?;
+
+    let rtc_id = plugin_data
+        .get("id")
+        .context("Missing 'id' in response")
+        .error(AppErrorKind::MessageParsingFailed)
+        .and_then(|val| {
+            serde_json::from_value::<db::rtc::Id>(val.clone())
+                .context("Invalid value for 'id'")
+                .error(AppErrorKind::MessageParsingFailed)
+        })?;
+
+    // if vacuuming was already started by previous request - just do nothing
+    let maybe_already_running =
+        plugin_data.get("state").and_then(|v| v.as_str()) == Some(ALREADY_RUNNING_STATE);
+    if maybe_already_running {
+        return Ok(Box::new(stream::empty()) as MessageStream);
+    }
+
+    let mjr_dumps_uris = plugin_data
+        .get("mjr_dumps_uris")
+        .context("Missing 'mjr_dumps_uris' in response")
+        .error(AppErrorKind::MessageParsingFailed)
+        .and_then(|dumps| {
+            serde_json::from_value::<Vec<String>>(dumps.clone())
+                .context("Invalid value for 'dumps_uris'")
+                .error(AppErrorKind::MessageParsingFailed)
+        })?;
+
+    let mut conn = context.get_conn().await?;
+    let rtc = rtc::FindQuery::new(rtc_id)
+        .execute(&mut conn)
+        .await?
+        .context("RTC not found")
+        .error(AppErrorKind::RtcNotFound)?;
+
+    let room = endpoint::helpers::find_room_by_rtc_id(
+        rtc.id(),
+        endpoint::helpers::RoomTimeRequirement::Any,
+        endpoint::helpers::AudienceScope::Unrestricted,
+        &mut conn,
+    )
+    .await?;
+
+    // Plugins not computing checksums don't report any.
+    let checksum = plugin_data
+        .get("checksum")
+        .and_then(|v| v.as_str())
+        .map(ToOwned::to_owned);
+
+    let recording = recording::UpdateQuery::new(rtc_id)
+        .status(recording::Status::Ready)
+        .mjr_dumps_uris(mjr_dumps_uris)
+        .checksum(checksum)
+        .execute(&mut conn)
+        .await?;
+
+    if recording.checksum_mismatch() {
+        storage_events::notify_integrity_failed(context, &room, &recording);
+    }
+
+    room_upload(context, &room).await
+}
+
+/// The composite recording of the room has been uploaded.
+struct UploadCompositeHandler;
+
+#[async_trait]
+impl TransactionHandler for UploadCompositeHandler {
+    type Transaction = UploadCompositeTransaction;
+
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        transaction: Self::Transaction,
+        response: Response,
+    ) -> Result<MessageStream, AppError> {
+        Span::current().record("room_id", transaction.room_id.to_string().as_str());
+
+        let plugin_data = plugin_data(response.data.as_ref())?;
+
+        if plugin_data.get("state").and_then(|v| v.as_str()) == Some(ALREADY_RUNNING_STATE) {
+            return Ok(Box::new(stream::empty()));
+        }
+
+        let status = match status(plugin_data)? {
+            val if val == "200" => recording::Status::Ready,
+            val if val == "404" => {
+                // Source recordings are still worth uploading without the composite.
+                error!(room_id = %transaction.room_id, "Janus is missing composite recording");
+                recording::Status::Missing
+            }
+            status => {
+                return Err(anyhow!("Received {} status", status))
+                    .error(AppErrorKind::BackendRequestFailed)
+            }
+        };
+
+        let room = {
+            let mut conn = context.get_conn().await?;
+            db::composite_recording::set_status(transaction.room_id, status, &mut conn).await?;
+
+            endpoint::helpers::find_room_by_id(
+                transaction.room_id,
+                endpoint::helpers::RoomTimeRequirement::Any,
+                endpoint::helpers::AudienceScope::Unrestricted,
+                &mut conn,
+            )
+            .await?
+        };
+
+        room_upload(context, &room).await
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct SpeakingNotification {
+    speaking: bool,
+    agent_id: AgentId,
+}
+
+struct AgentSpeakingHandler;
+
+#[async_trait]
+impl TransactionHandler for AgentSpeakingHandler {
+    type Transaction = ();
+
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        _transaction: Self::Transaction,
+        response: Response,
+    ) -> Result<MessageStream, AppError> {
+        let data = plugin_data(response.data.as_ref())?;
+        let mut notification: SpeakingNotification =
+            serde_json::from_value(data.clone()).error(AppErrorKind::MessageParsingFailed)?;
+
+        let opaque_id = response
+            .opaque_id
+            .context("Missing opaque id")
+            .error(AppErrorKind::MessageParsingFailed)?;
+
+        context.active_speakers().observe(
+            opaque_id.room_id,
+            &notification.agent_id,
+            notification.speaking,
+            Instant::now(),
+        );
+
+        context.audio_levels().set_speaking(
+            opaque_id.room_id,
+            &notification.agent_id,
+            notification.speaking,
+        );
+
+        if let Some(agent_id) = context.pseudonyms().agent_id(&notification.agent_id) {
+            notification.agent_id = agent_id;
+        }
+
+        let uri = format!("rooms/{}/events", opaque_id.room_id);
+        let timing = ShortTermTimingProperties::until_now(context.start_timestamp());
+        let props = OutgoingEventProperties::new("rtc_stream.agent_speaking", timing);
+        let event = OutgoingEvent::broadcast(notification, props, &uri);
+
+        Ok(Box::new(stream::once(std::future::ready(
+            Box::new(event) as Box<dyn IntoPublishableMessage + Send + Sync + 'static>
+        ))) as MessageStream)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct AudioLevelNotification {
+    agent_id: AgentId,
+    /// Level in dBov, 0 for the loudest sound and 127 for silence.
+    level: u8,
+}
+
+struct AgentAudioLevelHandler;
+
+#[async_trait]
+impl TransactionHandler for AgentAudioLevelHandler {
+    type Transaction = ();
+
+    async fn handle<C: Context + Send + Sync>(
+        context: &mut C,
+        _transaction: Self::Transaction,
+        response: Response,
+    ) -> Result<MessageStream, AppError> {
+        let data = plugin_data(response.data.as_ref())?;
+        let notification: AudioLevelNotification =
+            serde_json::from_value(data.clone()).error(AppErrorKind::MessageParsingFailed)?;
+
+        let opaque_id = response
+            .opaque_id
+            .context("Missing opaque id")
+            .error(AppErrorKind::MessageParsingFailed)?;
+
+        let sample = context.audio_levels().observe(
+            opaque_id.room_id,
+            &notification.agent_id,
+            notification.level,
+            Instant::now(),
+        );
+
+        let mut sample = match sample {
+            Some(sample) => sample,
+            None => return Ok(Box::new(stream::empty())),
+        };
+
+        if let Some(agent_id) = context.pseudonyms().agent_id(&sample.agent_id) {
+            sample.agent_id = agent_id;
+        }
+
+        let uri = format!("rooms/{}/events", opaque_id.room_id);
+        let timing = ShortTermTimingProperties::until_now(context.start_timestamp());
+        let props = OutgoingEventProperties::new("rtc_stream.audio_level", timing);
+        let event = OutgoingEvent::broadcast(sample, props, &uri);
+
+        Ok(Box::new(stream::once(std::future::ready(
+            Box::new(event) as Box<dyn IntoPublishableMessage + Send + Sync + 'static>
+        ))) as MessageStream)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+async fn store_disk_usage<C: Context>(
+    context: &mut C,
+    session_id: SessionId,
+    disk_usage: f32,
+) -> Result<(), AppError> {
+    let disk_full = context
+        .config()
+        .backend
+        .disk_watermark
+        .map_or(false, |watermark| disk_usage > watermark);
+
+    let mut conn = context.get_conn().await?;
+    let backend_id =
+        db::janus_backend::set_disk_usage(session_id, disk_usage, disk_full, &mut conn).await?;
+
+    if disk_full {
+        if let Some(backend_id) = backend_id {
+            warn!(%backend_id, disk_usage, "Backend is over the disk watermark");
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_response_error<C: Context>(
+    context: &mut C,
+    reqp: &IncomingRequestProperties,
+    err: AppError,
+) -> MessageStream {
+    error!(?err, "Failed to handle a response from janus",);
+    let svc_error: SvcError = err.to_svc_error();
+    err.notify_sentry();
+
+    let timing = ShortTermTimingProperties::until_now(context.start_timestamp());
+    let respp = reqp.to_response(svc_error.status_code(), timing);
+    let resp = OutgoingResponse::unicast(svc_error, respp, reqp, API_VERSION);
+    let boxed_resp = Box::new(resp) as Box<dyn IntoPublishableMessage + Send + Sync + 'static>;
+    Box::new(stream::once(std::future::ready(boxed_resp)))
+}
+
+fn backend_error_query<'a>(
+    kind: &'a str,
+    session_id: SessionId,
+    opaque_id: Option<&OpaqueId>,
+    trace_id: Option<&'a TraceId>,
+    payload: Option<&JsonValue>,
+) -> db::backend_error::InsertQuery<'a> {
+    let mut query =
+        db::backend_error::InsertQuery::new(session_id, kind, payload.cloned().unwrap_or_default());
+
+    if let Some(opaque_id) = opaque_id {
+        query = query
+            .room_id(opaque_id.room_id)
+            .rtc_stream_id(opaque_id.stream_id);
+    }
+
+    if let Some(trace_id) = trace_id {
+        query = query.trace_id(trace_id.as_str());
+    }
+
+    query
+}
+
+/// Persists the raw Janus payload of a failed transaction and links it to the error
+/// which is going to be sent to the client.
+async fn store_backend_error<C: Context, T>(
+    context: &mut C,
+    query: db::backend_error::InsertQuery<'_>,
+    result: Result<T, AppError>,
+) -> Result<T, AppError> {
+    let err = match result {
+        Ok(value) => return Ok(value),
+        Err(err) => err,
+    };
+
+    let stored = async {
+        let mut conn = context.get_conn().await?;
+        let backend_error = query.execute(&mut conn).await?;
+        Ok::<_, AppError>(backend_error)
+    };
+
+    match stored.await {
+        Ok(backend_error) => Err(err.with_backend_error(backend_error.id())),
+        Err(store_err) => {
+            error!(?store_err, "Failed to store backend error");
+            Err(err)
+        }
+    }
+}
+
+/// Sends `room.upload` event once all the recordings of the room are uploaded.
+async fn room_upload<C: Context>(
+    context: &mut C,
+    room: &db::room::Object,
+) -> Result<MessageStream, AppError> {
+    match super::room_upload_event(context, room).await? {
+        Some(event) => {
+            let event_box =
+                Box::new(event) as Box<dyn IntoPublishableMessage + Send + Sync + 'static>;
+
+            Ok(Box::new(stream::once(std::future::ready(event_box))) as MessageStream)
+        }
+        None => Ok(Box::new(stream::empty()) as MessageStream),
+    }
+}
+
+/// Hands the response over to the replica which has sent the request to the backend.
+fn complete_correlation<C: Context>(
+    context: &mut C,
+    correlation: CorrelationRef,
+    response: Result<CreateResponseData, AppError>,
+) {
+    let CorrelationRef { id, replica_addr } = correlation;
+
+    if context.janus_clients().own_ip_addr() == replica_addr {
+        if let Err(err) = context
+            .janus_clients()
+            .correlations()
+            .complete(id, response)
+        {
+            warn!(?err, "Janus response has no correlation to complete");
+        }
+
+        return;
+    }
+
+    // Handling the transaction doesn't have to wait for the round trip to the other replica.
+    let conference_client = context.conference_client().clone();
+
+    tokio::task::spawn(async move {
+        if let Err(err) = conference_client
+            .stream_callback(replica_addr, response, id)
+            .await
+        {
+            error!(?err, "failed to callback replica {}", replica_addr);
+        }
+    });
+}
+
+/// The MQTT path publishes the response itself and reports a copy to the correlation.
+fn clone_response_data(
+    response: &Result<CreateResponseData, AppError>,
+) -> Result<CreateResponseData, AppError> {
+    match response {
+        Ok(data) => Ok(data.clone()),
+        Err(err) => Err(AppError::new(err.error_kind(), anyhow!(err.detail()))),
+    }
+}
+
+/// Keeps the answer published over MQTT for `rtc_signal.history`, see `signal_history`.
+fn record_answer<C: Context>(
+    context: &C,
+    opaque_id: Option<&OpaqueId>,
+    handle_id: Option<&AppHandleId>,
+    reqp: &IncomingRequestProperties,
+    payload: &CreateResponseData,
+) {
+    if let (Some(opaque_id), Some(handle_id)) = (opaque_id, handle_id) {
+        signal_history::record_answer(
+            context,
+            opaque_id.room_id,
+            handle_id.rtc_id(),
+            reqp.as_agent_id(),
+            payload.jsep.as_ref(),
+        );
+    }
+}
+
+/// Whether the agent has left the room while the backend was negotiating its stream.
+async fn requester_left<C: Context>(
+    context: &mut C,
+    reqp: &IncomingRequestProperties,
+    opaque_id: Option<&OpaqueId>,
+) -> Result<bool, AppError> {
+    let room_id = match opaque_id {
+        Some(opaque_id) => opaque_id.room_id,
+        None => return Ok(false),
+    };
+
+    let mut conn = context.get_conn().await?;
+    let agents = db::agent::ListQuery::new()
+        .status(db::agent::Status::Ready)
+        .agent_id(reqp.as_agent_id())
+        .room_id(room_id)
+        .limit(1)
+        .execute(&mut conn)
+        .await?;
+
+    Ok(agents.is_empty())
+}
+
+/// There's no one to send the answer to so instead of keeping the handle until Janus
+/// times it out we detach it right away and stop the stream created for it.
+async fn release_handle<C: Context>(
+    context: &mut C,
+    opaque_id: Option<OpaqueId>,
+    handle_id: Option<AppHandleId>,
+) -> Result<MessageStream, AppError> {
+    let (opaque_id, handle_id) = match (opaque_id, handle_id) {
+        (Some(opaque_id), Some(handle_id)) => (opaque_id, handle_id),
+        _ => return Ok(Box::new(stream::empty())),
+    };
+
+    warn!(
+        room_id = %opaque_id.room_id,
+        %handle_id,
+        "Requester has left before the stream got negotiated, releasing the handle"
+    );
+
+    super::detach_handle(context, opaque_id, handle_id).await
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        db::janus_rtc_stream::Id as StreamId,
+        test_helpers::{db::TestDb, prelude::*},
+    };
+
+    #[test]
+    fn signal_answer_with_jsep() {
+        let data = json!({ "status": "200" });
+        let jsep = json!({ "type": "answer", "sdp": "v=0" });
+
+        for signal in [Signal::Create, Signal::Read] {
+            let answer = signal_answer(signal, &data, Some(jsep.clone())).expect("No answer");
+            assert_eq!(answer.jsep, Some(jsep.clone()));
+        }
+    }
+
+    #[test]
+    fn signal_answer_without_jsep() {
+        let data = json!({ "status": "200" });
+        let err = signal_answer(Signal::Create, &data, None).expect_err("Answered without jsep");
+        assert_eq!(err.kind(), "message_parsing_failed");
+
+        let err = signal_answer(Signal::Read, &json!({}), None).expect_err("Answered w/o status");
+        assert_eq!(err.kind(), "message_parsing_failed");
+    }
+
+    #[test]
+    fn signal_answer_errors() {
+        let data = json!({ "status": "503" });
+        let err = signal_answer(Signal::Read, &data, None).expect_err("Read over capacity");
+        assert_eq!(err.kind(), "capacity_exceeded");
+
+        let err = signal_answer(Signal::Create, &data, None).expect_err("Create over capacity");
+        assert_eq!(err.kind(), "backend_request_failed");
+
+        let data = json!({ "status": "415", "reason": "No compatible video codec" });
+
+        for signal in [Signal::Create, Signal::Read] {
+            let err = signal_answer(signal, &data, None).expect_err("Codec mismatch");
+            assert_eq!(err.kind(), "codec_mismatch");
+        }
+    }
+
+    #[sqlx::test]
+    async fn requester_left_the_room(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let room = {
+            let mut conn = db.get_conn().await;
+            let room = shared_helpers::insert_room(&mut conn).await;
+            shared_helpers::insert_agent(&mut conn, agent.agent_id(), room.id()).await;
+            room
+        };
+
+        let mut context = TestContext::new(db, TestAuthz::new()).await;
+
+        let opaque_id = OpaqueId {
+            stream_id: StreamId::random(),
+            room_id: room.id(),
+        };
+
+        let reqp = build_reqp(agent.agent_id(), "rtc_signal.create");
+        let left = requester_left(&mut context, &reqp, Some(&opaque_id))
+            .await
+            .expect("Failed to check the requester");
+        assert!(!left);
+
+        let gone = TestAgent::new("web", "gone", USR_AUDIENCE);
+        let reqp = build_reqp(gone.agent_id(), "rtc_signal.create");
+        let left = requester_left(&mut context, &reqp, Some(&opaque_id))
+            .await
+            .expect("Failed to check the requester");
+        assert!(left);
+
+        // Can't tell without the room so respond as usual.
+        let left = requester_left(&mut context, &reqp, None)
+            .await
+            .expect("Failed to check the requester");
+        assert!(!left);
+    }
+}
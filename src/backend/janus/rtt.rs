@@ -0,0 +1,151 @@
+use std::{collections::BTreeMap, convert::TryFrom, time::Instant};
+
+use futures::future;
+use serde::Serialize;
+use tracing::{error, warn};
+
+use crate::{config::JanusRttConfig, db::janus_backend};
+
+use super::{
+    client::{
+        service_ping::{ServicePingRequest, ServicePingRequestBody},
+        JanusClient,
+    },
+    client_pool::Clients,
+};
+
+/// Periodically measures round trips to the backends of the instance's group.
+pub async fn start_prober(clients: Clients, db: sqlx::PgPool, config: JanusRttConfig) {
+    loop {
+        let probes = clients
+            .backends()
+            .into_iter()
+            .map(|(backend, client)| async move {
+                let rtt = probe(&client, &backend).await;
+                (backend, rtt)
+            });
+
+        for (backend, rtt) in future::join_all(probes).await {
+            if let Err(err) = store_rtt(&db, &backend, rtt).await {
+                error!(?err, backend = %backend.id(), "Failed to store backend rtt");
+            }
+        }
+
+        tokio::time::sleep(config.interval).await;
+    }
+}
+
+async fn probe(client: &JanusClient, backend: &janus_backend::Object) -> Option<i32> {
+    let start = Instant::now();
+
+    let request = ServicePingRequest {
+        session_id: backend.session_id(),
+        handle_id: backend.handle_id(),
+        body: ServicePingRequestBody::new(),
+    };
+
+    match client.service_ping(request).await {
+        Ok(()) => Some(i32::try_from(start.elapsed().as_millis()).unwrap_or(i32::MAX)),
+        Err(err) => {
+            warn!(?err, backend = %backend.id(), "Backend rtt probe failed");
+            None
+        }
+    }
+}
+
+async fn store_rtt(
+    db: &sqlx::PgPool,
+    backend: &janus_backend::Object,
+    rtt: Option<i32>,
+) -> sqlx::Result<()> {
+    let mut conn = db.acquire().await?;
+    janus_backend::set_rtt(backend.id(), rtt, &mut conn).await
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct GroupRtt {
+    pub group: Option<String>,
+    pub backends: usize,
+    /// Backends which have answered the last probe.
+    pub measured: usize,
+    pub avg_ms: Option<i32>,
+    pub max_ms: Option<i32>,
+}
+
+/// Aggregates round trips of the backends by their group.
+pub fn by_group(rtts: &[janus_backend::Rtt]) -> Vec<GroupRtt> {
+    let mut groups = BTreeMap::<Option<&str>, Vec<Option<i32>>>::new();
+
+    for rtt in rtts {
+        groups
+            .entry(rtt.group.as_deref())
+            .or_default()
+            .push(rtt.rtt_ms);
+    }
+
+    groups
+        .into_iter()
+        .map(|(group, rtts)| {
+            let measured = rtts.iter().flatten().copied().collect::<Vec<_>>();
+            let avg_ms = match measured.len() {
+                0 => None,
+                len => Some((measured.iter().map(|x| *x as i64).sum::<i64>() / len as i64) as i32),
+            };
+
+            GroupRtt {
+                group: group.map(ToOwned::to_owned),
+                backends: rtts.len(),
+                measured: measured.len(),
+                avg_ms,
+                max_ms: measured.iter().max().copied(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use svc_agent::{AccountId, AgentId};
+
+    use super::*;
+
+    fn rtt(label: &str, group: Option<&str>, rtt_ms: Option<i32>) -> janus_backend::Rtt {
+        janus_backend::Rtt {
+            backend_id: AgentId::new(label, AccountId::new("janus-gateway", "svc.example.org")),
+            group: group.map(ToOwned::to_owned),
+            rtt_ms,
+        }
+    }
+
+    #[test]
+    fn aggregate_by_group() {
+        let rtts = [
+            rtt("alpha", Some("webinar"), Some(10)),
+            rtt("beta", Some("webinar"), Some(31)),
+            rtt("gamma", Some("webinar"), None),
+            rtt("delta", None, None),
+        ];
+
+        assert_eq!(
+            by_group(&rtts),
+            vec![
+                GroupRtt {
+                    group: None,
+                    backends: 1,
+                    measured: 0,
+                    avg_ms: None,
+                    max_ms: None,
+                },
+                GroupRtt {
+                    group: Some("webinar".to_owned()),
+                    backends: 3,
+                    measured: 2,
+                    avg_ms: Some(20),
+                    max_ms: Some(31),
+                },
+            ]
+        );
+    }
+}
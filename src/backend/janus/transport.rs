@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Context};
+use reqwest::{Certificate, Client, Identity};
+use tracing::warn;
+
+use crate::config::{JanusTransportConfig, JanusTransportConfigMap};
+
+const DEFAULT_GROUP: &str = "default";
+const PEM_CERT_END: &str = "-----END CERTIFICATE-----";
+
+/// HTTP clients for talking to backends built from per group transport settings.
+#[derive(Clone, Debug, Default)]
+pub struct Transports {
+    clients: HashMap<String, Client>,
+    default: Client,
+    plain: Client,
+}
+
+impl Transports {
+    pub fn new(config: &JanusTransportConfigMap) -> anyhow::Result<Self> {
+        let mut clients = HashMap::with_capacity(config.len());
+
+        for (group, transport) in config {
+            let client = build_client(transport)
+                .with_context(|| format!("Failed to build janus transport for group {}", group))?;
+
+            clients.insert(group.to_owned(), client);
+        }
+
+        let default = clients.get(DEFAULT_GROUP).cloned().unwrap_or_default();
+        Ok(Self {
+            clients,
+            default,
+            plain: Client::default(),
+        })
+    }
+
+    /// Backends without a group get the `default` settings. Groups without settings of their own
+    /// get none rather than those meant for another group.
+    pub fn client(&self, group: Option<&str>) -> Client {
+        let group = match group {
+            Some(group) => group,
+            None => return self.default.clone(),
+        };
+
+        match self.clients.get(group) {
+            Some(client) => client.clone(),
+            None => {
+                if !self.clients.is_empty() {
+                    warn!(%group, "No janus transport settings for the group");
+                }
+
+                self.plain.clone()
+            }
+        }
+    }
+}
+
+fn build_client(config: &JanusTransportConfig) -> anyhow::Result<Client> {
+    let mut builder = Client::builder();
+
+    if let Some(path) = &config.ca_bundle {
+        let bundle = std::fs::read(path)
+            .with_context(|| format!("Failed to read CA bundle {}", path.display()))?;
+
+        for pem in split_pem_bundle(&bundle) {
+            builder = builder.add_root_certificate(Certificate::from_pem(pem)?);
+        }
+    }
+
+    match (&config.client_cert, &config.client_key) {
+        (Some(cert_path), Some(key_path)) => {
+            let cert = std::fs::read(cert_path).with_context(|| {
+                format!("Failed to read client certificate {}", cert_path.display())
+            })?;
+            let key = std::fs::read(key_path)
+                .with_context(|| format!("Failed to read client key {}", key_path.display()))?;
+
+            builder = builder.identity(Identity::from_pkcs8_pem(&cert, &key)?);
+        }
+        (None, None) => (),
+        _ => return Err(anyhow!("Both client_cert and client_key must be specified")),
+    }
+
+    Ok(builder.build()?)
+}
+
+/// `Certificate::from_pem` only takes the first certificate so a bundle has to be split.
+fn split_pem_bundle(bundle: &[u8]) -> Vec<&[u8]> {
+    let end = PEM_CERT_END.as_bytes();
+    let mut certs = vec![];
+    let mut start = 0;
+    let mut idx = 0;
+
+    while idx + end.len() <= bundle.len() {
+        if &bundle[idx..idx + end.len()] == end {
+            certs.push(&bundle[start..idx + end.len()]);
+            idx += end.len();
+            start = idx;
+        } else {
+            idx += 1;
+        }
+    }
+
+    certs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::janus::client::JanusClient;
+
+    #[test]
+    fn split_bundle() {
+        let bundle = b"-----BEGIN CERTIFICATE-----\nAAA\n-----END CERTIFICATE-----\n\
+            -----BEGIN CERTIFICATE-----\nBBB\n-----END CERTIFICATE-----\n";
+
+        let certs = split_pem_bundle(bundle);
+        assert_eq!(certs.len(), 2);
+        assert!(certs[1].ends_with(PEM_CERT_END.as_bytes()));
+        assert!(String::from_utf8_lossy(certs[1]).contains("BBB"));
+    }
+
+    #[test]
+    fn incomplete_client_identity() {
+        let config = JanusTransportConfig {
+            client_cert: Some("cert.pem".into()),
+            ..Default::default()
+        };
+
+        let mut map = JanusTransportConfigMap::new();
+        map.insert("webinar".to_owned(), config);
+        assert!(Transports::new(&map).is_err());
+    }
+
+    #[test]
+    fn reject_unix_socket_url() {
+        let transports = Transports::default();
+        let http = transports.client(Some("webinar"));
+
+        assert!(JanusClient::with_http(http.clone(), "unix:///var/run/janus.sock").is_err());
+        assert!(JanusClient::with_http(http, "https://janus.example.org/janus").is_ok());
+    }
+}
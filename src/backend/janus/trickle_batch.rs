@@ -0,0 +1,110 @@
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::anyhow;
+use parking_lot::Mutex;
+use tokio::sync::oneshot;
+
+use crate::config::JanusTrickleConfig;
+
+use super::client::{
+    trickle::TrickleRequest, HandleId, IceCandidateSdp, IceCandidateSdpItem, JanusClient, SessionId,
+};
+
+type Key = (SessionId, HandleId);
+
+/// Coalesces candidates trickled to a handle within `window` into a single request to Janus.
+/// The first candidates of a handle open a batch which is sent once the window passes or
+/// `max_candidates` are collected. Every request of the batch gets its outcome.
+#[derive(Clone, Default)]
+pub struct TrickleBatcher {
+    config: JanusTrickleConfig,
+    batches: Arc<Mutex<HashMap<Key, Batch>>>,
+}
+
+#[derive(Default)]
+struct Batch {
+    candidates: Vec<IceCandidateSdpItem>,
+    waiters: Vec<oneshot::Sender<Result<(), String>>>,
+}
+
+impl TrickleBatcher {
+    pub fn new(config: JanusTrickleConfig) -> Self {
+        Self {
+            config,
+            batches: Arc::default(),
+        }
+    }
+
+    pub async fn trickle(
+        &self,
+        client: &JanusClient,
+        session_id: SessionId,
+        handle_id: HandleId,
+        candidates: IceCandidateSdp,
+    ) -> anyhow::Result<()> {
+        if self.config.window.is_zero() {
+            let request = TrickleRequest {
+                session_id,
+                handle_id,
+                candidates: candidates.into_items().into(),
+            };
+
+            return client.trickle_request(request).await;
+        }
+
+        let key = (session_id, handle_id);
+        let (tx, rx) = oneshot::channel();
+
+        let (opened, full) = {
+            let mut batches = self.batches.lock();
+            let opened = !batches.contains_key(&key);
+            let batch = batches.entry(key).or_default();
+            batch.candidates.extend(candidates.into_items());
+            batch.waiters.push(tx);
+            (opened, batch.candidates.len() >= self.config.max_candidates)
+        };
+
+        if full {
+            self.flush(client, key).await;
+        } else if opened {
+            // The batch is sent by a task of its own so that it isn't lost along with
+            // the request that opened it.
+            let batcher = self.clone();
+            let client = client.clone();
+
+            tokio::spawn(async move {
+                tokio::time::sleep(batcher.config.window).await;
+                batcher.flush(&client, key).await;
+            });
+        }
+
+        rx.await
+            .map_err(|_| anyhow!("Trickle batch dropped"))?
+            .map_err(|err| anyhow!(err))
+    }
+
+    async fn flush(&self, client: &JanusClient, key: Key) {
+        // The batch may have been sent already on reaching the limit.
+        let batch = match self.batches.lock().remove(&key) {
+            Some(batch) => batch,
+            None => return,
+        };
+
+        let (session_id, handle_id) = key;
+
+        let request = TrickleRequest {
+            session_id,
+            handle_id,
+            candidates: batch.candidates.into(),
+        };
+
+        let result = client
+            .trickle_request(request)
+            .await
+            .map_err(|err| format!("{:#}", err));
+
+        for waiter in batch.waiters {
+            let _ = waiter.send(result.clone());
+        }
+    }
+}
@@ -1,31 +1,47 @@
 use async_trait::async_trait;
 use chrono::Utc;
-use serde_json::json;
+use serde_json::{json, Value as JsonValue};
 use svc_agent::{
-    mqtt::{Agent, OutgoingEvent, OutgoingEventProperties, ShortTermTimingProperties},
+    mqtt::{OutgoingEvent, OutgoingEventProperties, ShortTermTimingProperties},
     Error,
 };
 
+use crate::app::broker::BrokerAgent;
+
 #[async_trait]
 pub trait MqttClient: Send + Sync {
-    fn publish(&mut self, label: &'static str, path: &str) -> Result<(), Error>;
+    fn publish(&mut self, label: &'static str, path: &str) -> Result<(), Error> {
+        self.publish_payload(label, path, json!({}))
+    }
+
+    fn publish_payload(
+        &mut self,
+        label: &'static str,
+        path: &str,
+        payload: JsonValue,
+    ) -> Result<(), Error>;
 }
 
 #[derive(Clone)]
 pub struct Client {
-    agent: Agent,
+    agent: BrokerAgent,
 }
 
-pub fn new(agent: Agent) -> Client {
+pub fn new(agent: BrokerAgent) -> Client {
     Client { agent }
 }
 
 impl MqttClient for Client {
-    fn publish(&mut self, label: &'static str, path: &str) -> Result<(), Error> {
+    fn publish_payload(
+        &mut self,
+        label: &'static str,
+        path: &str,
+        payload: JsonValue,
+    ) -> Result<(), Error> {
         let timing = ShortTermTimingProperties::until_now(Utc::now());
         let props = OutgoingEventProperties::new(label, timing);
 
-        let msg = Box::new(OutgoingEvent::broadcast(json!({}), props, path));
+        let msg = Box::new(OutgoingEvent::broadcast(payload, props, path));
 
         self.agent.publish_publishable(msg)
     }
@@ -1,7 +1,12 @@
-use std::{collections::HashMap, net::SocketAddr, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    net::SocketAddr,
+    path::PathBuf,
+    time::Duration,
+};
 
 use reqwest::Url;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use svc_agent::{mqtt::AgentConfig, AccountId};
 use svc_authn::jose::Algorithm;
 use svc_authz::ConfigMap as Authz;
@@ -16,6 +21,7 @@ pub struct Config {
     pub broker_id: AccountId,
     pub authz: Authz,
     pub mqtt: AgentConfig,
+    pub mqtt_failover: Option<MqttFailoverConfig>,
     pub mqtt_api_host_uri: Url,
     pub sentry: Option<SentryConfig>,
     pub backend: BackendConfig,
@@ -27,12 +33,92 @@ pub struct Config {
     pub orphaned_room_timeout: Duration,
     pub janus_registry: JanusRegistry,
     pub authn: svc_authn::jose::ConfigMap,
+    /// How long a request to Janus is tracked without a response before it counts as expired.
     #[serde(with = "humantime_serde", default = "default_waitlist_epoch_duration")]
     pub waitlist_epoch_duration: Duration,
     #[serde(with = "humantime_serde", default = "default_waitlist_timeout")]
     pub waitlist_timeout: Duration,
+    /// SDPs larger than this many bytes are rejected before reaching Janus.
+    pub max_sdp_size: Option<usize>,
     pub outbox: crate::outbox::config::Config,
     pub nats: Option<svc_nats_client::Config>,
+    /// Consumer of config sync events from other instances. Each instance must have its own
+    /// durable consumer in `nats.subscribe_durable` so that every one of them receives events.
+    pub nats_consumer: Option<svc_nats_client::ConsumerConfig>,
+    /// Consumer of the recordings storage's object-created notifications.
+    pub storage_events: Option<StorageEventsConfig>,
+    #[serde(default)]
+    pub janus_transport: JanusTransportConfigMap,
+    #[serde(default)]
+    pub preflight: PreflightConfig,
+    pub janus_rate_limit: Option<JanusRateLimitConfig>,
+    pub janus_handle_pool: Option<JanusHandlePoolConfig>,
+    #[serde(default)]
+    pub janus_event_capture: JanusEventCaptureConfig,
+    #[serde(default)]
+    pub janus_poll: JanusPollConfig,
+    #[serde(default)]
+    pub janus_trickle: JanusTrickleConfig,
+    /// Measuring round trips to the owned backends, disabled if missing.
+    pub janus_rtt: Option<JanusRttConfig>,
+    #[serde(default)]
+    pub room_health: RoomHealthConfig,
+    #[serde(default)]
+    pub stream_limit: StreamLimitConfig,
+    #[serde(default)]
+    pub recording_archive: RecordingArchiveConfig,
+    /// Asking rooms of an overloaded backend to reduce quality, disabled if missing.
+    pub quality_hint: Option<QualityHintConfig>,
+    /// Keeping SDPs and candidates of the rooms with signal debugging on, disabled if missing.
+    pub rtc_signal_history: Option<RtcSignalHistoryConfig>,
+    /// Internal gRPC API for other backend services, disabled if missing.
+    pub grpc: Option<GrpcConfig>,
+    #[serde(default)]
+    pub compat: CompatConfig,
+    #[serde(default)]
+    pub tenant_usage: TenantUsageConfig,
+    #[serde(default)]
+    pub message_archive: MessageArchiveConfig,
+    #[serde(default)]
+    pub encrypted_message: EncryptedMessageConfig,
+    /// Audiences whose agent ids must not reach other participants.
+    #[serde(default)]
+    pub pseudonymize: PseudonymizeConfigMap,
+    #[serde(default)]
+    pub vacuum: VacuumConfig,
+    #[serde(default)]
+    pub room_bulk_close: RoomBulkCloseConfig,
+    /// Origins allowed to call the HTTP API by audience.
+    #[serde(default)]
+    pub cors: CorsConfigMap,
+    /// Rules rooms of the audience must follow on creation and update.
+    #[serde(default)]
+    pub room_policy: RoomPolicyConfigMap,
+    /// Devices an account of the audience may be used from at once.
+    #[serde(default)]
+    pub connection_limit: ConnectionLimitConfigMap,
+    /// Restrictions of the offers sent to the rooms of the audience.
+    #[serde(default)]
+    pub sdp: SdpConfigMap,
+    /// Audiences authorizing listening and publishing to RTCs with actions of their own.
+    #[serde(default)]
+    pub rtc_authz: RtcAuthzConfigMap,
+    #[serde(default)]
+    pub long_poll: LongPollConfig,
+    #[serde(default)]
+    pub room_schedule: RoomScheduleConfig,
+    #[serde(default)]
+    pub room_upload: RoomUploadConfig,
+    #[serde(default)]
+    pub active_speaker: ActiveSpeakerConfig,
+    #[serde(default)]
+    pub audio_level: AudioLevelConfig,
+    #[serde(default)]
+    pub room_authz_cache: RoomAuthzCacheConfig,
+    /// Registers an in-process stub backend on start instead of waiting for Janus to come online.
+    #[cfg(feature = "janus_stub")]
+    #[serde(default)]
+    pub janus_stub: Option<JanusStubConfig>,
 }
 
 fn default_waitlist_epoch_duration() -> Duration {
@@ -43,12 +129,42 @@ fn default_waitlist_timeout() -> Duration {
     Duration::from_secs(25)
 }
 
+/// Brokers to fall back to when the one from `mqtt` becomes unreachable.
+#[derive(Clone, Debug, Deserialize)]
+pub struct MqttFailoverConfig {
+    /// Consecutive connection errors after which the next broker is tried.
+    #[serde(default = "default_max_connection_errors")]
+    pub max_connection_errors: u32,
+    /// Fallback brokers in order of preference.
+    pub brokers: Vec<MqttBrokerConfig>,
+}
+
+fn default_max_connection_errors() -> u32 {
+    3
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct MqttBrokerConfig {
+    /// Broker name to report in logs on failover.
+    pub name: String,
+    #[serde(flatten)]
+    pub agent: AgentConfig,
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct JanusRegistry {
     pub bind_addr: SocketAddr,
     pub token: String,
 }
 
+#[cfg(feature = "janus_stub")]
+#[derive(Clone, Debug, Deserialize)]
+pub struct JanusStubConfig {
+    /// Agent id the stub backend registers with.
+    pub agent_id: svc_agent::AgentId,
+    pub capacity: Option<i32>,
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct JwtConfig {
     #[serde(deserialize_with = "svc_authn::serde::algorithm")]
@@ -70,6 +186,25 @@ pub struct BackendConfig {
     pub default_timeout: u64,
     pub stream_upload_timeout: u64,
     pub transaction_watchdog_check_period: u64,
+    /// Streams starting slower than this after `rtc.connect` get logged with their context.
+    #[serde(with = "humantime_serde", default = "default_stream_start_outlier")]
+    pub stream_start_outlier: Duration,
+    /// Share of the recordings disk used, from 0 to 1, above which a backend gets no new rooms
+    /// that record. Usage is reported by the plugin in `service.ping` events.
+    #[serde(default)]
+    pub disk_watermark: Option<f32>,
+}
+
+fn default_stream_start_outlier() -> Duration {
+    Duration::from_secs(10)
+}
+
+/// Unlike config sync, instances share the durable consumer here so that every notification
+/// is verified once. Hence the separate connection with its own `subscribe_durable`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct StorageEventsConfig {
+    pub nats: svc_nats_client::Config,
+    pub consumer: svc_nats_client::ConsumerConfig,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -86,6 +221,688 @@ pub struct UploadConfig {
     pub bucket: String,
 }
 
+/// Transport settings by backend group. Backends without a group use the `default` entry.
+pub type JanusTransportConfigMap = HashMap<String, JanusTransportConfig>;
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct JanusTransportConfig {
+    /// PEM bundle with additional root certificates to trust.
+    pub ca_bundle: Option<PathBuf>,
+    /// PEM certificate and PKCS#8 key to present to the backend.
+    pub client_cert: Option<PathBuf>,
+    pub client_key: Option<PathBuf>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct RoomHealthConfig {
+    /// How often open rooms get assessed.
+    #[serde(with = "humantime_serde")]
+    pub interval: Duration,
+}
+
+impl Default for RoomHealthConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct JanusRttConfig {
+    /// How often the owned backends get pinged.
+    #[serde(with = "humantime_serde")]
+    pub interval: Duration,
+    /// Rooms of these audiences go to the backends with the lowest round trip first.
+    pub latency_sensitive_audiences: Vec<String>,
+    /// Round trips within the same bucket of this many milliseconds are considered equal
+    /// so that the load still spreads over close backends.
+    pub bucket_ms: i32,
+}
+
+impl JanusRttConfig {
+    /// The bucket to weight the balancer with for the rooms of the audience.
+    pub fn balancer_bucket(&self, audience: &str) -> Option<i32> {
+        let sensitive = self
+            .latency_sensitive_audiences
+            .iter()
+            .any(|a| a == audience);
+
+        Some(self.bucket_ms).filter(|bucket| sensitive && *bucket > 0)
+    }
+}
+
+impl Default for JanusRttConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(15),
+            latency_sensitive_audiences: vec![],
+            bucket_ms: 10,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct StreamLimitConfig {
+    /// How often streams of owned rtcs get checked against the room's `stream_max_duration`.
+    #[serde(with = "humantime_serde")]
+    pub interval: Duration,
+}
+
+impl Default for StreamLimitConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct RecordingArchiveConfig {
+    /// How often ready recordings get checked against the policy of their audience.
+    #[serde(with = "humantime_serde")]
+    pub interval: Duration,
+    /// Recordings announced per audience at most on each check.
+    pub batch_size: i64,
+    /// Tiering policies by audience. Recordings of other audiences are never announced.
+    pub policy: RecordingArchivePolicyMap,
+}
+
+impl Default for RecordingArchiveConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(60 * 60),
+            batch_size: 100,
+            policy: HashMap::new(),
+        }
+    }
+}
+
+pub type RecordingArchivePolicyMap = HashMap<String, RecordingArchivePolicy>;
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct RecordingArchivePolicy {
+    /// Storage class uploads of the audience start in, e.g. `STANDARD_IA`. Backends not
+    /// supporting it ignore the hint.
+    pub upload_storage_class: Option<String>,
+    /// How long after the room closes its recordings become `recording.archive_due`.
+    #[serde(default, with = "humantime_serde")]
+    pub archive_after: Option<Duration>,
+    /// Storage class the recordings should move to, passed in `recording.archive_due`.
+    pub archive_storage_class: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct QualityHintConfig {
+    /// How often backend loads get checked.
+    #[serde(with = "humantime_serde")]
+    pub interval: Duration,
+    /// A room still on an overloaded backend gets the hint again after that.
+    #[serde(with = "humantime_serde")]
+    pub resend_interval: Duration,
+    /// Thresholds of the audiences missing in `audiences`.
+    pub default: QualityHintThresholds,
+    pub audiences: HashMap<String, QualityHintThresholds>,
+}
+
+impl QualityHintConfig {
+    pub fn thresholds(&self, audience: &str) -> &QualityHintThresholds {
+        self.audiences.get(audience).unwrap_or(&self.default)
+    }
+}
+
+impl Default for QualityHintConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(30),
+            resend_interval: Duration::from_secs(300),
+            default: QualityHintThresholds::default(),
+            audiences: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct RtcSignalHistoryConfig {
+    /// Offers, answers and candidate batches kept per agent connection, older ones get dropped.
+    #[serde(default = "default_max_artifacts")]
+    pub max_artifacts: i64,
+    /// Replaces IP addresses in SDPs and candidates before storing them.
+    #[serde(default)]
+    pub redact_ips: bool,
+}
+
+fn default_max_artifacts() -> i64 {
+    10
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct GrpcConfig {
+    pub bind_address: SocketAddr,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct QualityHintThresholds {
+    /// Slow links per minute over the backend's open rooms to start asking for less.
+    /// The hint is lifted once the rate falls below half of it.
+    pub slow_link_rate: f32,
+    /// Suggested caps of the publishers' video.
+    pub max_height: u32,
+    pub max_fps: u32,
+    pub max_bitrate: u32,
+}
+
+impl Default for QualityHintThresholds {
+    fn default() -> Self {
+        Self {
+            slow_link_rate: 30.,
+            max_height: 360,
+            max_fps: 15,
+            max_bitrate: 500_000,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct TenantUsageConfig {
+    /// How often the accumulated usage gets stored to the DB.
+    #[serde(with = "humantime_serde")]
+    pub flush_interval: Duration,
+}
+
+impl Default for TenantUsageConfig {
+    fn default() -> Self {
+        Self {
+            flush_interval: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct MessageArchiveConfig {
+    /// Broadcasts with larger serialized payloads get rejected in the rooms with the archive.
+    #[serde(default = "default_message_archive_max_payload_size")]
+    pub max_payload_size: usize,
+    /// Object fields dropped from archived payloads at any depth, e.g. `email`.
+    #[serde(default)]
+    pub scrub_keys: HashSet<String>,
+    /// Storage the archive of a room gets exported to along with its recordings.
+    #[serde(default)]
+    pub export: Option<MessageExportConfig>,
+}
+
+fn default_message_archive_max_payload_size() -> usize {
+    16 * 1024
+}
+
+impl Default for MessageArchiveConfig {
+    fn default() -> Self {
+        Self {
+            max_payload_size: default_message_archive_max_payload_size(),
+            scrub_keys: HashSet::new(),
+            export: None,
+        }
+    }
+}
+
+/// S3-compatible storage holding the recordings buckets.
+#[derive(Clone, Debug, Deserialize)]
+pub struct MessageExportConfig {
+    pub endpoint: Url,
+    #[serde(default = "default_message_export_region")]
+    pub region: String,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+}
+
+fn default_message_export_region() -> String {
+    String::from("us-east-1")
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct EncryptedMessageConfig {
+    /// Encrypted unicasts with a larger decoded ciphertext get rejected.
+    #[serde(default = "default_encrypted_message_max_size")]
+    pub max_size: usize,
+    /// Encryption schemes clients may use, any when empty.
+    #[serde(default)]
+    pub algorithms: HashSet<String>,
+}
+
+fn default_encrypted_message_max_size() -> usize {
+    64 * 1024
+}
+
+impl Default for EncryptedMessageConfig {
+    fn default() -> Self {
+        Self {
+            max_size: default_encrypted_message_max_size(),
+            algorithms: HashSet::new(),
+        }
+    }
+}
+
+/// Pseudonymization settings by audience.
+pub type PseudonymizeConfigMap = HashMap<String, PseudonymizeConfig>;
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct PseudonymizeConfig {
+    /// HMAC key. Changing it changes every pseudonym of the audience.
+    pub secret: String,
+}
+
+/// Browser origins allowed by audience. Audiences missing here accept any origin.
+pub type CorsConfigMap = HashMap<String, CorsConfig>;
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct CorsConfig {
+    /// Exact origins, e.g. `https://app.example.org`.
+    pub allowed_origins: HashSet<String>,
+}
+
+impl CorsConfig {
+    pub fn allows(&self, origin: &str) -> bool {
+        self.allowed_origins.contains(origin)
+    }
+}
+
+/// Room policies by audience. Audiences missing here accept any room.
+pub type RoomPolicyConfigMap = HashMap<String, RoomPolicyConfig>;
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct RoomPolicyConfig {
+    /// How long before its opening a room must be scheduled.
+    #[serde(default, with = "humantime_serde")]
+    pub min_lead_time: Option<Duration>,
+    pub max_reserve: Option<i32>,
+    /// Classroom ids of the audience start with it.
+    pub classroom_id_prefix: Option<String>,
+    /// Tenant's service checking the room after the rules above. It gets the room fields set
+    /// by the request and responds with `{"violations": [{"rule": ..., "message": ...}]}`.
+    pub endpoint: Option<Url>,
+    #[serde(with = "humantime_serde", default = "default_room_policy_timeout")]
+    pub timeout: Duration,
+}
+
+fn default_room_policy_timeout() -> Duration {
+    Duration::from_secs(5)
+}
+
+/// SDP restrictions by audience. Audiences missing here get the offers as is.
+pub type SdpConfigMap = HashMap<String, SdpConfig>;
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct SdpConfig {
+    /// Codecs removed from the offers, case insensitive.
+    #[serde(default)]
+    pub disallowed_codecs: Vec<String>,
+}
+
+/// RTC authz models by audience. Audiences missing here authorize `read` and `update` of RTCs.
+pub type RtcAuthzConfigMap = HashMap<String, RtcAuthzConfig>;
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct RtcAuthzConfig {
+    /// Listening is authorized as `listen` and publishing as `publish` instead of `read`
+    /// and `update`.
+    pub fine_grained: bool,
+    /// `read` or `update` is checked when `listen` or `publish` is denied, so the audience's
+    /// policies may grant the new actions gradually.
+    pub fallback: bool,
+}
+
+impl Default for RtcAuthzConfig {
+    fn default() -> Self {
+        Self {
+            fine_grained: true,
+            fallback: true,
+        }
+    }
+}
+
+/// Connection limits by audience. Audiences missing here don't limit accounts.
+pub type ConnectionLimitConfigMap = HashMap<String, ConnectionLimitConfig>;
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ConnectionLimitConfig {
+    /// Agents of a single account in open rooms. One more suggests the credentials are shared.
+    pub max_agents: usize,
+    /// The account gets banned in the classroom for this long on exceeding the limit.
+    #[serde(
+        with = "humantime_serde",
+        default = "default_connection_limit_ban_duration"
+    )]
+    pub ban_duration: Duration,
+    /// Whether to tell the audience about the ban with `agent.connection_limit_exceeded`.
+    #[serde(default)]
+    pub notify: bool,
+}
+
+fn default_connection_limit_ban_duration() -> Duration {
+    Duration::from_secs(15 * 60)
+}
+
+/// Room events kept for HTTP long polling and the bounds of the polls.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct LongPollConfig {
+    /// How long a poll waits for new events before responding with none.
+    #[serde(with = "humantime_serde")]
+    pub timeout: Duration,
+    /// Events kept per room, older ones get dropped.
+    pub capacity: usize,
+    /// Events older than that get dropped along with the journals of quiet rooms.
+    #[serde(with = "humantime_serde")]
+    pub retention: Duration,
+    /// Polls waiting at once on the instance.
+    pub max_polls: usize,
+    /// Polls of a single agent waiting at once.
+    pub max_polls_per_agent: usize,
+}
+
+impl Default for LongPollConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(25),
+            capacity: 500,
+            retention: Duration::from_secs(300),
+            max_polls: 1000,
+            max_polls_per_agent: 2,
+        }
+    }
+}
+
+/// Order and pace of recording uploads started by `system.vacuum`.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct VacuumConfig {
+    /// Audiences with a higher value get uploaded first. Missing ones have zero.
+    #[serde(default)]
+    pub audience_priority: HashMap<String, i32>,
+    /// Uploads started for a single audience in one run. The rest wait for the next run.
+    pub max_uploads_per_audience: Option<usize>,
+}
+
+impl VacuumConfig {
+    pub fn priority(&self, audience: &str) -> i32 {
+        self.audience_priority.get(audience).copied().unwrap_or(0)
+    }
+}
+
+/// Pace of `room.bulk_close` jobs.
+#[derive(Clone, Debug, Deserialize)]
+pub struct RoomBulkCloseConfig {
+    /// Rooms closed by a single outbox stage.
+    pub batch_size: i64,
+    /// Delay between closing two rooms to spread notifications and summaries over time.
+    #[serde(with = "humantime_serde")]
+    pub pause: Duration,
+}
+
+impl Default for RoomBulkCloseConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 50,
+            pause: Duration::from_millis(100),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct RoomScheduleConfig {
+    /// How often schedules get checked for upcoming occurrences.
+    #[serde(with = "humantime_serde")]
+    pub interval: Duration,
+    /// How long before an occurrence starts its room gets created.
+    #[serde(with = "humantime_serde")]
+    pub lead: Duration,
+    /// Rooms created or announced per check.
+    pub batch_size: i64,
+}
+
+impl Default for RoomScheduleConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(30),
+            lead: Duration::from_secs(600),
+            batch_size: 50,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct RoomUploadConfig {
+    /// How long after closing a room its recordings get uploaded. Zero leaves them for vacuum.
+    #[serde(with = "humantime_serde")]
+    pub settle_delay: Duration,
+    /// How often due uploads get checked.
+    #[serde(with = "humantime_serde")]
+    pub interval: Duration,
+    /// Rooms uploaded per check.
+    pub batch_size: i64,
+    /// How many times the upload is postponed while the room still has streams before it's
+    /// left for vacuum.
+    pub max_attempts: i32,
+}
+
+impl Default for RoomUploadConfig {
+    fn default() -> Self {
+        Self {
+            settle_delay: Duration::from_secs(120),
+            interval: Duration::from_secs(10),
+            batch_size: 20,
+            max_attempts: 10,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct ActiveSpeakerConfig {
+    /// How often the active speakers of the rooms get reevaluated and speaking time flushed.
+    #[serde(with = "humantime_serde")]
+    pub interval: Duration,
+    /// How long an agent must keep speaking to become an active speaker.
+    #[serde(with = "humantime_serde")]
+    pub activation_delay: Duration,
+    /// How long an active speaker stays one after going silent.
+    #[serde(with = "humantime_serde")]
+    pub release_delay: Duration,
+}
+
+impl Default for ActiveSpeakerConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_millis(250),
+            activation_delay: Duration::from_millis(500),
+            release_delay: Duration::from_millis(1500),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct AudioLevelConfig {
+    /// How often a publisher's levels get published, zero turns `rtc_stream.audio_level` off.
+    #[serde(with = "humantime_serde")]
+    pub sample_interval: Duration,
+    /// Period the average and peak levels are computed over.
+    #[serde(with = "humantime_serde")]
+    pub window: Duration,
+    /// Average level in dB above which a publisher that doesn't speak is suggested to be muted.
+    pub noisy_level: f32,
+}
+
+impl Default for AudioLevelConfig {
+    fn default() -> Self {
+        Self {
+            sample_interval: Duration::from_millis(500),
+            window: Duration::from_secs(3),
+            noisy_level: -40.0,
+        }
+    }
+}
+
+/// Successful authorizations reused between `room.enter` and `rtc.connect` of an agent.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct RoomAuthzCacheConfig {
+    /// How long a decision is reused, zero turns the cache off.
+    #[serde(with = "humantime_serde")]
+    pub ttl: Duration,
+    /// Decisions kept at most. Expired ones get dropped to make room for new ones.
+    pub capacity: usize,
+}
+
+impl Default for RoomAuthzCacheConfig {
+    fn default() -> Self {
+        Self {
+            ttl: Duration::from_secs(30),
+            capacity: 10_000,
+        }
+    }
+}
+
+/// Audiences whose clients haven't migrated from legacy payload shapes yet.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct CompatConfig {
+    #[serde(default)]
+    pub v1_audiences: HashSet<String>,
+}
+
+/// Token bucket for requests to a backend on behalf of a single room.
+#[derive(Clone, Debug, Deserialize)]
+pub struct JanusRateLimitConfig {
+    /// Sustained number of requests per second.
+    pub rate: u32,
+    /// Number of requests that may be sent at once after a quiet period.
+    pub burst: u32,
+    /// Number of requests allowed to wait for a token. The rest are rejected.
+    pub queue_size: u32,
+}
+
+/// Slots for handles being created on a backend at once.
+#[derive(Clone, Debug, Deserialize)]
+pub struct JanusHandlePoolConfig {
+    /// Number of shards the rooms are spread over.
+    pub shards: usize,
+    /// Number of slots in each shard.
+    pub shard_size: usize,
+    /// Number of slots a single room may take.
+    pub room_cap: usize,
+}
+
+/// Long polls of the backends served by the instance.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct JanusPollConfig {
+    /// Polls in flight at once over all the backends, unlimited if missing.
+    pub concurrency: Option<usize>,
+    /// Pause between the polls of a backend.
+    #[serde(with = "humantime_serde")]
+    pub interval: Duration,
+    /// Upper bound of the random delay added to pauses and backoffs.
+    #[serde(with = "humantime_serde")]
+    pub jitter: Duration,
+    /// A poll of the backend fails if it doesn't respond in time.
+    #[serde(with = "humantime_serde")]
+    pub timeout: Duration,
+    /// Pause after a failed poll multiplied by the number of failures in a row.
+    #[serde(with = "humantime_serde")]
+    pub error_backoff: Duration,
+    /// Failures in a row after which the backend gets recovered or removed.
+    pub max_failures: u32,
+}
+
+impl Default for JanusPollConfig {
+    fn default() -> Self {
+        Self {
+            concurrency: None,
+            interval: Duration::ZERO,
+            jitter: Duration::from_millis(50),
+            timeout: Duration::from_secs(45),
+            error_backoff: Duration::from_millis(500),
+            max_failures: 5,
+        }
+    }
+}
+
+/// Coalescing of the candidates trickled to a handle.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct JanusTrickleConfig {
+    /// Candidates of a handle trickled within it are sent at once, zero sends every request.
+    #[serde(with = "humantime_serde")]
+    pub window: Duration,
+    /// A batch is sent right away once it has that many candidates.
+    pub max_candidates: usize,
+}
+
+impl Default for JanusTrickleConfig {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_millis(20),
+            max_candidates: 32,
+        }
+    }
+}
+
+/// Limits of the raw Janus events stored by `system.event_capture_start`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct JanusEventCaptureConfig {
+    /// Longest capture one may start.
+    #[serde(with = "humantime_serde")]
+    pub max_duration: Duration,
+    /// Captured events get removed after that.
+    #[serde(with = "humantime_serde")]
+    pub retention: Duration,
+    /// The most recent events kept over all the rooms.
+    pub max_rows: i64,
+    /// How often pollers learn about captures started on other instances.
+    #[serde(with = "humantime_serde")]
+    pub refresh_interval: Duration,
+}
+
+impl Default for JanusEventCaptureConfig {
+    fn default() -> Self {
+        Self {
+            max_duration: Duration::from_secs(60 * 60),
+            retention: Duration::from_secs(24 * 60 * 60),
+            max_rows: 100_000,
+            refresh_interval: Duration::from_secs(10),
+        }
+    }
+}
+
+/// What `rtc.preflight` tells clients about the backends before they connect.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct PreflightConfig {
+    #[serde(default)]
+    pub ice_servers: Vec<IceServer>,
+    /// Codecs the backends are expected to negotiate, in the order of preference.
+    #[serde(default)]
+    pub codecs: Vec<String>,
+    /// Region by backend group. Backends without a group use the `default` entry.
+    #[serde(default)]
+    pub regions: HashMap<String, String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct IceServer {
+    pub urls: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub credential: Option<String>,
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct MetricsConfig {
     pub http: MetricsHttpConfig,
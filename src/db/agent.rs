@@ -1,23 +1,44 @@
 use chrono::{serde::ts_seconds, DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use svc_agent::AgentId;
+use svc_agent::{AccountId, AgentId};
 
 use crate::db;
 
 ////////////////////////////////////////////////////////////////////////////////
 pub type Id = db::id::Id;
 
-#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq, sqlx::Type)]
+/// Room entry state: `requested` → `subscribed` → `ready`. Agents entering a room with
+/// the lobby start as `pending` until a host admits them.
+///
+/// Variants are declared in the same order as the Postgres enum labels so
+/// comparisons agree on both sides.
+#[derive(
+    Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord, sqlx::Type,
+)]
 #[serde(rename_all = "lowercase")]
 #[sqlx(type_name = "agent_status")]
 pub enum Status {
-    #[serde(rename = "in_progress")]
+    /// Waits in the lobby, neither subscribed to the room's events nor allowed to connect.
+    #[sqlx(rename = "pending")]
+    Pending,
+    /// `room.enter` has registered the agent but the broker subscription is not confirmed yet.
+    /// The database keeps the label of the previous versions.
     #[sqlx(rename = "in_progress")]
-    InProgress,
+    Requested,
+    /// The broker has subscribed the agent to the room's events.
+    #[sqlx(rename = "subscribed")]
+    Subscribed,
     #[sqlx(rename = "ready")]
     Ready,
 }
 
+/// Events an agent may opt out of with `agent.preferences_update`.
+pub const MUTABLE_EVENTS: &[&str] = &[
+    "message.unicast",
+    "rtc_stream.agent_speaking",
+    "rtc_stream.update",
+];
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Object {
     id: Id,
@@ -26,12 +47,28 @@ pub struct Object {
     #[serde(with = "ts_seconds")]
     created_at: DateTime<Utc>,
     status: Status,
+    // The agent's own business, not listed to the others.
+    #[serde(skip)]
+    muted_events: Vec<String>,
 }
 
 impl Object {
     pub fn id(&self) -> Id {
         self.id
     }
+
+    pub fn status(&self) -> Status {
+        self.status
+    }
+
+    pub fn muted_events(&self) -> &[String] {
+        &self.muted_events
+    }
+
+    /// Whether the event may be delivered to the agent individually.
+    pub fn accepts(&self, label: &str) -> bool {
+        !self.muted_events.iter().any(|muted| muted == label)
+    }
 }
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -99,10 +136,10 @@ impl<'a> ListQuery<'a> {
                 agent_id as "agent_id: AgentId",
                 room_id as "room_id: Id",
                 created_at,
-                status as "status: Status"
+                status as "status: Status",
+                muted_events
             FROM agent
             WHERE
-                status = 'ready' AND
                 ($1::agent_id IS NULL     OR agent_id = $1::agent_id) AND
                 ($2::uuid IS NULL         OR room_id  = $2::uuid) AND
                 ($3::agent_status IS NULL OR status = $3::agent_status)
@@ -129,6 +166,7 @@ pub struct InsertQuery<'a> {
     room_id: db::room::Id,
     status: Status,
     created_at: Option<DateTime<Utc>>,
+    session_key: Option<db::agent_session::SessionKey>,
 }
 
 impl<'a> InsertQuery<'a> {
@@ -136,12 +174,19 @@ impl<'a> InsertQuery<'a> {
         Self {
             agent_id,
             room_id,
-            status: Status::InProgress,
+            status: Status::Requested,
             created_at: None,
+            session_key: None,
+        }
+    }
+
+    pub fn session_key(self, session_key: db::agent_session::SessionKey) -> Self {
+        Self {
+            session_key: Some(session_key),
+            ..self
         }
     }
 
-    #[cfg(test)]
     pub fn status(self, status: Status) -> Self {
         Self { status, ..self }
     }
@@ -158,22 +203,27 @@ impl<'a> InsertQuery<'a> {
         sqlx::query_as!(
             Object,
             r#"
-            INSERT INTO agent (agent_id, room_id, status, created_at)
-            VALUES ($1, $2, $3, COALESCE($4, now()))
+            INSERT INTO agent (agent_id, room_id, status, created_at, session_key)
+            VALUES ($1, $2, $3, COALESCE($4, now()), COALESCE($5, gen_random_uuid()))
             ON CONFLICT (agent_id, room_id) DO UPDATE
+            -- Re-entering keeps the current state so a retry never
+            -- downgrades an agent that has already got further.
             SET
-                status = 'in_progress'
+                status = agent.status,
+                session_key = COALESCE($5, agent.session_key)
             RETURNING
                 id as "id: Id",
                 agent_id as "agent_id: AgentId",
                 room_id as "room_id: Id",
                 created_at,
-                status as "status: Status"
+                status as "status: Status",
+                muted_events
             "#,
             self.agent_id as &AgentId,
             self.room_id as Id,
             self.status as Status,
-            self.created_at
+            self.created_at,
+            self.session_key as Option<db::agent_session::SessionKey>,
         )
         .fetch_one(conn)
         .await
@@ -182,6 +232,8 @@ impl<'a> InsertQuery<'a> {
 
 ///////////////////////////////////////////////////////////////////////////////
 
+/// Moves the agent forward through the entry states. Never steps back, so
+/// a late or duplicate transition is a no-op returning `None`.
 #[derive(Debug)]
 pub struct UpdateQuery<'a> {
     agent_id: &'a AgentId,
@@ -214,13 +266,15 @@ impl<'a> UpdateQuery<'a> {
                 status = $3
             WHERE
                 agent_id = $1 AND
-                room_id  = $2
+                room_id  = $2 AND
+                status < $3
             RETURNING
                 id as "id: Id",
                 agent_id as "agent_id: AgentId",
                 room_id as "room_id: Id",
                 created_at,
-                status as "status: Status"
+                status as "status: Status",
+                muted_events
             "#,
             self.agent_id as &AgentId,
             self.room_id as Id,
@@ -233,6 +287,166 @@ impl<'a> UpdateQuery<'a> {
 
 ///////////////////////////////////////////////////////////////////////////////
 
+pub async fn set_muted_events(
+    agent_id: &AgentId,
+    room_id: db::room::Id,
+    muted_events: &[String],
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Option<Object>> {
+    sqlx::query_as!(
+        Object,
+        r#"
+        UPDATE agent
+        SET
+            muted_events = $3
+        WHERE
+            agent_id = $1 AND
+            room_id  = $2
+        RETURNING
+            id as "id: Id",
+            agent_id as "agent_id: AgentId",
+            room_id as "room_id: Id",
+            created_at,
+            status as "status: Status",
+            muted_events
+        "#,
+        agent_id as &AgentId,
+        room_id as Id,
+        muted_events,
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// An agent of the account in an open room, one per rtc it's connected to.
+#[derive(Debug)]
+pub struct Location {
+    pub agent_id: AgentId,
+    pub room_id: db::room::Id,
+    pub classroom_id: uuid::Uuid,
+    pub status: Status,
+    pub entered_at: DateTime<Utc>,
+    pub backend_id: Option<AgentId>,
+    pub rtc_id: Option<db::rtc::Id>,
+    pub connection_status: Option<db::agent_connection::Status>,
+}
+
+pub async fn locate(
+    account_id: &AccountId,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Vec<Location>> {
+    sqlx::query_as!(
+        Location,
+        r#"
+        SELECT
+            a.agent_id as "agent_id!: AgentId",
+            a.room_id as "room_id!: db::room::Id",
+            r.classroom_id,
+            a.status as "status!: Status",
+            a.created_at as entered_at,
+            r.backend_id as "backend_id?: AgentId",
+            ac.rtc_id as "rtc_id?: db::rtc::Id",
+            ac.status as "connection_status?: db::agent_connection::Status"
+        FROM agent AS a
+        INNER JOIN room AS r
+        ON r.id = a.room_id
+        LEFT JOIN agent_connection AS ac
+        ON ac.agent_id = a.id
+        WHERE (a.agent_id).account_id = $1
+        AND   r.time @> NOW()
+        ORDER BY a.created_at, a.id, ac.created_at
+        "#,
+        account_id as &AccountId,
+    )
+    .fetch_all(conn)
+    .await
+}
+
+/// Counts the other agents of the agent's account present in open rooms, i.e. the devices
+/// the account is used from at the moment.
+pub async fn count_account_agents(
+    agent_id: &AgentId,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<i64> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(DISTINCT a.agent_id) as "count!: i64"
+        FROM agent AS a
+        INNER JOIN room AS r
+        ON r.id = a.room_id
+        WHERE (a.agent_id).account_id = ($1::agent_id).account_id
+        AND   a.agent_id <> $1
+        AND   r.time @> NOW()
+        "#,
+        agent_id as &AgentId,
+    )
+    .fetch_one(conn)
+    .await
+}
+
+/// Counts the sessions occupying the room but `except_session`. Agents waiting in the lobby
+/// don't occupy it and a reconnected session counts once.
+pub async fn count_occupants(
+    room_id: db::room::Id,
+    except_session: Option<db::agent_session::SessionKey>,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<i64> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(DISTINCT session_key) as "count!: i64"
+        FROM agent
+        WHERE room_id = $1
+        AND   status <> 'pending'
+        AND   ($2::uuid IS NULL OR session_key <> $2)
+        "#,
+        room_id as db::room::Id,
+        except_session as Option<db::agent_session::SessionKey>,
+    )
+    .fetch_one(conn)
+    .await
+}
+
+/// Counts the sessions occupying the room which entered it before the agent's session.
+/// An agent missing in the room comes after all of them.
+pub async fn count_occupants_ahead(
+    room_id: db::room::Id,
+    agent_id: &AgentId,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<i64> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(DISTINCT o.session_key) as "count!: i64"
+        FROM agent AS o
+        LEFT JOIN agent_session AS os
+        ON os.room_id = o.room_id AND os.session_key = o.session_key
+        LEFT JOIN (
+            SELECT a.session_key, COALESCE(s.created_at, a.created_at) AS entered_at
+            FROM agent AS a
+            LEFT JOIN agent_session AS s
+            ON s.room_id = a.room_id AND s.session_key = a.session_key
+            WHERE a.room_id = $1 AND a.agent_id = $2
+        ) AS me
+        ON TRUE
+        WHERE o.room_id = $1
+        AND   o.status <> 'pending'
+        AND   (
+            me.session_key IS NULL OR (
+                o.session_key <> me.session_key AND
+                COALESCE(os.created_at, o.created_at) < me.entered_at
+            )
+        )
+        "#,
+        room_id as db::room::Id,
+        agent_id as &AgentId,
+    )
+    .fetch_one(conn)
+    .await
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
 /// Deletes the agent and associated agent_connection (cascade).
 pub struct DeleteQuery<'a> {
     agent_id: Option<&'a AgentId>,
@@ -335,6 +549,7 @@ mod tests {
             .await;
 
         let r = ListQuery::new()
+            .status(Status::Ready)
             .room_id(room.id())
             .execute(&mut conn)
             .await
@@ -348,6 +563,7 @@ mod tests {
 
         assert_eq!(r, 1);
         let r = ListQuery::new()
+            .status(Status::Ready)
             .room_id(room.id())
             .execute(&mut conn)
             .await
@@ -22,9 +22,7 @@ pub struct Object {
     #[allow(unused)]
     agent_id: super::agent::Id,
     handle_id: HandleId,
-    #[allow(dead_code)]
     created_at: DateTime<Utc>,
-    #[allow(dead_code)]
     rtc_id: db::rtc::Id,
     #[allow(dead_code)]
     status: Status,
@@ -34,6 +32,14 @@ impl Object {
     pub fn handle_id(&self) -> HandleId {
         self.handle_id
     }
+
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    pub fn rtc_id(&self) -> db::rtc::Id {
+        self.rtc_id
+    }
 }
 
 ///////////////////////////////////////////////////////////////////////////////
@@ -120,7 +126,7 @@ impl UpsertQuery {
         }
     }
 
-    #[cfg(test)]
+    /// When the agent has asked to connect. Defaults to now.
     pub fn created_at(self, created_at: DateTime<Utc>) -> Self {
         Self { created_at, ..self }
     }
@@ -160,11 +166,21 @@ impl UpsertQuery {
 pub struct UpdateQuery {
     handle_id: HandleId,
     status: Status,
+    relayed: Option<bool>,
 }
 
 impl UpdateQuery {
     pub fn new(handle_id: HandleId, status: Status) -> Self {
-        Self { handle_id, status }
+        Self {
+            handle_id,
+            status,
+            relayed: None,
+        }
+    }
+
+    /// Whether the media goes through a TURN relay. Kept as is when unknown.
+    pub fn relayed(self, relayed: Option<bool>) -> Self {
+        Self { relayed, ..self }
     }
 
     pub async fn execute(&self, conn: &mut sqlx::PgConnection) -> sqlx::Result<Option<Object>> {
@@ -173,7 +189,8 @@ impl UpdateQuery {
             r#"
             UPDATE agent_connection
             SET
-                status = $2
+                status = $2,
+                relayed = COALESCE($3, relayed)
             WHERE
                 handle_id = $1
             RETURNING
@@ -184,7 +201,8 @@ impl UpdateQuery {
                 status as "status: Status"
             "#,
             self.handle_id as HandleId,
-            self.status as Status
+            self.status as Status,
+            self.relayed,
         )
         .fetch_optional(conn)
         .await
@@ -302,6 +320,94 @@ impl<'a> BulkDisconnectByBackendQuery<'a> {
     }
 }
 
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug)]
+pub struct DisconnectedRtc {
+    pub room_id: db::room::Id,
+    pub rtc_id: db::rtc::Id,
+}
+
+/// Drops all connections to the backend like `BulkDisconnectByBackendQuery` but tells
+/// which RTCs they belonged to, so their agents could be asked to connect again.
+pub async fn disconnect_by_backend(
+    backend_id: &AgentId,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Vec<DisconnectedRtc>> {
+    sqlx::query_as!(
+        DisconnectedRtc,
+        r#"
+        DELETE FROM agent_connection AS ac
+        USING agent AS a,
+            room AS r
+        WHERE a.id = ac.agent_id
+        AND   r.id = a.room_id
+        AND   r.backend_id = $1
+        RETURNING
+            r.id as "room_id: db::room::Id",
+            ac.rtc_id as "rtc_id: db::rtc::Id"
+        "#,
+        backend_id as &AgentId
+    )
+    .fetch_all(conn)
+    .await
+}
+
+/// Drops all connections of the room's agents and returns them so their handles could be
+/// detached on the backend they were made to.
+pub async fn disconnect_by_room(
+    room_id: db::room::Id,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Vec<Object>> {
+    sqlx::query_as!(
+        Object,
+        r#"
+        DELETE FROM agent_connection AS ac
+        USING agent AS a
+        WHERE a.id = ac.agent_id
+        AND   a.room_id = $1
+        RETURNING
+            ac.agent_id as "agent_id: db::id::Id",
+            ac.handle_id as "handle_id: HandleId",
+            ac.created_at,
+            ac.rtc_id as "rtc_id: db::id::Id",
+            ac.status as "status: Status"
+        "#,
+        room_id as db::room::Id
+    )
+    .fetch_all(conn)
+    .await
+}
+
+/// Remembers the ICE username fragment of the connection's latest offer and returns the one
+/// of the previous offer. A changed fragment means the agent restarts ICE on the handle.
+pub async fn swap_ice_ufrag(
+    handle_id: HandleId,
+    rtc_id: db::rtc::Id,
+    ice_ufrag: &str,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Option<String>> {
+    let row = sqlx::query!(
+        r#"
+        UPDATE agent_connection AS ac
+        SET ice_ufrag = $3
+        FROM agent_connection AS prev
+        WHERE prev.agent_id = ac.agent_id
+        AND   prev.rtc_id = ac.rtc_id
+        AND   ac.handle_id = $1
+        AND   ac.rtc_id = $2
+        RETURNING prev.ice_ufrag
+        "#,
+        handle_id as HandleId,
+        rtc_id as db::rtc::Id,
+        ice_ufrag,
+    )
+    .fetch_optional(conn)
+    .await?;
+
+    Ok(row.and_then(|row| row.ice_ufrag))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -364,4 +470,34 @@ mod tests {
 
         assert_eq!(r, 1);
     }
+
+    #[sqlx::test]
+    async fn swap_ice_ufrag_of_offers(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let mut conn = db.get_conn().await;
+
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+        let room = shared_helpers::insert_room(&mut conn).await;
+        let rtc = shared_helpers::insert_rtc_with_room(&mut conn, &room).await;
+
+        let (_, connection) = shared_helpers::insert_connected_agent(
+            &mut conn,
+            agent.agent_id(),
+            room.id(),
+            rtc.id(),
+        )
+        .await;
+
+        let previous = swap_ice_ufrag(connection.handle_id(), rtc.id(), "074c6550", &mut conn)
+            .await
+            .expect("Failed to swap ICE ufrag");
+
+        assert_eq!(previous, None);
+
+        let previous = swap_ice_ufrag(connection.handle_id(), rtc.id(), "5e1d3a09", &mut conn)
+            .await
+            .expect("Failed to swap ICE ufrag");
+
+        assert_eq!(previous.as_deref(), Some("074c6550"));
+    }
 }
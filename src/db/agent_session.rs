@@ -0,0 +1,107 @@
+use svc_agent::AgentId;
+
+use crate::db;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Links successive agents of the same logical session in a room, e.g. when a client
+/// reconnects with a new agent label.
+pub type SessionKey = db::id::Id;
+
+/// Resolves the session the agent enters the room with and records the entrance.
+///
+/// The key echoed by the client continues its session only when it belongs to the same
+/// account, otherwise a retried enter keeps the agent's current key or a new one is issued.
+pub async fn resolve(
+    room_id: db::room::Id,
+    agent_id: &AgentId,
+    session_key: Option<SessionKey>,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<SessionKey> {
+    sqlx::query!(
+        r#"
+        INSERT INTO agent_session (room_id, session_key, agent_id)
+        VALUES (
+            $1,
+            COALESCE(
+                (
+                    SELECT session_key
+                    FROM agent_session
+                    WHERE room_id = $1
+                    AND   session_key = $3
+                    AND   (agent_id).account_id = ($2::agent_id).account_id
+                ),
+                (
+                    SELECT session_key
+                    FROM agent
+                    WHERE room_id = $1
+                    AND   agent_id = $2
+                ),
+                gen_random_uuid()
+            ),
+            $2
+        )
+        ON CONFLICT (room_id, session_key) DO UPDATE
+        SET
+            -- A retried enter of the same agent isn't a reconnect.
+            reconnects = agent_session.reconnects + (agent_session.agent_id <> EXCLUDED.agent_id)::INT,
+            agent_id = EXCLUDED.agent_id,
+            updated_at = NOW()
+        RETURNING
+            session_key as "session_key: SessionKey"
+        "#,
+        room_id as db::room::Id,
+        agent_id as &AgentId,
+        session_key as Option<SessionKey>,
+    )
+    .fetch_one(conn)
+    .await
+    .map(|r| r.session_key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::{db::TestDb, prelude::*};
+
+    #[sqlx::test]
+    async fn stitch_reconnects(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let mut conn = db.get_conn().await;
+
+        let room = shared_helpers::insert_room(&mut conn).await;
+        let agent = TestAgent::new("web", "user123", USR_AUDIENCE);
+
+        let key = resolve(room.id(), agent.agent_id(), None, &mut conn)
+            .await
+            .expect("Failed to resolve session");
+
+        // The client reconnects with another label and echoes the key.
+        let reconnected = TestAgent::new("web-1", "user123", USR_AUDIENCE);
+
+        let same_key = resolve(room.id(), reconnected.agent_id(), Some(key), &mut conn)
+            .await
+            .expect("Failed to resolve session");
+
+        assert_eq!(same_key, key);
+
+        // Someone else's key starts a session of its own.
+        let other = TestAgent::new("web", "user456", USR_AUDIENCE);
+
+        let other_key = resolve(room.id(), other.agent_id(), Some(key), &mut conn)
+            .await
+            .expect("Failed to resolve session");
+
+        assert_ne!(other_key, key);
+
+        let row = sqlx::query!(
+            "SELECT reconnects FROM agent_session WHERE session_key = $1",
+            key as SessionKey,
+        )
+        .fetch_one(&mut conn)
+        .await
+        .expect("Failed to fetch session");
+
+        assert_eq!(row.reconnects, 1);
+    }
+}
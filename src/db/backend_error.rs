@@ -0,0 +1,327 @@
+use chrono::{serde::ts_milliseconds, DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use svc_agent::AgentId;
+
+use crate::{backend::janus::client::SessionId, db};
+
+////////////////////////////////////////////////////////////////////////////////
+
+pub type Id = db::id::Id;
+
+/// Maximum number of stored errors. `system.vacuum` removes older ones.
+pub const MAX_ROWS: i64 = 10_000;
+
+/// Number of days stored errors are kept for unless `MAX_ROWS` is reached earlier.
+pub const RETENTION_DAYS: i64 = 7;
+
+#[derive(Debug, Serialize)]
+pub struct Object {
+    pub id: Id,
+    pub backend_id: Option<AgentId>,
+    pub session_id: SessionId,
+    pub kind: String,
+    pub payload: JsonValue,
+    pub room_id: Option<db::room::Id>,
+    pub rtc_stream_id: Option<db::janus_rtc_stream::Id>,
+    pub trace_id: Option<String>,
+    pub correlation_id: Option<String>,
+    #[serde(with = "ts_milliseconds")]
+    pub created_at: DateTime<Utc>,
+}
+
+impl Object {
+    pub fn id(&self) -> Id {
+        self.id
+    }
+
+    pub fn kind(&self) -> &str {
+        &self.kind
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug)]
+pub struct InsertQuery<'a> {
+    session_id: SessionId,
+    kind: &'a str,
+    payload: JsonValue,
+    room_id: Option<db::room::Id>,
+    rtc_stream_id: Option<db::janus_rtc_stream::Id>,
+    trace_id: Option<&'a str>,
+    correlation_id: Option<String>,
+}
+
+impl<'a> InsertQuery<'a> {
+    pub fn new(session_id: SessionId, kind: &'a str, payload: JsonValue) -> Self {
+        Self {
+            session_id,
+            kind,
+            payload,
+            room_id: None,
+            rtc_stream_id: None,
+            trace_id: None,
+            correlation_id: None,
+        }
+    }
+
+    pub fn room_id(self, room_id: db::room::Id) -> Self {
+        Self {
+            room_id: Some(room_id),
+            ..self
+        }
+    }
+
+    pub fn rtc_stream_id(self, rtc_stream_id: db::janus_rtc_stream::Id) -> Self {
+        Self {
+            rtc_stream_id: Some(rtc_stream_id),
+            ..self
+        }
+    }
+
+    pub fn trace_id(self, trace_id: &'a str) -> Self {
+        Self {
+            trace_id: Some(trace_id),
+            ..self
+        }
+    }
+
+    pub fn correlation_id(self, correlation_id: String) -> Self {
+        Self {
+            correlation_id: Some(correlation_id),
+            ..self
+        }
+    }
+
+    pub async fn execute(self, conn: &mut sqlx::PgConnection) -> sqlx::Result<Object> {
+        // Backend is resolved by the session since Janus events don't carry the backend id.
+        sqlx::query_as!(
+            Object,
+            r#"
+            INSERT INTO backend_error
+                (backend_id, session_id, kind, payload, room_id, rtc_stream_id, trace_id, correlation_id)
+            VALUES (
+                (SELECT id FROM janus_backend WHERE session_id = $1 LIMIT 1),
+                $1, $2, $3, $4, $5, $6, $7
+            )
+            RETURNING
+                id as "id: Id",
+                backend_id as "backend_id: AgentId",
+                session_id as "session_id: SessionId",
+                kind,
+                payload,
+                room_id as "room_id: Id",
+                rtc_stream_id as "rtc_stream_id: Id",
+                trace_id,
+                correlation_id,
+                created_at
+            "#,
+            self.session_id as SessionId,
+            self.kind,
+            self.payload,
+            self.room_id as Option<Id>,
+            self.rtc_stream_id as Option<Id>,
+            self.trace_id,
+            self.correlation_id,
+        )
+        .fetch_one(conn)
+        .await
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Default)]
+pub struct ListQuery<'a> {
+    backend_id: Option<&'a AgentId>,
+    kind: Option<&'a str>,
+    room_id: Option<db::room::Id>,
+    created_after: Option<DateTime<Utc>>,
+    offset: Option<i64>,
+    limit: Option<i64>,
+}
+
+impl<'a> ListQuery<'a> {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn backend_id(self, backend_id: &'a AgentId) -> Self {
+        Self {
+            backend_id: Some(backend_id),
+            ..self
+        }
+    }
+
+    pub fn kind(self, kind: &'a str) -> Self {
+        Self {
+            kind: Some(kind),
+            ..self
+        }
+    }
+
+    pub fn room_id(self, room_id: db::room::Id) -> Self {
+        Self {
+            room_id: Some(room_id),
+            ..self
+        }
+    }
+
+    pub fn created_after(self, created_after: DateTime<Utc>) -> Self {
+        Self {
+            created_after: Some(created_after),
+            ..self
+        }
+    }
+
+    pub fn offset(self, offset: i64) -> Self {
+        Self {
+            offset: Some(offset),
+            ..self
+        }
+    }
+
+    pub fn limit(self, limit: i64) -> Self {
+        Self {
+            limit: Some(limit),
+            ..self
+        }
+    }
+
+    pub async fn execute(&self, conn: &mut sqlx::PgConnection) -> sqlx::Result<Vec<Object>> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            SELECT
+                id as "id: Id",
+                backend_id as "backend_id: AgentId",
+                session_id as "session_id: SessionId",
+                kind,
+                payload,
+                room_id as "room_id: Id",
+                rtc_stream_id as "rtc_stream_id: Id",
+                trace_id,
+                correlation_id,
+                created_at
+            FROM backend_error
+            WHERE
+                ($1::agent_id IS NULL OR backend_id = $1) AND
+                ($2::text IS NULL OR kind = $2) AND
+                ($3::uuid IS NULL OR room_id = $3) AND
+                ($4::timestamptz IS NULL OR created_at > $4)
+            ORDER BY created_at DESC
+            OFFSET $5
+            LIMIT $6
+            "#,
+            self.backend_id as Option<&AgentId>,
+            self.kind,
+            self.room_id as Option<Id>,
+            self.created_after,
+            self.offset,
+            self.limit,
+        )
+        .fetch_all(conn)
+        .await
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Removes errors stored before `retained_after` and everything beyond `max_rows` most recent
+/// errors.
+pub async fn trim(
+    max_rows: i64,
+    retained_after: DateTime<Utc>,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<u64> {
+    sqlx::query!(
+        r#"
+        DELETE FROM backend_error
+        WHERE created_at < $2
+        OR    id IN (
+            SELECT id
+            FROM backend_error
+            ORDER BY created_at DESC
+            OFFSET $1
+        )
+        "#,
+        max_rows,
+        retained_after,
+    )
+    .execute(conn)
+    .await
+    .map(|r| r.rows_affected())
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        backend::janus::client::HandleId,
+        test_helpers::{db::TestDb, prelude::*},
+    };
+
+    #[sqlx::test]
+    async fn insert_list_and_trim(pool: sqlx::PgPool) {
+        let mut conn = TestDb::new(pool).get_conn().await;
+
+        let backend = shared_helpers::insert_janus_backend(
+            &mut conn,
+            "test",
+            SessionId::random(),
+            HandleId::random(),
+        )
+        .await;
+        let room = shared_helpers::insert_room(&mut conn).await;
+
+        for status in ["500", "503"] {
+            InsertQuery::new(
+                backend.session_id(),
+                "create_stream",
+                json!({ "status": status }),
+            )
+            .room_id(room.id())
+            .execute(&mut conn)
+            .await
+            .expect("Failed to insert backend error");
+        }
+
+        InsertQuery::new(SessionId::random(), "read_stream", json!({}))
+            .execute(&mut conn)
+            .await
+            .expect("Failed to insert backend error");
+
+        let errors = ListQuery::new()
+            .backend_id(backend.id())
+            .kind("create_stream")
+            .execute(&mut conn)
+            .await
+            .expect("Failed to list backend errors");
+
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].room_id, Some(room.id()));
+        assert_eq!(errors[0].backend_id.as_ref(), Some(backend.id()));
+
+        let removed = trim(2, Utc::now() - chrono::Duration::hours(1), &mut conn)
+            .await
+            .expect("Failed to trim backend errors");
+        assert_eq!(removed, 1);
+
+        let removed = trim(MAX_ROWS, Utc::now() + chrono::Duration::hours(1), &mut conn)
+            .await
+            .expect("Failed to trim backend errors");
+        assert_eq!(removed, 2);
+
+        let errors = ListQuery::new()
+            .execute(&mut conn)
+            .await
+            .expect("Failed to list backend errors");
+
+        assert!(errors.is_empty());
+    }
+}
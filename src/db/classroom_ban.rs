@@ -0,0 +1,203 @@
+use chrono::{
+    serde::{ts_seconds, ts_seconds_option},
+    DateTime, Utc,
+};
+use serde::{Deserialize, Serialize};
+use svc_agent::{AccountId, AgentId};
+use uuid::Uuid;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Ban of an account in all rooms of a classroom. Every change is recorded to
+/// `classroom_ban_audit` in the same statement.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Object {
+    pub classroom_id: Uuid,
+    pub account_id: AccountId,
+    pub banned_by: AgentId,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(with = "ts_seconds_option")]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(with = "ts_seconds")]
+    pub created_at: DateTime<Utc>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Finds the ban of the account in the classroom unless it has expired.
+pub async fn find_active(
+    classroom_id: Uuid,
+    account_id: &AccountId,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Option<Object>> {
+    sqlx::query_as!(
+        Object,
+        r#"
+        SELECT
+            classroom_id,
+            account_id as "account_id: AccountId",
+            banned_by as "banned_by: AgentId",
+            reason,
+            expires_at,
+            created_at
+        FROM classroom_ban
+        WHERE classroom_id = $1
+        AND   account_id = $2
+        AND   (expires_at IS NULL OR expires_at > NOW())
+        "#,
+        classroom_id,
+        account_id as &AccountId,
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+pub struct ListQuery {
+    classroom_id: Uuid,
+}
+
+impl ListQuery {
+    pub fn new(classroom_id: Uuid) -> Self {
+        Self { classroom_id }
+    }
+
+    pub async fn execute(&self, conn: &mut sqlx::PgConnection) -> sqlx::Result<Vec<Object>> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            SELECT
+                classroom_id,
+                account_id as "account_id: AccountId",
+                banned_by as "banned_by: AgentId",
+                reason,
+                expires_at,
+                created_at
+            FROM classroom_ban
+            WHERE classroom_id = $1
+            AND   (expires_at IS NULL OR expires_at > NOW())
+            ORDER BY created_at
+            "#,
+            self.classroom_id,
+        )
+        .fetch_all(conn)
+        .await
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+pub struct UpsertQuery<'a> {
+    classroom_id: Uuid,
+    account_id: &'a AccountId,
+    banned_by: &'a AgentId,
+    reason: Option<&'a str>,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+impl<'a> UpsertQuery<'a> {
+    pub fn new(classroom_id: Uuid, account_id: &'a AccountId, banned_by: &'a AgentId) -> Self {
+        Self {
+            classroom_id,
+            account_id,
+            banned_by,
+            reason: None,
+            expires_at: None,
+        }
+    }
+
+    pub fn reason(self, reason: &'a str) -> Self {
+        Self {
+            reason: Some(reason),
+            ..self
+        }
+    }
+
+    pub fn expires_at(self, expires_at: DateTime<Utc>) -> Self {
+        Self {
+            expires_at: Some(expires_at),
+            ..self
+        }
+    }
+
+    /// Bans the account or replaces the existing ban.
+    pub async fn execute(&self, conn: &mut sqlx::PgConnection) -> sqlx::Result<Object> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            WITH ban AS (
+                INSERT INTO classroom_ban (classroom_id, account_id, banned_by, reason, expires_at)
+                VALUES ($1, $2, $3, $4, $5)
+                ON CONFLICT (classroom_id, account_id) DO UPDATE
+                SET
+                    banned_by = EXCLUDED.banned_by,
+                    reason = EXCLUDED.reason,
+                    expires_at = EXCLUDED.expires_at,
+                    created_at = NOW()
+                RETURNING *
+            ), audit AS (
+                INSERT INTO classroom_ban_audit
+                    (classroom_id, account_id, action, performed_by, reason, expires_at)
+                SELECT classroom_id, account_id, 'ban', banned_by, reason, expires_at
+                FROM ban
+            )
+            SELECT
+                classroom_id as "classroom_id!: Uuid",
+                account_id as "account_id!: AccountId",
+                banned_by as "banned_by!: AgentId",
+                reason,
+                expires_at,
+                created_at as "created_at!: DateTime<Utc>"
+            FROM ban
+            "#,
+            self.classroom_id,
+            self.account_id as &AccountId,
+            self.banned_by as &AgentId,
+            self.reason,
+            self.expires_at,
+        )
+        .fetch_one(conn)
+        .await
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Lifts the ban. Returns `None` if the account hasn't been banned in the classroom.
+pub async fn delete(
+    classroom_id: Uuid,
+    account_id: &AccountId,
+    performed_by: &AgentId,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Option<Object>> {
+    sqlx::query_as!(
+        Object,
+        r#"
+        WITH ban AS (
+            DELETE FROM classroom_ban
+            WHERE classroom_id = $1
+            AND   account_id = $2
+            RETURNING *
+        ), audit AS (
+            INSERT INTO classroom_ban_audit (classroom_id, account_id, action, performed_by)
+            SELECT classroom_id, account_id, 'unban', $3
+            FROM ban
+        )
+        SELECT
+            classroom_id as "classroom_id!: Uuid",
+            account_id as "account_id!: AccountId",
+            banned_by as "banned_by!: AgentId",
+            reason,
+            expires_at,
+            created_at as "created_at!: DateTime<Utc>"
+        FROM ban
+        "#,
+        classroom_id,
+        account_id as &AccountId,
+        performed_by as &AgentId,
+    )
+    .fetch_optional(conn)
+    .await
+}
@@ -0,0 +1,253 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use svc_agent::AgentId;
+
+use crate::{
+    backend::janus::{
+        client::{HandleId, SessionId},
+        JANUS_API_VERSION,
+    },
+    db::{self, janus_backend::Object as JanusBackend, recording::Status},
+};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// The mixed recording of the whole room made by the backend it has been started on.
+/// Shares the status semantics with the per-RTC source recordings.
+#[derive(Debug, Serialize)]
+pub struct Object {
+    pub room_id: db::room::Id,
+    pub backend_id: AgentId,
+    pub status: Status,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub started_at: DateTime<Utc>,
+}
+
+impl Object {
+    pub fn status(&self) -> Status {
+        self.status
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+pub async fn find(
+    room_id: db::room::Id,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Option<Object>> {
+    sqlx::query_as!(
+        Object,
+        r#"
+        SELECT
+            room_id as "room_id: db::room::Id",
+            backend_id as "backend_id: AgentId",
+            status as "status: Status",
+            started_at
+        FROM composite_recording
+        WHERE room_id = $1
+        "#,
+        room_id as db::room::Id,
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+/// Returns `None` if the composite recording of the room has already been started.
+pub async fn insert(
+    room_id: db::room::Id,
+    backend_id: &AgentId,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Option<Object>> {
+    sqlx::query_as!(
+        Object,
+        r#"
+        INSERT INTO composite_recording (room_id, backend_id)
+        VALUES ($1, $2)
+        ON CONFLICT (room_id) DO NOTHING
+        RETURNING
+            room_id as "room_id: db::room::Id",
+            backend_id as "backend_id: AgentId",
+            status as "status: Status",
+            started_at
+        "#,
+        room_id as db::room::Id,
+        backend_id as &AgentId,
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+pub async fn set_status(
+    room_id: db::room::Id,
+    status: Status,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Option<Object>> {
+    sqlx::query_as!(
+        Object,
+        r#"
+        UPDATE composite_recording
+        SET status = $2
+        WHERE
+            room_id = $1 AND
+            -- do not overwrite existing `ready` status with `missing`
+            ($2 <> 'missing'::recording_status OR status = 'in_progress')
+        RETURNING
+            room_id as "room_id: db::room::Id",
+            backend_id as "backend_id: AgentId",
+            status as "status: Status",
+            started_at
+        "#,
+        room_id as db::room::Id,
+        status as Status,
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+struct FinishedInProgressRow {
+    room_id: db::room::Id,
+    backend_id: AgentId,
+    handle_id: HandleId,
+    session_id: SessionId,
+    created_at: DateTime<Utc>,
+    capacity: Option<i32>,
+    balancer_capacity: Option<i32>,
+    api_version: String,
+    group: Option<String>,
+    janus_url: String,
+}
+
+/// Composite recordings of closed rooms waiting to be uploaded along with their backends.
+pub async fn finished_in_progress(
+    maybe_group: Option<&str>,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Vec<(db::room::Id, JanusBackend)>> {
+    let rows = sqlx::query_as!(
+        FinishedInProgressRow,
+        r#"
+        SELECT
+            cr.room_id as "room_id: db::room::Id",
+            jb.id as "backend_id: AgentId",
+            jb.handle_id as "handle_id: HandleId",
+            jb.session_id as "session_id: SessionId",
+            jb.created_at,
+            jb.capacity,
+            jb.balancer_capacity,
+            jb.api_version,
+            jb.group,
+            jb.janus_url
+        FROM composite_recording AS cr
+        INNER JOIN room
+        ON room.id = cr.room_id
+        INNER JOIN janus_backend AS jb
+        ON jb.id = cr.backend_id
+        WHERE
+            cr.status = 'in_progress' AND
+            upper(room.time) < NOW() AND
+            jb.api_version = $1 AND
+            ($2::text IS NULL OR (jb.group = $2 OR jb.group IS NULL))
+        "#,
+        JANUS_API_VERSION,
+        maybe_group,
+    )
+    .fetch_all(conn)
+    .await?;
+
+    let composites = rows
+        .into_iter()
+        .map(|r| {
+            let backend = JanusBackend {
+                id: r.backend_id,
+                handle_id: r.handle_id,
+                session_id: r.session_id,
+                created_at: r.created_at,
+                capacity: r.capacity,
+                balancer_capacity: r.balancer_capacity,
+                api_version: r.api_version,
+                group: r.group,
+                janus_url: r.janus_url,
+            };
+
+            (r.room_id, backend)
+        })
+        .collect();
+
+    Ok(composites)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Bound;
+
+    use chrono::{Duration, SubsecRound};
+
+    use super::*;
+    use crate::test_helpers::{db::TestDb, prelude::*};
+
+    #[sqlx::test]
+    async fn upload_lifecycle(pool: sqlx::PgPool) {
+        let mut conn = TestDb::new(pool).get_conn().await;
+
+        let backend = shared_helpers::insert_janus_backend(
+            &mut conn,
+            "test",
+            SessionId::random(),
+            HandleId::random(),
+        )
+        .await;
+        let now = Utc::now().trunc_subsecs(0);
+
+        let room = factory::Room::new()
+            .audience(USR_AUDIENCE)
+            .time((
+                Bound::Included(now - Duration::hours(10)),
+                Bound::Excluded(now - Duration::hours(8)),
+            ))
+            .rtc_sharing_policy(db::rtc::SharingPolicy::Shared)
+            .backend_id(backend.id())
+            .composite_recording(true)
+            .insert(&mut conn)
+            .await;
+        assert!(room.composite_recording());
+
+        insert(room.id(), backend.id(), &mut conn)
+            .await
+            .expect("Failed to insert composite recording")
+            .expect("Composite recording not inserted");
+
+        // The second writer must not start another composite.
+        let duplicate = insert(room.id(), backend.id(), &mut conn)
+            .await
+            .expect("Failed to insert composite recording");
+        assert!(duplicate.is_none());
+
+        let pending = finished_in_progress(None, &mut conn)
+            .await
+            .expect("Failed to list finished composites");
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].0, room.id());
+        assert_eq!(&pending[0].1.id, backend.id());
+
+        set_status(room.id(), Status::Ready, &mut conn)
+            .await
+            .expect("Failed to set status");
+
+        // Late `missing` doesn't override an uploaded composite.
+        set_status(room.id(), Status::Missing, &mut conn)
+            .await
+            .expect("Failed to set status");
+
+        let composite = find(room.id(), &mut conn)
+            .await
+            .expect("Failed to find composite recording")
+            .expect("Composite recording not found");
+        assert_eq!(composite.status(), Status::Ready);
+
+        let pending = finished_in_progress(None, &mut conn)
+            .await
+            .expect("Failed to list finished composites");
+        assert!(pending.is_empty());
+    }
+}
@@ -93,6 +93,7 @@ pub struct UpsertQuery<'a> {
     api_version: String,
     group: Option<&'a str>,
     janus_url: &'a str,
+    capabilities: Option<&'a [String]>,
 }
 
 impl<'a> UpsertQuery<'a> {
@@ -111,6 +112,7 @@ impl<'a> UpsertQuery<'a> {
             api_version: JANUS_API_VERSION.to_string(),
             group: None,
             janus_url,
+            capabilities: None,
         }
     }
 
@@ -135,13 +137,21 @@ impl<'a> UpsertQuery<'a> {
         }
     }
 
+    /// Optional plugin features like `audio_processing` the backend has reported.
+    pub fn capabilities(self, capabilities: &'a [String]) -> Self {
+        Self {
+            capabilities: Some(capabilities),
+            ..self
+        }
+    }
+
     pub async fn execute(&self, conn: &mut sqlx::PgConnection) -> sqlx::Result<Object> {
         sqlx::query_as!(
             Object,
             r#"
             INSERT INTO janus_backend
-                (id, handle_id, session_id, capacity, balancer_capacity, api_version, "group", janus_url)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                (id, handle_id, session_id, capacity, balancer_capacity, api_version, "group", janus_url, capabilities)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, COALESCE($9, '{}'::text[]))
             ON CONFLICT (id) DO UPDATE
             SET
                 handle_id         = $2,
@@ -150,7 +160,8 @@ impl<'a> UpsertQuery<'a> {
                 balancer_capacity = COALESCE($5, janus_backend.balancer_capacity),
                 api_version       = $6,
                 "group"           = COALESCE($7, janus_backend."group"),
-                janus_url         = $8
+                janus_url         = $8,
+                capabilities      = COALESCE($9, janus_backend.capabilities)
             RETURNING
                 id as "id: AgentId",
                 handle_id as "handle_id: HandleId",
@@ -169,7 +180,8 @@ impl<'a> UpsertQuery<'a> {
             self.balancer_capacity,
             self.api_version,
             self.group,
-            self.janus_url
+            self.janus_url,
+            self.capabilities,
         )
         .fetch_one(conn)
         .await
@@ -178,6 +190,84 @@ impl<'a> UpsertQuery<'a> {
 
 ////////////////////////////////////////////////////////////////////////////////
 
+pub async fn capabilities(
+    id: &AgentId,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Vec<String>> {
+    sqlx::query!(
+        r#"
+        SELECT capabilities
+        FROM janus_backend
+        WHERE id = $1
+        "#,
+        id as &AgentId,
+    )
+    .fetch_optional(conn)
+    .await
+    .map(|r| r.map(|r| r.capabilities).unwrap_or_default())
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Moves the backend to a new session after Janus has restarted.
+/// Returns `None` unless the backend was still on `old_session_id`,
+/// so of several concurrent recoveries only the first one applies.
+pub struct SwapSessionQuery<'a> {
+    id: &'a AgentId,
+    old_session_id: SessionId,
+    session_id: SessionId,
+    handle_id: HandleId,
+}
+
+impl<'a> SwapSessionQuery<'a> {
+    pub fn new(
+        id: &'a AgentId,
+        old_session_id: SessionId,
+        session_id: SessionId,
+        handle_id: HandleId,
+    ) -> Self {
+        Self {
+            id,
+            old_session_id,
+            session_id,
+            handle_id,
+        }
+    }
+
+    pub async fn execute(&self, conn: &mut sqlx::PgConnection) -> sqlx::Result<Option<Object>> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            UPDATE janus_backend
+            SET
+                session_id = $3,
+                handle_id  = $4
+            WHERE
+                id = $1 AND
+                session_id = $2
+            RETURNING
+                id as "id: AgentId",
+                handle_id as "handle_id: HandleId",
+                session_id as "session_id: SessionId",
+                created_at,
+                capacity,
+                balancer_capacity,
+                api_version,
+                "group",
+                janus_url
+            "#,
+            self.id as &AgentId,
+            self.old_session_id as SessionId,
+            self.session_id as SessionId,
+            self.handle_id as HandleId,
+        )
+        .fetch_optional(conn)
+        .await
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
 pub struct DeleteQuery<'a> {
     id: &'a AgentId,
     session_id: SessionId,
@@ -214,16 +304,47 @@ impl<'a> DeleteQuery<'a> {
 
 ////////////////////////////////////////////////////////////////////////////////
 
+pub async fn list_by_group(
+    group: &str,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Vec<Object>> {
+    sqlx::query_as!(
+        Object,
+        r#"
+        SELECT
+            id as "id: AgentId",
+            handle_id as "handle_id: HandleId",
+            session_id as "session_id: SessionId",
+            created_at,
+            capacity,
+            balancer_capacity,
+            api_version,
+            "group",
+            janus_url
+        FROM janus_backend
+        WHERE "group" = $1
+        "#,
+        group
+    )
+    .fetch_all(conn)
+    .await
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
 // Returns the most loaded backend capable to host the room with its reserve considering:
 // - room opening period;
 // - actual number of online agents;
 // - optional backend capacity;
 // - optional room reserve;
 // - writer's bitrate;
-// - possible multiple RTCs in each room.
+// - possible multiple RTCs in each room;
+// - recordings disk watermark for rooms which record;
+// - round trip from the service bucketed by `rtt_bucket` milliseconds when it's set.
 pub async fn most_loaded(
     room_id: db::room::Id,
     group: Option<&str>,
+    rtt_bucket: Option<i32>,
     conn: &mut sqlx::PgConnection,
 ) -> sqlx::Result<Option<Object>> {
     sqlx::query_as!(
@@ -282,12 +403,14 @@ pub async fn most_loaded(
         AND   COALESCE(jb.balancer_capacity, jb.capacity, 2147483647) - COALESCE(jbl.load, 0) >= COALESCE(r2.reserve, 1)
         AND   jb.api_version = $2
         AND   ($3::text IS NULL OR jb."group" = $3::text)
-        ORDER BY COALESCE(jbl.load, 0) DESC, RANDOM()
+        AND   NOT (jb.disk_full AND (r2.record_rtcs OR r2.composite_recording))
+        ORDER BY jb.rtt_ms / $4::int NULLS LAST, COALESCE(jbl.load, 0) DESC, RANDOM()
         LIMIT 1
         "#,
         room_id as db::room::Id,
         JANUS_API_VERSION,
         group,
+        rtt_bucket,
     ).fetch_optional(conn).await
 }
 
@@ -295,6 +418,7 @@ pub async fn most_loaded(
 pub async fn least_loaded(
     room_id: db::room::Id,
     group: Option<&str>,
+    rtt_bucket: Option<i32>,
     conn: &mut sqlx::PgConnection,
 ) -> sqlx::Result<Option<Object>> {
     sqlx::query_as!(
@@ -343,7 +467,9 @@ pub async fn least_loaded(
                 WHERE r2.id = $1
                 AND   jb.api_version = $2
                 AND   ($3::text IS NULL OR jb."group" = $3::text)
+                AND   NOT (jb.disk_full AND (r2.record_rtcs OR r2.composite_recording))
                 ORDER BY
+                    jb.rtt_ms / $4::int NULLS LAST,
                     COALESCE(jb.balancer_capacity, jb.capacity, 2147483647) - COALESCE(jbl.load, 0) DESC
                 LIMIT 3
             )
@@ -358,12 +484,13 @@ pub async fn least_loaded(
             "group",
             janus_url
         FROM least_loaded
-        ORDER BY RANDOM()
+        ORDER BY rtt_ms / $4::int NULLS LAST, RANDOM()
         LIMIT 1
         "#,
         room_id as db::room::Id,
         JANUS_API_VERSION,
         group,
+        rtt_bucket,
     )
     .fetch_optional(conn)
     .await
@@ -371,6 +498,84 @@ pub async fn least_loaded(
 
 ////////////////////////////////////////////////////////////////////////////////
 
+/// A backend as seen by `most_loaded` and `least_loaded` with the inputs of their filters.
+#[derive(Debug)]
+pub struct BalancerCandidate {
+    pub id: AgentId,
+    pub group: Option<String>,
+    pub api_version: String,
+    pub disk_full: bool,
+    /// Balancer capacity falling back to the capacity, unlimited when missing.
+    pub capacity: Option<i32>,
+    /// Load counting room reserves as `most_loaded` does.
+    pub load: f64,
+    /// Load without reserves as `least_loaded` does.
+    pub taken: f64,
+}
+
+// Returns all the backends with the load computed the same way as in the balancing queries above
+// so that one can tell why a backend was or wasn't picked.
+pub async fn balancer_candidates(
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Vec<BalancerCandidate>> {
+    sqlx::query_as!(
+        BalancerCandidate,
+        r#"
+        WITH
+            room_load AS (
+                SELECT
+                    a.room_id,
+                    SUM(COALESCE(rwc.video_remb, 1000000) / 1000000.0) AS taken
+                FROM agent AS a
+                INNER JOIN agent_connection AS ac
+                ON ac.agent_id = a.id
+                LEFT JOIN rtc_writer_config AS rwc
+                ON rwc.rtc_id = ac.rtc_id
+                GROUP BY a.room_id
+            ),
+            active_room AS (
+                SELECT *
+                FROM room
+                WHERE backend_id IS NOT NULL
+                AND   time @> NOW()
+            ),
+            janus_backend_load AS (
+                SELECT
+                    backend_id,
+                    SUM(taken) AS taken,
+                    SUM(GREATEST(taken, reserve)) AS load
+                FROM (
+                    SELECT DISTINCT ON(backend_id, room_id)
+                        ar.backend_id,
+                        ar.id                   AS room_id,
+                        COALESCE(rl.taken, 0)   AS taken,
+                        COALESCE(ar.reserve, 0) AS reserve
+                    FROM active_room AS ar
+                    LEFT JOIN room_load AS rl
+                    ON rl.room_id = ar.id
+                ) AS sub
+                GROUP BY backend_id
+            )
+        SELECT
+            jb.id as "id: AgentId",
+            jb."group",
+            jb.api_version,
+            jb.disk_full,
+            COALESCE(jb.balancer_capacity, jb.capacity) AS capacity,
+            COALESCE(jbl.load, 0)::FLOAT8 AS "load!: f64",
+            COALESCE(jbl.taken, 0)::FLOAT8 AS "taken!: f64"
+        FROM janus_backend AS jb
+        LEFT JOIN janus_backend_load AS jbl
+        ON jbl.backend_id = jb.id
+        ORDER BY jb.created_at
+        "#,
+    )
+    .fetch_all(conn)
+    .await
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
 struct FreeCapacityQueryRow {
     free_capacity: i32,
 }
@@ -461,6 +666,81 @@ pub async fn free_capacity(
 
 ////////////////////////////////////////////////////////////////////////////////
 
+// Load of the backend counted the same way as in `most_loaded`.
+pub async fn load(id: &AgentId, conn: &mut sqlx::PgConnection) -> sqlx::Result<i64> {
+    sqlx::query!(
+        r#"
+        WITH
+            room_load AS (
+                SELECT
+                    a.room_id,
+                    SUM(COALESCE(rwc.video_remb, 1000000) / 1000000.0) AS taken
+                FROM agent AS a
+                INNER JOIN agent_connection AS ac
+                ON ac.agent_id = a.id
+                LEFT JOIN rtc_writer_config AS rwc
+                ON rwc.rtc_id = ac.rtc_id
+                GROUP BY a.room_id
+            )
+        SELECT
+            COALESCE(SUM(GREATEST(COALESCE(rl.taken, 0), COALESCE(r.reserve, 0))), 0)::BIGINT
+                AS "load!: i64"
+        FROM room AS r
+        LEFT JOIN room_load AS rl
+        ON rl.room_id = r.id
+        WHERE r.backend_id = $1
+        AND   r.time @> NOW()
+        "#,
+        id as &AgentId,
+    )
+    .fetch_one(conn)
+    .await
+    .map(|r| r.load)
+}
+
+// The same as above but as if the room had the given reserve.
+pub async fn load_with_reserve(
+    id: &AgentId,
+    room_id: db::room::Id,
+    reserve: i32,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<i64> {
+    sqlx::query!(
+        r#"
+        WITH
+            room_load AS (
+                SELECT
+                    a.room_id,
+                    SUM(COALESCE(rwc.video_remb, 1000000) / 1000000.0) AS taken
+                FROM agent AS a
+                INNER JOIN agent_connection AS ac
+                ON ac.agent_id = a.id
+                LEFT JOIN rtc_writer_config AS rwc
+                ON rwc.rtc_id = ac.rtc_id
+                GROUP BY a.room_id
+            )
+        SELECT
+            COALESCE(SUM(GREATEST(
+                COALESCE(rl.taken, 0),
+                CASE WHEN r.id = $2 THEN $3 ELSE COALESCE(r.reserve, 0) END
+            )), 0)::BIGINT AS "load!: i64"
+        FROM room AS r
+        LEFT JOIN room_load AS rl
+        ON rl.room_id = r.id
+        WHERE r.backend_id = $1
+        AND   r.time @> NOW()
+        "#,
+        id as &AgentId,
+        room_id as db::room::Id,
+        reserve,
+    )
+    .fetch_one(conn)
+    .await
+    .map(|r| r.load)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
 pub struct TotalCapacityResult {
     total_capacity: Option<i64>,
 }
@@ -560,6 +840,98 @@ pub async fn reserve_load_for_each_backend(
     .await
 }
 
+/// Stores the share of the recordings disk used on the backend as reported by its plugin.
+/// Backends with `disk_full` set don't get new recorded rooms.
+pub async fn set_disk_usage(
+    session_id: SessionId,
+    disk_usage: f32,
+    disk_full: bool,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Option<AgentId>> {
+    sqlx::query!(
+        r#"
+        UPDATE janus_backend
+        SET disk_usage = $2,
+            disk_full = $3
+        WHERE session_id = $1
+        RETURNING id as "id: AgentId"
+        "#,
+        session_id as SessionId,
+        disk_usage,
+        disk_full,
+    )
+    .fetch_optional(conn)
+    .await
+    .map(|r| r.map(|r| r.id))
+}
+
+#[derive(Debug)]
+pub struct DiskUsage {
+    pub backend_id: AgentId,
+    pub disk_usage: Option<f32>,
+    pub disk_full: bool,
+}
+
+pub async fn disk_usage_for_each_backend(
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Vec<DiskUsage>> {
+    sqlx::query_as!(
+        DiskUsage,
+        r#"
+        SELECT
+            id as "backend_id: AgentId",
+            disk_usage,
+            disk_full
+        FROM janus_backend
+        "#
+    )
+    .fetch_all(conn)
+    .await
+}
+
+/// Stores the round trip of a service ping to the backend, `None` if it hasn't answered.
+pub async fn set_rtt(
+    id: &AgentId,
+    rtt_ms: Option<i32>,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        r#"
+        UPDATE janus_backend
+        SET rtt_ms = $2
+        WHERE id = $1
+        "#,
+        id as &AgentId,
+        rtt_ms,
+    )
+    .execute(conn)
+    .await
+    .map(|_| ())
+}
+
+#[derive(Debug)]
+pub struct Rtt {
+    pub backend_id: AgentId,
+    pub group: Option<String>,
+    pub rtt_ms: Option<i32>,
+}
+
+pub async fn rtt_for_each_backend(conn: &mut sqlx::PgConnection) -> sqlx::Result<Vec<Rtt>> {
+    sqlx::query_as!(
+        Rtt,
+        r#"
+        SELECT
+            id as "backend_id: AgentId",
+            "group",
+            rtt_ms
+        FROM janus_backend
+        ORDER BY "group", created_at
+        "#
+    )
+    .fetch_all(conn)
+    .await
+}
+
 #[cfg(test)]
 mod tests {
     use chrono::{Duration, Utc};
@@ -658,4 +1030,105 @@ mod tests {
                 assert_eq!(b.load, *expected_load as i64);
             });
     }
+
+    #[sqlx::test]
+    async fn skip_full_disk_for_recorded_rooms(pool: sqlx::PgPool) {
+        let now = Utc::now();
+        let mut conn = TestDb::new(pool).get_conn().await;
+
+        let full = shared_helpers::insert_janus_backend(
+            &mut conn,
+            "test",
+            SessionId::random(),
+            HandleId::random(),
+        )
+        .await;
+
+        let room = factory::Room::new()
+            .audience(USR_AUDIENCE)
+            .time((
+                Bound::Included(now),
+                Bound::Excluded(now + Duration::hours(1)),
+            ))
+            .rtc_sharing_policy(RtcSharingPolicy::Shared)
+            .insert(&mut conn)
+            .await;
+
+        super::set_disk_usage(full.session_id(), 0.95, true, &mut conn)
+            .await
+            .expect("Db query failed")
+            .expect("Backend not found");
+
+        let backend = super::most_loaded(room.id(), None, None, &mut conn)
+            .await
+            .expect("Db query failed");
+        assert!(backend.is_none());
+
+        let backend = super::least_loaded(room.id(), None, None, &mut conn)
+            .await
+            .expect("Db query failed");
+        assert!(backend.is_none());
+
+        let available = shared_helpers::insert_janus_backend(
+            &mut conn,
+            "test",
+            SessionId::random(),
+            HandleId::random(),
+        )
+        .await;
+
+        let backend = super::most_loaded(room.id(), None, None, &mut conn)
+            .await
+            .expect("Db query failed")
+            .expect("No backend found");
+        assert_eq!(backend.id(), available.id());
+    }
+
+    #[sqlx::test]
+    async fn prefer_lower_rtt(pool: sqlx::PgPool) {
+        let now = Utc::now();
+        let mut conn = TestDb::new(pool).get_conn().await;
+
+        let mut backends = vec![];
+
+        for rtt_ms in [45, 3] {
+            let backend = shared_helpers::insert_janus_backend(
+                &mut conn,
+                "test",
+                SessionId::random(),
+                HandleId::random(),
+            )
+            .await;
+
+            super::set_rtt(backend.id(), Some(rtt_ms), &mut conn)
+                .await
+                .expect("Db query failed");
+
+            backends.push(backend);
+        }
+
+        let room = factory::Room::new()
+            .audience(USR_AUDIENCE)
+            .time((
+                Bound::Included(now),
+                Bound::Excluded(now + Duration::hours(1)),
+            ))
+            .rtc_sharing_policy(RtcSharingPolicy::Shared)
+            .insert(&mut conn)
+            .await;
+
+        for _ in 0..5 {
+            let backend = super::most_loaded(room.id(), None, Some(10), &mut conn)
+                .await
+                .expect("Db query failed")
+                .expect("No backend found");
+            assert_eq!(backend.id(), backends[1].id());
+
+            let backend = super::least_loaded(room.id(), None, Some(10), &mut conn)
+                .await
+                .expect("Db query failed")
+                .expect("No backend found");
+            assert_eq!(backend.id(), backends[1].id());
+        }
+    }
 }
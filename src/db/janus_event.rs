@@ -0,0 +1,143 @@
+use chrono::{serde::ts_milliseconds, DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use svc_agent::AgentId;
+
+use crate::db;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// A room whose raw Janus events get stored until `expires_at`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Capture {
+    pub room_id: db::room::Id,
+    #[serde(with = "ts_milliseconds")]
+    pub started_at: DateTime<Utc>,
+    #[serde(with = "ts_milliseconds")]
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Starts capturing the room's events or prolongs the running capture.
+pub async fn start_capture(
+    room_id: db::room::Id,
+    expires_at: DateTime<Utc>,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Capture> {
+    sqlx::query_as!(
+        Capture,
+        r#"
+        INSERT INTO janus_event_capture (room_id, expires_at)
+        VALUES ($1, $2)
+        ON CONFLICT (room_id) DO UPDATE
+        SET expires_at = EXCLUDED.expires_at
+        RETURNING
+            room_id as "room_id: db::room::Id",
+            started_at,
+            expires_at
+        "#,
+        room_id as db::room::Id,
+        expires_at,
+    )
+    .fetch_one(conn)
+    .await
+}
+
+pub async fn stop_capture(
+    room_id: db::room::Id,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Option<Capture>> {
+    sqlx::query_as!(
+        Capture,
+        r#"
+        DELETE FROM janus_event_capture
+        WHERE room_id = $1
+        RETURNING
+            room_id as "room_id: db::room::Id",
+            started_at,
+            expires_at
+        "#,
+        room_id as db::room::Id,
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+/// Rooms being captured right now.
+pub async fn capturing_rooms(conn: &mut sqlx::PgConnection) -> sqlx::Result<Vec<db::room::Id>> {
+    sqlx::query!(
+        r#"
+        SELECT room_id as "room_id: db::room::Id"
+        FROM janus_event_capture
+        WHERE expires_at > NOW()
+        "#,
+    )
+    .fetch_all(conn)
+    .await
+    .map(|rows| rows.into_iter().map(|r| r.room_id).collect())
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+pub async fn insert(
+    room_id: db::room::Id,
+    backend_id: &AgentId,
+    payload: &JsonValue,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO janus_event (room_id, backend_id, payload)
+        VALUES ($1, $2, $3)
+        "#,
+        room_id as db::room::Id,
+        backend_id as &AgentId,
+        payload,
+    )
+    .execute(conn)
+    .await
+    .map(|_| ())
+}
+
+pub async fn count(room_id: db::room::Id, conn: &mut sqlx::PgConnection) -> sqlx::Result<i64> {
+    sqlx::query!(
+        r#"
+        SELECT COUNT(*) AS "count!"
+        FROM janus_event
+        WHERE room_id = $1
+        "#,
+        room_id as db::room::Id,
+    )
+    .fetch_one(conn)
+    .await
+    .map(|r| r.count)
+}
+
+/// Removes expired captures, events older than `retained_after` and everything beyond
+/// `max_rows` most recent events.
+pub async fn trim(
+    max_rows: i64,
+    retained_after: DateTime<Utc>,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<u64> {
+    sqlx::query!("DELETE FROM janus_event_capture WHERE expires_at <= NOW()")
+        .execute(&mut *conn)
+        .await?;
+
+    sqlx::query!(
+        r#"
+        DELETE FROM janus_event
+        WHERE created_at < $2
+        OR    id IN (
+            SELECT id
+            FROM janus_event
+            ORDER BY created_at DESC
+            OFFSET $1
+        )
+        "#,
+        max_rows,
+        retained_after,
+    )
+    .execute(conn)
+    .await
+    .map(|r| r.rows_affected())
+}
@@ -3,7 +3,10 @@ use serde::{Deserialize, Serialize};
 use std::ops::Bound;
 use svc_agent::AgentId;
 
-use crate::{backend::janus::client::HandleId, db};
+use crate::{
+    backend::janus::client::{HandleId, SessionId},
+    db,
+};
 
 use super::room::TimePg;
 
@@ -30,12 +33,10 @@ pub struct Object {
 }
 
 impl Object {
-    #[cfg(test)]
     pub fn id(&self) -> Id {
         self.id
     }
 
-    #[cfg(test)]
     pub fn handle_id(&self) -> HandleId {
         self.handle_id
     }
@@ -54,11 +55,14 @@ impl Object {
         self.label.as_ref()
     }
 
-    #[cfg(test)]
     pub fn sent_by(&self) -> &AgentId {
         &self.sent_by
     }
 
+    pub fn with_sent_by(self, sent_by: AgentId) -> Self {
+        Self { sent_by, ..self }
+    }
+
     pub fn time(&self) -> Option<Time> {
         self.time.as_ref().map(|t| Time::from(t.clone()))
     }
@@ -107,6 +111,13 @@ impl ListQuery {
         }
     }
 
+    pub fn active(self, active: bool) -> Self {
+        Self {
+            active: Some(active),
+            ..self
+        }
+    }
+
     pub fn offset(self, offset: i64) -> Self {
         Self {
             offset: Some(offset),
@@ -365,7 +376,55 @@ pub async fn stop_running_streams_by_backend(
     .await
 }
 
-#[cfg(test)]
+/// A running stream of an owned rtc lasting longer than the room's `stream_max_duration`.
+#[derive(Debug)]
+pub struct ExpiredStream {
+    pub id: Id,
+    pub rtc_id: db::rtc::Id,
+    pub room_id: db::room::Id,
+    pub handle_id: HandleId,
+    pub session_id: SessionId,
+    pub backend_id: AgentId,
+    pub sent_by: AgentId,
+    pub started_at: DateTime<Utc>,
+    pub max_duration: i32,
+}
+
+pub async fn expired_in_owned_rooms(
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Vec<ExpiredStream>> {
+    sqlx::query_as!(
+        ExpiredStream,
+        r#"
+        SELECT
+            jrs.id as "id: Id",
+            jrs.rtc_id as "rtc_id: db::rtc::Id",
+            r.id as "room_id: db::room::Id",
+            jrs.handle_id as "handle_id: HandleId",
+            jb.session_id as "session_id: SessionId",
+            jrs.backend_id as "backend_id: AgentId",
+            jrs.sent_by as "sent_by: AgentId",
+            LOWER(jrs.time) as "started_at!",
+            r.stream_max_duration as "max_duration!"
+        FROM janus_rtc_stream AS jrs
+        INNER JOIN rtc
+        ON rtc.id = jrs.rtc_id
+        INNER JOIN room AS r
+        ON r.id = rtc.room_id
+        INNER JOIN janus_backend AS jb
+        ON jb.id = jrs.backend_id
+        WHERE r.rtc_sharing_policy = 'owned'
+        AND   r.stream_max_duration IS NOT NULL
+        AND   LOWER(jrs.time) IS NOT NULL
+        AND   UPPER_INF(jrs.time)
+        AND   LOWER(jrs.time) + MAKE_INTERVAL(secs => r.stream_max_duration) < NOW()
+        ORDER BY LOWER(jrs.time)
+        "#,
+    )
+    .fetch_all(conn)
+    .await
+}
+
 pub async fn get_rtc_stream(
     conn: &mut sqlx::PgConnection,
     id: db::janus_rtc_stream::Id,
@@ -421,4 +480,59 @@ mod tests {
             Some((std::ops::Bound::Included(_), std::ops::Bound::Excluded(_)))
         ));
     }
+
+    #[sqlx::test]
+    async fn find_expired_in_owned_rooms(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let mut conn = db.get_conn().await;
+
+        let mut streams = vec![];
+
+        for stream_max_duration in [Some(1), None] {
+            let mut room = factory::Room::new()
+                .audience(USR_AUDIENCE)
+                .time((Bound::Included(Utc::now()), Bound::Unbounded))
+                .rtc_sharing_policy(db::rtc::SharingPolicy::Owned);
+
+            if let Some(stream_max_duration) = stream_max_duration {
+                room = room.stream_max_duration(stream_max_duration);
+            }
+
+            let room = room.insert(&mut conn).await;
+            let rtc = factory::Rtc::new(room.id()).insert(&mut conn).await;
+
+            let rtc_stream = factory::JanusRtcStream::new(USR_AUDIENCE)
+                .rtc(&rtc)
+                .insert(&mut conn)
+                .await;
+
+            start(rtc_stream.id(), &mut conn)
+                .await
+                .expect("Failed to start rtc stream");
+
+            streams.push((room, rtc_stream));
+        }
+
+        tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+
+        let expired = expired_in_owned_rooms(&mut conn)
+            .await
+            .expect("Failed to find expired streams");
+
+        let (room, rtc_stream) = &streams[0];
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].id, rtc_stream.id());
+        assert_eq!(expired[0].room_id, room.id());
+        assert_eq!(expired[0].max_duration, 1);
+
+        stop(rtc_stream.id(), &mut conn)
+            .await
+            .expect("Failed to stop rtc stream");
+
+        let expired = expired_in_owned_rooms(&mut conn)
+            .await
+            .expect("Failed to find expired streams");
+
+        assert!(expired.is_empty());
+    }
 }
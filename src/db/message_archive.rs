@@ -0,0 +1,180 @@
+use chrono::{serde::ts_seconds, DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use svc_agent::AgentId;
+use uuid::Uuid;
+
+use crate::db;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// A `message.broadcast` payload retained for the rooms with `archive_messages` enabled.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Object {
+    pub id: Uuid,
+    pub room_id: db::room::Id,
+    pub agent_id: AgentId,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    pub data: JsonValue,
+    #[serde(with = "ts_seconds")]
+    pub created_at: DateTime<Utc>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+pub struct InsertQuery<'a> {
+    room_id: db::room::Id,
+    agent_id: &'a AgentId,
+    label: Option<&'a str>,
+    data: &'a JsonValue,
+}
+
+impl<'a> InsertQuery<'a> {
+    pub fn new(room_id: db::room::Id, agent_id: &'a AgentId, data: &'a JsonValue) -> Self {
+        Self {
+            room_id,
+            agent_id,
+            label: None,
+            data,
+        }
+    }
+
+    pub fn label(self, label: &'a str) -> Self {
+        Self {
+            label: Some(label),
+            ..self
+        }
+    }
+
+    pub async fn execute(&self, conn: &mut sqlx::PgConnection) -> sqlx::Result<Object> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            INSERT INTO message_archive (room_id, agent_id, label, data)
+            VALUES ($1, $2, $3, $4)
+            RETURNING
+                id,
+                room_id as "room_id: db::room::Id",
+                agent_id as "agent_id: AgentId",
+                label,
+                data,
+                created_at
+            "#,
+            self.room_id as db::room::Id,
+            self.agent_id as &AgentId,
+            self.label,
+            self.data,
+        )
+        .fetch_one(conn)
+        .await
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+pub struct ListQuery<'a> {
+    room_id: db::room::Id,
+    labels: Option<&'a [String]>,
+    search: Option<&'a str>,
+    offset: Option<i64>,
+    limit: Option<i64>,
+}
+
+impl<'a> ListQuery<'a> {
+    pub fn new(room_id: db::room::Id) -> Self {
+        Self {
+            room_id,
+            labels: None,
+            search: None,
+            offset: None,
+            limit: None,
+        }
+    }
+
+    pub fn labels(self, labels: &'a [String]) -> Self {
+        Self {
+            labels: Some(labels),
+            ..self
+        }
+    }
+
+    /// Matches the messages containing all the words of the query in their string values.
+    pub fn search(self, search: &'a str) -> Self {
+        Self {
+            search: Some(search),
+            ..self
+        }
+    }
+
+    pub fn offset(self, offset: i64) -> Self {
+        Self {
+            offset: Some(offset),
+            ..self
+        }
+    }
+
+    pub fn limit(self, limit: i64) -> Self {
+        Self {
+            limit: Some(limit),
+            ..self
+        }
+    }
+
+    /// Returns the most recent messages first.
+    pub async fn execute(&self, conn: &mut sqlx::PgConnection) -> sqlx::Result<Vec<Object>> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            SELECT
+                id,
+                room_id as "room_id: db::room::Id",
+                agent_id as "agent_id: AgentId",
+                label,
+                data,
+                created_at
+            FROM message_archive
+            WHERE room_id = $1
+            AND   ($2::TEXT[] IS NULL OR label = ANY($2::TEXT[]))
+            AND   ($3::TEXT IS NULL OR search @@ plainto_tsquery('simple', $3::TEXT))
+            ORDER BY created_at DESC, id
+            OFFSET $4
+            LIMIT $5
+            "#,
+            self.room_id as db::room::Id,
+            self.labels,
+            self.search,
+            self.offset.unwrap_or(0),
+            self.limit,
+        )
+        .fetch_all(conn)
+        .await
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// All the archived messages of the room in the order they were sent.
+pub async fn list_all(
+    room_id: db::room::Id,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Vec<Object>> {
+    sqlx::query_as!(
+        Object,
+        r#"
+        SELECT
+            id,
+            room_id as "room_id: db::room::Id",
+            agent_id as "agent_id: AgentId",
+            label,
+            data,
+            created_at
+        FROM message_archive
+        WHERE room_id = $1
+        ORDER BY created_at, id
+        "#,
+        room_id as db::room::Id,
+    )
+    .fetch_all(conn)
+    .await
+}
@@ -19,14 +19,36 @@ pub async fn create_pool(
 
 pub mod agent;
 pub mod agent_connection;
+pub mod agent_session;
+pub mod backend_error;
+pub mod classroom_ban;
+pub mod composite_recording;
 pub mod group_agent;
 pub mod id;
 pub mod janus_backend;
+pub mod janus_event;
 pub mod janus_rtc_stream;
+pub mod message_archive;
 pub mod orphaned_room;
+pub mod poll;
 pub mod recording;
+pub mod relay_usage;
 pub mod room;
+pub mod room_backend_assignment;
+pub mod room_bulk_close;
+pub mod room_health;
+pub mod room_host;
+pub mod room_quality_hint;
+pub mod room_reserve_audit;
+pub mod room_schedule;
+pub mod room_speaking_time;
+pub mod room_summary;
+pub mod room_template;
+pub mod room_upload_job;
 pub mod rtc;
 pub mod rtc_reader_config;
+pub mod rtc_signal_artifact;
+pub mod rtc_subscribe_set;
 pub mod rtc_writer_config;
 pub mod rtc_writer_config_snapshot;
+pub mod tenant_usage;
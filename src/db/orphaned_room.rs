@@ -1,8 +1,9 @@
 use chrono::{serde::ts_seconds, DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
 use svc_agent::AgentId;
 
-use super::room::Object as Room;
+use super::room::{AudioProcessing, Object as Room};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Object {
@@ -49,6 +50,16 @@ struct TimedOutRow {
     rtc_sharing_policy: super::rtc::SharingPolicy,
     infinite: bool,
     closed_by: Option<AgentId>,
+    version: i32,
+    audio_processing: Option<Json<AudioProcessing>>,
+    archive_messages: bool,
+    composite_recording: bool,
+    record_rtcs: bool,
+    stream_max_duration: Option<i32>,
+    metadata: Option<serde_json::Value>,
+    lobby: bool,
+    active_speaker_events: bool,
+    max_agents: Option<i32>,
 }
 
 impl TimedOutRow {
@@ -72,7 +83,17 @@ impl TimedOutRow {
                 host: self.host,
                 timed_out: self.timed_out,
                 closed_by: self.closed_by,
+                version: self.version,
                 infinite: self.infinite,
+                audio_processing: self.audio_processing,
+                archive_messages: self.archive_messages,
+                composite_recording: self.composite_recording,
+                record_rtcs: self.record_rtcs,
+                stream_max_duration: self.stream_max_duration,
+                metadata: self.metadata,
+                lobby: self.lobby,
+                active_speaker_events: self.active_speaker_events,
+                max_agents: self.max_agents,
             }),
         )
     }
@@ -100,7 +121,17 @@ pub async fn get_timed_out(
             r.backend as "backend: super::room::RoomBackend",
             r.rtc_sharing_policy as "rtc_sharing_policy: super::rtc::SharingPolicy",
             r.infinite,
-            r.closed_by as "closed_by: AgentId"
+            r.closed_by as "closed_by: AgentId",
+            r.version,
+            r.audio_processing as "audio_processing: Json<AudioProcessing>",
+            r.archive_messages,
+            r.composite_recording,
+            r.record_rtcs,
+            r.stream_max_duration,
+            r.metadata,
+            r.lobby,
+            r.active_speaker_events,
+            r.max_agents
         FROM orphaned_room as orph
         LEFT JOIN room as r
         ON r.id = orph.id
@@ -0,0 +1,270 @@
+use chrono::{
+    serde::{ts_seconds, ts_seconds_option},
+    DateTime, Utc,
+};
+use serde::{Deserialize, Serialize};
+use svc_agent::AgentId;
+
+use crate::db;
+
+////////////////////////////////////////////////////////////////////////////////
+
+pub type Id = db::id::Id;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Object {
+    pub id: Id,
+    pub room_id: db::room::Id,
+    pub created_by: AgentId,
+    pub question: String,
+    pub options: Vec<String>,
+    #[serde(with = "ts_seconds_option")]
+    pub closed_at: Option<DateTime<Utc>>,
+    #[serde(with = "ts_seconds")]
+    pub created_at: DateTime<Utc>,
+}
+
+impl Object {
+    pub fn id(&self) -> Id {
+        self.id
+    }
+
+    pub fn room_id(&self) -> db::room::Id {
+        self.room_id
+    }
+
+    pub fn options(&self) -> &[String] {
+        &self.options
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.closed_at.is_some()
+    }
+}
+
+/// Poll with the number of votes for each of its options.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Results {
+    #[serde(flatten)]
+    pub poll: Object,
+    pub votes: Vec<i64>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+pub struct FindQuery {
+    id: Id,
+}
+
+impl FindQuery {
+    pub fn new(id: Id) -> Self {
+        Self { id }
+    }
+
+    pub async fn execute(&self, conn: &mut sqlx::PgConnection) -> sqlx::Result<Option<Object>> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            SELECT
+                id as "id: Id",
+                room_id as "room_id: db::room::Id",
+                created_by as "created_by: AgentId",
+                question,
+                options,
+                closed_at,
+                created_at
+            FROM poll
+            WHERE id = $1
+            "#,
+            self.id as Id,
+        )
+        .fetch_optional(conn)
+        .await
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+pub struct InsertQuery<'a> {
+    room_id: db::room::Id,
+    created_by: &'a AgentId,
+    question: &'a str,
+    options: &'a [String],
+}
+
+impl<'a> InsertQuery<'a> {
+    pub fn new(
+        room_id: db::room::Id,
+        created_by: &'a AgentId,
+        question: &'a str,
+        options: &'a [String],
+    ) -> Self {
+        Self {
+            room_id,
+            created_by,
+            question,
+            options,
+        }
+    }
+
+    pub async fn execute(&self, conn: &mut sqlx::PgConnection) -> sqlx::Result<Object> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            INSERT INTO poll (room_id, created_by, question, options)
+            VALUES ($1, $2, $3, $4)
+            RETURNING
+                id as "id: Id",
+                room_id as "room_id: db::room::Id",
+                created_by as "created_by: AgentId",
+                question,
+                options,
+                closed_at,
+                created_at
+            "#,
+            self.room_id as db::room::Id,
+            self.created_by as &AgentId,
+            self.question,
+            self.options,
+        )
+        .fetch_one(conn)
+        .await
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Closes the poll. Returns `None` if the poll is missing or has already been closed.
+pub async fn close(id: Id, conn: &mut sqlx::PgConnection) -> sqlx::Result<Option<Object>> {
+    sqlx::query_as!(
+        Object,
+        r#"
+        UPDATE poll
+        SET closed_at = NOW()
+        WHERE id = $1
+        AND   closed_at IS NULL
+        RETURNING
+            id as "id: Id",
+            room_id as "room_id: db::room::Id",
+            created_by as "created_by: AgentId",
+            question,
+            options,
+            closed_at,
+            created_at
+        "#,
+        id as Id,
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+pub struct VoteInsertQuery<'a> {
+    poll_id: Id,
+    agent_id: &'a AgentId,
+    option_idx: i32,
+}
+
+impl<'a> VoteInsertQuery<'a> {
+    pub fn new(poll_id: Id, agent_id: &'a AgentId, option_idx: i32) -> Self {
+        Self {
+            poll_id,
+            agent_id,
+            option_idx,
+        }
+    }
+
+    /// Returns `false` if the agent has already voted in the poll.
+    pub async fn execute(&self, conn: &mut sqlx::PgConnection) -> sqlx::Result<bool> {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO poll_vote (poll_id, agent_id, option_idx)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (poll_id, agent_id) DO NOTHING
+            "#,
+            self.poll_id as Id,
+            self.agent_id as &AgentId,
+            self.option_idx,
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+pub async fn results(poll: Object, conn: &mut sqlx::PgConnection) -> sqlx::Result<Results> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT
+            option_idx,
+            COUNT(*) as "count!: i64"
+        FROM poll_vote
+        WHERE poll_id = $1
+        GROUP BY option_idx
+        "#,
+        poll.id as Id,
+    )
+    .fetch_all(conn)
+    .await?;
+
+    let mut votes = vec![0; poll.options.len()];
+
+    for row in rows {
+        if let Some(count) = votes.get_mut(row.option_idx as usize) {
+            *count = row.count;
+        }
+    }
+
+    Ok(Results { poll, votes })
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::{db::TestDb, prelude::*};
+
+    #[sqlx::test]
+    async fn vote_once(pool: sqlx::PgPool) {
+        let mut conn = TestDb::new(pool).get_conn().await;
+        let room = shared_helpers::insert_room(&mut conn).await;
+        let host = TestAgent::new("web", "host", USR_AUDIENCE);
+        let options = vec!["yes".to_owned(), "no".to_owned()];
+
+        let poll = InsertQuery::new(room.id(), host.agent_id(), "Ready?", &options)
+            .execute(&mut conn)
+            .await
+            .expect("Failed to insert poll");
+
+        for (label, option_idx) in [("alpha", 0), ("bravo", 1), ("charlie", 1)] {
+            let agent = TestAgent::new("web", label, USR_AUDIENCE);
+
+            let voted = VoteInsertQuery::new(poll.id(), agent.agent_id(), option_idx)
+                .execute(&mut conn)
+                .await
+                .expect("Failed to insert vote");
+
+            assert!(voted);
+        }
+
+        // Repeated vote is ignored.
+        let agent = TestAgent::new("web", "alpha", USR_AUDIENCE);
+        let voted = VoteInsertQuery::new(poll.id(), agent.agent_id(), 1)
+            .execute(&mut conn)
+            .await
+            .expect("Failed to insert vote");
+
+        assert!(!voted);
+
+        let results = results(poll, &mut conn)
+            .await
+            .expect("Failed to count votes");
+
+        assert_eq!(results.votes, vec![1, 2]);
+    }
+}
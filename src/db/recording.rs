@@ -64,6 +64,15 @@ pub struct Object {
     pub segments: Option<Vec<SegmentPg>>,
     pub status: Status,
     pub mjr_dumps_uris: Option<Vec<String>>,
+    /// When the storage confirmed the recording's object with a non-zero size.
+    #[serde(with = "crate::serde::ts_seconds_option")]
+    pub verified_at: Option<DateTime<Utc>>,
+    /// Object size the storage reported, zero for an empty upload.
+    pub object_size: Option<i64>,
+    /// MD5 of the uploaded file the backend computed, hex encoded.
+    pub checksum: Option<String>,
+    /// ETag of the object the storage reported.
+    pub object_checksum: Option<String>,
 }
 
 impl Object {
@@ -75,6 +84,26 @@ impl Object {
         self.status
     }
 
+    pub fn verified_at(&self) -> Option<DateTime<Utc>> {
+        self.verified_at
+    }
+
+    pub fn checksum(&self) -> Option<&str> {
+        self.checksum.as_deref()
+    }
+
+    /// Whether both the backend and the storage reported checksums and they differ. ETags of
+    /// multipart uploads aren't MD5 of the object so they can't be compared.
+    pub fn checksum_mismatch(&self) -> bool {
+        match (&self.checksum, &self.object_checksum) {
+            (Some(checksum), Some(object_checksum)) => {
+                let object_checksum = object_checksum.trim_matches('"');
+                !object_checksum.contains('-') && !object_checksum.eq_ignore_ascii_case(checksum)
+            }
+            _ => false,
+        }
+    }
+
     /// Get a reference to the object's janus dumps uris.
     pub fn mjr_dumps_uris(&self) -> Option<&Vec<String>> {
         self.mjr_dumps_uris.as_ref()
@@ -102,7 +131,11 @@ impl FindQuery {
                 started_at,
                 segments as "segments: Vec<SegmentPg>",
                 status as "status: Status",
-                mjr_dumps_uris
+                mjr_dumps_uris,
+                verified_at,
+                object_size,
+                checksum,
+                object_checksum
             FROM recording
             WHERE
                 rtc_id = $1
@@ -137,7 +170,11 @@ impl InsertQuery {
                 started_at,
                 segments as "segments: Vec<SegmentPg>",
                 status as "status: Status",
-                mjr_dumps_uris
+                mjr_dumps_uris,
+                verified_at,
+                object_size,
+                checksum,
+                object_checksum
             "#,
             self.rtc_id as db::rtc::Id,
         )
@@ -153,6 +190,7 @@ pub struct UpdateQuery {
     rtc_id: db::rtc::Id,
     status: Option<Status>,
     mjr_dumps_uris: Option<Vec<String>>,
+    checksum: Option<String>,
 }
 
 impl UpdateQuery {
@@ -161,6 +199,7 @@ impl UpdateQuery {
             rtc_id,
             status: None,
             mjr_dumps_uris: None,
+            checksum: None,
         }
     }
 
@@ -178,6 +217,10 @@ impl UpdateQuery {
         }
     }
 
+    pub fn checksum(self, checksum: Option<String>) -> Self {
+        Self { checksum, ..self }
+    }
+
     pub async fn execute(&self, conn: &mut sqlx::PgConnection) -> sqlx::Result<Object> {
         sqlx::query_as!(
             Object,
@@ -185,7 +228,8 @@ impl UpdateQuery {
             UPDATE recording
             SET
                 status = $1,
-                mjr_dumps_uris = $2
+                mjr_dumps_uris = $2,
+                checksum = COALESCE($4, checksum)
             WHERE
                 rtc_id = $3 AND
                 -- do not overwrite existing `ready` status with `missing`
@@ -198,13 +242,243 @@ impl UpdateQuery {
                 started_at,
                 segments as "segments: Vec<SegmentPg>",
                 status as "status: Status",
-                mjr_dumps_uris
+                mjr_dumps_uris,
+                verified_at,
+                object_size,
+                checksum,
+                object_checksum
             "#,
             self.status as Option<Status>,
             self.mjr_dumps_uris.as_ref().map(|m| m.as_slice()),
             self.rtc_id as db::rtc::Id,
+            self.checksum,
         )
         .fetch_one(conn)
         .await
     }
 }
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Marks in progress recordings of the given rtcs as missing if no media has ever been
+/// recorded: the recording hasn't started and none of the rtc's streams has started either.
+/// Returns the rtcs whose recordings were marked.
+pub async fn mark_empty_as_missing(
+    rtc_ids: &[db::rtc::Id],
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Vec<db::rtc::Id>> {
+    sqlx::query_scalar!(
+        r#"
+        UPDATE recording
+        SET status = 'missing'::recording_status
+        WHERE
+            rtc_id = ANY($1) AND
+            status = 'in_progress' AND
+            started_at IS NULL AND
+            COALESCE(CARDINALITY(segments), 0) = 0 AND
+            NOT EXISTS (
+                SELECT 1
+                FROM janus_rtc_stream
+                WHERE
+                    janus_rtc_stream.rtc_id = recording.rtc_id AND
+                    janus_rtc_stream.time IS NOT NULL
+            )
+        RETURNING rtc_id as "rtc_id: db::rtc::Id"
+        "#,
+        rtc_ids as &[db::rtc::Id],
+    )
+    .fetch_all(conn)
+    .await
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Records the size and the checksum of the recording's object reported by the storage.
+/// Only a non-empty object counts as verified.
+pub async fn set_verified(
+    rtc_id: db::rtc::Id,
+    object_size: i64,
+    object_checksum: Option<&str>,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Option<Object>> {
+    sqlx::query_as!(
+        Object,
+        r#"
+        UPDATE recording
+        SET
+            object_size = $2::bigint,
+            object_checksum = $3,
+            verified_at = CASE WHEN $2::bigint > 0 THEN now() END
+        WHERE
+            rtc_id = $1
+        RETURNING
+            rtc_id as "rtc_id: db::rtc::Id",
+            started_at,
+            segments as "segments: Vec<SegmentPg>",
+            status as "status: Status",
+            mjr_dumps_uris,
+            verified_at,
+            object_size,
+            checksum,
+            object_checksum
+        "#,
+        rtc_id as db::rtc::Id,
+        object_size,
+        object_checksum,
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug)]
+pub struct ArchiveDue {
+    pub rtc_id: db::rtc::Id,
+    pub room_id: db::room::Id,
+    pub room_closed_at: DateTime<Utc>,
+}
+
+/// Marks ready recordings of the audience's rooms closed before the given time as due for
+/// archiving and returns them, oldest first. Every recording gets returned once.
+pub async fn mark_archive_due(
+    audience: &str,
+    closed_before: DateTime<Utc>,
+    limit: i64,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Vec<ArchiveDue>> {
+    sqlx::query_as!(
+        ArchiveDue,
+        r#"
+        WITH due AS (
+            SELECT rec.rtc_id
+            FROM recording AS rec
+            INNER JOIN rtc
+            ON rtc.id = rec.rtc_id
+            INNER JOIN room AS r
+            ON r.id = rtc.room_id
+            WHERE r.audience = $1
+            AND   rec.status = 'ready'
+            AND   rec.archive_due_at IS NULL
+            AND   UPPER(r.time) < $2
+            ORDER BY UPPER(r.time)
+            LIMIT $3
+            FOR UPDATE OF rec SKIP LOCKED
+        )
+        UPDATE recording
+        SET archive_due_at = now()
+        FROM due, rtc, room
+        WHERE recording.rtc_id = due.rtc_id
+        AND   rtc.id = recording.rtc_id
+        AND   room.id = rtc.room_id
+        RETURNING
+            recording.rtc_id as "rtc_id: db::rtc::Id",
+            rtc.room_id as "room_id: db::room::Id",
+            UPPER(room.time) as "room_closed_at!"
+        "#,
+        audience,
+        closed_before,
+        limit,
+    )
+    .fetch_all(conn)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::{db::TestDb, prelude::*};
+
+    #[sqlx::test]
+    async fn mark_only_empty_recordings_as_missing(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let mut conn = db.get_conn().await;
+
+        // The stream of this rtc has started so its recording has some media.
+        let rtc_stream = factory::JanusRtcStream::new(USR_AUDIENCE)
+            .insert(&mut conn)
+            .await;
+
+        db::janus_rtc_stream::start(rtc_stream.id(), &mut conn)
+            .await
+            .expect("Failed to start rtc stream");
+
+        let recorded_rtc = db::rtc::FindQuery::new(rtc_stream.rtc_id())
+            .execute(&mut conn)
+            .await
+            .expect("Failed to find rtc")
+            .expect("Missing rtc");
+
+        let recorded = shared_helpers::insert_recording(&mut conn, &recorded_rtc).await;
+
+        // Nothing has ever been streamed to this one.
+        let empty_rtc = shared_helpers::insert_rtc(&mut conn).await;
+        let empty = shared_helpers::insert_recording(&mut conn, &empty_rtc).await;
+
+        let marked = mark_empty_as_missing(&[recorded.rtc_id(), empty.rtc_id()], &mut conn)
+            .await
+            .expect("Failed to mark empty recordings");
+
+        assert_eq!(marked, vec![empty.rtc_id()]);
+
+        let recorded = FindQuery::new(recorded.rtc_id())
+            .execute(&mut conn)
+            .await
+            .expect("Failed to find recording")
+            .expect("Missing recording");
+
+        let empty = FindQuery::new(empty.rtc_id())
+            .execute(&mut conn)
+            .await
+            .expect("Failed to find recording")
+            .expect("Missing recording");
+
+        assert_eq!(recorded.status(), Status::InProgress);
+        assert_eq!(empty.status(), Status::Missing);
+    }
+
+    #[sqlx::test]
+    async fn mark_archive_due_once(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let mut conn = db.get_conn().await;
+
+        // Closed 8 hours ago.
+        let room = shared_helpers::insert_closed_room(&mut conn).await;
+        let rtc = shared_helpers::insert_rtc_with_room(&mut conn, &room).await;
+        shared_helpers::insert_recording(&mut conn, &rtc).await;
+
+        let pending_rtc = shared_helpers::insert_rtc_with_room(&mut conn, &room).await;
+        shared_helpers::insert_recording(&mut conn, &pending_rtc).await;
+
+        UpdateQuery::new(rtc.id())
+            .status(Status::Ready)
+            .execute(&mut conn)
+            .await
+            .expect("Failed to update recording");
+
+        let due = mark_archive_due(
+            USR_AUDIENCE,
+            Utc::now() - chrono::Duration::hours(9),
+            10,
+            &mut conn,
+        )
+        .await
+        .expect("Failed to mark recordings");
+
+        assert!(due.is_empty());
+
+        let due = mark_archive_due(USR_AUDIENCE, Utc::now(), 10, &mut conn)
+            .await
+            .expect("Failed to mark recordings");
+
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].rtc_id, rtc.id());
+        assert_eq!(due[0].room_id, room.id());
+
+        let due = mark_archive_due(USR_AUDIENCE, Utc::now(), 10, &mut conn)
+            .await
+            .expect("Failed to mark recordings");
+
+        assert!(due.is_empty());
+    }
+}
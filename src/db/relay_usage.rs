@@ -0,0 +1,101 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::db;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Connections of the room split by whether their media went through TURN.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Object {
+    pub room_id: db::room::Id,
+    pub classroom_id: Uuid,
+    pub relayed: i32,
+    pub direct: i32,
+}
+
+/// Connections of all the audience's rooms.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Total {
+    pub relayed: i64,
+    pub direct: i64,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+pub async fn total(audience: &str, conn: &mut sqlx::PgConnection) -> sqlx::Result<Total> {
+    sqlx::query_as!(
+        Total,
+        r#"
+        SELECT
+            COALESCE(SUM(c.relayed_connection_count), 0)::BIGINT as "relayed!: i64",
+            COALESCE(SUM(c.direct_connection_count), 0)::BIGINT as "direct!: i64"
+        FROM room_counter AS c
+        INNER JOIN room AS r
+        ON r.id = c.room_id
+        WHERE r.audience = $1
+        "#,
+        audience,
+    )
+    .fetch_one(conn)
+    .await
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+pub struct ListQuery<'a> {
+    audience: &'a str,
+    offset: Option<i64>,
+    limit: Option<i64>,
+}
+
+impl<'a> ListQuery<'a> {
+    pub fn new(audience: &'a str) -> Self {
+        Self {
+            audience,
+            offset: None,
+            limit: None,
+        }
+    }
+
+    pub fn offset(self, offset: i64) -> Self {
+        Self {
+            offset: Some(offset),
+            ..self
+        }
+    }
+
+    pub fn limit(self, limit: i64) -> Self {
+        Self {
+            limit: Some(limit),
+            ..self
+        }
+    }
+
+    /// Rooms with the most relayed connections come first.
+    pub async fn execute(&self, conn: &mut sqlx::PgConnection) -> sqlx::Result<Vec<Object>> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            SELECT
+                r.id as "room_id: db::room::Id",
+                r.classroom_id,
+                c.relayed_connection_count as relayed,
+                c.direct_connection_count as direct
+            FROM room_counter AS c
+            INNER JOIN room AS r
+            ON r.id = c.room_id
+            WHERE r.audience = $1
+            AND   c.relayed_connection_count + c.direct_connection_count > 0
+            ORDER BY c.relayed_connection_count DESC, r.created_at DESC
+            OFFSET $2
+            LIMIT $3
+            "#,
+            self.audience,
+            self.offset.unwrap_or(0),
+            self.limit,
+        )
+        .fetch_all(conn)
+        .await
+    }
+}
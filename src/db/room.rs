@@ -1,8 +1,10 @@
 use std::{fmt, ops::Bound};
 
+use anyhow::anyhow;
 use chrono::{serde::ts_seconds, DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
+use sqlx::types::Json;
 use svc_agent::AgentId;
 use uuid::Uuid;
 
@@ -17,30 +19,158 @@ use crate::{
         recording::{Object as Recording, Status as RecordingStatus},
         rtc::SharingPolicy as RtcSharingPolicy,
     },
+    serde::Time as BoundedTime,
 };
 
 use super::recording::SegmentPg;
 
 ////////////////////////////////////////////////////////////////////////////////
 
-pub type Time = (Bound<DateTime<Utc>>, Bound<DateTime<Utc>>);
+/// Time range of a room.
+///
+/// A room without opening time never opens and a room without closing time stays open
+/// until it gets closed explicitly. When both bounds are set, closing must follow opening.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RoomTime {
+    start: Bound<DateTime<Utc>>,
+    end: Bound<DateTime<Utc>>,
+}
+
+impl RoomTime {
+    pub fn new(start: Bound<DateTime<Utc>>, end: Bound<DateTime<Utc>>) -> anyhow::Result<Self> {
+        match (bound_value(start), bound_value(end)) {
+            (Some(opened_at), Some(closed_at)) if opened_at >= closed_at => Err(anyhow!(
+                "Room closing time {} is not after its opening time {}",
+                closed_at,
+                opened_at
+            )),
+            _ => Ok(Self { start, end }),
+        }
+    }
+
+    /// Checks the ordering of a time range which came from a request.
+    pub fn validate(self) -> anyhow::Result<Self> {
+        Self::new(self.start, self.end)
+    }
+
+    pub fn start(&self) -> Bound<DateTime<Utc>> {
+        self.start
+    }
+
+    pub fn end(&self) -> Bound<DateTime<Utc>> {
+        self.end
+    }
+
+    pub fn opened_at(&self) -> Option<DateTime<Utc>> {
+        bound_value(self.start)
+    }
+
+    pub fn closed_at(&self) -> Option<DateTime<Utc>> {
+        bound_value(self.end)
+    }
+
+    pub fn is_closed_at(&self, now: DateTime<Utc>) -> bool {
+        match self.end {
+            Bound::Included(t) => t < now,
+            Bound::Excluded(t) => t <= now,
+            Bound::Unbounded => false,
+        }
+    }
+
+    pub fn is_open_at(&self, now: DateTime<Utc>) -> bool {
+        let opened = match self.start {
+            Bound::Included(t) => t <= now,
+            Bound::Excluded(t) => t < now,
+            Bound::Unbounded => false,
+        };
+
+        opened && !self.is_closed_at(now)
+    }
+
+    /// Moves closing to `at` keeping the opening time.
+    pub fn close_at(self, at: DateTime<Utc>) -> Self {
+        Self {
+            start: self.start,
+            end: Bound::Excluded(at),
+        }
+    }
+
+    pub fn close_now(self) -> Self {
+        self.close_at(Utc::now())
+    }
+}
+
+fn bound_value(bound: Bound<DateTime<Utc>>) -> Option<DateTime<Utc>> {
+    match bound {
+        Bound::Included(t) | Bound::Excluded(t) => Some(t),
+        Bound::Unbounded => None,
+    }
+}
+
+/// Takes the bounds as they are, see [`RoomTime::validate`].
+impl From<BoundedTime> for RoomTime {
+    fn from((start, end): BoundedTime) -> Self {
+        Self { start, end }
+    }
+}
+
+impl From<RoomTime> for BoundedTime {
+    fn from(value: RoomTime) -> Self {
+        (value.start, value.end)
+    }
+}
+
+impl Serialize for RoomTime {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        crate::serde::ts_seconds_bound_tuple::serialize(&(self.start, self.end), serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for RoomTime {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        // Handlers validate the range to respond with `invalid_room_time`.
+        crate::serde::ts_seconds_bound_tuple::deserialize(deserializer).map(Self::from)
+    }
+}
 
 #[derive(sqlx::Type, Debug, Clone)]
 #[sqlx(transparent)]
 pub struct TimePg(sqlx::postgres::types::PgRange<DateTime<Utc>>);
 
-impl From<Time> for TimePg {
-    fn from(value: Time) -> Self {
+impl From<BoundedTime> for TimePg {
+    fn from(value: BoundedTime) -> Self {
         Self(sqlx::postgres::types::PgRange::from(value))
     }
 }
 
-impl From<TimePg> for Time {
+impl From<TimePg> for BoundedTime {
     fn from(value: TimePg) -> Self {
         (value.0.start, value.0.end)
     }
 }
 
+impl From<RoomTime> for TimePg {
+    fn from(value: RoomTime) -> Self {
+        Self::from(BoundedTime::from(value))
+    }
+}
+
+// The ordering is guaranteed by Postgres range type.
+impl From<TimePg> for RoomTime {
+    fn from(value: TimePg) -> Self {
+        Self {
+            start: value.0.start,
+            end: value.0.end,
+        }
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 pub type Id = db::id::Id;
 
@@ -83,6 +213,22 @@ impl From<RoomBackend> for RtcSharingPolicy {
 
 ////////////////////////////////////////////////////////////////////////////////
 
+/// Audio processing the backend applies to the streams published in the room.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct AudioProcessing {
+    /// Automatic gain control.
+    #[serde(default)]
+    pub agc: bool,
+    #[serde(default)]
+    pub denoise: bool,
+}
+
+impl AudioProcessing {
+    pub fn is_enabled(&self) -> bool {
+        self.agc || self.denoise
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Object {
     pub id: Id,
@@ -104,6 +250,32 @@ pub struct Object {
     pub closed_by: Option<AgentId>,
     #[serde(skip)]
     pub infinite: bool,
+    pub version: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audio_processing: Option<Json<AudioProcessing>>,
+    #[serde(default)]
+    pub archive_messages: bool,
+    #[serde(default)]
+    pub composite_recording: bool,
+    #[serde(default = "default_record_rtcs")]
+    pub record_rtcs: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream_max_duration: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<JsonValue>,
+    /// Agents entering the room wait in the lobby until a host admits them.
+    #[serde(default)]
+    pub lobby: bool,
+    /// The room gets `room.active_speaker_changed` events.
+    #[serde(default)]
+    pub active_speaker_events: bool,
+    /// Agents the room fits, unlimited if missing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_agents: Option<i32>,
+}
+
+fn default_record_rtcs() -> bool {
+    true
 }
 
 impl Object {
@@ -115,8 +287,8 @@ impl Object {
         self.id
     }
 
-    pub fn time(&self) -> Time {
-        Time::from(self.time.clone())
+    pub fn time(&self) -> RoomTime {
+        RoomTime::from(self.time.clone())
     }
 
     pub fn reserve(&self) -> Option<i32> {
@@ -124,11 +296,7 @@ impl Object {
     }
 
     pub fn is_closed(&self) -> bool {
-        match self.time().1 {
-            Bound::Included(t) => t < Utc::now(),
-            Bound::Excluded(t) => t <= Utc::now(),
-            Bound::Unbounded => false,
-        }
+        self.time().is_closed_at(Utc::now())
     }
 
     #[cfg(test)]
@@ -160,6 +328,49 @@ impl Object {
     pub fn infinite(&self) -> bool {
         self.infinite
     }
+
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    pub fn audio_processing(&self) -> Option<AudioProcessing> {
+        self.audio_processing.as_ref().map(|ap| ap.0)
+    }
+
+    pub fn archive_messages(&self) -> bool {
+        self.archive_messages
+    }
+
+    pub fn composite_recording(&self) -> bool {
+        self.composite_recording
+    }
+
+    /// Whether new rtcs of the room are recorded unless a host says otherwise.
+    pub fn record_rtcs(&self) -> bool {
+        self.record_rtcs
+    }
+
+    /// Seconds a stream of an owned rtc may last before it gets stopped.
+    pub fn stream_max_duration(&self) -> Option<i32> {
+        self.stream_max_duration
+    }
+
+    #[cfg(test)]
+    pub fn metadata(&self) -> Option<&JsonValue> {
+        self.metadata.as_ref()
+    }
+
+    pub fn lobby(&self) -> bool {
+        self.lobby
+    }
+
+    pub fn active_speaker_events(&self) -> bool {
+        self.active_speaker_events
+    }
+
+    pub fn max_agents(&self) -> Option<i32> {
+        self.max_agents
+    }
 }
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -200,7 +411,17 @@ impl FindQueryable for FindQuery {
                 backend as "backend: RoomBackend",
                 rtc_sharing_policy as "rtc_sharing_policy: RtcSharingPolicy",
                 infinite,
-                closed_by as "closed_by: AgentId"
+                closed_by as "closed_by: AgentId",
+                version,
+                audio_processing as "audio_processing: Json<AudioProcessing>",
+                archive_messages,
+                composite_recording,
+                record_rtcs,
+                stream_max_duration,
+                metadata,
+                lobby,
+                active_speaker_events,
+                max_agents
             FROM room
             WHERE
                 id = $1
@@ -243,7 +464,17 @@ impl FindQueryable for FindByRtcIdQuery {
                 r.backend as "backend: RoomBackend",
                 r.rtc_sharing_policy as "rtc_sharing_policy: RtcSharingPolicy",
                 r.infinite,
-                r.closed_by as "closed_by: AgentId"
+                r.closed_by as "closed_by: AgentId",
+                r.version,
+                r.audio_processing as "audio_processing: Json<AudioProcessing>",
+                r.archive_messages,
+                r.composite_recording,
+                r.record_rtcs,
+                r.stream_max_duration,
+                r.metadata,
+                r.lobby,
+                r.active_speaker_events,
+                r.max_agents
             FROM room as r
             INNER JOIN rtc
             ON r.id = rtc.room_id
@@ -259,6 +490,64 @@ impl FindQueryable for FindByRtcIdQuery {
 
 ////////////////////////////////////////////////////////////////////////////////
 
+/// Rooms of a classroom, the ones not closed yet first and then the latest ones.
+pub struct FindByClassroomIdQuery {
+    classroom_id: Uuid,
+    limit: i64,
+}
+
+impl FindByClassroomIdQuery {
+    pub fn new(classroom_id: Uuid, limit: i64) -> Self {
+        Self {
+            classroom_id,
+            limit,
+        }
+    }
+
+    pub async fn execute(&self, conn: &mut sqlx::PgConnection) -> sqlx::Result<Vec<Object>> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            SELECT
+                id as "id: Id",
+                backend_id as "backend_id: AgentId",
+                time as "time: TimePg",
+                reserve,
+                tags,
+                classroom_id,
+                host as "host: AgentId",
+                timed_out,
+                audience,
+                created_at,
+                backend as "backend: RoomBackend",
+                rtc_sharing_policy as "rtc_sharing_policy: RtcSharingPolicy",
+                infinite,
+                closed_by as "closed_by: AgentId",
+                version,
+                audio_processing as "audio_processing: Json<AudioProcessing>",
+                archive_messages,
+                composite_recording,
+                record_rtcs,
+                stream_max_duration,
+                metadata,
+                lobby,
+                active_speaker_events,
+                max_agents
+            FROM room
+            WHERE classroom_id = $1
+            ORDER BY COALESCE(UPPER(time) <= NOW(), FALSE), created_at DESC
+            LIMIT $2
+            "#,
+            self.classroom_id,
+            self.limit,
+        )
+        .fetch_all(conn)
+        .await
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
 struct FinishedInProgressRecordingsRow {
     room_id: Id,
     time: TimePg,
@@ -274,11 +563,25 @@ struct FinishedInProgressRecordingsRow {
     timed_out: bool,
     closed_by: Option<AgentId>,
     infinite: bool,
+    room_version: i32,
+    audio_processing: Option<Json<AudioProcessing>>,
+    archive_messages: bool,
+    composite_recording: bool,
+    record_rtcs: bool,
+    stream_max_duration: Option<i32>,
+    metadata: Option<JsonValue>,
+    lobby: bool,
+    active_speaker_events: bool,
+    max_agents: Option<i32>,
     rtc_id: db::rtc::Id,
     started_at: Option<DateTime<Utc>>,
     segments: Option<Vec<SegmentPg>>,
     status: RecordingStatus,
     mjr_dumps_uris: Option<Vec<String>>,
+    verified_at: Option<DateTime<Utc>>,
+    object_size: Option<i64>,
+    checksum: Option<String>,
+    object_checksum: Option<String>,
     handle_id: HandleId,
     session_id: SessionId,
     janus_backend_created_at: DateTime<Utc>,
@@ -307,6 +610,16 @@ impl FinishedInProgressRecordingsRow {
                 timed_out: self.timed_out,
                 closed_by: self.closed_by,
                 infinite: self.infinite,
+                version: self.room_version,
+                audio_processing: self.audio_processing,
+                archive_messages: self.archive_messages,
+                composite_recording: self.composite_recording,
+                record_rtcs: self.record_rtcs,
+                stream_max_duration: self.stream_max_duration,
+                metadata: self.metadata,
+                lobby: self.lobby,
+                active_speaker_events: self.active_speaker_events,
+                max_agents: self.max_agents,
             },
             Recording {
                 rtc_id: self.rtc_id,
@@ -314,6 +627,10 @@ impl FinishedInProgressRecordingsRow {
                 segments: self.segments,
                 status: self.status,
                 mjr_dumps_uris: self.mjr_dumps_uris,
+                verified_at: self.verified_at,
+                object_size: self.object_size,
+                checksum: self.checksum,
+                object_checksum: self.object_checksum,
             },
             JanusBackend {
                 id: self.backend_id,
@@ -338,9 +655,12 @@ impl FinishedInProgressRecordingsRow {
 // room1 | rtc2 | recording1  -> room1 | rtc2 | recording1
 // room2 | rtc3 | recording2     room2 | rtc3 | recording2
 // room3 | rtc4 | null           room3 | null | null
+//
+// Rows come ordered by the room's close time, oldest first.
 pub async fn finished_with_in_progress_recordings(
     conn: &mut sqlx::PgConnection,
     maybe_group: Option<&str>,
+    maybe_room_id: Option<Id>,
 ) -> sqlx::Result<Vec<(Object, Recording, JanusBackend)>> {
     sqlx::query_as!(
         FinishedInProgressRecordingsRow,
@@ -360,11 +680,25 @@ pub async fn finished_with_in_progress_recordings(
             room.timed_out,
             room.closed_by as "closed_by: AgentId",
             room.infinite,
+            room.version as "room_version",
+            room.audio_processing as "audio_processing: Json<AudioProcessing>",
+            room.archive_messages,
+            room.composite_recording,
+            room.record_rtcs,
+            room.stream_max_duration,
+            room.metadata,
+            room.lobby,
+            room.active_speaker_events,
+            room.max_agents,
             recording.rtc_id as "rtc_id: db::rtc::Id",
             recording.started_at,
             recording.segments as "segments: Vec<SegmentPg>",
             recording.status as "status: RecordingStatus",
             recording.mjr_dumps_uris,
+            recording.verified_at,
+            recording.object_size,
+            recording.checksum,
+            recording.object_checksum,
             janus_backend.handle_id as "handle_id: HandleId",
             janus_backend.session_id as "session_id: SessionId",
             janus_backend.created_at as "janus_backend_created_at: _",
@@ -384,22 +718,213 @@ pub async fn finished_with_in_progress_recordings(
             room.rtc_sharing_policy = ANY(ARRAY ['shared'::rtc_sharing_policy, 'owned']) AND
             janus_backend.api_version = $1 AND
             upper(room.time) < now() AND
+            rtc.record AND
             recording.status = 'in_progress' AND
-            ($2::text IS NULL OR (janus_backend.group = $2 OR janus_backend.group IS NULL))
+            ($2::text IS NULL OR (janus_backend.group = $2 OR janus_backend.group IS NULL)) AND
+            ($3::uuid IS NULL OR room.id = $3)
+        ORDER BY upper(room.time), room.id, rtc.id
         "#,
         JANUS_API_VERSION,
-        maybe_group
+        maybe_group,
+        maybe_room_id as Option<Id>,
     )
     .fetch_all(conn)
     .await
     .map(|v| v.into_iter().map(|r| r.split()).collect())
 }
 
+/// Rooms not closed yet which are bound to a backend of the group.
+pub async fn open_by_backend_group(
+    group: &str,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Vec<Object>> {
+    sqlx::query_as!(
+        Object,
+        r#"
+        SELECT
+            r.id as "id: Id",
+            r.backend_id as "backend_id: AgentId",
+            r.time as "time: TimePg",
+            r.reserve,
+            r.tags,
+            r.classroom_id,
+            r.host as "host: AgentId",
+            r.timed_out,
+            r.audience,
+            r.created_at,
+            r.backend as "backend: RoomBackend",
+            r.rtc_sharing_policy as "rtc_sharing_policy: RtcSharingPolicy",
+            r.infinite,
+            r.closed_by as "closed_by: AgentId",
+            r.version,
+            r.audio_processing as "audio_processing: Json<AudioProcessing>",
+            r.archive_messages,
+            r.composite_recording,
+            r.record_rtcs,
+            r.stream_max_duration,
+            r.metadata,
+            r.lobby,
+            r.active_speaker_events,
+            r.max_agents
+        FROM room AS r
+        INNER JOIN janus_backend AS jb
+        ON jb.id = r.backend_id
+        WHERE jb."group" = $1
+        AND   (UPPER(r.time) IS NULL OR UPPER(r.time) > NOW())
+        "#,
+        group
+    )
+    .fetch_all(conn)
+    .await
+}
+
+/// Locks the room's row until the end of the transaction to serialize entries into it.
+pub async fn lock(id: Id, conn: &mut sqlx::PgConnection) -> sqlx::Result<()> {
+    sqlx::query!(
+        r#"
+        SELECT 1 as "one!"
+        FROM room
+        WHERE id = $1
+        FOR NO KEY UPDATE
+        "#,
+        id as Id,
+    )
+    .fetch_optional(conn)
+    .await
+    .map(|_| ())
+}
+
+/// Rooms of `ids` which get `room.active_speaker_changed` events.
+pub async fn with_active_speaker_events(
+    ids: &[Id],
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Vec<Id>> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT id as "id: Id"
+        FROM room
+        WHERE id = ANY($1) AND active_speaker_events
+        "#,
+        ids as &[Id],
+    )
+    .fetch_all(conn)
+    .await?;
+
+    Ok(rows.into_iter().map(|r| r.id).collect())
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug, Default)]
+pub struct ListQuery<'a> {
+    audience: &'a str,
+    classroom_id: Option<Uuid>,
+    closed: Option<bool>,
+    backend_id: Option<&'a AgentId>,
+    offset: Option<i64>,
+    limit: Option<i64>,
+}
+
+impl<'a> ListQuery<'a> {
+    pub fn new(audience: &'a str) -> Self {
+        Self {
+            audience,
+            ..Default::default()
+        }
+    }
+
+    pub fn classroom_id(self, classroom_id: Uuid) -> Self {
+        Self {
+            classroom_id: Some(classroom_id),
+            ..self
+        }
+    }
+
+    /// Rooms with closing time in the past when `true`, the rest of them otherwise.
+    pub fn closed(self, closed: bool) -> Self {
+        Self {
+            closed: Some(closed),
+            ..self
+        }
+    }
+
+    pub fn backend_id(self, backend_id: &'a AgentId) -> Self {
+        Self {
+            backend_id: Some(backend_id),
+            ..self
+        }
+    }
+
+    pub fn offset(self, offset: i64) -> Self {
+        Self {
+            offset: Some(offset),
+            ..self
+        }
+    }
+
+    pub fn limit(self, limit: i64) -> Self {
+        Self {
+            limit: Some(limit),
+            ..self
+        }
+    }
+
+    pub async fn execute(&self, conn: &mut sqlx::PgConnection) -> sqlx::Result<Vec<Object>> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            SELECT
+                id as "id: Id",
+                backend_id as "backend_id: AgentId",
+                time as "time: TimePg",
+                reserve,
+                tags,
+                classroom_id,
+                host as "host: AgentId",
+                timed_out,
+                audience,
+                created_at,
+                backend as "backend: RoomBackend",
+                rtc_sharing_policy as "rtc_sharing_policy: RtcSharingPolicy",
+                infinite,
+                closed_by as "closed_by: AgentId",
+                version,
+                audio_processing as "audio_processing: Json<AudioProcessing>",
+                archive_messages,
+                composite_recording,
+                record_rtcs,
+                stream_max_duration,
+                metadata,
+                lobby,
+                active_speaker_events,
+                max_agents
+            FROM room
+            WHERE
+                audience = $1 AND
+                ($2::uuid IS NULL OR classroom_id = $2) AND
+                ($3::boolean IS NULL OR COALESCE(UPPER(time) <= NOW(), FALSE) = $3) AND
+                ($4::agent_id IS NULL OR backend_id = $4)
+            ORDER BY created_at DESC
+            OFFSET $5
+            LIMIT $6
+            "#,
+            self.audience,
+            self.classroom_id,
+            self.closed,
+            self.backend_id as Option<&AgentId>,
+            self.offset,
+            self.limit
+        )
+        .fetch_all(conn)
+        .await
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 
 #[derive(Debug)]
 pub struct InsertQuery<'a> {
-    time: Time,
+    time: RoomTime,
     audience: &'a str,
     backend: RoomBackend,
     reserve: Option<i32>,
@@ -408,11 +933,20 @@ pub struct InsertQuery<'a> {
     rtc_sharing_policy: RtcSharingPolicy,
     classroom_id: Uuid,
     infinite: bool,
+    audio_processing: Option<AudioProcessing>,
+    archive_messages: bool,
+    composite_recording: bool,
+    record_rtcs: bool,
+    stream_max_duration: Option<i32>,
+    metadata: Option<&'a JsonValue>,
+    lobby: bool,
+    active_speaker_events: bool,
+    max_agents: Option<i32>,
 }
 
 impl<'a> InsertQuery<'a> {
     pub fn new(
-        time: Time,
+        time: RoomTime,
         audience: &'a str,
         rtc_sharing_policy: RtcSharingPolicy,
         classroom_id: Uuid,
@@ -427,6 +961,15 @@ impl<'a> InsertQuery<'a> {
             rtc_sharing_policy,
             classroom_id,
             infinite: false,
+            audio_processing: None,
+            archive_messages: false,
+            composite_recording: false,
+            record_rtcs: true,
+            stream_max_duration: None,
+            metadata: None,
+            lobby: false,
+            active_speaker_events: false,
+            max_agents: None,
         }
     }
 
@@ -444,6 +987,66 @@ impl<'a> InsertQuery<'a> {
         }
     }
 
+    pub fn audio_processing(self, audio_processing: AudioProcessing) -> Self {
+        Self {
+            audio_processing: Some(audio_processing),
+            ..self
+        }
+    }
+
+    pub fn archive_messages(self, archive_messages: bool) -> Self {
+        Self {
+            archive_messages,
+            ..self
+        }
+    }
+
+    pub fn composite_recording(self, composite_recording: bool) -> Self {
+        Self {
+            composite_recording,
+            ..self
+        }
+    }
+
+    pub fn record_rtcs(self, record_rtcs: bool) -> Self {
+        Self {
+            record_rtcs,
+            ..self
+        }
+    }
+
+    pub fn stream_max_duration(self, stream_max_duration: i32) -> Self {
+        Self {
+            stream_max_duration: Some(stream_max_duration),
+            ..self
+        }
+    }
+
+    pub fn metadata(self, metadata: &'a JsonValue) -> Self {
+        Self {
+            metadata: Some(metadata),
+            ..self
+        }
+    }
+
+    pub fn lobby(self, lobby: bool) -> Self {
+        Self { lobby, ..self }
+    }
+
+    pub fn active_speaker_events(self, active_speaker_events: bool) -> Self {
+        Self {
+            active_speaker_events,
+            ..self
+        }
+    }
+
+    pub fn max_agents(self, max_agents: i32) -> Self {
+        Self {
+            max_agents: Some(max_agents),
+            ..self
+        }
+    }
+
     #[cfg(test)]
     pub fn backend_id(self, backend_id: &'a AgentId) -> Self {
         Self {
@@ -463,9 +1066,14 @@ impl<'a> InsertQuery<'a> {
             r#"
             INSERT INTO room (
                 time, audience, backend, reserve, tags,
-                backend_id, rtc_sharing_policy, classroom_id, infinite
+                backend_id, rtc_sharing_policy, classroom_id, infinite, audio_processing,
+                archive_messages, composite_recording, record_rtcs, stream_max_duration, metadata,
+                lobby, active_speaker_events, max_agents
+            )
+            VALUES (
+                $1, $2, $3, $4, COALESCE($5, '{}'::jsonb), $6, $7, $8, $9, $10, $11, $12, $13, $14,
+                $15, $16, $17, $18
             )
-            VALUES ($1, $2, $3, $4, COALESCE($5, '{}'::jsonb), $6, $7, $8, $9)
             RETURNING
                 id as "id: Id",
                 backend_id as "backend_id: AgentId",
@@ -480,7 +1088,17 @@ impl<'a> InsertQuery<'a> {
                 backend as "backend: RoomBackend",
                 rtc_sharing_policy as "rtc_sharing_policy: RtcSharingPolicy",
                 infinite,
-                closed_by as "closed_by: AgentId"
+                closed_by as "closed_by: AgentId",
+                version,
+                audio_processing as "audio_processing: Json<AudioProcessing>",
+                archive_messages,
+                composite_recording,
+                record_rtcs,
+                stream_max_duration,
+                metadata,
+                lobby,
+                active_speaker_events,
+                max_agents
             "#,
             TimePg::from(self.time) as TimePg,
             self.audience,
@@ -491,6 +1109,15 @@ impl<'a> InsertQuery<'a> {
             self.rtc_sharing_policy as RtcSharingPolicy,
             self.classroom_id,
             self.infinite,
+            self.audio_processing.map(Json) as Option<Json<AudioProcessing>>,
+            self.archive_messages,
+            self.composite_recording,
+            self.record_rtcs,
+            self.stream_max_duration,
+            self.metadata,
+            self.lobby,
+            self.active_speaker_events,
+            self.max_agents,
         )
         .fetch_one(conn)
         .await
@@ -502,13 +1129,22 @@ impl<'a> InsertQuery<'a> {
 #[derive(Debug)]
 pub struct UpdateQuery<'a> {
     id: Id,
-    time: Option<Time>,
+    time: Option<RoomTime>,
     reserve: Option<Option<i32>>,
     tags: Option<JsonValue>,
     backend_id: Option<&'a AgentId>,
     classroom_id: Option<Uuid>,
-    host: Option<&'a AgentId>,
     timed_out: Option<bool>,
+    audio_processing: Option<AudioProcessing>,
+    archive_messages: Option<bool>,
+    composite_recording: Option<bool>,
+    record_rtcs: Option<bool>,
+    stream_max_duration: Option<i32>,
+    metadata: Option<JsonValue>,
+    lobby: Option<bool>,
+    active_speaker_events: Option<bool>,
+    max_agents: Option<i32>,
+    if_version: Option<i32>,
 }
 
 impl<'a> UpdateQuery<'a> {
@@ -520,12 +1156,21 @@ impl<'a> UpdateQuery<'a> {
             reserve: Default::default(),
             tags: Default::default(),
             classroom_id: Default::default(),
-            host: Default::default(),
             timed_out: Default::default(),
+            audio_processing: Default::default(),
+            archive_messages: Default::default(),
+            composite_recording: Default::default(),
+            record_rtcs: Default::default(),
+            stream_max_duration: Default::default(),
+            metadata: Default::default(),
+            lobby: Default::default(),
+            active_speaker_events: Default::default(),
+            max_agents: Default::default(),
+            if_version: Default::default(),
         }
     }
 
-    pub fn time(self, time: Option<Time>) -> Self {
+    pub fn time(self, time: Option<RoomTime>) -> Self {
         Self { time, ..self }
     }
 
@@ -555,8 +1200,64 @@ impl<'a> UpdateQuery<'a> {
         }
     }
 
-    pub fn host(self, host: Option<&'a AgentId>) -> Self {
-        Self { host, ..self }
+    pub fn audio_processing(self, audio_processing: Option<AudioProcessing>) -> Self {
+        Self {
+            audio_processing,
+            ..self
+        }
+    }
+
+    pub fn archive_messages(self, archive_messages: Option<bool>) -> Self {
+        Self {
+            archive_messages,
+            ..self
+        }
+    }
+
+    pub fn composite_recording(self, composite_recording: Option<bool>) -> Self {
+        Self {
+            composite_recording,
+            ..self
+        }
+    }
+
+    pub fn record_rtcs(self, record_rtcs: Option<bool>) -> Self {
+        Self {
+            record_rtcs,
+            ..self
+        }
+    }
+
+    pub fn stream_max_duration(self, stream_max_duration: Option<i32>) -> Self {
+        Self {
+            stream_max_duration,
+            ..self
+        }
+    }
+
+    pub fn metadata(self, metadata: Option<JsonValue>) -> Self {
+        Self { metadata, ..self }
+    }
+
+    pub fn lobby(self, lobby: Option<bool>) -> Self {
+        Self { lobby, ..self }
+    }
+
+    pub fn active_speaker_events(self, active_speaker_events: Option<bool>) -> Self {
+        Self {
+            active_speaker_events,
+            ..self
+        }
+    }
+
+    pub fn max_agents(self, max_agents: Option<i32>) -> Self {
+        Self { max_agents, ..self }
+    }
+
+    /// Apply the update only if the room's version equals `if_version`.
+    /// Otherwise the query fails with `RowNotFound`.
+    pub fn if_version(self, if_version: Option<i32>) -> Self {
+        Self { if_version, ..self }
     }
 
     pub async fn execute(&self, conn: &mut sqlx::PgConnection) -> sqlx::Result<Object> {
@@ -570,10 +1271,20 @@ impl<'a> UpdateQuery<'a> {
                 reserve      = COALESCE($4, reserve),
                 tags         = COALESCE($5, tags::jsonb),
                 classroom_id = COALESCE($6, classroom_id),
-                host         = COALESCE($7, host),
-                timed_out    = COALESCE($8, timed_out)
+                timed_out    = COALESCE($7, timed_out),
+                audio_processing = COALESCE($9, audio_processing),
+                archive_messages = COALESCE($10, archive_messages),
+                composite_recording = COALESCE($11, composite_recording),
+                record_rtcs  = COALESCE($12, record_rtcs),
+                stream_max_duration = COALESCE($13, stream_max_duration),
+                metadata     = COALESCE($14, metadata),
+                lobby        = COALESCE($15, lobby),
+                active_speaker_events = COALESCE($16, active_speaker_events),
+                max_agents   = COALESCE($17, max_agents),
+                version      = version + 1
             WHERE
-                id = $1
+                id = $1 AND
+                ($8::integer IS NULL OR version = $8)
             RETURNING
                 id as "id: Id",
                 backend_id as "backend_id: AgentId",
@@ -588,7 +1299,17 @@ impl<'a> UpdateQuery<'a> {
                 backend as "backend: RoomBackend",
                 rtc_sharing_policy as "rtc_sharing_policy: RtcSharingPolicy",
                 infinite,
-                closed_by as "closed_by: AgentId"
+                closed_by as "closed_by: AgentId",
+                version,
+                audio_processing as "audio_processing: Json<AudioProcessing>",
+                archive_messages,
+                composite_recording,
+                record_rtcs,
+                stream_max_duration,
+                metadata,
+                lobby,
+                active_speaker_events,
+                max_agents
             "#,
             self.id as db::room::Id,
             self.backend_id as Option<&AgentId>,
@@ -596,8 +1317,17 @@ impl<'a> UpdateQuery<'a> {
             self.reserve.flatten(),
             self.tags,
             self.classroom_id,
-            self.host as Option<&AgentId>,
-            self.timed_out
+            self.timed_out,
+            self.if_version,
+            self.audio_processing.map(Json) as Option<Json<AudioProcessing>>,
+            self.archive_messages,
+            self.composite_recording,
+            self.record_rtcs,
+            self.stream_max_duration,
+            self.metadata,
+            self.lobby,
+            self.active_speaker_events,
+            self.max_agents,
         )
         .fetch_one(conn)
         .await
@@ -617,7 +1347,8 @@ pub async fn set_closed_by(
         UPDATE room
         SET
             closed_by = $2,
-            time = TSTZRANGE(LOWER(time), NOW())
+            time = TSTZRANGE(LOWER(time), NOW()),
+            version = version + 1
         WHERE
             id = $1
         RETURNING
@@ -634,7 +1365,17 @@ pub async fn set_closed_by(
             backend as "backend: RoomBackend",
             rtc_sharing_policy as "rtc_sharing_policy: RtcSharingPolicy",
             infinite,
-            closed_by as "closed_by: AgentId"
+            closed_by as "closed_by: AgentId",
+            version,
+            audio_processing as "audio_processing: Json<AudioProcessing>",
+            archive_messages,
+            composite_recording,
+            record_rtcs,
+            stream_max_duration,
+            metadata,
+            lobby,
+            active_speaker_events,
+            max_agents
         "#,
         room_id as Id,
         agent as &AgentId,
@@ -645,8 +1386,89 @@ pub async fn set_closed_by(
 
 ////////////////////////////////////////////////////////////////////////////////
 
+/// Deletes the room. Rtcs, agents, recordings and the rest of the room's rows go along with it
+/// by cascade except the orphaned room entry which has to be removed before.
+pub async fn delete(id: Id, conn: &mut sqlx::PgConnection) -> sqlx::Result<u64> {
+    sqlx::query!(
+        r#"
+        DELETE FROM room
+        WHERE
+            id = $1
+        "#,
+        id as Id,
+    )
+    .execute(conn)
+    .await
+    .map(|r| r.rows_affected())
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
 #[cfg(test)]
 mod tests {
+    mod room_time {
+        use chrono::Duration;
+        use serde_json::json;
+
+        use super::super::*;
+
+        #[test]
+        fn validates_ordering() {
+            let now = Utc::now();
+
+            RoomTime::new(Bound::Included(now), Bound::Excluded(now))
+                .expect_err("Empty room time accepted");
+
+            RoomTime::new(
+                Bound::Included(now),
+                Bound::Excluded(now - Duration::hours(1)),
+            )
+            .expect_err("Reversed room time accepted");
+
+            RoomTime::new(Bound::Unbounded, Bound::Unbounded)
+                .expect("Unbounded room time rejected");
+        }
+
+        #[test]
+        fn open_and_close() {
+            let now = Utc::now();
+            let time = RoomTime::new(Bound::Included(now - Duration::hours(1)), Bound::Unbounded)
+                .expect("Invalid room time");
+
+            assert!(time.is_open_at(now));
+            assert!(!time.is_closed_at(now));
+            assert!(!time.is_open_at(now - Duration::hours(2)));
+
+            let closed = time.close_at(now);
+            assert_eq!(closed.start(), time.start());
+            assert_eq!(closed.closed_at(), Some(now));
+            assert!(closed.is_closed_at(now));
+            assert!(!closed.is_open_at(now));
+
+            let no_opening =
+                RoomTime::new(Bound::Unbounded, Bound::Unbounded).expect("Invalid room time");
+            assert!(!no_opening.is_open_at(now));
+        }
+
+        #[test]
+        fn serde() {
+            let time: RoomTime =
+                serde_json::from_value(json!([1600000000, null])).expect("Failed to parse");
+            assert_eq!(time.opened_at().map(|t| t.timestamp()), Some(1600000000));
+            assert_eq!(time.end(), Bound::Unbounded);
+            assert_eq!(
+                serde_json::to_value(time).expect("Failed to serialize"),
+                json!([1600000000, null])
+            );
+
+            let reversed: RoomTime = serde_json::from_value(json!([1600000000, 1500000000]))
+                .expect("Failed to parse reversed room time");
+            reversed
+                .validate()
+                .expect_err("Reversed room time validated");
+        }
+    }
+
     mod finished_with_in_progress_recordings {
         use super::super::*;
         use crate::{
@@ -698,7 +1520,7 @@ mod tests {
             shared_helpers::insert_recording(&mut conn, &rtc1).await;
             shared_helpers::insert_recording(&mut conn, &rtc2).await;
 
-            let rooms = finished_with_in_progress_recordings(&mut conn, None)
+            let rooms = finished_with_in_progress_recordings(&mut conn, None, None)
                 .await
                 .expect("finished_with_in_progress_recordings call failed");
 
@@ -751,7 +1573,7 @@ mod tests {
             shared_helpers::insert_recording(&mut conn, &rtc1).await;
             shared_helpers::insert_recording(&mut conn, &rtc2).await;
 
-            let rooms = finished_with_in_progress_recordings(&mut conn, Some("minigroup"))
+            let rooms = finished_with_in_progress_recordings(&mut conn, Some("minigroup"), None)
                 .await
                 .expect("finished_with_in_progress_recordings call failed");
 
@@ -0,0 +1,49 @@
+use chrono::{serde::ts_seconds, DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+////////////////////////////////////////////////////////////////////////////////
+
+// Assignments themselves are recorded by a trigger on every change of `room.backend_id`.
+
+/// Assignments of rooms to the backends of a group within a week.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WeeklyObject {
+    #[serde(with = "ts_seconds")]
+    pub week: DateTime<Utc>,
+    pub group: Option<String>,
+    /// Rooms assigned to a backend for the first time.
+    pub assignments: i64,
+    /// Rooms moved over from another backend, e.g. on decommission.
+    pub reassignments: i64,
+    pub rooms: i64,
+    pub backends: i64,
+}
+
+/// Lists assignments made within `[since, until)` grouped by week and backend group.
+pub async fn weekly(
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Vec<WeeklyObject>> {
+    sqlx::query_as!(
+        WeeklyObject,
+        r#"
+        SELECT
+            DATE_TRUNC('week', assigned_at) AS "week!",
+            backend_group AS "group",
+            COUNT(*) FILTER (WHERE previous_backend_id IS NULL) AS "assignments!",
+            COUNT(*) FILTER (WHERE previous_backend_id IS NOT NULL) AS "reassignments!",
+            COUNT(DISTINCT room_id) AS "rooms!",
+            COUNT(DISTINCT backend_id) AS "backends!"
+        FROM room_backend_assignment
+        WHERE ($1::TIMESTAMPTZ IS NULL OR assigned_at >= $1::TIMESTAMPTZ)
+        AND   ($2::TIMESTAMPTZ IS NULL OR assigned_at < $2::TIMESTAMPTZ)
+        GROUP BY 1, 2
+        ORDER BY 1, 2 NULLS FIRST
+        "#,
+        since,
+        until,
+    )
+    .fetch_all(conn)
+    .await
+}
@@ -0,0 +1,204 @@
+use chrono::{
+    serde::{ts_seconds, ts_seconds_option},
+    DateTime, Utc,
+};
+use serde::Serialize;
+use svc_agent::AgentId;
+use uuid::Uuid;
+
+use crate::db;
+
+////////////////////////////////////////////////////////////////////////////////
+
+pub type Id = db::id::Id;
+
+/// A job closing every room matching the filters. Rooms match when they're open, not infinite
+/// and either have no closing time or are due to close before `closed_before`.
+#[derive(Debug, Serialize)]
+pub struct Object {
+    pub id: Id,
+    pub audience: Option<String>,
+    pub classroom_ids: Option<Vec<Uuid>>,
+    #[serde(with = "ts_seconds_option")]
+    pub closed_before: Option<DateTime<Utc>>,
+    /// Rooms matched when the job was created.
+    pub total: i64,
+    pub closed: i64,
+    pub failed: i64,
+    pub created_by: AgentId,
+    #[serde(with = "ts_seconds")]
+    pub created_at: DateTime<Utc>,
+    #[serde(with = "ts_seconds_option")]
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+impl Object {
+    pub fn id(&self) -> Id {
+        self.id
+    }
+
+    pub fn created_by(&self) -> &AgentId {
+        &self.created_by
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug)]
+pub struct InsertQuery<'a> {
+    audience: Option<&'a str>,
+    classroom_ids: Option<&'a [Uuid]>,
+    closed_before: Option<DateTime<Utc>>,
+    created_by: &'a AgentId,
+}
+
+impl<'a> InsertQuery<'a> {
+    pub fn new(created_by: &'a AgentId) -> Self {
+        Self {
+            audience: None,
+            classroom_ids: None,
+            closed_before: None,
+            created_by,
+        }
+    }
+
+    pub fn audience(self, audience: &'a str) -> Self {
+        Self {
+            audience: Some(audience),
+            ..self
+        }
+    }
+
+    pub fn classroom_ids(self, classroom_ids: &'a [Uuid]) -> Self {
+        Self {
+            classroom_ids: Some(classroom_ids),
+            ..self
+        }
+    }
+
+    pub fn closed_before(self, closed_before: DateTime<Utc>) -> Self {
+        Self {
+            closed_before: Some(closed_before),
+            ..self
+        }
+    }
+
+    /// Creates the job counting the rooms it's going to close.
+    pub async fn execute(self, conn: &mut sqlx::PgConnection) -> sqlx::Result<Object> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            INSERT INTO room_bulk_close (audience, classroom_ids, closed_before, created_by, total)
+            SELECT $1, $2, $3, $4, COUNT(r.id)
+            FROM room AS r
+            WHERE
+                ($1::text IS NULL OR r.audience = $1) AND
+                ($2::uuid[] IS NULL OR r.classroom_id = ANY($2)) AND
+                NOT r.infinite AND
+                (LOWER_INF(r.time) OR LOWER(r.time) <= NOW()) AND
+                (UPPER_INF(r.time) OR UPPER(r.time) > NOW()) AND
+                ($3::timestamptz IS NULL OR UPPER_INF(r.time) OR UPPER(r.time) < $3)
+            RETURNING
+                id as "id: Id",
+                audience,
+                classroom_ids,
+                closed_before,
+                total,
+                closed,
+                failed,
+                created_by as "created_by: AgentId",
+                created_at,
+                finished_at
+            "#,
+            self.audience,
+            self.classroom_ids,
+            self.closed_before,
+            self.created_by as &AgentId,
+        )
+        .fetch_one(conn)
+        .await
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+pub async fn find(id: Id, conn: &mut sqlx::PgConnection) -> sqlx::Result<Option<Object>> {
+    sqlx::query_as!(
+        Object,
+        r#"
+        SELECT
+            id as "id: Id",
+            audience,
+            classroom_ids,
+            closed_before,
+            total,
+            closed,
+            failed,
+            created_by as "created_by: AgentId",
+            created_at,
+            finished_at
+        FROM room_bulk_close
+        WHERE id = $1
+        "#,
+        id as Id,
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+/// Ids of the rooms still matching the job's filters ordered by id starting after `after`.
+pub async fn list_rooms(
+    id: Id,
+    after: Option<db::room::Id>,
+    limit: i64,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Vec<db::room::Id>> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT r.id as "id!: db::room::Id"
+        FROM room AS r, room_bulk_close AS j
+        WHERE
+            j.id = $1 AND
+            ($2::uuid IS NULL OR r.id > $2) AND
+            (j.audience IS NULL OR r.audience = j.audience) AND
+            (j.classroom_ids IS NULL OR r.classroom_id = ANY(j.classroom_ids)) AND
+            NOT r.infinite AND
+            (LOWER_INF(r.time) OR LOWER(r.time) <= NOW()) AND
+            (UPPER_INF(r.time) OR UPPER(r.time) > NOW()) AND
+            (j.closed_before IS NULL OR UPPER_INF(r.time) OR UPPER(r.time) < j.closed_before)
+        ORDER BY r.id
+        LIMIT $3
+        "#,
+        id as Id,
+        after as Option<db::room::Id>,
+        limit,
+    )
+    .fetch_all(conn)
+    .await
+}
+
+pub async fn add_progress(
+    id: Id,
+    closed: i64,
+    failed: i64,
+    finished: bool,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        r#"
+        UPDATE room_bulk_close
+        SET
+            closed = closed + $2,
+            failed = failed + $3,
+            finished_at = CASE WHEN $4 THEN NOW() ELSE finished_at END
+        WHERE id = $1
+        "#,
+        id as Id,
+        closed,
+        failed,
+        finished,
+    )
+    .execute(conn)
+    .await
+    .map(|_| ())
+}
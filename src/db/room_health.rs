@@ -0,0 +1,242 @@
+use chrono::{serde::ts_seconds, DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::db;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Health of an open room as assessed by the aggregator. The higher the score the worse.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Object {
+    pub room_id: db::room::Id,
+    pub classroom_id: Uuid,
+    pub score: f32,
+    pub reasons: Vec<String>,
+    pub error_rate: f32,
+    pub slow_link_rate: f32,
+    pub hangup_rate: f32,
+    #[serde(with = "ts_seconds")]
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Room counters since the previous assessment along with its results.
+#[derive(Debug, Clone)]
+pub struct Sample {
+    pub room_id: db::room::Id,
+    pub errors: i64,
+    pub slow_link_count: i32,
+    pub hangup_count: i32,
+    pub backend_online: bool,
+    pub prev_error_rate: Option<f32>,
+    pub prev_slow_link_rate: Option<f32>,
+    pub prev_hangup_rate: Option<f32>,
+    pub prev_slow_link_count: Option<i32>,
+    pub prev_hangup_count: Option<i32>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Samples open rooms not assessed since `stale_before`. Backend errors of rooms
+/// that haven't been assessed yet are counted since `since`.
+pub async fn samples(
+    since: DateTime<Utc>,
+    stale_before: DateTime<Utc>,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Vec<Sample>> {
+    sqlx::query_as!(
+        Sample,
+        r#"
+        SELECT
+            r.id as "room_id: db::room::Id",
+            (
+                SELECT COUNT(*)
+                FROM backend_error AS be
+                WHERE be.room_id = r.id
+                AND   be.created_at > COALESCE(rh.updated_at, $1)
+            ) as "errors!: i64",
+            COALESCE(c.slow_link_count, 0) as "slow_link_count!: i32",
+            COALESCE(c.hangup_count, 0) as "hangup_count!: i32",
+            (r.backend_id IS NULL OR jb.id IS NOT NULL) as "backend_online!: bool",
+            rh.error_rate as "prev_error_rate?: f32",
+            rh.slow_link_rate as "prev_slow_link_rate?: f32",
+            rh.hangup_rate as "prev_hangup_rate?: f32",
+            rh.slow_link_count as "prev_slow_link_count?: i32",
+            rh.hangup_count as "prev_hangup_count?: i32"
+        FROM room AS r
+        LEFT JOIN room_counter AS c
+        ON c.room_id = r.id
+        LEFT JOIN room_health AS rh
+        ON rh.room_id = r.id
+        LEFT JOIN janus_backend AS jb
+        ON jb.id = r.backend_id
+        WHERE r.time @> NOW()
+        AND   (rh.updated_at IS NULL OR rh.updated_at < $2)
+        "#,
+        since,
+        stale_before,
+    )
+    .fetch_all(conn)
+    .await
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+pub struct UpsertQuery<'a> {
+    room_id: db::room::Id,
+    score: f32,
+    reasons: &'a [String],
+    error_rate: f32,
+    slow_link_rate: f32,
+    hangup_rate: f32,
+    slow_link_count: i32,
+    hangup_count: i32,
+}
+
+impl<'a> UpsertQuery<'a> {
+    pub fn new(room_id: db::room::Id, score: f32, reasons: &'a [String]) -> Self {
+        Self {
+            room_id,
+            score,
+            reasons,
+            error_rate: 0.,
+            slow_link_rate: 0.,
+            hangup_rate: 0.,
+            slow_link_count: 0,
+            hangup_count: 0,
+        }
+    }
+
+    pub fn rates(self, error_rate: f32, slow_link_rate: f32, hangup_rate: f32) -> Self {
+        Self {
+            error_rate,
+            slow_link_rate,
+            hangup_rate,
+            ..self
+        }
+    }
+
+    /// Counters the next assessment computes the rates from.
+    pub fn counters(self, slow_link_count: i32, hangup_count: i32) -> Self {
+        Self {
+            slow_link_count,
+            hangup_count,
+            ..self
+        }
+    }
+
+    pub async fn execute(&self, conn: &mut sqlx::PgConnection) -> sqlx::Result<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO room_health
+                (room_id, score, reasons, error_rate, slow_link_rate, hangup_rate, slow_link_count, hangup_count)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            ON CONFLICT (room_id) DO UPDATE
+            SET
+                score = EXCLUDED.score,
+                reasons = EXCLUDED.reasons,
+                error_rate = EXCLUDED.error_rate,
+                slow_link_rate = EXCLUDED.slow_link_rate,
+                hangup_rate = EXCLUDED.hangup_rate,
+                slow_link_count = EXCLUDED.slow_link_count,
+                hangup_count = EXCLUDED.hangup_count,
+                updated_at = NOW()
+            "#,
+            self.room_id as db::room::Id,
+            self.score,
+            self.reasons,
+            self.error_rate,
+            self.slow_link_rate,
+            self.hangup_rate,
+            self.slow_link_count,
+            self.hangup_count,
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Removes health of the rooms that are no longer open.
+pub async fn delete_closed(conn: &mut sqlx::PgConnection) -> sqlx::Result<u64> {
+    sqlx::query!(
+        r#"
+        DELETE FROM room_health AS rh
+        USING room AS r
+        WHERE r.id = rh.room_id
+        AND   NOT r.time @> NOW()
+        "#
+    )
+    .execute(conn)
+    .await
+    .map(|r| r.rows_affected())
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Default)]
+pub struct ListQuery {
+    min_score: Option<f32>,
+    offset: Option<i64>,
+    limit: Option<i64>,
+}
+
+impl ListQuery {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn min_score(self, min_score: f32) -> Self {
+        Self {
+            min_score: Some(min_score),
+            ..self
+        }
+    }
+
+    pub fn offset(self, offset: i64) -> Self {
+        Self {
+            offset: Some(offset),
+            ..self
+        }
+    }
+
+    pub fn limit(self, limit: i64) -> Self {
+        Self {
+            limit: Some(limit),
+            ..self
+        }
+    }
+
+    pub async fn execute(&self, conn: &mut sqlx::PgConnection) -> sqlx::Result<Vec<Object>> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            SELECT
+                rh.room_id as "room_id: db::room::Id",
+                r.classroom_id,
+                rh.score,
+                rh.reasons,
+                rh.error_rate,
+                rh.slow_link_rate,
+                rh.hangup_rate,
+                rh.updated_at
+            FROM room_health AS rh
+            INNER JOIN room AS r
+            ON r.id = rh.room_id
+            WHERE r.time @> NOW()
+            AND   ($1::REAL IS NULL OR rh.score >= $1::REAL)
+            ORDER BY rh.score DESC, rh.room_id
+            OFFSET $2
+            LIMIT $3
+            "#,
+            self.min_score,
+            self.offset.unwrap_or(0),
+            self.limit,
+        )
+        .fetch_all(conn)
+        .await
+    }
+}
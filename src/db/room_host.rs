@@ -0,0 +1,223 @@
+use svc_agent::AgentId;
+
+use crate::db::{self, rtc::Id as RtcId};
+
+////////////////////////////////////////////////////////////////////////////////
+
+pub async fn list(
+    room_id: db::room::Id,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Vec<AgentId>> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT agent_id as "agent_id: AgentId"
+        FROM room_host
+        WHERE room_id = $1
+        ORDER BY created_at
+        "#,
+        room_id as db::room::Id,
+    )
+    .fetch_all(conn)
+    .await?;
+
+    Ok(rows.into_iter().map(|r| r.agent_id).collect())
+}
+
+pub async fn is_host(
+    room_id: db::room::Id,
+    agent_id: &AgentId,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<bool> {
+    sqlx::query!(
+        r#"
+        SELECT 1 as "one!"
+        FROM room_host
+        WHERE room_id = $1 AND agent_id = $2
+        "#,
+        room_id as db::room::Id,
+        agent_id as &AgentId,
+    )
+    .fetch_optional(conn)
+    .await
+    .map(|r| r.is_some())
+}
+
+/// Whether any host of the room but `except` is present in it.
+pub async fn any_present(
+    room_id: db::room::Id,
+    except: &AgentId,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<bool> {
+    sqlx::query!(
+        r#"
+        SELECT 1 as "one!"
+        FROM room_host AS rh
+        INNER JOIN agent AS a
+        ON a.room_id = rh.room_id AND a.agent_id = rh.agent_id
+        WHERE
+            rh.room_id = $1 AND
+            rh.agent_id <> $2 AND
+            a.status <> 'pending'
+        LIMIT 1
+        "#,
+        room_id as db::room::Id,
+        except as &AgentId,
+    )
+    .fetch_optional(conn)
+    .await
+    .map(|r| r.is_some())
+}
+
+/// RTCs the hosts of the room have created.
+pub async fn list_rtc_ids(
+    room_id: db::room::Id,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Vec<RtcId>> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT rtc.id as "id: RtcId"
+        FROM rtc
+        INNER JOIN room_host AS rh
+        ON rh.room_id = rtc.room_id AND rh.agent_id = rtc.created_by
+        WHERE rtc.room_id = $1
+        "#,
+        room_id as db::room::Id,
+    )
+    .fetch_all(conn)
+    .await?;
+
+    Ok(rows.into_iter().map(|r| r.id).collect())
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+pub async fn insert(
+    room_id: db::room::Id,
+    agent_ids: &[&AgentId],
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO room_host (room_id, agent_id)
+        -- array of agent_id unnests to account_id and label so they're merged back
+        SELECT $1, (account_id, label)::agent_id
+        FROM UNNEST($2::agent_id[]) AS t(account_id, label)
+        ON CONFLICT DO NOTHING
+        "#,
+        room_id as db::room::Id,
+        agent_ids as &[&AgentId],
+    )
+    .execute(conn)
+    .await
+    .map(|_| ())
+}
+
+pub async fn delete(
+    room_id: db::room::Id,
+    agent_ids: &[&AgentId],
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        r#"
+        DELETE FROM room_host
+        WHERE room_id = $1 AND agent_id = ANY($2)
+        "#,
+        room_id as db::room::Id,
+        agent_ids as &[&AgentId],
+    )
+    .execute(conn)
+    .await
+    .map(|_| ())
+}
+
+/// Leaves the only host in the room.
+pub async fn replace(
+    room_id: db::room::Id,
+    agent_id: &AgentId,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        r#"
+        DELETE FROM room_host
+        WHERE room_id = $1 AND agent_id <> $2
+        "#,
+        room_id as db::room::Id,
+        agent_id as &AgentId,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    insert(room_id, &[agent_id], conn).await
+}
+
+/// Points `room.host` to the first of the hosts left.
+pub async fn sync_room(room_id: db::room::Id, conn: &mut sqlx::PgConnection) -> sqlx::Result<()> {
+    sqlx::query!(
+        r#"
+        UPDATE room
+        SET host = (
+            SELECT agent_id
+            FROM room_host
+            WHERE room_id = $1
+            ORDER BY created_at
+            LIMIT 1
+        )
+        WHERE id = $1
+        "#,
+        room_id as db::room::Id,
+    )
+    .execute(conn)
+    .await
+    .map(|_| ())
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        db::room::FindQueryable,
+        test_helpers::{db::TestDb, prelude::*},
+    };
+
+    use super::*;
+
+    #[sqlx::test]
+    async fn hosts_lifecycle(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let mut conn = db.get_conn().await;
+        let room = shared_helpers::insert_room(&mut conn).await;
+
+        let alpha = TestAgent::new("web", "alpha", USR_AUDIENCE);
+        let beta = TestAgent::new("web", "beta", USR_AUDIENCE);
+
+        insert(room.id(), &[alpha.agent_id(), beta.agent_id()], &mut conn)
+            .await
+            .expect("Failed to insert hosts");
+
+        assert!(is_host(room.id(), beta.agent_id(), &mut conn)
+            .await
+            .expect("Failed to check host"));
+
+        delete(room.id(), &[alpha.agent_id()], &mut conn)
+            .await
+            .expect("Failed to delete hosts");
+
+        sync_room(room.id(), &mut conn)
+            .await
+            .expect("Failed to sync room host");
+
+        let hosts = list(room.id(), &mut conn)
+            .await
+            .expect("Failed to list hosts");
+        assert_eq!(hosts, vec![beta.agent_id().to_owned()]);
+
+        let room = db::room::FindQuery::new(room.id())
+            .execute(&mut conn)
+            .await
+            .expect("Failed to find room")
+            .expect("Room not found");
+
+        assert_eq!(room.host(), Some(beta.agent_id()));
+    }
+}
@@ -0,0 +1,83 @@
+use chrono::{DateTime, Utc};
+use svc_agent::AgentId;
+
+use crate::db;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// An open room on a backend along with the slow link rate of the whole backend.
+#[derive(Debug, Clone)]
+pub struct BackendLoad {
+    pub room_id: db::room::Id,
+    pub audience: String,
+    pub backend_id: AgentId,
+    /// Sum of the assessed slow link rates per minute of the backend's open rooms.
+    pub slow_link_rate: f32,
+    /// When the room was asked to reduce quality, if it still is.
+    pub hint_sent_at: Option<DateTime<Utc>>,
+}
+
+pub async fn backend_loads(conn: &mut sqlx::PgConnection) -> sqlx::Result<Vec<BackendLoad>> {
+    sqlx::query_as!(
+        BackendLoad,
+        r#"
+        SELECT
+            r.id as "room_id: db::room::Id",
+            r.audience,
+            r.backend_id as "backend_id!: AgentId",
+            (SUM(COALESCE(rh.slow_link_rate, 0)) OVER (PARTITION BY r.backend_id))::real
+                as "slow_link_rate!: f32",
+            qh.sent_at as "hint_sent_at?"
+        FROM room AS r
+        LEFT JOIN room_health AS rh
+        ON rh.room_id = r.id
+        LEFT JOIN room_quality_hint AS qh
+        ON qh.room_id = r.id
+        WHERE r.time @> NOW()
+        AND   r.backend_id IS NOT NULL
+        "#,
+    )
+    .fetch_all(conn)
+    .await
+}
+
+/// Records the hint unless it was sent after `resend_before`. Returns whether it should be sent,
+/// so only one of the instances does that.
+pub async fn claim(
+    room_id: db::room::Id,
+    resend_before: DateTime<Utc>,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<bool> {
+    let claimed = sqlx::query!(
+        r#"
+        INSERT INTO room_quality_hint (room_id)
+        VALUES ($1)
+        ON CONFLICT (room_id) DO UPDATE
+        SET sent_at = NOW()
+        WHERE room_quality_hint.sent_at < $2
+        RETURNING room_id
+        "#,
+        room_id as db::room::Id,
+        resend_before,
+    )
+    .fetch_optional(conn)
+    .await?;
+
+    Ok(claimed.is_some())
+}
+
+/// Drops the hint of the recovered room. Returns whether the lifting should be sent.
+pub async fn release(room_id: db::room::Id, conn: &mut sqlx::PgConnection) -> sqlx::Result<bool> {
+    let released = sqlx::query!(
+        r#"
+        DELETE FROM room_quality_hint
+        WHERE room_id = $1
+        RETURNING room_id
+        "#,
+        room_id as db::room::Id,
+    )
+    .fetch_optional(conn)
+    .await?;
+
+    Ok(released.is_some())
+}
@@ -0,0 +1,94 @@
+use chrono::{serde::ts_seconds, DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use svc_agent::AgentId;
+use uuid::Uuid;
+
+use crate::db;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// A change of the room reserve along with the agent who has made it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Object {
+    pub id: Uuid,
+    pub room_id: db::room::Id,
+    pub old_reserve: Option<i32>,
+    pub new_reserve: Option<i32>,
+    pub changed_by: AgentId,
+    #[serde(with = "ts_seconds")]
+    pub created_at: DateTime<Utc>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+pub struct InsertQuery<'a> {
+    room_id: db::room::Id,
+    old_reserve: Option<i32>,
+    new_reserve: Option<i32>,
+    changed_by: &'a AgentId,
+}
+
+impl<'a> InsertQuery<'a> {
+    pub fn new(
+        room_id: db::room::Id,
+        old_reserve: Option<i32>,
+        new_reserve: Option<i32>,
+        changed_by: &'a AgentId,
+    ) -> Self {
+        Self {
+            room_id,
+            old_reserve,
+            new_reserve,
+            changed_by,
+        }
+    }
+
+    pub async fn execute(&self, conn: &mut sqlx::PgConnection) -> sqlx::Result<Object> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            INSERT INTO room_reserve_audit (room_id, old_reserve, new_reserve, changed_by)
+            VALUES ($1, $2, $3, $4)
+            RETURNING
+                id,
+                room_id as "room_id: db::room::Id",
+                old_reserve,
+                new_reserve,
+                changed_by as "changed_by: AgentId",
+                created_at
+            "#,
+            self.room_id as db::room::Id,
+            self.old_reserve,
+            self.new_reserve,
+            self.changed_by as &AgentId,
+        )
+        .fetch_one(conn)
+        .await
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+pub async fn list(
+    room_id: db::room::Id,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Vec<Object>> {
+    sqlx::query_as!(
+        Object,
+        r#"
+        SELECT
+            id,
+            room_id as "room_id: db::room::Id",
+            old_reserve,
+            new_reserve,
+            changed_by as "changed_by: AgentId",
+            created_at
+        FROM room_reserve_audit
+        WHERE room_id = $1
+        ORDER BY created_at
+        "#,
+        room_id as db::room::Id,
+    )
+    .fetch_all(conn)
+    .await
+}
@@ -0,0 +1,332 @@
+use chrono::{serde::ts_seconds, DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use svc_agent::AgentId;
+use uuid::Uuid;
+
+use crate::db::{self, rtc::SharingPolicy as RtcSharingPolicy};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Recurring rooms of a classroom. A room of each occurrence gets created some time before it
+/// starts and lasts `duration` seconds.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Object {
+    pub id: Uuid,
+    pub audience: String,
+    pub classroom_id: Uuid,
+    pub recurrence: String,
+    #[serde(with = "ts_seconds")]
+    pub starts_at: DateTime<Utc>,
+    pub duration: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    pub rtc_sharing_policy: RtcSharingPolicy,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reserve: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<JsonValue>,
+    #[serde(
+        default,
+        with = "chrono::serde::ts_seconds_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub last_occurrence_at: Option<DateTime<Utc>>,
+    #[serde(
+        default,
+        with = "chrono::serde::ts_seconds_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub next_occurrence_at: Option<DateTime<Utc>>,
+    pub created_by: AgentId,
+    #[serde(with = "ts_seconds")]
+    pub created_at: DateTime<Utc>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+pub struct InsertQuery<'a> {
+    audience: &'a str,
+    classroom_id: Uuid,
+    recurrence: &'a str,
+    starts_at: DateTime<Utc>,
+    duration: i32,
+    next_occurrence_at: Option<DateTime<Utc>>,
+    template: Option<&'a str>,
+    rtc_sharing_policy: RtcSharingPolicy,
+    reserve: Option<i32>,
+    tags: Option<&'a JsonValue>,
+    created_by: &'a AgentId,
+}
+
+impl<'a> InsertQuery<'a> {
+    pub fn new(
+        audience: &'a str,
+        classroom_id: Uuid,
+        recurrence: &'a str,
+        starts_at: DateTime<Utc>,
+        duration: i32,
+        created_by: &'a AgentId,
+    ) -> Self {
+        Self {
+            audience,
+            classroom_id,
+            recurrence,
+            starts_at,
+            duration,
+            next_occurrence_at: None,
+            template: None,
+            rtc_sharing_policy: RtcSharingPolicy::None,
+            reserve: None,
+            tags: None,
+            created_by,
+        }
+    }
+
+    pub fn next_occurrence_at(self, next_occurrence_at: DateTime<Utc>) -> Self {
+        Self {
+            next_occurrence_at: Some(next_occurrence_at),
+            ..self
+        }
+    }
+
+    pub fn template(self, template: &'a str) -> Self {
+        Self {
+            template: Some(template),
+            ..self
+        }
+    }
+
+    pub fn rtc_sharing_policy(self, rtc_sharing_policy: RtcSharingPolicy) -> Self {
+        Self {
+            rtc_sharing_policy,
+            ..self
+        }
+    }
+
+    pub fn reserve(self, reserve: i32) -> Self {
+        Self {
+            reserve: Some(reserve),
+            ..self
+        }
+    }
+
+    pub fn tags(self, tags: &'a JsonValue) -> Self {
+        Self {
+            tags: Some(tags),
+            ..self
+        }
+    }
+
+    pub async fn execute(&self, conn: &mut sqlx::PgConnection) -> sqlx::Result<Object> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            INSERT INTO room_schedule (
+                audience, classroom_id, recurrence, starts_at, duration, next_occurrence_at,
+                template, rtc_sharing_policy, reserve, tags, created_by
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            RETURNING
+                id,
+                audience,
+                classroom_id,
+                recurrence,
+                starts_at,
+                duration,
+                template,
+                rtc_sharing_policy as "rtc_sharing_policy: RtcSharingPolicy",
+                reserve,
+                tags,
+                last_occurrence_at,
+                next_occurrence_at,
+                created_by as "created_by: AgentId",
+                created_at
+            "#,
+            self.audience,
+            self.classroom_id,
+            self.recurrence,
+            self.starts_at,
+            self.duration,
+            self.next_occurrence_at,
+            self.template,
+            self.rtc_sharing_policy as RtcSharingPolicy,
+            self.reserve,
+            self.tags,
+            self.created_by as &AgentId,
+        )
+        .fetch_one(conn)
+        .await
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+pub async fn find(id: Uuid, conn: &mut sqlx::PgConnection) -> sqlx::Result<Option<Object>> {
+    sqlx::query_as!(
+        Object,
+        r#"
+        SELECT
+            id,
+            audience,
+            classroom_id,
+            recurrence,
+            starts_at,
+            duration,
+            template,
+            rtc_sharing_policy as "rtc_sharing_policy: RtcSharingPolicy",
+            reserve,
+            tags,
+            last_occurrence_at,
+            next_occurrence_at,
+            created_by as "created_by: AgentId",
+            created_at
+        FROM room_schedule
+        WHERE id = $1
+        "#,
+        id,
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+pub async fn delete(id: Uuid, conn: &mut sqlx::PgConnection) -> sqlx::Result<Option<Object>> {
+    sqlx::query_as!(
+        Object,
+        r#"
+        DELETE FROM room_schedule
+        WHERE id = $1
+        RETURNING
+            id,
+            audience,
+            classroom_id,
+            recurrence,
+            starts_at,
+            duration,
+            template,
+            rtc_sharing_policy as "rtc_sharing_policy: RtcSharingPolicy",
+            reserve,
+            tags,
+            last_occurrence_at,
+            next_occurrence_at,
+            created_by as "created_by: AgentId",
+            created_at
+        "#,
+        id,
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+/// Schedules whose next occurrence starts before `until`, the earliest first.
+pub async fn list_due(
+    until: DateTime<Utc>,
+    limit: i64,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Vec<Object>> {
+    sqlx::query_as!(
+        Object,
+        r#"
+        SELECT
+            id,
+            audience,
+            classroom_id,
+            recurrence,
+            starts_at,
+            duration,
+            template,
+            rtc_sharing_policy as "rtc_sharing_policy: RtcSharingPolicy",
+            reserve,
+            tags,
+            last_occurrence_at,
+            next_occurrence_at,
+            created_by as "created_by: AgentId",
+            created_at
+        FROM room_schedule
+        WHERE next_occurrence_at <= $1
+        ORDER BY next_occurrence_at
+        LIMIT $2
+        "#,
+        until,
+        limit,
+    )
+    .fetch_all(conn)
+    .await
+}
+
+/// Moves the schedule from `occurrence` to the `next` one. Returns `false` when the occurrence
+/// has already been taken by another instance.
+pub async fn advance(
+    id: Uuid,
+    occurrence: DateTime<Utc>,
+    next: Option<DateTime<Utc>>,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<bool> {
+    sqlx::query!(
+        r#"
+        UPDATE room_schedule
+        SET
+            last_occurrence_at = $2,
+            next_occurrence_at = $3
+        WHERE id = $1 AND next_occurrence_at = $2
+        "#,
+        id,
+        occurrence,
+        next,
+    )
+    .execute(conn)
+    .await
+    .map(|r| r.rows_affected() == 1)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+pub async fn insert_occurrence(
+    room_id: db::room::Id,
+    schedule_id: Uuid,
+    opens_at: DateTime<Utc>,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO room_schedule_occurrence (room_id, schedule_id, opens_at)
+        VALUES ($1, $2, $3)
+        "#,
+        room_id as db::room::Id,
+        schedule_id,
+        opens_at,
+    )
+    .execute(conn)
+    .await
+    .map(|_| ())
+}
+
+/// Marks scheduled rooms which have opened by `now` and haven't been announced yet.
+/// Concurrent callers get disjoint sets of rooms.
+pub async fn claim_opened(
+    now: DateTime<Utc>,
+    limit: i64,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Vec<db::room::Id>> {
+    let rows = sqlx::query!(
+        r#"
+        UPDATE room_schedule_occurrence
+        SET opened_at = $1
+        WHERE room_id IN (
+            SELECT room_id
+            FROM room_schedule_occurrence
+            WHERE opened_at IS NULL AND opens_at <= $1
+            ORDER BY opens_at
+            LIMIT $2
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING room_id as "room_id: db::room::Id"
+        "#,
+        now,
+        limit,
+    )
+    .fetch_all(conn)
+    .await?;
+
+    Ok(rows.into_iter().map(|r| r.room_id).collect())
+}
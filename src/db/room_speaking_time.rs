@@ -0,0 +1,30 @@
+use svc_agent::AgentId;
+
+use crate::db;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Adds up speaking time of the agent which is folded into the room's summary on close.
+pub async fn add(
+    room_id: db::room::Id,
+    agent_id: &AgentId,
+    speaking_ms: i64,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO room_speaking_time (room_id, agent_id, speaking_ms)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (room_id, agent_id) DO UPDATE
+        SET
+            speaking_ms = room_speaking_time.speaking_ms + EXCLUDED.speaking_ms,
+            updated_at = NOW()
+        "#,
+        room_id as db::room::Id,
+        agent_id as &AgentId,
+        speaking_ms,
+    )
+    .execute(conn)
+    .await
+    .map(|_| ())
+}
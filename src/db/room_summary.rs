@@ -0,0 +1,357 @@
+use chrono::{serde::ts_seconds, DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+
+use crate::db;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Room metrics snapshot taken at close time.
+#[derive(Debug, Clone, Serialize)]
+pub struct Object {
+    pub room_id: db::room::Id,
+    pub duration_ms: i64,
+    pub peak_concurrency: i32,
+    pub streams_count: i64,
+    pub slow_link_count: i32,
+    pub broadcast_count: i32,
+    /// Logical sessions of agents with reconnects stitched together.
+    pub sessions_count: i64,
+    pub reconnects_count: i64,
+    /// Final results of the room's polls.
+    pub polls: JsonValue,
+    /// Agents with their speaking time, the most talkative first.
+    pub speaking_time: JsonValue,
+    #[serde(with = "ts_seconds")]
+    pub created_at: DateTime<Utc>,
+}
+
+impl Object {
+    pub fn room_id(&self) -> db::room::Id {
+        self.room_id
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+pub struct InsertQuery {
+    room_id: db::room::Id,
+}
+
+impl InsertQuery {
+    pub fn new(room_id: db::room::Id) -> Self {
+        Self { room_id }
+    }
+
+    /// Computes the snapshot from the room's counters and streams. Repeated calls replace
+    /// the snapshot since some close paths may process the same room more than once.
+    pub async fn execute(&self, conn: &mut sqlx::PgConnection) -> sqlx::Result<Object> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            INSERT INTO room_summary
+                (room_id, duration_ms, peak_concurrency, streams_count, slow_link_count, broadcast_count, sessions_count, reconnects_count, polls, speaking_time)
+            SELECT
+                r.id,
+                GREATEST(
+                    EXTRACT(EPOCH FROM LEAST(COALESCE(UPPER(r.time), NOW()), NOW()) - LOWER(r.time)) * 1000,
+                    0
+                )::BIGINT,
+                COALESCE(c.peak_concurrency, 0),
+                (
+                    SELECT COUNT(*)
+                    FROM janus_rtc_stream AS jrs
+                    INNER JOIN rtc
+                    ON rtc.id = jrs.rtc_id
+                    WHERE rtc.room_id = r.id
+                    AND   jrs.time IS NOT NULL
+                ),
+                COALESCE(c.slow_link_count, 0),
+                COALESCE(c.broadcast_count, 0),
+                COALESCE(s.sessions_count, 0),
+                COALESCE(s.reconnects_count, 0),
+                (
+                    SELECT COALESCE(
+                        JSONB_AGG(
+                            JSONB_BUILD_OBJECT(
+                                'id', p.id,
+                                'question', p.question,
+                                'options', p.options,
+                                'votes', (
+                                    SELECT JSONB_AGG(
+                                        (
+                                            SELECT COUNT(*)
+                                            FROM poll_vote AS pv
+                                            WHERE pv.poll_id = p.id
+                                            AND   pv.option_idx = o.idx
+                                        )
+                                        ORDER BY o.idx
+                                    )
+                                    FROM GENERATE_SERIES(0, CARDINALITY(p.options) - 1) AS o(idx)
+                                )
+                            )
+                            ORDER BY p.created_at
+                        ),
+                        '[]'::JSONB
+                    )
+                    FROM poll AS p
+                    WHERE p.room_id = r.id
+                ),
+                (
+                    SELECT COALESCE(
+                        JSONB_AGG(
+                            JSONB_BUILD_OBJECT(
+                                'agent_id', FORMAT(
+                                    '%s.%s.%s',
+                                    (st.agent_id).label,
+                                    ((st.agent_id).account_id).label,
+                                    ((st.agent_id).account_id).audience
+                                ),
+                                'speaking_ms', st.speaking_ms
+                            )
+                            ORDER BY st.speaking_ms DESC
+                        ),
+                        '[]'::JSONB
+                    )
+                    FROM room_speaking_time AS st
+                    WHERE st.room_id = r.id
+                )
+            FROM room AS r
+            LEFT JOIN room_counter AS c
+            ON c.room_id = r.id
+            LEFT JOIN (
+                SELECT
+                    room_id,
+                    COUNT(*) AS sessions_count,
+                    SUM(reconnects)::BIGINT AS reconnects_count
+                FROM agent_session
+                WHERE room_id = $1
+                GROUP BY room_id
+            ) AS s
+            ON s.room_id = r.id
+            WHERE r.id = $1
+            ON CONFLICT (room_id) DO UPDATE
+            SET
+                duration_ms = EXCLUDED.duration_ms,
+                peak_concurrency = EXCLUDED.peak_concurrency,
+                streams_count = EXCLUDED.streams_count,
+                slow_link_count = EXCLUDED.slow_link_count,
+                broadcast_count = EXCLUDED.broadcast_count,
+                sessions_count = EXCLUDED.sessions_count,
+                reconnects_count = EXCLUDED.reconnects_count,
+                polls = EXCLUDED.polls,
+                speaking_time = EXCLUDED.speaking_time,
+                created_at = NOW()
+            RETURNING
+                room_id as "room_id: db::room::Id",
+                duration_ms,
+                peak_concurrency,
+                streams_count,
+                slow_link_count,
+                broadcast_count,
+                sessions_count,
+                reconnects_count,
+                polls as "polls!: JsonValue",
+                speaking_time as "speaking_time!: JsonValue",
+                created_at
+            "#,
+            self.room_id as db::room::Id,
+        )
+        .fetch_one(conn)
+        .await
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Raises the peak concurrency counter to the current number of sessions of ready agents in
+/// the room. A reconnected client counts once while its stale agent is still around.
+pub async fn observe_concurrency(
+    room_id: db::room::Id,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO room_counter (room_id, peak_concurrency)
+        SELECT $1, COUNT(DISTINCT session_key)
+        FROM agent
+        WHERE room_id = $1
+        AND   status = 'ready'
+        ON CONFLICT (room_id) DO UPDATE
+        SET
+            peak_concurrency = GREATEST(room_counter.peak_concurrency, EXCLUDED.peak_concurrency)
+        "#,
+        room_id as db::room::Id,
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn increment_slow_link_count(
+    room_id: db::room::Id,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO room_counter (room_id, slow_link_count)
+        VALUES ($1, 1)
+        ON CONFLICT (room_id) DO UPDATE
+        SET
+            slow_link_count = room_counter.slow_link_count + 1
+        "#,
+        room_id as db::room::Id,
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Counts hangups and detaches of the room's handles which mostly happen on reconnects.
+pub async fn increment_hangup_count(
+    room_id: db::room::Id,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO room_counter (room_id, hangup_count)
+        VALUES ($1, 1)
+        ON CONFLICT (room_id) DO UPDATE
+        SET
+            hangup_count = room_counter.hangup_count + 1
+        "#,
+        room_id as db::room::Id,
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Counts connections by whether their media is relayed through TURN.
+pub async fn increment_connection_count(
+    room_id: db::room::Id,
+    relayed: bool,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO room_counter (room_id, relayed_connection_count, direct_connection_count)
+        VALUES ($1, $2::BOOLEAN::INT, (NOT $2)::INT)
+        ON CONFLICT (room_id) DO UPDATE
+        SET
+            relayed_connection_count = room_counter.relayed_connection_count + EXCLUDED.relayed_connection_count,
+            direct_connection_count = room_counter.direct_connection_count + EXCLUDED.direct_connection_count
+        "#,
+        room_id as db::room::Id,
+        relayed,
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn increment_broadcast_count(
+    room_id: db::room::Id,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO room_counter (room_id, broadcast_count)
+        VALUES ($1, 1)
+        ON CONFLICT (room_id) DO UPDATE
+        SET
+            broadcast_count = room_counter.broadcast_count + 1
+        "#,
+        room_id as db::room::Id,
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Bound;
+
+    use chrono::{Duration, SubsecRound};
+
+    use super::*;
+    use crate::test_helpers::{db::TestDb, prelude::*};
+
+    #[sqlx::test]
+    async fn summarize_room(pool: sqlx::PgPool) {
+        let mut conn = TestDb::new(pool).get_conn().await;
+
+        let now = Utc::now().trunc_subsecs(0);
+        let room = factory::Room::new()
+            .audience(USR_AUDIENCE)
+            .time((
+                Bound::Included(now - Duration::hours(1)),
+                Bound::Excluded(now - Duration::minutes(30)),
+            ))
+            .insert(&mut conn)
+            .await;
+
+        for label in ["alpha", "bravo"] {
+            let agent = TestAgent::new("web", label, USR_AUDIENCE);
+            shared_helpers::insert_agent(&mut conn, agent.agent_id(), room.id()).await;
+            observe_concurrency(room.id(), &mut conn)
+                .await
+                .expect("Failed to observe concurrency");
+        }
+
+        db::agent::DeleteQuery::new()
+            .room_id(room.id())
+            .execute(&mut conn)
+            .await
+            .expect("Failed to delete agents");
+
+        observe_concurrency(room.id(), &mut conn)
+            .await
+            .expect("Failed to observe concurrency");
+
+        increment_slow_link_count(room.id(), &mut conn)
+            .await
+            .expect("Failed to increment slow link count");
+
+        for _ in 0..3 {
+            increment_broadcast_count(room.id(), &mut conn)
+                .await
+                .expect("Failed to increment broadcast count");
+        }
+
+        let host = TestAgent::new("web", "host", USR_AUDIENCE);
+        let options = vec!["yes".to_owned(), "no".to_owned()];
+
+        let poll = db::poll::InsertQuery::new(room.id(), host.agent_id(), "Ready?", &options)
+            .execute(&mut conn)
+            .await
+            .expect("Failed to insert poll");
+
+        db::poll::VoteInsertQuery::new(poll.id(), host.agent_id(), 1)
+            .execute(&mut conn)
+            .await
+            .expect("Failed to insert vote");
+
+        let summary = InsertQuery::new(room.id())
+            .execute(&mut conn)
+            .await
+            .expect("Failed to insert room summary");
+
+        assert_eq!(summary.room_id(), room.id());
+        assert_eq!(summary.peak_concurrency, 2);
+        assert_eq!(summary.slow_link_count, 1);
+        assert_eq!(summary.broadcast_count, 3);
+        assert_eq!(summary.streams_count, 0);
+        assert_eq!(summary.duration_ms, 30 * 60 * 1000);
+        assert_eq!(summary.polls[0]["question"], "Ready?");
+        assert_eq!(summary.polls[0]["votes"], serde_json::json!([0, 1]));
+    }
+}
@@ -0,0 +1,239 @@
+use chrono::{serde::ts_seconds, DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use sqlx::types::Json;
+use svc_agent::AgentId;
+
+use crate::db::{self, rtc::SharingPolicy as RtcSharingPolicy};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Writer config every RTC of a room created from a template starts with.
+/// Missing flags fall back to the usual defaults.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct WriterConfigDefaults {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub send_video: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub send_audio: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub video_remb: Option<i64>,
+}
+
+/// Reader config the agents of a room created from a template start with for each RTC.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ReaderConfigDefaults {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub receive_video: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub receive_audio: Option<bool>,
+}
+
+/// A named set of room properties of an audience to create recurring rooms from.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Object {
+    pub audience: String,
+    pub name: String,
+    pub rtc_sharing_policy: RtcSharingPolicy,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reserve: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<JsonValue>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub writer_config: Option<Json<WriterConfigDefaults>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reader_config: Option<Json<ReaderConfigDefaults>>,
+    pub updated_by: AgentId,
+    #[serde(with = "ts_seconds")]
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Object {
+    pub fn writer_config(&self) -> Option<WriterConfigDefaults> {
+        self.writer_config.as_ref().map(|c| c.0)
+    }
+
+    pub fn reader_config(&self) -> Option<ReaderConfigDefaults> {
+        self.reader_config.as_ref().map(|c| c.0)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+pub async fn find(
+    audience: &str,
+    name: &str,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Option<Object>> {
+    sqlx::query_as!(
+        Object,
+        r#"
+        SELECT
+            audience,
+            name,
+            rtc_sharing_policy as "rtc_sharing_policy: RtcSharingPolicy",
+            reserve,
+            metadata,
+            writer_config as "writer_config: Json<WriterConfigDefaults>",
+            reader_config as "reader_config: Json<ReaderConfigDefaults>",
+            updated_by as "updated_by: AgentId",
+            updated_at
+        FROM room_template
+        WHERE audience = $1 AND name = $2
+        "#,
+        audience,
+        name,
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+pub struct UpsertQuery<'a> {
+    audience: &'a str,
+    name: &'a str,
+    rtc_sharing_policy: RtcSharingPolicy,
+    reserve: Option<i32>,
+    metadata: Option<&'a JsonValue>,
+    writer_config: Option<WriterConfigDefaults>,
+    reader_config: Option<ReaderConfigDefaults>,
+    updated_by: &'a AgentId,
+}
+
+impl<'a> UpsertQuery<'a> {
+    pub fn new(
+        audience: &'a str,
+        name: &'a str,
+        rtc_sharing_policy: RtcSharingPolicy,
+        updated_by: &'a AgentId,
+    ) -> Self {
+        Self {
+            audience,
+            name,
+            rtc_sharing_policy,
+            reserve: None,
+            metadata: None,
+            writer_config: None,
+            reader_config: None,
+            updated_by,
+        }
+    }
+
+    pub fn reserve(self, reserve: i32) -> Self {
+        Self {
+            reserve: Some(reserve),
+            ..self
+        }
+    }
+
+    pub fn metadata(self, metadata: &'a JsonValue) -> Self {
+        Self {
+            metadata: Some(metadata),
+            ..self
+        }
+    }
+
+    pub fn writer_config(self, writer_config: WriterConfigDefaults) -> Self {
+        Self {
+            writer_config: Some(writer_config),
+            ..self
+        }
+    }
+
+    pub fn reader_config(self, reader_config: ReaderConfigDefaults) -> Self {
+        Self {
+            reader_config: Some(reader_config),
+            ..self
+        }
+    }
+
+    /// Replaces the whole template so properties missing in the update get dropped.
+    pub async fn execute(&self, conn: &mut sqlx::PgConnection) -> sqlx::Result<Object> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            INSERT INTO room_template (
+                audience, name, rtc_sharing_policy, reserve, metadata,
+                writer_config, reader_config, updated_by
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            ON CONFLICT (audience, name) DO UPDATE
+            SET
+                rtc_sharing_policy = EXCLUDED.rtc_sharing_policy,
+                reserve = EXCLUDED.reserve,
+                metadata = EXCLUDED.metadata,
+                writer_config = EXCLUDED.writer_config,
+                reader_config = EXCLUDED.reader_config,
+                updated_by = EXCLUDED.updated_by,
+                updated_at = now()
+            RETURNING
+                audience,
+                name,
+                rtc_sharing_policy as "rtc_sharing_policy: RtcSharingPolicy",
+                reserve,
+                metadata,
+                writer_config as "writer_config: Json<WriterConfigDefaults>",
+                reader_config as "reader_config: Json<ReaderConfigDefaults>",
+                updated_by as "updated_by: AgentId",
+                updated_at
+            "#,
+            self.audience,
+            self.name,
+            self.rtc_sharing_policy as RtcSharingPolicy,
+            self.reserve,
+            self.metadata,
+            self.writer_config.map(Json) as Option<Json<WriterConfigDefaults>>,
+            self.reader_config.map(Json) as Option<Json<ReaderConfigDefaults>>,
+            self.updated_by as &AgentId,
+        )
+        .fetch_one(conn)
+        .await
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Copies the config defaults of the template to the room so that later template updates
+/// don't affect the rooms created before.
+pub async fn copy_defaults(
+    room_id: db::room::Id,
+    template: &Object,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<()> {
+    if template.writer_config.is_none() && template.reader_config.is_none() {
+        return Ok(());
+    }
+
+    sqlx::query!(
+        r#"
+        INSERT INTO room_config_default (room_id, writer_config, reader_config)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (room_id) DO NOTHING
+        "#,
+        room_id as db::room::Id,
+        template.writer_config().map(Json) as Option<Json<WriterConfigDefaults>>,
+        template.reader_config().map(Json) as Option<Json<ReaderConfigDefaults>>,
+    )
+    .execute(conn)
+    .await
+    .map(|_| ())
+}
+
+pub async fn find_writer_defaults(
+    room_id: db::room::Id,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Option<WriterConfigDefaults>> {
+    let row = sqlx::query!(
+        r#"
+        SELECT writer_config as "writer_config: Json<WriterConfigDefaults>"
+        FROM room_config_default
+        WHERE room_id = $1
+        "#,
+        room_id as db::room::Id,
+    )
+    .fetch_optional(conn)
+    .await?;
+
+    Ok(row.and_then(|r| r.writer_config).map(|c| c.0))
+}
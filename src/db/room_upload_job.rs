@@ -0,0 +1,147 @@
+use chrono::{DateTime, Utc};
+
+use crate::{backend::janus::JANUS_API_VERSION, db};
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Debug)]
+pub struct Object {
+    pub room_id: db::room::Id,
+    /// Times the job has been put off because the room still had streams running.
+    pub attempts: i32,
+}
+
+/// Schedules the room's upload, a job scheduled already is moved to the new time.
+pub async fn enqueue(
+    room_id: db::room::Id,
+    due_at: DateTime<Utc>,
+    attempts: i32,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO room_upload_job (room_id, due_at, attempts)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (room_id) DO UPDATE
+        SET
+            due_at = EXCLUDED.due_at,
+            attempts = EXCLUDED.attempts
+        "#,
+        room_id as db::room::Id,
+        due_at,
+        attempts,
+    )
+    .execute(conn)
+    .await
+    .map(|_| ())
+}
+
+/// Removes and returns the jobs due for the rooms on the backends of the group. Instances
+/// taking jobs at once get different ones.
+pub async fn take_due(
+    maybe_group: Option<&str>,
+    limit: i64,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Vec<Object>> {
+    sqlx::query_as!(
+        Object,
+        r#"
+        DELETE FROM room_upload_job
+        WHERE room_id IN (
+            SELECT room_upload_job.room_id
+            FROM room_upload_job
+            INNER JOIN room
+            ON room.id = room_upload_job.room_id
+            INNER JOIN janus_backend
+            ON janus_backend.id = room.backend_id
+            WHERE
+                room_upload_job.due_at <= NOW() AND
+                janus_backend.api_version = $1 AND
+                ($2::text IS NULL OR (janus_backend.group = $2 OR janus_backend.group IS NULL))
+            ORDER BY room_upload_job.due_at
+            LIMIT $3
+            FOR UPDATE OF room_upload_job SKIP LOCKED
+        )
+        RETURNING
+            room_id as "room_id: db::room::Id",
+            attempts
+        "#,
+        JANUS_API_VERSION,
+        maybe_group,
+        limit,
+    )
+    .fetch_all(conn)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+    use crate::{
+        backend::janus::client::{HandleId, SessionId},
+        test_helpers::{db::TestDb, prelude::*},
+    };
+
+    #[sqlx::test]
+    async fn enqueue_and_take_due(pool: sqlx::PgPool) {
+        let mut conn = TestDb::new(pool).get_conn().await;
+
+        let backend = shared_helpers::insert_janus_backend(
+            &mut conn,
+            "test",
+            SessionId::random(),
+            HandleId::random(),
+        )
+        .await;
+
+        let due_room =
+            shared_helpers::insert_closed_room_with_backend_id(&mut conn, backend.id()).await;
+        let later_room =
+            shared_helpers::insert_closed_room_with_backend_id(&mut conn, backend.id()).await;
+
+        enqueue(
+            due_room.id(),
+            Utc::now() + Duration::minutes(1),
+            0,
+            &mut conn,
+        )
+        .await
+        .expect("Failed to enqueue room upload");
+
+        // Enqueueing again moves the job.
+        enqueue(
+            due_room.id(),
+            Utc::now() - Duration::seconds(1),
+            2,
+            &mut conn,
+        )
+        .await
+        .expect("Failed to enqueue room upload");
+
+        enqueue(
+            later_room.id(),
+            Utc::now() + Duration::minutes(1),
+            0,
+            &mut conn,
+        )
+        .await
+        .expect("Failed to enqueue room upload");
+
+        let jobs = take_due(None, 10, &mut conn)
+            .await
+            .expect("Failed to take due room uploads");
+
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].room_id, due_room.id());
+        assert_eq!(jobs[0].attempts, 2);
+
+        // Taken jobs are gone.
+        let jobs = take_due(None, 10, &mut conn)
+            .await
+            .expect("Failed to take due room uploads");
+
+        assert!(jobs.is_empty());
+    }
+}
@@ -6,6 +6,7 @@ use std::fmt;
 
 use chrono::{serde::ts_seconds, DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
 use svc_agent::AgentId;
 
 use crate::db;
@@ -36,6 +37,16 @@ impl fmt::Display for SharingPolicy {
     }
 }
 
+/// What is published to the rtc. RTCs created before the kind appeared are cameras.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq, Hash, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "rtc_kind", rename_all = "lowercase")]
+pub enum Kind {
+    #[default]
+    Camera,
+    Screen,
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -45,6 +56,11 @@ pub struct Object {
     #[serde(with = "ts_seconds")]
     pub created_at: DateTime<Utc>,
     pub created_by: AgentId,
+    pub record: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<JsonValue>,
+    #[serde(default)]
+    pub kind: Kind,
 }
 
 impl Object {
@@ -59,6 +75,20 @@ impl Object {
     pub fn created_by(&self) -> &AgentId {
         &self.created_by
     }
+
+    /// Whether the backend records the streams published to the rtc.
+    pub fn record(&self) -> bool {
+        self.record
+    }
+
+    /// Client-defined labels of the rtc, e.g. which camera it's published from.
+    pub fn tags(&self) -> Option<&JsonValue> {
+        self.tags.as_ref()
+    }
+
+    pub fn kind(&self) -> Kind {
+        self.kind
+    }
 }
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -80,7 +110,10 @@ impl FindQuery {
                 id as "id: Id",
                 room_id as "room_id: Id",
                 created_at,
-                created_by as "created_by: AgentId"
+                created_by as "created_by: AgentId",
+                record,
+                tags,
+                kind as "kind: Kind"
             FROM rtc
             WHERE
                 id = $1
@@ -98,6 +131,7 @@ impl FindQuery {
 pub struct ListQuery<'a> {
     room_id: Option<db::room::Id>,
     created_by: Option<&'a [&'a AgentId]>,
+    kind: Option<Kind>,
     offset: Option<i64>,
     limit: Option<i64>,
 }
@@ -121,6 +155,13 @@ impl<'a> ListQuery<'a> {
         }
     }
 
+    pub fn kind(self, kind: Kind) -> Self {
+        Self {
+            kind: Some(kind),
+            ..self
+        }
+    }
+
     pub fn offset(self, offset: i64) -> Self {
         Self {
             offset: Some(offset),
@@ -145,11 +186,15 @@ impl<'a> ListQuery<'a> {
                 id as "id: Id",
                 room_id as "room_id: Id",
                 created_at,
-                created_by as "created_by: AgentId"
+                created_by as "created_by: AgentId",
+                record,
+                tags,
+                kind as "kind: Kind"
             FROM rtc
             WHERE
                 ($1::uuid IS NULL OR room_id = $1) AND
-                (array_length($2::agent_id[], 1) IS NULL OR created_by = ANY($2))
+                (array_length($2::agent_id[], 1) IS NULL OR created_by = ANY($2)) AND
+                ($5::rtc_kind IS NULL OR kind = $5)
             ORDER BY created_at
             OFFSET $3
             LIMIT $4
@@ -157,7 +202,8 @@ impl<'a> ListQuery<'a> {
             self.room_id as Option<Id>,
             created_by as &[&AgentId],
             self.offset,
-            self.limit
+            self.limit,
+            self.kind as Option<Kind>,
         )
         .fetch_all(conn)
         .await
@@ -175,10 +221,17 @@ struct ListWithRecordingRow {
     room_id: db::room::Id,
     created_at: DateTime<Utc>,
     created_by: AgentId,
+    record: bool,
+    tags: Option<JsonValue>,
+    kind: Kind,
     started_at: Option<DateTime<Utc>>,
     segments: Option<Vec<db::recording::SegmentPg>>,
     status: Option<db::recording::Status>,
     mjr_dumps_uris: Option<Vec<String>>,
+    verified_at: Option<DateTime<Utc>>,
+    object_size: Option<i64>,
+    checksum: Option<String>,
+    object_checksum: Option<String>,
 }
 
 impl ListWithRecordingRow {
@@ -189,6 +242,9 @@ impl ListWithRecordingRow {
                 room_id: self.room_id,
                 created_at: self.created_at,
                 created_by: self.created_by,
+                record: self.record,
+                tags: self.tags,
+                kind: self.kind,
             },
             match self.status {
                 Some(status) => Some(Recording {
@@ -197,6 +253,10 @@ impl ListWithRecordingRow {
                     segments: self.segments,
                     status,
                     mjr_dumps_uris: self.mjr_dumps_uris,
+                    verified_at: self.verified_at,
+                    object_size: self.object_size,
+                    checksum: self.checksum,
+                    object_checksum: self.object_checksum,
                 }),
                 None => None,
             },
@@ -221,10 +281,17 @@ impl ListWithRecordingQuery {
                 rtc.room_id as "room_id: db::room::Id",
                 rtc.created_at,
                 rtc.created_by as "created_by: AgentId",
+                rtc.record,
+                rtc.tags,
+                rtc.kind as "kind: Kind",
                 recording.started_at,
                 recording.segments as "segments: Vec<db::recording::SegmentPg>",
                 recording.status as "status?: db::recording::Status",
-                recording.mjr_dumps_uris
+                recording.mjr_dumps_uris,
+                recording.verified_at,
+                recording.object_size,
+                recording.checksum,
+                recording.object_checksum
             FROM rtc
             LEFT JOIN recording
             ON rtc.id = recording.rtc_id
@@ -245,6 +312,9 @@ impl ListWithRecordingQuery {
 pub struct InsertQuery<'a> {
     room_id: db::room::Id,
     created_by: &'a AgentId,
+    record: bool,
+    tags: Option<&'a JsonValue>,
+    kind: Kind,
 }
 
 impl<'a> InsertQuery<'a> {
@@ -252,25 +322,103 @@ impl<'a> InsertQuery<'a> {
         Self {
             room_id,
             created_by,
+            record: true,
+            tags: None,
+            kind: Kind::Camera,
         }
     }
 
+    pub fn record(self, record: bool) -> Self {
+        Self { record, ..self }
+    }
+
+    pub fn tags(self, tags: &'a JsonValue) -> Self {
+        Self {
+            tags: Some(tags),
+            ..self
+        }
+    }
+
+    pub fn kind(self, kind: Kind) -> Self {
+        Self { kind, ..self }
+    }
+
     pub async fn execute(&self, conn: &mut sqlx::PgConnection) -> sqlx::Result<Object> {
         sqlx::query_as!(
             Object,
             r#"
-            INSERT INTO rtc (room_id, created_by)
-            VALUES ($1, $2)
+            INSERT INTO rtc (room_id, created_by, record, tags, kind)
+            VALUES ($1, $2, $3, $4, $5)
             RETURNING
                 id as "id: Id",
                 room_id as "room_id: Id",
                 created_at,
-                created_by as "created_by: AgentId"
+                created_by as "created_by: AgentId",
+                record,
+                tags,
+                kind as "kind: Kind"
             "#,
             self.room_id as Id,
-            self.created_by as &AgentId
+            self.created_by as &AgentId,
+            self.record,
+            self.tags,
+            self.kind as Kind,
         )
         .fetch_one(conn)
         .await
     }
 }
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Changes the given fields leaving the rest as is.
+#[derive(Debug)]
+pub struct UpdateQuery<'a> {
+    id: Id,
+    record: Option<bool>,
+    tags: Option<&'a JsonValue>,
+}
+
+impl<'a> UpdateQuery<'a> {
+    pub fn new(id: Id) -> Self {
+        Self {
+            id,
+            record: None,
+            tags: None,
+        }
+    }
+
+    pub fn record(self, record: Option<bool>) -> Self {
+        Self { record, ..self }
+    }
+
+    pub fn tags(self, tags: Option<&'a JsonValue>) -> Self {
+        Self { tags, ..self }
+    }
+
+    pub async fn execute(&self, conn: &mut sqlx::PgConnection) -> sqlx::Result<Option<Object>> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            UPDATE rtc
+            SET
+                record = COALESCE($2, record),
+                tags = COALESCE($3, tags)
+            WHERE id = $1
+            RETURNING
+                id as "id: Id",
+                room_id as "room_id: Id",
+                created_at,
+                created_by as "created_by: AgentId",
+                record,
+                tags,
+                kind as "kind: Kind"
+            "#,
+            self.id as Id,
+            self.record,
+            self.tags,
+        )
+        .fetch_optional(conn)
+        .await
+    }
+}
@@ -1,4 +1,5 @@
 use chrono::{DateTime, Utc};
+use serde_json::Value as JsonValue;
 use svc_agent::AgentId;
 
 use crate::{db, db::rtc::Object as Rtc};
@@ -6,7 +7,6 @@ use crate::{db, db::rtc::Object as Rtc};
 ////////////////////////////////////////////////////////////////////////////////
 
 pub struct Object {
-    #[allow(unused)]
     rtc_id: db::rtc::Id,
     reader_id: AgentId,
     receive_video: bool,
@@ -14,7 +14,6 @@ pub struct Object {
 }
 
 impl Object {
-    #[cfg(test)]
     pub fn rtc_id(&self) -> db::rtc::Id {
         self.rtc_id
     }
@@ -47,6 +46,9 @@ struct ListWithRtcRow {
     receive_audio: bool,
     room_id: db::room::Id,
     created_by: AgentId,
+    record: bool,
+    tags: Option<JsonValue>,
+    kind: db::rtc::Kind,
     created_at: DateTime<Utc>,
 }
 
@@ -64,6 +66,9 @@ impl ListWithRtcRow {
                 room_id: self.room_id,
                 created_at: self.created_at,
                 created_by: self.created_by,
+                record: self.record,
+                tags: self.tags,
+                kind: self.kind,
             },
         )
     }
@@ -88,7 +93,10 @@ impl<'a> ListWithRtcQuery<'a> {
                 rrc.receive_audio,
                 rtc.room_id as "room_id: db::room::Id",
                 rtc.created_by as "created_by: AgentId",
-                rtc.created_at
+                rtc.created_at,
+                rtc.record,
+                rtc.tags,
+                rtc.kind as "kind: db::rtc::Kind"
             FROM rtc_reader_config as rrc
             INNER JOIN rtc
             ON rrc.rtc_id = rtc.id
@@ -135,6 +143,7 @@ pub struct UpsertQuery<'a> {
     reader_id: &'a AgentId,
     receive_video: Option<bool>,
     receive_audio: Option<bool>,
+    pending_since: Option<DateTime<Utc>>,
 }
 
 impl<'a> UpsertQuery<'a> {
@@ -144,6 +153,7 @@ impl<'a> UpsertQuery<'a> {
             reader_id,
             receive_video: None,
             receive_audio: None,
+            pending_since: None,
         }
     }
 
@@ -161,6 +171,15 @@ impl<'a> UpsertQuery<'a> {
         }
     }
 
+    /// Writes the values as an intent which is yet to be pushed to Janus and confirmed with
+    /// [`confirm`]. Without it the values are considered applied right away.
+    pub fn pending_since(self, pending_since: DateTime<Utc>) -> Self {
+        Self {
+            pending_since: Some(pending_since),
+            ..self
+        }
+    }
+
     pub async fn execute(&self, conn: &mut sqlx::PgConnection) -> sqlx::Result<Object> {
         let receive_video = self.receive_video.unwrap_or(true);
         let receive_audio = self.receive_audio.unwrap_or(true);
@@ -168,12 +187,31 @@ impl<'a> UpsertQuery<'a> {
         sqlx::query_as!(
             Object,
             r#"
-            INSERT INTO rtc_reader_config
-            VALUES ($1, $2, $3, $4)
+            INSERT INTO rtc_reader_config (
+                rtc_id, reader_id, receive_video, receive_audio, pending_since,
+                applied_receive_video, applied_receive_audio
+            )
+            VALUES (
+                $1, $2, $3, $4, $7,
+                CASE WHEN $7::timestamptz IS NULL THEN $3 ELSE true END,
+                CASE WHEN $7::timestamptz IS NULL THEN $4 ELSE true END
+            )
             ON CONFLICT (rtc_id, reader_id) DO UPDATE
             SET
                 receive_video = COALESCE($5, rtc_reader_config.receive_video),
-                receive_audio = COALESCE($6, rtc_reader_config.receive_audio)
+                receive_audio = COALESCE($6, rtc_reader_config.receive_audio),
+                pending_since = $7,
+                push_attempts = 0,
+                applied_receive_video = CASE
+                    WHEN $7::timestamptz IS NULL
+                    THEN COALESCE($5, rtc_reader_config.receive_video)
+                    ELSE rtc_reader_config.applied_receive_video
+                END,
+                applied_receive_audio = CASE
+                    WHEN $7::timestamptz IS NULL
+                    THEN COALESCE($6, rtc_reader_config.receive_audio)
+                    ELSE rtc_reader_config.applied_receive_audio
+                END
             RETURNING
                 rtc_id as "rtc_id: db::rtc::Id",
                 reader_id as "reader_id: AgentId",
@@ -185,7 +223,8 @@ impl<'a> UpsertQuery<'a> {
             receive_video,
             receive_audio,
             self.receive_video,
-            self.receive_audio
+            self.receive_audio,
+            self.pending_since,
         )
         .fetch_one(conn)
         .await
@@ -202,16 +241,25 @@ pub async fn batch_insert(
     sqlx::query_as!(
         Object,
         r#"
-        INSERT INTO rtc_reader_config
+        INSERT INTO rtc_reader_config (
+            rtc_id, reader_id, receive_video, receive_audio,
+            applied_receive_video, applied_receive_audio
+        )
         -- array of agent_id unnests to 2 arrays account_id[] and label[]
         -- so we merge them after unnest back to agent_id type
-        SELECT rtc_id, (reader_account_id, reader_label)::agent_id, receive_video, receive_audio
+        SELECT
+            rtc_id, (reader_account_id, reader_label)::agent_id, receive_video, receive_audio,
+            receive_video, receive_audio
         FROM UNNEST($1::uuid[], $2::agent_id[], $3::bool[], $4::bool[])
             AS t(rtc_id, reader_account_id, reader_label, receive_video, receive_audio)
         ON CONFLICT (rtc_id, reader_id) DO UPDATE
         SET
             receive_video = EXCLUDED.receive_video,
-            receive_audio = EXCLUDED.receive_audio
+            receive_audio = EXCLUDED.receive_audio,
+            applied_receive_video = EXCLUDED.receive_video,
+            applied_receive_audio = EXCLUDED.receive_audio,
+            pending_since = NULL,
+            push_attempts = 0
         RETURNING
             rtc_id as "rtc_id: db::rtc::Id",
             reader_id as "reader_id: AgentId",
@@ -226,3 +274,292 @@ pub async fn batch_insert(
     .fetch_all(conn)
     .await
 }
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Translates the reader's subscribe set into reader configs of all the other RTCs of the room.
+/// Only the configs whose video flag changes are written, as intents pending since
+/// `pending_since`, and returned.
+pub async fn apply_subscribe_set(
+    room_id: db::room::Id,
+    reader_id: &AgentId,
+    pending_since: DateTime<Utc>,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Vec<Object>> {
+    sqlx::query_as!(
+        Object,
+        r#"
+        INSERT INTO rtc_reader_config (
+            rtc_id, reader_id, receive_video, receive_audio, pending_since,
+            applied_receive_video, applied_receive_audio
+        )
+        SELECT rtc.id, s.reader_id, rtc.id = ANY(s.rtc_ids), true, $3, true, true
+        FROM rtc_subscribe_set AS s
+        INNER JOIN rtc
+        ON rtc.room_id = s.room_id
+        LEFT JOIN rtc_reader_config AS rrc
+        ON rrc.rtc_id = rtc.id AND rrc.reader_id = s.reader_id
+        WHERE
+            s.room_id = $1 AND
+            s.reader_id = $2 AND
+            rtc.created_by <> s.reader_id AND
+            COALESCE(rrc.receive_video, true) <> (rtc.id = ANY(s.rtc_ids))
+        ON CONFLICT (rtc_id, reader_id) DO UPDATE
+        SET
+            receive_video = EXCLUDED.receive_video,
+            pending_since = EXCLUDED.pending_since,
+            push_attempts = 0
+        RETURNING
+            rtc_id as "rtc_id: db::rtc::Id",
+            reader_id as "reader_id: AgentId",
+            receive_video,
+            receive_audio
+        "#,
+        room_id as db::room::Id,
+        reader_id as &AgentId,
+        pending_since,
+    )
+    .fetch_all(conn)
+    .await
+}
+
+/// Brings the reader configs of the RTC in line with the subscribe sets of the room's readers.
+/// Meant to run before the stream gets created so the configs are applied along with it.
+pub async fn apply_subscribe_sets(
+    rtc_id: db::rtc::Id,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Vec<Object>> {
+    sqlx::query_as!(
+        Object,
+        r#"
+        INSERT INTO rtc_reader_config (
+            rtc_id, reader_id, receive_video, receive_audio,
+            applied_receive_video, applied_receive_audio
+        )
+        SELECT
+            rtc.id, s.reader_id, rtc.id = ANY(s.rtc_ids), true,
+            rtc.id = ANY(s.rtc_ids), true
+        FROM rtc
+        INNER JOIN rtc_subscribe_set AS s
+        ON s.room_id = rtc.room_id
+        LEFT JOIN rtc_reader_config AS rrc
+        ON rrc.rtc_id = rtc.id AND rrc.reader_id = s.reader_id
+        WHERE
+            rtc.id = $1 AND
+            rtc.created_by <> s.reader_id AND
+            COALESCE(rrc.receive_video, true) <> (rtc.id = ANY(s.rtc_ids))
+        ON CONFLICT (rtc_id, reader_id) DO UPDATE
+        SET
+            receive_video = EXCLUDED.receive_video,
+            applied_receive_video = EXCLUDED.receive_video,
+            pending_since = NULL,
+            push_attempts = 0
+        RETURNING
+            rtc_id as "rtc_id: db::rtc::Id",
+            reader_id as "reader_id: AgentId",
+            receive_video,
+            receive_audio
+        "#,
+        rtc_id as db::rtc::Id,
+    )
+    .fetch_all(conn)
+    .await
+}
+
+/// Gives the agents of the room which have no reader config of the RTC yet the room's reader
+/// config defaults. Agents entering after the stream has been created receive everything
+/// until they get configured.
+pub async fn apply_room_defaults(
+    rtc_id: db::rtc::Id,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Vec<Object>> {
+    sqlx::query_as!(
+        Object,
+        r#"
+        INSERT INTO rtc_reader_config (
+            rtc_id, reader_id, receive_video, receive_audio,
+            applied_receive_video, applied_receive_audio
+        )
+        SELECT
+            rtc.id, a.agent_id,
+            COALESCE((d.reader_config->>'receive_video')::boolean, true),
+            COALESCE((d.reader_config->>'receive_audio')::boolean, true),
+            COALESCE((d.reader_config->>'receive_video')::boolean, true),
+            COALESCE((d.reader_config->>'receive_audio')::boolean, true)
+        FROM rtc
+        INNER JOIN room_config_default AS d
+        ON d.room_id = rtc.room_id
+        INNER JOIN agent AS a
+        ON a.room_id = rtc.room_id
+        WHERE
+            rtc.id = $1 AND
+            d.reader_config IS NOT NULL AND
+            a.status = 'ready' AND
+            a.agent_id <> rtc.created_by
+        ON CONFLICT (rtc_id, reader_id) DO NOTHING
+        RETURNING
+            rtc_id as "rtc_id: db::rtc::Id",
+            reader_id as "reader_id: AgentId",
+            receive_video,
+            receive_audio
+        "#,
+        rtc_id as db::rtc::Id,
+    )
+    .fetch_all(conn)
+    .await
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Marks the reader's configs written at `pending_since` as applied. Intents written later
+/// stay pending since Janus hasn't seen them yet.
+pub async fn confirm(
+    reader_id: &AgentId,
+    rtc_ids: &[db::rtc::Id],
+    pending_since: DateTime<Utc>,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<u64> {
+    sqlx::query!(
+        r#"
+        UPDATE rtc_reader_config
+        SET
+            applied_receive_video = receive_video,
+            applied_receive_audio = receive_audio,
+            pending_since = NULL,
+            push_attempts = 0
+        WHERE
+            reader_id = $1 AND
+            rtc_id = ANY($2) AND
+            pending_since = $3
+        "#,
+        reader_id as &AgentId,
+        rtc_ids as &[db::rtc::Id],
+        pending_since,
+    )
+    .execute(conn)
+    .await
+    .map(|r| r.rows_affected())
+}
+
+pub async fn has_pending(
+    room_id: db::room::Id,
+    reader_id: &AgentId,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<bool> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS (
+            SELECT 1
+            FROM rtc_reader_config AS rrc
+            INNER JOIN rtc
+            ON rrc.rtc_id = rtc.id
+            WHERE
+                rtc.room_id = $1 AND
+                rrc.reader_id = $2 AND
+                rrc.pending_since IS NOT NULL
+        ) AS "exists!"
+        "#,
+        room_id as db::room::Id,
+        reader_id as &AgentId,
+    )
+    .fetch_one(conn)
+    .await
+}
+
+/// A config which was written but not confirmed to be pushed to Janus.
+#[derive(Debug)]
+pub struct PendingObject {
+    pub rtc_id: db::rtc::Id,
+    pub reader_id: AgentId,
+    pub receive_video: bool,
+    pub receive_audio: bool,
+    pub pending_since: DateTime<Utc>,
+    pub backend_id: Option<AgentId>,
+}
+
+/// Lists configs pending since before `pending_before`, oldest first.
+pub async fn list_pending(
+    pending_before: DateTime<Utc>,
+    limit: i64,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Vec<PendingObject>> {
+    sqlx::query_as!(
+        PendingObject,
+        r#"
+        SELECT
+            rrc.rtc_id as "rtc_id: db::rtc::Id",
+            rrc.reader_id as "reader_id: AgentId",
+            rrc.receive_video,
+            rrc.receive_audio,
+            rrc.pending_since as "pending_since!",
+            r.backend_id as "backend_id: AgentId"
+        FROM rtc_reader_config AS rrc
+        INNER JOIN rtc
+        ON rrc.rtc_id = rtc.id
+        INNER JOIN room AS r
+        ON rtc.room_id = r.id
+        WHERE rrc.pending_since < $1
+        ORDER BY rrc.pending_since
+        LIMIT $2
+        "#,
+        pending_before,
+        limit,
+    )
+    .fetch_all(conn)
+    .await
+}
+
+/// Counts a failed push of the intent. Returns the number of attempts so far.
+pub async fn record_failed_push(
+    config: &PendingObject,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Option<i32>> {
+    sqlx::query_scalar!(
+        r#"
+        UPDATE rtc_reader_config
+        SET push_attempts = push_attempts + 1
+        WHERE
+            rtc_id = $1 AND
+            reader_id = $2 AND
+            pending_since = $3
+        RETURNING push_attempts
+        "#,
+        config.rtc_id as db::rtc::Id,
+        &config.reader_id as &AgentId,
+        config.pending_since,
+    )
+    .fetch_optional(conn)
+    .await
+}
+
+/// Drops the intent restoring the last values confirmed to be applied in Janus.
+pub async fn rollback(
+    config: &PendingObject,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Option<Object>> {
+    sqlx::query_as!(
+        Object,
+        r#"
+        UPDATE rtc_reader_config
+        SET
+            receive_video = applied_receive_video,
+            receive_audio = applied_receive_audio,
+            pending_since = NULL,
+            push_attempts = 0
+        WHERE
+            rtc_id = $1 AND
+            reader_id = $2 AND
+            pending_since = $3
+        RETURNING
+            rtc_id as "rtc_id: db::rtc::Id",
+            reader_id as "reader_id: AgentId",
+            receive_video,
+            receive_audio
+        "#,
+        config.rtc_id as db::rtc::Id,
+        &config.reader_id as &AgentId,
+        config.pending_since,
+    )
+    .fetch_optional(conn)
+    .await
+}
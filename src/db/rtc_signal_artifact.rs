@@ -0,0 +1,172 @@
+use chrono::{serde::ts_milliseconds, DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use svc_agent::AgentId;
+use uuid::Uuid;
+
+use crate::db;
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "rtc_signal_artifact_kind")]
+pub enum Kind {
+    #[sqlx(rename = "offer")]
+    Offer,
+    #[sqlx(rename = "answer")]
+    Answer,
+    /// Candidate lines of one trickle request.
+    #[sqlx(rename = "candidates")]
+    Candidates,
+}
+
+/// An SDP or a candidate batch sent while negotiating an agent's connection to an rtc.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Object {
+    pub id: Uuid,
+    pub rtc_id: db::rtc::Id,
+    pub agent_id: AgentId,
+    pub kind: Kind,
+    pub body: String,
+    #[serde(with = "ts_milliseconds")]
+    pub created_at: DateTime<Utc>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Turns storing artifacts of the room's rtcs on.
+pub async fn enable(room_id: db::room::Id, conn: &mut sqlx::PgConnection) -> sqlx::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO rtc_signal_debug (room_id)
+        VALUES ($1)
+        ON CONFLICT (room_id) DO NOTHING
+        "#,
+        room_id as db::room::Id,
+    )
+    .execute(conn)
+    .await
+    .map(|_| ())
+}
+
+/// Turns storing off. Already stored artifacts are kept.
+pub async fn disable(room_id: db::room::Id, conn: &mut sqlx::PgConnection) -> sqlx::Result<()> {
+    sqlx::query!(
+        r#"
+        DELETE FROM rtc_signal_debug
+        WHERE room_id = $1
+        "#,
+        room_id as db::room::Id,
+    )
+    .execute(conn)
+    .await
+    .map(|_| ())
+}
+
+pub struct InsertQuery<'a> {
+    room_id: db::room::Id,
+    rtc_id: db::rtc::Id,
+    agent_id: &'a AgentId,
+    kind: Kind,
+    body: &'a str,
+}
+
+impl<'a> InsertQuery<'a> {
+    pub fn new(
+        room_id: db::room::Id,
+        rtc_id: db::rtc::Id,
+        agent_id: &'a AgentId,
+        kind: Kind,
+        body: &'a str,
+    ) -> Self {
+        Self {
+            room_id,
+            rtc_id,
+            agent_id,
+            kind,
+            body,
+        }
+    }
+
+    /// Stores the artifact if the room has debugging on and drops the connection's artifacts
+    /// beyond `max_artifacts` most recent ones. Returns whether it got stored.
+    pub async fn execute(
+        self,
+        max_artifacts: i64,
+        conn: &mut sqlx::PgConnection,
+    ) -> sqlx::Result<bool> {
+        let inserted = sqlx::query!(
+            r#"
+            INSERT INTO rtc_signal_artifact (room_id, rtc_id, agent_id, kind, body)
+            SELECT $1::uuid, $2::uuid, $3::agent_id, $4::rtc_signal_artifact_kind, $5::text
+            WHERE EXISTS (
+                SELECT 1
+                FROM rtc_signal_debug
+                WHERE room_id = $1
+            )
+            "#,
+            self.room_id as db::room::Id,
+            self.rtc_id as db::rtc::Id,
+            self.agent_id as &AgentId,
+            self.kind as Kind,
+            self.body,
+        )
+        .execute(&mut *conn)
+        .await?
+        .rows_affected()
+            > 0;
+
+        if inserted {
+            sqlx::query!(
+                r#"
+                DELETE FROM rtc_signal_artifact
+                WHERE rtc_id = $1
+                AND   agent_id = $2
+                AND   id NOT IN (
+                    SELECT id
+                    FROM rtc_signal_artifact
+                    WHERE rtc_id = $1
+                    AND   agent_id = $2
+                    ORDER BY created_at DESC
+                    LIMIT $3
+                )
+                "#,
+                self.rtc_id as db::rtc::Id,
+                self.agent_id as &AgentId,
+                max_artifacts,
+            )
+            .execute(conn)
+            .await?;
+        }
+
+        Ok(inserted)
+    }
+}
+
+/// Artifacts of the rtc, the most recent first, optionally of a single agent.
+pub async fn list(
+    rtc_id: db::rtc::Id,
+    agent_id: Option<&AgentId>,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Vec<Object>> {
+    sqlx::query_as!(
+        Object,
+        r#"
+        SELECT
+            id,
+            rtc_id as "rtc_id: db::rtc::Id",
+            agent_id as "agent_id: AgentId",
+            kind as "kind: Kind",
+            body,
+            created_at
+        FROM rtc_signal_artifact
+        WHERE rtc_id = $1
+        AND   ($2::agent_id IS NULL OR agent_id = $2)
+        ORDER BY created_at DESC
+        "#,
+        rtc_id as db::rtc::Id,
+        agent_id as Option<&AgentId>,
+    )
+    .fetch_all(conn)
+    .await
+}
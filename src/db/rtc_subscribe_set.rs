@@ -0,0 +1,168 @@
+use svc_agent::AgentId;
+
+use crate::db;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// RTCs a reader wants to receive video from. Video of the rest of the room's RTCs is off.
+pub struct Object {
+    room_id: db::room::Id,
+    rtc_ids: Vec<db::rtc::Id>,
+}
+
+impl Object {
+    pub fn room_id(&self) -> db::room::Id {
+        self.room_id
+    }
+
+    pub fn rtc_ids(&self) -> &[db::rtc::Id] {
+        &self.rtc_ids
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+pub async fn upsert(
+    room_id: db::room::Id,
+    reader_id: &AgentId,
+    rtc_ids: &[db::rtc::Id],
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<Object> {
+    sqlx::query_as!(
+        Object,
+        r#"
+        INSERT INTO rtc_subscribe_set (room_id, reader_id, rtc_ids)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (room_id, reader_id) DO UPDATE
+        SET
+            rtc_ids = EXCLUDED.rtc_ids,
+            updated_at = now()
+        RETURNING
+            room_id as "room_id: db::room::Id",
+            rtc_ids as "rtc_ids: Vec<db::rtc::Id>"
+        "#,
+        room_id as db::room::Id,
+        reader_id as &AgentId,
+        rtc_ids as &[db::rtc::Id],
+    )
+    .fetch_one(conn)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use chrono::Utc;
+
+    use super::*;
+    use crate::test_helpers::{db::TestDb, prelude::*};
+
+    #[sqlx::test]
+    async fn apply_set(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let mut conn = db.get_conn().await;
+
+        let room = shared_helpers::insert_room_with_owned(&mut conn).await;
+        let reader = TestAgent::new("web", "reader", USR_AUDIENCE);
+
+        let mut rtcs = vec![];
+
+        for idx in 0..3 {
+            let writer = TestAgent::new("web", &format!("writer{idx}"), USR_AUDIENCE);
+
+            let rtc = factory::Rtc::new(room.id())
+                .created_by(writer.agent_id().to_owned())
+                .insert(&mut conn)
+                .await;
+
+            rtcs.push(rtc);
+        }
+
+        // The reader's own rtc is never touched.
+        factory::Rtc::new(room.id())
+            .created_by(reader.agent_id().to_owned())
+            .insert(&mut conn)
+            .await;
+
+        upsert(room.id(), reader.agent_id(), &[rtcs[0].id()], &mut conn)
+            .await
+            .expect("Failed to upsert subscribe set");
+
+        let configs = db::rtc_reader_config::apply_subscribe_set(
+            room.id(),
+            reader.agent_id(),
+            Utc::now(),
+            &mut conn,
+        )
+        .await
+        .expect("Failed to apply subscribe set")
+        .into_iter()
+        .map(|c| (c.rtc_id(), c.receive_video()))
+        .collect::<HashMap<_, _>>();
+
+        let expected = [(rtcs[1].id(), false), (rtcs[2].id(), false)].into();
+        assert_eq!(configs, expected);
+
+        // Applying the same set again changes nothing.
+        let configs = db::rtc_reader_config::apply_subscribe_set(
+            room.id(),
+            reader.agent_id(),
+            Utc::now(),
+            &mut conn,
+        )
+        .await
+        .expect("Failed to apply subscribe set");
+
+        assert!(configs.is_empty());
+
+        // Switching the tiles turns the video of the previous one off.
+        upsert(room.id(), reader.agent_id(), &[rtcs[1].id()], &mut conn)
+            .await
+            .expect("Failed to upsert subscribe set");
+
+        let configs = db::rtc_reader_config::apply_subscribe_set(
+            room.id(),
+            reader.agent_id(),
+            Utc::now(),
+            &mut conn,
+        )
+        .await
+        .expect("Failed to apply subscribe set")
+        .into_iter()
+        .map(|c| (c.rtc_id(), c.receive_video()))
+        .collect::<HashMap<_, _>>();
+
+        let expected = [(rtcs[0].id(), false), (rtcs[1].id(), true)].into();
+        assert_eq!(configs, expected);
+    }
+
+    #[sqlx::test]
+    async fn apply_set_to_new_rtc(pool: sqlx::PgPool) {
+        let db = TestDb::new(pool);
+        let mut conn = db.get_conn().await;
+
+        let room = shared_helpers::insert_room_with_owned(&mut conn).await;
+        let reader = TestAgent::new("web", "reader", USR_AUDIENCE);
+        let writer = TestAgent::new("web", "writer", USR_AUDIENCE);
+
+        upsert(room.id(), reader.agent_id(), &[], &mut conn)
+            .await
+            .expect("Failed to upsert subscribe set");
+
+        // The rtc appears after the set has been chosen.
+        let rtc = factory::Rtc::new(room.id())
+            .created_by(writer.agent_id().to_owned())
+            .insert(&mut conn)
+            .await;
+
+        let configs = db::rtc_reader_config::apply_subscribe_sets(rtc.id(), &mut conn)
+            .await
+            .expect("Failed to apply subscribe sets");
+
+        assert_eq!(configs.len(), 1);
+        assert_eq!(configs[0].reader_id(), reader.agent_id());
+        assert!(!configs[0].receive_video());
+        assert!(configs[0].receive_audio());
+    }
+}
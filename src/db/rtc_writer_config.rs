@@ -1,4 +1,5 @@
 use chrono::{DateTime, Utc};
+use serde_json::Value as JsonValue;
 use svc_agent::AgentId;
 
 use crate::{db, db::rtc::Object as Rtc};
@@ -12,6 +13,8 @@ pub struct Object {
     send_video: bool,
     send_audio: bool,
     video_remb: Option<i64>,
+    max_video_bitrate: Option<i64>,
+    max_audio_bitrate: Option<i64>,
     send_audio_updated_by: Option<AgentId>,
     updated_at: DateTime<Utc>,
 }
@@ -29,6 +32,14 @@ impl Object {
         self.video_remb
     }
 
+    pub fn max_video_bitrate(&self) -> Option<i64> {
+        self.max_video_bitrate
+    }
+
+    pub fn max_audio_bitrate(&self) -> Option<i64> {
+        self.max_audio_bitrate
+    }
+
     pub fn send_audio_updated_by(&self) -> Option<&AgentId> {
         self.send_audio_updated_by.as_ref()
     }
@@ -51,11 +62,16 @@ struct ListWithRtcRow {
     send_video: bool,
     send_audio: bool,
     video_remb: Option<i64>,
+    max_video_bitrate: Option<i64>,
+    max_audio_bitrate: Option<i64>,
     send_audio_updated_by: Option<AgentId>,
     updated_at: DateTime<Utc>,
     room_id: db::room::Id,
     created_at: DateTime<Utc>,
     created_by: AgentId,
+    record: bool,
+    tags: Option<JsonValue>,
+    kind: db::rtc::Kind,
 }
 
 impl ListWithRtcRow {
@@ -66,6 +82,8 @@ impl ListWithRtcRow {
                 send_video: self.send_video,
                 send_audio: self.send_audio,
                 video_remb: self.video_remb,
+                max_video_bitrate: self.max_video_bitrate,
+                max_audio_bitrate: self.max_audio_bitrate,
                 send_audio_updated_by: self.send_audio_updated_by,
                 updated_at: self.updated_at,
             },
@@ -74,6 +92,9 @@ impl ListWithRtcRow {
                 room_id: self.room_id,
                 created_at: self.created_at,
                 created_by: self.created_by,
+                record: self.record,
+                tags: self.tags,
+                kind: self.kind,
             },
         )
     }
@@ -93,11 +114,16 @@ impl ListWithRtcQuery {
                 rwc.send_video,
                 rwc.send_audio,
                 rwc.video_remb,
+                rwc.max_video_bitrate,
+                rwc.max_audio_bitrate,
                 rwc.send_audio_updated_by as "send_audio_updated_by: AgentId",
                 rwc.updated_at,
                 r.room_id as "room_id: db::room::Id",
                 r.created_at,
-                r.created_by as "created_by: AgentId"
+                r.created_by as "created_by: AgentId",
+                r.record,
+                r.tags,
+                r.kind as "kind: db::rtc::Kind"
             FROM rtc_writer_config as rwc
             INNER JOIN rtc as r
             ON rwc.rtc_id = r.id
@@ -124,6 +150,8 @@ pub async fn read_config(
             send_video,
             send_audio,
             video_remb,
+            max_video_bitrate,
+            max_audio_bitrate,
             send_audio_updated_by as "send_audio_updated_by: AgentId",
             updated_at
         FROM rtc_writer_config
@@ -144,6 +172,8 @@ pub struct UpsertQuery<'a> {
     send_video: Option<bool>,
     send_audio: Option<bool>,
     video_remb: Option<i64>,
+    max_video_bitrate: Option<i64>,
+    max_audio_bitrate: Option<i64>,
     send_audio_updated_by: Option<&'a AgentId>,
 }
 
@@ -155,6 +185,8 @@ impl<'a> UpsertQuery<'a> {
             send_audio_updated_by: Default::default(),
             send_video: Default::default(),
             video_remb: Default::default(),
+            max_video_bitrate: Default::default(),
+            max_audio_bitrate: Default::default(),
         }
     }
 
@@ -179,6 +211,20 @@ impl<'a> UpsertQuery<'a> {
         }
     }
 
+    pub fn max_video_bitrate(self, max_video_bitrate: i64) -> Self {
+        Self {
+            max_video_bitrate: Some(max_video_bitrate),
+            ..self
+        }
+    }
+
+    pub fn max_audio_bitrate(self, max_audio_bitrate: i64) -> Self {
+        Self {
+            max_audio_bitrate: Some(max_audio_bitrate),
+            ..self
+        }
+    }
+
     pub fn send_audio_updated_by(self, send_audio_updated_by: &'a AgentId) -> Self {
         Self {
             send_audio_updated_by: Some(send_audio_updated_by),
@@ -193,19 +239,23 @@ impl<'a> UpsertQuery<'a> {
         sqlx::query_as!(
             Object,
             r#"
-            INSERT INTO rtc_writer_config (rtc_id, send_video, send_audio, video_remb, send_audio_updated_by)
-            VALUES ($1, $2, $3, $4, $5)
+            INSERT INTO rtc_writer_config (rtc_id, send_video, send_audio, video_remb, send_audio_updated_by, max_video_bitrate, max_audio_bitrate)
+            VALUES ($1, $2, $3, $4, $5, $8, $9)
             ON CONFLICT (rtc_id) DO UPDATE
             SET
                 video_remb = $4,
                 send_audio_updated_by = $5,
                 send_video = COALESCE($6, rtc_writer_config.send_video),
-                send_audio = COALESCE($7, rtc_writer_config.send_audio)
+                send_audio = COALESCE($7, rtc_writer_config.send_audio),
+                max_video_bitrate = $8,
+                max_audio_bitrate = $9
             RETURNING
                 rtc_id as "rtc_id: db::rtc::Id",
                 send_video,
                 send_audio,
                 video_remb,
+                max_video_bitrate,
+                max_audio_bitrate,
                 send_audio_updated_by as "send_audio_updated_by: AgentId",
                 updated_at
             "#,
@@ -215,7 +265,9 @@ impl<'a> UpsertQuery<'a> {
             self.video_remb,
             self.send_audio_updated_by as Option<&AgentId>,
             self.send_video,
-            self.send_audio
+            self.send_audio,
+            self.max_video_bitrate,
+            self.max_audio_bitrate
         )
         .fetch_one(conn)
         .await
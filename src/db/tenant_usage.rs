@@ -0,0 +1,122 @@
+use chrono::{serde::ts_seconds, DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::app::tenant_usage::Counters;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Resources spent on behalf of the audience within an hour.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Object {
+    pub audience: String,
+    #[serde(with = "ts_seconds")]
+    pub period_start: DateTime<Utc>,
+    pub requests: i64,
+    pub db_time_ms: i64,
+    pub janus_calls: i64,
+    pub bytes_published: i64,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Adds the counters to the audience usage of the current hour.
+pub async fn add(
+    audience: &str,
+    counters: &Counters,
+    conn: &mut sqlx::PgConnection,
+) -> sqlx::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO tenant_usage
+            (audience, period_start, requests, db_time_ms, janus_calls, bytes_published)
+        VALUES ($1, DATE_TRUNC('hour', NOW()), $2, $3, $4, $5)
+        ON CONFLICT (audience, period_start) DO UPDATE
+        SET
+            requests = tenant_usage.requests + EXCLUDED.requests,
+            db_time_ms = tenant_usage.db_time_ms + EXCLUDED.db_time_ms,
+            janus_calls = tenant_usage.janus_calls + EXCLUDED.janus_calls,
+            bytes_published = tenant_usage.bytes_published + EXCLUDED.bytes_published,
+            updated_at = NOW()
+        "#,
+        audience,
+        counters.requests,
+        counters.db_time_ms,
+        counters.janus_calls,
+        counters.bytes_published,
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Default)]
+pub struct ListQuery<'a> {
+    audience: Option<&'a str>,
+    since: Option<DateTime<Utc>>,
+    offset: Option<i64>,
+    limit: Option<i64>,
+}
+
+impl<'a> ListQuery<'a> {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn audience(self, audience: &'a str) -> Self {
+        Self {
+            audience: Some(audience),
+            ..self
+        }
+    }
+
+    pub fn since(self, since: DateTime<Utc>) -> Self {
+        Self {
+            since: Some(since),
+            ..self
+        }
+    }
+
+    pub fn offset(self, offset: i64) -> Self {
+        Self {
+            offset: Some(offset),
+            ..self
+        }
+    }
+
+    pub fn limit(self, limit: i64) -> Self {
+        Self {
+            limit: Some(limit),
+            ..self
+        }
+    }
+
+    pub async fn execute(&self, conn: &mut sqlx::PgConnection) -> sqlx::Result<Vec<Object>> {
+        sqlx::query_as!(
+            Object,
+            r#"
+            SELECT
+                audience,
+                period_start,
+                requests,
+                db_time_ms,
+                janus_calls,
+                bytes_published
+            FROM tenant_usage
+            WHERE ($1::TEXT IS NULL OR audience = $1::TEXT)
+            AND   ($2::TIMESTAMPTZ IS NULL OR period_start >= $2::TIMESTAMPTZ)
+            ORDER BY period_start DESC, audience
+            OFFSET $3
+            LIMIT $4
+            "#,
+            self.audience,
+            self.since,
+            self.offset.unwrap_or(0),
+            self.limit,
+        )
+        .fetch_all(conn)
+        .await
+    }
+}
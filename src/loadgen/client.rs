@@ -0,0 +1,182 @@
+use std::{sync::Arc, time::Instant};
+
+use anyhow::{anyhow, Context, Result};
+use reqwest::{Method, Url};
+use serde::Serialize;
+use serde_json::{json, Value as JsonValue};
+use svc_agent::{AccountId, AgentId};
+use svc_authn::token::jws_compact;
+
+use super::{stats::Stats, Scenario};
+use crate::{
+    backend::janus::client::{JsepType, JsonSdp},
+    db,
+};
+
+/// Synthetic agent calling the HTTP API on its own behalf.
+pub struct Agent {
+    id: AgentId,
+    token: String,
+}
+
+impl Agent {
+    pub fn new(scenario: &Scenario, account_label: &str) -> Result<Self> {
+        let account_id = AccountId::new(account_label, &scenario.audience);
+
+        let token = jws_compact::TokenBuilder::new()
+            .issuer(&scenario.token.issuer)
+            .subject(&account_id)
+            .expires_in(scenario.token.expires_in)
+            .key(scenario.token.algorithm, scenario.token.key.as_slice())
+            .build()
+            .context("Failed to build token")?;
+
+        Ok(Self {
+            id: AgentId::new("web", account_id),
+            token,
+        })
+    }
+
+    pub fn id(&self) -> &AgentId {
+        &self.id
+    }
+}
+
+/// Calls the endpoints a client goes through and records their latencies.
+#[derive(Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    base_url: Url,
+    stats: Arc<Stats>,
+}
+
+impl Client {
+    pub fn new(base_url: Url, stats: Arc<Stats>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url,
+            stats,
+        }
+    }
+
+    pub async fn create_room(
+        &self,
+        agent: &Agent,
+        audience: &str,
+        rtc_sharing_policy: db::rtc::SharingPolicy,
+    ) -> Result<db::room::Id> {
+        let time = db::room::RoomTime::new(
+            std::ops::Bound::Included(chrono::Utc::now()),
+            std::ops::Bound::Unbounded,
+        )?;
+
+        let payload = json!({
+            "time": time,
+            "audience": audience,
+            "rtc_sharing_policy": rtc_sharing_policy,
+            "classroom_id": uuid::Uuid::new_v4(),
+        });
+
+        let room = self
+            .call("room.create", agent, Method::POST, "rooms", Some(payload))
+            .await?;
+
+        id(&room)
+    }
+
+    pub async fn enter_room(&self, agent: &Agent, room_id: db::room::Id) -> Result<()> {
+        let path = format!("rooms/{}/enter", room_id);
+        self.call("room.enter", agent, Method::POST, &path, None::<()>)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn create_rtc(&self, agent: &Agent, room_id: db::room::Id) -> Result<db::rtc::Id> {
+        let path = format!("rooms/{}/rtcs", room_id);
+        let rtc = self
+            .call("rtc.create", agent, Method::POST, &path, None::<()>)
+            .await?;
+
+        id(&rtc)
+    }
+
+    pub async fn signal(
+        &self,
+        agent: &Agent,
+        rtc_id: db::rtc::Id,
+        intent: &str,
+        sdp: &str,
+    ) -> Result<()> {
+        let endpoint = match intent {
+            "write" => "rtc.signal (write)",
+            _ => "rtc.signal (read)",
+        };
+
+        let payload = json!({
+            "intent": intent,
+            "jsep": JsonSdp {
+                kind: JsepType::Offer,
+                sdp: sdp.to_owned(),
+            },
+        });
+
+        let path = format!("rtcs/{}/signal", rtc_id);
+        self.call(endpoint, agent, Method::POST, &path, Some(payload))
+            .await?;
+        Ok(())
+    }
+
+    async fn call<T: Serialize>(
+        &self,
+        endpoint: &'static str,
+        agent: &Agent,
+        method: Method,
+        path: &str,
+        payload: Option<T>,
+    ) -> Result<JsonValue> {
+        let url = self.base_url.join(path)?;
+
+        let mut request = self
+            .http
+            .request(method, url)
+            .bearer_auth(&agent.token)
+            .header("X-Agent-Label", agent.id.label());
+
+        if let Some(payload) = payload {
+            request = request.json(&payload);
+        }
+
+        let started_at = Instant::now();
+        let result = request.send().await;
+        let elapsed = started_at.elapsed();
+
+        let response = match result {
+            Ok(response) => response,
+            Err(err) => {
+                self.stats.record(endpoint, elapsed, false);
+                return Err(err.into());
+            }
+        };
+
+        let status = response.status();
+        self.stats.record(endpoint, elapsed, status.is_success());
+
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(anyhow!("{} failed with {}: {}", endpoint, status, body));
+        }
+
+        let body = response.bytes().await?;
+
+        if body.is_empty() {
+            Ok(JsonValue::Null)
+        } else {
+            Ok(serde_json::from_slice(&body)?)
+        }
+    }
+}
+
+fn id<T: serde::de::DeserializeOwned>(object: &JsonValue) -> Result<T> {
+    let id = object.get("id").context("Missing id in response")?;
+    Ok(serde_json::from_value(id.to_owned())?)
+}
@@ -0,0 +1,210 @@
+use std::{sync::Arc, time::Instant};
+
+use anyhow::{Context, Result};
+use futures::future;
+use reqwest::Url;
+use serde::Deserialize;
+use svc_authn::jose::Algorithm;
+use tokio::sync::Semaphore;
+use tracing::{error, info};
+
+use crate::db;
+
+use self::{
+    client::{Agent, Client},
+    stats::Stats,
+};
+
+mod client;
+mod stats;
+
+const SENDONLY_OFFER: &str = include_str!("offer_sendonly.sdp");
+const RECVONLY_OFFER: &str = include_str!("offer_recvonly.sdp");
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Run with `conference loadgen <scenario>`, see `Loadgen.toml.sample` for the format.
+#[derive(Debug, Deserialize)]
+pub struct Scenario {
+    /// Base URL of the HTTP API, e.g. `https://conference.example.org/api/v1/`.
+    base_url: Url,
+    /// Audience of the synthetic rooms and accounts.
+    audience: String,
+    rooms: usize,
+    agents_per_room: usize,
+    /// How many of the room's agents publish a stream. Everyone else reads all of them.
+    #[serde(default = "default_publishers_per_room")]
+    publishers_per_room: usize,
+    /// Limit of requests in flight.
+    #[serde(default = "default_concurrency")]
+    concurrency: usize,
+    #[serde(default = "default_rtc_sharing_policy")]
+    rtc_sharing_policy: db::rtc::SharingPolicy,
+    /// Skip signaling to only load the room flows.
+    #[serde(default = "default_signaling")]
+    signaling: bool,
+    token: TokenConfig,
+}
+
+fn default_publishers_per_room() -> usize {
+    1
+}
+
+fn default_concurrency() -> usize {
+    100
+}
+
+fn default_rtc_sharing_policy() -> db::rtc::SharingPolicy {
+    db::rtc::SharingPolicy::Owned
+}
+
+fn default_signaling() -> bool {
+    true
+}
+
+/// Key to sign tokens of the synthetic accounts with. It must be trusted by the service under
+/// test.
+#[derive(Debug, Deserialize)]
+struct TokenConfig {
+    #[serde(deserialize_with = "svc_authn::serde::algorithm")]
+    algorithm: Algorithm,
+    #[serde(deserialize_with = "svc_authn::serde::file")]
+    key: Vec<u8>,
+    issuer: String,
+    #[serde(default = "default_token_expires_in")]
+    expires_in: i64,
+}
+
+fn default_token_expires_in() -> i64 {
+    3600
+}
+
+impl Scenario {
+    fn load(path: &str) -> Result<Self> {
+        let mut parser = config::Config::default();
+        parser.merge(config::File::with_name(path))?;
+        parser
+            .try_into::<Self>()
+            .context("Failed to parse scenario")
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+pub async fn run(scenario_path: &str) -> Result<()> {
+    let scenario = Arc::new(Scenario::load(scenario_path)?);
+    let stats = Arc::new(Stats::default());
+    let client = Client::new(scenario.base_url.clone(), stats.clone());
+    let limit = Arc::new(Semaphore::new(scenario.concurrency));
+
+    info!(
+        rooms = scenario.rooms,
+        agents_per_room = scenario.agents_per_room,
+        "Load test started"
+    );
+
+    let started_at = Instant::now();
+
+    let rooms = (0..scenario.rooms).map(|n| {
+        let room = Room {
+            n,
+            scenario: scenario.clone(),
+            client: client.clone(),
+            limit: limit.clone(),
+        };
+
+        async move {
+            if let Err(err) = room.run().await {
+                error!(?err, room = n, "Synthetic room failed");
+            }
+        }
+    });
+
+    future::join_all(rooms).await;
+
+    println!("{}", stats.report(started_at.elapsed()));
+    Ok(())
+}
+
+struct Room {
+    n: usize,
+    scenario: Arc<Scenario>,
+    client: Client,
+    limit: Arc<Semaphore>,
+}
+
+impl Room {
+    async fn run(&self) -> Result<()> {
+        let scenario = &self.scenario;
+
+        let agents = (0..scenario.agents_per_room)
+            .map(|i| Agent::new(scenario, &format!("loadgen-{}-{}", self.n, i)))
+            .collect::<Result<Vec<_>>>()?;
+
+        let room_id = {
+            let _permit = self.limit.acquire().await?;
+            let host = agents.first().context("No agents in the room")?;
+
+            self.client
+                .create_room(host, &scenario.audience, scenario.rtc_sharing_policy)
+                .await?
+        };
+
+        self.each(&agents, |agent| self.client.enter_room(agent, room_id))
+            .await;
+
+        let publishers = &agents[..scenario.publishers_per_room.min(agents.len())];
+        let readers = &agents[publishers.len()..];
+
+        let rtc_ids = self
+            .each(publishers, |agent| async move {
+                let rtc_id = self.client.create_rtc(agent, room_id).await?;
+
+                if scenario.signaling {
+                    self.client
+                        .signal(agent, rtc_id, "write", SENDONLY_OFFER)
+                        .await?;
+                }
+
+                Ok(rtc_id)
+            })
+            .await;
+
+        if scenario.signaling {
+            for rtc_id in rtc_ids {
+                self.each(readers, |agent| {
+                    self.client.signal(agent, rtc_id, "read", RECVONLY_OFFER)
+                })
+                .await;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Runs `f` for every agent concurrently within the limit and collects successful results.
+    /// Failures are already accounted in the stats so they're only logged.
+    async fn each<'a, F, Fut, T>(&self, agents: &'a [Agent], f: F) -> Vec<T>
+    where
+        F: Fn(&'a Agent) -> Fut,
+        Fut: std::future::Future<Output = Result<T>>,
+    {
+        let calls = agents.iter().map(|agent| {
+            let call = f(agent);
+
+            async move {
+                let _permit = self.limit.acquire().await.ok()?;
+
+                call.await
+                    .map_err(|err| error!(?err, agent = %agent.id(), "Synthetic agent failed"))
+                    .ok()
+            }
+        });
+
+        future::join_all(calls)
+            .await
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+}
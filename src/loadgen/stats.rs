@@ -0,0 +1,132 @@
+use std::{collections::BTreeMap, fmt, time::Duration};
+
+use parking_lot::Mutex;
+
+/// Latencies of calls grouped by endpoint.
+#[derive(Default)]
+pub struct Stats {
+    endpoints: Mutex<BTreeMap<&'static str, Endpoint>>,
+}
+
+#[derive(Default)]
+struct Endpoint {
+    latencies: Vec<Duration>,
+    errors: usize,
+}
+
+impl Stats {
+    pub fn record(&self, endpoint: &'static str, latency: Duration, success: bool) {
+        let mut endpoints = self.endpoints.lock();
+        let entry = endpoints.entry(endpoint).or_default();
+        entry.latencies.push(latency);
+
+        if !success {
+            entry.errors += 1;
+        }
+    }
+
+    pub fn report(&self, elapsed: Duration) -> Report {
+        let endpoints = self.endpoints.lock();
+
+        let rows = endpoints
+            .iter()
+            .map(|(name, endpoint)| {
+                let mut latencies = endpoint.latencies.clone();
+                latencies.sort();
+
+                ReportRow {
+                    endpoint: name,
+                    count: latencies.len(),
+                    errors: endpoint.errors,
+                    p50: percentile(&latencies, 50.0),
+                    p90: percentile(&latencies, 90.0),
+                    p99: percentile(&latencies, 99.0),
+                    max: latencies.last().copied().unwrap_or_default(),
+                }
+            })
+            .collect();
+
+        Report { elapsed, rows }
+    }
+}
+
+/// Nearest-rank percentile of sorted `latencies`.
+fn percentile(latencies: &[Duration], p: f64) -> Duration {
+    if latencies.is_empty() {
+        return Duration::default();
+    }
+
+    let rank = (p / 100.0 * latencies.len() as f64).ceil() as usize;
+    latencies[rank.clamp(1, latencies.len()) - 1]
+}
+
+pub struct Report {
+    elapsed: Duration,
+    rows: Vec<ReportRow>,
+}
+
+struct ReportRow {
+    endpoint: &'static str,
+    count: usize,
+    errors: usize,
+    p50: Duration,
+    p90: Duration,
+    p99: Duration,
+    max: Duration,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Finished in {:.1}s", self.elapsed.as_secs_f64())?;
+        writeln!(
+            f,
+            "{:<24} {:>8} {:>8} {:>10} {:>10} {:>10} {:>10}",
+            "endpoint", "count", "errors", "p50, ms", "p90, ms", "p99, ms", "max, ms"
+        )?;
+
+        for row in &self.rows {
+            writeln!(
+                f,
+                "{:<24} {:>8} {:>8} {:>10} {:>10} {:>10} {:>10}",
+                row.endpoint,
+                row.count,
+                row.errors,
+                row.p50.as_millis(),
+                row.p90.as_millis(),
+                row.p99.as_millis(),
+                row.max.as_millis(),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentiles() {
+        let latencies = (1..=100).map(Duration::from_millis).collect::<Vec<_>>();
+
+        assert_eq!(percentile(&latencies, 50.0), Duration::from_millis(50));
+        assert_eq!(percentile(&latencies, 99.0), Duration::from_millis(99));
+        assert_eq!(percentile(&latencies, 100.0), Duration::from_millis(100));
+        assert_eq!(percentile(&latencies[..1], 99.0), Duration::from_millis(1));
+        assert_eq!(percentile(&[], 50.0), Duration::default());
+    }
+
+    #[test]
+    fn report() {
+        let stats = Stats::default();
+        stats.record("room.create", Duration::from_millis(10), true);
+        stats.record("room.create", Duration::from_millis(30), false);
+
+        let report = stats.report(Duration::from_secs(1));
+        assert_eq!(report.rows.len(), 1);
+        assert_eq!(report.rows[0].count, 2);
+        assert_eq!(report.rows[0].errors, 1);
+        assert_eq!(report.rows[0].max, Duration::from_millis(30));
+    }
+}
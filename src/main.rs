@@ -23,6 +23,15 @@ async fn main() -> Result<()> {
 
     tracing::subscriber::set_global_default(subscriber)?;
 
+    #[cfg(feature = "loadgen")]
+    if let Some("loadgen") = std::env::args().nth(1).as_deref() {
+        let scenario = std::env::args()
+            .nth(2)
+            .unwrap_or_else(|| "Loadgen".to_owned());
+
+        return loadgen::run(&scenario).await;
+    }
+
     let db = create_db().await;
 
     let (redis_pool, authz_cache) = if let Some("1") = var("CACHE_ENABLED").ok().as_deref() {
@@ -99,6 +108,8 @@ mod backend;
 mod client;
 mod config;
 mod db;
+#[cfg(feature = "loadgen")]
+mod loadgen;
 mod outbox;
 mod serde;
 #[cfg(test)]
@@ -1,6 +1,7 @@
 use chrono::{serde::ts_seconds, DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
+use uuid::Uuid;
 
 use svc_events::EventId;
 
@@ -85,6 +86,7 @@ pub struct InsertQuery<'a> {
     stage: JsonValue,
     delivery_deadline_at: DateTime<Utc>,
     operation: &'a str,
+    sequence_id: Option<i64>,
 }
 
 impl<'a> InsertQuery<'a> {
@@ -99,6 +101,15 @@ impl<'a> InsertQuery<'a> {
             stage,
             delivery_deadline_at,
             operation,
+            sequence_id: None,
+        }
+    }
+
+    /// Reuses the sequence id of an existing event instead of taking the next one.
+    pub fn sequence_id(self, sequence_id: i64) -> Self {
+        Self {
+            sequence_id: Some(sequence_id),
+            ..self
         }
     }
 
@@ -106,8 +117,8 @@ impl<'a> InsertQuery<'a> {
         sqlx::query_as!(
             Object,
             r#"
-            INSERT INTO outbox (entity_type, stage, delivery_deadline_at, operation)
-            VALUES ($1, $2, $3, $4)
+            INSERT INTO outbox (entity_type, stage, delivery_deadline_at, operation, id)
+            VALUES ($1, $2, $3, $4, COALESCE($5, NEXTVAL('outbox_id_seq')))
             RETURNING
                 id,
                 entity_type,
@@ -121,7 +132,8 @@ impl<'a> InsertQuery<'a> {
             self.entity_type,
             self.stage,
             self.delivery_deadline_at,
-            self.operation
+            self.operation,
+            self.sequence_id,
         )
         .fetch_one(conn)
         .await
@@ -253,3 +265,226 @@ impl<'a> FindQuery<'a> {
         .await
     }
 }
+
+/// Counts events due for delivery, i.e. the backlog the outbox handler has yet to go through.
+pub struct BacklogQuery;
+
+impl BacklogQuery {
+    pub async fn execute(&self, conn: &mut sqlx::PgConnection) -> sqlx::Result<i64> {
+        sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) as "count!"
+            FROM outbox
+            WHERE
+                delivery_deadline_at <= now()
+            "#
+        )
+        .fetch_one(conn)
+        .await
+    }
+}
+
+pub const HISTORY_STATUS_IN_PROGRESS: &str = "in_progress";
+pub const HISTORY_STATUS_DONE: &str = "done";
+pub const HISTORY_STATUS_FAILED: &str = "failed";
+pub const HISTORY_STATUS_REPLAYED: &str = "replayed";
+
+/// Processing history of an event. The stage is the first one handled under the event id
+/// so replaying it runs the whole chain of stages again.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HistoryObject {
+    id: i64,
+    entity_type: String,
+    operation: String,
+    classroom_id: Option<Uuid>,
+    stage: JsonValue,
+    payload_hash: String,
+    status: String,
+    error_kind: Option<String>,
+    replay_count: i32,
+    #[serde(with = "ts_seconds")]
+    created_at: DateTime<Utc>,
+    #[serde(with = "ts_seconds")]
+    updated_at: DateTime<Utc>,
+}
+
+impl HistoryObject {
+    pub fn status(&self) -> &str {
+        &self.status
+    }
+
+    pub fn replay_count(&self) -> i32 {
+        self.replay_count
+    }
+}
+
+pub struct HistoryUpsertQuery<'a> {
+    id: &'a EventId,
+    classroom_id: Option<Uuid>,
+    stage: &'a JsonValue,
+    status: &'a str,
+    error_kind: Option<&'a str>,
+}
+
+impl<'a> HistoryUpsertQuery<'a> {
+    pub fn new(id: &'a EventId, stage: &'a JsonValue, status: &'a str) -> Self {
+        Self {
+            id,
+            classroom_id: None,
+            stage,
+            status,
+            error_kind: None,
+        }
+    }
+
+    pub fn classroom_id(self, classroom_id: Option<Uuid>) -> Self {
+        Self {
+            classroom_id,
+            ..self
+        }
+    }
+
+    pub fn error_kind(self, error_kind: &'a str) -> Self {
+        Self {
+            error_kind: Some(error_kind),
+            ..self
+        }
+    }
+
+    pub async fn execute(&self, conn: &mut sqlx::PgConnection) -> sqlx::Result<()> {
+        sqlx::query!(
+            r#"
+            INSERT INTO outbox_history
+                (id, entity_type, operation, classroom_id, stage, payload_hash, status, error_kind)
+            VALUES ($1, $2, $3, $4, $5::JSONB, MD5($5::JSONB::TEXT), $6, $7)
+            ON CONFLICT (entity_type, operation, id) DO UPDATE
+            SET
+                classroom_id = COALESCE(outbox_history.classroom_id, EXCLUDED.classroom_id),
+                status = EXCLUDED.status,
+                error_kind = EXCLUDED.error_kind,
+                updated_at = NOW()
+            "#,
+            self.id.sequence_id(),
+            self.id.entity_type(),
+            self.id.operation(),
+            self.classroom_id,
+            self.stage,
+            self.status,
+            self.error_kind,
+        )
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+}
+
+pub struct HistoryFindQuery<'a> {
+    id: &'a EventId,
+}
+
+impl<'a> HistoryFindQuery<'a> {
+    pub fn new(id: &'a EventId) -> Self {
+        Self { id }
+    }
+
+    pub async fn execute(
+        &self,
+        conn: &mut sqlx::PgConnection,
+    ) -> sqlx::Result<Option<HistoryObject>> {
+        sqlx::query_as!(
+            HistoryObject,
+            r#"
+            SELECT
+                id,
+                entity_type,
+                operation,
+                classroom_id,
+                stage,
+                payload_hash,
+                status,
+                error_kind,
+                replay_count,
+                created_at,
+                updated_at
+            FROM outbox_history
+            WHERE
+                id = $1 AND
+                entity_type = $2 AND
+                operation = $3
+            "#,
+            self.id.sequence_id(),
+            self.id.entity_type(),
+            self.id.operation()
+        )
+        .fetch_optional(conn)
+        .await
+    }
+}
+
+/// Puts the classroom's events with sequence ids in the range back to the outbox
+/// under their original ids so the consumers could deduplicate them.
+///
+/// Events which are still in the outbox and those whose stage doesn't match the stored hash
+/// are skipped.
+pub struct ReplayQuery {
+    classroom_id: Uuid,
+    from_sequence_id: i64,
+    to_sequence_id: i64,
+}
+
+impl ReplayQuery {
+    pub fn new(classroom_id: Uuid, from_sequence_id: i64, to_sequence_id: i64) -> Self {
+        Self {
+            classroom_id,
+            from_sequence_id,
+            to_sequence_id,
+        }
+    }
+
+    pub async fn execute(&self, conn: &mut sqlx::PgConnection) -> sqlx::Result<Vec<HistoryObject>> {
+        sqlx::query_as!(
+            HistoryObject,
+            r#"
+            WITH replayed AS (
+                INSERT INTO outbox (id, entity_type, operation, stage, delivery_deadline_at)
+                SELECT h.id, h.entity_type, h.operation, h.stage, NOW()
+                FROM outbox_history AS h
+                WHERE h.classroom_id = $1
+                AND   h.id BETWEEN $2 AND $3
+                AND   h.payload_hash = MD5(h.stage::TEXT)
+                ON CONFLICT (entity_type, operation, id) DO NOTHING
+                RETURNING id, entity_type, operation
+            )
+            UPDATE outbox_history AS h
+            SET
+                status = $4,
+                error_kind = NULL,
+                replay_count = h.replay_count + 1,
+                updated_at = NOW()
+            FROM replayed AS r
+            WHERE h.id = r.id
+            AND   h.entity_type = r.entity_type
+            AND   h.operation = r.operation
+            RETURNING
+                h.id,
+                h.entity_type,
+                h.operation,
+                h.classroom_id,
+                h.stage,
+                h.payload_hash,
+                h.status,
+                h.error_kind,
+                h.replay_count,
+                h.created_at,
+                h.updated_at
+            "#,
+            self.classroom_id,
+            self.from_sequence_id,
+            self.to_sequence_id,
+            HISTORY_STATUS_REPLAYED,
+        )
+        .fetch_all(conn)
+        .await
+    }
+}
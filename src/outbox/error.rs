@@ -35,6 +35,7 @@ pub enum ErrorKind {
     DeleteStageFailed,
     UpdateStageFailed,
     InsertStageFailed,
+    RecordHistoryFailed,
     StageError(String),
 }
 
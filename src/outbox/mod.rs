@@ -1,5 +1,6 @@
 use crate::outbox::error::StageError;
 use svc_events::EventId;
+use uuid::Uuid;
 
 pub mod config;
 pub mod db;
@@ -20,4 +21,9 @@ where
         ctx: &Self::Context,
         id: &EventId,
     ) -> Result<Option<Self::Stage>, StageError>;
+
+    /// The classroom to look up the event in the processing history by.
+    fn classroom_id(&self) -> Option<Uuid> {
+        None
+    }
 }
@@ -85,6 +85,17 @@ impl Pipeline {
 
         let result = <T as StageHandle>::handle(&stage, &ctx, &event_id).await;
 
+        let history = crate::outbox::db::sqlx::HistoryUpsertQuery::new(
+            &event_id,
+            record.stage(),
+            match &result {
+                Ok(Some(_)) => crate::outbox::db::sqlx::HISTORY_STATUS_IN_PROGRESS,
+                Ok(None) => crate::outbox::db::sqlx::HISTORY_STATUS_DONE,
+                Err(_) => crate::outbox::db::sqlx::HISTORY_STATUS_FAILED,
+            },
+        )
+        .classroom_id(stage.classroom_id());
+
         match result {
             Ok(Some(next_stage)) => {
                 let record = crate::outbox::db::sqlx::DeleteQuery::new(&event_id)
@@ -95,17 +106,25 @@ impl Pipeline {
                 let json =
                     serde_json::to_value(&next_stage).error(ErrorKind::SerializationFailed)?;
 
-                let event_id = crate::outbox::db::sqlx::InsertQuery::new(
+                // The next stage keeps the event id so the whole chain could be
+                // looked up and replayed by it.
+                let next_event_id = crate::outbox::db::sqlx::InsertQuery::new(
                     record.entity_type(),
                     json,
                     record.delivery_deadline_at(),
                     record.operation(),
                 )
+                .sequence_id(event_id.sequence_id())
                 .execute(conn)
                 .await
                 .error(ErrorKind::InsertStageFailed)?;
 
-                Ok(Some(event_id))
+                history
+                    .execute(conn)
+                    .await
+                    .error(ErrorKind::RecordHistoryFailed)?;
+
+                Ok(Some(next_event_id))
             }
             Ok(None) => {
                 crate::outbox::db::sqlx::DeleteQuery::new(&event_id)
@@ -113,6 +132,11 @@ impl Pipeline {
                     .await
                     .error(ErrorKind::DeleteStageFailed)?;
 
+                history
+                    .execute(conn)
+                    .await
+                    .error(ErrorKind::RecordHistoryFailed)?;
+
                 Ok(None)
             }
             Err(error) => {
@@ -132,6 +156,20 @@ impl Pipeline {
                 .await
                 .error(ErrorKind::UpdateStageFailed)?;
 
+                // The transaction is going to be rolled back because of the error
+                // so the failure is recorded separately.
+                let mut history_conn = self
+                    .db
+                    .acquire()
+                    .await
+                    .error(ErrorKind::DbConnAcquisitionFailed)?;
+
+                history
+                    .error_kind(error.kind())
+                    .execute(&mut history_conn)
+                    .await
+                    .error(ErrorKind::RecordHistoryFailed)?;
+
                 Err(error.into())
             }
         }
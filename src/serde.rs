@@ -160,9 +160,9 @@ pub mod ts_seconds_bound_tuple {
 pub mod ts_seconds_bound_tuple_pg {
     use serde::{de, ser};
 
-    use crate::db::room::{Time, TimePg};
+    use crate::db::room::TimePg;
 
-    use super::ts_seconds_bound_tuple::TupleSecondsTimestampVisitor;
+    use super::{ts_seconds_bound_tuple::TupleSecondsTimestampVisitor, Time};
 
     pub fn serialize<S>(value: &TimePg, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -19,8 +19,11 @@ use tokio::sync::mpsc::UnboundedSender;
 
 use crate::{
     app::{
+        active_speaker::ActiveSpeakers,
+        audio_level::AudioLevels,
         context::{Context, GlobalContext, MessageContext},
         metrics::Metrics,
+        room_authz::RoomAuthzCache,
     },
     backend::janus::{client::IncomingEvent, client_pool::Clients},
     client::{
@@ -132,7 +135,12 @@ impl NatsClient for TestNatsClient {
 struct TestMqttClient;
 
 impl MqttClient for TestMqttClient {
-    fn publish(&mut self, _label: &'static str, _path: &str) -> Result<(), svc_agent::Error> {
+    fn publish_payload(
+        &mut self,
+        _label: &'static str,
+        _path: &str,
+        _payload: serde_json::Value,
+    ) -> Result<(), svc_agent::Error> {
         Ok(())
     }
 }
@@ -149,6 +157,10 @@ pub struct TestContext {
     conference_client: ConferenceHttpClient,
     mqtt_client: Arc<Mutex<dyn MqttClient>>,
     nats_client: Option<Arc<dyn NatsClient>>,
+    metrics: Arc<Metrics>,
+    active_speakers: ActiveSpeakers,
+    audio_levels: AudioLevels,
+    room_authz: RoomAuthzCache,
 }
 
 const WAITLIST_DURATION: std::time::Duration = std::time::Duration::from_secs(10);
@@ -178,6 +190,10 @@ impl TestContext {
             conference_client: ConferenceHttpClient::new("test".to_owned()),
             mqtt_client: Arc::new(Mutex::new(TestMqttClient)),
             nats_client: Some(Arc::new(TestNatsClient {}) as Arc<dyn NatsClient>),
+            metrics: Arc::new(Metrics::new(&Registry::new()).unwrap()),
+            active_speakers: ActiveSpeakers::default(),
+            audio_levels: AudioLevels::default(),
+            room_authz: RoomAuthzCache::default(),
         }
     }
 
@@ -237,8 +253,7 @@ impl GlobalContext for TestContext {
     }
 
     fn metrics(&self) -> Arc<Metrics> {
-        let registry = Registry::new();
-        Arc::new(Metrics::new(&registry).unwrap())
+        self.metrics.clone()
     }
 
     fn mqtt_gateway_client(&self) -> &MqttGatewayHttpClient {
@@ -256,6 +271,18 @@ impl GlobalContext for TestContext {
     fn nats_client(&self) -> Option<&dyn NatsClient> {
         self.nats_client.as_deref()
     }
+
+    fn active_speakers(&self) -> &ActiveSpeakers {
+        &self.active_speakers
+    }
+
+    fn audio_levels(&self) -> &AudioLevels {
+        &self.audio_levels
+    }
+
+    fn room_authz(&self) -> &RoomAuthzCache {
+        &self.room_authz
+    }
 }
 
 impl MessageContext for TestContext {
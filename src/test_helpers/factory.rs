@@ -16,11 +16,19 @@ use super::{
 
 pub struct Room<'a> {
     audience: Option<String>,
-    time: Option<db::room::Time>,
+    time: Option<db::room::RoomTime>,
     rtc_sharing_policy: db::rtc::SharingPolicy,
     backend_id: Option<&'a AgentId>,
     reserve: Option<i32>,
     infinite: bool,
+    audio_processing: Option<db::room::AudioProcessing>,
+    archive_messages: bool,
+    composite_recording: bool,
+    stream_max_duration: Option<i32>,
+    lobby: bool,
+    active_speaker_events: bool,
+    max_agents: Option<i32>,
+    classroom_id: Option<uuid::Uuid>,
 }
 
 impl<'a> Room<'a> {
@@ -32,6 +40,14 @@ impl<'a> Room<'a> {
             backend_id: None,
             reserve: None,
             infinite: false,
+            audio_processing: None,
+            archive_messages: false,
+            composite_recording: false,
+            stream_max_duration: None,
+            lobby: false,
+            active_speaker_events: false,
+            max_agents: None,
+            classroom_id: None,
         }
     }
 
@@ -42,7 +58,9 @@ impl<'a> Room<'a> {
         }
     }
 
-    pub fn time(self, time: db::room::Time) -> Self {
+    pub fn time(self, (start, end): crate::serde::Time) -> Self {
+        let time = db::room::RoomTime::new(start, end).expect("Invalid room time");
+
         Self {
             time: Some(time),
             ..self
@@ -77,6 +95,59 @@ impl<'a> Room<'a> {
         }
     }
 
+    pub fn audio_processing(self, audio_processing: db::room::AudioProcessing) -> Self {
+        Self {
+            audio_processing: Some(audio_processing),
+            ..self
+        }
+    }
+
+    pub fn archive_messages(self, archive_messages: bool) -> Self {
+        Self {
+            archive_messages,
+            ..self
+        }
+    }
+
+    pub fn composite_recording(self, composite_recording: bool) -> Self {
+        Self {
+            composite_recording,
+            ..self
+        }
+    }
+
+    pub fn stream_max_duration(self, stream_max_duration: i32) -> Self {
+        Self {
+            stream_max_duration: Some(stream_max_duration),
+            ..self
+        }
+    }
+
+    pub fn lobby(self, lobby: bool) -> Self {
+        Self { lobby, ..self }
+    }
+
+    pub fn active_speaker_events(self, active_speaker_events: bool) -> Self {
+        Self {
+            active_speaker_events,
+            ..self
+        }
+    }
+
+    pub fn max_agents(self, max_agents: i32) -> Self {
+        Self {
+            max_agents: Some(max_agents),
+            ..self
+        }
+    }
+
+    pub fn classroom_id(self, classroom_id: uuid::Uuid) -> Self {
+        Self {
+            classroom_id: Some(classroom_id),
+            ..self
+        }
+    }
+
     pub async fn insert(self, conn: &mut sqlx::PgConnection) -> db::room::Object {
         let audience = self.audience.expect("Audience not set");
         let time = self.time.expect("Time not set");
@@ -85,7 +156,7 @@ impl<'a> Room<'a> {
             time,
             &audience,
             self.rtc_sharing_policy,
-            uuid::Uuid::new_v4(),
+            self.classroom_id.unwrap_or_else(uuid::Uuid::new_v4),
         );
 
         if let Some(backend_id) = self.backend_id {
@@ -100,6 +171,34 @@ impl<'a> Room<'a> {
             q = q.infinite(true);
         }
 
+        if let Some(audio_processing) = self.audio_processing {
+            q = q.audio_processing(audio_processing);
+        }
+
+        if self.archive_messages {
+            q = q.archive_messages(true);
+        }
+
+        if self.composite_recording {
+            q = q.composite_recording(true);
+        }
+
+        if let Some(stream_max_duration) = self.stream_max_duration {
+            q = q.stream_max_duration(stream_max_duration);
+        }
+
+        if self.lobby {
+            q = q.lobby(true);
+        }
+
+        if self.active_speaker_events {
+            q = q.active_speaker_events(true);
+        }
+
+        if let Some(max_agents) = self.max_agents {
+            q = q.max_agents(max_agents);
+        }
+
         q.execute(conn).await.expect("Failed to insert room")
     }
 }
@@ -220,6 +319,7 @@ impl AgentConnection {
 pub struct Rtc {
     room_id: db::room::Id,
     created_by: AgentId,
+    kind: db::rtc::Kind,
 }
 
 impl Rtc {
@@ -227,6 +327,7 @@ impl Rtc {
         Self {
             room_id,
             created_by: AgentId::new("web", AccountId::new("nevermind", "example.com")),
+            kind: db::rtc::Kind::Camera,
         }
     }
 
@@ -234,8 +335,13 @@ impl Rtc {
         Self { created_by, ..self }
     }
 
+    pub fn kind(self, kind: db::rtc::Kind) -> Self {
+        Self { kind, ..self }
+    }
+
     pub async fn insert(&self, conn: &mut sqlx::PgConnection) -> db::rtc::Object {
         db::rtc::InsertQuery::new(self.room_id, &self.created_by)
+            .kind(self.kind)
             .execute(conn)
             .await
             .expect("Failed to insert janus_backend")
@@ -251,6 +357,7 @@ pub struct JanusBackend {
     capacity: Option<i32>,
     balancer_capacity: Option<i32>,
     group: Option<String>,
+    capabilities: Option<Vec<String>>,
     janus_url: String,
 }
 
@@ -263,6 +370,7 @@ impl JanusBackend {
             capacity: None,
             balancer_capacity: None,
             group: None,
+            capabilities: None,
             janus_url,
         }
     }
@@ -288,6 +396,13 @@ impl JanusBackend {
         }
     }
 
+    pub fn capabilities(self, capabilities: &[&str]) -> Self {
+        Self {
+            capabilities: Some(capabilities.iter().map(|c| c.to_string()).collect()),
+            ..self
+        }
+    }
+
     pub async fn insert(&self, conn: &mut sqlx::PgConnection) -> db::janus_backend::Object {
         let mut q = db::janus_backend::UpsertQuery::new(
             &self.id,
@@ -308,6 +423,10 @@ impl JanusBackend {
             q = q.group(group);
         }
 
+        if let Some(ref capabilities) = self.capabilities {
+            q = q.capabilities(capabilities);
+        }
+
         q.execute(conn)
             .await
             .expect("Failed to insert janus_backend")
@@ -333,6 +452,20 @@ impl<'a> JanusRtcStream<'a> {
         }
     }
 
+    pub fn backend(self, backend: &'a db::janus_backend::Object) -> Self {
+        Self {
+            backend: Some(backend),
+            ..self
+        }
+    }
+
+    pub fn rtc(self, rtc: &'a db::rtc::Object) -> Self {
+        Self {
+            rtc: Some(rtc),
+            ..self
+        }
+    }
+
     pub async fn insert(&self, conn: &mut sqlx::PgConnection) -> db::janus_rtc_stream::Object {
         let default_backend;
 
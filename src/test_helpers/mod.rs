@@ -4,11 +4,12 @@ use serde::de::DeserializeOwned;
 use serde_json::json;
 use svc_agent::{
     mqtt::{IncomingEventProperties, IncomingRequestProperties, IncomingResponseProperties},
-    AgentId,
+    AgentId, Authenticable,
 };
 use uuid::Uuid;
 
 use crate::app::{
+    context::GlobalContext,
     endpoint::{EventHandler, RequestHandler, ResponseHandler},
     error::Error as AppError,
     message_handler::MessageStream,
@@ -37,6 +38,14 @@ pub async fn handle_request<H: RequestHandler>(
 ) -> Result<Vec<OutgoingEnvelope>, AppError> {
     let reqp = build_reqp(agent.agent_id(), "ignore");
     let messages = H::handle(context, payload, RequestParams::MqttParams(&reqp)).await?;
+
+    let pseudonyms = context.pseudonyms();
+    let hide_from_reader = pseudonyms.hides_from(
+        agent.account_id(),
+        context.agent_id().as_account_id().audience(),
+    );
+
+    let messages = messages.pseudonymize(&pseudonyms, hide_from_reader);
     Ok(parse_messages(messages.into_mqtt_messages(&reqp)?).await)
 }
 
@@ -61,7 +70,7 @@ pub async fn handle_event<H: EventHandler>(
     Ok(parse_messages(messages).await)
 }
 
-async fn parse_messages(mut messages: MessageStream) -> Vec<OutgoingEnvelope> {
+pub async fn parse_messages(mut messages: MessageStream) -> Vec<OutgoingEnvelope> {
     let mut parsed_messages = vec![];
 
     while let Some(message) = messages.next().await {